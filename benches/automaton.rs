@@ -0,0 +1,55 @@
+//! Times matching queries over a generated C file, with and without query
+//! sets, which `cargo bench --bench automaton` runs
+//!
+//! Each query is matched several times, and the fastest run is printed, as
+//! the others are slowed only by whatever else the machine was doing.
+
+use std::time::{Duration, Instant};
+
+use spidior::languages::{cache::Parsed, clike::Clike};
+use spidior::{matcher, regexparser};
+
+/// How many times each query is matched
+const RUNS: usize = 5;
+
+/// A C file of `n` functions, each declaring and using a few variables
+fn source(n: usize) -> String {
+    let mut s = String::new();
+    for i in 0..n {
+        s += &format!(
+            "int f{i}(int count, char *name) {{\n    int total = 0;\n    for (int j = 0; j < count; j++) {{\n        total += name[j] * {i};\n    }}\n    return total + count;\n}}\n\n",
+            i = i
+        );
+    }
+    s
+}
+
+fn time(query: &str, input: &str, parsed: &Parsed) -> (Duration, usize) {
+    let find = regexparser::parse(query).unwrap().find;
+    let mut best = Duration::MAX;
+    let mut found = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        found = matcher::find_parsed(input, find.clone(), parsed, false).len();
+        best = best.min(start.elapsed());
+    }
+    (best, found)
+}
+
+fn main() {
+    let input = source(500);
+    let parsed = Parsed::new(&input, &Clike {});
+    println!("{} bytes, {} identifiers", input.len(), parsed.identifiers.len());
+    for query in [
+        "%s/total|count//g",
+        "%s/[a-zA-Z_][a-zA-Z0-9_]*//g",
+        "%s/[[name=count]]//g",
+        "%s/[[type=int]] *= *[0-9]+//g",
+        "%s/([[name=total]]|[[name=j]]) *\\+=//g",
+        "%s/[[name=name]]\\[[[name=j]]\\]//g",
+        "%s/e.//gc",
+    ] {
+        let (best, found) = time(query, &input, &parsed);
+        println!("{:<45} {:>6} matches {:>10.2?}", query, found, best);
+    }
+}
@@ -0,0 +1,48 @@
+/*
+ * The C interface to spidior, built by `cargo build -p spidior-ffi` as
+ * libspidior_ffi.so (or .dylib, .dll) and libspidior_ffi.a.
+ *
+ * A query is compiled once with spidior_compile, then run over as many
+ * buffers as needed. Buffers are UTF-8 and need not be nul-terminated, and
+ * offsets into them are in bytes. Everything returned is owned by the
+ * caller and must be given back to the matching _free function. Functions
+ * that can fail take a `char **error`, which, if not NULL, is set to a
+ * message to free with spidior_string_free, or to NULL on success. A
+ * panic within spidior never unwinds into the caller: it fails the call as
+ * an error would, with a message starting "Internal error: ".
+ *
+ * A `lang` of NULL detects the language from a #! line or doctype in the
+ * buffer, and otherwise treats it as plain text.
+ */
+#ifndef SPIDIOR_H
+#define SPIDIOR_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct SpidiorPattern SpidiorPattern;
+typedef struct SpidiorMatches SpidiorMatches;
+
+/* Compiles a query, like "%s/[[type=Session]]/sess/g", or returns NULL */
+SpidiorPattern *spidior_compile(const char *query, char **error);
+void spidior_pattern_free(SpidiorPattern *pattern);
+
+/* Finds what the find part of a pattern matches, or returns NULL */
+SpidiorMatches *spidior_find(const SpidiorPattern *pattern, const char *buffer, size_t len, const char *lang, char **error);
+size_t spidior_matches_len(const SpidiorMatches *matches);
+/* Sets start and len to those of the match at index and returns 1, or returns 0 */
+int spidior_matches_get(const SpidiorMatches *matches, size_t index, size_t *start, size_t *len);
+void spidior_matches_free(SpidiorMatches *matches);
+
+/* Performs every replacement a pattern makes, or returns NULL */
+char *spidior_replace(const SpidiorPattern *pattern, const char *buffer, size_t len, const char *lang, size_t *out_len, char **error);
+void spidior_string_free(char *text);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,328 @@
+//! A C ABI over spidior, so that editors and tools written in C or C++ can
+//! embed the engine rather than running the binary. `spidior.h`, beside this
+//! crate's manifest, declares everything here.
+//!
+//! A query is compiled once with `spidior_compile`, then run over as many
+//! buffers as needed with `spidior_find` or `spidior_replace`. Everything
+//! these return is owned by the caller, and must be given back to the
+//! matching `_free` function. Functions that can fail take a `char **error`,
+//! which, if not null, is set to a message the caller frees with
+//! `spidior_string_free`, or to null on success. A panic within spidior
+//! never unwinds into the caller: it fails the call as an error would, with
+//! a message starting `Internal error: `.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use spidior::languages::{cache::Parsed, registry::Registry};
+use spidior::regexparser::{self, ast::Replace};
+use spidior::error::catch_panic;
+use spidior::{matcher, replacer, SpidiorError};
+
+/// A compiled query
+pub struct SpidiorPattern {
+    replace: Replace,
+}
+
+/// The matches of a query within a buffer, as byte offsets into it
+pub struct SpidiorMatches {
+    matches: Vec<(usize, usize)>,
+}
+
+/// Hands a string to C, replacing any nul bytes, which C cannot hold
+///
+/// # Returns
+///
+/// The string, and its length in bytes once each nul is replaced by the
+/// three bytes of U+FFFD
+fn to_c(text: &str) -> (*mut c_char, usize) {
+    let text = text.replace('\0', "\u{fffd}");
+    let len = text.len();
+    (CString::new(text).map_or(ptr::null_mut(), CString::into_raw), len)
+}
+
+/// Stores `message` in `error`, if it is not null
+///
+/// # Safety
+///
+/// `error` must be null or valid to write a pointer to
+unsafe fn set_error(error: *mut *mut c_char, message: Option<&str>) {
+    if !error.is_null() {
+        *error = message.map_or(ptr::null_mut(), |x| to_c(x).0);
+    }
+}
+
+/// Reads a nul-terminated UTF-8 string from C
+///
+/// # Safety
+///
+/// `text` must be null or point to a nul-terminated string
+unsafe fn from_c<'a>(text: *const c_char, what: &str) -> Result<&'a str, SpidiorError> {
+    if text.is_null() {
+        return Err(SpidiorError::parse(format!("The {} is null", what)));
+    }
+    CStr::from_ptr(text).to_str().map_err(|_| SpidiorError::parse(format!("The {} is not UTF-8", what)))
+}
+
+/// Reads a buffer of code from C
+///
+/// # Safety
+///
+/// `buffer` must point to `len` readable bytes, or be null if `len` is 0
+unsafe fn buffer<'a>(buffer: *const c_char, len: usize) -> Result<&'a str, SpidiorError> {
+    if len == 0 {
+        return Ok("");
+    }
+    if buffer.is_null() {
+        return Err(SpidiorError::parse("The buffer is null"));
+    }
+    std::str::from_utf8(slice::from_raw_parts(buffer as *const u8, len)).map_err(|_| SpidiorError::parse("The buffer is not UTF-8"))
+}
+
+/// Parses a buffer as a language, given its name, or as the language its
+/// contents name if `lang` is null
+///
+/// # Safety
+///
+/// `lang` must be null or point to a nul-terminated string
+unsafe fn parse(code: &str, lang: *const c_char) -> Result<Parsed, SpidiorError> {
+    let registry = Registry::builtin();
+    let lang = if lang.is_null() {
+        registry.detect(std::path::Path::new(""), code)
+    } else {
+        let name = from_c(lang, "language")?;
+        registry.by_name(name).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}`", name)))?
+    };
+    Ok(Parsed::new(code, lang))
+}
+
+/// Stores the outcome of `f` in `error`, and returns its value, or `failed`
+/// if it failed or panicked
+///
+/// # Safety
+///
+/// `error` must be null or valid to write a pointer to
+unsafe fn report<T>(error: *mut *mut c_char, failed: T, f: impl FnOnce() -> Result<T, SpidiorError>) -> T {
+    match catch_panic(f) {
+        Ok(Ok(x)) => {
+            set_error(error, None);
+            x
+        }
+        Ok(Err(e)) => {
+            set_error(error, Some(&e.to_string()));
+            failed
+        }
+        Err(e) => {
+            set_error(error, Some(&format!("Internal error: {}", e)));
+            failed
+        }
+    }
+}
+
+/// Returns what `f` returns, or `failed` if it panicked, for functions that
+/// cannot report an error
+fn guard<T>(failed: T, f: impl FnOnce() -> T) -> T {
+    catch_panic(f).unwrap_or(failed)
+}
+
+/// Compiles a query, like `%s/[[type=Session]]/sess/g`
+///
+/// # Safety
+///
+/// `query` must point to a nul-terminated string, and `error` must be null
+/// or valid to write a pointer to
+///
+/// # Returns
+///
+/// The pattern, to be freed with `spidior_pattern_free`, or null if the
+/// query is not valid
+#[no_mangle]
+pub unsafe extern "C" fn spidior_compile(query: *const c_char, error: *mut *mut c_char) -> *mut SpidiorPattern {
+    report(error, ptr::null_mut(), || {
+        let replace = regexparser::parse(from_c(query, "query")?)?;
+        Ok(Box::into_raw(Box::new(SpidiorPattern { replace })))
+    })
+}
+
+/// Frees a pattern returned by `spidior_compile`
+///
+/// # Safety
+///
+/// `pattern` must be null or returned by `spidior_compile`, and not already
+/// freed
+#[no_mangle]
+pub unsafe extern "C" fn spidior_pattern_free(pattern: *mut SpidiorPattern) {
+    guard((), || {
+        if !pattern.is_null() {
+            drop(Box::from_raw(pattern));
+        }
+    })
+}
+
+/// Finds the text the find part of a pattern matches within a buffer
+///
+/// # Safety
+///
+/// `pattern` must be returned by `spidior_compile`, `buffer` must point to
+/// `len` readable bytes of UTF-8, `lang` must be null or point to a
+/// nul-terminated language name, like `java`, and `error` must be null or
+/// valid to write a pointer to
+///
+/// # Returns
+///
+/// The matches, to be freed with `spidior_matches_free`, or null if the
+/// buffer or language is not valid
+#[no_mangle]
+pub unsafe extern "C" fn spidior_find(pattern: *const SpidiorPattern, buffer: *const c_char, len: usize, lang: *const c_char, error: *mut *mut c_char) -> *mut SpidiorMatches {
+    report(error, ptr::null_mut(), || {
+        let pattern = pattern.as_ref().ok_or_else(|| SpidiorError::parse("The pattern is null"))?;
+        let code = self::buffer(buffer, len)?;
+        let parsed = parse(code, lang)?;
+        let matches = matcher::find_parsed(code, pattern.replace.find.clone(), &parsed, false).iter().map(|x| (x.start(), x.len())).collect();
+        Ok(Box::into_raw(Box::new(SpidiorMatches { matches })))
+    })
+}
+
+/// The number of matches in a set returned by `spidior_find`
+///
+/// # Safety
+///
+/// `matches` must be null or returned by `spidior_find`
+#[no_mangle]
+pub unsafe extern "C" fn spidior_matches_len(matches: *const SpidiorMatches) -> usize {
+    guard(0, || matches.as_ref().map_or(0, |x| x.matches.len()))
+}
+
+/// Reads a match from a set returned by `spidior_find`, in the order they
+/// appear in the buffer
+///
+/// # Safety
+///
+/// `matches` must be null or returned by `spidior_find`, and `start` and
+/// `len` must each be null or valid to write a `size_t` to
+///
+/// # Returns
+///
+/// 1, having set `start` and `len` to the byte offset and length of the
+/// match, or 0 if there is no match at `index`
+#[no_mangle]
+pub unsafe extern "C" fn spidior_matches_get(matches: *const SpidiorMatches, index: usize, start: *mut usize, len: *mut usize) -> i32 {
+    guard(0, || match matches.as_ref().and_then(|x| x.matches.get(index)) {
+        Some(&(s, l)) => {
+            if !start.is_null() {
+                *start = s;
+            }
+            if !len.is_null() {
+                *len = l;
+            }
+            1
+        }
+        None => 0,
+    })
+}
+
+/// Frees a set of matches returned by `spidior_find`
+///
+/// # Safety
+///
+/// `matches` must be null or returned by `spidior_find`, and not already
+/// freed
+#[no_mangle]
+pub unsafe extern "C" fn spidior_matches_free(matches: *mut SpidiorMatches) {
+    guard((), || {
+        if !matches.is_null() {
+            drop(Box::from_raw(matches));
+        }
+    })
+}
+
+/// Performs every replacement a pattern makes within a buffer
+///
+/// # Safety
+///
+/// `pattern` must be returned by `spidior_compile`, `buffer` must point to
+/// `len` readable bytes of UTF-8, `lang` must be null or point to a
+/// nul-terminated language name, like `java`, and `out_len` and `error`
+/// must each be null or valid to write to
+///
+/// # Returns
+///
+/// The changed buffer, nul-terminated, with its length without the nul
+/// stored in `out_len`, to be freed with `spidior_string_free`, or null if
+/// the buffer or language is not valid
+#[no_mangle]
+pub unsafe extern "C" fn spidior_replace(pattern: *const SpidiorPattern, buffer: *const c_char, len: usize, lang: *const c_char, out_len: *mut usize, error: *mut *mut c_char) -> *mut c_char {
+    report(error, ptr::null_mut(), || {
+        let pattern = pattern.as_ref().ok_or_else(|| SpidiorError::parse("The pattern is null"))?;
+        let code = self::buffer(buffer, len)?;
+        let parsed = parse(code, lang)?;
+        let out = replacer::edits_parsed(code, pattern.replace.clone(), |_, _| true, &parsed, false, false, false)?.apply(code)?;
+        let (out, len) = to_c(&out);
+        if !out_len.is_null() {
+            *out_len = len;
+        }
+        Ok(out)
+    })
+}
+
+/// Frees a string returned by `spidior_replace`, or an error message
+///
+/// # Safety
+///
+/// `text` must be null or returned by this library, and not already freed
+#[no_mangle]
+pub unsafe extern "C" fn spidior_string_free(text: *mut c_char) {
+    guard((), || {
+        if !text.is_null() {
+            drop(CString::from_raw(text));
+        }
+    })
+}
+
+#[test]
+fn test_ffi() {
+    unsafe {
+        let mut error = ptr::null_mut();
+        let pattern = spidior_compile(b"%s/[[type=Session]]/sess/g\0".as_ptr() as *const c_char, &mut error);
+        assert!(!pattern.is_null() && error.is_null());
+        let code = "class A { Session s; void go() { s.run(); } }";
+        let matches = spidior_find(pattern, code.as_ptr() as *const c_char, code.len(), b"java\0".as_ptr() as *const c_char, &mut error);
+        assert_eq!(spidior_matches_len(matches), 2);
+        let (mut start, mut len) = (0, 0);
+        assert_eq!(spidior_matches_get(matches, 1, &mut start, &mut len), 1);
+        assert_eq!(&code[start..start + len], "s");
+        assert_eq!(spidior_matches_get(matches, 2, &mut start, &mut len), 0);
+        spidior_matches_free(matches);
+        let mut out_len = 0;
+        let out = spidior_replace(pattern, code.as_ptr() as *const c_char, code.len(), b"java\0".as_ptr() as *const c_char, &mut out_len, &mut error);
+        assert_eq!(CStr::from_ptr(out).to_str().unwrap(), "class A { Session sess; void go() { sess.run(); } }");
+        assert_eq!(out_len, code.len() + 6);
+        spidior_string_free(out);
+        let nul = "Session\0";
+        let out = spidior_replace(pattern, nul.as_ptr() as *const c_char, nul.len(), b"java\0".as_ptr() as *const c_char, &mut out_len, &mut error);
+        assert_eq!(out_len, CStr::from_ptr(out).to_bytes().len());
+        spidior_string_free(out);
+        let none = spidior_find(pattern, code.as_ptr() as *const c_char, code.len(), b"cobol\0".as_ptr() as *const c_char, &mut error);
+        assert!(none.is_null());
+        assert_eq!(CStr::from_ptr(error).to_str().unwrap(), "Unknown language `cobol`");
+        spidior_string_free(error);
+        spidior_pattern_free(pattern);
+        assert!(spidior_compile(b"%s/a/b\0".as_ptr() as *const c_char, &mut error).is_null());
+        spidior_string_free(error);
+    }
+}
+
+#[test]
+fn test_panic() {
+    unsafe {
+        let mut error = ptr::null_mut();
+        assert_eq!(report(&mut error, -1, || -> Result<i32, SpidiorError> { panic!("bad buffer") }), -1);
+        assert_eq!(CStr::from_ptr(error).to_str().unwrap(), "Internal error: bad buffer");
+        spidior_string_free(error);
+        assert_eq!(report(&mut error, -1, || Ok(1)), 1);
+        assert!(error.is_null());
+    }
+    assert_eq!(guard(0, || -> usize { panic!("bad matches") }), 0);
+}
@@ -0,0 +1,248 @@
+//! Python bindings for spidior, built with PyO3 as the `spidior` module, so
+//! that scripts can run semantic rewrites without running the binary.
+//! `maturin build` in this directory builds a wheel of it.
+//!
+//! Offsets given to Python count characters, as Python strings do, rather
+//! than the bytes spidior counts internally.
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use spidior::languages::parsing::{Function as RustFunction, Identifier as RustIdentifier, Import as RustImport};
+use spidior::languages::{cache::Parsed, registry::Registry};
+use spidior::regexparser::{self, ast::Replace};
+use spidior::{matcher, replacer, SpidiorError};
+
+create_exception!(spidior, Error, PyException, "Raised when a query, language, or edit is not valid");
+
+/// Turns an error into a Python `spidior.Error` holding its message
+fn to_py(e: SpidiorError) -> PyErr {
+    Error::new_err(e.to_string())
+}
+
+/// Counts characters rather than bytes, for handing offsets into `text` to
+/// Python
+struct Offsets<'a> {
+    text: &'a str,
+}
+
+impl<'a> Offsets<'a> {
+    fn of(&self, byte: usize) -> usize {
+        self.text[..byte].chars().count()
+    }
+}
+
+/// Parses a piece of code as a language, given its name, or as the language
+/// its contents name if no name is given
+fn parse_code(code: &str, lang: Option<&str>) -> Result<Parsed, SpidiorError> {
+    let registry = Registry::builtin();
+    let lang = match lang {
+        Some(name) => registry.by_name(name).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}`", name)))?,
+        None => registry.detect(std::path::Path::new(""), code),
+    };
+    Ok(Parsed::new(code, lang))
+}
+
+/// A parsed query, which can be run over many pieces of code without being
+/// parsed again
+#[pyclass(module = "spidior")]
+struct Query {
+    replace: Replace,
+    /// The query as it was written
+    #[pyo3(get)]
+    text: String,
+}
+
+#[pymethods]
+impl Query {
+    /// Whether the query replaces every match, rather than only the first
+    #[getter]
+    fn is_global(&self) -> bool {
+        self.replace.global
+    }
+
+    /// Finds the text the find part of the query matches, as a list of
+    /// (start, end) tuples
+    #[pyo3(signature = (code, lang = None))]
+    fn find(&self, code: &str, lang: Option<&str>) -> PyResult<Vec<(usize, usize)>> {
+        let parsed = parse_code(code, lang).map_err(to_py)?;
+        let offsets = Offsets { text: code };
+        Ok(matcher::find_parsed(code, self.replace.find.clone(), &parsed, false)
+            .iter()
+            .map(|x| (offsets.of(x.start()), offsets.of(x.start() + x.len())))
+            .collect())
+    }
+
+    /// Performs every replacement the query makes, returning the changed
+    /// code
+    #[pyo3(signature = (code, lang = None))]
+    fn replace(&self, code: &str, lang: Option<&str>) -> PyResult<String> {
+        let parsed = parse_code(code, lang).map_err(to_py)?;
+        replacer::edits_parsed(code, self.replace.clone(), |_, _| true, &parsed, false, false, false)
+            .and_then(|x| x.apply(code))
+            .map_err(to_py)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("spidior.Query({:?})", self.text)
+    }
+}
+
+/// A function found by a language parser
+#[pyclass(module = "spidior", get_all)]
+struct Function {
+    name: String,
+    /// The return type, which is empty when it is not written down
+    typ: String,
+    /// The parameters, as (name, type) tuples
+    params: Vec<(String, String)>,
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Function {
+    fn new(x: &RustFunction, offsets: &Offsets) -> Self {
+        Self {
+            name: x.name.clone(),
+            typ: x.typ.clone(),
+            params: x.params.iter().map(|p| (p.name.clone(), p.typ.clone())).collect(),
+            start: offsets.of(x.start),
+            end: offsets.of(x.end),
+            line: x.line,
+            column: x.column,
+        }
+    }
+}
+
+/// An identifier found by a language parser
+#[pyclass(module = "spidior", get_all)]
+struct Identifier {
+    name: String,
+    typ: String,
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+    /// The ID of the innermost scope the identifier is within
+    scope_id: usize,
+}
+
+impl Identifier {
+    fn new(x: &RustIdentifier, offsets: &Offsets) -> Self {
+        Self {
+            name: x.name.clone(),
+            typ: x.typ.clone(),
+            start: offsets.of(x.start),
+            end: offsets.of(x.end),
+            line: x.line,
+            column: x.column,
+            scope_id: x.scope_id,
+        }
+    }
+}
+
+/// An import found by a language parser
+#[pyclass(module = "spidior", get_all)]
+struct Import {
+    path: String,
+    alias: Option<String>,
+    start: usize,
+    end: usize,
+}
+
+impl Import {
+    fn new(x: &RustImport, offsets: &Offsets) -> Self {
+        Self {
+            path: x.path.clone(),
+            alias: x.alias.clone(),
+            start: offsets.of(x.start),
+            end: offsets.of(x.end),
+        }
+    }
+}
+
+/// What a language parser found in a piece of code
+#[pyclass(module = "spidior", get_all)]
+struct Code {
+    functions: Vec<Py<Function>>,
+    identifiers: Vec<Py<Identifier>>,
+    aliases: Vec<Py<Identifier>>,
+    imports: Vec<Py<Import>>,
+}
+
+/// Parses a query, like `%s/[[type=Session]]/sess/g`, raising
+/// `spidior.Error` if it is not valid
+#[pyfunction]
+fn parse_query(query: &str) -> PyResult<Query> {
+    Ok(Query {
+        replace: regexparser::parse(query).map_err(to_py)?,
+        text: query.to_string(),
+    })
+}
+
+/// Finds the text the find part of a query matches within a piece of code,
+/// as a list of (start, end) tuples
+#[pyfunction]
+#[pyo3(signature = (code, query, lang = None))]
+fn find(code: &str, query: &str, lang: Option<&str>) -> PyResult<Vec<(usize, usize)>> {
+    parse_query(query)?.find(code, lang)
+}
+
+/// Performs every replacement a query makes within a piece of code,
+/// returning the changed code
+#[pyfunction]
+#[pyo3(signature = (code, query, lang = None))]
+fn replace(code: &str, query: &str, lang: Option<&str>) -> PyResult<String> {
+    parse_query(query)?.replace(code, lang)
+}
+
+/// Runs a language parser over a piece of code, as the language named by
+/// `lang`, or the one the code names in a `#!` line or doctype if None
+#[pyfunction]
+#[pyo3(signature = (code, lang = None))]
+fn parse(py: Python<'_>, code: &str, lang: Option<&str>) -> PyResult<Code> {
+    let parsed = parse_code(code, lang).map_err(to_py)?;
+    let offsets = Offsets { text: code };
+    let identifiers = |xs: &[RustIdentifier]| xs.iter().map(|x| Py::new(py, Identifier::new(x, &offsets))).collect::<PyResult<Vec<_>>>();
+    Ok(Code {
+        functions: parsed.functions.iter().map(|x| Py::new(py, Function::new(x, &offsets))).collect::<PyResult<_>>()?,
+        identifiers: identifiers(&parsed.identifiers)?,
+        aliases: identifiers(&parsed.aliases)?,
+        imports: parsed.imports.iter().map(|x| Py::new(py, Import::new(x, &offsets))).collect::<PyResult<_>>()?,
+    })
+}
+
+#[pymodule]
+#[pyo3(name = "spidior")]
+fn spidior_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("Error", m.py().get_type::<Error>())?;
+    m.add_class::<Query>()?;
+    m.add_class::<Code>()?;
+    m.add_class::<Function>()?;
+    m.add_class::<Identifier>()?;
+    m.add_class::<Import>()?;
+    m.add_function(wrap_pyfunction!(parse_query, m)?)?;
+    m.add_function(wrap_pyfunction!(find, m)?)?;
+    m.add_function(wrap_pyfunction!(replace, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    Ok(())
+}
+
+#[test]
+fn test_python() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let code = "String é = \"\"; int count = 0; count++;";
+        assert_eq!(find(code, "%s/[[name=count]]/total/g", Some("java")).unwrap(), vec![(19, 24), (30, 35)]);
+        let query = parse_query("%s/[[name=count]]/total/g").unwrap();
+        assert!(query.is_global());
+        assert_eq!(query.replace(code, Some("java")).unwrap(), "String é = \"\"; int total = 0; total++;");
+        assert!(parse_query("%s/a/b").err().unwrap().is_instance_of::<Error>(py));
+        let parsed = parse(py, "int go(int a) { return a; }", Some("c")).unwrap();
+        assert_eq!(parsed.functions[0].borrow(py).name, "go");
+        assert_eq!(parsed.functions[0].borrow(py).params, vec![("a".to_string(), "int".to_string())]);
+    });
+}
@@ -0,0 +1,35 @@
+package spells
+
+import (
+	"fmt"
+	str "strings"
+)
+
+type Lightning struct {
+	Charge int
+	label  string
+}
+
+var (
+	name string = "bolt"
+	hits       = 0
+)
+
+func NewLightning() *Lightning {
+	return &Lightning{Charge: 0}
+}
+
+// OnSpawn is called when { the spell spawns
+func (l *Lightning) OnSpawn(me *Session, x, y int) {
+	total := x + y
+	me.X = x
+	me.Y = y + total
+	fmt.Println(`{ "raw": me }`, str.ToUpper(name), l.label)
+	l.Charge = me.Power
+}
+
+func main() {
+	light := &Lightning{}
+	light.OnSpawn(nil, 1, 2)
+	fmt.Println(name)
+}
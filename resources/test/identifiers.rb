@@ -0,0 +1,30 @@
+require 'json'
+
+module Spells
+  class Lightning < Spell
+    def initialize(charge = 0)
+      @session = Game::Session.new
+      @charge = charge
+    end
+
+    # def commented(me) end
+    def on_spawn(me)
+      hidden = 1 if me.nil?
+      me.x = @charge
+      @session.log(<<~TEXT)
+        me = "not code" end
+      TEXT
+      [1, 2].each do |spark|
+        me.y = spark
+      end
+      @session
+    end
+
+    def charged? = @charge > 0
+  end
+end
+
+def build(charge)
+  bolt = Spells::Lightning.new(charge)
+  bolt
+end
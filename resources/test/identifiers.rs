@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+use crate::session::{Session, Spawn};
+
+pub struct Lightning {
+    charge: u32,
+}
+
+impl Lightning {
+    pub fn new() -> Self {
+        Self { charge: 0 }
+    }
+}
+
+impl<'a> Spawn<'a> for Lightning {
+    /* fn commented() { let brace = '{'; } */
+    fn spawn(&self, me: &mut Session<'a>) {
+        let charge: u32 = self.charge;
+        me.x = charge;
+        me.name = r#"fn not_a_function() { "#.to_string();
+        let mut count = HashMap::new().len();
+        count += 1;
+        // me.y = count;
+        me.y = '}' as usize;
+    }
+}
+
+fn main() {
+    let label = "}";
+}
@@ -0,0 +1,24 @@
+#!/bin/bash
+# session=ignored
+session="$HOME/game"
+
+function spawn {
+    local -i count=0
+    echo '$session is not expanded here'
+    for item in a b; do
+        count=$((count + 1))
+        echo "${session}/${item}"
+    done
+    cat <<'EOF'
+$session is literal here
+EOF
+    cat <<EOF
+session is $session
+EOF
+    echo "$count"
+}
+
+cleanup() {
+    read -r line < "$session"
+    echo "$line" >> "$session.log"
+}
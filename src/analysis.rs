@@ -0,0 +1,302 @@
+//! Provides lightweight analyses of what the language layer finds in code,
+//! for `spidior analyze`, which work on the identifiers and scopes of a
+//! file rather than on a full semantic model of its language
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::csv::Delimited;
+use crate::json::Value;
+use crate::languages::cache::Parsed;
+use crate::languages::parsing::{Function, Identifier};
+use crate::languages::tokens::{self, TokenKind};
+use crate::languages::Language;
+
+/// The words that declare the name after them, whatever its type
+const DECLARERS: [&str; 10] = ["let", "mut", "var", "val", "const", "auto", "local", "my", "our", "final"];
+
+/// The header row of `analyze shadowing` as CSV or TSV
+pub const SHADOW_COLUMNS: [&str; 6] = ["path", "line", "column", "name", "shadowed_line", "shadowed_column"];
+
+/// The header row of `analyze types` as CSV or TSV, where the rows of the
+/// total have an empty `path`
+pub const CENSUS_COLUMNS: [&str; 4] = ["path", "type", "identifiers", "declarations"];
+
+/// The header row of `analyze duplicates` as CSV or TSV, with a row for each
+/// function
+pub const DUPLICATE_COLUMNS: [&str; 4] = ["name", "path", "line", "column"];
+
+/// A declaration that shadows a declaration of the same name in a scope
+/// enclosing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shadow {
+    /// The declaration in the inner scope
+    pub inner: Identifier,
+    /// The declaration it shadows, which is the nearest one before it in a
+    /// scope enclosing its own
+    pub outer: Identifier,
+}
+
+/// Finds which identifiers in a piece of code are where their names are
+/// declared, rather than uses of them
+///
+/// The language layer records every use of a declared name, so a
+/// declaration is told apart by what is around it: a word that declares,
+/// like `let` or `val`, or the identifier's type, like `int` in `int a`,
+/// just before it, a `:=` just after it, or, for a parameter, a `(` or `,`
+/// before it and a `:` after it.
+///
+/// # Arguments
+///
+/// * `text` - The code
+/// * `lang` - The language of `text`
+/// * `identifiers` - The identifiers in `text`
+///
+/// # Returns
+///
+/// The identifiers that are declarations, in the order of `identifiers`
+pub fn declarations<'a>(text: &str, lang: &dyn Language, identifiers: &'a [Identifier]) -> Vec<&'a Identifier> {
+    let tokens = tokens::code(lang.tokenize(text));
+    let at: HashMap<usize, usize> = tokens.iter().enumerate().filter(|(_, t)| t.kind == TokenKind::Ident).map(|(i, t)| (t.start, i)).collect();
+    identifiers
+        .iter()
+        .filter(|x| {
+            let i = match at.get(&x.start) {
+                Some(&i) if i > 0 => i,
+                _ => return false,
+            };
+            let before = tokens[i - 1].text(text);
+            let after = text[x.end..].trim_start();
+            DECLARERS.contains(&before)
+                || (!x.typ.is_empty() && x.typ.ends_with(before) && !x.typ.ends_with(&format!(".{}", before)))
+                || after.starts_with(":=")
+                || ((before == "(" || before == ",") && after.starts_with(':') && !after.starts_with("::"))
+        })
+        .collect()
+}
+
+/// Finds the declarations in a piece of code that shadow a declaration of
+/// the same name in a scope enclosing theirs, like a local variable with the
+/// name of a parameter or a global
+///
+/// # Arguments
+///
+/// * `text` - The code
+/// * `lang` - The language of `text`
+/// * `parsed` - The parse results of `text`
+///
+/// # Returns
+///
+/// A Vec of Shadow, in the order the inner declarations appear
+pub fn shadowing(text: &str, lang: &dyn Language, parsed: &Parsed) -> Vec<Shadow> {
+    let declared = declarations(text, lang, &parsed.identifiers);
+    declared
+        .iter()
+        .filter_map(|inner| {
+            let outer = declared
+                .iter()
+                .filter(|x| x.name == inner.name && x.start < inner.start && x.scope_id != inner.scope_id && parsed.scopes.encloses(x.scope_id, inner.scope_id))
+                .max_by_key(|x| x.start)?;
+            Some(Shadow {
+                inner: (*inner).clone(),
+                outer: (*outer).clone(),
+            })
+        })
+        .collect()
+}
+
+/// A function name declared in more than one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    /// The name of the functions
+    pub name: String,
+    /// Each function with the name, with the path of its file, in the order
+    /// the files were given and then of where the functions start
+    pub functions: Vec<(String, Function)>,
+}
+
+/// Finds the function names declared in more than one file, like a helper
+/// copied from one file to another, which a query renaming one of them by
+/// name would rename both of
+///
+/// # Arguments
+///
+/// * `files` - The path of each file, as it should be reported, and its
+///   parse results
+///
+/// # Returns
+///
+/// A Vec of Duplicate, ordered by name
+pub fn duplicates<'a>(files: impl IntoIterator<Item = (&'a str, &'a Parsed)>) -> Vec<Duplicate> {
+    let mut by_name: BTreeMap<&str, Vec<(&str, &Function)>> = BTreeMap::new();
+    for (path, parsed) in files {
+        for f in &parsed.functions {
+            by_name.entry(f.name.as_str()).or_default().push((path, f));
+        }
+    }
+    by_name
+        .into_iter()
+        .filter(|(_, found)| found.iter().any(|(path, _)| *path != found[0].0))
+        .map(|(name, found)| Duplicate {
+            name: name.to_string(),
+            functions: found.into_iter().map(|(path, f)| (path.to_string(), f.clone())).collect(),
+        })
+        .collect()
+}
+
+/// How many identifiers of one type there are, and how many of those are
+/// declarations
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TypeCount {
+    /// Every identifier of the type, whether a declaration or a use
+    pub identifiers: usize,
+    /// The identifiers of the type that are declarations
+    pub declarations: usize,
+}
+
+/// A count of the identifiers of each type in a file, or a whole tree, to
+/// size a migration off a type before writing the query that makes it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Census {
+    /// The count of each type, by the type's name
+    pub counts: BTreeMap<String, TypeCount>,
+}
+
+impl Census {
+    /// Creates a Census that has counted nothing
+    pub fn new() -> Self {
+        Self { counts: BTreeMap::new() }
+    }
+
+    /// Counts the identifiers of each type in a piece of code, leaving out
+    /// those with no type
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The code
+    /// * `lang` - The language of `text`
+    /// * `parsed` - The parse results of `text`
+    pub fn of(text: &str, lang: &dyn Language, parsed: &Parsed) -> Self {
+        let mut census = Self::new();
+        for x in parsed.identifiers.iter().filter(|x| !x.typ.is_empty()) {
+            census.counts.entry(x.typ.clone()).or_default().identifiers += 1;
+        }
+        for x in declarations(text, lang, &parsed.identifiers).into_iter().filter(|x| !x.typ.is_empty()) {
+            census.counts.entry(x.typ.clone()).or_default().declarations += 1;
+        }
+        census
+    }
+
+    /// Adds the counts of another Census to this one, as for a total
+    pub fn add(&mut self, other: &Census) {
+        for (typ, count) in &other.counts {
+            let total = self.counts.entry(typ.clone()).or_default();
+            total.identifiers += count.identifiers;
+            total.declarations += count.declarations;
+        }
+    }
+
+    /// The counts, with the types of the most identifiers first, and those
+    /// of as many by name
+    fn sorted(&self) -> Vec<(&String, &TypeCount)> {
+        let mut counts: Vec<(&String, &TypeCount)> = self.counts.iter().collect();
+        counts.sort_by(|a, b| b.1.identifiers.cmp(&a.1.identifiers).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// Lists the counts as a table, with a header row and a line for each
+    /// type, in the layout of `symbols::table`
+    pub fn table(&self) -> String {
+        let mut lines = vec![["TYPE".to_string(), "IDENTIFIERS".into(), "DECLARATIONS".into()]];
+        lines.extend(self.sorted().into_iter().map(|(typ, x)| [typ.clone(), x.identifiers.to_string(), x.declarations.to_string()]));
+        let mut widths = [0; 3];
+        for line in &lines {
+            for (width, cell) in widths.iter_mut().zip(line) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut out = String::new();
+        for line in lines {
+            let cells: Vec<String> = line.iter().zip(widths).map(|(cell, width)| format!("{:width$}", cell, width = width)).collect();
+            out += cells.join("  ").trim_end();
+            out += "\n";
+        }
+        out
+    }
+
+    /// Lists the counts as a JSON array, with an object for each type with
+    /// `type`, `identifiers`, and `declarations` members, ordered as in
+    /// `table`
+    pub fn json(&self) -> Value {
+        Value::Array(
+            self.sorted()
+                .into_iter()
+                .map(|(typ, x)| {
+                    Value::Object(vec![
+                        ("type".to_string(), Value::String(typ.clone())),
+                        ("identifiers".to_string(), Value::Number(x.identifiers as i64)),
+                        ("declarations".to_string(), Value::Number(x.declarations as i64)),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    /// Lists the counts as the rows of delimited text, under
+    /// `CENSUS_COLUMNS`, ordered as in `table`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to put on each row, which is empty for a total
+    /// * `delimited` - Whether the rows are CSV or TSV
+    ///
+    /// # Returns
+    ///
+    /// The rows, each ending with a line break
+    pub fn delimited(&self, path: &str, delimited: Delimited) -> String {
+        self.sorted()
+            .into_iter()
+            .map(|(typ, x)| delimited.row(&[path, typ, &x.identifiers.to_string(), &x.declarations.to_string()]) + "\n")
+            .collect()
+    }
+}
+
+#[test]
+fn test_shadowing() {
+    use crate::languages::{clike::Clike, rust::Rust};
+    let text = "int g;\nvoid f(int a) {\n    int b = a + g;\n    { int a = g; String g; }\n}\nvoid h(int b) { b = 1; }\n";
+    let parsed = Parsed::new(text, &Clike {});
+    let names: Vec<&str> = declarations(text, &Clike {}, &parsed.identifiers).iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["g", "a", "b", "a", "g", "b"]);
+    let found: Vec<(String, usize, usize)> = shadowing(text, &Clike {}, &parsed).into_iter().map(|x| (x.inner.name, x.inner.line, x.outer.line)).collect();
+    assert_eq!(found, vec![("a".to_string(), 4, 2), ("g".to_string(), 4, 1)]);
+    let text = "fn f(x: i32) -> i32 {\n    let y = x;\n    if y > 0 { let x = 2; return x; }\n    y\n}\n";
+    let found: Vec<(String, usize, usize)> = shadowing(text, &Rust {}, &Parsed::new(text, &Rust {})).into_iter().map(|x| (x.inner.name, x.inner.line, x.outer.line)).collect();
+    assert_eq!(found, vec![("x".to_string(), 3, 1)]);
+}
+
+#[test]
+fn test_census() {
+    use crate::languages::clike::Clike;
+    let text = "Session s;\nvoid f(Session t, int n) {\n    s.run(); t.run(); n++;\n}\n";
+    let mut total = Census::of(text, &Clike {}, &Parsed::new(text, &Clike {}));
+    assert_eq!(total.counts["Session"], TypeCount { identifiers: 4, declarations: 2 });
+    assert_eq!(total.counts["int"], TypeCount { identifiers: 2, declarations: 1 });
+    total.add(&total.clone());
+    assert_eq!(total.table(), "TYPE     IDENTIFIERS  DECLARATIONS\nSession  8            4\nint      4            2\n");
+    assert_eq!(total.json().as_array().map(|x| x.len()), Some(2));
+    assert_eq!(total.delimited("", Delimited::Csv), ",Session,8,4\n,int,4,2\n");
+}
+
+#[test]
+fn test_duplicates() {
+    use crate::languages::clike::Clike;
+    let a = Parsed::new("int clamp(int x) { return x; }\nvoid run() {}\n", &Clike {});
+    let b = Parsed::new("void log() {}\n\nint clamp(int y) { return y; }\n", &Clike {});
+    let c = Parsed::new("void run() {}\nvoid log() {}\n", &Clike {});
+    let found: Vec<(String, Vec<(String, usize)>)> = duplicates([("a.c", &a), ("b.c", &b)]).into_iter().map(|x| (x.name, x.functions.into_iter().map(|(p, f)| (p, f.line)).collect())).collect();
+    assert_eq!(found, vec![("clamp".to_string(), vec![("a.c".to_string(), 1), ("b.c".to_string(), 3)])]);
+    // Overloads within one file are not duplicated across files
+    let d = Parsed::new("void f(int x) {}\nvoid f() {}\n", &Clike {});
+    assert!(duplicates([("d.c", &d)]).is_empty());
+    assert_eq!(duplicates([("a.c", &a), ("b.c", &b), ("c.c", &c)]).len(), 3);
+}
@@ -0,0 +1,137 @@
+//! Provides the reading of the files within archives, so that `--archives`
+//! can search the sources inside dependency jars and release tarballs
+//!
+//! Archives are only read, never written, so a query over them finds
+//! matches but makes no replacements. A file within an archive is named by
+//! a pseudo-path of the archive's path and its path within the archive,
+//! joined by `!`, like `lib/guava.jar!com/google/common/base/Strings.java`,
+//! as Java names resources inside jars. Archives within archives, like a jar
+//! inside a war, are read too, adding another `!` for each.
+
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::error::SpidiorError;
+
+/// The kinds of archive that can be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Zip,
+    TarGz,
+}
+
+fn kind(name: &str) -> Option<Kind> {
+    let name = name.to_lowercase();
+    if [".zip", ".jar", ".war", ".ear"].iter().any(|x| name.ends_with(x)) {
+        Some(Kind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+/// Whether a path names an archive that can be read, which is told by its
+/// extension: `.zip`, `.jar`, `.war`, and `.ear` are zip archives, and
+/// `.tar.gz` and `.tgz` are gzipped tarballs
+pub fn is_archive(path: &Path) -> bool {
+    kind(&path.to_string_lossy()).is_some()
+}
+
+/// Reads every file within an archive, and within any archives inside it
+///
+/// # Arguments
+///
+/// * `path` - The archive
+///
+/// # Returns
+///
+/// A Result<Vec<(PathBuf, Vec<u8>)>, SpidiorError>, where on success, it
+/// returns the pseudo-path and contents of each file, in the order they are
+/// stored. It will Err with an IoError if the archive cannot be read, or is
+/// not an archive of the kind its extension names.
+pub fn read(path: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>, SpidiorError> {
+    let name = path.display().to_string();
+    let kind = kind(&name).ok_or_else(|| SpidiorError::io(format!("Could not read {}", name), io::Error::new(io::ErrorKind::InvalidInput, "not an archive")))?;
+    let file = std::fs::File::open(path).map_err(|e| SpidiorError::io(format!("Could not read {}", name), e))?;
+    let mut files = Vec::new();
+    read_from(&name, kind, io::BufReader::new(file), &mut files).map_err(|e| SpidiorError::io(format!("Could not read archive {}", name), e))?;
+    Ok(files.into_iter().map(|(path, bytes)| (PathBuf::from(path), bytes)).collect())
+}
+
+/// Reads the files of an archive named `name` into `files`, reading the
+/// archives among them in turn
+fn read_from(name: &str, kind: Kind, reader: impl Read + Seek, files: &mut Vec<(String, Vec<u8>)>) -> io::Result<()> {
+    let mut found = Vec::new();
+    match kind {
+        Kind::Zip => {
+            let mut zip = zip::ZipArchive::new(reader)?;
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i)?;
+                if file.is_file() {
+                    let mut bytes = Vec::new();
+                    file.read_to_end(&mut bytes)?;
+                    found.push((file.name().to_string(), bytes));
+                }
+            }
+        }
+        Kind::TarGz => {
+            let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(reader));
+            for file in tar.entries()? {
+                let mut file = file?;
+                if file.header().entry_type().is_file() {
+                    let inner = file.path()?.display().to_string();
+                    let mut bytes = Vec::new();
+                    file.read_to_end(&mut bytes)?;
+                    found.push((inner, bytes));
+                }
+            }
+        }
+    }
+    for (inner, bytes) in found {
+        let path = format!("{}!{}", name, inner);
+        match self::kind(&inner) {
+            Some(kind) => read_from(&path, kind, Cursor::new(bytes), files)?,
+            None => files.push((path, bytes)),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_archive() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let zip = |files: &[(&str, &[u8])]| -> zip::result::ZipResult<Vec<u8>> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, bytes) in files {
+            zip.start_file(*name, zip::write::FileOptions::default())?;
+            zip.write_all(bytes)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    };
+    let inner = zip(&[("com/A.java", b"class A {}")])?;
+    let outer = zip(&[("README", b"hi"), ("lib/inner.jar", &inner)])?;
+    let dir = std::env::temp_dir().join(format!("spidior-archive-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let war = dir.join("app.war");
+    std::fs::write(&war, outer)?;
+    let found: Vec<(String, Vec<u8>)> = read(&war)?.into_iter().map(|(path, bytes)| (path.display().to_string(), bytes)).collect();
+    let name = war.display().to_string();
+    assert_eq!(found, vec![(format!("{}!README", name), b"hi".to_vec()), (format!("{}!lib/inner.jar!com/A.java", name), b"class A {}".to_vec())]);
+
+    let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(3);
+    header.set_mode(0o644);
+    tar.append_data(&mut header, "src/a.c", &b"x++"[..])?;
+    let tgz = dir.join("release.tar.gz");
+    std::fs::write(&tgz, tar.into_inner()?.finish()?)?;
+    assert_eq!(read(&tgz)?, vec![(PathBuf::from(format!("{}!src/a.c", tgz.display())), b"x++".to_vec())]);
+
+    assert!(is_archive(Path::new("deps/Guava.JAR")) && !is_archive(Path::new("a.java")));
+    let bad = dir.join("bad.zip");
+    std::fs::write(&bad, "not a zip")?;
+    assert!(matches!(read(&bad), Err(SpidiorError::IoError { .. })));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,440 @@
+//! Provides builders for running a query over files on disk, for tools that
+//! want what the `spidior` binary does without going through its command
+//! line
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::editing::{editset::EditSet, report, transaction::Transaction};
+use crate::error::SpidiorError;
+use crate::languages::{cache::{ParseCache, Parsed}, registry::Registry, Language};
+use crate::matcher::{self, Match};
+use crate::regexparser;
+use crate::replacer;
+#[cfg(feature = "tokio")]
+use crate::source::Visited;
+use crate::source::{generated, once_each};
+
+/// A language to parse files as, rather than detecting it from each file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lang {
+    C,
+    Cpp,
+    CSharp,
+    Go,
+    Java,
+    JavaScript,
+    Kotlin,
+    Markup,
+    Plain,
+    Ruby,
+    Rust,
+    Shell,
+    TypeScript,
+    /// Any other language, by a name or extension `--lang` accepts
+    Named(String),
+}
+
+impl Lang {
+    /// The name of the language, as given to `--lang`
+    pub fn name(&self) -> &str {
+        match self {
+            Lang::C => "c",
+            Lang::Cpp => "cpp",
+            Lang::CSharp => "csharp",
+            Lang::Go => "go",
+            Lang::Java => "java",
+            Lang::JavaScript => "js",
+            Lang::Kotlin => "kotlin",
+            Lang::Markup => "markup",
+            Lang::Plain => "plain",
+            Lang::Ruby => "ruby",
+            Lang::Rust => "rust",
+            Lang::Shell => "shell",
+            Lang::TypeScript => "ts",
+            Lang::Named(x) => x,
+        }
+    }
+}
+
+/// The files a query runs over, and how each is parsed and matched, which
+/// both builders share
+#[derive(Debug, Clone)]
+struct Target {
+    query: String,
+    lang: Option<Lang>,
+    paths: Vec<PathBuf>,
+    recursive: bool,
+    skip_strings: bool,
+    resolve_aliases: bool,
+    skip_disabled: bool,
+}
+
+impl Target {
+    fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            lang: None,
+            paths: vec![PathBuf::from(".")],
+            recursive: true,
+            skip_strings: false,
+            resolve_aliases: false,
+            skip_disabled: false,
+        }
+    }
+
+    /// Reads every file under the paths, once each, however many paths
+    /// reach it, at the path `source::once_each` picks, leaving out
+    /// spidior's own `.spidior` directory
+    fn read(&self) -> Vec<(PathBuf, String)> {
+        let entries = self.paths.iter().flat_map(|root| {
+            let mut walk = WalkDir::new(root).follow_links(true);
+            if !self.recursive {
+                walk = walk.max_depth(1);
+            }
+            walk.into_iter().filter_entry(|e| e.depth() == 0 || e.file_name() != report::STATE_DIR).filter_map(|e| e.ok())
+        });
+        let mut files = Vec::new();
+        for entry in once_each(entries) {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            if let Ok(contents) = fs::read_to_string(path) {
+                files.push((path.to_path_buf(), contents));
+            }
+        }
+        files
+    }
+
+    /// Reads every file under the paths without blocking, skipping any
+    /// directory already walked through a link, any file already read
+    /// through another hard link, and the `.spidior` directory
+    #[cfg(feature = "tokio")]
+    async fn read_async(&self) -> Vec<(PathBuf, String)> {
+        let mut files = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let mut visited = Visited::new();
+        for root in &self.paths {
+            let mut stack = vec![(root.clone(), 0)];
+            while let Some((path, depth)) = stack.pop() {
+                let metadata = match tokio::fs::metadata(&path).await {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                if metadata.is_file() {
+                    if !visited.first(&path) {
+                        continue;
+                    }
+                    if let Ok(contents) = tokio::fs::read_to_string(&path).await {
+                        files.push((path, contents));
+                    }
+                    continue;
+                }
+                if (depth > 0 && !self.recursive) || !seen.insert(tokio::fs::canonicalize(&path).await.unwrap_or_else(|_| path.clone())) {
+                    continue;
+                }
+                let mut entries = match tokio::fs::read_dir(&path).await {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                let mut children = Vec::new();
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    // As in `read`, run reports are never searched
+                    if entry.file_name() != report::STATE_DIR {
+                        children.push((entry.path(), depth + 1));
+                    }
+                }
+                // Pushed in reverse, so that they are walked in the order
+                // they were listed
+                stack.extend(children.into_iter().rev());
+            }
+        }
+        files
+    }
+
+    /// Parses each file as the language given, or else the one detected
+    /// from the file itself
+    fn parse(&self, files: Vec<(PathBuf, String)>) -> Result<Vec<(PathBuf, String, Parsed)>, SpidiorError> {
+        let registry = Registry::builtin();
+        let lang = match &self.lang {
+            Some(x) => Some(registry.by_name(x.name()).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}`", x.name())))?),
+            None => None,
+        };
+        let langs: Vec<&dyn Language> = files.iter().map(|(path, contents)| lang.unwrap_or_else(|| registry.detect(path, contents))).collect();
+        let parsed = ParseCache::new(None).parse_all(&files.iter().zip(&langs).map(|((_, contents), lang)| (contents.as_str(), *lang)).collect::<Vec<_>>());
+        Ok(files.into_iter().zip(parsed).map(|((path, contents), parsed)| (path, contents, parsed)).collect())
+    }
+}
+
+/// Runs `f` on a thread where blocking is allowed, for the async runs to
+/// match on
+#[cfg(feature = "tokio")]
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T, SpidiorError> + Send + 'static) -> Result<T, SpidiorError> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| SpidiorError::io("The matching thread failed", e.into()))?
+}
+
+/// Sets the options both builders share, each of which returns the builder
+/// so that they chain
+macro_rules! target_options {
+    () => {
+        /// Parses every file as `lang`, rather than detecting each file's
+        /// language from its extension or contents
+        pub fn lang(mut self, lang: Lang) -> Self {
+            self.target.lang = Some(lang);
+            self
+        }
+
+        /// Runs over these files and directories, rather than the current
+        /// directory
+        pub fn paths<I: IntoIterator<Item = P>, P: AsRef<Path>>(mut self, paths: I) -> Self {
+            self.target.paths = paths.into_iter().map(|x| x.as_ref().to_path_buf()).collect();
+            self
+        }
+
+        /// Whether to descend into directories below the paths, which it
+        /// does by default
+        pub fn recursive(mut self, recursive: bool) -> Self {
+            self.target.recursive = recursive;
+            self
+        }
+
+        /// Whether to skip matches inside string literals and comments, as
+        /// `--skip-strings` does
+        pub fn skip_strings(mut self, skip: bool) -> Self {
+            self.target.skip_strings = skip;
+            self
+        }
+
+        /// Whether `[[name=...]]` also matches aliases of the name, as
+        /// `--resolve-aliases` does
+        pub fn resolve_aliases(mut self, resolve: bool) -> Self {
+            self.target.resolve_aliases = resolve;
+            self
+        }
+
+        /// Whether to skip matches in code that preprocessor conditions
+        /// disable, as `--skip-disabled` does
+        pub fn skip_disabled(mut self, skip: bool) -> Self {
+            self.target.skip_disabled = skip;
+            self
+        }
+    };
+}
+
+/// The matches a query found in one file
+#[derive(Debug)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    /// The matches, in the order they appear
+    pub matches: Vec<Match>,
+}
+
+/// The edits a query made to one file
+#[derive(Debug)]
+pub struct FileEdits {
+    pub path: PathBuf,
+    /// The edits, against `original`
+    pub edits: EditSet,
+    /// The contents of the file before the edits
+    pub original: String,
+    /// The contents of the file after the edits
+    pub replaced: String,
+}
+
+/// Finds what the find part of a query matches across files, created by
+/// `Spidior::search`
+#[derive(Debug, Clone)]
+pub struct SearchBuilder {
+    target: Target,
+}
+
+impl SearchBuilder {
+    /// Creates a SearchBuilder for a query, which runs over the current
+    /// directory until given paths
+    pub fn new(query: &str) -> Self {
+        Self { target: Target::new(query) }
+    }
+
+    target_options!();
+
+    /// Runs the search
+    ///
+    /// # Returns
+    ///
+    /// A Result<Vec<FileMatches>, SpidiorError>, where on success, it
+    /// returns the files with at least one match, in the order they were
+    /// walked. It will Err if the query or language is not valid.
+    pub fn run(self) -> Result<Vec<FileMatches>, SpidiorError> {
+        let files = self.target.read();
+        self.search(files)
+    }
+
+    /// Runs the search like `run`, but walks and reads the files without
+    /// blocking, and matches on a thread where blocking is allowed
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(self) -> Result<Vec<FileMatches>, SpidiorError> {
+        let files = self.target.read_async().await;
+        blocking(move || self.search(files)).await
+    }
+
+    /// Matches the query against files that have been read
+    fn search(&self, files: Vec<(PathBuf, String)>) -> Result<Vec<FileMatches>, SpidiorError> {
+        let replace = regexparser::parse(&self.target.query)?;
+        let mut results = Vec::new();
+        for (path, contents, parsed) in self.target.parse(files)? {
+            let mut matches = matcher::find_parsed(&contents, replace.find.clone(), &parsed, self.target.resolve_aliases);
+            if self.target.skip_strings {
+                matches = matcher::skip_literals(matches, &parsed.spans);
+            }
+            if self.target.skip_disabled {
+                matches = matcher::skip_disabled(matches, &parsed.spans);
+            }
+            if !matches.is_empty() {
+                results.push(FileMatches { path, matches });
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Performs every replacement a query makes across files, created by
+/// `Spidior::replace`
+#[derive(Debug, Clone)]
+pub struct ReplaceBuilder {
+    target: Target,
+    dry_run: bool,
+    include_generated: bool,
+}
+
+impl ReplaceBuilder {
+    /// Creates a ReplaceBuilder for a query, which runs over the current
+    /// directory until given paths, and writes the files it changes
+    pub fn new(query: &str) -> Self {
+        Self {
+            target: Target::new(query),
+            dry_run: false,
+            include_generated: false,
+        }
+    }
+
+    target_options!();
+
+    /// Whether to only work out the edits, leaving every file as it was
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Whether to replace in files that look generated too, as
+    /// `--include-generated` does, rather than leaving them out
+    pub fn include_generated(mut self, include: bool) -> Self {
+        self.include_generated = include;
+        self
+    }
+
+    /// Runs the replacement, writing every changed file together unless
+    /// this is a dry run
+    ///
+    /// # Returns
+    ///
+    /// A Result<Vec<FileEdits>, SpidiorError>, where on success, it returns
+    /// the files with at least one edit, in the order they were walked. It
+    /// will Err if the query or language is not valid, or if any file could
+    /// not be written, in which case no file is changed.
+    pub fn run(self) -> Result<Vec<FileEdits>, SpidiorError> {
+        let files = self.target.read();
+        self.replace(files)
+    }
+
+    /// Runs the replacement like `run`, but walks and reads the files
+    /// without blocking, and matches and writes on a thread where blocking
+    /// is allowed, so that the files are still written together
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(self) -> Result<Vec<FileEdits>, SpidiorError> {
+        let files = self.target.read_async().await;
+        blocking(move || self.replace(files)).await
+    }
+
+    /// Works out the edits to files that have been read, and writes them
+    /// unless this is a dry run
+    fn replace(&self, files: Vec<(PathBuf, String)>) -> Result<Vec<FileEdits>, SpidiorError> {
+        let replace = regexparser::parse(&self.target.query)?;
+        let t = &self.target;
+        let mut results = Vec::new();
+        for (path, original, parsed) in t.parse(files)? {
+            if !self.include_generated && generated(&original).is_some() {
+                continue;
+            }
+            let edits = replacer::edits_parsed(&original, replace.clone(), |_, _| true, &parsed, t.skip_strings, t.resolve_aliases, t.skip_disabled)?.with_origin(&t.query);
+            if edits.is_empty() {
+                continue;
+            }
+            let replaced = edits.apply(&original)?;
+            results.push(FileEdits {
+                path,
+                edits,
+                original,
+                replaced,
+            });
+        }
+        if !self.dry_run {
+            let mut tx = Transaction::new();
+            for x in &results {
+                tx.stage(&x.path, &x.replaced)?;
+            }
+            tx.commit()?;
+        }
+        Ok(results)
+    }
+}
+
+#[test]
+fn test_builders() {
+    let dir = std::env::temp_dir().join(format!("spidior-test-builders-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    let code = "class A { Session s; void go() { s.run(); } }";
+    fs::write(dir.join("A.java"), code).unwrap();
+    fs::write(dir.join("sub").join("B.java"), "class B { int x; }").unwrap();
+    let found = SearchBuilder::new("%s/[[type=Session]]/sess/g").paths([&dir]).run().unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].matches.len(), 2);
+    let changed = ReplaceBuilder::new("%s/[[type=Session]]/sess/g").lang(Lang::Java).paths([&dir]).dry_run(true).run().unwrap();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].replaced, "class A { Session sess; void go() { sess.run(); } }");
+    assert_eq!(fs::read_to_string(dir.join("A.java")).unwrap(), code);
+    ReplaceBuilder::new("%s/[[type=Session]]/sess/g").paths([&dir]).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.join("A.java")).unwrap(), changed[0].replaced);
+    let generated = "// DO NOT EDIT\nclass G { Session s; }";
+    fs::write(dir.join("G.java"), generated).unwrap();
+    ReplaceBuilder::new("%s/[[type=Session]]/sess/g").paths([&dir]).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.join("G.java")).unwrap(), generated);
+    ReplaceBuilder::new("%s/[[type=Session]]/sess/g").paths([&dir]).include_generated(true).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.join("G.java")).unwrap(), "// DO NOT EDIT\nclass G { Session sess; }");
+    assert_eq!(SearchBuilder::new("%s/x/y/g").paths([&dir]).recursive(false).run().unwrap().len(), 0);
+    assert!(matches!(SearchBuilder::new("%s/x/y/g").lang(Lang::Named("cobol".into())).paths([&dir]).run(), Err(SpidiorError::LanguageError(_))));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_builders_async() {
+    let dir = std::env::temp_dir().join(format!("spidior-test-builders-async-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("A.java"), "class A { Session s; }").unwrap();
+    fs::write(dir.join("sub").join("B.java"), "class B { Session t; }").unwrap();
+    fs::create_dir_all(dir.join(report::STATE_DIR)).unwrap();
+    fs::write(dir.join(report::STATE_DIR).join("R.java"), "class R { Session r; }").unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let found = runtime.block_on(SearchBuilder::new("%s/[[type=Session]]/sess/g").paths([&dir]).run_async()).unwrap();
+    assert_eq!(found.len(), 2);
+    let shallow = runtime.block_on(SearchBuilder::new("%s/[[type=Session]]/sess/g").paths([&dir]).recursive(false).run_async()).unwrap();
+    assert_eq!(shallow.iter().map(|x| x.path.clone()).collect::<Vec<_>>(), vec![dir.join("A.java")]);
+    runtime.block_on(ReplaceBuilder::new("%s/[[type=Session]]/sess/g").paths([&dir]).run_async()).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("sub").join("B.java")).unwrap(), "class B { Session sess; }");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,194 @@
+//! Provides the config file, which is a small subset of TOML: `[section]`
+//! headers, each followed by `key = value` lines whose values are strings
+//! or arrays of strings, which may span several lines. For example,
+//!
+//! ```toml
+//! [languages.clike]
+//! keywords = ["unless"]
+//! modifiers = ["__declspec", "mutating"]
+//! ```
+
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::error::SpidiorError;
+
+/// The name of the config file looked for in the current directory when
+/// `--config` is not given
+pub const DEFAULT_PATH: &str = ".spidior.toml";
+
+/// The settings read from a config file
+#[derive(Debug, Default)]
+pub struct Config {
+    sections: HashMap<String, HashMap<String, Vec<String>>>,
+    /// The names of the sections, in the order they first appear
+    order: Vec<String>,
+}
+
+impl Config {
+    /// Creates a Config with no settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the config file at `path`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the config file
+    ///
+    /// # Returns
+    ///
+    /// The Config, or an error if the file cannot be read or parsed
+    pub fn load(path: &Path) -> Result<Self, SpidiorError> {
+        let text = fs::read_to_string(path).map_err(|e| SpidiorError::io(format!("Could not read {}", path.display()), e))?;
+        Self::parse(&text).map_err(|x| SpidiorError::parse(format!("{}: {}", path.display(), x)))
+    }
+
+    /// Parses the text of a config file
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the config file
+    ///
+    /// # Returns
+    ///
+    /// The Config, or a message naming the first line that is not valid
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::new();
+        let mut section = String::new();
+        let mut lines = text.lines().enumerate();
+        while let Some((n, line)) = lines.next() {
+            let mut line = Self::strip_comment(line).trim().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                section = name.trim().to_string();
+                if !config.sections.contains_key(&section) {
+                    config.order.push(section.clone());
+                }
+                config.sections.entry(section.clone()).or_default();
+                continue;
+            }
+            let eq = line.find('=').ok_or_else(|| Self::error(n, &line))?;
+            // An array continues until the line that closes it
+            if line[eq + 1..].trim_start().starts_with('[') {
+                while !line.ends_with(']') {
+                    match lines.next() {
+                        Some((_, next)) => line = line + " " + Self::strip_comment(next).trim(),
+                        None => return Err(format!("line {}: unclosed array", n + 1)),
+                    }
+                }
+            }
+            let key = line[..eq].trim().to_string();
+            let values = Self::parse_value(line[eq + 1..].trim()).ok_or_else(|| Self::error(n, &line))?;
+            if key.is_empty() || section.is_empty() {
+                return Err(Self::error(n, &line));
+            }
+            config.sections.entry(section.clone()).or_default().insert(key, values);
+        }
+        Ok(config)
+    }
+
+    /// The message for the invalid setting starting on line `n`, counting from 0
+    fn error(n: usize, line: &str) -> String {
+        format!("line {}: expected `key = value`, found `{}`", n + 1, line)
+    }
+
+    /// Removes a `#` comment from the end of a line, unless it is in a
+    /// string, where a `\"` does not end the string
+    fn strip_comment(line: &str) -> &str {
+        let (mut quoted, mut escaped) = (false, false);
+        for (i, c) in line.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                '#' if !quoted => return &line[..i],
+                _ => {}
+            }
+        }
+        line
+    }
+
+    /// Parses a string, or an array of strings, into the strings it holds
+    fn parse_value(value: &str) -> Option<Vec<String>> {
+        let string = |x: &str| -> Option<String> {
+            let x = x.trim().strip_prefix('"')?.strip_suffix('"')?;
+            Some(x.replace("\\\"", "\"").replace("\\\\", "\\"))
+        };
+        match value.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            Some(items) => Self::split_items(items)
+                .into_iter()
+                .filter(|x| !x.trim().is_empty())
+                .map(string)
+                .collect(),
+            None => Some(vec![string(value)?]),
+        }
+    }
+
+    /// Splits the items of an array at the commas that are not in a string
+    fn split_items(items: &str) -> Vec<&str> {
+        let mut out = Vec::new();
+        let (mut quoted, mut escaped, mut start) = (false, false, 0);
+        for (i, c) in items.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                ',' if !quoted => {
+                    out.push(&items[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        out.push(&items[start..]);
+        out
+    }
+
+    /// The names of the sections within the config, like `languages.clike`,
+    /// in the order they first appear
+    pub fn sections(&self) -> impl Iterator<Item = &str> {
+        self.order.iter().map(|x| x.as_str())
+    }
+
+    /// Looks up a setting
+    ///
+    /// # Arguments
+    ///
+    /// * `section` - The name of the section the setting is in
+    /// * `key` - The name of the setting
+    ///
+    /// # Returns
+    ///
+    /// The strings the setting holds, or `None` if it is not set
+    pub fn get(&self, section: &str, key: &str) -> Option<&[String]> {
+        self.sections.get(section)?.get(key).map(|x| x.as_slice())
+    }
+
+    /// The names of the settings within a section
+    pub fn keys(&self, section: &str) -> impl Iterator<Item = &str> {
+        self.sections.get(section).into_iter().flat_map(|x| x.keys().map(|k| k.as_str()))
+    }
+}
+
+#[test]
+fn test_parse() {
+    let text = "# spidior settings\n[languages.clike]\nkeywords = [\"unless\", \"until\"] # loops\nmodifiers = [\n    \"__declspec\",\n    \"mutating\",\n]\n\n[languages.go]\nkeywords = \"iota\"\n";
+    let config = Config::parse(text).unwrap();
+    let sections: Vec<&str> = config.sections().collect();
+    assert_eq!(sections, vec!["languages.clike", "languages.go"]);
+    assert_eq!(config.get("languages.clike", "keywords").unwrap(), ["unless", "until"]);
+    assert_eq!(config.get("languages.clike", "modifiers").unwrap(), ["__declspec", "mutating"]);
+    assert_eq!(config.get("languages.go", "keywords").unwrap(), ["iota"]);
+    assert_eq!(config.get("languages.go", "modifiers"), None);
+    let config = Config::parse("[a]\nkeys = [\"f(a, b)\", \"\\\", \\\"\"]").unwrap();
+    assert_eq!(config.get("a", "keys").unwrap(), ["f(a, b)", "\", \""]);
+    let config = Config::parse("[a]\nmatch = \"a\\\"#b\" # c\nkeys = [\"\\\\\" # d\n]").unwrap();
+    assert_eq!(config.get("a", "match").unwrap(), ["a\"#b"]);
+    assert_eq!(config.get("a", "keys").unwrap(), ["\\"]);
+    assert!(Config::parse("keywords = [\"a\"]").is_err());
+    assert!(Config::parse("[a]\nkeywords = [unquoted]").is_err());
+    assert!(Config::parse("[a]\nkeywords = [\"a\",").is_err());
+}
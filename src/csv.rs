@@ -0,0 +1,46 @@
+//! Provides the delimited text that spidior prints for spreadsheets and
+//! databases to load: CSV, quoted as RFC 4180 has it, and TSV, in which
+//! tabs and line breaks within a field are escaped, as the formats
+//! `--format csv` and `tsv` name
+
+/// A kind of delimited text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimited {
+    /// Comma-separated values, where a field holding a comma, a quote, or a
+    /// line break is quoted, with each quote within it doubled
+    Csv,
+    /// Tab-separated values, where tabs, line breaks, and backslashes
+    /// within a field are written as `\t`, `\n`, `\r`, and `\\`
+    Tsv,
+}
+
+impl Delimited {
+    /// Writes a field, quoting or escaping it as needed
+    pub fn field(&self, text: &str) -> String {
+        match self {
+            Delimited::Csv if text.contains([',', '"', '\n', '\r']) => format!("\"{}\"", text.replace('"', "\"\"")),
+            Delimited::Csv => text.to_string(),
+            Delimited::Tsv => text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r"),
+        }
+    }
+
+    /// Writes a row of fields, without a line break after it
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The fields of the row, in order
+    pub fn row<S: AsRef<str>>(&self, fields: &[S]) -> String {
+        let separator = match self {
+            Delimited::Csv => ",",
+            Delimited::Tsv => "\t",
+        };
+        fields.iter().map(|x| self.field(x.as_ref())).collect::<Vec<_>>().join(separator)
+    }
+}
+
+#[test]
+fn test_delimited() {
+    assert_eq!(Delimited::Csv.row(&["a.c", "3", "x, y", "say \"hi\""]), "a.c,3,\"x, y\",\"say \"\"hi\"\"\"");
+    assert_eq!(Delimited::Csv.row(&["a\nb", ""]), "\"a\nb\",");
+    assert_eq!(Delimited::Tsv.row(&["a\tb", "c\\d\ne", "x, y"]), "a\\tb\tc\\\\d\\ne\tx, y");
+}
@@ -0,0 +1,426 @@
+//! Provides the daemon run by `spidior serve`, a long-lived process that
+//! answers find and replace requests in JSON-RPC, so that editor plugins
+//! and watch tooling do not pay to parse queries and files on every request
+//!
+//! Each request and response is one line of JSON. The daemon keeps every
+//! query it has parsed, and the parse results of every file it has read,
+//! which are only parsed again once the file changes. It has these methods:
+//!
+//! * `find` - with `query`, and either `code` to search a buffer, or
+//!   `paths` (the current directory by default) to search files. Optional
+//!   `lang` and `recursive` (true by default) work as the command line's.
+//!   Returns each file with matches, and its matches.
+//! * `replace` - with the same parameters, returns each file with edits,
+//!   and its edits, writing the files together unless `dry_run` is true.
+//!   Files that look generated are left out unless `include_generated` is.
+//!   Given `code`, also returns the changed buffer.
+//! * `shutdown` - stops the daemon once it has answered.
+//!
+//! A request that panics is answered with an internal error, and the daemon
+//! goes on to the next one.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use walkdir::WalkDir;
+
+use crate::editing::{editset::EditSet, report, transaction::Transaction};
+use crate::error::{catch_panic, SpidiorError};
+use crate::json::{self, Value};
+use crate::languages::{cache::{ParseCache, Parsed}, registry::Registry};
+use crate::matcher::{self, Match};
+use crate::regexparser::{self, ast::Replace};
+use crate::replacer;
+use crate::source::{generated, once_each};
+
+/// The JSON-RPC error code for a line that is not JSON
+const PARSE_ERROR: i64 = -32700;
+/// The JSON-RPC error code for a method the daemon does not offer
+const METHOD_NOT_FOUND: i64 = -32601;
+/// The JSON-RPC error code for missing or mistyped parameters
+const INVALID_PARAMS: i64 = -32602;
+/// The JSON-RPC error code for a request that panicked, which the daemon
+/// answers rather than stopping
+const INTERNAL_ERROR: i64 = -32603;
+/// The JSON-RPC error code for a request spidior could not carry out, whose
+/// message starts with the kind of SpidiorError, like `ParseError: `
+const REQUEST_FAILED: i64 = -32000;
+
+/// Builds a JSON object from its members
+fn object(members: Vec<(&str, Value)>) -> Value {
+    Value::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+/// A JSON-RPC error, with its code and message
+type RpcError = (i64, String);
+
+/// Turns an error into a JSON-RPC error, naming its kind
+fn failed(e: SpidiorError) -> RpcError {
+    let kind = match e {
+        SpidiorError::ParseError(_) => "ParseError",
+        SpidiorError::CompileError(_) => "CompileError",
+        SpidiorError::IoError { .. } => "IoError",
+        SpidiorError::LanguageError(_) => "LanguageError",
+        SpidiorError::EditError(_) => "EditError",
+        SpidiorError::LimitError(_) => "LimitError",
+    };
+    (REQUEST_FAILED, format!("{}: {}", kind, e))
+}
+
+/// A file the daemon has parsed, which is reused while the file's contents
+/// and language stay the same
+struct Indexed {
+    /// A hash of the contents the file was parsed from
+    hash: u64,
+    lang: String,
+    parsed: Arc<Parsed>,
+}
+
+/// The queries and files a daemon keeps between requests
+pub struct Daemon {
+    queries: HashMap<String, Replace>,
+    index: HashMap<PathBuf, Indexed>,
+    /// Where parse results are stored between runs, which is read for any
+    /// file not yet in the index
+    cache: ParseCache,
+    /// Whether a client has asked the daemon to stop
+    pub shutdown: bool,
+}
+
+impl Daemon {
+    /// Creates a Daemon
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache to read parse results from for files the daemon
+    ///   has not parsed yet
+    pub fn new(cache: ParseCache) -> Self {
+        Self {
+            queries: HashMap::new(),
+            index: HashMap::new(),
+            cache,
+            shutdown: false,
+        }
+    }
+
+    /// Answers one line of a request
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The request, as JSON
+    ///
+    /// # Returns
+    ///
+    /// The response, as JSON, or None if the request was a notification. A
+    /// request that panics is answered with an internal error.
+    pub fn handle(&mut self, line: &str) -> Option<String> {
+        self.answer(line, Self::call)
+    }
+
+    /// Answers one line of a request, carrying it out with `call`
+    fn answer(&mut self, line: &str, call: impl FnOnce(&mut Self, &str, &Value) -> Result<Value, RpcError>) -> Option<String> {
+        let (id, result) = match json::parse(line) {
+            Ok(request) => {
+                let method = request.get("method").and_then(|x| x.as_str()).unwrap_or_default().to_string();
+                let params = request.get("params").cloned().unwrap_or(Value::Null);
+                let result = match catch_panic(|| call(self, &method, &params)) {
+                    Ok(x) => x,
+                    Err(e) => Err((INTERNAL_ERROR, format!("Internal error: {}", e))),
+                };
+                (request.get("id")?.clone(), result)
+            }
+            Err(e) => (Value::Null, Err((PARSE_ERROR, e))),
+        };
+        let outcome = match result {
+            Ok(x) => ("result", x),
+            Err((code, message)) => ("error", object(vec![("code", Value::Number(code)), ("message", Value::String(message))])),
+        };
+        Some(object(vec![("jsonrpc", Value::String("2.0".into())), ("id", id), outcome]).to_string())
+    }
+
+    /// Carries out a request for `method` with `params`
+    fn call(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "find" => self.find(params),
+            "replace" => self.replace(params),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method `{}`", method))),
+        }
+    }
+
+    /// The parsed form of a query, parsing it only the first time it is seen
+    fn query(&mut self, params: &Value) -> Result<Replace, RpcError> {
+        let text = params.get("query").and_then(|x| x.as_str()).ok_or((INVALID_PARAMS, "Expected a `query` string".to_string()))?;
+        if let Some(x) = self.queries.get(text) {
+            return Ok(x.clone());
+        }
+        let replace = regexparser::parse(text).map_err(failed)?;
+        self.queries.insert(text.to_string(), replace.clone());
+        Ok(replace)
+    }
+
+    /// The name of the language given by `lang`, if it is given
+    fn lang(&self, params: &Value) -> Result<Option<String>, RpcError> {
+        match params.get("lang").and_then(|x| x.as_str()) {
+            Some(name) => match Registry::builtin().by_name(name) {
+                Some(x) => Ok(Some(x.name().to_string())),
+                None => Err(failed(SpidiorError::language(format!("Unknown language `{}`", name)))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Parses a file, or finds its results in the index if it has not
+    /// changed since it was last parsed
+    fn parse(&mut self, path: &Path, text: &str, lang: Option<&str>) -> Arc<Parsed> {
+        let registry = Registry::builtin();
+        let lang = match lang.and_then(|x| registry.by_name(x)) {
+            Some(x) => x,
+            None => registry.detect(path, text),
+        };
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+        match self.index.get(path) {
+            Some(x) if x.hash == hash && x.lang == lang.name() => x.parsed.clone(),
+            _ => {
+                let parsed = Arc::new(self.cache.parse(text, lang));
+                let indexed = Indexed {
+                    hash,
+                    lang: lang.name().to_string(),
+                    parsed: parsed.clone(),
+                };
+                self.index.insert(path.to_path_buf(), indexed);
+                parsed
+            }
+        }
+    }
+
+    /// The files a request runs over, with their contents, which is the
+    /// buffer given as `code` if there is one, leaving out spidior's own
+    /// `.spidior` directory
+    fn files(&self, params: &Value) -> Result<Vec<(PathBuf, String)>, RpcError> {
+        if let Some(code) = params.get("code").and_then(|x| x.as_str()) {
+            let path = params.get("path").and_then(|x| x.as_str()).unwrap_or_default();
+            return Ok(vec![(PathBuf::from(path), code.to_string())]);
+        }
+        let paths = match params.get("paths").and_then(|x| x.as_array()) {
+            Some(x) => x.iter().map(|x| x.as_str().map(PathBuf::from).ok_or((INVALID_PARAMS, "Expected `paths` to hold strings".to_string()))).collect::<Result<_, _>>()?,
+            None => vec![PathBuf::from(".")],
+        };
+        let recursive = !matches!(params.get("recursive"), Some(Value::Bool(false)));
+        let entries = paths.into_iter().flat_map(|root| {
+            let mut walk = WalkDir::new(root).follow_links(true);
+            if !recursive {
+                walk = walk.max_depth(1);
+            }
+            walk.into_iter().filter_entry(|e| e.depth() == 0 || e.file_name() != report::STATE_DIR).filter_map(|e| e.ok())
+        });
+        let mut files = Vec::new();
+        for entry in once_each(entries) {
+            if entry.path().is_file() {
+                if let Ok(contents) = std::fs::read_to_string(entry.path()) {
+                    files.push((entry.path().to_path_buf(), contents));
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Finds what the find part of a query matches
+    fn find(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let replace = self.query(params)?;
+        let lang = self.lang(params)?;
+        let mut results = Vec::new();
+        for (path, text) in self.files(params)? {
+            let parsed = self.parse(&path, &text, lang.as_deref());
+            let matches: Vec<Match> = matcher::find_parsed(&text, replace.find.clone(), &parsed, false);
+            if matches.is_empty() {
+                continue;
+            }
+            let matches = matches
+                .iter()
+                .map(|m| {
+                    let (line, column) = m.position();
+                    object(vec![
+                        ("start", Value::Number(m.start() as i64)),
+                        ("end", Value::Number(m.end() as i64)),
+                        ("line", Value::Number(line as i64)),
+                        ("column", Value::Number(column as i64)),
+                        ("text", Value::String(m.text().to_string())),
+                    ])
+                })
+                .collect();
+            results.push(object(vec![("file", Value::String(path.display().to_string())), ("matches", Value::Array(matches))]));
+        }
+        Ok(Value::Array(results))
+    }
+
+    /// Performs every replacement a query makes
+    fn replace(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let replace = self.query(params)?;
+        let lang = self.lang(params)?;
+        let buffer = params.get("code").is_some();
+        let dry_run = buffer || matches!(params.get("dry_run"), Some(Value::Bool(true)));
+        let include_generated = matches!(params.get("include_generated"), Some(Value::Bool(true)));
+        let mut changed: Vec<(PathBuf, String, EditSet)> = Vec::new();
+        for (path, text) in self.files(params)? {
+            if !buffer && !include_generated && generated(&text).is_some() {
+                continue;
+            }
+            let parsed = self.parse(&path, &text, lang.as_deref());
+            let edits = replacer::edits_parsed(&text, replace.clone(), |_, _| true, &parsed, false, false, false).map_err(failed)?;
+            if !edits.is_empty() || buffer {
+                changed.push((path, text, edits));
+            }
+        }
+        let mut tx = Transaction::new();
+        let mut results = Vec::new();
+        for (path, text, edits) in &changed {
+            let replaced = edits.apply(text).map_err(failed)?;
+            if !dry_run {
+                tx.stage(path, &replaced).map_err(failed)?;
+            }
+            let list = edits
+                .iter()
+                .map(|x| {
+                    object(vec![
+                        ("start", Value::Number(x.start as i64)),
+                        ("end", Value::Number(x.end as i64)),
+                        ("old", Value::String(text[x.start..x.end].to_string())),
+                        ("new", Value::String(x.replacement.clone())),
+                    ])
+                })
+                .collect();
+            let mut members = vec![("file", Value::String(path.display().to_string())), ("edits", Value::Array(list))];
+            if buffer {
+                members.push(("code", Value::String(replaced)));
+            }
+            results.push(object(members));
+        }
+        if !dry_run {
+            tx.commit().map_err(failed)?;
+        }
+        Ok(Value::Array(results))
+    }
+}
+
+/// Answers requests, a line each, from `input` until it ends or a client
+/// asks the daemon to stop
+///
+/// # Arguments
+///
+/// * `daemon` - The daemon, which may be shared with other connections
+/// * `input` - Where requests are read from
+/// * `output` - Where responses are written to
+pub fn serve(daemon: &Mutex<Daemon>, input: impl BufRead, mut output: impl Write) -> Result<(), SpidiorError> {
+    for line in input.lines() {
+        let line = line.map_err(|e| SpidiorError::io("Could not read a request", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut daemon = daemon.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(response) = daemon.handle(&line) {
+            writeln!(output, "{}", response).and_then(|_| output.flush()).map_err(|e| SpidiorError::io("Could not write a response", e))?;
+        }
+        if daemon.shutdown {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Answers requests over a Unix socket, a connection at a time on its own
+/// thread, until a client asks the daemon to stop
+///
+/// # Arguments
+///
+/// * `daemon` - The daemon
+/// * `path` - Where to create the socket, which is removed when the daemon
+///   stops
+#[cfg(unix)]
+pub fn serve_socket(daemon: Daemon, path: &Path) -> Result<(), SpidiorError> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+    let listener = UnixListener::bind(path).map_err(|e| SpidiorError::io(format!("Could not listen on {}", path.display()), e))?;
+    let daemon = Arc::new(Mutex::new(daemon));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let shared = daemon.clone();
+        let path = path.to_path_buf();
+        std::thread::spawn(move || {
+            if let Ok(reader) = stream.try_clone() {
+                let _ = serve(&shared, std::io::BufReader::new(reader), &stream);
+            }
+            // A connection that stops the daemon wakes the listener, so that
+            // it sees the daemon has stopped
+            if shared.lock().map_or(true, |x| x.shutdown) {
+                let _ = UnixStream::connect(&path);
+            }
+        });
+        if daemon.lock().map_or(true, |x| x.shutdown) {
+            break;
+        }
+    }
+    let _ = std::fs::remove_file(path);
+    Ok(())
+}
+
+#[test]
+fn test_daemon() {
+    let dir = std::env::temp_dir().join(format!("spidior-test-daemon-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("A.java"), "class A { Session s; void go() { s.run(); } }").unwrap();
+    let daemon = Mutex::new(Daemon::new(ParseCache::new(None)));
+    let paths = format!("[{}]", Value::String(dir.display().to_string()));
+    let input = [
+        format!("{{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"find\", \"params\": {{\"query\": \"%s/[[type=Session]]/sess/g\", \"paths\": {}}}}}", paths),
+        format!("{{\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"replace\", \"params\": {{\"query\": \"%s/[[type=Session]]/sess/g\", \"paths\": {}}}}}", paths),
+        "{\"jsonrpc\": \"2.0\", \"id\": 3, \"method\": \"replace\", \"params\": {\"query\": \"%s/[[name=x]]/y/g\", \"code\": \"int x = x;\", \"lang\": \"c\"}}".to_string(),
+        "{\"jsonrpc\": \"2.0\", \"id\": 4, \"method\": \"find\", \"params\": {\"query\": \"%s/a/b\"}}".to_string(),
+        "not json".to_string(),
+        "{\"jsonrpc\": \"2.0\", \"id\": 5, \"method\": \"shutdown\"}".to_string(),
+        "{\"jsonrpc\": \"2.0\", \"id\": 6, \"method\": \"find\"}".to_string(),
+    ]
+    .join("\n");
+    let mut output = Vec::new();
+    serve(&daemon, input.as_bytes(), &mut output).unwrap();
+    let responses: Vec<Value> = String::from_utf8(output).unwrap().lines().map(|x| json::parse(x).unwrap()).collect();
+    assert_eq!(responses.len(), 6);
+    let found = responses[0].get("result").and_then(|x| x.as_array()).unwrap();
+    assert_eq!(found[0].get("matches").and_then(|x| x.as_array()).map(|x| x.len()), Some(2));
+    assert_eq!(std::fs::read_to_string(dir.join("A.java")).unwrap(), "class A { Session sess; void go() { sess.run(); } }");
+    let buffer = &responses[2].get("result").and_then(|x| x.as_array()).unwrap()[0];
+    assert_eq!(buffer.get("code").and_then(|x| x.as_str()), Some("int y = y;"));
+    assert_eq!(responses[3].get("error").and_then(|x| x.get("code")), Some(&Value::Number(REQUEST_FAILED)));
+    assert_eq!(responses[4].get("error").and_then(|x| x.get("code")), Some(&Value::Number(PARSE_ERROR)));
+    // Both requests for the Session query shared its parse, and the query
+    // that is not valid was not kept
+    assert_eq!(daemon.lock().unwrap().queries.len(), 2);
+    assert_eq!(daemon.lock().unwrap().index.len(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_panic() {
+    let mut daemon = Daemon::new(ParseCache::new(None));
+    let request = "{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"replace\", \"params\": {\"query\": \"%s/x/y/g\", \"code\": \"x\"}}";
+    let response = json::parse(&daemon.answer(request, |_, _, _| panic!("bad request")).unwrap()).unwrap();
+    let error = response.get("error").unwrap();
+    assert_eq!(error.get("code"), Some(&Value::Number(INTERNAL_ERROR)));
+    assert_eq!(error.get("message").and_then(|x| x.as_str()), Some("Internal error: bad request"));
+    // The daemon answers the next request as if nothing had happened
+    let response = json::parse(&daemon.handle(request).unwrap()).unwrap();
+    let buffer = &response.get("result").and_then(|x| x.as_array()).unwrap()[0];
+    assert_eq!(buffer.get("code").and_then(|x| x.as_str()), Some("y"));
+}
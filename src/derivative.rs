@@ -0,0 +1,294 @@
+//! Provides a second way of matching a regex, by Brzozowski derivatives,
+//! which `--engine derivatives` runs in place of the NFA, and
+//! `--engine differential` runs alongside it, reporting wherever the two
+//! disagree
+//!
+//! The derivative of a regex by a character is the regex that matches what
+//! may follow that character in a match of the first. Matching takes the
+//! derivative by each character of the input in turn, and a prefix matches
+//! when its derivative matches the empty string. This shares nothing with
+//! the NFA but the parsed regex, so it is a check on how the NFA is built
+//! and run. It finds no groups, and cannot match query sets, whose matches
+//! depend on the parse of the code rather than its characters, nor `.`
+//! under the `c` flag.
+
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::error::SpidiorError;
+use crate::languages::lines::{locate, LineIndex};
+use crate::nfa::matcher::{Budget, Match};
+use crate::regex2nfa::set_chars;
+use crate::regexparser::ast::{self, Basic, Concatenation, Elementary, Regex, Set, Simple, Union};
+
+/// Which way queries are matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// By the NFA, or the tagged DFA made from it
+    Nfa,
+    /// By derivatives, which find no groups
+    Derivatives,
+    /// By both, reporting any difference in what they match, and keeping
+    /// the NFA's matches
+    Differential,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nfa" => Ok(Engine::Nfa),
+            "derivatives" => Ok(Engine::Derivatives),
+            "differential" => Ok(Engine::Differential),
+            _ => Err(format!("Unknown engine `{}`, expected `nfa`, `derivatives`, or `differential`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Engine::Nfa => write!(f, "nfa"),
+            Engine::Derivatives => write!(f, "derivatives"),
+            Engine::Differential => write!(f, "differential"),
+        }
+    }
+}
+
+/// A regex as derivatives are taken of it, kept simplified so that it
+/// does not grow without bound
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Re {
+    /// Matches nothing
+    Null,
+    /// Matches only the empty string
+    Empty,
+    Char(char),
+    /// A set, holding its characters, which is negative if the flag is set
+    Set(Rc<str>, bool),
+    /// Any character but a newline
+    Any,
+    Cat(Rc<Re>, Rc<Re>),
+    /// The alternatives, sorted and without repeats
+    Alt(Rc<[Re]>),
+    Star(Rc<Re>),
+}
+
+fn cat(a: Re, b: Re) -> Re {
+    match (a, b) {
+        (Re::Null, _) | (_, Re::Null) => Re::Null,
+        (Re::Empty, x) | (x, Re::Empty) => x,
+        (a, b) => Re::Cat(Rc::new(a), Rc::new(b)),
+    }
+}
+
+fn alt(a: Re, b: Re) -> Re {
+    let mut all = Vec::new();
+    for x in [a, b] {
+        match x {
+            Re::Null => {}
+            Re::Alt(xs) => all.extend(xs.iter().cloned()),
+            x => all.push(x),
+        }
+    }
+    all.sort();
+    all.dedup();
+    match all.len() {
+        0 => Re::Null,
+        1 => all.pop().unwrap(),
+        _ => Re::Alt(all.into()),
+    }
+}
+
+fn star(a: Re) -> Re {
+    match a {
+        Re::Null | Re::Empty => Re::Empty,
+        Re::Star(x) => Re::Star(x),
+        a => Re::Star(Rc::new(a)),
+    }
+}
+
+impl Re {
+    /// Whether the regex matches the empty string
+    fn nullable(&self) -> bool {
+        match self {
+            Re::Empty | Re::Star(_) => true,
+            Re::Null | Re::Char(_) | Re::Set(..) | Re::Any => false,
+            Re::Cat(a, b) => a.nullable() && b.nullable(),
+            Re::Alt(xs) => xs.iter().any(Re::nullable),
+        }
+    }
+
+    /// The derivative of the regex by `c`
+    fn derive(&self, c: char) -> Re {
+        let accept = |x: bool| if x { Re::Empty } else { Re::Null };
+        match self {
+            Re::Null | Re::Empty => Re::Null,
+            Re::Char(x) => accept(*x == c),
+            Re::Set(s, negative) => accept(s.contains(c) != *negative),
+            Re::Any => accept(c != '\n'),
+            Re::Cat(a, b) => {
+                let left = cat(a.derive(c), b.as_ref().clone());
+                if a.nullable() {
+                    alt(left, b.derive(c))
+                } else {
+                    left
+                }
+            }
+            Re::Alt(xs) => xs.iter().fold(Re::Null, |acc, x| alt(acc, x.derive(c))),
+            Re::Star(a) => cat(a.derive(c), Re::Star(a.clone())),
+        }
+    }
+}
+
+/// Converts a parsed regex for matching by derivatives
+fn convert(r: &Regex) -> Result<Re, SpidiorError> {
+    match r {
+        Regex::Union(x) => {
+            let Union::O(left, right) = x.as_ref();
+            Ok(alt(convert(left)?, convert_simple(right)?))
+        }
+        Regex::Simple(x) => convert_simple(x),
+    }
+}
+
+fn convert_simple(s: &Simple) -> Result<Re, SpidiorError> {
+    match s {
+        Simple::Concatenation(x) => {
+            let Concatenation::O(left, right) = x.as_ref();
+            Ok(cat(convert_simple(left)?, convert_basic(right)?))
+        }
+        Simple::Basic(x) => convert_basic(x),
+    }
+}
+
+fn convert_basic(b: &Basic) -> Result<Re, SpidiorError> {
+    match b {
+        Basic::Star(x) => {
+            let ast::Star::O(e) = x.as_ref();
+            Ok(star(convert_elem(e)?))
+        }
+        Basic::Plus(x) => {
+            let ast::Plus::O(e) = x.as_ref();
+            let e = convert_elem(e)?;
+            Ok(cat(e.clone(), star(e)))
+        }
+        Basic::Elementary(e) => convert_elem(e),
+    }
+}
+
+fn convert_elem(e: &Elementary) -> Result<Re, SpidiorError> {
+    let unsupported = |what| Err(SpidiorError::parse(format!("The derivatives engine cannot match {}", what)));
+    match e {
+        Elementary::Group(g) => {
+            let ast::Group::O(r) = g.as_ref();
+            convert(r)
+        }
+        Elementary::Any(x) => match x.as_ref() {
+            ast::Any::O => Ok(Re::Any),
+            ast::Any::Cluster => unsupported("`.` under the `c` flag"),
+        },
+        Elementary::Eos(_) => unsupported("`$`"),
+        Elementary::Char(c) => match c.as_ref() {
+            ast::Char::Char(c) | ast::Char::Meta(c) => Ok(Re::Char(*c)),
+        },
+        Elementary::Set(s) => match s.as_ref() {
+            Set::Positive(x) => {
+                let ast::Positive::O(items) = x.as_ref();
+                Ok(Re::Set(set_chars(items.clone()).into(), false))
+            }
+            Set::Negative(x) => {
+                let ast::Negative::O(items) = x.as_ref();
+                Ok(Re::Set(set_chars(items.clone()).into(), true))
+            }
+            Set::QuerySet(_) => unsupported("query sets like `[[name=x]]`"),
+        },
+    }
+}
+
+/// Finds all matches of `regex` within `input` by derivatives, taking the
+/// longest match at each position and carrying on after it, as the NFA does,
+/// and stopping once matching goes over `budget`, which is checked before
+/// each position a match could start at, as `find_within` does
+///
+/// # Returns
+///
+/// A Result<Vec<Match>, SpidiorError>, where on success, it returns the
+/// matches, which have no groups. It will Err with a ParseError if the
+/// regex has a query set, a `$`, or a `.` under the `c` flag, or with a
+/// LimitError if matching goes over the budget.
+pub fn find(input: &str, regex: &Regex, budget: &Budget) -> Result<Vec<Match>, SpidiorError> {
+    let re = convert(regex)?;
+    let mut found = Vec::new();
+    let mut used = 0;
+    let mut at = 0;
+    while at < input.len() {
+        budget.check(used)?;
+        let mut r = re.clone();
+        let mut len = None;
+        for (i, c) in input[at..].char_indices() {
+            r = r.derive(c);
+            if r == Re::Null {
+                break;
+            }
+            if r.nullable() {
+                len = Some(i + c.len_utf8());
+            }
+        }
+        match len {
+            Some(len) => {
+                let m = Match::new(at, len, Vec::new());
+                used += Budget::held(&m);
+                found.push(m);
+                at += len;
+            }
+            None => at += input[at..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    Ok(locate(input, found))
+}
+
+/// Describes the first difference between the matches the NFA and
+/// derivatives found in `input`
+///
+/// # Returns
+///
+/// The description, like `3:5: nfa matched "ab", derivatives matched
+/// nothing`, or None if they found the same matches
+pub fn disagreement(input: &str, nfa: &[Match], derivatives: &[Match]) -> Option<String> {
+    let span = |m: &Match| (m.start(), m.len());
+    let first = (0..nfa.len().max(derivatives.len())).find(|i| nfa.get(*i).map(span) != derivatives.get(*i).map(span))?;
+    let (a, b) = (nfa.get(first), derivatives.get(first));
+    let start = a.into_iter().chain(b).map(Match::start).min()?;
+    let text = |m: Option<&Match>| match m {
+        Some(m) if m.start() == start => format!("{:?}", &input[m.start()..m.start() + m.len()]),
+        _ => "nothing".to_string(),
+    };
+    let (line, column) = LineIndex::new(input).position(start);
+    Some(format!("{}:{}: nfa matched {}, derivatives matched {}", line, column, text(a), text(b)))
+}
+
+#[test]
+fn test_derivatives() {
+    use crate::nfa::matcher;
+    use crate::regexparser;
+    let input = "bob dole joee ooooo aab abcabd\nxé[q]";
+    for pattern in ["bob|joe|e*", "(o*)o", "a*b", "(ab|a)(c|bd)", "[a-c]+", "[^\\ a-z]", "o+|e", ".", "d.l", "x(é|e)\\[", "O+E"] {
+        let regex = regexparser::parse(&format!("%s/{}//gi", pattern)).unwrap().find;
+        let derived = find(input, &regex, &Budget::new(None, None)).unwrap();
+        let found = matcher::find(input, regex);
+        assert_eq!(disagreement(input, &found, &derived), None, "`{}`", pattern);
+    }
+    let nfa = vec![Match::new(4, 3, Vec::new())];
+    assert_eq!(disagreement("bob dole", &nfa, &[]).unwrap(), "1:5: nfa matched \"dol\", derivatives matched nothing");
+    assert!(find("x", &regexparser::parse("%s/[[name=x]]//g").unwrap().find, &Budget::new(None, None)).is_err());
+    let regex = regexparser::parse("%s/a//g").unwrap().find;
+    let limited = find(&"a".repeat(100), &regex, &Budget::new(None, Some(1)));
+    assert!(matches!(limited, Err(SpidiorError::LimitError(_))), "{:?}", limited);
+    let late = find("a", &regex, &Budget::new(Some(std::time::Duration::ZERO), None));
+    assert!(matches!(late, Err(SpidiorError::LimitError(_))), "{:?}", late);
+    assert_eq!("differential".parse::<Engine>(), Ok(Engine::Differential));
+    assert!("dfa".parse::<Engine>().is_err());
+}
@@ -0,0 +1,484 @@
+use std::str::FromStr;
+
+use super::positions::PositionMapper;
+use crate::error::SpidiorError;
+use crate::languages::lines::LineIndex;
+
+/// The number of unchanged lines shown around each change in a diff
+const CONTEXT: usize = 3;
+
+/// A single edit to a piece of text, replacing the bytes from `start` up to
+/// `end` with `replacement`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edit {
+    /// The byte index the replaced text starts at
+    pub start: usize,
+    /// The byte index one past the end of the replaced text, which is
+    /// `start` for an insertion
+    pub end: usize,
+    /// The text put in place of the replaced text
+    pub replacement: String,
+    /// What made the edit, like the query it came from, which is empty if
+    /// that is not known
+    pub origin: String,
+}
+
+/// What to do when an edit overlaps one already in an EditSet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conflict {
+    /// Refuse the edit, failing with a report of the overlap
+    Error,
+    /// Keep the edit already in the set, dropping the new one
+    First,
+    /// Replace both edits with one covering them, whose replacement is
+    /// theirs joined in the order they start
+    Merge,
+}
+
+impl FromStr for Conflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Conflict::Error),
+            "first" => Ok(Conflict::First),
+            "merge" => Ok(Conflict::Merge),
+            _ => Err(format!("Unknown conflict policy `{}`, expected `error`, `first`, or `merge`", s)),
+        }
+    }
+}
+
+/// A set of edits to one piece of text, none of which overlap, which are
+/// applied together so that no edit has to account for the others shifting
+/// the text around it
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EditSet {
+    /// The edits, ordered by where they start
+    edits: Vec<Edit>,
+}
+
+impl EditSet {
+    /// Creates an empty EditSet
+    pub fn new() -> Self {
+        Self { edits: Vec::new() }
+    }
+
+    /// Adds an edit to the set
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The byte index the replaced text starts at
+    /// * `end` - The byte index one past the end of the replaced text
+    /// * `replacement` - The text to put in place of the replaced text
+    ///
+    /// # Returns
+    ///
+    /// A Result<(), SpidiorError>, which will Err if `end` is before
+    /// `start`, or if the edit overlaps one already in the set. Two
+    /// insertions at the same index overlap, as their order is ambiguous.
+    pub fn add(&mut self, start: usize, end: usize, replacement: String) -> Result<(), SpidiorError> {
+        if end < start {
+            return Err(SpidiorError::edit(format!("Edit ends at {} before it starts at {}", end, start)));
+        }
+        if let Some(x) = self.overlapping(start, end).first().map(|&i| &self.edits[i]) {
+            return Err(SpidiorError::edit(format!("Edit of {}..{} overlaps the edit of {}..{}", start, end, x.start, x.end)));
+        }
+        self.insert(Edit {
+            start,
+            end,
+            replacement,
+            origin: String::new(),
+        });
+        Ok(())
+    }
+
+    /// Records what made every edit in the set, like the query it came from
+    pub fn with_origin(mut self, origin: &str) -> Self {
+        for x in self.edits.iter_mut() {
+            x.origin = origin.to_string();
+        }
+        self
+    }
+
+    /// Re-indents the lines that each edit's replacement introduces to match
+    /// the line the edit starts on, keeping their indentation relative to
+    /// each other, so that a multi-line replacement lines up with the code
+    /// around it
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text the edits were made against
+    pub fn reindented(mut self, text: &str) -> Self {
+        for x in self.edits.iter_mut().filter(|x| x.replacement.contains('\n')) {
+            let line = text.get(..x.start).map_or(0, |t| t.rfind('\n').map_or(0, |i| i + 1));
+            let site = text.get(line..x.start).unwrap_or("");
+            let indent = &site[..site.len() - site.trim_start().len()];
+            x.replacement = reindent(&x.replacement, indent);
+        }
+        self
+    }
+
+    /// Inserts an edit that overlaps none in the set, keeping them ordered
+    fn insert(&mut self, edit: Edit) {
+        let i = self.edits.partition_point(|x| (x.start, x.end) <= (edit.start, edit.end));
+        self.edits.insert(i, edit);
+    }
+
+    /// Finds the indices of the edits that overlap the text from `start` up
+    /// to `end`, where two insertions at the same index overlap
+    fn overlapping(&self, start: usize, end: usize) -> Vec<usize> {
+        (0..self.edits.len())
+            .filter(|&i| {
+                let x = &self.edits[i];
+                (x.start < end && start < x.end) || (x.start == start && (x.start == x.end || start == end))
+            })
+            .collect()
+    }
+
+    /// Adds the edits of another set to this one, settling any that overlap
+    /// an edit already in this one according to a policy
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The edits to add, made against the same text as this set
+    /// * `policy` - What to do with an edit that overlaps one in this set
+    /// * `text` - The text the edits were made against, used in reports
+    /// * `path` - The path of the file `text` is from, used in reports
+    ///
+    /// # Returns
+    ///
+    /// A Result<Vec<String>, SpidiorError>, where on success, it returns a
+    /// report of each overlap that was settled. Identical edits do not
+    /// overlap, and are kept once. It will Err with the report of the first
+    /// overlap if `policy` is `Conflict::Error`.
+    pub fn extend(&mut self, other: EditSet, policy: Conflict, text: &str, path: &str) -> Result<Vec<String>, SpidiorError> {
+        let index = LineIndex::new(text);
+        let mut reports = Vec::new();
+        for edit in other.edits {
+            let found = self.overlapping(edit.start, edit.end);
+            if found.is_empty() {
+                self.insert(edit);
+                continue;
+            }
+            // Two queries making the same edit agree, rather than conflict
+            let same = |x: &Edit| (x.start, x.end, &x.replacement) == (edit.start, edit.end, &edit.replacement);
+            if found.iter().any(|&i| same(&self.edits[i])) {
+                continue;
+            }
+            let report = Self::report(text, &index, path, &self.edits[found[0]], &edit);
+            match policy {
+                Conflict::Error => return Err(SpidiorError::edit(report)),
+                Conflict::First => {}
+                Conflict::Merge => {
+                    // Every edit found is removed from the set, so earlier
+                    // indices stay valid while removing from the back
+                    let mut merged = vec![edit];
+                    for &i in found.iter().rev() {
+                        merged.push(self.edits.remove(i));
+                    }
+                    merged.sort_by_key(|x| (x.start, x.end));
+                    let mut origins: Vec<&str> = merged.iter().map(|x| x.origin.as_str()).filter(|x| !x.is_empty()).collect();
+                    origins.dedup();
+                    self.insert(Edit {
+                        start: merged[0].start,
+                        end: merged.iter().map(|x| x.end).max().unwrap_or(merged[0].end),
+                        replacement: merged.iter().map(|x| x.replacement.as_str()).collect(),
+                        origin: origins.join("; "),
+                    });
+                }
+            }
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    /// Describes an overlap between two edits, with the line it starts on
+    fn report(text: &str, index: &LineIndex, path: &str, first: &Edit, second: &Edit) -> String {
+        let start = first.start.min(second.start);
+        let (line, column) = index.position(start);
+        let context = text.get(..start).map_or(0, |x| x.rfind('\n').map_or(0, |i| i + 1));
+        let context = text.get(context..).and_then(|x| x.lines().next()).unwrap_or("");
+        let describe = |x: &Edit| format!("{}..{} ({:?} to {:?})", x.start, x.end, text.get(x.start..x.end).unwrap_or(""), x.replacement);
+        format!("{}:{}:{}: edit of {} overlaps edit of {}, in `{}`", path, line, column, describe(second), describe(first), context.trim())
+    }
+
+    /// The number of edits in the set
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+
+    /// Whether the set has no edits
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Iterates over the edits, ordered by where they start
+    pub fn iter(&self) -> impl Iterator<Item = &Edit> {
+        self.edits.iter()
+    }
+
+    /// Checks that every edit lies within a piece of text, on character
+    /// boundaries
+    fn check(&self, text: &str) -> Result<(), SpidiorError> {
+        match self.edits.iter().find(|x| !text.is_char_boundary(x.start) || !text.is_char_boundary(x.end)) {
+            Some(x) if x.end > text.len() => Err(SpidiorError::edit("Replacing more of the string than exists")),
+            Some(x) => Err(SpidiorError::edit(format!("Edit of {}..{} is not on character boundaries", x.start, x.end))),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies the edits to a piece of text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text the edits were made against
+    ///
+    /// # Returns
+    ///
+    /// A Result<String, SpidiorError>, where on success, it returns the
+    /// edited text. It will Err if an edit lies outside `text`.
+    pub fn apply(&self, text: &str) -> Result<String, SpidiorError> {
+        self.check(text)?;
+        Ok(Self::splice(text, 0, &self.edits))
+    }
+
+    /// Applies the edits to a piece of text, as `apply` does, along with a
+    /// PositionMapper to find where offsets in `text` end up
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text the edits were made against
+    ///
+    /// # Returns
+    ///
+    /// A Result<(String, PositionMapper), SpidiorError>, where on success,
+    /// it returns the edited text and the mapper between offsets in `text`
+    /// and in the edited text. It will Err if an edit lies outside `text`.
+    pub fn apply_mapped(&self, text: &str) -> Result<(String, PositionMapper), SpidiorError> {
+        Ok((self.apply(text)?, PositionMapper::new(self)))
+    }
+
+    /// Applies edits to the part of `text` from `start` onwards, which must
+    /// hold every edit
+    fn splice(text: &str, start: usize, edits: &[Edit]) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut last = start;
+        for x in edits {
+            out += &text[last..x.start];
+            out += &x.replacement;
+            last = x.end;
+        }
+        out += &text[last..];
+        out
+    }
+
+    /// Renders the edits as a unified diff, as `diff -u` would
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text the edits were made against
+    /// * `path` - The path of the file `text` is from, used in the headers
+    ///
+    /// # Returns
+    ///
+    /// A Result<String, SpidiorError>, where on success, it returns the
+    /// diff, which is empty if there are no edits. It will Err if an edit
+    /// lies outside `text`.
+    pub fn diff(&self, text: &str, path: &str) -> Result<String, SpidiorError> {
+        self.check(text)?;
+        if self.edits.is_empty() {
+            return Ok(String::new());
+        }
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1).filter(|x| *x < text.len()))
+            .collect();
+        let line_of = |offset: usize| starts.partition_point(|x| *x <= offset) - 1;
+        let line_start = |line: usize| starts.get(line).copied().unwrap_or(text.len());
+        // Group the edits into blocks of whole lines, where edits on the
+        // same or neighbouring lines are in the same block
+        let mut blocks: Vec<(usize, usize, &[Edit])> = Vec::new();
+        let mut first = 0;
+        for (i, x) in self.edits.iter().enumerate() {
+            let (from, to) = (line_of(x.start), line_of(x.end.max(x.start + 1) - 1).max(line_of(x.start)));
+            match blocks.last_mut() {
+                Some(block) if from <= block.1 + 1 => {
+                    block.1 = block.1.max(to);
+                    block.2 = &self.edits[first..=i];
+                }
+                _ => {
+                    first = i;
+                    blocks.push((from, to, &self.edits[i..=i]));
+                }
+            }
+        }
+        let lines = |from: usize, to: usize| text[line_start(from)..line_start(to + 1)].split_inclusive('\n').map(String::from).collect::<Vec<_>>();
+        let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+        // How many more lines the new text has than the old, before the hunk
+        let mut shift: isize = 0;
+        let mut i = 0;
+        while i < blocks.len() {
+            // Blocks close enough to share context are in the same hunk
+            let mut j = i;
+            while j + 1 < blocks.len() && blocks[j + 1].0 <= blocks[j].1 + 2 * CONTEXT + 1 {
+                j += 1;
+            }
+            let from = blocks[i].0.saturating_sub(CONTEXT);
+            let to = (blocks[j].1 + CONTEXT).min(starts.len() - 1);
+            let mut body = Vec::new();
+            let (mut old, mut new) = (0, 0);
+            let mut line = from;
+            for block in &blocks[i..=j] {
+                if block.0 > line {
+                    body.extend(lines(line, block.0 - 1).into_iter().map(|x| format!(" {}", x)));
+                }
+                let removed = lines(block.0, block.1);
+                let added: Vec<String> = Self::splice(&text[..line_start(block.1 + 1)], line_start(block.0), block.2)
+                    .split_inclusive('\n')
+                    .map(String::from)
+                    .collect();
+                old += block.0 - line + removed.len();
+                new += block.0 - line + added.len();
+                body.extend(removed.into_iter().map(|x| format!("-{}", x)));
+                body.extend(added.into_iter().map(|x| format!("+{}", x)));
+                line = block.1 + 1;
+            }
+            if line <= to {
+                old += to + 1 - line;
+                new += to + 1 - line;
+                body.extend(lines(line, to).into_iter().map(|x| format!(" {}", x)));
+            }
+            let new_from = from as isize + shift;
+            out += &format!("@@ -{},{} +{},{} @@\n", Self::hunk_start(from, old), old, Self::hunk_start(new_from as usize, new), new);
+            for x in body {
+                out += &x;
+                if !x.ends_with('\n') {
+                    out += "\n\\ No newline at end of file\n";
+                }
+            }
+            shift += new as isize - old as isize;
+            i = j + 1;
+        }
+        Ok(out)
+    }
+
+    /// The line a hunk of `len` lines starting at the 0-based `line` is said
+    /// to start on, which `diff` numbers from 1, or gives as the line before
+    /// an empty hunk
+    fn hunk_start(line: usize, len: usize) -> usize {
+        if len == 0 {
+            line
+        } else {
+            line + 1
+        }
+    }
+
+    /// Renders the edits as a journal entry, with enough to undo them
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text the edits were made against
+    /// * `path` - The path of the file `text` is from
+    ///
+    /// # Returns
+    ///
+    /// A Result<String, SpidiorError>, where on success, it returns a line
+    /// for each edit holding the path, the start and end of the replaced
+    /// text, the start and end of its replacement in the edited text, and
+    /// the replaced and replacement text quoted as Rust strings, separated
+    /// by tabs. It will Err if an edit lies outside `text`.
+    pub fn journal(&self, text: &str, path: &str) -> Result<String, SpidiorError> {
+        self.check(text)?;
+        let mapper = PositionMapper::new(self);
+        Ok(self
+            .edits
+            .iter()
+            .map(|x| {
+                let (start, end) = (mapper.map(x.start), mapper.map(x.start) + x.replacement.len());
+                format!("{}\t{}\t{}\t{}\t{}\t{:?}\t{:?}\n", path, x.start, x.end, start, end, &text[x.start..x.end], x.replacement)
+            })
+            .collect())
+    }
+}
+
+/// Indents every line of `text` after the first by `indent`, after removing
+/// the indentation those lines have in common. Blank lines are left empty.
+fn reindent(text: &str, indent: &str) -> String {
+    let mut lines = text.split('\n');
+    let first = lines.next().unwrap_or_default();
+    let rest: Vec<&str> = lines.collect();
+    let common = rest
+        .iter()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| x.len() - x.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let mut out = first.to_string();
+    for x in rest {
+        out.push('\n');
+        if !x.trim().is_empty() {
+            out += indent;
+            out += x.get(common..).unwrap_or_else(|| x.trim_start());
+        }
+    }
+    out
+}
+
+#[test]
+fn test_edit_set() {
+    let mut edits = EditSet::new();
+    edits.add(6, 11, "there".into()).unwrap();
+    edits.add(0, 5, "bye".into()).unwrap();
+    edits.add(12, 12, "!".into()).unwrap();
+    assert!(edits.add(4, 7, "x".into()).is_err());
+    assert!(edits.add(12, 12, "?".into()).is_err());
+    assert!(edits.add(3, 2, "x".into()).is_err());
+    assert_eq!(edits.len(), 3);
+    assert_eq!(edits.apply("hello world.").unwrap(), "bye there.!");
+    assert!(edits.apply("hello").is_err());
+    assert_eq!(edits.journal("hello world.", "a.txt").unwrap().lines().next(), Some("a.txt\t0\t5\t0\t3\t\"hello\"\t\"bye\""));
+}
+
+#[test]
+fn test_diff() {
+    let text = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\nn";
+    let mut edits = EditSet::new();
+    edits.add(2, 3, "B".into()).unwrap();
+    edits.add(4, 6, "".into()).unwrap();
+    edits.add(26, 27, "N\nO".into()).unwrap();
+    assert_eq!(
+        edits.diff(text, "x").unwrap(),
+        "--- a/x\n+++ b/x\n@@ -1,6 +1,5 @@\n a\n-b\n-c\n+B\n d\n e\n f\n@@ -11,4 +10,5 @@\n k\n l\n m\n-n\n\\ No newline at end of file\n+N\n+O\n\\ No newline at end of file\n"
+    );
+}
+
+#[test]
+fn test_extend() {
+    let text = "int a = b;\n";
+    let mut first = EditSet::new();
+    first.add(4, 5, "x".into()).unwrap();
+    let mut second = EditSet::new();
+    second.add(4, 9, "y = z".into()).unwrap();
+    second.add(0, 3, "long".into()).unwrap();
+    let err = first.clone().extend(second.clone(), Conflict::Error, text, "a.c").unwrap_err();
+    assert_eq!(err.to_string(), "a.c:1:5: edit of 4..9 (\"a = b\" to \"y = z\") overlaps edit of 4..5 (\"a\" to \"x\"), in `int a = b;`");
+    let mut kept = first.clone();
+    assert_eq!(kept.extend(second.clone(), Conflict::First, text, "a.c").unwrap().len(), 1);
+    assert_eq!(kept.apply(text).unwrap(), "long x = b;\n");
+    let mut merged = first.clone();
+    merged.extend(second, Conflict::Merge, text, "a.c").unwrap();
+    assert_eq!(merged.apply(text).unwrap(), "long xy = z;\n");
+    // Identical edits are kept once, rather than conflicting
+    let mut same = first.clone();
+    assert!(same.extend(first.clone(), Conflict::Error, text, "a.c").unwrap().is_empty());
+    assert_eq!(same.apply(text).unwrap(), "int x = b;\n");
+}
+
+#[test]
+fn test_reindented() {
+    let text = "fn f() {\n        go();\n}\n";
+    let mut edits = EditSet::new();
+    edits.add(17, 22, "if ok {\n  go();\n\n}".into()).unwrap();
+    assert_eq!(edits.reindented(text).apply(text).unwrap(), "fn f() {\n        if ok {\n          go();\n\n        }\n}\n");
+}
@@ -0,0 +1,171 @@
+//! Provides HTML reports of the edits a run makes, for `--format html`, so
+//! that a large rewrite can be reviewed in a browser: an `index.html` that
+//! sums up the edits to each file and links to a page for it, on which the
+//! whole file is shown with each replaced span and its replacement marked
+//!
+//! The files are shown as plain text, without any highlighting of their
+//! syntax, so every language looks the same.
+
+use std::fs;
+use std::path::Path;
+
+use super::editset::EditSet;
+use crate::error::SpidiorError;
+use crate::languages::lines::LineIndex;
+
+/// The styles of every page, which mark replaced spans in red and struck
+/// through, and their replacements in green
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}pre{background:#f6f8fa;padding:1em;overflow-x:auto}\
+table{border-collapse:collapse}td,th{padding:.2em 1em;text-align:left;border-bottom:1px solid #ddd}\
+del{background:#ffd7d5;color:#82071e}ins{background:#ccffd8;color:#055d20;text-decoration:none}";
+
+/// Escapes the characters of some text that HTML gives a meaning to
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out += "&amp;",
+            '<' => out += "&lt;",
+            '>' => out += "&gt;",
+            '"' => out += "&quot;",
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Wraps the body of a page in the rest of an HTML document
+fn document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        STYLE,
+        body
+    )
+}
+
+/// A page of a report, for one file
+#[derive(Debug, Clone, PartialEq)]
+struct Page {
+    /// The path of the file
+    path: String,
+    /// The name of the page, within the report's directory
+    name: String,
+    /// How many edits are made to the file
+    edits: usize,
+    /// The page
+    html: String,
+}
+
+/// A report of the edits a run makes, with a page for each file it edits
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HtmlReport {
+    pages: Vec<Page>,
+}
+
+impl HtmlReport {
+    /// Creates a report of no files
+    pub fn new() -> Self {
+        Self { pages: Vec::new() }
+    }
+
+    /// Adds a page for the edits to a file, unless there are none
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, as it should be shown
+    /// * `text` - The contents of the file
+    /// * `edits` - The edits to the file
+    pub fn add(&mut self, path: &str, text: &str, edits: &EditSet) {
+        if edits.is_empty() {
+            return;
+        }
+        let index = LineIndex::new(text);
+        let mut list = String::from("<table>\n<tr><th>Line:Col</th><th>Replaced</th><th>With</th><th>Query</th></tr>\n");
+        let mut file = String::new();
+        let mut at = 0;
+        for (i, x) in edits.iter().enumerate() {
+            let (line, column) = index.position(x.start);
+            list += &format!(
+                "<tr><td><a href=\"#edit-{}\">{}:{}</a></td><td><code>{}</code></td><td><code>{}</code></td><td><code>{}</code></td></tr>\n",
+                i + 1,
+                line,
+                column,
+                escape(&text[x.start..x.end]),
+                escape(&x.replacement),
+                escape(&x.origin)
+            );
+            file += &escape(&text[at..x.start]);
+            file += &format!("<span id=\"edit-{}\"><del>{}</del><ins>{}</ins></span>", i + 1, escape(&text[x.start..x.end]), escape(&x.replacement));
+            at = x.end;
+        }
+        list += "</table>\n";
+        file += &escape(&text[at..]);
+        let edits = edits.len();
+        let body = format!(
+            "<p><a href=\"index.html\">All files</a></p>\n<h1>{}</h1>\n<p>{} edit{}</p>\n{}<pre>{}</pre>\n",
+            escape(path),
+            edits,
+            if edits == 1 { "" } else { "s" },
+            list,
+            file
+        );
+        let slug: String = path.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect();
+        self.pages.push(Page {
+            path: path.to_string(),
+            name: format!("{}-{}.html", self.pages.len() + 1, slug),
+            edits,
+            html: document(path, &body),
+        });
+    }
+
+    /// The index page, with a row for each file, linking to its page, with
+    /// how many edits are made to it, and the total
+    pub fn index(&self) -> String {
+        let total: usize = self.pages.iter().map(|x| x.edits).sum();
+        let mut body = format!(
+            "<h1>spidior report</h1>\n<p>{} edit{} to {} file{}</p>\n<table>\n<tr><th>File</th><th>Edits</th></tr>\n",
+            total,
+            if total == 1 { "" } else { "s" },
+            self.pages.len(),
+            if self.pages.len() == 1 { "" } else { "s" }
+        );
+        for x in &self.pages {
+            body += &format!("<tr><td><a href=\"{}\">{}</a></td><td>{}</td></tr>\n", x.name, escape(&x.path), x.edits);
+        }
+        body += "</table>\n";
+        document("spidior report", &body)
+    }
+
+    /// Writes the report to a directory, creating it if it does not exist,
+    /// as `index.html` and a page for each file
+    pub fn write(&self, dir: &Path) -> Result<(), SpidiorError> {
+        let write = |name: &str, html: &str| {
+            let path = dir.join(name);
+            fs::write(&path, html).map_err(|e| SpidiorError::io(format!("Could not write {}", path.display()), e))
+        };
+        fs::create_dir_all(dir).map_err(|e| SpidiorError::io(format!("Could not create {}", dir.display()), e))?;
+        for x in &self.pages {
+            write(&x.name, &x.html)?;
+        }
+        write("index.html", &self.index())
+    }
+}
+
+#[test]
+fn test_html_report() {
+    let text = "int a = 1;\nif (a < 2) { a++; }\n";
+    let mut edits = EditSet::new();
+    edits.add(4, 5, "b".to_string()).unwrap();
+    edits.add(15, 16, "b".to_string()).unwrap();
+    let mut report = HtmlReport::new();
+    report.add("src/a&b.c", text, &edits.with_origin("%s/a/b/g"));
+    report.add("src/c.c", text, &EditSet::new());
+    assert_eq!(report.pages.len(), 1);
+    let page = &report.pages[0];
+    assert_eq!(page.name, "1-src_a_b.c.html");
+    assert!(page.html.contains("<pre>int <span id=\"edit-1\"><del>a</del><ins>b</ins></span> = 1;\nif (<span id=\"edit-2\"><del>a</del><ins>b</ins></span> &lt; 2) { a++; }\n</pre>"));
+    assert!(page.html.contains("<a href=\"#edit-2\">2:5</a>") && page.html.contains("<h1>src/a&amp;b.c</h1>"));
+    let index = report.index();
+    assert!(index.contains("<p>2 edits to 1 file</p>") && index.contains("<a href=\"1-src_a_b.c.html\">src/a&amp;b.c</a>"));
+}
@@ -0,0 +1,216 @@
+//! Provides edits to the imports of a file, which work on the imports the
+//! language layer finds rather than on lines that look like imports, so that
+//! a rewrite can bring in what its replacements need
+
+use std::collections::BTreeMap;
+
+use super::editset::EditSet;
+use crate::error::SpidiorError;
+use crate::languages::{parsing::Import, Language};
+
+/// The starts of the lines that come before any imports, which are kept at
+/// the top of a file
+const HEADERS: [&str; 4] = ["#!", "package ", "//!", "#!["];
+
+/// Finds the line `i` is on, as the index of its start and the index one
+/// past its newline, or the end of `text` if it has none
+fn line(text: &str, i: usize) -> (usize, usize) {
+    let start = text[..i].rfind('\n').map_or(0, |x| x + 1);
+    let end = text[i..].find('\n').map_or(text.len(), |x| i + x + 1);
+    (start, end)
+}
+
+/// Finds where imports go in a file that has none written like them, which
+/// is after any `#!` line, `package` declaration, or inner attributes and
+/// doc comments at its top
+fn top(text: &str) -> usize {
+    let mut i = 0;
+    while i < text.len() && HEADERS.iter().any(|x| text[i..].starts_with(x)) {
+        i = line(text, i).1;
+    }
+    i
+}
+
+/// Works out the edits that import each of `paths` into a file that does
+/// not already import it
+///
+/// Each import is written by the file's language, and put among the import
+/// lines of the file written the same way, like the `#include`s of a C file,
+/// where it sorts among them, or at the top of the file, after any `package`
+/// declaration and with a blank line around it, if there are none.
+///
+/// # Arguments
+///
+/// * `text` - The file
+/// * `imports` - The imports `lang` found in `text`
+/// * `lang` - The language of `text`
+/// * `paths` - The paths to import, like `java.util.List` or `<stdio.h>`,
+///   which may repeat
+///
+/// # Returns
+///
+/// A Result<EditSet, SpidiorError> of the insertions, which is empty if the
+/// language cannot import anything
+pub fn add(text: &str, imports: &[Import], lang: &dyn Language, paths: &[String]) -> Result<EditSet, SpidiorError> {
+    let mut statements: Vec<String> = paths
+        .iter()
+        .filter(|x| !imports.iter().any(|y| y.path == x.trim_matches(['<', '>', '"'])))
+        .filter_map(|x| lang.import_statement(x))
+        .collect();
+    statements.sort();
+    statements.dedup();
+    // The statements that go at each index, and whether that index is the
+    // top of a file with no imports like them
+    let mut at: BTreeMap<(usize, bool), Vec<String>> = BTreeMap::new();
+    for statement in statements {
+        let keyword = statement.split_whitespace().next();
+        let mut lines: Vec<(usize, usize)> = imports
+            .iter()
+            .map(|x| line(text, x.start))
+            .filter(|&(start, end)| text[start..end].split_whitespace().next() == keyword)
+            .collect();
+        lines.dedup();
+        let key = match lines.iter().find(|&&(start, end)| text[start..end].trim() > statement.as_str()) {
+            Some(&(start, _)) => (start, false),
+            None => lines.last().map_or((top(text), true), |&(_, end)| (end, false)),
+        };
+        at.entry(key).or_default().push(statement);
+    }
+    let mut edits = EditSet::new();
+    for ((i, fresh), statements) in at {
+        let mut insert: String = statements.iter().map(|x| format!("{}\n", x)).collect();
+        if fresh && i > 0 {
+            insert.insert(0, '\n');
+        }
+        if fresh && !text[i..].starts_with('\n') && i < text.len() {
+            insert.push('\n');
+        }
+        if i > 0 && !text[..i].ends_with('\n') {
+            insert.insert(0, '\n');
+            insert.pop();
+        }
+        edits.add(i, i, insert)?;
+    }
+    Ok(edits)
+}
+
+/// Whether `path` matches `pattern`, in which `*` matches any run of
+/// characters, so `com.old.*` matches `com.old.Client` and `com.old.a.B`
+fn glob(pattern: &str, path: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == path,
+        Some((head, rest)) => path.strip_prefix(head).is_some_and(|path| (0..=path.len()).filter(|&i| path.is_char_boundary(i)).any(|i| glob(rest, &path[i..]))),
+    }
+}
+
+/// Works out the edits that tidy the import statements of a file, removing
+/// those of paths that match a pattern or that an earlier statement already
+/// imports, and sorting them
+///
+/// A statement that brings in several names, like `use a::{b, c};`, is only
+/// removed if all of them are. A statement on lines of its own is removed
+/// along with them, and statements on consecutive lines of their own are
+/// sorted among each other, by their text.
+///
+/// # Arguments
+///
+/// * `text` - The file
+/// * `imports` - The imports the language of `text` found in it
+/// * `remove` - The patterns of the paths to remove, where `*` matches any
+///   run of characters, like `com.old.*`
+/// * `dedupe` - Whether to remove statements that import only what earlier
+///   statements do
+/// * `sort` - Whether to sort the statements
+///
+/// # Returns
+///
+/// A Result<EditSet, SpidiorError> of the edits, which is empty if the
+/// imports are already tidy
+pub fn tidy(text: &str, imports: &[Import], remove: &[String], dedupe: bool, sort: bool) -> Result<EditSet, SpidiorError> {
+    // Each statement, as its span and the imports it holds
+    let mut statements: Vec<((usize, usize), Vec<&Import>)> = Vec::new();
+    for x in imports {
+        match statements.iter_mut().find(|(span, _)| *span == (x.start, x.end)) {
+            Some((_, held)) => held.push(x),
+            None => statements.push(((x.start, x.end), vec![x])),
+        }
+    }
+    statements.sort_by_key(|(span, _)| *span);
+    let mut edits = EditSet::new();
+    let mut seen: Vec<(&str, Option<&str>)> = Vec::new();
+    // Runs of statements on consecutive lines of their own, as the span of
+    // their lines and the lines of the statements in them that are kept
+    let mut runs: Vec<((usize, usize), Vec<&str>)> = Vec::new();
+    for ((start, end), held) in &statements {
+        let removed = held.iter().all(|x| remove.iter().any(|p| glob(p, &x.path)));
+        let repeated = dedupe && held.iter().all(|x| seen.contains(&(x.path.as_str(), x.alias.as_deref())));
+        seen.extend(held.iter().map(|x| (x.path.as_str(), x.alias.as_deref())));
+        let kept = !removed && !repeated;
+        let first = line(text, *start).0;
+        let last = line(text, end.saturating_sub(1).max(*start)).1;
+        if !text[first..*start].trim().is_empty() || !text[*end..last].trim().is_empty() {
+            // A statement sharing its lines with other code is left in place
+            if !kept {
+                edits.add(*start, *end, String::new())?;
+            }
+            continue;
+        }
+        let lines = if kept { vec![&text[first..last]] } else { Vec::new() };
+        match runs.last_mut() {
+            Some(((_, x), held)) if *x == first => {
+                *x = last;
+                held.extend(lines);
+            }
+            _ => runs.push(((first, last), lines)),
+        }
+    }
+    for ((start, end), mut lines) in runs {
+        if sort {
+            lines.sort_by_key(|x| x.trim());
+        }
+        let mut replacement: String = lines.iter().map(|x| format!("{}\n", x.trim_end_matches('\n'))).collect();
+        if !text[..end].ends_with('\n') {
+            replacement.pop();
+        }
+        if replacement != text[start..end] {
+            edits.add(start, end, replacement)?;
+        }
+    }
+    Ok(edits)
+}
+
+#[test]
+fn test_add() -> Result<(), SpidiorError> {
+    use crate::languages::{clike::Clike, go::Go};
+    let add_to = |text: &str, lang: &dyn Language, paths: &[&str]| -> Result<String, SpidiorError> {
+        let paths: Vec<String> = paths.iter().map(|x| x.to_string()).collect();
+        add(text, &lang.read_imports(text), lang, &paths)?.apply(text)
+    };
+    // Sorted among the imports already there, once, leaving out those there
+    let text = "package a;\n\nimport a.b.C;\nimport a.d.E;\n\nclass X {}\n";
+    assert_eq!(add_to(text, &Clike {}, &["a.c.D", "a.a.B", "a.c.D", "a.b.C"])?, "package a;\n\nimport a.a.B;\nimport a.b.C;\nimport a.c.D;\nimport a.d.E;\n\nclass X {}\n");
+    assert_eq!(add_to("#include <stdio.h>", &Clike {}, &["<stdlib.h>", "<stdio.h>"])?, "#include <stdio.h>\n#include <stdlib.h>");
+    // At the top, after the package, if there are no imports
+    assert_eq!(add_to("package main\n\nfunc main() {}\n", &Go {}, &["fmt"])?, "package main\n\nimport \"fmt\"\n\nfunc main() {}\n");
+    assert_eq!(add_to("int x;\n", &Clike {}, &["<stdio.h>"])?, "#include <stdio.h>\n\nint x;\n");
+    Ok(())
+}
+
+#[test]
+fn test_tidy() -> Result<(), SpidiorError> {
+    use crate::languages::{clike::Clike, rust::Rust};
+    let tidy_in = |text: &str, lang: &dyn Language, remove: &[&str], dedupe: bool, sort: bool| -> Result<String, SpidiorError> {
+        let remove: Vec<String> = remove.iter().map(|x| x.to_string()).collect();
+        tidy(text, &lang.read_imports(text), &remove, dedupe, sort)?.apply(text)
+    };
+    let text = "import com.old.Client;\nimport java.util.List;\nimport com.old.a.B;\nimport java.util.List;\nimport a.Z;\n\nclass A {}\n";
+    assert_eq!(tidy_in(text, &Clike {}, &["com.old.*"], false, false)?, "import java.util.List;\nimport java.util.List;\nimport a.Z;\n\nclass A {}\n");
+    assert_eq!(tidy_in(text, &Clike {}, &[], true, true)?, "import a.Z;\nimport com.old.Client;\nimport com.old.a.B;\nimport java.util.List;\n\nclass A {}\n");
+    assert_eq!(tidy_in(text, &Clike {}, &[], false, false)?, text);
+    // A group is removed only if everything in it is, and a statement
+    // sharing its line is removed on its own
+    let text = "use old::{a, b};\nuse old::c; fn f() {}";
+    assert_eq!(tidy_in(text, &Rust {}, &["old::a", "old::c"], false, false)?, "use old::{a, b};\n fn f() {}");
+    assert!(glob("*.h", "stdio.h") && glob("a*b*c", "abbc") && !glob("a.*", "b.a"));
+    Ok(())
+}
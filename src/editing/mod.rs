@@ -1 +1,8 @@
-pub mod textbuffer;
\ No newline at end of file
+pub mod editset;
+pub mod html;
+pub mod imports;
+pub mod plan;
+pub mod positions;
+pub mod report;
+pub mod textbuffer;
+pub mod transaction;
@@ -0,0 +1,174 @@
+//! Provides edit plans, which record every edit a run would make as JSON,
+//! so that the edits can be reviewed before they are applied, or applied
+//! elsewhere
+
+use std::fs;
+use std::path::Path;
+
+use super::editset::EditSet;
+use crate::error::SpidiorError;
+use crate::json::{self, Value};
+
+/// The version of the plan format, which is bumped whenever it changes
+const VERSION: i64 = 1;
+
+/// An edit within a plan, which holds the text it replaces so that it is
+/// only applied to the text it was planned against
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedEdit {
+    /// The path of the file to edit
+    pub file: String,
+    /// The byte index the replaced text starts at
+    pub start: usize,
+    /// The byte index one past the end of the replaced text
+    pub end: usize,
+    /// The replaced text
+    pub old: String,
+    /// The text put in place of the replaced text
+    pub new: String,
+    /// The query or rewrite the edit came from
+    pub query: String,
+}
+
+/// Every edit a run would make, across every file it would change
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Plan {
+    /// The edits, grouped by file in the order the files were planned
+    pub edits: Vec<PlannedEdit>,
+}
+
+impl Plan {
+    /// Creates an empty Plan
+    pub fn new() -> Self {
+        Self { edits: Vec::new() }
+    }
+
+    /// Adds the edits to a file to the plan
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The path of the file
+    /// * `text` - The contents of the file the edits were made against
+    /// * `edits` - The edits to the file
+    pub fn add(&mut self, file: &str, text: &str, edits: &EditSet) {
+        self.edits.extend(edits.iter().map(|x| PlannedEdit {
+            file: file.to_string(),
+            start: x.start,
+            end: x.end,
+            old: text.get(x.start..x.end).unwrap_or_default().to_string(),
+            new: x.replacement.clone(),
+            query: x.origin.clone(),
+        }));
+    }
+
+    /// The files the plan edits, in the order they were planned
+    pub fn files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self.edits.iter().map(|x| x.file.as_str()).collect();
+        files.dedup();
+        files
+    }
+
+    /// Finds the edits the plan makes to a file, checking that the file has
+    /// not changed since they were planned
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The path of the file, as the plan has it
+    /// * `text` - The current contents of the file
+    ///
+    /// # Returns
+    ///
+    /// A Result<EditSet, SpidiorError>, which will Err if any edit's
+    /// replaced text is not in `text` where the plan says it is
+    pub fn edits_for(&self, file: &str, text: &str) -> Result<EditSet, SpidiorError> {
+        let mut edits = EditSet::new();
+        for x in self.edits.iter().filter(|x| x.file == file) {
+            if text.get(x.start..x.end) != Some(x.old.as_str()) {
+                return Err(SpidiorError::edit(format!("{} has changed since the plan was made: expected {:?} at {}..{}", file, x.old, x.start, x.end)));
+            }
+            let mut one = EditSet::new();
+            one.add(x.start, x.end, x.new.clone())?;
+            edits.extend(one.with_origin(&x.query), super::editset::Conflict::Error, text, file)?;
+        }
+        Ok(edits)
+    }
+
+    /// Writes the plan as JSON, with an edit on each line so that plans diff
+    /// well
+    pub fn to_json(&self) -> String {
+        let mut out = format!("{{\n  \"version\": {},\n  \"edits\": [", VERSION);
+        for (i, x) in self.edits.iter().enumerate() {
+            let edit = Value::Object(vec![
+                ("file".into(), Value::String(x.file.clone())),
+                ("start".into(), Value::Number(x.start as i64)),
+                ("end".into(), Value::Number(x.end as i64)),
+                ("old".into(), Value::String(x.old.clone())),
+                ("new".into(), Value::String(x.new.clone())),
+                ("query".into(), Value::String(x.query.clone())),
+            ]);
+            out += if i == 0 { "\n    " } else { ",\n    " };
+            out += &edit.to_string();
+        }
+        out += if self.edits.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" };
+        out
+    }
+
+    /// Reads a plan written by `to_json`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the plan
+    ///
+    /// # Returns
+    ///
+    /// The Plan, or a message describing why `text` is not one
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value = json::parse(text)?;
+        match value.get("version") {
+            Some(Value::Number(VERSION)) => {}
+            _ => return Err(format!("expected a plan of version {}", VERSION)),
+        }
+        let items = value.get("edits").and_then(|x| x.as_array()).ok_or("expected an `edits` array")?;
+        let mut plan = Self::new();
+        for (i, item) in items.iter().enumerate() {
+            let string = |key: &str| item.get(key).and_then(|x| x.as_str()).map(String::from);
+            let number = |key: &str| item.get(key).and_then(|x| x.as_usize());
+            let edit = (|| {
+                Some(PlannedEdit {
+                    file: string("file")?,
+                    start: number("start")?,
+                    end: number("end")?,
+                    old: string("old")?,
+                    new: string("new")?,
+                    query: string("query").unwrap_or_default(),
+                })
+            })();
+            plan.edits.push(edit.ok_or_else(|| format!("edit {}: expected `file`, `start`, `end`, `old`, and `new`", i))?);
+        }
+        Ok(plan)
+    }
+
+    /// Reads the plan at `path`
+    pub fn load(path: &Path) -> Result<Self, SpidiorError> {
+        let text = fs::read_to_string(path).map_err(|e| SpidiorError::io(format!("Could not read {}", path.display()), e))?;
+        Self::parse(&text).map_err(|x| SpidiorError::parse(format!("{}: {}", path.display(), x)))
+    }
+}
+
+#[test]
+fn test_plan() {
+    let text = "int a = a;\n";
+    let mut edits = EditSet::new();
+    edits.add(4, 5, "b".into()).unwrap();
+    edits.add(8, 9, "b".into()).unwrap();
+    let mut plan = Plan::new();
+    plan.add("x.c", text, &edits.with_origin("%s/a/b/g"));
+    let json = plan.to_json();
+    assert_eq!(json.lines().nth(3), Some("    {\"file\": \"x.c\", \"start\": 4, \"end\": 5, \"old\": \"a\", \"new\": \"b\", \"query\": \"%s/a/b/g\"},"));
+    let read = Plan::parse(&json).unwrap();
+    assert_eq!(read, plan);
+    assert_eq!(read.files(), vec!["x.c"]);
+    assert_eq!(read.edits_for("x.c", text).unwrap().apply(text).unwrap(), "int b = b;\n");
+    assert!(read.edits_for("x.c", "int c = a;\n").is_err());
+    assert_eq!(Plan::parse(&Plan::new().to_json()).unwrap(), Plan::new());
+}
@@ -0,0 +1,114 @@
+//! Provides the translation of positions across applied edits, so that an
+//! offset found before the edits can be found again after them, and back
+
+use super::editset::{Edit, EditSet};
+
+/// Where an edit lies in the text before and after it was applied
+#[derive(Debug, Clone, PartialEq)]
+struct Mapping {
+    /// The byte range the edit replaced, in the text before
+    old: (usize, usize),
+    /// The byte range of its replacement, in the text after
+    new: (usize, usize),
+}
+
+/// Maps byte offsets in a piece of text to where they are once an EditSet
+/// has been applied to it, and back again
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionMapper {
+    /// The edits, ordered by where they start
+    mappings: Vec<Mapping>,
+}
+
+impl PositionMapper {
+    /// Creates a PositionMapper for the edits of an EditSet
+    ///
+    /// # Arguments
+    ///
+    /// * `edits` - The edits, which do not need to have been applied yet
+    pub fn new(edits: &EditSet) -> Self {
+        let mut shift: isize = 0;
+        let mappings = edits
+            .iter()
+            .map(|x: &Edit| {
+                let start = (x.start as isize + shift) as usize;
+                shift += x.replacement.len() as isize - (x.end - x.start) as isize;
+                Mapping {
+                    old: (x.start, x.end),
+                    new: (start, start + x.replacement.len()),
+                }
+            })
+            .collect();
+        Self { mappings }
+    }
+
+    /// Translates an offset from one side of the edits to the other
+    fn translate(&self, offset: usize, from: fn(&Mapping) -> (usize, usize), to: fn(&Mapping) -> (usize, usize)) -> usize {
+        let i = self.mappings.partition_point(|x| from(x).0 < offset);
+        match i.checked_sub(1).map(|j| &self.mappings[j]) {
+            None => offset,
+            // Within the replaced text, which no longer exists
+            Some(x) if offset < from(x).1 => to(x).1,
+            Some(x) => offset - from(x).1 + to(x).1,
+        }
+    }
+
+    /// Finds where an offset in the text before the edits is after them
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The byte offset, in the text before the edits
+    ///
+    /// # Returns
+    ///
+    /// The byte offset in the text after the edits. An offset at the start
+    /// of an edit maps to the start of its replacement, and one within the
+    /// text an edit replaced maps to the end of its replacement.
+    pub fn map(&self, offset: usize) -> usize {
+        self.translate(offset, |x| x.old, |x| x.new)
+    }
+
+    /// Finds where an offset in the text after the edits was before them,
+    /// which is the reverse of `map`
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The byte offset, in the text after the edits
+    ///
+    /// # Returns
+    ///
+    /// The byte offset in the text before the edits. An offset at the start
+    /// of a replacement maps to the start of the text it replaced, and one
+    /// within a replacement maps to the end of the text it replaced.
+    pub fn map_back(&self, offset: usize) -> usize {
+        self.translate(offset, |x| x.new, |x| x.old)
+    }
+
+    /// Finds where a span in the text before the edits is after them, which
+    /// covers the whole replacement of any edit it overlaps
+    pub fn map_span(&self, start: usize, end: usize) -> (usize, usize) {
+        (self.map(start), self.map(end))
+    }
+}
+
+#[test]
+fn test_position_mapper() {
+    let text = "let a = b + c;";
+    let mut edits = EditSet::new();
+    edits.add(4, 5, "alpha".into()).unwrap();
+    edits.add(8, 8, "(".into()).unwrap();
+    edits.add(12, 13, "".into()).unwrap();
+    let (after, mapper) = edits.apply_mapped(text).unwrap();
+    assert_eq!(after, "let alpha = (b + ;");
+    // `b` moves over by the four bytes `a` grew and the inserted `(`
+    assert_eq!(mapper.map(8), 12);
+    assert_eq!(&after[mapper.map(8) + 1..mapper.map(9)], "b");
+    assert_eq!(mapper.map_span(4, 5), (4, 9));
+    assert_eq!(mapper.map(13), 17);
+    // The deleted `c` left nothing behind, so its end maps back to its start
+    assert_eq!(mapper.map_back(17), 12);
+    assert_eq!(mapper.map_back(6), 5);
+    for offset in [0, 3, 6, 10, 14] {
+        assert_eq!(mapper.map_back(mapper.map(offset)), offset);
+    }
+}
@@ -0,0 +1,408 @@
+//! Provides run reports, which record what an in-place run changed - the
+//! files, the hunks of each, the queries they came from, when the run
+//! started and finished, and the version of spidior that made it - as JSON
+//! in `.spidior/runs/` at the root of the Git worktree, so that a run can be
+//! audited, or looked at again with `spidior report`, after the fact
+//!
+//! Each worktree of a repository keeps its own reports, as each holds its
+//! own checkout of the files they are about. Outside of Git, reports are
+//! kept beneath the current directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::editset::EditSet;
+use crate::error::SpidiorError;
+use crate::json::{self, Value};
+
+/// The version of the report format, which is bumped whenever it changes
+const VERSION: i64 = 1;
+
+/// The directory spidior keeps its own files in, like reports, which walks
+/// for files to search leave out, so that a later run never edits them
+pub const STATE_DIR: &str = ".spidior";
+
+/// The directory, beneath the root of the worktree, that reports are kept in
+pub const RUNS_DIR: &str = ".spidior/runs";
+
+/// What a run changed in a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileReport {
+    /// The path of the file, relative to the root of the worktree if it is
+    /// within it
+    pub path: String,
+    /// How many edits were made to the file
+    pub edits: usize,
+    /// How many hunks the edits make up, in `diff`
+    pub hunks: usize,
+    /// The queries the edits came from, in the order they first appear
+    pub queries: Vec<String>,
+    /// A unified diff of the edits
+    pub diff: String,
+}
+
+/// What an in-place run changed, across every file it wrote
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// The ID of the run, which names its report, like `20261017-224300`
+    pub id: String,
+    /// The version of spidior that made the run
+    pub tool: String,
+    /// When the run started, like `2026-10-17T22:43:00Z`
+    pub started: String,
+    /// When the run finished writing files
+    pub finished: String,
+    /// The branch checked out in the worktree, if it is on one
+    pub branch: Option<String>,
+    /// The commit checked out in the worktree, if it could be found
+    pub commit: Option<String>,
+    /// The files changed, in the order they were written
+    pub files: Vec<FileReport>,
+}
+
+impl Report {
+    /// Creates an empty Report of a run starting now
+    pub fn new() -> Self {
+        let now = now();
+        Self {
+            id: id(now),
+            tool: format!("spidior {}", env!("CARGO_PKG_VERSION")),
+            started: timestamp(now),
+            finished: String::new(),
+            branch: None,
+            commit: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Adds the edits made to a file to the report
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, as it should be reported
+    /// * `text` - The contents of the file the edits were made against
+    /// * `edits` - The edits to the file
+    ///
+    /// # Returns
+    ///
+    /// A Result<(), SpidiorError>, which will Err if an edit lies outside
+    /// `text`
+    pub fn add(&mut self, path: &str, text: &str, edits: &EditSet) -> Result<(), SpidiorError> {
+        let diff = edits.diff(text, path)?;
+        let mut queries: Vec<String> = Vec::new();
+        for x in edits.iter() {
+            if !queries.contains(&x.origin) {
+                queries.push(x.origin.clone());
+            }
+        }
+        self.files.push(FileReport {
+            path: path.to_string(),
+            edits: edits.len(),
+            hunks: diff.lines().filter(|x| x.starts_with("@@")).count(),
+            queries,
+            diff,
+        });
+        Ok(())
+    }
+
+    /// The queries of the run, in the order they first appear
+    pub fn queries(&self) -> Vec<&str> {
+        let mut queries: Vec<&str> = Vec::new();
+        for x in self.files.iter().flat_map(|x| &x.queries) {
+            if !queries.contains(&x.as_str()) {
+                queries.push(x);
+            }
+        }
+        queries
+    }
+
+    /// Writes the report as JSON, with a file on each line
+    pub fn to_json(&self) -> String {
+        let string = |x: &str| Value::String(x.to_string());
+        let optional = |x: &Option<String>| x.as_deref().map_or(Value::Null, string);
+        let mut out = format!("{{\n  \"version\": {}", VERSION);
+        for (key, value) in [
+            ("id", string(&self.id)),
+            ("tool", string(&self.tool)),
+            ("started", string(&self.started)),
+            ("finished", string(&self.finished)),
+            ("branch", optional(&self.branch)),
+            ("commit", optional(&self.commit)),
+            ("queries", Value::Array(self.queries().into_iter().map(string).collect())),
+        ] {
+            out += &format!(",\n  {}: {}", Value::String(key.into()), value);
+        }
+        out += ",\n  \"files\": [";
+        for (i, x) in self.files.iter().enumerate() {
+            let file = Value::Object(vec![
+                ("path".into(), string(&x.path)),
+                ("edits".into(), Value::Number(x.edits as i64)),
+                ("hunks".into(), Value::Number(x.hunks as i64)),
+                ("queries".into(), Value::Array(x.queries.iter().map(|x| string(x)).collect())),
+                ("diff".into(), string(&x.diff)),
+            ]);
+            out += if i == 0 { "\n    " } else { ",\n    " };
+            out += &file.to_string();
+        }
+        out += if self.files.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" };
+        out
+    }
+
+    /// Reads a report written by `to_json`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the report
+    ///
+    /// # Returns
+    ///
+    /// The Report, or a message describing why `text` is not one
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value = json::parse(text)?;
+        match value.get("version") {
+            Some(Value::Number(VERSION)) => {}
+            _ => return Err(format!("expected a report of version {}", VERSION)),
+        }
+        let string = |key: &str| value.get(key).and_then(|x| x.as_str()).map(String::from);
+        let mut report = Self {
+            id: string("id").ok_or("expected an `id`")?,
+            tool: string("tool").unwrap_or_default(),
+            started: string("started").unwrap_or_default(),
+            finished: string("finished").unwrap_or_default(),
+            branch: string("branch"),
+            commit: string("commit"),
+            files: Vec::new(),
+        };
+        let items = value.get("files").and_then(|x| x.as_array()).ok_or("expected a `files` array")?;
+        for (i, item) in items.iter().enumerate() {
+            let string = |key: &str| item.get(key).and_then(|x| x.as_str()).map(String::from);
+            let number = |key: &str| item.get(key).and_then(|x| x.as_usize());
+            let file = (|| {
+                Some(FileReport {
+                    path: string("path")?,
+                    edits: number("edits")?,
+                    hunks: number("hunks")?,
+                    queries: item.get("queries").and_then(|x| x.as_array()).map(|x| x.iter().filter_map(|x| x.as_str().map(String::from)).collect()).unwrap_or_default(),
+                    diff: string("diff")?,
+                })
+            })();
+            report.files.push(file.ok_or_else(|| format!("file {}: expected `path`, `edits`, `hunks`, and `diff`", i))?);
+        }
+        Ok(report)
+    }
+
+    /// Renders the report for reading: a header describing the run, a line
+    /// for each file, then the diff of each
+    pub fn render(&self) -> String {
+        let mut out = format!("Run {}, by {}\nStarted {}, finished {}\n", self.id, self.tool, self.started, self.finished);
+        match (&self.branch, &self.commit) {
+            (Some(branch), Some(commit)) => out += &format!("On {} at {}\n", branch, commit),
+            (Some(branch), None) => out += &format!("On {}\n", branch),
+            (None, Some(commit)) => out += &format!("At {}\n", commit),
+            (None, None) => {}
+        }
+        for query in self.queries() {
+            out += &format!("Query {}\n", query);
+        }
+        let (edits, hunks) = self.files.iter().fold((0, 0), |(e, h), x| (e + x.edits, h + x.hunks));
+        out += &format!("{} edit{} in {} hunk{} across {} file{}\n", edits, plural(edits), hunks, plural(hunks), self.files.len(), plural(self.files.len()));
+        for x in &self.files {
+            out += &format!("  {}: {} edit{}, {} hunk{}\n", x.path, x.edits, plural(x.edits), x.hunks, plural(x.hunks));
+        }
+        for x in &self.files {
+            out += "\n";
+            out += &x.diff;
+        }
+        out
+    }
+
+    /// Marks the run finished, records the branch and commit checked out
+    /// in the worktree at `root`, if it is one, and writes the report to
+    /// `RUNS_DIR` beneath it, making the ID unique among the reports there
+    ///
+    /// # Returns
+    ///
+    /// A Result<PathBuf, SpidiorError>, where on success, it returns the
+    /// path of the report. It will Err with an IoError if the report
+    /// cannot be written.
+    pub fn write(&mut self, root: &Path) -> Result<PathBuf, SpidiorError> {
+        self.finished = timestamp(now());
+        let (branch, commit) = head(root);
+        self.branch = branch;
+        self.commit = commit;
+        let dir = root.join(RUNS_DIR);
+        fs::create_dir_all(&dir).map_err(|e| SpidiorError::io(format!("Could not create {}", dir.display()), e))?;
+        let base = self.id.clone();
+        let mut n = 1;
+        while dir.join(format!("{}.json", self.id)).exists() {
+            n += 1;
+            self.id = format!("{}-{}", base, n);
+        }
+        let path = dir.join(format!("{}.json", self.id));
+        fs::write(&path, self.to_json()).map_err(|e| SpidiorError::io(format!("Could not write {}", path.display()), e))?;
+        Ok(path)
+    }
+
+    /// Reads the report of a run, given its ID, from `RUNS_DIR` beneath
+    /// `root`
+    pub fn load(root: &Path, id: &str) -> Result<Self, SpidiorError> {
+        let path = root.join(RUNS_DIR).join(format!("{}.json", id));
+        let text = fs::read_to_string(&path).map_err(|e| SpidiorError::io(format!("Could not read the report of run {} at {}", id, path.display()), e))?;
+        Self::parse(&text).map_err(|x| SpidiorError::parse(format!("{}: {}", path.display(), x)))
+    }
+
+    /// Lists the IDs of the runs with reports beneath `root`, oldest first
+    pub fn list(root: &Path) -> Vec<String> {
+        let mut ids: Vec<String> = fs::read_dir(root.join(RUNS_DIR))
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.ok())
+            .filter_map(|x| x.file_name().to_string_lossy().strip_suffix(".json").map(String::from))
+            .collect();
+        // IDs start with the time they were made at, and a repeated ID has
+        // a number after it
+        ids.sort_by_key(|x| {
+            let (time, n) = x.split_at(x.len().min(15));
+            (time.to_string(), n.trim_start_matches('-').parse::<usize>().unwrap_or(1))
+        });
+        ids
+    }
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// The seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
+
+/// The year, month, and day, and the hour, minute, and second, in UTC, of
+/// a time given in seconds since the Unix epoch
+fn civil(secs: u64) -> ([u64; 3], [u64; 3]) {
+    let (days, rest) = (secs / 86400, secs % 86400);
+    // Counts from 0000-03-01, so that leap days fall at the end of a year
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    ([year, month, day], [rest / 3600, rest / 60 % 60, rest % 60])
+}
+
+/// A time as an RFC 3339 timestamp in UTC, like `2026-10-17T22:43:00Z`
+fn timestamp(secs: u64) -> String {
+    let ([y, mo, d], [h, mi, s]) = civil(secs);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, h, mi, s)
+}
+
+/// The ID of a run starting at a time, like `20261017-224300`
+fn id(secs: u64) -> String {
+    let ([y, mo, d], [h, mi, s]) = civil(secs);
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", y, mo, d, h, mi, s)
+}
+
+/// Finds the root of the Git worktree a path is within, which is the
+/// nearest directory above it holding `.git`, as either the repository's
+/// directory or, in a linked worktree, a file pointing to it
+pub fn worktree_root(path: &Path) -> Option<PathBuf> {
+    let path = fs::canonicalize(path).ok()?;
+    path.ancestors().find(|x| x.join(".git").exists()).map(Path::to_path_buf)
+}
+
+/// Finds the branch and commit checked out in the worktree at `root`,
+/// reading `.git` directly, so that Git need not be installed
+fn head(root: &Path) -> (Option<String>, Option<String>) {
+    let dot_git = root.join(".git");
+    // A linked worktree has a `.git` file like `gitdir: <path>`, whose
+    // directory holds its own `HEAD`, and a `commondir` file pointing to
+    // the directory holding the repository's refs
+    let git_dir = match fs::read_to_string(&dot_git) {
+        Ok(x) => match x.trim().strip_prefix("gitdir:") {
+            Some(x) => root.join(x.trim()),
+            None => return (None, None),
+        },
+        Err(_) => dot_git,
+    };
+    let common = fs::read_to_string(git_dir.join("commondir")).map_or_else(|_| git_dir.clone(), |x| git_dir.join(x.trim()));
+    let head = match fs::read_to_string(git_dir.join("HEAD")) {
+        Ok(x) => x.trim().to_string(),
+        Err(_) => return (None, None),
+    };
+    let name = match head.strip_prefix("ref:") {
+        Some(x) => x.trim().to_string(),
+        None => return (None, Some(head)),
+    };
+    let commit = fs::read_to_string(git_dir.join(&name))
+        .or_else(|_| fs::read_to_string(common.join(&name)))
+        .map(|x| x.trim().to_string())
+        .ok()
+        .or_else(|| {
+            let packed = fs::read_to_string(common.join("packed-refs")).ok()?;
+            packed.lines().find_map(|x| x.strip_suffix(name.as_str()).filter(|x| x.ends_with(' ')).map(|x| x.trim().to_string()))
+        });
+    (Some(name.strip_prefix("refs/heads/").unwrap_or(&name).to_string()), commit)
+}
+
+#[test]
+fn test_report() {
+    assert_eq!(timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(timestamp(951782400), "2000-02-29T00:00:00Z");
+    assert_eq!(id(1792269780), "20261017-204300");
+    let text = "int a = a;\nint b;\n";
+    let mut edits = EditSet::new();
+    edits.add(4, 5, "c".into()).unwrap();
+    edits.add(8, 9, "c".into()).unwrap();
+    let mut report = Report::new();
+    report.add("x.c", text, &edits.with_origin("%s/a/c/g")).unwrap();
+    assert_eq!((report.files[0].edits, report.files[0].hunks), (2, 1));
+    assert_eq!(report.queries(), vec!["%s/a/c/g"]);
+    let json = report.to_json();
+    assert_eq!(Report::parse(&json).unwrap(), report);
+    assert!(report.render().contains("2 edits in 1 hunk across 1 file\n  x.c: 2 edits, 1 hunk\n"));
+    assert!(Report::parse("{\"version\": 9}").is_err());
+
+    let dir = std::env::temp_dir().join(format!("spidior-report-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (main, linked) = (dir.join("main"), dir.join("linked"));
+    fs::create_dir_all(main.join(".git").join("refs").join("heads")).unwrap();
+    fs::create_dir_all(main.join(".git").join("worktrees").join("linked")).unwrap();
+    fs::create_dir_all(linked.join("src")).unwrap();
+    fs::write(main.join(".git").join("HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::write(main.join(".git").join("packed-refs"), "# pack-refs\nabc123 refs/heads/main\n").unwrap();
+    fs::write(main.join(".git").join("refs").join("heads").join("fix"), "def456\n").unwrap();
+    let worktree = main.join(".git").join("worktrees").join("linked");
+    fs::write(worktree.join("HEAD"), "ref: refs/heads/fix\n").unwrap();
+    fs::write(worktree.join("commondir"), "../..\n").unwrap();
+    fs::write(linked.join(".git"), format!("gitdir: {}\n", worktree.display())).unwrap();
+    assert_eq!(head(&main), (Some("main".into()), Some("abc123".into())));
+    let root = worktree_root(&linked.join("src")).unwrap();
+    assert_eq!(root, fs::canonicalize(&linked).unwrap());
+    let path = report.write(&root).unwrap();
+    assert_eq!(path, root.join(RUNS_DIR).join(format!("{}.json", report.id)));
+    assert_eq!((report.branch.as_deref(), report.commit.as_deref()), (Some("fix"), Some("def456")));
+    let first = report.id.clone();
+    report.write(&root).unwrap();
+    assert_eq!(report.id, format!("{}-2", first));
+    assert_eq!(Report::list(&root), vec![first.clone(), report.id.clone()]);
+    assert_eq!(Report::load(&root, &first).unwrap().files, report.files);
+    assert!(Report::load(&root, "missing").is_err());
+    assert!(!main.join(RUNS_DIR).exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,189 @@
+//! Provides the two-phase writing of many files, so that an in-place run
+//! either changes every file it means to or leaves every file as it was
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::SpidiorError;
+
+/// A file waiting to be moved into place
+struct Staged {
+    /// The file to replace, with any links to it followed
+    target: PathBuf,
+    /// The temporary file holding its new contents, beside it
+    temp: PathBuf,
+}
+
+/// A set of files to write together, each of which is first written to a
+/// temporary file beside it, and only renamed into place once all of them
+/// have been written
+#[derive(Default)]
+pub struct Transaction {
+    staged: Vec<Staged>,
+}
+
+impl Transaction {
+    /// Creates an empty Transaction
+    pub fn new() -> Self {
+        Self { staged: Vec::new() }
+    }
+
+    /// The path of a file beside `path`, named for this process and `kind`
+    fn sibling(path: &Path, kind: &str) -> PathBuf {
+        let name = path.file_name().map_or_else(Default::default, |x| x.to_string_lossy().into_owned());
+        path.with_file_name(format!(".{}.spidior-{}.{}", name, std::process::id(), kind))
+    }
+
+    /// Writes the new contents of a file to a temporary file beside it, with
+    /// the same permissions
+    ///
+    /// A link is followed to the file it leads to, which is the one
+    /// replaced, with the temporary file beside it, so that the link is left
+    /// in place and the change is seen through it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to replace
+    /// * `contents` - The new contents of the file
+    ///
+    /// # Returns
+    ///
+    /// A Result<(), SpidiorError>, which will Err if the temporary file
+    /// cannot be written, in which case every file staged so far is
+    /// discarded
+    pub fn stage(&mut self, path: &Path, contents: &str) -> Result<(), SpidiorError> {
+        // A file that does not exist yet has no links to follow
+        let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let temp = Self::sibling(&target, "tmp");
+        let written = fs::write(&temp, contents).and_then(|_| match fs::metadata(&target) {
+            Ok(x) => fs::set_permissions(&temp, x.permissions()),
+            Err(_) => Ok(()),
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            self.discard();
+            return Err(SpidiorError::io(format!("Could not write {}", path.display()), e));
+        }
+        self.staged.push(Staged { target, temp });
+        Ok(())
+    }
+
+    /// Removes every staged file without moving any into place
+    pub fn discard(&mut self) {
+        for x in self.staged.drain(..) {
+            let _ = fs::remove_file(&x.temp);
+        }
+    }
+
+    /// Moves every staged file into place, putting back every file already
+    /// replaced if one cannot be
+    ///
+    /// # Returns
+    ///
+    /// A Result<(), SpidiorError>, which will Err if any file could not be
+    /// replaced, in which case every file is as it was before
+    pub fn commit(mut self) -> Result<(), SpidiorError> {
+        // The originals are moved aside rather than overwritten, so that
+        // they can be moved back if a later file fails
+        let mut done: Vec<(&Staged, Option<PathBuf>)> = Vec::new();
+        let mut failure = None;
+        for x in &self.staged {
+            let backup = Self::sibling(&x.target, "bak");
+            let moved = if x.target.exists() {
+                fs::rename(&x.target, &backup).map(|_| Some(backup))
+            } else {
+                Ok(None)
+            };
+            let backup = match moved {
+                Ok(x) => x,
+                Err(e) => {
+                    failure = Some((format!("Could not replace {}", x.target.display()), e));
+                    break;
+                }
+            };
+            let renamed = fs::rename(&x.temp, &x.target);
+            done.push((x, backup));
+            if let Err(e) = renamed {
+                failure = Some((format!("Could not replace {}", x.target.display()), e));
+                break;
+            }
+        }
+        match failure {
+            Some((message, e)) => {
+                for (x, backup) in done.into_iter().rev() {
+                    match backup {
+                        Some(backup) => {
+                            let _ = fs::rename(&backup, &x.target);
+                        }
+                        None => {
+                            let _ = fs::remove_file(&x.target);
+                        }
+                    }
+                }
+                self.discard();
+                Err(SpidiorError::io(format!("{}, so no files were changed", message), e))
+            }
+            None => {
+                for (_, backup) in done {
+                    if let Some(backup) = backup {
+                        let _ = fs::remove_file(backup);
+                    }
+                }
+                self.staged.clear();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        self.discard();
+    }
+}
+
+#[test]
+fn test_transaction() {
+    let dir = std::env::temp_dir().join(format!("spidior-test-transaction-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+    fs::write(&a, "a").unwrap();
+    fs::write(&b, "b").unwrap();
+    let mut tx = Transaction::new();
+    tx.stage(&a, "A").unwrap();
+    tx.stage(&b, "B").unwrap();
+    tx.commit().unwrap();
+    assert_eq!((fs::read_to_string(&a).unwrap(), fs::read_to_string(&b).unwrap()), ("A".into(), "B".into()));
+    // Losing the second file's new contents puts the first file back
+    let mut tx = Transaction::new();
+    tx.stage(&a, "AA").unwrap();
+    tx.stage(&b, "BB").unwrap();
+    fs::remove_file(Transaction::sibling(&fs::canonicalize(&b).unwrap(), "tmp")).unwrap();
+    assert!(tx.commit().is_err());
+    assert_eq!((fs::read_to_string(&a).unwrap(), fs::read_to_string(&b).unwrap()), ("A".into(), "B".into()));
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_transaction_links() {
+    let dir = std::env::temp_dir().join(format!("spidior-test-transaction-links-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("real")).unwrap();
+    fs::create_dir_all(dir.join("w")).unwrap();
+    fs::write(dir.join("real").join("a.c"), "a").unwrap();
+    // A link to a link to the file, as `w/x.c -> ../link.c -> real/a.c`
+    std::os::unix::fs::symlink("real/a.c", dir.join("link.c")).unwrap();
+    std::os::unix::fs::symlink("../link.c", dir.join("w").join("x.c")).unwrap();
+    let mut tx = Transaction::new();
+    tx.stage(&dir.join("w").join("x.c"), "A").unwrap();
+    tx.commit().unwrap();
+    assert_eq!(fs::read_to_string(dir.join("real").join("a.c")).unwrap(), "A");
+    assert!(fs::symlink_metadata(dir.join("w").join("x.c")).unwrap().file_type().is_symlink());
+    assert!(fs::symlink_metadata(dir.join("link.c")).unwrap().file_type().is_symlink());
+    assert_eq!(fs::read_dir(dir.join("w")).unwrap().count(), 1);
+    assert_eq!(fs::read_dir(dir.join("real")).unwrap().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,126 @@
+//! Provides the errors spidior reports, sorted by what failed, so that tools
+//! using the library can tell a bad query from a missing file
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while finding or replacing
+#[derive(Debug)]
+pub enum SpidiorError {
+    /// A query, template, config file, or plan is not valid
+    ParseError(String),
+    /// A query was parsed, but its automaton could not be built
+    CompileError(String),
+    /// A file could not be read or written
+    IoError {
+        /// What was being done, like `Could not write a.txt`
+        message: String,
+        source: io::Error,
+    },
+    /// A language was named that spidior does not know, or given a setting
+    /// it does not have
+    LanguageError(String),
+    /// Edits could not be made, because they overlap one another, lie
+    /// outside the text they were made against, or no longer match it, or
+    /// their replacement could not be built
+    EditError(String),
+    /// Matching a file took longer, or held more memory, than a limit allows
+    LimitError(String),
+}
+
+impl SpidiorError {
+    /// Creates a ParseError with a message
+    pub fn parse(message: impl Into<String>) -> Self {
+        SpidiorError::ParseError(message.into())
+    }
+
+    /// Creates a CompileError with a message
+    pub fn compile(message: impl Into<String>) -> Self {
+        SpidiorError::CompileError(message.into())
+    }
+
+    /// Creates an IoError with a message saying what was being done
+    pub fn io(message: impl Into<String>, source: io::Error) -> Self {
+        SpidiorError::IoError {
+            message: message.into(),
+            source,
+        }
+    }
+
+    /// Creates a LanguageError with a message
+    pub fn language(message: impl Into<String>) -> Self {
+        SpidiorError::LanguageError(message.into())
+    }
+
+    /// Creates an EditError with a message
+    pub fn edit(message: impl Into<String>) -> Self {
+        SpidiorError::EditError(message.into())
+    }
+
+    /// Creates a LimitError with a message
+    pub fn limit(message: impl Into<String>) -> Self {
+        SpidiorError::LimitError(message.into())
+    }
+}
+
+impl fmt::Display for SpidiorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpidiorError::ParseError(x)
+            | SpidiorError::CompileError(x)
+            | SpidiorError::LanguageError(x)
+            | SpidiorError::EditError(x)
+            | SpidiorError::LimitError(x)
+            | SpidiorError::IoError { message: x, .. } => write!(f, "{}", x),
+        }
+    }
+}
+
+impl Error for SpidiorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpidiorError::IoError { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SpidiorError {
+    fn from(source: io::Error) -> Self {
+        SpidiorError::io("Could not read or write a file", source)
+    }
+}
+
+/// Runs `f`, catching any panic within it, so that one input spidior
+/// cannot handle does not bring down a long-lived process, like `spidior
+/// serve`, or unwind into a caller that cannot catch it
+///
+/// # Arguments
+///
+/// * `f` - What to run
+///
+/// # Returns
+///
+/// A Result<T, String>, which will Err with the message `f` panicked with
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| match payload.downcast::<String>() {
+        Ok(x) => *x,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("an unknown panic", |x| x).to_string(),
+    })
+}
+
+#[test]
+fn test_error() {
+    let e = SpidiorError::io("Could not read a.txt", io::Error::new(io::ErrorKind::NotFound, "gone"));
+    assert_eq!(e.to_string(), "Could not read a.txt");
+    assert_eq!(e.source().map(|x| x.to_string()), Some("gone".to_string()));
+    assert!(matches!(SpidiorError::parse("bad"), SpidiorError::ParseError(x) if x == "bad"));
+}
+
+#[test]
+fn test_catch_panic() {
+    assert_eq!(catch_panic(|| 1), Ok(1));
+    assert_eq!(catch_panic(|| -> i32 { panic!("bad {}", "input") }), Err("bad input".to_string()));
+    assert_eq!(catch_panic(|| -> i32 { panic!("bad") }), Err("bad".to_string()));
+}
@@ -0,0 +1,312 @@
+//! Provides the formats `--format` can print matches in, so that editors
+//! can read them like the output of `grep` or a compiler, or spreadsheets
+//! can load them as CSV or TSV
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::csv::Delimited;
+use crate::nfa::matcher::Match;
+
+/// How a run prints what it finds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Prints each file as the replacement leaves it, or a diff of it
+    Text,
+    /// Prints each match as `file:line:col:text`, where `col` counts bytes
+    /// and `text` is the line the match starts on, for Vim's quickfix list
+    Vimgrep,
+    /// Prints each match as `file:line:col: text`, where `col` counts
+    /// characters, for an Emacs compilation buffer
+    Emacs,
+    /// Writes a report of the edits to each file, as HTML pages, rather
+    /// than printing the files
+    Html,
+    /// Prints a header row and then each match as a row of comma-separated
+    /// values, under `HEADER`, where `column` counts characters
+    Csv,
+    /// Prints matches as `Csv` does, but as tab-separated values
+    Tsv,
+}
+
+/// The header row of `Csv` and `Tsv`, where `replacement` is only filled in
+/// by `--preview`, and `pattern` only for the patterns of `-e`
+pub const HEADER: [&str; 6] = ["path", "line", "column", "match", "replacement", "pattern"];
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "vimgrep" => Ok(Format::Vimgrep),
+            "emacs" => Ok(Format::Emacs),
+            "html" => Ok(Format::Html),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(format!("Unknown format `{}`, expected `text`, `vimgrep`, `emacs`, `html`, `csv`, or `tsv`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Vimgrep => write!(f, "vimgrep"),
+            Format::Emacs => write!(f, "emacs"),
+            Format::Html => write!(f, "html"),
+            Format::Csv => write!(f, "csv"),
+            Format::Tsv => write!(f, "tsv"),
+        }
+    }
+}
+
+/// How the lines describing matches are styled for where they are printed,
+/// which `--format vimgrep` and `emacs` pick from the environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+    /// Whether the match, or its preview, is highlighted in color
+    pub color: bool,
+    /// The most characters of the line a match is on to print, if there is
+    /// a limit, beyond which the line is cut short around the match
+    pub max_columns: Option<usize>,
+}
+
+/// Starts and ends the highlighting of a match
+const HIGHLIGHT: (&str, &str) = ("\x1b[1;31m", "\x1b[0m");
+
+/// The fewest characters of a line that are shown, however narrow the
+/// terminal is
+const MIN_COLUMNS: usize = 20;
+
+/// Marks where a line is cut short
+const ELLIPSIS: char = '…';
+
+impl Style {
+    /// Picks the style for printing to a terminal or not: color is used only
+    /// on a terminal, and never when `NO_COLOR` is set to anything, and lines
+    /// are cut short to `max_columns`, if given, or else on a terminal, to
+    /// fit the `width` of the terminal after each line's `path:line:col:`
+    ///
+    /// # Arguments
+    ///
+    /// * `terminal` - Whether the lines are printed to a terminal
+    /// * `no_color` - The value of `NO_COLOR`, if it is set
+    /// * `width` - The width of the terminal, if it is known
+    /// * `max_columns` - The limit given by `--max-columns`, where 0 is none
+    pub fn detect(terminal: bool, no_color: Option<&str>, width: Option<usize>, max_columns: Option<usize>) -> Self {
+        Self {
+            color: terminal && no_color.is_none_or(str::is_empty),
+            max_columns: match max_columns {
+                Some(0) => None,
+                Some(x) => Some(x),
+                None => width.filter(|_| terminal),
+            },
+        }
+    }
+
+    /// Cuts a line short, keeping `mid` whole if it fits, with as much of
+    /// `before` and `after` on either side of it as the limit leaves, and
+    /// highlights `mid`
+    fn apply(&self, before: &str, mid: &str, after: &str, limit: Option<usize>) -> String {
+        let (mut before, mut mid, mut after) = (before.to_string(), mid.to_string(), after.to_string());
+        let len = |x: &str| x.chars().count();
+        if let Some(limit) = limit.filter(|x| len(&before) + len(&mid) + len(&after) > *x) {
+            let cut_front = |x: &str, keep: usize| match keep {
+                0 => String::new(),
+                _ if len(x) <= keep => x.to_string(),
+                _ => std::iter::once(ELLIPSIS).chain(x.chars().skip(len(x) + 1 - keep)).collect(),
+            };
+            let cut_back = |x: &str, keep: usize| match keep {
+                0 => String::new(),
+                _ if len(x) <= keep => x.to_string(),
+                _ => x.chars().take(keep - 1).chain(std::iter::once(ELLIPSIS)).collect(),
+            };
+            if len(&mid) >= limit {
+                before = cut_front(&before, 1);
+                mid = cut_back(&mid, limit - len(&before).min(limit - 1));
+                after = String::new();
+            } else {
+                let room = limit - len(&mid);
+                let left = (room / 2).max(room.saturating_sub(len(&after))).min(len(&before));
+                before = cut_front(&before, left);
+                after = cut_back(&after, room - left);
+            }
+        }
+        if self.color {
+            mid = format!("{}{}{}", HIGHLIGHT.0, mid, HIGHLIGHT.1);
+        }
+        before + &mid + &after
+    }
+}
+
+impl Format {
+    /// Whether the format prints each match, rather than what replacing
+    /// them makes
+    pub fn lists_matches(&self) -> bool {
+        matches!(self, Format::Vimgrep | Format::Emacs | Format::Csv | Format::Tsv)
+    }
+
+    /// The kind of delimited text the format is, if it is one
+    pub fn delimited(&self) -> Option<Delimited> {
+        match self {
+            Format::Csv => Some(Delimited::Csv),
+            Format::Tsv => Some(Delimited::Tsv),
+            _ => None,
+        }
+    }
+
+    /// The line to print before any matches, which only `Csv` and `Tsv` have
+    pub fn header(&self) -> Option<String> {
+        self.delimited().map(|x| x.row(&HEADER))
+    }
+
+    /// Describes a match in the format, as one line
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file the match is in
+    /// * `text` - The contents of the file
+    /// * `m` - The match, with its line and column filled in
+    ///
+    /// # Returns
+    ///
+    /// The line, or None for `Text` and `Html`, which print files rather than
+    /// matches
+    pub fn line(&self, path: &str, text: &str, m: &Match) -> Option<String> {
+        self.styled(path, text, m, None, None, &Style::default())
+    }
+
+    /// Describes a match in the format, as `line` does, but with the text
+    /// that would replace it shown inline, as `[old → new]`, in place of the
+    /// match, for `--preview`
+    ///
+    /// Line breaks within the match or its replacement are shown as `\n`, so
+    /// each match still takes one line, which ends where the line the match
+    /// ends on does.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file the match is in
+    /// * `text` - The contents of the file
+    /// * `m` - The match, with its line and column filled in
+    /// * `with` - The text that would replace the match
+    pub fn preview(&self, path: &str, text: &str, m: &Match, with: &str) -> Option<String> {
+        self.styled(path, text, m, Some(with), None, &Style::default())
+    }
+
+    /// Describes a match in the format, as `line` does, or with `with`, as
+    /// `preview` does, in a style, after a label in brackets, like the
+    /// pattern that made it, if given one
+    ///
+    /// A line longer than the style allows is cut short around the match,
+    /// with `…` wherever text is left out, while a match that is itself too
+    /// long is cut short after its start. Columns are those of the whole
+    /// line, however it is cut. `Csv` and `Tsv` rows hold the whole match,
+    /// its replacement, and the label in fields of their own, unstyled.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file the match is in
+    /// * `text` - The contents of the file
+    /// * `m` - The match, with its line and column filled in
+    /// * `with` - The text that would replace the match, to preview it
+    /// * `label` - The label to put before the line
+    /// * `style` - How to style the line
+    pub fn styled(&self, path: &str, text: &str, m: &Match, with: Option<&str>, label: Option<&str>, style: &Style) -> Option<String> {
+        let start = m.start();
+        let begin = text[..start].rfind('\n').map_or(0, |x| x + 1);
+        let mut after = m.end().min(text.len());
+        while !text.is_char_boundary(after) {
+            after += 1;
+        }
+        let end = text[after..].find('\n').map_or(text.len(), |x| after + x);
+        let escape = |x: &str| x.replace('\r', "").replace('\n', "\\n");
+        let (mid, rest) = match with {
+            Some(with) => (format!("[{} → {}]", escape(&text[start..after]), escape(with)), &text[after..end]),
+            // Without a preview, only the line the match starts on is shown
+            None => {
+                let line_end = text[start..].find('\n').map_or(text.len(), |x| start + x);
+                let mid = after.min(line_end);
+                (text[start..mid].to_string(), &text[mid..line_end])
+            }
+        };
+        let (line, column) = m.position();
+        if let Some(delimited) = self.delimited() {
+            let fields = [path, &line.to_string(), &column.to_string(), &text[start..m.end().min(text.len())], with.unwrap_or_default(), label.unwrap_or_default()];
+            return Some(delimited.row(&fields));
+        }
+        let mut prefix = match self {
+            Format::Text | Format::Html | Format::Csv | Format::Tsv => return None,
+            Format::Vimgrep => format!("{}:{}:{}:", path, line, start - begin + 1),
+            Format::Emacs => format!("{}:{}:{}: ", path, line, column),
+        };
+        if let Some(label) = label {
+            prefix += &format!("[{}] ", label);
+        }
+        // A terminal's width is shared with the prefix, but is never left
+        // too narrow to show anything around a match
+        let limit = style.max_columns.map(|x| x.saturating_sub(prefix.chars().count()).max(MIN_COLUMNS));
+        Some(prefix + &style.apply(&text[begin..start], &mid, rest.trim_end_matches('\r'), limit))
+    }
+}
+
+#[test]
+fn test_format() {
+    use crate::languages::lines::locate;
+    let text = "int é;\r\nint count = 0;\nint é2 = count;";
+    let at = |start| locate(text, vec![Match::new(start, 1, Vec::new())]).remove(0);
+    let start = text.rfind("count").unwrap();
+    assert_eq!(Format::Vimgrep.line("a.c", text, &at(start)).unwrap(), "a.c:3:11:int é2 = count;");
+    assert_eq!(Format::Emacs.line("a.c", text, &at(start)).unwrap(), "a.c:3:10: int é2 = count;");
+    assert_eq!(Format::Emacs.line("a.c", text, &at(4)).unwrap(), "a.c:1:5: int é;");
+    assert_eq!(Format::Text.line("a.c", text, &at(0)), None);
+    let m = locate(text, vec![Match::new(start, 5, Vec::new())]).remove(0);
+    assert_eq!(Format::Vimgrep.preview("a.c", text, &m, "total").unwrap(), "a.c:3:11:int é2 = [count → total];");
+    let m = locate(text, vec![Match::new(4, 8, Vec::new())]).remove(0);
+    assert_eq!(Format::Emacs.preview("a.c", text, &m, "x").unwrap(), "a.c:1:5: int [é;\\nint → x] count = 0;");
+    assert_eq!("vimgrep".parse::<Format>(), Ok(Format::Vimgrep));
+    assert!(!"html".parse::<Format>().unwrap().lists_matches());
+
+    let text = format!("{}count{}", "a".repeat(40), "b".repeat(40));
+    let m = locate(&text, vec![Match::new(40, 5, Vec::new())]).remove(0);
+    let style = Style { color: false, max_columns: Some(21) };
+    assert_eq!(Format::Emacs.styled("a.c", &text, &m, None, None, &style).unwrap(), "a.c:1:41: …aaaaaacountbbbbbbb…");
+    assert_eq!(Format::Emacs.styled("a.c", &text, &m, Some("total"), None, &style).unwrap(), "a.c:1:41: …a[count → total]bb…");
+    let style = Style { color: true, max_columns: None };
+    assert_eq!(Format::Vimgrep.styled("a.c", "x count", &at(2), None, None, &style).unwrap(), "a.c:1:3:x \x1b[1;31mc\x1b[0mount");
+    assert_eq!(Format::Vimgrep.styled("a.c", "x count", &at(2), None, Some("c.u"), &Style::default()).unwrap(), "a.c:1:3:[c.u] x count");
+    assert_eq!(Style::detect(true, Some(""), Some(80), None), Style { color: true, max_columns: Some(80) });
+    assert_eq!(Style::detect(true, Some("1"), Some(80), Some(0)), Style { color: false, max_columns: None });
+    assert_eq!(Style::detect(false, None, Some(80), None), Style::default());
+    assert!("quickfix".parse::<Format>().is_err());
+    assert_eq!(Format::Csv.header().as_deref(), Some("path,line,column,match,replacement,pattern"));
+    assert_eq!(Format::Csv.preview("a, b.c", &text, &m, "total").as_deref(), Some("\"a, b.c\",1,41,count,total,"));
+    assert_eq!(Format::Tsv.styled("a.c", "x\tcount", &at(2), None, Some("c.u"), &style).as_deref(), Some("a.c\t1\t3\tc\t\tc.u"));
+    assert_eq!(Format::Vimgrep.header(), None);
+}
+
+#[test]
+fn test_style() {
+    use crate::languages::lines::locate;
+    // Color needs a terminal and an unset or empty NO_COLOR, and lines are
+    // only fit to the width of a terminal
+    assert_eq!(Style::detect(true, None, None, None), Style { color: true, max_columns: None });
+    assert_eq!(Style::detect(true, Some("0"), Some(80), None), Style { color: false, max_columns: Some(80) });
+    assert_eq!(Style::detect(false, None, Some(80), Some(30)), Style { color: false, max_columns: Some(30) });
+    assert_eq!(Style::detect(false, None, None, Some(0)), Style::default());
+    let text = format!("{}count{}", "a".repeat(40), "b".repeat(40));
+    let m = locate(&text, vec![Match::new(40, 5, Vec::new())]).remove(0);
+    // The limit never leaves fewer than MIN_COLUMNS after the prefix
+    let style = Style { color: false, max_columns: Some(1) };
+    assert_eq!(Format::Vimgrep.styled("a.c", &text, &m, None, None, &style).unwrap(), "a.c:1:41:…aaaaaacountbbbbbbb…");
+    // A match longer than the limit is cut short after its start
+    let m = locate(&text, vec![Match::new(0, 80, Vec::new())]).remove(0);
+    assert_eq!(Format::Vimgrep.styled("a.c", &text, &m, None, None, &style).unwrap(), format!("a.c:1:1:{}…", "a".repeat(19)));
+    // A line that just fits is left whole, with its match highlighted
+    let style = Style { color: true, max_columns: Some(101) };
+    let m = locate(&text, vec![Match::new(40, 5, Vec::new())]).remove(0);
+    let line = Format::Emacs.styled("a.c", &text, &m, Some("x"), None, &style).unwrap();
+    assert_eq!(line, format!("a.c:1:41: {}\x1b[1;31m[count → x]\x1b[0m{}", "a".repeat(40), "b".repeat(40)));
+}
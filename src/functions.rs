@@ -0,0 +1,130 @@
+//! Provides the functions a replacement can pass a group through, like
+//! `${1:snake_case}`, which are the builtin functions plus any registered
+//! by library users
+//!
+//! A function takes the text of a group and gives back the text to put in
+//! its place. Registered functions are kept for the life of the process,
+//! and must be registered before the queries that use them are parsed, as
+//! an unknown function is a parse error.
+
+use std::sync::RwLock;
+
+/// A function a replacement can pass a group through
+pub type Function = fn(&str) -> String;
+
+/// The functions registered by library users, which are looked up before
+/// the builtin functions, so may replace them
+static ADDED: RwLock<Vec<(String, Function)>> = RwLock::new(Vec::new());
+
+/// The builtin functions, with their names
+const BUILTIN: [(&str, Function); 7] = [
+    ("upper", upper),
+    ("lower", lower),
+    ("capitalize", capitalize),
+    ("snake_case", snake_case),
+    ("camelCase", camel_case),
+    ("PascalCase", pascal_case),
+    ("len", len),
+];
+
+/// Registers a function, so replacements can use it as `${1:name}`
+///
+/// # Arguments
+///
+/// * `name` - The name of the function, which is made of letters, digits,
+///   and `_`, and replaces any builtin function or function registered
+///   before with that name
+/// * `function` - The function
+pub fn register(name: &str, function: Function) {
+    if let Ok(mut added) = ADDED.write() {
+        added.retain(|(x, _)| x != name);
+        added.push((name.to_string(), function));
+    }
+}
+
+/// Finds a function by its name, looking at registered functions first
+pub fn get(name: &str) -> Option<Function> {
+    let added = ADDED.read().ok().and_then(|x| x.iter().find(|(x, _)| x == name).map(|(_, f)| *f));
+    added.or_else(|| BUILTIN.iter().find(|(x, _)| *x == name).map(|(_, f)| *f))
+}
+
+/// The names of the builtin functions
+pub fn builtin() -> impl Iterator<Item = &'static str> {
+    BUILTIN.iter().map(|(x, _)| *x)
+}
+
+fn upper(text: &str) -> String {
+    text.to_uppercase()
+}
+
+fn lower(text: &str) -> String {
+    text.to_lowercase()
+}
+
+/// Uppercases the first letter of a text, leaving the rest alone
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// The number of characters in a text
+fn len(text: &str) -> String {
+    text.chars().count().to_string()
+}
+
+/// Splits an identifier into its words, at `_`, `-`, and spaces, and where
+/// its case changes, so `parseHTTPRequest_v2` is `parse`, `HTTP`, `Request`,
+/// and `v2`
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in text.split(|c: char| c == '_' || c == '-' || c.is_whitespace()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut word = String::new();
+        for (i, c) in chars.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|x| chars[x]);
+            let next = chars.get(i + 1);
+            // A word starts at an uppercase letter after a lowercase one or
+            // a digit, or at the last uppercase letter of an acronym
+            let starts = c.is_uppercase() && prev.is_some_and(|p| !p.is_uppercase() || next.is_some_and(|n| n.is_lowercase()));
+            if starts && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.push(*c);
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    words
+}
+
+fn snake_case(text: &str) -> String {
+    words(text).iter().map(|x| x.to_lowercase()).collect::<Vec<_>>().join("_")
+}
+
+fn pascal_case(text: &str) -> String {
+    words(text).iter().map(|x| capitalize(&x.to_lowercase())).collect()
+}
+
+fn camel_case(text: &str) -> String {
+    words(text).iter().enumerate().map(|(i, x)| if i == 0 { x.to_lowercase() } else { capitalize(&x.to_lowercase()) }).collect()
+}
+
+#[test]
+fn test_functions() {
+    let apply = |name: &str, text: &str| get(name).map(|f| f(text));
+    assert_eq!(words("parseHTTPRequest_v2"), vec!["parse", "HTTP", "Request", "v2"]);
+    assert_eq!(apply("snake_case", "parseHTTPRequest"), Some("parse_http_request".to_string()));
+    assert_eq!(apply("camelCase", "user_id-count"), Some("userIdCount".to_string()));
+    assert_eq!(apply("PascalCase", "user_id"), Some("UserId".to_string()));
+    assert_eq!(apply("upper", "straße"), Some("STRASSE".to_string()));
+    assert_eq!(apply("capitalize", "élan vital"), Some("Élan vital".to_string()));
+    assert_eq!(apply("len", "héllo"), Some("5".to_string()));
+    assert_eq!(apply("reverse", "abc"), None);
+    register("reverse", |x| x.chars().rev().collect());
+    assert_eq!(apply("reverse", "abc"), Some("cba".to_string()));
+    assert_eq!(builtin().count(), 7);
+}
@@ -0,0 +1,203 @@
+//! Compares what spidior matches against the `regex` crate, over random
+//! patterns and inputs within the syntax both understand, which `spidior
+//! fuzz-compare` runs from the command line
+//!
+//! spidior takes the longest match at each position, and carries on after
+//! it, while the `regex` crate prefers the leftmost alternative. The
+//! `regex` crate's side is found by trying every end at each position,
+//! longest first, against the pattern anchored at both ends, so the two
+//! should agree on every span. Groups are not compared, as the two prefer
+//! different boundaries for them.
+
+use crate::nfa::matcher;
+use crate::regexparser;
+
+/// The characters patterns and inputs are made of, few enough that random
+/// patterns often match
+const LETTERS: &[char] = &['a', 'b', 'c'];
+
+/// The characters inputs are made of, which add characters no letter of a
+/// pattern names, a newline that `.` does not match, and one of several
+/// bytes
+const INPUT: &[char] = &['a', 'b', 'c', 'd', '.', ' ', '\n', 'é'];
+
+/// A small, seeded source of random numbers, so that a run can be repeated
+/// from its seed
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift never leaves zero
+        Self(seed.max(1))
+    }
+
+    /// A number below `n`, which must not be zero
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    fn pick(&mut self, from: &[char]) -> char {
+        from[self.below(from.len())]
+    }
+}
+
+/// Makes a random pattern of letters, `.`, `\.`, sets, groups, `*`, `+`,
+/// and `|`, with groups nested at most `depth` deep
+pub fn pattern(rng: &mut Rng, depth: usize) -> String {
+    let mut out = concatenation(rng, depth);
+    while rng.below(4) == 0 {
+        out = format!("{}|{}", out, concatenation(rng, depth));
+    }
+    out
+}
+
+fn concatenation(rng: &mut Rng, depth: usize) -> String {
+    (0..1 + rng.below(3)).map(|_| basic(rng, depth)).collect()
+}
+
+fn basic(rng: &mut Rng, depth: usize) -> String {
+    let elem = match rng.below(if depth == 0 { 6 } else { 7 }) {
+        0..=2 => rng.pick(LETTERS).to_string(),
+        3 => ".".to_string(),
+        4 => "\\.".to_string(),
+        5 => set(rng),
+        _ => format!("({})", pattern(rng, depth - 1)),
+    };
+    match rng.below(4) {
+        0 => elem + "*",
+        1 => elem + "+",
+        _ => elem,
+    }
+}
+
+fn set(rng: &mut Rng) -> String {
+    let negative = if rng.below(3) == 0 { "^" } else { "" };
+    let items: String = (0..1 + rng.below(2))
+        .map(|_| match rng.below(3) {
+            0 => "a-b".to_string(),
+            _ => rng.pick(LETTERS).to_string(),
+        })
+        .collect();
+    format!("[{}{}]", negative, items)
+}
+
+/// Makes a random input of at most `len` characters
+pub fn input(rng: &mut Rng, len: usize) -> String {
+    (0..rng.below(len + 1)).map(|_| rng.pick(INPUT)).collect()
+}
+
+/// Finds the spans the `regex` crate matches, taking the longest match at
+/// each position, and carrying on after it, as spidior does
+///
+/// # Returns
+///
+/// The start and length of each match, or None if the `regex` crate cannot
+/// compile the pattern
+pub fn expected(pattern: &str, input: &str) -> Option<Vec<(usize, usize)>> {
+    let re = regex::Regex::new(&format!("^(?:{})$", pattern)).ok()?;
+    let mut found = Vec::new();
+    let mut at = 0;
+    while at < input.len() {
+        let end = (at + 1..=input.len()).rev().filter(|x| input.is_char_boundary(*x)).find(|x| re.is_match(&input[at..*x]));
+        match end {
+            Some(end) => {
+                found.push((at, end - at));
+                at = end;
+            }
+            None => at += input[at..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    Some(found)
+}
+
+/// Matches a pattern against an input with both spidior and the `regex`
+/// crate
+///
+/// # Returns
+///
+/// A description of the first span on which they differ, like `` `a*b` on
+/// "aab": spidior matched "b" at 2, regex matched "aab" at 0 ``, or None if
+/// they match the same spans
+pub fn compare(pattern: &str, input: &str) -> Option<String> {
+    let find = match regexparser::parse(&format!("%s/{}//g", pattern)) {
+        Ok(x) => x.find,
+        Err(e) => return Some(format!("`{}`: spidior could not parse it: {}", pattern, e)),
+    };
+    let found: Vec<(usize, usize)> = matcher::find(input, find).iter().map(|x| (x.start(), x.len())).collect();
+    let expected = match expected(pattern, input) {
+        Some(x) => x,
+        None => return Some(format!("`{}`: regex could not compile it", pattern)),
+    };
+    let first = (0..found.len().max(expected.len())).find(|i| found.get(*i) != expected.get(*i))?;
+    let text = |x: Option<&(usize, usize)>| match x {
+        Some((start, len)) => format!("{:?} at {}", &input[*start..start + len], start),
+        None => "nothing more".to_string(),
+    };
+    Some(format!("`{}` on {:?}: spidior matched {}, regex matched {}", pattern, input, text(found.get(first)), text(expected.get(first))))
+}
+
+/// Compares spidior against the `regex` crate over random patterns and
+/// inputs
+///
+/// # Arguments
+///
+/// * `cases` - How many patterns to try, each against one input
+/// * `seed` - The seed the patterns and inputs are made from
+///
+/// # Returns
+///
+/// A description of each case on which they differ
+pub fn run(cases: usize, seed: u64) -> Vec<String> {
+    let mut rng = Rng::new(seed);
+    (0..cases)
+        .filter_map(|_| {
+            let pattern = pattern(&mut rng, 2);
+            let input = input(&mut rng, 16);
+            compare(&pattern, &input)
+        })
+        .collect()
+}
+
+#[test]
+fn test_fuzz_compare() {
+    assert_eq!(expected("a|ab", "xabab"), Some(vec![(1, 2), (3, 2)]));
+    assert_eq!(expected("(o*)o", "é ooo"), Some(vec![(3, 3)]));
+    assert_eq!(compare("a|ab", "abab"), None);
+    assert_eq!(compare("[^a-b]+", "abcd\né"), None);
+    assert!(compare("a(", "a").unwrap().contains("could not parse"));
+    assert_eq!(run(300, 7), Vec::<String>::new());
+    let mut rng = Rng::new(3);
+    assert_eq!(pattern(&mut rng, 2), pattern(&mut Rng::new(3), 2));
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_fuzz_proptest(pattern in strategy(), input in "[abcd. \né]{0,12}") {
+        proptest::prop_assert_eq!(compare(&pattern, &input), None);
+    }
+}
+
+/// Patterns as proptest makes them, so that a failing one is shrunk to a
+/// small one
+#[cfg(test)]
+fn strategy() -> impl proptest::strategy::Strategy<Value = String> {
+    use proptest::prelude::*;
+    let leaf = prop_oneof![
+        "[abc]",
+        Just(".".to_string()),
+        Just("\\.".to_string()),
+        "\\[\\^?(a-b|[abc]){1,2}\\]",
+    ];
+    let leaf = (leaf, "[*+]?").prop_map(|(a, op)| a + &op);
+    leaf.prop_recursive(3, 16, 3, |inner| {
+        prop_oneof![
+            proptest::collection::vec(inner.clone(), 1..4).prop_map(|x| x.concat()),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| format!("{}|{}", a, b)),
+            (inner, "[*+]?").prop_map(|(a, op)| format!("({}){}", a, op)),
+        ]
+    })
+}
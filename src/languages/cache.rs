@@ -0,0 +1,622 @@
+//! Provides the cache of parse results, so that files which have not changed
+//! since the last run are not parsed again, and the parallel parsing of the
+//! files that have
+
+use super::lexer::{Span, SpanKind};
+use super::lines::locate;
+use super::registry::Registry;
+use super::parsing::{Call, Diagnostic, Function, Identifier, Import, Parameter};
+use super::scopes::{Scope, ScopeTree};
+use super::{keywords, Language};
+use std::fs;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The version of the cache's format, which is bumped whenever the format
+/// changes so that older files are ignored
+const FORMAT: u32 = 7;
+
+/// The version of what the parsers find, which is bumped whenever a
+/// language's parse of the same code changes, like the diagnostics Clike
+/// gives for unbalanced braces, so that results parsed the old way are
+/// ignored
+const PARSERS: u32 = 2;
+
+/// The most bytes the cache directory may hold, past which the results used
+/// least recently are removed
+const LIMIT: u64 = 256 * 1024 * 1024;
+
+/// The first line of every cache file, holding `FORMAT` and `PARSERS`
+fn header() -> String {
+    format!("spidior-cache {} parsers {}", FORMAT, PARSERS)
+}
+
+/// Hashes bytes with 128 bit FNV-1a, which gives the same hash in every
+/// build and on every platform, unlike the standard library's hasher
+fn fnv(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(0x6c62272e07bb014262b821756295c58d, |hash, &x| (hash ^ x as u128).wrapping_mul(0x0000000001000000000000000000013b))
+}
+
+/// What a run does with a file whose parse lost track of its structure,
+/// which is any with `diagnostics`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Keeps whatever the parse found past where it lost track
+    Lenient,
+    /// Reports each file that lost track and fails the run, before it
+    /// matches anything
+    Strict,
+}
+
+impl FromStr for Strictness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lenient" => Ok(Strictness::Lenient),
+            "strict" => Ok(Strictness::Strict),
+            _ => Err(format!("Unknown strictness `{}`, expected `lenient` or `strict`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Strictness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strictness::Lenient => write!(f, "lenient"),
+            Strictness::Strict => write!(f, "strict"),
+        }
+    }
+}
+
+/// Everything the query engine and replacer need from a file, which is
+/// what the cache stores
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parsed {
+    /// The identifiers in the file, with their lines, columns, and scopes
+    pub identifiers: Vec<Identifier>,
+    /// The functions in the file, with their lines and columns
+    pub functions: Vec<Function>,
+    /// The imports in the file
+    pub imports: Vec<Import>,
+    /// The calls in the file, other than those within comments, literals,
+    /// and disabled regions
+    pub calls: Vec<Call>,
+    /// The type aliases in the file, with their lines, columns, and scopes
+    pub aliases: Vec<Identifier>,
+    /// The comments, literals, and disabled regions in the file
+    pub spans: Vec<Span>,
+    /// The scopes in the file
+    pub scopes: ScopeTree,
+    /// Where the parse lost track of the file's structure, past which the
+    /// rest may be wrong
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Parsed {
+    /// Parses a piece of code
+    ///
+    /// The regions `lang` finds in another language, like the `<script>`
+    /// elements of an HTML page, are blanked out of the code `lang` parses,
+    /// and parsed as their own language, if a builtin one has its name, with
+    /// the results merged in. The scopes of a region are nested within the
+    /// scope it is in.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    /// * `lang` - The language to parse `text` as
+    pub fn new(text: &str, lang: &dyn Language) -> Self {
+        // A region of the whole text in its own language would never end
+        let regions: Vec<_> = lang
+            .regions(text)
+            .into_iter()
+            .filter(|x| x.end - x.start < text.len() && text.is_char_boundary(x.start) && text.is_char_boundary(x.end))
+            .collect();
+        if regions.is_empty() {
+            return Self::parse(text, lang);
+        }
+        let mut outer = text.as_bytes().to_vec();
+        for region in &regions {
+            for x in &mut outer[region.start..region.end] {
+                // Regions hold whole characters, every byte of which becomes
+                // a space, so this stays UTF-8
+                if *x != b'\n' {
+                    *x = b' ';
+                }
+            }
+        }
+        let mut parsed = Self::parse(std::str::from_utf8(&outer).unwrap(), lang);
+        let registry = Registry::builtin();
+        for region in regions {
+            if let Some(inner) = registry.by_name(&region.lang) {
+                parsed.embed(region.start, Self::new(&text[region.start..region.end], inner));
+            }
+        }
+        // Columns count characters, which blanking out changed
+        parsed.identifiers = locate(text, parsed.identifiers);
+        parsed.aliases = locate(text, parsed.aliases);
+        parsed.functions = locate(text, parsed.functions);
+        parsed.diagnostics = locate(text, parsed.diagnostics);
+        parsed
+    }
+
+    /// Parses a piece of code as one language, as `new` does for code
+    /// without regions
+    fn parse(text: &str, lang: &dyn Language) -> Self {
+        let functions = locate(text, lang.read_functions(text));
+        let scopes = ScopeTree::build(text, lang, &functions);
+        let spans = lang.spans(text);
+        let calls = lang.read_calls(text, &functions).into_iter().filter(|x| !spans.iter().any(|s| s.start <= x.start && x.start < s.end)).collect();
+        Self {
+            identifiers: scopes.assign(locate(text, lang.read_identifiers(text))),
+            imports: lang.read_imports(text),
+            calls,
+            aliases: scopes.assign(locate(text, lang.read_aliases(text))),
+            spans,
+            functions,
+            scopes,
+            diagnostics: locate(text, lang.diagnose(text)),
+        }
+    }
+
+    /// Sums up what the parse found, like `3 functions, 40 identifiers, 2
+    /// comments`, so that a file parsed as the wrong language stands out
+    pub fn summary(&self) -> String {
+        let count = |n: usize, noun: &str| format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" });
+        let comments = self.spans.iter().filter(|x| x.kind == SpanKind::Comment).count();
+        format!(
+            "{}, {}, {}, {}",
+            count(self.functions.len(), "function"),
+            count(self.identifiers.len(), "identifier"),
+            count(self.imports.len(), "import"),
+            count(comments, "comment")
+        )
+    }
+
+    /// Merges in the results of parsing a region starting at `offset`,
+    /// moving them to where the region is
+    fn embed(&mut self, offset: usize, inner: Parsed) {
+        let parent = self.scopes.innermost(offset);
+        let base = self.scopes.iter().count();
+        let mut scopes: Vec<Scope> = self.scopes.iter().cloned().collect();
+        scopes.extend(inner.scopes.iter().map(|x| Scope {
+            id: base + x.id,
+            parent: Some(x.parent.map_or(parent, |p| base + p)),
+            start: offset + x.start,
+            end: offset + x.end,
+        }));
+        self.scopes = ScopeTree::new(scopes);
+        let ident = |mut x: Identifier| {
+            (x.start, x.end, x.scope_id) = (offset + x.start, offset + x.end, base + x.scope_id);
+            x
+        };
+        self.identifiers.extend(inner.identifiers.into_iter().map(ident));
+        self.aliases.extend(inner.aliases.into_iter().map(ident));
+        self.functions.extend(inner.functions.into_iter().map(|mut x| {
+            (x.start, x.body, x.end) = (offset + x.start, offset + x.body, offset + x.end);
+            x
+        }));
+        self.diagnostics.extend(inner.diagnostics.into_iter().map(|mut x| {
+            x.start += offset;
+            x
+        }));
+        self.diagnostics.sort_by_key(|x| x.start);
+        self.imports.extend(inner.imports.into_iter().map(|mut x| {
+            (x.start, x.end) = (offset + x.start, offset + x.end);
+            x
+        }));
+        self.calls.extend(inner.calls.into_iter().map(|mut x| {
+            (x.start, x.open, x.end) = (offset + x.start, offset + x.open, offset + x.end);
+            for arg in &mut x.args {
+                *arg = (offset + arg.0, offset + arg.1);
+            }
+            x
+        }));
+        self.spans.extend(inner.spans.into_iter().map(|x| Span {
+            start: offset + x.start,
+            end: offset + x.end,
+            ..x
+        }));
+        self.identifiers.sort_by_key(|x| x.start);
+        self.aliases.sort_by_key(|x| x.start);
+        self.functions.sort_by_key(|x| x.start);
+        self.imports.sort_by_key(|x| x.start);
+        self.calls.sort_by_key(|x| x.start);
+        self.spans.sort_by_key(|x| x.start);
+    }
+
+    /// Writes the parse results in the cache's format, which is a line for
+    /// each item, holding a letter for its kind and its tab separated fields
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The code the results were parsed from, whose length and
+    ///   hash are written after the header to be checked on reading
+    fn encode(&self, text: &str) -> String {
+        let mut out = format!("{}\n", header());
+        let mut line = |kind: &str, fields: Vec<String>| {
+            out += kind;
+            for field in fields {
+                out.push('\t');
+                out += &escape(&field);
+            }
+            out.push('\n');
+        };
+        let ident = |x: &Identifier| {
+            vec![
+                x.name.clone(),
+                x.typ.clone(),
+                x.start.to_string(),
+                x.end.to_string(),
+                x.line.to_string(),
+                x.column.to_string(),
+                x.scope_id.to_string(),
+            ]
+        };
+        line("t", vec![text.len().to_string(), format!("{:032x}", fnv(text.as_bytes()))]);
+        for x in &self.identifiers {
+            line("i", ident(x));
+        }
+        for x in &self.aliases {
+            line("a", ident(x));
+        }
+        for x in &self.functions {
+            let mut fields = vec![
+                x.name.clone(),
+                x.typ.clone(),
+                x.start.to_string(),
+                x.body.to_string(),
+                x.end.to_string(),
+                x.line.to_string(),
+                x.column.to_string(),
+            ];
+            for p in &x.params {
+                fields.push(p.name.clone());
+                fields.push(p.typ.clone());
+            }
+            line("f", fields);
+        }
+        for x in &self.imports {
+            let alias = x.alias.clone().unwrap_or_default();
+            line("m", vec![x.path.clone(), alias, x.start.to_string(), x.end.to_string()]);
+        }
+        for x in &self.calls {
+            let mut fields = vec![x.name.clone(), x.start.to_string(), x.open.to_string(), x.end.to_string()];
+            for (start, end) in &x.args {
+                fields.push(start.to_string());
+                fields.push(end.to_string());
+            }
+            line("k", fields);
+        }
+        for x in &self.spans {
+            line("s", vec![format!("{:?}", x.kind), x.start.to_string(), x.end.to_string()]);
+        }
+        for x in self.scopes.iter() {
+            let parent = x.parent.map(|x| x.to_string()).unwrap_or_default();
+            line("c", vec![parent, x.start.to_string(), x.end.to_string()]);
+        }
+        for x in &self.diagnostics {
+            line("d", vec![x.message.clone(), x.start.to_string(), x.line.to_string(), x.column.to_string()]);
+        }
+        out
+    }
+
+    /// Reads parse results written by `encode`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The results, as `encode` wrote them
+    /// * `code` - The code the results should have been parsed from
+    ///
+    /// # Returns
+    ///
+    /// The parse results, or None if `text` is not in the cache's format or
+    /// was parsed from other code than `code`
+    fn decode(text: &str, code: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        if lines.next() != Some(header().as_str()) {
+            return None;
+        }
+        // A different file whose key collided with `code`'s is never used
+        let parsed_from = format!("t\t{}\t{:032x}", code.len(), fnv(code.as_bytes()));
+        if lines.next() != Some(parsed_from.as_str()) {
+            return None;
+        }
+        let mut parsed = Self {
+            identifiers: Vec::new(),
+            functions: Vec::new(),
+            imports: Vec::new(),
+            calls: Vec::new(),
+            aliases: Vec::new(),
+            spans: Vec::new(),
+            scopes: ScopeTree::new(Vec::new()),
+            diagnostics: Vec::new(),
+        };
+        let mut scopes = Vec::new();
+        for line in lines {
+            let mut fields = line.split('\t');
+            let kind = fields.next()?;
+            let fields: Vec<String> = fields.map(unescape).collect();
+            let n = |i: usize| fields.get(i)?.parse::<usize>().ok();
+            match kind {
+                "i" | "a" if fields.len() == 7 => {
+                    let mut x = Identifier::new(fields[0].clone(), fields[1].clone(), n(2)?, n(3)?);
+                    (x.line, x.column, x.scope_id) = (n(4)?, n(5)?, n(6)?);
+                    if kind == "i" {
+                        parsed.identifiers.push(x);
+                    } else {
+                        parsed.aliases.push(x);
+                    }
+                }
+                "f" if fields.len() >= 7 && fields.len() % 2 == 1 => {
+                    let params = fields[7..].chunks(2).map(|p| Parameter::new(p[0].clone(), p[1].clone())).collect();
+                    let mut x = Function::new(fields[0].clone(), fields[1].clone(), params, n(2)?, n(3)?, n(4)?);
+                    (x.line, x.column) = (n(5)?, n(6)?);
+                    parsed.functions.push(x);
+                }
+                "m" if fields.len() == 4 => {
+                    let alias = Some(fields[1].clone()).filter(|x| !x.is_empty());
+                    parsed.imports.push(Import::new(fields[0].clone(), alias, n(2)?, n(3)?));
+                }
+                "k" if fields.len() >= 4 && fields.len().is_multiple_of(2) => {
+                    let args = (4..fields.len()).step_by(2).map(|i| Some((n(i)?, n(i + 1)?))).collect::<Option<_>>()?;
+                    parsed.calls.push(Call::new(fields[0].clone(), n(1)?, n(2)?, n(3)?, args));
+                }
+                "s" if fields.len() == 3 => {
+                    let kind = match fields[0].as_str() {
+                        "Comment" => SpanKind::Comment,
+                        "String" => SpanKind::String,
+                        "Char" => SpanKind::Char,
+                        "Directive" => SpanKind::Directive,
+                        "Disabled" => SpanKind::Disabled,
+                        "Attribute" => SpanKind::Attribute,
+                        _ => return None,
+                    };
+                    parsed.spans.push(Span {
+                        kind,
+                        start: n(1)?,
+                        end: n(2)?,
+                    });
+                }
+                "c" if fields.len() == 3 => scopes.push(Scope {
+                    id: scopes.len(),
+                    parent: if fields[0].is_empty() { None } else { Some(n(0)?) },
+                    start: n(1)?,
+                    end: n(2)?,
+                }),
+                "d" if fields.len() == 4 => {
+                    let mut x = Diagnostic::new(fields[0].clone(), n(1)?);
+                    (x.line, x.column) = (n(2)?, n(3)?);
+                    parsed.diagnostics.push(x);
+                }
+                _ => return None,
+            }
+        }
+        parsed.scopes = ScopeTree::new(scopes);
+        Some(parsed)
+    }
+}
+
+/// Escapes the characters the cache's format uses as separators
+fn escape(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+/// Reverses `escape`
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(x) => out.push(x),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// A directory of parse results, each stored under a hash of the code it
+/// was parsed from, the language it was parsed as, and any words the config
+/// file added to that language, so a file is only parsed again once it or
+/// the way it is parsed changes. The directory is kept under `LIMIT` bytes
+/// by removing the results used least recently.
+pub struct ParseCache {
+    /// The directory the results are stored in, or None to parse every file
+    dir: Option<PathBuf>,
+}
+
+impl ParseCache {
+    /// Creates a ParseCache that stores its results in a directory, which is
+    /// created when the first result is stored, first removing the results
+    /// used least recently if the directory holds more than `LIMIT` bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to store results in, or None to store nothing
+    ///   and parse every file
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        let cache = Self { dir };
+        cache.evict(LIMIT);
+        cache
+    }
+
+    /// Removes the results used least recently, by when they were last
+    /// written or read, until the directory holds at most `limit` bytes.
+    /// Failures are ignored, as for the rest of the cache.
+    fn evict(&self, limit: u64) {
+        let entries = match self.dir.as_ref().and_then(|x| fs::read_dir(x).ok()) {
+            Some(x) => x,
+            None => return,
+        };
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+            .filter_map(|x| {
+                let x = x.ok()?;
+                let metadata = x.metadata().ok().filter(|x| x.is_file())?;
+                Some((metadata.modified().ok()?, metadata.len(), x.path()))
+            })
+            .collect();
+        let mut total: u64 = files.iter().map(|x| x.1).sum();
+        files.sort();
+        for (_, len, path) in files {
+            if total <= limit {
+                break;
+            }
+            if fs::remove_file(path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+
+    /// The directory results are stored in by default, which is `spidior`
+    /// within `$XDG_CACHE_HOME`, or within `~/.cache` if that is not set
+    pub fn default_dir() -> Option<PathBuf> {
+        match std::env::var_os("XDG_CACHE_HOME").filter(|x| !x.is_empty()) {
+            Some(x) => Some(PathBuf::from(x).join("spidior")),
+            None => std::env::var_os("HOME").map(|x| Path::new(&x).join(".cache").join("spidior")),
+        }
+    }
+
+    /// The key a piece of code's results are stored under, which is the
+    /// same from one build to the next
+    fn key(text: &str, lang: &dyn Language) -> String {
+        let mut bytes = Vec::with_capacity(text.len() + 64);
+        // Each part but the last is followed by a NUL, which none hold, so
+        // that no two sets of parts run together the same way
+        for part in [env!("CARGO_PKG_VERSION"), &header(), lang.name(), &keywords::fingerprint(lang.name())] {
+            bytes.extend_from_slice(part.as_bytes());
+            bytes.push(0);
+        }
+        bytes.extend_from_slice(text.as_bytes());
+        format!("{:032x}", fnv(&bytes))
+    }
+
+    /// Parses a piece of code, or reads its results from the cache if it
+    /// was parsed before, storing them if it was not
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    /// * `lang` - The language to parse `text` as
+    pub fn parse(&self, text: &str, lang: &dyn Language) -> Parsed {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Parsed::new(text, lang),
+        };
+        let path = dir.join(Self::key(text, lang));
+        if let Some(parsed) = fs::read_to_string(&path).ok().and_then(|x| Parsed::decode(&x, text)) {
+            // Marks the results as used, so they are the last to be evicted
+            let _ = fs::File::options().write(true).open(&path).and_then(|x| x.set_modified(SystemTime::now()));
+            return parsed;
+        }
+        let parsed = Parsed::new(text, lang);
+        // A cache that cannot be written to only costs time, so failures
+        // are ignored. Results are written to a temporary file first, so
+        // that a run reading the cache never sees half of them.
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let stored = fs::create_dir_all(dir).and_then(|_| fs::write(&temp, parsed.encode(text))).and_then(|_| fs::rename(&temp, &path));
+        if stored.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        parsed
+    }
+
+    /// Parses many pieces of code at once, spread across a thread for each
+    /// available core
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The code to parse, each with the language to parse it as
+    ///
+    /// # Returns
+    ///
+    /// The results for each piece of code, in the order of `files`
+    pub fn parse_all(&self, files: &[(&str, &dyn Language)]) -> Vec<Parsed> {
+        self.parse_all_timed(files).into_iter().map(|(parsed, _)| parsed).collect()
+    }
+
+    /// Parses many pieces of code at once, as `parse_all` does, timing how
+    /// long each took to parse, or to read from the cache
+    ///
+    /// # Returns
+    ///
+    /// The results for each piece of code, with how long it took, in the
+    /// order of `files`
+    pub fn parse_all_timed(&self, files: &[(&str, &dyn Language)]) -> Vec<(Parsed, Duration)> {
+        let threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+        let chunk = files.len().div_ceil(threads).max(1);
+        let timed = |text: &str, lang: &dyn Language| {
+            let start = Instant::now();
+            let parsed = self.parse(text, lang);
+            (parsed, start.elapsed())
+        };
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = files
+                .chunks(chunk)
+                .map(|x| scope.spawn(move || x.iter().map(|(text, lang)| timed(text, *lang)).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|x| x.join().unwrap()).collect()
+        })
+    }
+}
+
+#[test]
+fn test_cache() {
+    use super::clike::Clike;
+    let dir = std::env::temp_dir().join(format!("spidior-test-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let text = "import java.util.List;\nclass A {\n    void go(int\ttab) { String s = \"x\"; s.go(); }\n}\n}\n";
+    let cache = ParseCache::new(Some(dir.clone()));
+    let parsed = cache.parse(text, &Clike {});
+    assert_eq!(parsed, Parsed::new(text, &Clike {}));
+    assert_eq!(parsed.diagnostics, vec![Diagnostic { message: "`}` closes no block".to_string(), start: 84, line: 5, column: 1 }]);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    // Read back from the cache, rather than parsed again
+    assert_eq!(cache.parse(text, &Clike {}), parsed);
+    let all = cache.parse_all(&[(text, &Clike {}), ("int x;", &Clike {})]);
+    assert_eq!(all[0], parsed);
+    assert_eq!(all[1].identifiers[0].name, "x");
+    assert_eq!(parsed.summary(), "1 function, 4 identifiers, 1 import, 0 comments");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cache_checks() {
+    use super::clike::Clike;
+    let dir = std::env::temp_dir().join(format!("spidior-test-cache-checks-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    // Keys do not depend on the build, or hashing would not be stable
+    assert_eq!(format!("{:032x}", fnv(b"a")), "d228cb696f1a8caf78912b704e4a8964");
+    let cache = ParseCache::new(Some(dir.clone()));
+    let (a, b) = ("int a;", "int b;");
+    cache.parse(a, &Clike {});
+    // The results of other code stored under `b`'s key, as a collision
+    // would leave them, are parsed again rather than used
+    fs::copy(dir.join(ParseCache::key(a, &Clike {})), dir.join(ParseCache::key(b, &Clike {}))).unwrap();
+    assert_eq!(cache.parse(b, &Clike {}).identifiers[0].name, "b");
+    // Results of another version of the parsers are ignored
+    let path = dir.join(ParseCache::key(a, &Clike {}));
+    let stored = fs::read_to_string(&path).unwrap();
+    assert!(Parsed::decode(&stored, a).is_some());
+    assert!(Parsed::decode(&stored.replacen(&header(), "spidior-cache 7 parsers 1", 1), a).is_none());
+    assert!(Parsed::decode(&stored, "int a; ").is_none());
+    // The oldest results are evicted first
+    let old = SystemTime::now() - Duration::from_secs(60);
+    fs::File::options().write(true).open(&path).unwrap().set_modified(old).unwrap();
+    let len = fs::metadata(&path).unwrap().len();
+    cache.evict(len);
+    assert!(!path.exists());
+    assert!(dir.join(ParseCache::key(b, &Clike {})).exists());
+    cache.evict(0);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
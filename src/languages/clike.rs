@@ -1,232 +1,1502 @@
 //! Provides the parser for "c-like" languages, including C and Java
 
-use super::parsing::{Function, Functions, Identifier, Identifiers};
-use std::collections::HashMap;
+use super::parsing::{import_aliases, Aliases, Class, Classes, Diagnostic, Function, Functions, Identifier, Identifiers, Import, Imports, Parameter};
+use super::tokens::{self, Scopes, TokenKind, Tokenizer};
+use super::{keywords, lexer, CommentSyntax, Language};
+use std::collections::{HashMap, HashSet};
 
 /// A Functions and Identifiers parser for Clike languages,
-/// including C, C++, and Java.
+/// including C, C++, and Java. Comments, string and char literals,
+/// annotations and attributes, and preprocessor directives are blanked out
+/// before parsing, so nothing inside them is seen as code.
 pub struct Clike {}
 
+/// The type of lambda parameters whose type is not written down
+const INFERRED: &str = "_";
+
+/// The preprocessor directives of C, C++, Objective-C, and C#
+const DIRECTIVES: &[&str] = &[
+    "include", "import", "define", "undef", "if", "ifdef", "ifndef", "elif", "else", "endif", "error", "warning",
+    "pragma", "line", "region", "endregion", "nullable",
+];
+
 impl Clike {
-    fn is_allowed(x: &str) -> bool {
-        !vec![
-            "public",
-            "package",
-            "private",
-            "protected",
-            "import",
-            "void",
-            "true",
-            "false",
-            "extends",
-        ]
-        .contains(&x)
+    /// Replaces comments, literals, annotations, attributes, and preprocessor
+    /// directives with spaces, so that neither FSM sees their contents
+    fn strip(text: &str) -> String {
+        let text = lexer::blank(text, &lexer::lex(text, CommentSyntax::C));
+        let text = lexer::blank(&text, &lexer::attributes(&text, false));
+        let directives: Vec<lexer::Span> = Self::directives(&text)
+            .into_iter()
+            .map(|(_, start, end)| lexer::Span { kind: lexer::SpanKind::Directive, start, end })
+            .collect();
+        lexer::blank(&text, &directives)
     }
-}
 
-enum FunctionFsm {
-    NAME,
-    SPACE,
-    BRACE,
-    NONE,
-    PARENS(i32),
-}
+    /// Finds the annotations and attributes in code, which `strip` blanks
+    fn attributes(text: &str) -> Vec<lexer::Span> {
+        lexer::attributes(&lexer::blank(text, &lexer::lex(text, CommentSyntax::C)), false)
+    }
 
-impl Functions for Clike {
-    /// Parses out function declarations from c-like code
+    /// Finds where a declaration starts, given where the statement before
+    /// it ends and where its first word is, so that the declaration includes
+    /// any annotations or attributes written before it
+    fn declaration_start(attributes: &[lexer::Span], after: usize, first: usize) -> usize {
+        attributes
+            .iter()
+            .find(|x| x.start >= after && x.start < first)
+            .map_or(first, |x| x.start)
+    }
+
+    /// The character starting at byte `i` of `text`, if any
+    fn char_at(text: &str, i: usize) -> Option<char> {
+        text.get(i..)?.chars().next()
+    }
+
+    /// The number of bytes in the run of characters starting at byte `i` of
+    /// `text` that all satisfy `f`
+    fn span(text: &str, i: usize, f: impl Fn(char) -> bool) -> usize {
+        text[i..].find(|x| !f(x)).unwrap_or(text.len() - i)
+    }
+
+    /// The identifier starting at byte `i` of `text`, which is empty if
+    /// there is none
+    fn word_at(text: &str, i: usize) -> &str {
+        &text[i..i + Self::span(text, i, lexer::is_ident_char)]
+    }
+
+    /// The byte index just past the identifier starting at `i`, or just past
+    /// the character at `i` if it does not start one
+    fn next_word(text: &str, i: usize) -> usize {
+        match Self::word_at(text, i).len() {
+            0 => i + Self::char_at(text, i).map_or(1, char::len_utf8),
+            len => i + len,
+        }
+    }
+
+    /// The byte index just past the last character of `text` that satisfies `f`
+    fn after_last(text: &str, f: impl Fn(char) -> bool) -> Option<usize> {
+        text.char_indices().rev().find(|(_, x)| f(*x)).map(|(i, x)| i + x.len_utf8())
+    }
+
+    /// Whether the identifier starting at byte `i` of `text` is a whole word,
+    /// rather than the tail of a longer name or a member like `a.b` or `@b`
+    fn starts_word(text: &str, i: usize) -> bool {
+        !text[..i].chars().next_back().is_some_and(|x| lexer::is_ident_char(x) || x == '.' || x == '@')
+    }
+
+    /// Finds the preprocessor directives in code whose comments and literals
+    /// have been blanked, which are lines starting with `#` and a known
+    /// directive name, so that JavaScript's `#private` fields are left alone
+    ///
+    /// # Returns
+    ///
+    /// The name, start, and end of each directive, in order, where a
+    /// directive ends at the end of the last line it is continued onto
+    fn directives(text: &str) -> Vec<(String, usize, usize)> {
+        let blank = |x: char| x == ' ' || x == '\t';
+        let mut v = Vec::new();
+        let mut i = 0;
+        while i < text.len() {
+            let line_end = |x: usize| text[x..].find('\n').map_or(text.len(), |c| x + c);
+            let hash = i + Self::span(text, i, blank);
+            let mut end = line_end(i);
+            if text[hash..].starts_with('#') {
+                let name_start = hash + 1 + Self::span(text, hash + 1, blank);
+                let name = &text[name_start..name_start + Self::span(text, name_start, char::is_alphabetic)];
+                if DIRECTIVES.contains(&name) {
+                    while end < text.len() && text[..end].trim_end().ends_with('\\') {
+                        end = line_end(end + 1);
+                    }
+                    v.push((name.to_string(), hash, end));
+                }
+            }
+            i = end + 1;
+        }
+        v
+    }
+
+    /// Finds the code disabled by `#if 0` or `#if false`, up to the matching
+    /// `#elif`, `#else`, or `#endif`
     ///
     /// # Arguments
     ///
-    /// * `text` - A string slice that contains the code to be parsed
+    /// * `text` - A string slice that contains the code
     ///
     /// # Returns
     ///
-    /// A Vec of Function containing information on every function
-    /// declared within text
-    fn read_functions(&self, text: &str) -> Vec<Function> {
-        let mut s = FunctionFsm::NONE;
-        let mut start = 0;
-        let mut end = 0;
+    /// A Vec of `Disabled` Span, one for each disabled region, in order
+    pub(crate) fn disabled(text: &str) -> Vec<lexer::Span> {
+        let code = lexer::blank(text, &lexer::lex(text, CommentSyntax::C));
+        let span = |start, end| lexer::Span { kind: lexer::SpanKind::Disabled, start, end };
         let mut v = Vec::new();
-        for (i, c) in text.chars().enumerate() {
-            match s {
-                FunctionFsm::NONE => {
-                    if c.is_alphanumeric() {
-                        s = FunctionFsm::NAME;
-                        start = i;
+        let mut depth = 0;
+        // The depth of the `#if 0` being skipped, and where its body starts
+        let mut off: Option<(usize, usize)> = None;
+        for (name, start, end) in Self::directives(&code) {
+            match name.as_str() {
+                "if" | "ifdef" | "ifndef" => {
+                    depth += 1;
+                    let after = start + 1 + Self::span(&code, start + 1, char::is_whitespace) + name.len();
+                    let condition: String = code[after..end].chars().filter(|x| !x.is_whitespace()).collect();
+                    if off.is_none() && name == "if" && ["0", "(0)", "false", "(false)"].contains(&condition.as_str()) {
+                        off = Some((depth, end));
                     }
                 }
-                FunctionFsm::NAME => {
-                    if c == '(' {
-                        s = FunctionFsm::PARENS(1);
-                        end = i;
-                    } else if !c.is_alphanumeric() {
-                        s = FunctionFsm::NONE;
+                "elif" | "else" | "endif" => {
+                    if let Some((_, body)) = off.filter(|(d, _)| *d == depth) {
+                        v.push(span(body, start));
+                        off = None;
                     }
+                    if name == "endif" {
+                        depth -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some((_, body)) = off {
+            v.push(span(body, code.len()));
+        }
+        v
+    }
+
+    /// Finds every definition and use of a preprocessor macro, which are
+    /// the names given to `#define`, `#undef`, `#ifdef`, `#ifndef`, and
+    /// `defined`, wherever they appear outside of comments and literals
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier of type `macro`, in order
+    fn macros(text: &str) -> Vec<Identifier> {
+        let code = lexer::blank(text, &lexer::lex(text, CommentSyntax::C));
+        let word_at = |i: usize| Self::word_at(&code, i).to_string();
+        let skip = |i: usize, also: &str| i + Self::span(&code, i, |x| x.is_whitespace() || also.contains(x));
+        let directives = Self::directives(&code);
+        let mut names = HashSet::new();
+        for (name, start, end) in &directives {
+            let after = skip(start + 1, "") + name.len();
+            match name.as_str() {
+                "define" | "undef" | "ifdef" | "ifndef" => {
+                    names.insert(word_at(skip(after, "")));
                 }
-                FunctionFsm::PARENS(j) => {
-                    if c == '(' {
-                        s = FunctionFsm::PARENS(j + 1);
-                    } else if c == ')' {
-                        s = FunctionFsm::PARENS(j - 1);
-                    } else if c.is_whitespace() && j == 0 {
-                        s = FunctionFsm::SPACE;
-                    } else if c == '{' && j == 0 {
-                        s = FunctionFsm::BRACE;
+                "if" | "elif" => {
+                    let mut i = after;
+                    while i < *end {
+                        let word = word_at(i);
+                        if word == "defined" {
+                            names.insert(word_at(skip(i + word.len(), "(")));
+                        }
+                        i = Self::next_word(&code, i);
                     }
                 }
-                FunctionFsm::SPACE => {
-                    if c.is_alphanumeric() {
-                        s = FunctionFsm::NAME;
-                        start = i;
-                    } else if c == '{' {
-                        s = FunctionFsm::BRACE;
-                    } else if !c.is_whitespace() {
-                        s = FunctionFsm::NONE;
+                _ => {}
+            }
+        }
+        names.remove("");
+        let mut v = Vec::new();
+        if names.is_empty() {
+            return v;
+        }
+        // Include paths are not code, even when unquoted
+        let includes: Vec<(usize, usize)> =
+            directives.iter().filter(|x| x.0 == "include" || x.0 == "import").map(|x| (x.1, x.2)).collect();
+        let mut i = 0;
+        while i < code.len() {
+            let word = word_at(i);
+            let boundary = !code[..i].ends_with("->") && !code[..i].chars().next_back().is_some_and(|x| lexer::is_ident_char(x) || x == '.');
+            if boundary && names.contains(&word) && !includes.iter().any(|(s, e)| *s <= i && i < *e) {
+                v.push(Identifier::new(word.clone(), "macro".to_string(), i, i + word.len()));
+            }
+            i = Self::next_word(&code, i);
+        }
+        v
+    }
+
+    /// Finds the end of a type suffix starting at `i`, which is either a
+    /// balanced generic argument list like `<String, List<Foo>>` or one or
+    /// more array brackets like `[]`
+    ///
+    /// # Returns
+    ///
+    /// The index just past the suffix, or `None` if there is no suffix at `i`
+    fn type_suffix(text: &str, i: usize) -> Option<usize> {
+        let mut depth = 0;
+        let mut end = None;
+        for (j, c) in text[i..].char_indices() {
+            let j = i + j;
+            match c {
+                '<' => depth += 1,
+                '>' if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(j + 1);
+                        break;
                     }
                 }
-                FunctionFsm::BRACE => {
-                    v.push(Function::new(text[start..end].to_string()));
-                    s = FunctionFsm::NONE;
+                '[' if depth == 0 => {
+                    let close = j + 1 + Self::span(text, j + 1, char::is_whitespace);
+                    if !text[close..].starts_with(']') {
+                        break;
+                    }
+                    end = Some(close + 1);
+                    return Self::type_suffix(text, close + 1).or(end);
                 }
+                c if depth > 0
+                    && (lexer::is_ident_char(c) || c.is_whitespace() || ",.?&*:[]".contains(c)) => {}
+                _ => break,
             }
         }
+        let end = end?;
+        Self::type_suffix(text, end).or(Some(end))
+    }
+
+    /// Collapses the whitespace within a type, so that `Map<String ,Foo >`
+    /// and `Map<String, Foo>` are recorded the same way
+    fn normalize_type(typ: &str) -> String {
+        let mut out = String::with_capacity(typ.len());
+        for word in typ.split_whitespace() {
+            let glue = out.ends_with(|x| "<[".contains(x)) || word.starts_with(|x| "<>,[]".contains(x));
+            if !out.is_empty() && !glue {
+                out.push(' ');
+            }
+            out += word;
+        }
+        out.replace(",", ", ").replace(",  ", ", ")
+    }
+
+    /// Whether the word `next` continues a type whose last word is `prev`,
+    /// as in `const char`, `unsigned long long`, or `Foo const`, rather
+    /// than being the name being declared
+    fn continues_type(prev: &str, next: &str) -> bool {
+        const QUALIFIERS: &[&str] = &["const", "volatile", "struct", "enum", "union", "typename", "register", "mutable"];
+        const SIZES: &[&str] = &["unsigned", "signed", "long", "short"];
+        const PRIMITIVES: &[&str] = &["long", "short", "int", "char", "double"];
+        QUALIFIERS.contains(&prev)
+            || (SIZES.contains(&prev) && PRIMITIVES.contains(&next))
+            || next == "const"
+            || next == "volatile"
+    }
+
+    /// Whether a `*` or `&` after the type starting at `start` makes a
+    /// pointer or reference declarator, rather than being an operator.
+    /// This is only the case at the start of a statement or parameter,
+    /// and never in an `if`, `while`, or `switch` condition.
+    fn starts_declaration(text: &str, start: usize) -> bool {
+        let before = text[..start].trim_end();
+        match before.chars().last() {
+            None | Some(';') | Some('{') | Some('}') | Some(',') => true,
+            Some('(') => {
+                let word = before[..before.len() - 1]
+                    .trim_end()
+                    .rsplit(|x: char| !lexer::is_ident_char(x))
+                    .next()
+                    .unwrap_or("");
+                !["if", "while", "switch", "return", "sizeof"].contains(&word)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the `,` at `i` is followed by another declarator of the same
+    /// declaration, as in `int a, *b`, rather than by a new declaration with
+    /// its own type, as in `f(int a, int b)`
+    fn declarator_follows(text: &str, i: usize) -> bool {
+        let declarator = |x: char| x.is_whitespace() || x == '*' || x == '&';
+        let mut j = i + 1 + Self::span(text, i + 1, declarator);
+        if !Self::char_at(text, j).is_some_and(lexer::is_ident_start) {
+            return false;
+        }
+        j += Self::span(text, j, lexer::is_ident_char);
+        j += Self::span(text, j, declarator);
+        !Self::char_at(text, j).is_some_and(|x| lexer::is_ident_start(x) || x == ':' || x == '<')
+    }
+
+    /// Whether the name from `start` to `end`, which is followed by
+    /// parentheses and a block, declares a function, rather than being a
+    /// control-flow statement like `if (x) {` or a call with a trailing
+    /// block like `list.each(f) {`
+    fn declares_function(text: &str, start: usize, end: usize) -> bool {
+        // Statements with a parenthesized head that are not in the keyword
+        // table, as they are names in some C-like languages
+        const STATEMENTS: &[&str] = &["synchronized", "foreach", "lock", "fixed", "function"];
+        let name = &text[start..end];
+        if Self::is_keyword(name) || STATEMENTS.contains(&name) {
+            return false;
+        }
+        let before = text[..start].trim_end();
+        let word = before.rsplit(|x: char| !lexer::is_ident_char(x)).next().unwrap_or("");
+        let call = ["return", "new", "throw", "await", "yield", "else", "case", "delete", "typeof", "do"];
+        // `::` qualifies a declared name, as in `Foo::bar`, but a lone `:`
+        // ends a label or starts a constructor's initializer list
+        let qualified = before.ends_with("::");
+        !(call.contains(&word)
+            || before.ends_with("->")
+            || (before.ends_with(':') && !qualified)
+            || before.ends_with(['.', '=', '(', ',', '[', '!', '?', '|', '+', '-', '/', '%', '^', '~']))
+    }
+
+    /// Finds the Java-style lambdas, like `(a, b) -> a + b` and `x -> { ... }`,
+    /// in code that has been stripped. C++ lambdas need no special handling,
+    /// as their parameters are typed and their bodies are always blocks.
+    fn lambdas(text: &str) -> Vec<Lambda> {
+        let mut v = Vec::new();
+        for (arrow, _) in text.match_indices("->") {
+            let before = text[..arrow].trim_end();
+            let mut untyped = Vec::new();
+            let start = if before.ends_with(')') {
+                let mut depth = 0;
+                let open = before.char_indices().rev().find(|&(_, c)| {
+                    match c {
+                        ')' => depth += 1,
+                        '(' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                });
+                let Some((open, _)) = open else { continue };
+                // A call or declaration, like `f() -> int` or `[](int a) -> int`
+                let prev = before[..open].trim_end().chars().next_back();
+                if prev.is_some_and(|x| lexer::is_ident_char(x) || x == ']' || x == '>') {
+                    continue;
+                }
+                // Typed parameters are declared as usual, so only the
+                // parameters that are a lone name are left
+                let (mut depth, mut from) = (0, open + 1);
+                for (j, c) in before[open + 1..].char_indices() {
+                    let j = open + 1 + j;
+                    match c {
+                        '(' | '<' | '[' => depth += 1,
+                        ')' | '>' | ']' if depth > 0 => depth -= 1,
+                        ',' | ')' if depth == 0 => {
+                            let part = before[from..j].trim_start();
+                            let at = j - part.len();
+                            let part = part.trim_end();
+                            if part.starts_with(lexer::is_ident_start) && part.chars().all(lexer::is_ident_char) {
+                                untyped.push((at, at + part.len()));
+                            }
+                            from = j + 1;
+                        }
+                        _ => {}
+                    }
+                }
+                open
+            } else {
+                let name = Self::after_last(before, |x| !lexer::is_ident_char(x)).unwrap_or(0);
+                let prev = before[..name].trim_end();
+                let statement = &prev[prev.rfind([';', '{', '}']).map_or(0, |x| x + 1)..];
+                // `p->next` in C, or `case A, B ->` in a Java switch
+                let spaced = before.len() < arrow || text[arrow + 2..].starts_with(char::is_whitespace);
+                let follows = prev.is_empty()
+                    || prev.ends_with(['(', ',', '=', '?', ':', '>'])
+                    || prev.rsplit(|x: char| !lexer::is_ident_char(x)).next() == Some("return");
+                if !before[name..].starts_with(lexer::is_ident_start)
+                    || !spaced
+                    || !follows
+                    || statement.split(|x: char| !lexer::is_ident_char(x)).any(|x| x == "case")
+                {
+                    continue;
+                }
+                untyped.push((name, before.len()));
+                name
+            };
+            // A block body gets its frame from its `{`, but an expression
+            // runs to the end of the argument or statement it is in
+            let body = arrow + 2 + Self::span(text, arrow + 2, char::is_whitespace);
+            let mut end = None;
+            if !text[body..].starts_with('{') {
+                let mut depth = 0;
+                let mut stop = text.len();
+                for (j, c) in text[body..].char_indices() {
+                    match c {
+                        '(' | '[' | '{' => depth += 1,
+                        ')' | ']' | '}' if depth > 0 => depth -= 1,
+                        ')' | ']' | '}' | ',' | ';' if depth == 0 => {
+                            stop = body + j;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                end = Some(stop);
+            }
+            v.push(Lambda { start, arrow, untyped, end });
+        }
         v
     }
-}
 
-enum IFsm {
-    NONE,
-    NAME1,
-    SPACE,
-    NAME2,
-    DOT,
-}
+    /// Finds the bodies of anonymous classes, like `new Runnable() { ... }`,
+    /// in code that has been stripped
+    ///
+    /// # Returns
+    ///
+    /// The index each body opens at and the index one past where it closes
+    fn anonymous_classes(text: &str) -> Vec<(usize, usize)> {
+        let skip_space = |i: usize| i + Self::span(text, i, char::is_whitespace);
+        let mut v = Vec::new();
+        let mut i = 0;
+        while i < text.len() {
+            let word = Self::word_at(text, i);
+            i = Self::next_word(text, i);
+            if word != "new" || !Self::starts_word(text, i - word.len()) {
+                continue;
+            }
+            // The type, like `Comparator<String>` or `Outer.Inner`
+            let mut j = skip_space(i);
+            j += Self::span(text, j, |x| lexer::is_ident_char(x) || x == '.');
+            j = Self::type_suffix(text, j).unwrap_or(j);
+            let open = skip_space(j);
+            if j == skip_space(i) || !text[open..].starts_with('(') {
+                continue;
+            }
+            let body = skip_space(Self::matching(text, open, '(', ')'));
+            if text[body..].starts_with('{') {
+                v.push((body, Self::matching(text, body, '{', '}')));
+            }
+        }
+        v
+    }
 
-impl Identifiers for Clike {
-    /// Parses out identifier uses from c-like code
+    /// Parses out the imports of c-like code, as `read_imports` does
     ///
     /// # Arguments
     ///
     /// * `text` - A string slice that contains the code to be parsed
+    /// * `aliases` - Whether `using X = Y;` is an import of `Y` as `X`, as
+    ///   it is in C#, rather than a type alias
+    pub(crate) fn imports(text: &str, aliases: bool) -> Vec<Import> {
+        // Only comments are blanked, as include and module paths are quoted
+        let spans: Vec<lexer::Span> =
+            lexer::lex(text, CommentSyntax::C).into_iter().filter(|x| x.kind == lexer::SpanKind::Comment).collect();
+        let code = lexer::blank(text, &spans);
+        let b = code.as_bytes();
+        let skip_space = |i: usize| i + Self::span(&code, i, char::is_whitespace);
+        let mut v = Vec::new();
+        let mut i = 0;
+        while i < code.len() {
+            let word = Self::word_at(&code, i);
+            if !Self::starts_word(&code, i) || word.is_empty() {
+                i = Self::next_word(&code, i);
+                continue;
+            }
+            let at_line_start = |x: usize| code[..x].rsplit('\n').next().unwrap_or("").trim().is_empty();
+            let before = code[..i].rfind(|x: char| !x.is_whitespace());
+            // `#include <stdio.h>`, `#import "Foo.h"`
+            let directive = before.filter(|&x| b[x] == b'#' && at_line_start(x));
+            if let Some(hash) = directive.filter(|_| word == "include" || word == "import") {
+                let open = skip_space(i + word.len());
+                let close = match b.get(open) {
+                    Some(b'<') => '>',
+                    Some(b'"') => '"',
+                    _ => {
+                        i += word.len();
+                        continue;
+                    }
+                };
+                if let Some(x) = code[open + 1..].find([close, '\n']).filter(|&x| code[open + 1 + x..].starts_with(close)) {
+                    let path = code[open + 1..open + 1 + x].to_string();
+                    v.push(Import::new(path, None, hash, open + x + 2));
+                    i = open + x + 2;
+                    continue;
+                }
+            } else if (word == "import" || word == "using") && (at_line_start(i) || before.is_none_or(|x| b";{}".contains(&b[x]))) {
+                // The statement runs to its `;`, or the end of its line when
+                // it has none, as JavaScript allows
+                let rest = i + word.len();
+                let mut depth = 0;
+                let mut stop = code.len();
+                for (j, c) in code[rest..].char_indices() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        '\n' if depth > 0 => {}
+                        ';' | '\n' => {
+                            stop = rest + j;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                let end = if code[stop..].starts_with(';') { stop + 1 } else { stop };
+                if let Some(import) = Self::read_import(word, code[rest..stop].trim(), aliases, i, end) {
+                    v.push(import);
+                    i = end;
+                    continue;
+                }
+            }
+            i += word.len();
+        }
+        v
+    }
+
+    /// Reads the declarators of a `typedef`, given the part of the statement
+    /// after the `typedef` keyword, like `unsigned long u64` or
+    /// `struct Node { ... } Node, *NodePtr`
     ///
     /// # Returns
     ///
-    /// A Vec of Identifier containing information on every use of
-    /// an identifier declared within the code
-    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+    /// A Vec of Identifier for each name declared, typed with the type it
+    /// stands for. Function pointer typedefs and declarators of anonymous
+    /// structs are left out, as there is no named type to resolve them to.
+    fn read_typedef(text: &str, start: usize, stop: usize) -> Vec<Identifier> {
+        let (base, declarators) = match text[start..stop].find('{') {
+            Some(x) => (start + x, Self::matching(text, start + x, '{', '}')),
+            // A function pointer
+            None if text[start..stop].contains('(') => return Vec::new(),
+            None => {
+                // The first name is the last word before any `,` or `[`
+                let first = text[start..stop].find([',', '[']).map_or(stop, |x| start + x);
+                let name_end = Self::after_last(&text[start..first], lexer::is_ident_char).map_or(start, |x| start + x);
+                let name_start = Self::after_last(&text[start..name_end], |x| !lexer::is_ident_char(x)).map_or(start, |x| start + x);
+                let base = Self::after_last(&text[start..name_start], |x| !(x.is_whitespace() || x == '*' || x == '&'))
+                    .map_or(start, |x| start + x);
+                (base, base)
+            }
+        };
+        let base = &text[start..base];
+        if base.split_whitespace().all(|x| ["struct", "union", "enum", "class", "const"].contains(&x)) {
+            return Vec::new();
+        }
+        let mut v = Vec::new();
+        let mut i = declarators;
+        while i < stop {
+            let end = text[i..stop].find(',').map_or(stop, |x| i + x);
+            let declarator = &text[i..end];
+            let name_start = declarator.find(lexer::is_ident_char).map(|x| i + x);
+            if let Some(name_start) = name_start.filter(|_| !declarator.contains('(')) {
+                let name_end = name_start + Self::span(&text[..end], name_start, lexer::is_ident_char);
+                let pointers: String = text[i..name_start].chars().filter(|x| !x.is_whitespace()).collect();
+                let suffix: String = text[name_end..end].chars().filter(|x| !x.is_whitespace()).collect();
+                v.push(Identifier::new(
+                    text[name_start..name_end].to_string(),
+                    Self::normalize_type(&format!("{}{}{}", base.trim(), pointers, suffix)),
+                    name_start,
+                    name_end,
+                ));
+            }
+            i = end + 1;
+        }
+        v
+    }
+
+    /// Reads an `import` or `using` statement
+    ///
+    /// # Arguments
+    ///
+    /// * `keyword` - Either `import` or `using`
+    /// * `statement` - The statement after its keyword, without its `;`
+    /// * `aliases` - Whether `using X = Y;` is an import rather than a type alias
+    /// * `start` - Where the statement starts
+    /// * `end` - Where the statement ends
+    ///
+    /// # Returns
+    ///
+    /// The Import the statement makes, or `None` if it is not an import,
+    /// like a dynamic `import(...)` call or a `using` block
+    fn read_import(keyword: &str, statement: &str, aliases: bool, start: usize, end: usize) -> Option<Import> {
+        let ident = |x: &str| -> String { x.chars().take_while(|c| lexer::is_ident_char(*c)).collect() };
+        let path_like = |x: &str, seps: &str| !x.is_empty() && x.chars().all(|c| lexer::is_ident_char(c) || seps.contains(c));
+        if keyword == "import" {
+            if let Some(open) = statement.find(['"', '\'', '`']) {
+                // import Foo, { bar as baz } from "./foo"
+                let quote = statement[open..].chars().next()?;
+                let close = open + 1 + statement[open + 1..].find(quote)?;
+                let clause = statement[..open].trim().trim_end_matches("from").trim();
+                let clause = clause.strip_prefix("type ").unwrap_or(clause).trim_start();
+                let alias = match clause.strip_prefix('*') {
+                    Some(x) => x.trim_start().strip_prefix("as").map(|x| ident(x.trim_start())),
+                    None => Some(ident(clause)),
+                };
+                let alias = alias.filter(|x| !x.is_empty());
+                return Some(Import::new(statement[open + 1..close].to_string(), alias, start, end));
+            }
+            // import static java.util.Collections.sort;
+            let path = statement.strip_prefix("static").filter(|x| x.starts_with(char::is_whitespace)).unwrap_or(statement);
+            let path: String = path.chars().filter(|c| !c.is_whitespace()).collect();
+            return Some(Import::new(path, None, start, end)).filter(|x| path_like(&x.path, "_$.*"));
+        }
+        // using Json = Newtonsoft.Json;
+        if let Some((alias, path)) = statement.split_once('=').filter(|_| aliases) {
+            let path: String = path.chars().filter(|c| !c.is_whitespace()).collect();
+            let alias = Some(alias.trim().to_string()).filter(|x| path_like(x, "_"));
+            return Some(Import::new(path, alias, start, end)).filter(|x| x.alias.is_some() && path_like(&x.path, "_."));
+        }
+        // using namespace std; using std::vector; using static System.Math;
+        let path = ["namespace", "static"]
+            .iter()
+            .find_map(|x| statement.strip_prefix(x).filter(|x| x.starts_with(char::is_whitespace)))
+            .unwrap_or(statement);
+        let path: String = path.chars().filter(|c| !c.is_whitespace()).collect();
+        Some(Import::new(path, None, start, end)).filter(|x| path_like(&x.path, "_.:"))
+    }
+
+    /// Finds the index one past the bracket closing the one opened at `i`
+    fn matching(text: &str, i: usize, open: char, close: char) -> usize {
+        let mut depth = 0;
+        for (j, c) in text[i..].char_indices() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return i + j + 1;
+                }
+            }
+        }
+        text.len()
+    }
+
+    /// Normalizes the type written before a name, dropping any modifiers
+    /// and scope qualifiers like `Foo::` and gluing on `*` and `&`
+    /// declarators, so that `public static int *` becomes `int*`
+    fn read_type(text: &str) -> String {
+        let mut words: Vec<&str> = text
+            .split_whitespace()
+            .skip_while(|x| Self::is_modifier(x))
+            .collect();
+        if words.last().is_some_and(|x| x.ends_with("::")) {
+            words.pop();
+        }
+        let typ = words.join(" ");
+        let base = typ.trim_end_matches(|x: char| x == '*' || x == '&' || x.is_whitespace());
+        let ptr: String = typ[base.len()..].chars().filter(|x| !x.is_whitespace()).collect();
+        Self::normalize_type(base) + &ptr
+    }
+
+    /// Reads a single parameter, like `const char *name` or `int xs[]`,
+    /// out of a parameter list
+    ///
+    /// # Returns
+    ///
+    /// The Parameter, or `None` if `text` is empty or `void`
+    fn read_parameter(text: &str) -> Option<Parameter> {
+        let text = text.split('=').next().unwrap_or("").trim();
+        let mut decl = text.trim_end_matches(|x: char| x == '[' || x == ']' || x.is_whitespace());
+        let arrays: String = text[decl.len()..].chars().filter(|x| !x.is_whitespace()).collect();
+        let name_start = decl
+            .rfind(|x: char| !lexer::is_ident_char(x))
+            .map_or(0, |x| x + decl[x..].chars().next().unwrap().len_utf8());
+        let name = decl[name_start..].to_string();
+        decl = &decl[..name_start];
+        if name.is_empty() || (decl.trim().is_empty() && name == "void") {
+            return None;
+        }
+        let typ = if decl.trim().is_empty() { String::new() } else { Self::read_type(decl) + &arrays };
+        Some(Parameter::new(name, typ))
+    }
+
+    /// Splits a parameter list, without its parentheses, into parameters
+    fn read_parameters(text: &str) -> Vec<Parameter> {
+        let mut v = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in text.char_indices() {
+            match c {
+                '(' | '<' | '[' => depth += 1,
+                ')' | '>' | ']' => depth -= 1,
+                ',' if depth == 0 => {
+                    v.extend(Self::read_parameter(&text[start..i]));
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        v.extend(Self::read_parameter(&text[start..]));
+        v
+    }
+
+    /// Splits the part of a class header after its name, like
+    /// `extends Base<T> implements A, B` or `: public Base`, into the
+    /// supertypes it names
+    fn read_supertypes(header: &str) -> Vec<String> {
+        const SKIPPED: &[&str] = &[
+            "extends",
+            "implements",
+            "public",
+            "private",
+            "protected",
+            "virtual",
+            "final",
+            "sealed",
+        ];
+        let chars: Vec<char> = header.chars().collect();
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut depth = 0;
+        for (i, &c) in chars.iter().enumerate() {
+            let colon = c == ':' && chars.get(i + 1) != Some(&':') && (i == 0 || chars[i - 1] != ':');
+            match c {
+                '<' | '(' => depth += 1,
+                '>' | ')' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 && (c.is_whitespace() || c == ',' || colon) {
+                words.push(std::mem::take(&mut word));
+            } else {
+                word.push(c);
+            }
+        }
+        words.push(word);
+        words
+            .into_iter()
+            .filter(|x| !x.is_empty())
+            .take_while(|x| x != "where" && x != "permits")
+            .filter(|x| !SKIPPED.contains(&x.as_str()))
+            .map(|x| Self::normalize_type(&x))
+            .collect()
+    }
+
+    /// Reads the fields declared by the statement `chars[start..end]` of a
+    /// class body, like `private int a, *b = 0`, which is nothing if the
+    /// statement declares a method or other member instead
+    fn read_field(text: &str, start: usize, end: usize) -> Vec<Identifier> {
+        let mut v = Vec::new();
+        let first = Self::word_at(&text[..end], start);
+        if ["using", "typedef", "friend", "template"].contains(&first) {
+            return v;
+        }
+        // Split the declarators, dropping their initializers
+        let mut declarators = Vec::new();
+        let mut depth = 0;
+        let (mut from, mut to) = (start, None);
+        for (i, c) in text[start..end].char_indices() {
+            let i = start + i;
+            match c {
+                '(' | '<' | '[' | '{' => depth += 1,
+                ')' | '>' | ']' | '}' => depth -= 1,
+                '=' if depth == 0 && to.is_none() => to = Some(i),
+                ',' if depth == 0 => {
+                    declarators.push((from, to.unwrap_or(i)));
+                    from = i + 1;
+                    to = None;
+                }
+                _ => {}
+            }
+        }
+        declarators.push((from, to.unwrap_or(end)));
+        let mut base = String::new();
+        for (n, (from, to)) in declarators.into_iter().enumerate() {
+            let decl = &text[from..to];
+            if decl.contains('(') {
+                return v;
+            }
+            let name_end = decl.trim_end_matches(|x: char| x.is_whitespace() || x == '[' || x == ']').len();
+            let name_start = Self::after_last(&decl[..name_end], |x| !lexer::is_ident_char(x)).unwrap_or(0);
+            let name = &decl[name_start..name_end];
+            let arrays: String = decl[name_end..].chars().filter(|x| !x.is_whitespace()).collect();
+            if !name.starts_with(lexer::is_ident_start) {
+                return v;
+            }
+            let typ = if n == 0 {
+                let typ = Self::read_type(&decl[..name_start]);
+                base = typ.trim_end_matches(['*', '&']).to_string();
+                typ
+            } else {
+                base.clone() + decl[..name_start].trim()
+            };
+            if base.is_empty() || Self::is_keyword(&base) {
+                return v;
+            }
+            v.push(Identifier::new(name.to_string(), typ + &arrays, from + name_start, from + name_end));
+        }
+        v
+    }
+
+    /// Reads the fields declared directly within the class body running
+    /// from the `{` at `body` to the `}` just before `end`, skipping
+    /// methods and nested types
+    fn read_fields(text: &str, body: usize, end: usize) -> Vec<Identifier> {
+        let b = text.as_bytes();
+        let mut v = Vec::new();
+        let mut depth = 0;
+        let mut stmt = body + 1;
+        let mut member = false;
+        for i in body + 1..end.saturating_sub(1) {
+            match b[i] {
+                b'{' => {
+                    if depth == 0 && !text[stmt..i].contains('=') {
+                        // A method or nested type, rather than an initializer
+                        member = true;
+                    }
+                    depth += 1;
+                }
+                b'}' => {
+                    depth -= 1;
+                    if depth == 0 && member {
+                        member = false;
+                        stmt = i + 1;
+                    }
+                }
+                b';' if depth == 0 => {
+                    v.extend(Self::read_field(text, stmt, i));
+                    stmt = i + 1;
+                }
+                b':' if depth == 0 && b.get(i + 1) != Some(&b':') && b[i - 1] != b':' => {
+                    // An access label, like `public:`
+                    let label: String = text[stmt..i].chars().filter(|x| !x.is_whitespace()).collect();
+                    if ["public", "private", "protected"].contains(&label.as_str()) {
+                        stmt = i + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        v
+    }
+
+    /// Whether a word is a keyword, which is never the name or type of a
+    /// declaration, so that `return me;` is a use of `me`
+    fn is_keyword(x: &str) -> bool {
+        keywords::is_keyword(
+            "clike",
+            &[
+                "assert",
+                "await",
+                "break",
+                "case",
+                "catch",
+                "continue",
+                "delete",
+                "do",
+                "else",
+                "extends",
+                "false",
+                "finally",
+                "for",
+                "goto",
+                "if",
+                "implements",
+                "import",
+                "instanceof",
+                "new",
+                "null",
+                "nullptr",
+                "package",
+                "return",
+                "sizeof",
+                "super",
+                "switch",
+                "this",
+                "throw",
+                "throws",
+                "true",
+                "try",
+                "typeof",
+                "void",
+                "while",
+                "yield",
+            ],
+            x,
+        )
+    }
+
+    /// Whether a word is a modifier, which is dropped from the type of a
+    /// declaration, so that `static int x` declares an `int`
+    fn is_modifier(x: &str) -> bool {
+        keywords::is_modifier(
+            "clike",
+            &[
+                "public",
+                "private",
+                "protected",
+                "static",
+                "final",
+                "abstract",
+                "synchronized",
+                "native",
+                "transient",
+                "strictfp",
+                "default",
+                "inline",
+                "virtual",
+                "extern",
+                "explicit",
+                "constexpr",
+                "consteval",
+                "constinit",
+                "thread_local",
+                "friend",
+                "export",
+                "async",
+            ],
+            x,
+        )
+    }
+
+    /// Walks the identifiers of c-like code, as `read_identifiers` does
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the identifiers read and a Vec of Diagnostic for each
+    /// block its scopes could not follow: a `}` that closes no block, and a
+    /// `{` that is never closed
+    fn walk_identifiers(&self, text: &str) -> (Vec<Identifier>, Vec<Diagnostic>) {
+        let macros = Self::macros(text);
+        // The fields of each class by where its body opens, so that every
+        // method sees them wherever they are declared
+        let mut members: HashMap<usize, HashMap<String, String>> = self
+            .read_classes(text)
+            .into_iter()
+            .map(|x| (x.body, x.fields.into_iter().map(|f| (f.name, f.typ)).collect()))
+            .collect();
+        let text = &Self::strip(text);
+        for (body, end) in Self::anonymous_classes(text) {
+            let fields = Self::read_fields(text, body, end).into_iter().map(|f| (f.name, f.typ));
+            members.insert(body, fields.collect());
+        }
+        let lambdas = Self::lambdas(text);
+        let untyped: HashMap<usize, usize> = lambdas.iter().flat_map(|x| x.untyped.iter().copied()).collect();
+        let starts: HashSet<usize> = lambdas.iter().map(|x| x.start).collect();
+        let arrows: HashMap<usize, usize> = lambdas.iter().filter_map(|x| Some((x.arrow, x.end?))).collect();
+        // Each token, along with the whitespace between tokens, which is read
+        // as a single space, as it ends a name as a whole
+        let mut words: Vec<(usize, &str)> = Vec::new();
+        let mut after = 0;
+        for t in TOKENIZER.code(text) {
+            if after < t.start {
+                words.push((after, " "));
+            }
+            words.push((t.start, t.text(text)));
+            after = t.end;
+        }
+        if after < text.len() {
+            words.push((after, " "));
+        }
+        // Where the frames of the lambdas whose bodies are expressions end
+        let mut closes = Vec::new();
         let mut s = IFsm::NONE;
         let mut n1s = 0;
         let mut n1e = 0;
         let mut n2s = 0;
-        let mut n2e;
         let mut v = Vec::new();
-        let mut stack = Vec::<HashMap<String, String>>::new();
-        stack.push(HashMap::new());
-        for (i, c) in text.chars().enumerate() {
-            if c == '{' {
-                stack.push(HashMap::new());
+        let mut scopes = Scopes::new();
+        // Where each block entered opens, and the blocks that went wrong
+        let mut blocks = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut skip = 0;
+        // The `*` and `&` declarators between a type and the declared name
+        let mut ptr = String::new();
+        // The type and paren depth of the declaration being parsed, for
+        // declarations with more than one declarator
+        let mut decl: Option<(String, i32)> = None;
+        // The type of the next declarator, when it follows a `,`
+        let mut cont: Option<String> = None;
+        // Declarations within parentheses, like parameters, which belong to
+        // the block that follows them rather than to the enclosing one
+        let mut params = HashMap::<String, String>::new();
+        let mut parens = 0;
+        // Records a use of the name from `start` to `end`, if it is declared
+        let use_of = |v: &mut Vec<Identifier>, params: &HashMap<String, String>, scopes: &Scopes, start: usize, end: usize| {
+            let name = &text[start..end];
+            if let Some(typ) = params.get(name).or_else(|| scopes.lookup(name)) {
+                v.push(Identifier::new(name.to_string(), typ.to_string(), start, end));
+            }
+        };
+        for (i, word) in words {
+            while closes.last().is_some_and(|x| *x < i) {
+                closes.pop();
+                scopes.pop();
+            }
+            if i < skip {
+                continue;
+            }
+            let c = word.chars().next().unwrap_or(' ');
+            if starts.contains(&i) {
+                // The parameters of a lambda never belong to an enclosing call
+                params.clear();
+            }
+            if let Some(&end) = untyped.get(&i) {
+                let name = text[i..end].to_string();
+                v.push(Identifier::new(name.clone(), INFERRED.to_string(), i, end));
+                params.insert(name, INFERRED.to_string());
+                skip = end;
+                s = IFsm::NONE;
+                continue;
+            }
+            if let Some(&end) = arrows.get(&i) {
+                scopes.push(std::mem::take(&mut params));
+                closes.push(end);
+            }
+            if word == "{" {
+                let mut frame = std::mem::take(&mut params);
+                frame.extend(members.remove(&i).unwrap_or_default());
+                scopes.push(frame);
+                blocks.push(i);
                 s = IFsm::NONE;
-            } else if c == '}' {
-                stack.pop();
+            } else if word == "}" {
+                // A `}` that closes no block never leaves the outermost scope
+                if blocks.pop().is_some() {
+                    scopes.pop();
+                } else {
+                    diagnostics.push(Diagnostic::new("`}` closes no block".to_string(), i));
+                }
                 s = IFsm::NONE;
             }
             match s {
                 IFsm::NONE => {
-                    if c == '.' {
+                    if word == "." {
                         s = IFsm::DOT;
-                    } else if c.is_alphabetic() {
-                        s = IFsm::NAME1;
-                        n1s = i;
+                    } else if lexer::is_ident_start(c) {
+                        if Self::is_modifier(word) {
+                            skip = i + word.len();
+                        } else {
+                            s = IFsm::NAME1;
+                            n1s = i;
+                            n1e = i;
+                            ptr.clear();
+                        }
                     }
                 }
                 IFsm::DOT => {
-                    if c.is_whitespace() {
+                    if word == " " {
                         s = IFsm::SPACE;
+                    } else if !lexer::is_ident_char(c) && c != '.' {
+                        // The member access ends, as in `me.go();`
+                        s = IFsm::NONE;
                     }
                 }
                 IFsm::NAME1 => {
-                    if c.is_whitespace() {
+                    let suffix = if word == "<" || word == "[" { Self::type_suffix(text, i) } else { None };
+                    let known = || {
+                        let name = &text[n1s..i];
+                        params.contains_key(name) || scopes.lookup(name).is_some()
+                    };
+                    if let Some(end) = suffix {
+                        skip = end;
+                    } else if word == "::" {
+                        skip = i + 2;
+                    } else if word == " " {
                         s = IFsm::SPACE;
                         n1e = i;
-                    } else if !c.is_alphanumeric() {
-                        //Push declared identifier
-                        if c == '.' {
-                            s = IFsm::DOT;
-                        } else {
-                            s = IFsm::NONE;
-                        }
+                    } else if (word == "*" || word == "&") && !known() && Self::starts_declaration(text, n1s) {
+                        s = IFsm::SPACE;
                         n1e = i;
-                        let name = text[n1s..n1e].to_string();
-                        for frame in stack.iter().rev() {
-                            if let Some(typ) = frame.get(&name) {
-                                v.push(Identifier::new(name, typ.to_string(), n1s, n1e));
-                                break;
-                            }
-                        }
+                        ptr.push(c);
+                    } else if !lexer::is_ident_char(c) {
+                        s = if word == "." { IFsm::DOT } else { IFsm::NONE };
+                        n1e = i;
+                        use_of(&mut v, &params, &scopes, n1s, n1e);
                     }
                 }
                 IFsm::SPACE => {
-                    if c.is_alphabetic() {
+                    let last = text[n1s..n1e].rsplit(|x: char| !lexer::is_ident_char(x)).next().unwrap_or("");
+                    if lexer::is_ident_start(c) && cont.is_none() && ptr.is_empty() && Self::continues_type(last, word) {
+                        s = IFsm::NAME1;
+                    } else if lexer::is_ident_start(c) {
                         s = IFsm::NAME2;
                         n2s = i;
-                    } else if !c.is_whitespace() {
-                        //Push declared identifier
+                    } else if (word == "*" || word == "&")
+                        && (!ptr.is_empty()
+                            || cont.is_some()
+                            || (!params.contains_key(&text[n1s..n1e])
+                                && scopes.lookup(&text[n1s..n1e]).is_none()
+                                && Self::starts_declaration(text, n1s)))
+                    {
+                        ptr.push(c);
+                    } else if word != " " {
                         s = IFsm::NONE;
-                        let name = text[n1s..n1e].to_string();
-                        for frame in stack.iter().rev() {
-                            if let Some(typ) = frame.get(&name) {
-                                v.push(Identifier::new(name.clone(), typ.to_string(), n1s, n1e));
-                                break;
-                            }
-                        }
+                        use_of(&mut v, &params, &scopes, n1s, n1e);
                     }
                 }
                 IFsm::NAME2 => {
-                    if !c.is_alphanumeric() {
-                        //Push new delcaration
-                        if c == '.' {
-                            s = IFsm::DOT;
+                    if !lexer::is_ident_char(c) {
+                        s = if word == "." { IFsm::DOT } else { IFsm::NONE };
+                        let name = text[n2s..i].to_string();
+                        let base = cont.take().unwrap_or_else(|| Self::normalize_type(&text[n1s..n1e]));
+                        let typ = base.clone() + &ptr;
+                        ptr.clear();
+                        if !Self::is_keyword(&name) && !Self::is_keyword(&base) {
+                            decl = Some((base, 0));
+                            v.push(Identifier::new(name.clone(), typ.clone(), n2s, i));
+                            if parens > 0 {
+                                params.insert(name, typ);
+                            } else {
+                                scopes.declare(name, typ);
+                            }
                         } else {
-                            s = IFsm::NONE;
-                        }
-                        n2e = i;
-                        let name = text[n2s..n2e].to_string();
-                        let typ = text[n1s..n1e].to_string();
-                        if Clike::is_allowed(name.as_ref()) && Clike::is_allowed(typ.as_ref()) {
-                            v.push(Identifier::new(name.clone(), typ.clone(), n2s, n2e));
-                            stack.last_mut().unwrap().insert(name, typ);
+                            // A keyword followed by a name, like `return me`, uses the name
+                            use_of(&mut v, &params, &scopes, n2s, i);
                         }
                     }
                 }
             }
+            match word {
+                "(" => parens += 1,
+                ")" if parens > 0 => parens -= 1,
+                ";" | "}" if parens == 0 => params.clear(),
+                _ => {}
+            }
+            if let Some((base, depth)) = &mut decl {
+                match word {
+                    "(" | "[" => *depth += 1,
+                    ")" | "]" if *depth > 0 => *depth -= 1,
+                    "," if *depth == 0 && Self::declarator_follows(text, i) => {
+                        cont = Some(base.clone());
+                        ptr.clear();
+                        s = IFsm::SPACE;
+                    }
+                    ")" | "]" | ";" | "{" | "}" => decl = None,
+                    _ => {}
+                }
+            }
+        }
+        if !macros.is_empty() {
+            // A macro is never also a declaration, like `MAX` in `return MAX(a, b);`
+            let starts: HashSet<usize> = macros.iter().map(|x| x.start).collect();
+            v.retain(|x| !starts.contains(&x.start));
+            v.extend(macros);
+            v.sort_by_key(|x| x.start);
+        }
+        diagnostics.extend(blocks.into_iter().map(|x| Diagnostic::new("`{` is never closed".to_string(), x)));
+        diagnostics.sort_by_key(|x| x.start);
+        (v, diagnostics)
+    }
+}
+
+/// Splits stripped C-like code into tokens
+const TOKENIZER: Tokenizer = Tokenizer {
+    operators: &["::", "->"],
+    ..Tokenizer::new(CommentSyntax::C)
+};
+
+impl Functions for Clike {
+    /// Parses out function declarations from c-like code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Function containing information on every function
+    /// declared within text
+    fn read_functions(&self, text: &str) -> Vec<Function> {
+        let attributes = Self::attributes(text);
+        let text = &Self::strip(text);
+        let anonymous: HashSet<usize> = Self::anonymous_classes(text).into_iter().map(|(body, _)| body).collect();
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| t.text(text));
+        let mut v = Vec::new();
+        for (k, name) in tokens.iter().enumerate() {
+            if name.kind != TokenKind::Ident || s(k + 1) != Some("(") {
+                continue;
+            }
+            let close = tokens::matching(text, &tokens, k + 1);
+            let mut j = close + 1;
+            if s(j) == Some(":") {
+                // Skip a constructor's initializer list, as in
+                // `Foo() : a(1), b{2} {`, where a `{` right after a
+                // member's name initializes the member
+                let mut depth = 0;
+                j += 1;
+                while let Some(x) = s(j) {
+                    match x {
+                        "{" if depth == 0 && tokens[j - 1].kind != TokenKind::Ident => break,
+                        "(" | "{" => depth += 1,
+                        ")" | "}" => depth -= 1,
+                        ";" => break,
+                        _ => {}
+                    }
+                    j += 1;
+                }
+            }
+            if s(j) != Some("{") {
+                continue;
+            }
+            let body = tokens[j].start;
+            if anonymous.contains(&body) || !Self::declares_function(text, name.start, name.end) {
+                continue;
+            }
+            // The signature starts after the statement before it
+            let sig = text[..name.start].rfind([';', '{', '}']).map_or(0, |x| x + 1);
+            let first = sig + Self::span(&text[..name.start], sig, char::is_whitespace);
+            let typ = Self::read_type(&text[first..name.start]);
+            let sig = Self::declaration_start(&attributes, sig, first);
+            let params = Self::read_parameters(&text[tokens[k + 1].end..tokens[close].start]);
+            let last = tokens[tokens::matching(text, &tokens, j)].end;
+            v.push(Function::new(name.text(text).to_string(), typ, params, sig, body, last));
         }
         v
     }
 }
 
+impl Classes for Clike {
+    /// Parses out class, struct, interface, enum, and union definitions
+    /// from c-like code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Class containing information on every type defined with a
+    /// body within text, in the order they start
+    fn read_classes(&self, text: &str) -> Vec<Class> {
+        const KINDS: &[&str] = &["class", "struct", "interface", "enum", "union"];
+        let attributes = Self::attributes(text);
+        let text = &Self::strip(text);
+        let skip_space = |i: usize| i + Self::span(text, i, char::is_whitespace);
+        let mut v = Vec::new();
+        let mut i = 0;
+        while i < text.len() {
+            let kind = Self::word_at(text, i);
+            if !Self::starts_word(text, i) || !KINDS.contains(&kind) {
+                i = Self::next_word(text, i);
+                continue;
+            }
+            let mut name_start = skip_space(i + kind.len());
+            // `enum class Foo`
+            if kind == "enum" && ["class", "struct"].contains(&Self::word_at(text, name_start)) {
+                name_start = skip_space(name_start + Self::word_at(text, name_start).len());
+            }
+            let name = Self::word_at(text, name_start);
+            let mut after = name_start + name.len();
+            if let Some(end) = Self::type_suffix(text, after).filter(|_| text[after..].starts_with('<')) {
+                after = end;
+            }
+            i += kind.len();
+            let next = Self::char_at(text, skip_space(after));
+            if name.is_empty() || !next.is_some_and(|x| x.is_alphabetic() || x == '{' || x == ':') {
+                continue;
+            }
+            let body = match text[after..].find(['{', ';', '(', '=']) {
+                Some(x) if text[after + x..].starts_with('{') => after + x,
+                _ => continue,
+            };
+            let end = Self::matching(text, body, '{', '}');
+            // The declaration starts after the statement before it
+            let start = text[..i - kind.len()].rfind([';', '{', '}']).map_or(0, |x| x + 1);
+            let start = Self::declaration_start(&attributes, start, skip_space(start));
+            v.push(Class::new(
+                name.to_string(),
+                kind.to_string(),
+                Self::read_supertypes(&text[after..body]),
+                Self::read_fields(text, body, end),
+                start,
+                body,
+                end,
+            ));
+        }
+        v
+    }
+}
+
+impl Imports for Clike {
+    /// Writes an import of `path`, which is an `#include` if `path` is in
+    /// angle brackets or quotes, like `<stdio.h>`, and a Java `import`, like
+    /// `import java.util.List;`, otherwise
+    fn import_statement(&self, path: &str) -> Option<String> {
+        if path.starts_with(['<', '"']) {
+            Some(format!("#include {}", path))
+        } else {
+            Some(format!("import {};", path))
+        }
+    }
+
+    /// Parses out `#include`s, Java and JavaScript `import`s, and C++ and C#
+    /// `using` directives from c-like code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Import for every import within text. Includes give the path
+    /// between their quotes or angle brackets, JavaScript imports give the
+    /// module they import from along with the default or namespace name they
+    /// bind, and the rest give the imported name with whitespace removed.
+    /// `using X = Y;` is left out, as in C++ it declares a type alias.
+    fn read_imports(&self, text: &str) -> Vec<Import> {
+        Self::imports(text, false)
+    }
+}
+
+impl Aliases for Clike {
+    /// Parses out `typedef`s, `using X = Y;` aliases, and the names
+    /// brought in by Java imports and C++ `using` declarations
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier for each alias within text, whose type is the
+    /// type it stands for
+    fn read_aliases(&self, text: &str) -> Vec<Identifier> {
+        let code = Self::strip(text);
+        let mut v = Vec::new();
+        let mut i = 0;
+        while i < code.len() {
+            let word = Self::word_at(&code, i);
+            if !Self::starts_word(&code, i) || (word != "typedef" && word != "using") {
+                i = Self::next_word(&code, i);
+                continue;
+            }
+            let rest = i + word.len();
+            let mut depth = 0;
+            let mut stop = code.len();
+            for (j, c) in code[rest..].char_indices() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    ';' if depth <= 0 => {
+                        stop = rest + j;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            if word == "typedef" {
+                v.extend(Self::read_typedef(&code, rest, stop));
+            } else if let Some(eq) = code[rest..stop].find('=').map(|x| rest + x) {
+                // using Id = unsigned long;
+                let typ = &code[eq + 1..stop];
+                let offset = rest + Self::span(&code[..eq], rest, char::is_whitespace);
+                let name = code[rest..eq].trim();
+                if !name.is_empty() && name.chars().all(lexer::is_ident_char) && !typ.contains('(') {
+                    v.push(Identifier::new(name.to_string(), Self::normalize_type(typ), offset, offset + name.len()));
+                }
+            }
+            i = stop;
+        }
+        // `#include`s name files rather than types
+        let imports = Self::imports(text, false).into_iter().filter(|x| !text[x.start..].starts_with('#'));
+        v.extend(import_aliases(imports.collect()));
+        v
+    }
+}
+
+/// A Java-style lambda found by `Clike::lambdas`
+struct Lambda {
+    /// The index its parameters start at
+    start: usize,
+    /// The index of its `->`
+    arrow: usize,
+    /// The start and end of each parameter without a written type
+    untyped: Vec<(usize, usize)>,
+    /// The index its body ends at, if the body is an expression rather than
+    /// a block
+    end: Option<usize>,
+}
+
+/// What `read_identifiers` is reading, as it walks the tokens of the code
+enum IFsm {
+    /// Nothing in particular
+    NONE,
+    /// A name, which may be a use or the type of a declaration
+    NAME1,
+    /// The whitespace or declarators after a name
+    SPACE,
+    /// A name after a type, which is declared
+    NAME2,
+    /// The member named after a `.`, which is never a use
+    DOT,
+}
+
+impl Identifiers for Clike {
+    /// Parses out identifier uses from c-like code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing information on every use of
+    /// an identifier declared within the code. Parameters and other
+    /// declarations within parentheses are scoped to the block after them,
+    /// and the fields of a class are in scope throughout its body. Every
+    /// definition and use of a preprocessor macro has the type `macro`.
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        self.walk_identifiers(text).0
+    }
+}
+
+impl Language for Clike {
+    fn name(&self) -> &'static str {
+        "clike"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[
+            "c", "h", "cc", "cpp", "cxx", "hh", "hpp", "hxx", "java", "js", "jsx", "mjs", "ts",
+            "tsx", "swift", "scala", "groovy", "dart", "php", "m", "mm",
+        ]
+    }
+
+    fn interpreters(&self) -> &'static [&'static str] {
+        &["node", "deno"]
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax::C
+    }
+
+    fn spans(&self, text: &str) -> Vec<lexer::Span> {
+        let mut v = tokens::spans(&self.tokenize(text));
+        v.extend(Self::disabled(text));
+        v.sort_by_key(|x| x.start);
+        v
+    }
+
+    /// Reports the blocks that `read_identifiers` could not follow as it
+    /// entered and left its scopes
+    fn diagnose(&self, text: &str) -> Vec<Diagnostic> {
+        self.walk_identifiers(text).1
+    }
+}
+
 #[test]
 fn test_functions() {
-    let expected = "[Function { name: \"LightningOvercharge\" }, Function { name: \"getAction\" }, Function { name: \"onSpawn\" }, Function { name: \"getPassiveAction\" }, Function { name: \"getCost\" }, Function { name: \"getName\" }, Function { name: \"getTip\" }, Function { name: \"getActionNetwork\" }]";
+    let expected = "[\"LightningOvercharge\", \"getAction\", \"onSpawn\", \"getPassiveAction\", \"getCost\", \"getName\", \"getTip\", \"getActionNetwork\"]";
     let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     let clike = Clike {};
     d.push("resources/test/functions.java");
     let text = std::fs::read_to_string(d).unwrap();
-    let result = format!("{:?}", clike.read_functions(&text));
+    let names: Vec<String> = clike.read_functions(&text).into_iter().map(|x| x.name).collect();
+    let result = format!("{:?}", names);
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_signatures() {
+    let text = "@Override\npublic static Map<String, Foo> get(final String key, int xs[], char *s) {\n    if (key) { return null; }\n}\nint *twice(void) { return 0; }\n";
+    let clike = Clike {};
+    let functions = clike.read_functions(text);
+    let found: Vec<(String, String, usize, usize, usize)> =
+        functions.iter().map(|x| (x.name.clone(), x.typ.clone(), x.start, x.body, x.end)).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("get".to_string(), "Map<String, Foo>".to_string(), 0, 82, 115),
+            ("twice".to_string(), "int*".to_string(), 116, 133, 146),
+        ]
+    );
+    let params: Vec<(String, String)> = functions[0].params.iter().map(|x| (x.name.clone(), x.typ.clone())).collect();
+    let expected = vec![("key", "String"), ("xs", "int[]"), ("s", "char*")];
+    let expected: Vec<(String, String)> = expected.into_iter().map(|(x, y)| (x.to_string(), y.to_string())).collect();
+    assert_eq!(params, expected);
+    assert!(functions[1].params.is_empty());
+}
+
 #[test]
 fn test_identifiers() {
-    let expected = "[Identifier { name: \"com\", typ: \"static\", start: 67, end: 70 }, Identifier { name: \"com\", typ: \"static\", start: 232, end: 235 }, Identifier { name: \"com\", typ: \"static\", start: 273, end: 276 }, Identifier { name: \"com\", typ: \"static\", start: 316, end: 319 }, Identifier { name: \"com\", typ: \"static\", start: 361, end: 364 }, Identifier { name: \"LightningOvercharge\", typ: \"class\", start: 414, end: 433 }, Identifier { name: \"charge\", typ: \"int\", start: 462, end: 468 }, Identifier { name: \"charge\", typ: \"int\", start: 517, end: 523 }, Identifier { name: \"number\", typ: \"double\", start: 547, end: 553 }, Identifier { name: \"me\", typ: \"Session\", start: 601, end: 603 }, Identifier { name: \"number\", typ: \"double\", start: 615, end: 621 }, Identifier { name: \"me\", typ: \"Session\", start: 635, end: 637 }, Identifier { name: \"me\", typ: \"Session\", start: 635, end: 637 }]";
+    let expected = "[Identifier { name: \"LightningOvercharge\", typ: \"class\", start: 414, end: 433, line: 15, column: 14, scope_id: 0 }, Identifier { name: \"charge\", typ: \"int\", start: 462, end: 468, line: 16, column: 9, scope_id: 0 }, Identifier { name: \"LightningOvercharge\", typ: \"class\", start: 485, end: 504, line: 17, column: 12, scope_id: 0 }, Identifier { name: \"charge\", typ: \"int\", start: 517, end: 523, line: 18, column: 9, scope_id: 0 }, Identifier { name: \"number\", typ: \"double\", start: 547, end: 553, line: 21, column: 12, scope_id: 0 }, Identifier { name: \"me\", typ: \"Session\", start: 601, end: 603, line: 23, column: 33, scope_id: 0 }, Identifier { name: \"number\", typ: \"double\", start: 615, end: 621, line: 24, column: 9, scope_id: 0 }, Identifier { name: \"me\", typ: \"Session\", start: 635, end: 637, line: 25, column: 9, scope_id: 0 }, Identifier { name: \"me\", typ: \"Session\", start: 635, end: 637, line: 25, column: 9, scope_id: 0 }]";
     let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     d.push("resources/test/identifiers.java");
     let clike = Clike {};
     let text = std::fs::read_to_string(d).unwrap();
-    let result = format!("{:?}", clike.read_identifiers(&text));
+    let result = format!("{:?}", super::lines::locate(&text, clike.read_identifiers(&text)));
     assert_eq!(result, expected);
-    assert!(Clike::is_allowed("bob"));
-    assert!(!Clike::is_allowed("private"));
+    assert!(!Clike::is_keyword("bob") && !Clike::is_modifier("bob"));
+    assert!(Clike::is_modifier("private") && Clike::is_keyword("return"));
 }
 
 #[test]
@@ -247,3 +1517,344 @@ fn test_replace_whole() {
         expected
     );
 }
+
+#[test]
+fn test_comments() {
+    let text = "void f() {\n    // int ghost; }\n    /* { */ Session me;\n}\nvoid g() { me = 1; }\n";
+    let clike = Clike {};
+    let names: Vec<String> = clike.read_functions(text).into_iter().map(|x| x.name).collect();
+    assert_eq!(names, vec!["f", "g"]);
+    let found: Vec<(String, usize)> = clike.read_identifiers(text).into_iter().map(|x| (x.name, x.start)).collect();
+    assert_eq!(found, vec![("me".to_string(), 51)]);
+}
+
+#[test]
+fn test_generic_types() {
+    let text = "class A {\n    List<Session> items;\n    Map<String ,List<Foo> > m;\n    int[][] grid;\n    void f() { if (a < b) { use(items, m); grid[0] = x; } }\n}\n";
+    let clike = Clike {};
+    let found: Vec<(String, String)> = clike.read_identifiers(text).into_iter().map(|x| (x.name, x.typ)).collect();
+    let expected = vec![
+        ("A", "class"),
+        ("items", "List<Session>"),
+        ("m", "Map<String, List<Foo>>"),
+        ("grid", "int[][]"),
+        ("items", "List<Session>"),
+        ("m", "Map<String, List<Foo>>"),
+        ("grid", "int[][]"),
+    ];
+    let expected: Vec<(String, String)> = expected.into_iter().map(|(x, y)| (x.to_string(), y.to_string())).collect();
+    assert_eq!(found, expected);
+    assert_eq!(Clike::type_suffix("a<b)", 1), None);
+}
+
+#[test]
+fn test_qualified_types() {
+    let text = "int f(const char *name, Foo& ref) {\n    unsigned long long x;\n    std::vector<int> v;\n    char const* p = name;\n    int n = x * 2;\n    if (x & n) { use(ref, v, p); }\n}\n";
+    let clike = Clike {};
+    let found: Vec<(String, String)> = clike.read_identifiers(text).into_iter().map(|x| (x.name, x.typ)).collect();
+    let expected = vec![
+        ("f", "int"),
+        ("name", "const char*"),
+        ("ref", "Foo&"),
+        ("x", "unsigned long long"),
+        ("v", "std::vector<int>"),
+        ("p", "char const*"),
+        ("name", "const char*"),
+        ("n", "int"),
+        ("x", "unsigned long long"),
+        ("x", "unsigned long long"),
+        ("n", "int"),
+        ("ref", "Foo&"),
+        ("v", "std::vector<int>"),
+        ("p", "char const*"),
+    ];
+    let expected: Vec<(String, String)> = expected.into_iter().map(|(x, y)| (x.to_string(), y.to_string())).collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn test_multiple_declarators() {
+    let text = "void f(int a, Foo b) {\n    int x, *y, z = g(a, b);\n    for (int i = 0, j = z; i < j; i++) { h(x, y); }\n}\n";
+    let clike = Clike {};
+    let found: Vec<(String, String)> = clike.read_identifiers(text).into_iter().map(|x| (x.name, x.typ)).collect();
+    let expected = vec![
+        ("a", "int"),
+        ("b", "Foo"),
+        ("x", "int"),
+        ("y", "int*"),
+        ("z", "int"),
+        ("a", "int"),
+        ("b", "Foo"),
+        ("i", "int"),
+        ("j", "int"),
+        ("z", "int"),
+        ("i", "int"),
+        ("j", "int"),
+        ("i", "int"),
+        ("x", "int"),
+        ("y", "int*"),
+    ];
+    let expected: Vec<(String, String)> = expected.into_iter().map(|(x, y)| (x.to_string(), y.to_string())).collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn test_parameters() {
+    let text = "void f(Session me, int count) {\n    count = me.size;\n}\nvoid g() {\n    me = count;\n}\nint h(int a);\nint b = a;\n";
+    let clike = Clike {};
+    let found: Vec<(String, String, usize)> =
+        clike.read_identifiers(text).into_iter().map(|x| (x.name, x.typ, x.start)).collect();
+    let expected = vec![
+        ("me", "Session", 15),
+        ("count", "int", 23),
+        ("count", "int", 36),
+        ("me", "Session", 44),
+        ("h", "int", 88),
+        ("a", "int", 94),
+        ("b", "int", 102),
+    ];
+    let expected: Vec<(String, String, usize)> =
+        expected.into_iter().map(|(x, y, z)| (x.to_string(), y.to_string(), z)).collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn test_classes() {
+    let text = "@Entity\npublic class Spell<T> extends Base<T> implements Castable, Comparable<Spell<T>> {\n    private int charge = 0, cost;\n    static final Session me;\n    int[] xs = {1, 2};\n    public Spell() { charge = 1; }\n    interface Castable { void cast(); }\n    Object c = Foo.class;\n}\nstruct Node : public Base {\npublic:\n    struct Node *next;\n};\n";
+    let classes = Clike {}.read_classes(text);
+    let found: Vec<(String, String, Vec<String>, usize)> =
+        classes.iter().map(|x| (x.name.clone(), x.kind.clone(), x.supertypes.clone(), x.start)).collect();
+    let strings = |x: &[&str]| -> Vec<String> { x.iter().map(|y| y.to_string()).collect() };
+    assert_eq!(
+        found,
+        vec![
+            ("Spell".to_string(), "class".to_string(), strings(&["Base<T>", "Castable", "Comparable<Spell<T>>"]), 0),
+            ("Castable".to_string(), "interface".to_string(), Vec::new(), 215),
+            ("Node".to_string(), "struct".to_string(), strings(&["Base"]), 279),
+        ]
+    );
+    let fields: Vec<(String, String, usize)> =
+        classes[0].fields.iter().map(|x| (x.name.clone(), x.typ.clone(), x.start)).collect();
+    let expected = vec![("charge", "int", 106), ("cost", "int", 118), ("me", "Session", 149), ("xs", "int[]", 163), ("c", "Object", 262)];
+    let expected: Vec<(String, String, usize)> =
+        expected.into_iter().map(|(x, y, z)| (x.to_string(), y.to_string(), z)).collect();
+    assert_eq!(fields, expected);
+    assert_eq!(classes[2].fields[0].typ, "struct Node*");
+    assert_eq!(&text[classes[2].body..classes[2].end], "{\npublic:\n    struct Node *next;\n}");
+}
+
+#[test]
+fn test_imports() {
+    let found = |text: &str| -> Vec<(String, Option<String>, String)> {
+        Clike {}
+            .read_imports(text)
+            .into_iter()
+            .map(|x| (x.path, x.alias, text.chars().skip(x.start).take(x.end - x.start).collect()))
+            .collect()
+    };
+    let text = "#include <stdio.h>\n  # include \"spell.h\" // the spell\nusing namespace std;\nusing Id = int;\n";
+    assert_eq!(
+        found(text),
+        vec![
+            ("stdio.h".into(), None, "#include <stdio.h>".into()),
+            ("spell.h".into(), None, "# include \"spell.h\"".into()),
+            ("std".into(), None, "using namespace std;".into()),
+        ]
+    );
+    let text = "package game;\nimport java.util.*;\nimport static java.lang.Math.max;\n// import nothing;\nclass Spell { int imports; }\n";
+    assert_eq!(
+        found(text),
+        vec![
+            ("java.util.*".into(), None, "import java.util.*;".into()),
+            ("java.lang.Math.max".into(), None, "import static java.lang.Math.max;".into()),
+        ]
+    );
+    let text = "import React, { useState } from 'react'\nimport * as fs from \"fs\";\nimport {\n  a,\n  b as c,\n} from './ab';\n";
+    assert_eq!(
+        found(text),
+        vec![
+            ("react".into(), Some("React".into()), "import React, { useState } from 'react'".into()),
+            ("fs".into(), Some("fs".into()), "import * as fs from \"fs\";".into()),
+            ("./ab".into(), None, "import {\n  a,\n  b as c,\n} from './ab';".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_aliases() {
+    let found = |text: &str| -> Vec<(String, String)> {
+        Clike {}.read_aliases(text).into_iter().map(|x| (x.name, x.typ)).collect()
+    };
+    let text = "typedef unsigned long long u64;\ntypedef struct Node { int v; } Node, *NodePtr;\ntypedef struct { int x; } Point;\ntypedef int (*callback)(int);\ntypedef char name_t[16];\nusing Id = std::vector<int>;\n";
+    assert_eq!(
+        found(text),
+        vec![
+            ("u64".into(), "unsigned long long".into()),
+            ("Node".into(), "struct Node".into()),
+            ("NodePtr".into(), "struct Node*".into()),
+            ("name_t".into(), "char[16]".into()),
+            ("Id".into(), "std::vector<int>".into()),
+        ]
+    );
+    let text = "#include <list.h>\nimport java.util.List;\nimport java.util.*;\n";
+    assert_eq!(found(text), vec![("List".into(), "java.util.List".into())]);
+}
+
+#[test]
+fn test_preprocessor() {
+    let text = "#include <limits.h>\n#define MAX(a, b) \\\n    ((a) > (b) ? (a) : (b))\n#if defined(DEBUG) && LIMIT\nint f(int x) { return MAX(x, LIMIT); }\n#endif\n";
+    let names: Vec<String> = Clike {}.read_functions(text).into_iter().map(|x| x.name).collect();
+    assert_eq!(names, vec!["f"]);
+    let found: Vec<(String, String, usize)> = Clike {}
+        .read_identifiers(text)
+        .into_iter()
+        .map(|x| (x.name, x.typ, x.start))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("MAX".into(), "macro".into(), 28),
+            ("DEBUG".into(), "macro".into(), 80),
+            ("f".into(), "int".into(), 100),
+            ("x".into(), "int".into(), 106),
+            ("MAX".into(), "macro".into(), 118),
+            ("x".into(), "int".into(), 122),
+        ]
+    );
+    let text = "#if 0\nold();\n#  if X\n#  endif\n#elif Y\nnew();\n#endif\n#if false\nnone();\n";
+    let spans: Vec<(usize, usize)> = Clike::disabled(text).iter().map(|x| (x.start, x.end)).collect();
+    assert_eq!(spans, vec![(5, 30), (61, 70)]);
+}
+
+#[test]
+fn test_keywords() {
+    let text = "void f(final int q) {\n    static int x = 1;\n    final Session me = s;\n    return me;\n    throw err;\n    new Foo(q);\n}\n";
+    let found: Vec<(String, String, usize)> = Clike {}.read_identifiers(text).into_iter().map(|x| (x.name, x.typ, x.start)).collect();
+    let expected = vec![("q", "int", 17), ("x", "int", 37), ("me", "Session", 62), ("me", "Session", 81), ("q", "int", 112)];
+    let expected: Vec<(String, String, usize)> = expected.into_iter().map(|(x, y, z)| (x.to_string(), y.to_string(), z)).collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn test_attributes() {
+    let text = "@Override\npublic void f(@Nullable Session me) {\n  int x = me.y;\n}\n[[nodiscard]] int g(int a) { return a; }\n";
+    let functions: Vec<(String, String, Vec<Parameter>)> =
+        Clike {}.read_functions(text).into_iter().map(|x| (x.name, x.typ, x.params)).collect();
+    assert_eq!(
+        functions,
+        vec![
+            ("f".into(), "void".into(), vec![Parameter::new("me".into(), "Session".into())]),
+            ("g".into(), "int".into(), vec![Parameter::new("a".into(), "int".into())]),
+        ]
+    );
+    let names: Vec<(String, String)> = Clike {}.read_identifiers(text).into_iter().map(|x| (x.name, x.typ)).take(3).collect();
+    assert_eq!(
+        names,
+        vec![("me".into(), "Session".into()), ("x".into(), "int".into()), ("me".into(), "Session".into())]
+    );
+}
+
+#[test]
+fn test_members() {
+    let text = "class Spell {\n    void cast() { me.go(); charge++; }\n    private Session me;\n    private int charge;\n    void reset() { int charge = 0; }\n}\nvoid other() { me = 1; }\n";
+    let found: Vec<(String, String, usize)> =
+        Clike {}.read_identifiers(text).into_iter().map(|x| (x.name, x.typ, x.start)).collect();
+    let expected = vec![
+        ("Spell", "class", 6),
+        ("me", "Session", 32),
+        ("charge", "int", 41),
+        ("me", "Session", 73),
+        ("charge", "int", 93),
+        ("charge", "int", 124),
+    ];
+    let expected: Vec<(String, String, usize)> =
+        expected.into_iter().map(|(x, y, z)| (x.to_string(), y.to_string(), z)).collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn test_unicode() {
+    let text = "// héllo wörld\nint naïve = 1;\nvoid größe(String $x_1) {\n    naïve += $x_1.length();\n}\n";
+    let found: Vec<(String, String)> = Clike {}
+        .read_identifiers(text)
+        .into_iter()
+        .map(|x| (text[x.start..x.end].to_string(), x.typ))
+        .collect();
+    let expected = vec![("naïve", "int"), ("$x_1", "String"), ("naïve", "int"), ("$x_1", "String")];
+    let expected: Vec<(String, String)> = expected.into_iter().map(|(x, y)| (x.to_string(), y.to_string())).collect();
+    assert_eq!(found, expected);
+    let functions = Clike {}.read_functions(text);
+    assert_eq!(functions[0].name, "größe");
+    assert_eq!(&text[functions[0].start..functions[0].start + 4], "void");
+}
+
+#[test]
+fn test_lambdas() {
+    let text = "class Spell {\n    void cast(List<Session> all) {\n        all.forEach(me -> me.go());\n        Comparator<Session> c = (a, b) -> { return a.x - b.x; };\n        Runnable r = new Runnable() {\n            public void run() { count++; }\n            int count;\n        };\n        int me = 0;\n    }\n}\n";
+    let found: Vec<(String, String)> = Clike {}.read_identifiers(text).into_iter().map(|x| (x.name, x.typ)).collect();
+    let expected = vec![
+        ("Spell", "class"),
+        ("all", "List<Session>"),
+        ("all", "List<Session>"),
+        ("me", "_"),
+        ("me", "_"),
+        ("c", "Comparator<Session>"),
+        ("a", "_"),
+        ("b", "_"),
+        ("a", "_"),
+        ("b", "_"),
+        ("r", "Runnable"),
+        ("count", "int"),
+        ("count", "int"),
+        ("me", "int"),
+    ];
+    let expected: Vec<(String, String)> = expected.into_iter().map(|(x, y)| (x.to_string(), y.to_string())).collect();
+    assert_eq!(found, expected);
+    let names: Vec<String> = Clike {}.read_functions(text).into_iter().map(|x| x.name).collect();
+    assert_eq!(names, vec!["cast", "run"]);
+    let text = "void f(node *p) {\n    auto add = [&](int a, int b) -> int { return a + b; };\n    int x = p->next + a;\n}\n";
+    let names: Vec<String> = Clike {}.read_identifiers(text).into_iter().map(|x| x.name).collect();
+    assert_eq!(names, vec!["p", "add", "a", "b", "a", "b", "x", "p"]);
+}
+
+#[test]
+fn test_control_flow() {
+    let text = "function load(a) {\n    if(a) { go(); }\n    while(a){ a--; }\n    switch(a){ }\n    try { x(); } catch(e){ }\n    app.get(\"/\", function(req) { return req; });\n    items.each(f) { }\n}\nclass Foo {\n    Foo::Foo(int a) : b(a), c{2} { }\n    synchronized(this) { }\n    int *ptr(int a) { return 0; }\n}\n";
+    let functions = Clike {}.read_functions(text);
+    let names: Vec<&str> = functions.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["load", "Foo", "ptr"]);
+    assert_eq!(functions[1].params, vec![Parameter::new("a".into(), "int".into())]);
+    assert_eq!(&text[functions[1].body..functions[1].end], "{ }");
+}
+
+#[test]
+fn test_unbalanced() {
+    let text = "int f() {\n    int a = 1;\n}\n}\n#if X\nvoid g() {\n#endif\n    int b = a;\n}\n";
+    let names: Vec<(String, String)> = Clike {}.read_identifiers(text).into_iter().map(|x| (x.name, x.typ)).collect();
+    assert_eq!(names, vec![("f".to_string(), "int".to_string()), ("a".to_string(), "int".to_string()), ("b".to_string(), "int".to_string())]);
+    let found: Vec<(String, usize)> = Clike {}.diagnose(text).into_iter().map(|x| (x.message, x.start)).collect();
+    assert_eq!(found, vec![("`}` closes no block".to_string(), 27)]);
+    assert!(Clike {}.diagnose("void f() { char c = '}'; /* { */ }").is_empty());
+    let found: Vec<(String, usize)> = Clike {}.diagnose("void f() {\n    if (a) { }\n").into_iter().map(|x| (x.message, x.start)).collect();
+    assert_eq!(found, vec![("`{` is never closed".to_string(), 9)]);
+}
+
+#[test]
+fn test_interrupted_name() {
+    use super::{csharp::CSharp, go::Go, kotlin::Kotlin};
+    // A block opens partway through a name, which a member access and a
+    // string follow
+    let text = "f(x{<.x\"s\"";
+    for lang in [&Clike {} as &dyn Language, &CSharp {}, &Kotlin {}, &Go {}] {
+        lang.read_identifiers(text);
+    }
+}
+
+#[test]
+fn test_numbers() {
+    // A number is one token, so neither `x1F` nor the `.5` within it is read
+    // as a name
+    let text = "int x1F = 0x1F;\ndouble d = 1.5 ;\nint e = d;\n";
+    let found: Vec<(String, usize)> = Clike {}.read_identifiers(text).into_iter().map(|x| (x.name, x.start)).collect();
+    assert_eq!(found, vec![("x1F".to_string(), 4), ("d".to_string(), 23), ("e".to_string(), 37), ("d".to_string(), 41)]);
+}
@@ -0,0 +1,294 @@
+//! Provides the parser for C#, which specializes the `Clike` parser
+
+use super::clike::Clike;
+use super::parsing::{import_aliases, Aliases, Class, Classes, Function, Functions, Identifier, Identifiers, Import, Imports};
+use super::{keywords, lexer, CommentSyntax, Language};
+
+/// A Functions and Identifiers parser for C#, built on the `Clike` parser.
+///
+/// Before handing code to `Clike`, attributes (`[Serializable]`) and
+/// modifiers (`public`, `readonly`, ...) are blanked out so that properties
+/// and fields are seen as plain declarations, and afterwards identifiers
+/// declared with `var` have their type inferred from `new` expressions
+/// and literals where possible.
+pub struct CSharp {}
+
+impl CSharp {
+    fn is_modifier(x: &str) -> bool {
+        keywords::is_modifier(
+            "csharp",
+            &[
+                "abstract",
+                "async",
+                "const",
+                "extern",
+                "internal",
+                "new",
+                "override",
+                "partial",
+                "private",
+                "protected",
+                "public",
+                "readonly",
+                "sealed",
+                "static",
+                "unsafe",
+                "virtual",
+                "volatile",
+                "ref",
+                "out",
+                "in",
+                "params",
+            ],
+            x,
+        )
+    }
+
+    /// Replaces attributes and modifiers with spaces, keeping every other
+    /// character (and so every offset) where it was.
+    fn blank(text: &str) -> String {
+        let code = lexer::blank(text, &lexer::lex(text, CommentSyntax::C));
+        let text = &lexer::blank(text, &lexer::attributes(&code, true));
+        let mut out = String::with_capacity(text.len());
+        let mut i = 0;
+        while let Some(c) = text[i..].chars().next() {
+            if lexer::is_ident_start(c) {
+                let len = text[i..]
+                    .find(|x: char| !lexer::is_ident_char(x))
+                    .unwrap_or(text.len() - i);
+                let word = &text[i..i + len];
+                if Self::is_modifier(word)
+                    && !(word == "new" && Self::is_new_expression(text, i + len))
+                {
+                    out.extend(std::iter::repeat_n(' ', len));
+                } else {
+                    out += word;
+                }
+                i += len;
+                continue;
+            }
+            out.push(c);
+            i += c.len_utf8();
+        }
+        out
+    }
+
+    /// Whether the `new` ending at `end` creates an object, as opposed to
+    /// being the `new` modifier that hides an inherited member.
+    fn is_new_expression(text: &str, end: usize) -> bool {
+        let before = text[..end - 3].trim_end();
+        before.ends_with('=')
+            || before.ends_with('(')
+            || before.ends_with(',')
+            || before.ends_with("return")
+    }
+
+    /// Infers the type of a `var` declaration from its initializer, given
+    /// the index just past the declared name.
+    fn infer(text: &str, end: usize) -> Option<String> {
+        let rest = text[end..].trim_start().strip_prefix('=')?.trim_start();
+        if let Some(rest) = rest.strip_prefix("new ") {
+            let rest = rest.trim_start();
+            let len = rest
+                .find(|x: char| {
+                    !(x.is_alphanumeric()
+                        || x == '_'
+                        || x == '.'
+                        || x == '<'
+                        || x == '>'
+                        || x == ','
+                        || x == ' ')
+                })
+                .unwrap_or(rest.len());
+            let ty = rest[..len].trim();
+            if !ty.is_empty() {
+                return Some(ty.to_string());
+            }
+        }
+        match rest.chars().next()? {
+            '"' => Some("string".to_string()),
+            '\'' => Some("char".to_string()),
+            c if c.is_ascii_digit() => {
+                let len = rest
+                    .find(|x: char| !(x.is_ascii_alphanumeric() || x == '.'))
+                    .unwrap_or(rest.len());
+                let lit = &rest[..len];
+                Some(
+                    if lit.ends_with('m') || lit.ends_with('M') {
+                        "decimal"
+                    } else if lit.ends_with('f') || lit.ends_with('F') {
+                        "float"
+                    } else if lit.contains('.') || lit.ends_with('d') || lit.ends_with('D') {
+                        "double"
+                    } else if lit.ends_with('L') || lit.ends_with('l') {
+                        "long"
+                    } else {
+                        "int"
+                    }
+                    .to_string(),
+                )
+            }
+            _ if rest.starts_with("true") || rest.starts_with("false") => Some("bool".to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Functions for CSharp {
+    /// Parses out method declarations from C# code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Function containing information on every method
+    /// declared within text
+    fn read_functions(&self, text: &str) -> Vec<Function> {
+        Clike {}.read_functions(&Self::blank(text))
+    }
+}
+
+impl Identifiers for CSharp {
+    /// Parses out identifier uses from C# code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing information on every use of
+    /// an identifier declared within the code, with `var` replaced by
+    /// the inferred type where the initializer makes it clear
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        let mut v = Clike {}.read_identifiers(&Self::blank(text));
+        let mut inferred = Vec::new();
+        for ident in v.iter_mut() {
+            if ident.typ != "var" {
+                continue;
+            }
+            match inferred.iter().find(|(name, _)| *name == ident.name) {
+                Some((_, ty)) => ident.typ = String::clone(ty),
+                None => {
+                    if let Some(ty) = Self::infer(text, ident.end) {
+                        inferred.push((ident.name.clone(), ty.clone()));
+                        ident.typ = ty;
+                    }
+                }
+            }
+        }
+        v
+    }
+}
+
+impl Classes for CSharp {
+    /// Parses out class, struct, interface, and enum definitions from C# code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Class containing information on every type defined within text
+    fn read_classes(&self, text: &str) -> Vec<Class> {
+        Clike {}.read_classes(&Self::blank(text))
+    }
+}
+
+impl Imports for CSharp {
+    /// Writes a `using` directive of the namespace `path`, like
+    /// `using System.Text;`
+    fn import_statement(&self, path: &str) -> Option<String> {
+        Some(format!("using {};", path))
+    }
+
+    /// Parses out `using` directives from C# code, including aliases like
+    /// `using Json = Newtonsoft.Json;`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Import for every namespace or type imported within text
+    fn read_imports(&self, text: &str) -> Vec<Import> {
+        Clike::imports(&Self::blank(text), true)
+    }
+}
+
+impl Aliases for CSharp {
+    /// Parses out the names brought in by `using` directives from C# code,
+    /// including aliases like `using Json = Newtonsoft.Json;`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier for each alias within text, whose type is the
+    /// namespace or type it stands for
+    fn read_aliases(&self, text: &str) -> Vec<Identifier> {
+        import_aliases(self.read_imports(text))
+    }
+}
+
+impl Language for CSharp {
+    fn name(&self) -> &'static str {
+        "csharp"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["cs"]
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax::C
+    }
+
+    fn spans(&self, text: &str) -> Vec<lexer::Span> {
+        Clike {}.spans(text)
+    }
+}
+
+#[test]
+fn test_csharp_identifiers() {
+    let text = "[Serializable]\npublic class Spell {\n    public Session Me { get; set; }\n    private readonly int charge = 0;\n    public void OnSpawn() {\n        var bolt = new Lightning(Me);\n        var count = 1.5;\n        bolt.Fire();\n        count = 2;\n    }\n}\n";
+    let idents = CSharp {}.read_identifiers(text);
+    let of = |name: &str| -> Vec<String> {
+        idents
+            .iter()
+            .filter(|x| x.name == name)
+            .map(|x| x.typ.clone())
+            .collect()
+    };
+    assert_eq!(of("Me"), vec!["Session"; 2]);
+    assert_eq!(of("charge"), vec!["int"]);
+    assert_eq!(of("bolt"), vec!["Lightning"; 2]);
+    assert_eq!(of("count"), vec!["double"; 2]);
+    assert!(of("Serializable").is_empty());
+    let names: Vec<String> = CSharp {}
+        .read_functions(text)
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, vec!["OnSpawn"]);
+}
+
+#[test]
+fn test_csharp_imports() {
+    let text = "using System;\nusing static System.Math;\nusing Json = Newtonsoft.Json;\n\nclass Spell {\n    void Cast() {\n        using (var x = Open()) { }\n    }\n}\n";
+    let found: Vec<(String, Option<String>)> = CSharp {}.read_imports(text).into_iter().map(|x| (x.path, x.alias)).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("System".into(), None),
+            ("System.Math".into(), None),
+            ("Newtonsoft.Json".into(), Some("Json".into())),
+        ]
+    );
+}
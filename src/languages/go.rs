@@ -0,0 +1,788 @@
+//! Provides the parser for Go
+
+use super::parsing::{Aliases, Class, Classes, Function, Functions, Identifier, Identifiers, Import, Imports, Parameter};
+use super::tokens::{self, Scopes, Token, TokenKind, Tokenizer};
+use super::{keywords, CommentSyntax, Language};
+use std::collections::HashMap;
+
+/// A Functions and Identifiers parser for Go, which understands `func`
+/// declarations (including methods with receivers), `var` and `:=`
+/// declarations, and struct fields.
+pub struct Go {}
+
+/// Reads a raw (backquoted) string, which may span lines and has no escapes
+fn raw_string(text: &str, i: usize) -> Option<(Option<TokenKind>, usize)> {
+    text[i..].starts_with('`').then(|| {
+        let end = text[i + 1..].find('`').map_or(text.len(), |x| i + 1 + x + 1);
+        (Some(TokenKind::Str), end)
+    })
+}
+
+/// Splits Go source into tokens. Interpreted strings, raw strings, and runes
+/// become single literal tokens.
+const TOKENIZER: Tokenizer = Tokenizer {
+    operators: &[":=", "..."],
+    special: Some(raw_string),
+    ..Tokenizer::new(CommentSyntax::C)
+};
+
+/// The type recorded for declarations whose type we could not infer
+const INFERRED: &str = "_";
+
+impl Go {
+    fn is_keyword(x: &str) -> bool {
+        keywords::is_keyword(
+            "go",
+            &[
+                "break",
+                "case",
+                "chan",
+                "const",
+                "continue",
+                "default",
+                "defer",
+                "else",
+                "fallthrough",
+                "for",
+                "func",
+                "go",
+                "goto",
+                "if",
+                "import",
+                "interface",
+                "map",
+                "package",
+                "range",
+                "return",
+                "select",
+                "struct",
+                "switch",
+                "type",
+                "var",
+            ],
+            x,
+        )
+    }
+
+    /// Reads a type starting at token `i`, stopping at a `,`, `;`, `=`, `)`,
+    /// or `{` that is not nested inside brackets, or at the end of the line.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the type text (or None if there was no type) and the
+    /// index of the token following it.
+    fn read_type(text: &str, tokens: &[Token], mut i: usize) -> (Option<String>, usize) {
+        let first = i;
+        let mut depth = 0;
+        while let Some(t) = tokens.get(i) {
+            if i > first && depth == 0 && text[tokens[i - 1].end..t.start].contains('\n') {
+                break;
+            }
+            let s = &text[t.start..t.end];
+            match s {
+                "(" | "[" => depth += 1,
+                ")" | "]" if depth > 0 => depth -= 1,
+                "{" if depth == 0
+                    && i > first
+                    && matches!(
+                        &text[tokens[i - 1].start..tokens[i - 1].end],
+                        "struct" | "interface"
+                    ) =>
+                {
+                    // An anonymous struct or interface type; take it whole
+                    let mut inner = 0;
+                    while let Some(t) = tokens.get(i) {
+                        match &text[t.start..t.end] {
+                            "{" => inner += 1,
+                            "}" => inner -= 1,
+                            _ => {}
+                        }
+                        i += 1;
+                        if inner == 0 {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                "," | ";" | "=" | ")" | "{" if depth == 0 => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        if i == first {
+            (None, i)
+        } else {
+            let ty = &text[tokens[first].start..tokens[i - 1].end];
+            (Some(ty.split_whitespace().collect::<Vec<_>>().join(" ")), i)
+        }
+    }
+
+    /// Reads a parenthesized list of `name Type` pairs, as used for
+    /// receivers and parameters, where `a, b int` gives both names the type.
+    /// Lists of bare types (such as unnamed results) produce no entries.
+    ///
+    /// # Returns
+    ///
+    /// The (name, type, token) triples found, and the index of the
+    /// token after the closing parenthesis.
+    fn read_params(
+        text: &str,
+        tokens: &[Token],
+        mut i: usize,
+    ) -> (Vec<(String, String, Token)>, usize) {
+        let mut v = Vec::new();
+        let mut names = Vec::new();
+        i += 1;
+        while let Some(t) = tokens.get(i) {
+            let s = &text[t.start..t.end];
+            match s {
+                ")" => return (v, i + 1),
+                "," => i += 1,
+                _ if t.kind == TokenKind::Ident
+                    && tokens.get(i + 1).map(|x| &text[x.start..x.end]) == Some(",") =>
+                {
+                    names.push(*t);
+                    i += 1;
+                }
+                _ if t.kind == TokenKind::Ident => {
+                    let (ty, next) = Self::read_type(text, tokens, i + 1);
+                    match ty {
+                        Some(ty) => {
+                            names.push(*t);
+                            for n in names.drain(..) {
+                                v.push((text[n.start..n.end].to_string(), ty.clone(), n));
+                            }
+                        }
+                        None => names.clear(),
+                    }
+                    i = next.max(i + 1);
+                }
+                _ => {
+                    names.clear();
+                    let (_, next) = Self::read_type(text, tokens, i);
+                    i = next.max(i + 1);
+                }
+            }
+        }
+        (v, i)
+    }
+
+    /// Guesses the type of the expression starting at token `i` for
+    /// composite literals such as `Foo{...}` and `&Foo{...}`.
+    fn infer(text: &str, tokens: &[Token], i: usize) -> String {
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let (ptr, j) = if s(i) == Some("&") {
+            ("*", i + 1)
+        } else {
+            ("", i)
+        };
+        match (tokens.get(j), s(j + 1)) {
+            (Some(t), Some("{"))
+                if t.kind == TokenKind::Ident && !Self::is_keyword(&text[t.start..t.end]) =>
+            {
+                format!("{}{}", ptr, &text[t.start..t.end])
+            }
+            _ => INFERRED.to_string(),
+        }
+    }
+}
+
+impl Functions for Go {
+    /// Parses out `func` declarations from Go code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Function containing information on every function
+    /// and method declared within text
+    fn read_functions(&self, text: &str) -> Vec<Function> {
+        let tokens = TOKENIZER.code(text);
+        let mut v = Vec::new();
+        for (i, t) in tokens.iter().enumerate() {
+            if &text[t.start..t.end] != "func" {
+                continue;
+            }
+            let mut j = i + 1;
+            if tokens.get(j).map(|x| &text[x.start..x.end]) == Some("(") {
+                j = Self::read_params(text, &tokens, j).1;
+            }
+            let n = match tokens.get(j).filter(|x| x.kind == TokenKind::Ident) {
+                Some(n) => n,
+                None => continue,
+            };
+            if tokens.get(j + 1).map(|x| &text[x.start..x.end]) != Some("(") {
+                continue;
+            }
+            let (params, next) = Self::read_params(text, &tokens, j + 1);
+            let params = params.into_iter().map(|(x, y, _)| Parameter::new(x, y)).collect();
+            // The results are either a parenthesized list or a single type
+            let (typ, next) = match tokens.get(next).map(|x| &text[x.start..x.end]) {
+                Some("(") => {
+                    let close = tokens::matching(text, &tokens, next);
+                    let typ = &text[tokens[next].start..tokens[close].end];
+                    (typ.split_whitespace().collect::<Vec<_>>().join(" "), close + 1)
+                }
+                Some("{") | None => (String::new(), next),
+                Some(_) => {
+                    let (typ, next) = Self::read_type(text, &tokens, next);
+                    (typ.unwrap_or_default(), next)
+                }
+            };
+            let (body, end) = match tokens.get(next).filter(|x| &text[x.start..x.end] == "{") {
+                Some(b) => (b.start, tokens[tokens::matching(text, &tokens, next)].end),
+                None => {
+                    let end = tokens[next - 1].end;
+                    (end, end)
+                }
+            };
+            let name = text[n.start..n.end].to_string();
+            v.push(Function::new(name, typ, params, t.start, body, end));
+        }
+        v
+    }
+}
+
+impl Identifiers for Go {
+    /// Parses out identifier uses from Go code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing information on every use of
+    /// an identifier declared within the code. Declarations whose type
+    /// cannot be inferred are given the type `_`.
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        let mut scopes = Scopes::new();
+        let mut pending = HashMap::new();
+        let mut fields = false;
+        let mut i = 0;
+        while let Some(t) = tokens.get(i) {
+            let prev = if i > 0 { s(i - 1) } else { None };
+            let line_start = i == 0
+                || text[tokens[i - 1].end..t.start].contains('\n')
+                || matches!(prev, Some("{") | Some("(") | Some(";"));
+            match s(i).unwrap() {
+                "{" => {
+                    scopes.push(std::mem::take(&mut pending));
+                }
+                "}" => {
+                    scopes.pop();
+                    fields = false;
+                }
+                "import" => {
+                    i += 1;
+                    if s(i) == Some("(") {
+                        while s(i).is_some() && s(i) != Some(")") {
+                            i += 1;
+                        }
+                    } else {
+                        i += 2;
+                    }
+                }
+                "struct" if s(i + 1) == Some("{") => {
+                    // Field declarations look just like `var` declarations
+                    scopes.push(HashMap::new());
+                    fields = true;
+                    i += 2;
+                    continue;
+                }
+                "func" => {
+                    let mut j = i + 1;
+                    let mut params = Vec::new();
+                    if s(j) == Some("(") {
+                        let (p, next) = Self::read_params(text, &tokens, j);
+                        params.extend(p);
+                        j = next;
+                    }
+                    if tokens.get(j).map(|x| x.kind) == Some(TokenKind::Ident) {
+                        j += 1;
+                    }
+                    if s(j) == Some("(") {
+                        let (p, next) = Self::read_params(text, &tokens, j);
+                        params.extend(p);
+                        j = next;
+                    }
+                    if s(j) == Some("(") {
+                        // Named results are in scope too
+                        let (p, next) = Self::read_params(text, &tokens, j);
+                        params.extend(p);
+                        j = next;
+                    }
+                    for (name, typ, t) in params {
+                        v.push(Identifier::new(name.clone(), typ.clone(), t.start, t.end));
+                        pending.insert(name, typ);
+                    }
+                    i = j;
+                    continue;
+                }
+                "var" | "const" if s(i + 1) == Some("(") => {
+                    // A grouped declaration; each line is treated as its own `var`
+                    i += 2;
+                    fields = true;
+                    scopes.push(HashMap::new());
+                    continue;
+                }
+                ")" if fields && scopes.depth() > 1 => {
+                    for (name, typ) in scopes.pop().unwrap() {
+                        scopes.declare(name, typ);
+                    }
+                    fields = false;
+                }
+                "var" | "const" => {
+                    i = Self::declare(text, &tokens, i + 1, &mut scopes, &mut v);
+                    continue;
+                }
+                _ if fields && t.kind == TokenKind::Ident && line_start => {
+                    i = Self::declare(text, &tokens, i, &mut scopes, &mut v);
+                    continue;
+                }
+                _ if t.kind == TokenKind::Ident && Self::short_decl(text, &tokens, i) => {
+                    let mut names = vec![*t];
+                    let mut j = i + 1;
+                    while s(j) == Some(",") {
+                        names.push(tokens[j + 1]);
+                        j += 2;
+                    }
+                    let typ = if names.len() == 1 {
+                        Self::infer(text, &tokens, j + 1)
+                    } else {
+                        INFERRED.to_string()
+                    };
+                    for n in names {
+                        let name = text[n.start..n.end].to_string();
+                        if name != "_" {
+                            v.push(Identifier::new(name.clone(), typ.clone(), n.start, n.end));
+                            scopes.declare(name, typ.clone());
+                        }
+                    }
+                    i = j + 1;
+                    continue;
+                }
+                name if t.kind == TokenKind::Ident && !Self::is_keyword(name) && prev != Some(".") => {
+                    if let Some(typ) = scopes.lookup(name) {
+                        v.push(Identifier::new(name.to_string(), typ.to_string(), t.start, t.end));
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        v
+    }
+}
+
+impl Classes for Go {
+    /// Parses out struct and interface type declarations from Go code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Class containing information on every struct and
+    /// interface type declared within text, where embedded types are
+    /// the supertypes
+    fn read_classes(&self, text: &str) -> Vec<Class> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        let mut i = 0;
+        // The token closing a grouped `type ( ... )` declaration
+        let mut group = None;
+        while let Some(t) = tokens.get(i) {
+            let spec = match s(i).unwrap() {
+                "type" if s(i + 1) == Some("(") => {
+                    group = Some(tokens::matching(text, &tokens, i + 1));
+                    i += 2;
+                    continue;
+                }
+                "type" => Some((i + 1, t.start)),
+                _ if group.is_some_and(|x| i < x)
+                    && t.kind == TokenKind::Ident
+                    && (s(i - 1) == Some("(") || text[tokens[i - 1].end..t.start].contains('\n')) =>
+                {
+                    Some((i, t.start))
+                }
+                _ => None,
+            };
+            i += 1;
+            let (name, start) = match spec {
+                Some(spec) => spec,
+                None => continue,
+            };
+            let mut k = name + 1;
+            if s(k) == Some("[") {
+                // Type parameters
+                let mut depth = 0;
+                while let Some(x) = s(k) {
+                    match x {
+                        "[" => depth += 1,
+                        "]" => depth -= 1,
+                        _ => {}
+                    }
+                    k += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            let kind = match s(k) {
+                Some(x) if (x == "struct" || x == "interface") && s(k + 1) == Some("{") => x,
+                _ => continue,
+            };
+            let close = tokens::matching(text, &tokens, k + 1);
+            // A body that is never closed ends with the file
+            let (supertypes, fields) = Self::read_members(text, tokens.get(k + 2..close).unwrap_or(&[]));
+            v.push(Class::new(
+                text[tokens[name].start..tokens[name].end].to_string(),
+                kind.to_string(),
+                supertypes,
+                fields,
+                start,
+                tokens[k + 1].start,
+                tokens[close].end,
+            ));
+            i = k + 2;
+        }
+        v
+    }
+}
+
+impl Imports for Go {
+    /// Writes an `import` declaration of the package `path`, like
+    /// `import "net/http"`
+    fn import_statement(&self, path: &str) -> Option<String> {
+        Some(format!("import \"{}\"", path))
+    }
+
+    /// Parses out `import` declarations from Go code, including grouped
+    /// `import ( ... )` declarations
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Import for every imported package within text, with the
+    /// name it is bound to (including `_` and `.`) as the alias. Each import
+    /// in a group spans its own line.
+    fn read_imports(&self, text: &str) -> Vec<Import> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            if s(i) != Some("import") {
+                i += 1;
+                continue;
+            }
+            let (mut j, close, mut start) = match s(i + 1) {
+                Some("(") => (i + 2, tokens::matching(text, &tokens, i + 1), tokens.get(i + 2).map_or(0, |x| x.start)),
+                _ => (i + 1, i + 2, tokens[i].start),
+            };
+            while j < close.min(tokens.len()) {
+                let alias = match tokens[j].kind {
+                    TokenKind::Str => None,
+                    _ => {
+                        j += 1;
+                        s(j - 1).map(String::from)
+                    }
+                };
+                // A string cut off before its closing quote imports nothing
+                let path = tokens.get(j).filter(|x| x.kind == TokenKind::Str).and_then(|x| {
+                    let quoted = &text[x.start..x.end];
+                    let quote = quoted.chars().next()?;
+                    quoted.strip_prefix(quote)?.strip_suffix(quote)
+                });
+                if let Some(path) = path {
+                    v.push(Import::new(path.to_string(), alias, start, tokens[j].end));
+                }
+                j += 1;
+                start = tokens.get(j).map_or(0, |x| x.start);
+            }
+            i = close;
+        }
+        v
+    }
+}
+
+impl Aliases for Go {
+    /// Parses out type alias declarations like `type ID = int` from Go code,
+    /// including those within grouped `type ( ... )` declarations. Type
+    /// definitions without the `=` make new types, so they are not aliases.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier for each alias within text, whose type is the
+    /// type it stands for
+    fn read_aliases(&self, text: &str) -> Vec<Identifier> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        // The token closing a grouped `type ( ... )` declaration
+        let mut group = None;
+        for (i, t) in tokens.iter().enumerate() {
+            let name = match s(i).unwrap() {
+                "type" if s(i + 1) == Some("(") => {
+                    group = Some(tokens::matching(text, &tokens, i + 1));
+                    continue;
+                }
+                "type" => i + 1,
+                _ if group.is_some_and(|x| i < x)
+                    && t.kind == TokenKind::Ident
+                    && (s(i - 1) == Some("(") || text[tokens[i - 1].end..t.start].contains('\n')) =>
+                {
+                    i
+                }
+                _ => continue,
+            };
+            if tokens.get(name).map(|x| x.kind) != Some(TokenKind::Ident) || s(name + 1) != Some("=") {
+                continue;
+            }
+            if let (Some(typ), _) = Self::read_type(text, &tokens, name + 2) {
+                v.push(Identifier::new(s(name).unwrap().to_string(), typ, tokens[name].start, tokens[name].end));
+            }
+        }
+        v
+    }
+}
+
+impl Go {
+    /// Reads the members of a struct or interface body, given the tokens
+    /// between its braces, one member per line
+    ///
+    /// # Returns
+    ///
+    /// The embedded types, and the fields with their types. Interface
+    /// methods are neither.
+    fn read_members(text: &str, tokens: &[Token]) -> (Vec<String>, Vec<Identifier>) {
+        let mut supertypes = Vec::new();
+        let mut fields = Vec::new();
+        let mut lines: Vec<Vec<Token>> = Vec::new();
+        let mut depth = 0;
+        for (j, t) in tokens.iter().enumerate() {
+            let s = &text[t.start..t.end];
+            if j == 0 || (depth == 0 && text[tokens[j - 1].end..t.start].contains('\n')) {
+                lines.push(Vec::new());
+            }
+            match s {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => depth -= 1,
+                ";" if depth == 0 => {
+                    lines.push(Vec::new());
+                    continue;
+                }
+                _ => {}
+            }
+            lines.last_mut().unwrap().push(*t);
+        }
+        for mut line in lines {
+            // Drop any struct tag
+            if line.len() > 1 && line.last().unwrap().kind == TokenKind::Str {
+                line.pop();
+            }
+            let s = |j: usize| line.get(j).map(|t| &text[t.start..t.end]);
+            if line.is_empty() || s(1) == Some("(") {
+                continue;
+            }
+            if line.len() == 1 || s(0) == Some("*") || s(1) == Some(".") {
+                let typ = &text[line[0].start..line[line.len() - 1].end];
+                supertypes.push(typ.trim_start_matches('*').to_string());
+                continue;
+            }
+            let mut names = vec![line[0]];
+            let mut j = 1;
+            while s(j) == Some(",") && j + 1 < line.len() {
+                names.push(line[j + 1]);
+                j += 2;
+            }
+            if j >= line.len() {
+                continue;
+            }
+            let typ = &text[line[j].start..line[line.len() - 1].end];
+            let typ = typ.split_whitespace().collect::<Vec<_>>().join(" ");
+            for n in names {
+                fields.push(Identifier::new(text[n.start..n.end].to_string(), typ.clone(), n.start, n.end));
+            }
+        }
+        (supertypes, fields)
+    }
+
+    /// Whether the comma-separated identifiers starting at token `i`
+    /// are followed by `:=`, making them a short variable declaration.
+    fn short_decl(text: &str, tokens: &[Token], mut i: usize) -> bool {
+        while tokens.get(i).map(|t| t.kind) == Some(TokenKind::Ident) {
+            match tokens.get(i + 1).map(|t| &text[t.start..t.end]) {
+                Some(",") => i += 2,
+                Some(":=") => return true,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Processes the `a, b Type = ...` part of a `var` declaration or struct
+    /// field starting at token `i`, returning the index to continue from.
+    fn declare(
+        text: &str,
+        tokens: &[Token],
+        mut i: usize,
+        scopes: &mut Scopes,
+        v: &mut Vec<Identifier>,
+    ) -> usize {
+        let mut names = Vec::new();
+        while let Some(t) = tokens.get(i).filter(|x| x.kind == TokenKind::Ident) {
+            names.push(*t);
+            if tokens.get(i + 1).map(|x| &text[x.start..x.end]) == Some(",") {
+                i += 2;
+            } else {
+                i += 1;
+                break;
+            }
+        }
+        let (ty, next) = match tokens.get(i).map(|x| &text[x.start..x.end]) {
+            Some("=") => (None, i),
+            _ => Self::read_type(text, tokens, i),
+        };
+        let typ = match ty {
+            Some(ty) => ty,
+            None if names.len() == 1 => Self::infer(text, tokens, next + 1),
+            None => INFERRED.to_string(),
+        };
+        for n in names {
+            let name = text[n.start..n.end].to_string();
+            v.push(Identifier::new(name.clone(), typ.clone(), n.start, n.end));
+            scopes.declare(name, typ.clone());
+        }
+        next
+    }
+}
+
+impl Language for Go {
+    fn name(&self) -> &'static str {
+        "go"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["go"]
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax::C
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        TOKENIZER.tokenize(text)
+    }
+}
+
+#[test]
+fn test_go_functions() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.go");
+    let text = std::fs::read_to_string(d).unwrap();
+    let names: Vec<String> = Go {}
+        .read_functions(&text)
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, vec!["NewLightning", "OnSpawn", "main"]);
+
+    let text = "func (l *Lightning) Split(n int, s string) (int, error) {\n\treturn 0, nil\n}\n";
+    let f = &Go {}.read_functions(text)[0];
+    assert_eq!((f.typ.as_str(), f.start, f.body, f.end), ("(int, error)", 0, 56, 74));
+    assert_eq!(f.params, vec![Parameter::new("n".into(), "int".into()), Parameter::new("s".into(), "string".into())]);
+}
+
+#[test]
+fn test_go_identifiers() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.go");
+    let text = std::fs::read_to_string(d).unwrap();
+    let idents = Go {}.read_identifiers(&text);
+    let of = |name: &str| -> Vec<String> {
+        idents
+            .iter()
+            .filter(|x| x.name == name)
+            .map(|x| x.typ.clone())
+            .collect()
+    };
+    assert_eq!(of("me"), vec!["*Session"; 4]);
+    assert_eq!(of("l"), vec!["*Lightning"; 3]);
+    assert_eq!(of("Charge"), vec!["int"]);
+    assert_eq!(of("label"), vec!["string"]);
+    assert_eq!(of("x"), vec!["int"; 3]);
+    assert_eq!(of("y"), vec!["int"; 3]);
+    assert_eq!(of("total"), vec!["_"; 2]);
+    assert_eq!(of("hits"), vec!["_"]);
+    assert_eq!(of("light"), vec!["*Lightning"; 2]);
+    assert_eq!(of("name"), vec!["string"; 3]);
+    assert!(of("fmt").is_empty());
+    for ident in &idents {
+        assert_eq!(&text[ident.start..ident.end], ident.name);
+    }
+}
+
+#[test]
+fn test_go_imports() {
+    let text = "package main\n\nimport \"fmt\"\n\nimport (\n\t\"os\"\n\tstr \"strings\"\n\t_ \"embed\"\n)\n";
+    let found: Vec<(String, Option<String>, &str)> = Go {}
+        .read_imports(text)
+        .into_iter()
+        .map(|x| (x.path, x.alias, &text[x.start..x.end]))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("fmt".to_string(), None, "import \"fmt\""),
+            ("os".to_string(), None, "\"os\""),
+            ("strings".to_string(), Some("str".to_string()), "str \"strings\""),
+            ("embed".to_string(), Some("_".to_string()), "_ \"embed\""),
+        ]
+    );
+    // Strings cut off before their closing quote
+    for text in ["package m\nimport \"", "package m\nimport \"é", "import (\n\t`os"] {
+        assert!(Go {}.read_imports(text).is_empty());
+    }
+}
+
+#[test]
+fn test_go_aliases() {
+    let text = "type ID = int\ntype Score int\ntype (\n\tNames = []string\n\tSpell struct{}\n)\n";
+    let found: Vec<(String, String)> = Go {}.read_aliases(text).into_iter().map(|x| (x.name, x.typ)).collect();
+    assert_eq!(found, vec![("ID".into(), "int".into()), ("Names".into(), "[]string".into())]);
+}
+
+#[test]
+fn test_go_classes() {
+    let text = "type Lightning struct {\n\tSpell\n\t*game.Base\n\tcharge, cost int `json:\"c\"`\n\tme *Session\n}\n\ntype (\n\tCaster interface {\n\t\tio.Reader\n\t\tCast(n int) error\n\t}\n\tID int\n)\n";
+    let classes = Go {}.read_classes(text);
+    let found: Vec<(&str, &str, Vec<String>)> =
+        classes.iter().map(|x| (x.name.as_str(), x.kind.as_str(), x.supertypes.clone())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Lightning", "struct", vec!["Spell".to_string(), "game.Base".to_string()]),
+            ("Caster", "interface", vec!["io.Reader".to_string()]),
+        ]
+    );
+    let fields: Vec<(&str, &str)> = classes[0].fields.iter().map(|x| (x.name.as_str(), x.typ.as_str())).collect();
+    assert_eq!(fields, vec![("charge", "int"), ("cost", "int"), ("me", "*Session")]);
+    assert!(classes[1].fields.is_empty());
+    assert_eq!(&text[classes[1].start..classes[1].body], "Caster interface ");
+}
@@ -0,0 +1,91 @@
+//! Provides the keyword and modifier tables the parsers consult, which are
+//! each language's builtin tables plus any words added by the config file
+
+use std::sync::RwLock;
+
+/// The words the config file adds to a language's tables
+struct Added {
+    language: String,
+    keywords: Vec<String>,
+    modifiers: Vec<String>,
+}
+
+static ADDED: RwLock<Vec<Added>> = RwLock::new(Vec::new());
+
+/// Adds words to the tables of a language
+///
+/// # Arguments
+///
+/// * `language` - The name of the language, as returned by `Language::name`
+/// * `keywords` - Words that are never the name or type of a declaration
+/// * `modifiers` - Words that are dropped from the type of a declaration,
+///   like `static`
+pub fn extend(language: &str, keywords: Vec<String>, modifiers: Vec<String>) {
+    if let Ok(mut added) = ADDED.write() {
+        added.push(Added {
+            language: language.to_string(),
+            keywords,
+            modifiers,
+        });
+    }
+}
+
+/// Whether a word was added to one of a language's tables
+fn added(language: &str, word: &str, table: fn(&Added) -> &Vec<String>) -> bool {
+    ADDED
+        .read()
+        .is_ok_and(|x| x.iter().any(|a| a.language == language && table(a).iter().any(|w| w == word)))
+}
+
+/// The words added to a language's tables, which change how its code is
+/// parsed, so that parse results can be cached under them
+///
+/// # Arguments
+///
+/// * `language` - The name of the language
+///
+/// # Returns
+///
+/// A string listing every added keyword and modifier, which is empty if
+/// none were added
+pub fn fingerprint(language: &str) -> String {
+    ADDED.read().map_or(String::new(), |x| {
+        x.iter()
+            .filter(|a| a.language == language)
+            .map(|a| format!("{}|{};", a.keywords.join(","), a.modifiers.join(",")))
+            .collect()
+    })
+}
+
+/// Whether a word is a keyword of a language
+///
+/// # Arguments
+///
+/// * `language` - The name of the language
+/// * `builtin` - The keywords the language's parser ships with
+/// * `word` - The word to look up
+pub fn is_keyword(language: &str, builtin: &[&str], word: &str) -> bool {
+    builtin.contains(&word) || added(language, word, |x| &x.keywords)
+}
+
+/// Whether a word is a modifier of a language
+///
+/// # Arguments
+///
+/// * `language` - The name of the language
+/// * `builtin` - The modifiers the language's parser ships with
+/// * `word` - The word to look up
+pub fn is_modifier(language: &str, builtin: &[&str], word: &str) -> bool {
+    builtin.contains(&word) || added(language, word, |x| &x.modifiers)
+}
+
+#[test]
+fn test_extend() {
+    assert!(is_keyword("cobol", &["PERFORM"], "PERFORM"));
+    assert!(!is_keyword("cobol", &["PERFORM"], "MOVE"));
+    extend("cobol", vec!["MOVE".into()], vec!["GLOBAL".into()]);
+    assert!(is_keyword("cobol", &["PERFORM"], "MOVE"));
+    assert!(!is_keyword("fortran", &[], "MOVE"));
+    assert!(is_modifier("cobol", &[], "GLOBAL"));
+    assert!(!is_modifier("cobol", &[], "MOVE"));
+}
@@ -0,0 +1,757 @@
+//! Provides the parser for Kotlin
+
+use super::parsing::{import_aliases, Aliases, Class, Classes, Function, Functions, Identifier, Identifiers, Import, Imports, Parameter};
+use super::tokens::{Scopes, Token, TokenKind, Tokenizer};
+use super::{keywords, CommentSyntax, Language};
+use std::collections::HashMap;
+
+/// A Functions and Identifiers parser for Kotlin, which understands `fun`
+/// declarations, `val`/`var` properties and locals (with declared or
+/// inferred types), primary constructor parameters, and lambda parameters.
+pub struct Kotlin {}
+
+/// Reads the Kotlin tokens the shared Tokenizer does not know: annotations,
+/// which are dropped along with their arguments, raw `"""` strings, and
+/// backquoted names
+fn special(text: &str, i: usize) -> Option<(Option<TokenKind>, usize)> {
+    let b = text.as_bytes();
+    if b[i] == b'@' {
+        let mut j = i + 1;
+        while j < b.len() && (b[j].is_ascii_alphanumeric() || b[j] == b'_' || b[j] == b'.' || b[j] == b':') {
+            j += 1;
+        }
+        if b.get(j) == Some(&b'(') {
+            let mut depth = 0;
+            while j < b.len() {
+                match b[j] {
+                    b'(' => depth += 1,
+                    b')' => depth -= 1,
+                    _ => {}
+                }
+                j += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+        Some((None, j))
+    } else if text[i..].starts_with("\"\"\"") {
+        Some((Some(TokenKind::Str), text[i + 3..].find("\"\"\"").map_or(b.len(), |x| i + 3 + x + 3)))
+    } else if b[i] == b'`' {
+        Some((Some(TokenKind::Ident), text[i + 1..].find('`').map_or(b.len(), |x| i + 1 + x + 1)))
+    } else {
+        None
+    }
+}
+
+/// Splits Kotlin source into tokens, dropping annotations. Strings
+/// (including raw `"""` strings) and chars become single tokens.
+const TOKENIZER: Tokenizer = Tokenizer {
+    nested: true,
+    operators: &["->", "::", "?."],
+    special: Some(special),
+    ..Tokenizer::new(CommentSyntax::C)
+};
+
+/// The type recorded for declarations whose type we could not infer
+const INFERRED: &str = "_";
+
+impl Kotlin {
+    fn is_keyword(x: &str) -> bool {
+        keywords::is_keyword(
+            "kotlin",
+            &[
+                "as",
+                "break",
+                "class",
+                "continue",
+                "do",
+                "else",
+                "false",
+                "for",
+                "fun",
+                "if",
+                "in",
+                "interface",
+                "is",
+                "null",
+                "object",
+                "package",
+                "return",
+                "super",
+                "this",
+                "throw",
+                "true",
+                "try",
+                "typealias",
+                "typeof",
+                "val",
+                "var",
+                "when",
+                "while",
+                "import",
+            ],
+            x,
+        )
+    }
+
+    fn is_modifier(x: &str) -> bool {
+        keywords::is_modifier(
+            "kotlin",
+            &[
+                "public",
+                "private",
+                "protected",
+                "internal",
+                "open",
+                "final",
+                "abstract",
+                "override",
+                "suspend",
+                "inline",
+                "operator",
+                "infix",
+                "tailrec",
+                "external",
+            ],
+            x,
+        )
+    }
+
+    /// Reads a type starting at token `i`, stopping at a `,`, `;`, `=`, `)`,
+    /// `{`, or `->` that is not nested inside brackets, or at the end of the line.
+    fn read_type(text: &str, tokens: &[Token], mut i: usize) -> (Option<String>, usize) {
+        let first = i;
+        let mut depth = 0;
+        while let Some(t) = tokens.get(i) {
+            if i > first && depth == 0 && text[tokens[i - 1].end..t.start].contains('\n') {
+                break;
+            }
+            match &text[t.start..t.end] {
+                "<" | "(" | "[" => depth += 1,
+                ">" | ")" | "]" if depth > 0 => depth -= 1,
+                "," | ";" | "=" | ")" | "{" | "->" | "by" | "get" | "set" if depth == 0 => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        if i == first {
+            (None, i)
+        } else {
+            let ty = &text[tokens[first].start..tokens[i - 1].end];
+            (Some(ty.split_whitespace().collect::<Vec<_>>().join(" ")), i)
+        }
+    }
+
+    /// Reads a parameter list opening at token `i`, where each parameter
+    /// is `name: Type`, optionally preceded by `val`/`var` or modifiers and
+    /// followed by a default value.
+    fn read_params(
+        text: &str,
+        tokens: &[Token],
+        mut i: usize,
+    ) -> (Vec<(String, String, Token)>, usize) {
+        let mut v = Vec::new();
+        let mut depth = 0;
+        i += 1;
+        while let Some(t) = tokens.get(i) {
+            let s = &text[t.start..t.end];
+            match s {
+                ")" if depth == 0 => return (v, i + 1),
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => depth -= 1,
+                _ if depth == 0
+                    && t.kind == TokenKind::Ident
+                    && tokens.get(i + 1).map(|x| &text[x.start..x.end]) == Some(":") =>
+                {
+                    let (ty, next) = Self::read_type(text, tokens, i + 2);
+                    v.push((
+                        s.to_string(),
+                        ty.unwrap_or_else(|| INFERRED.to_string()),
+                        *t,
+                    ));
+                    i = next;
+                    continue;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        (v, i)
+    }
+
+    /// Finds the end of a function body starting at token `i`, which is
+    /// either a block or an `=` followed by an expression
+    ///
+    /// # Returns
+    ///
+    /// The index of the last token of the body
+    fn body_end(text: &str, tokens: &[Token], i: usize) -> usize {
+        let block = &text[tokens[i].start..tokens[i].end] == "{";
+        let mut depth = 0;
+        for (j, t) in tokens.iter().enumerate().skip(i) {
+            if !block && j > i + 1 && depth == 0 && text[tokens[j - 1].end..t.start].contains('\n') {
+                return j - 1;
+            }
+            match &text[t.start..t.end] {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => {
+                    depth -= 1;
+                    if block && depth == 0 {
+                        return j;
+                    }
+                }
+                _ => {}
+            }
+        }
+        tokens.len() - 1
+    }
+
+    /// Guesses the type of the initializer starting at token `i`:
+    /// literals, and constructor calls like `Foo(...)`.
+    fn infer(text: &str, tokens: &[Token], i: usize) -> String {
+        let t = match tokens.get(i) {
+            Some(t) => t,
+            None => return INFERRED.to_string(),
+        };
+        let s = &text[t.start..t.end];
+        let next = tokens.get(i + 1).map(|x| &text[x.start..x.end]);
+        match t.kind {
+            TokenKind::Char => "Char",
+            TokenKind::Str => "String",
+            TokenKind::Number if s.ends_with('L') => "Long",
+            TokenKind::Number if s.ends_with('f') || s.ends_with('F') => "Float",
+            TokenKind::Number if s.contains('.') => "Double",
+            TokenKind::Number => "Int",
+            TokenKind::Ident if s == "true" || s == "false" => "Boolean",
+            TokenKind::Ident
+                if s.starts_with(char::is_uppercase)
+                    && (next == Some("(") || next == Some("<")) =>
+            {
+                s
+            }
+            _ => INFERRED,
+        }
+        .to_string()
+    }
+}
+
+impl Functions for Kotlin {
+    /// Parses out `fun` declarations from Kotlin code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Function containing information on every function
+    /// declared within text, including extension functions
+    fn read_functions(&self, text: &str) -> Vec<Function> {
+        let tokens = TOKENIZER.code(text);
+        let mut v = Vec::new();
+        for (i, t) in tokens.iter().enumerate() {
+            if &text[t.start..t.end] != "fun" {
+                continue;
+            }
+            // The name is the last identifier before the parameter list,
+            // which skips over type parameters and extension receivers
+            let mut name = None;
+            let mut depth = 0;
+            let mut j = i + 1;
+            while let Some(x) = tokens.get(j) {
+                match &text[x.start..x.end] {
+                    "<" => depth += 1,
+                    ">" => depth -= 1,
+                    "(" if depth == 0 => break,
+                    _ if depth == 0 && x.kind == TokenKind::Ident => name = Some(x),
+                    _ => {}
+                }
+                j += 1;
+            }
+            let n = match name {
+                Some(n) if j < tokens.len() => n,
+                _ => continue,
+            };
+            // The signature starts at any modifiers before the `fun`
+            let mut start = i;
+            while start > 0 && Self::is_modifier(&text[tokens[start - 1].start..tokens[start - 1].end]) {
+                start -= 1;
+            }
+            let (params, mut next) = Self::read_params(text, &tokens, j);
+            let params = params.into_iter().map(|(x, y, _)| Parameter::new(x, y)).collect();
+            let mut typ = String::new();
+            if tokens.get(next).map(|x| &text[x.start..x.end]) == Some(":") {
+                let (ty, after) = Self::read_type(text, &tokens, next + 1);
+                typ = ty.unwrap_or_default();
+                next = after;
+            }
+            let (body, end) = match tokens.get(next).filter(|x| ["{", "="].contains(&&text[x.start..x.end])) {
+                Some(b) => (b.start, tokens[Self::body_end(text, &tokens, next)].end),
+                None => {
+                    let end = tokens[next - 1].end;
+                    (end, end)
+                }
+            };
+            let name = text[n.start..n.end].to_string();
+            v.push(Function::new(name, typ, params, tokens[start].start, body, end));
+        }
+        v
+    }
+}
+
+impl Classes for Kotlin {
+    /// Parses out class, interface, and object declarations from Kotlin code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Class containing information on every named class,
+    /// interface, and object declared within text. The fields are the
+    /// `val` and `var` constructor parameters and the properties declared
+    /// directly in the body.
+    fn read_classes(&self, text: &str) -> Vec<Class> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        for (i, t) in tokens.iter().enumerate() {
+            let word = &text[t.start..t.end];
+            let prev = if i > 0 { s(i - 1) } else { None };
+            if !["class", "interface", "object"].contains(&word) || prev == Some("::") {
+                continue;
+            }
+            let name = match tokens.get(i + 1).filter(|x| x.kind == TokenKind::Ident) {
+                Some(x) => x,
+                None => continue,
+            };
+            let kind = if prev == Some("enum") { "enum" } else { word };
+            let mut start = i;
+            while start > 0
+                && (Self::is_modifier(s(start - 1).unwrap())
+                    || ["data", "sealed", "enum", "inner", "annotation", "value", "fun"].contains(&s(start - 1).unwrap()))
+            {
+                start -= 1;
+            }
+            let mut fields = Vec::new();
+            let mut j = i + 2;
+            let mut depth = 0;
+            while let Some(x) = s(j) {
+                match x {
+                    "<" => depth += 1,
+                    ">" => depth -= 1,
+                    "(" | "{" | ":" | ";" if depth == 0 => break,
+                    _ if depth == 0 && text[tokens[j - 1].end..tokens[j].start].contains('\n') => break,
+                    _ => {}
+                }
+                j += 1;
+            }
+            if s(j) == Some("(") {
+                let (params, next) = Self::read_params(text, &tokens, j);
+                for (name, typ, p) in params {
+                    let k = tokens[j..next].iter().position(|x| x.start == p.start).unwrap() + j;
+                    if matches!(s(k - 1), Some("val") | Some("var")) {
+                        fields.push(Identifier::new(name, typ, p.start, p.end));
+                    }
+                }
+                j = next;
+            }
+            let mut supertypes = Vec::new();
+            if s(j) == Some(":") {
+                j += 1;
+                let mut depth = 0;
+                let mut current: Option<(usize, usize)> = None;
+                let mut done = false;
+                while let Some(x) = s(j) {
+                    if depth == 0 && (x == "{" || x == "where" || text[tokens[j - 1].end..tokens[j].start].contains('\n')) {
+                        break;
+                    }
+                    match x {
+                        "<" | "(" => {
+                            if x == "(" && depth == 0 {
+                                done = true;
+                            }
+                            depth += 1;
+                        }
+                        ">" | ")" => depth -= 1,
+                        "," if depth == 0 => {
+                            supertypes.extend(current.take());
+                            done = false;
+                            j += 1;
+                            continue;
+                        }
+                        "by" if depth == 0 => done = true,
+                        _ => {}
+                    }
+                    if !done {
+                        current = Some((current.map_or(tokens[j].start, |x| x.0), tokens[j].end));
+                    }
+                    j += 1;
+                }
+                supertypes.extend(current);
+            }
+            let supertypes = supertypes
+                .into_iter()
+                .map(|(a, b)| text[a..b].split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect();
+            let (body, end) = if s(j) == Some("{") {
+                let close = Self::body_end(text, &tokens, j);
+                let mut depth = 0;
+                for k in j + 1..close {
+                    match s(k).unwrap() {
+                        "(" | "[" | "{" => depth += 1,
+                        ")" | "]" | "}" => depth -= 1,
+                        "val" | "var" if depth == 0 => {
+                            if let Some(n) = tokens.get(k + 1).filter(|x| x.kind == TokenKind::Ident) {
+                                let typ = if s(k + 2) == Some(":") {
+                                    Self::read_type(text, &tokens, k + 3).0
+                                } else if s(k + 2) == Some("=") {
+                                    Some(Self::infer(text, &tokens, k + 3))
+                                } else {
+                                    None
+                                };
+                                let typ = typ.unwrap_or_else(|| INFERRED.to_string());
+                                fields.push(Identifier::new(text[n.start..n.end].to_string(), typ, n.start, n.end));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                (tokens[j].start, tokens[close].end)
+            } else {
+                let end = tokens[j - 1].end;
+                (end, end)
+            };
+            let name = text[name.start..name.end].to_string();
+            v.push(Class::new(name, kind.to_string(), supertypes, fields, tokens[start].start, body, end));
+        }
+        v
+    }
+}
+
+impl Imports for Kotlin {
+    /// Writes an `import` directive of `path`, like `import java.util.List`
+    fn import_statement(&self, path: &str) -> Option<String> {
+        Some(format!("import {}", path))
+    }
+
+    /// Parses out `import` directives from Kotlin code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Import for every import within text, with the name given
+    /// by `as` as the alias
+    fn read_imports(&self, text: &str) -> Vec<Import> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let same_line = |j: usize| tokens.get(j).is_some_and(|t| !text[tokens[j - 1].end..t.start].contains('\n')) && s(j) != Some(";");
+        let mut v = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let line_start = i == 0 || text[tokens[i - 1].end..tokens[i].start].contains('\n') || s(i - 1) == Some(";");
+            if s(i) != Some("import") || !line_start {
+                i += 1;
+                continue;
+            }
+            let start = tokens[i].start;
+            let mut path = String::new();
+            let mut alias = None;
+            i += 1;
+            while same_line(i) {
+                if s(i) == Some("as") && same_line(i + 1) {
+                    alias = s(i + 1).map(String::from);
+                    i += 1;
+                } else {
+                    path += s(i).unwrap();
+                }
+                i += 1;
+            }
+            v.push(Import::new(path, alias, start, tokens[i - 1].end));
+        }
+        v
+    }
+}
+
+impl Aliases for Kotlin {
+    /// Parses out `typealias` declarations and the names brought in by
+    /// imports from Kotlin code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier for each alias within text, whose type is the
+    /// type or name it stands for
+    fn read_aliases(&self, text: &str) -> Vec<Identifier> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        for i in 0..tokens.len() {
+            if s(i) != Some("typealias") || s(i + 2) != Some("=") || tokens.len() <= i + 3 {
+                continue;
+            }
+            // The aliased type runs to the end of the line, as function
+            // types like `(Int) -> Unit` are aliased whole
+            let mut end = i + 3;
+            while tokens.get(end + 1).is_some_and(|t| !text[tokens[end].end..t.start].contains('\n') && s(end + 1) != Some(";")) {
+                end += 1;
+            }
+            let typ = &text[tokens[i + 3].start..tokens[end].end];
+            let name = &tokens[i + 1];
+            v.push(Identifier::new(
+                text[name.start..name.end].to_string(),
+                typ.split_whitespace().collect::<Vec<_>>().join(" "),
+                name.start,
+                name.end,
+            ));
+        }
+        v.extend(import_aliases(self.read_imports(text)));
+        v
+    }
+}
+
+impl Identifiers for Kotlin {
+    /// Parses out identifier uses from Kotlin code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing information on every use of
+    /// an identifier declared within the code. Declarations whose type
+    /// cannot be inferred are given the type `_`.
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        // The properties of each class by where its body opens, so that
+        // every method sees them wherever they are declared
+        let mut members: HashMap<usize, HashMap<String, String>> = self
+            .read_classes(text)
+            .into_iter()
+            .map(|x| (x.body, x.fields.into_iter().map(|f| (f.name, f.typ)).collect()))
+            .collect();
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        let mut scopes = Scopes::new();
+        let mut pending = HashMap::new();
+        let mut i = 0;
+        while let Some(t) = tokens.get(i) {
+            let word = s(i).unwrap();
+            let prev = if i > 0 { s(i - 1) } else { None };
+            match word {
+                "{" => {
+                    let mut frame = std::mem::take(&mut pending);
+                    frame.extend(members.remove(&t.start).unwrap_or_default());
+                    // Lambda parameters, as in `{ a, b: Int -> ... }`
+                    let mut j = i + 1;
+                    let mut params = Vec::new();
+                    while let Some(p) = tokens.get(j).filter(|x| x.kind == TokenKind::Ident) {
+                        let (ty, next) = if s(j + 1) == Some(":") {
+                            Self::read_type(text, &tokens, j + 2)
+                        } else {
+                            (None, j + 1)
+                        };
+                        params.push((*p, ty.unwrap_or_else(|| INFERRED.to_string())));
+                        j = next;
+                        if s(j) != Some(",") {
+                            break;
+                        }
+                        j += 1;
+                    }
+                    if s(j) == Some("->") && !params.is_empty() {
+                        for (p, ty) in params {
+                            let name = text[p.start..p.end].to_string();
+                            v.push(Identifier::new(name.clone(), ty.clone(), p.start, p.end));
+                            frame.insert(name, ty);
+                        }
+                        i = j;
+                    } else if !frame.contains_key("it")
+                        && prev != Some(")")
+                        && prev != Some("class")
+                    {
+                        frame.insert("it".to_string(), INFERRED.to_string());
+                    }
+                    scopes.push(frame);
+                }
+                "}" if scopes.depth() > 1 => {
+                    scopes.pop();
+                }
+                "import" | "package" => {
+                    while s(i + 1).is_some()
+                        && !text[tokens[i].end..tokens[i + 1].start].contains('\n')
+                    {
+                        i += 1;
+                    }
+                }
+                "fun" | "class" | "constructor" => {
+                    let mut j = i + 1;
+                    let mut depth = 0;
+                    while let Some(x) = s(j) {
+                        match x {
+                            "<" => depth += 1,
+                            ">" => depth -= 1,
+                            "(" if depth == 0 => break,
+                            "{" | "=" | ";" if depth == 0 => break,
+                            _ => {}
+                        }
+                        j += 1;
+                    }
+                    if s(j) == Some("(") {
+                        let (params, next) = Self::read_params(text, &tokens, j);
+                        for (name, typ, p) in params {
+                            v.push(Identifier::new(name.clone(), typ.clone(), p.start, p.end));
+                            pending.insert(name, typ);
+                        }
+                        j = next;
+                    }
+                    i = j;
+                    continue;
+                }
+                "val" | "var" => {
+                    if let Some(n) = tokens.get(i + 1).filter(|x| x.kind == TokenKind::Ident) {
+                        let name = text[n.start..n.end].to_string();
+                        let (typ, next) = if s(i + 2) == Some(":") {
+                            let (ty, next) = Self::read_type(text, &tokens, i + 3);
+                            (ty.unwrap_or_else(|| INFERRED.to_string()), next)
+                        } else if s(i + 2) == Some("=") {
+                            (Self::infer(text, &tokens, i + 3), i + 3)
+                        } else {
+                            (INFERRED.to_string(), i + 2)
+                        };
+                        v.push(Identifier::new(name.clone(), typ.clone(), n.start, n.end));
+                        scopes.declare(name, typ);
+                        i = next;
+                        continue;
+                    }
+                }
+                _ if t.kind == TokenKind::Ident
+                    && !Self::is_keyword(word)
+                    && prev != Some(".")
+                    && prev != Some("?.")
+                    && prev != Some("::") =>
+                {
+                    if let Some(typ) = scopes.lookup(word) {
+                        v.push(Identifier::new(word.to_string(), typ.to_string(), t.start, t.end));
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        v
+    }
+}
+
+impl Language for Kotlin {
+    fn name(&self) -> &'static str {
+        "kotlin"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["kt", "kts"]
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax::C
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        TOKENIZER.tokenize(text)
+    }
+}
+
+#[test]
+fn test_kotlin_identifiers() {
+    let text = "import com.game.Session\n\n@Serializable\nclass Spell(val me: Session, charge: Int = 0) {\n    private val bolt = Lightning(me)\n    var label: String? = null\n\n    @Override\n    fun onSpawn(count: Int): Unit {\n        val total = count + 1\n        listOf(1, 2).forEach { spark -> me.x = spark + total }\n        // me.y = 0\n        label = \"me\"\n    }\n}\n";
+    let idents = Kotlin {}.read_identifiers(text);
+    let of = |name: &str| -> Vec<String> {
+        idents
+            .iter()
+            .filter(|x| x.name == name)
+            .map(|x| x.typ.clone())
+            .collect()
+    };
+    assert_eq!(of("me"), vec!["Session"; 3]);
+    assert_eq!(of("charge"), vec!["Int"]);
+    assert_eq!(of("bolt"), vec!["Lightning"]);
+    assert_eq!(of("label"), vec!["String?"; 2]);
+    assert_eq!(of("count"), vec!["Int"; 2]);
+    assert_eq!(of("total"), vec!["_"; 2]);
+    assert_eq!(of("spark"), vec!["_"; 2]);
+    assert!(of("Session").is_empty());
+    let names: Vec<String> = Kotlin {}
+        .read_functions(text)
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, vec!["onSpawn"]);
+
+    let text = "override fun String.twice(n: Int, s: String = \"\"): String = repeat(n)\nfun main() {}\n";
+    let functions = Kotlin {}.read_functions(text);
+    let found: Vec<(&str, usize, usize, usize)> =
+        functions.iter().map(|f| (f.typ.as_str(), f.start, f.body, f.end)).collect();
+    assert_eq!(found, vec![("String", 0, 58, 69), ("", 70, 81, 83)]);
+    assert_eq!(functions[0].params, vec![Parameter::new("n".into(), "Int".into()), Parameter::new("s".into(), "String".into())]);
+}
+
+#[test]
+fn test_kotlin_imports() {
+    let text = "package game\n\nimport kotlin.math.*\nimport game.spells.Lightning as Bolt\n\nfun main() {}\n";
+    let found: Vec<(String, Option<String>, &str)> = Kotlin {}
+        .read_imports(text)
+        .into_iter()
+        .map(|x| (x.path, x.alias, &text[x.start..x.end]))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("kotlin.math.*".to_string(), None, "import kotlin.math.*"),
+            ("game.spells.Lightning".to_string(), Some("Bolt".to_string()), "import game.spells.Lightning as Bolt"),
+        ]
+    );
+}
+
+#[test]
+fn test_kotlin_aliases() {
+    let text = "import game.spells.Lightning as Bolt\n\ntypealias Handler = (Int) -> Unit\ntypealias Names = List<String>\n";
+    let found: Vec<(String, String)> = Kotlin {}.read_aliases(text).into_iter().map(|x| (x.name, x.typ)).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Handler".into(), "(Int) -> Unit".into()),
+            ("Names".into(), "List<String>".into()),
+            ("Bolt".into(), "game.spells.Lightning".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_kotlin_classes() {
+    let text = "data class Spell<T>(val me: Session, charge: Int) : Base(me), Castable by me {\n    private val bolt = Lightning(me)\n    fun cast() { val local = 1 }\n}\nenum class Color { RED }\nval x = Foo::class\n";
+    let classes = Kotlin {}.read_classes(text);
+    let found: Vec<(&str, &str, Vec<String>, usize)> =
+        classes.iter().map(|x| (x.name.as_str(), x.kind.as_str(), x.supertypes.clone(), x.start)).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Spell", "class", vec!["Base".to_string(), "Castable".to_string()], 0),
+            ("Color", "enum", Vec::new(), 151),
+        ]
+    );
+    let fields: Vec<(&str, &str)> = classes[0].fields.iter().map(|x| (x.name.as_str(), x.typ.as_str())).collect();
+    assert_eq!(fields, vec![("me", "Session"), ("bolt", "Lightning")]);
+    assert_eq!(&text[classes[1].body..classes[1].end], "{ RED }");
+
+    let text = "class Spell {\n    fun cast() { bolt.fire() }\n    val bolt: Lightning = Lightning()\n}\n";
+    let found: Vec<(String, usize)> = Kotlin {}.read_identifiers(text).into_iter().map(|x| (x.typ, x.start)).collect();
+    assert_eq!(found, vec![("Lightning".to_string(), 31), ("Lightning".to_string(), 53)]);
+}
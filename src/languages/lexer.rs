@@ -0,0 +1,198 @@
+//! Provides a pre-lexing pass that finds the comments and literals in a
+//! piece of source code, so that parsers can skip them rather than mistaking
+//! their contents for code
+
+use super::tokens::{self, Tokenizer};
+use super::CommentSyntax;
+use unicode_xid::UnicodeXID;
+
+/// The kinds of span the lexer classifies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpanKind {
+    /// A line or block comment, including its delimiters
+    Comment,
+    /// A string literal, including its quotes
+    String,
+    /// A character literal, including its quotes
+    Char,
+    /// A preprocessor directive, like `#define FOO 1`, including any lines
+    /// it is continued onto with `\`
+    Directive,
+    /// Code disabled by conditional compilation, like the body of `#if 0`
+    Disabled,
+    /// An annotation or attribute, like `@Override`, `@Named("a")`, or
+    /// `[[nodiscard]]`, including its arguments
+    Attribute,
+}
+
+/// A span of source code that is not plain code, given in byte offsets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    /// What the span contains
+    pub kind: SpanKind,
+    /// The byte index the span starts at
+    pub start: usize,
+    /// The byte index one past the end of the span
+    pub end: usize,
+}
+
+/// Whether a character can start an identifier, which is any Unicode
+/// `XID_Start` character, `_`, or `$`
+pub fn is_ident_start(c: char) -> bool {
+    c == '_' || c == '$' || UnicodeXID::is_xid_start(c)
+}
+
+/// Whether a character can be part of an identifier after its first,
+/// which is any Unicode `XID_Continue` character or `$`
+pub fn is_ident_char(c: char) -> bool {
+    c == '$' || UnicodeXID::is_xid_continue(c)
+}
+
+/// Finds every comment and literal in a piece of source code
+///
+/// # Arguments
+///
+/// * `text` - A string slice that contains the source code
+/// * `syntax` - The comment syntax of the language the code is in
+///
+/// # Returns
+///
+/// A Vec of Span covering every comment and literal in `text`, in order.
+/// Line comments stop before the newline that ends them, and unterminated
+/// block comments run to the end of `text`. Literals quoted with `'` are
+/// `Char` spans and all others are `String` spans; unterminated literals
+/// stop before the end of their line.
+pub fn lex(text: &str, syntax: CommentSyntax) -> Vec<Span> {
+    tokens::spans(&Tokenizer::new(syntax).tokenize(text))
+}
+
+/// Finds the annotations and attributes in a piece of source code whose
+/// comments and literals have already been blanked out with `blank`, so
+/// that parsers can skip them rather than mistaking them for declarations
+/// or losing track of the brackets within them
+///
+/// # Arguments
+///
+/// * `code` - A string slice that contains the blanked source code
+/// * `brackets` - Whether attributes may also be in single brackets, like
+///   C#'s `[Serializable]`, when they start a line or follow a `(` or `,`
+///
+/// # Returns
+///
+/// A Vec of `Attribute` Span covering every Java or JavaScript style
+/// `@Name(...)` annotation (other than `@interface`), C++ `[[...]]`
+/// attribute, and C# attribute when `brackets` is set, in order
+pub fn attributes(code: &str, brackets: bool) -> Vec<Span> {
+    let b = code.as_bytes();
+    // The index just past the bracket closing the one opened at `i`
+    let closing = |i: usize, open: u8, close: u8| -> usize {
+        let mut depth = 0;
+        for (j, &c) in b.iter().enumerate().skip(i) {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return j + 1;
+                }
+            }
+        }
+        b.len()
+    };
+    let mut v = Vec::new();
+    let mut can_start = true;
+    let mut i = 0;
+    while let Some(c) = code[i..].chars().next() {
+        let mut end = None;
+        if c == '@' && !code[..i].chars().next_back().is_some_and(is_ident_char) {
+            let name = code[i + 1..].split(|x: char| !(is_ident_char(x) || x == '.')).next().unwrap_or("");
+            if !name.is_empty() && name != "interface" {
+                let after = i + 1 + name.len();
+                end = Some(if b.get(after) == Some(&b'(') { closing(after, b'(', b')') } else { after });
+            }
+        } else if c == '[' && (b.get(i + 1) == Some(&b'[') || (brackets && can_start)) {
+            end = Some(closing(i, b'[', b']'));
+        }
+        if let Some(end) = end {
+            v.push(Span {
+                kind: SpanKind::Attribute,
+                start: i,
+                end,
+            });
+            i = end;
+            continue;
+        }
+        if c == '\n' || c == '(' || c == ',' {
+            can_start = true;
+        } else if !c.is_whitespace() {
+            can_start = false;
+        }
+        i += c.len_utf8();
+    }
+    v
+}
+
+/// Replaces every span in a piece of source code with spaces, keeping
+/// newlines so that lines and every other character's offset are unchanged
+///
+/// # Arguments
+///
+/// * `text` - A string slice that contains the source code
+/// * `spans` - The spans to blank out, in order
+///
+/// # Returns
+///
+/// A String with the same characters as `text` outside of `spans`, where
+/// each blanked character becomes as many spaces as it had bytes
+pub fn blank(text: &str, spans: &[Span]) -> String {
+    let mut spans = spans.iter().peekable();
+    let mut out = String::with_capacity(text.len());
+    for (i, c) in text.char_indices() {
+        while spans.peek().is_some_and(|x| x.end <= i) {
+            spans.next();
+        }
+        match spans.peek() {
+            Some(x) if x.start <= i && c != '\n' => out.extend(std::iter::repeat_n(' ', c.len_utf8())),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[test]
+fn test_attributes() {
+    let text = "@Override\n@SuppressWarnings({\"a\"}) @interface A {}\n[[nodiscard]] int f(int xs[]);\nf(a, @Nullable b) x@y";
+    let code = blank(text, &lex(text, CommentSyntax::C));
+    let spans: Vec<&str> = attributes(&code, false).iter().map(|x| &text[x.start..x.end]).collect();
+    assert_eq!(spans, vec!["@Override", "@SuppressWarnings({\"a\"})", "[[nodiscard]]", "@Nullable"]);
+    let text = "[Serializable]\nclass A { void F([FromBody] Foo x, int[] ys) { ys[0] = x[1]; } }";
+    let spans: Vec<&str> = attributes(text, true).iter().map(|x| &text[x.start..x.end]).collect();
+    assert_eq!(spans, vec!["[Serializable]", "[FromBody]"]);
+}
+
+#[test]
+fn test_lex() {
+    let text = "int a; // one\n/* two\n */ int b; /*/ three */ #four\n/* open";
+    let spans: Vec<(usize, usize)> = lex(text, CommentSyntax::C).iter().map(|x| (x.start, x.end)).collect();
+    assert_eq!(spans, vec![(7, 13), (14, 24), (32, 44), (51, 58)]);
+    assert_eq!(lex(text, CommentSyntax::HASH).len(), 1);
+    assert_eq!(
+        blank(text, &lex(text, CommentSyntax::C)),
+        "int a;       \n      \n    int b;              #four\n       "
+    );
+}
+
+#[test]
+fn test_lex_literals() {
+    let text = "s = \"{ // \\\" }\"; c = '\\''; /* \" */ u = \"open\nx";
+    let spans: Vec<(SpanKind, usize, usize)> = lex(text, CommentSyntax::C).iter().map(|x| (x.kind, x.start, x.end)).collect();
+    assert_eq!(
+        spans,
+        vec![
+            (SpanKind::String, 4, 15),
+            (SpanKind::Char, 21, 25),
+            (SpanKind::Comment, 27, 34),
+            (SpanKind::String, 39, 44),
+        ]
+    );
+}
@@ -0,0 +1,160 @@
+//! Provides the line index shared by everything that reports positions as
+//! lines and columns rather than byte offsets
+
+use super::parsing::{Class, Diagnostic, Function, Identifier};
+
+/// Maps byte offsets within a piece of text to lines and columns
+pub struct LineIndex<'a> {
+    text: &'a str,
+    /// The byte offset each line starts at
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// Creates a LineIndex for a piece of text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the text to be indexed
+    pub fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, starts }
+    }
+
+    /// The text that is indexed
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Finds the line and column of a byte offset
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The byte offset, which is clamped to the end of the text
+    ///
+    /// # Returns
+    ///
+    /// The line and column of `offset`, both counting from 1, where the
+    /// column counts characters rather than bytes
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.starts.partition_point(|x| *x <= offset) - 1;
+        let start = self.starts[line];
+        let column = match self.text.get(start..offset) {
+            Some(x) => x.chars().count(),
+            None => offset - start,
+        };
+        (line + 1, column + 1)
+    }
+
+    /// Finds the line and character of a byte offset as the Language Server
+    /// Protocol counts them, both from 0, with the character counted in
+    /// UTF-16 code units
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The byte offset, which is clamped to the end of the text
+    pub fn utf16_position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.starts.partition_point(|x| *x <= offset) - 1;
+        let start = self.starts[line];
+        (line, self.text.get(start..offset).map_or(offset - start, |x| x.encode_utf16().count()))
+    }
+
+    /// Finds the byte offset of a line and character as the Language Server
+    /// Protocol counts them, the inverse of `utf16_position`
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line, counting from 0, which is clamped to the last
+    /// * `character` - The UTF-16 code unit within the line, which is
+    ///   clamped to the end of the line
+    pub fn utf16_offset(&self, line: usize, character: usize) -> usize {
+        let start = self.starts[line.min(self.starts.len() - 1)];
+        let rest = &self.text[start..];
+        let rest = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        let mut units = 0;
+        for (i, c) in rest.char_indices() {
+            if units >= character {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        start + rest.len()
+    }
+}
+
+/// A parsed item with a position, whose line and column are filled in from
+/// its byte offset
+pub trait Locate {
+    /// Sets the line and column of the item, and of any items within it
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The LineIndex of the text the item was parsed from
+    fn locate(&mut self, index: &LineIndex);
+}
+
+impl Locate for Function {
+    fn locate(&mut self, index: &LineIndex) {
+        (self.line, self.column) = index.position(self.start);
+    }
+}
+
+impl Locate for Diagnostic {
+    fn locate(&mut self, index: &LineIndex) {
+        (self.line, self.column) = index.position(self.start);
+    }
+}
+
+impl Locate for Identifier {
+    fn locate(&mut self, index: &LineIndex) {
+        (self.line, self.column) = index.position(self.start);
+    }
+}
+
+impl Locate for Class {
+    fn locate(&mut self, index: &LineIndex) {
+        for field in self.fields.iter_mut() {
+            field.locate(index);
+        }
+    }
+}
+
+/// Fills in the lines and columns of the items parsed from a piece of text
+///
+/// # Arguments
+///
+/// * `text` - A string slice that contains the text the items were parsed from
+/// * `items` - The items, like the result of `read_identifiers`
+///
+/// # Returns
+///
+/// The items, with their lines and columns set
+pub fn locate<T: Locate>(text: &str, mut items: Vec<T>) -> Vec<T> {
+    let index = LineIndex::new(text);
+    for item in items.iter_mut() {
+        item.locate(&index);
+    }
+    items
+}
+
+#[test]
+fn test_line_index() {
+    let text = "int x;\nint naïve = x;\n\nx++;";
+    let index = LineIndex::new(text);
+    assert_eq!(index.position(0), (1, 1));
+    assert_eq!(index.position(4), (1, 5));
+    assert_eq!(index.position(7), (2, 1));
+    assert_eq!(index.position(text.find(" = ").unwrap()), (2, 10));
+    assert_eq!(index.position(text.len()), (4, 5));
+    assert_eq!(index.position(23), (3, 1));
+    let e = text.find('ï').unwrap();
+    assert_eq!(index.utf16_position(e + 2), (1, 7));
+    assert_eq!(index.utf16_offset(1, 7), e + 2);
+    assert_eq!(index.utf16_offset(1, 99), text.find("\n\n").unwrap());
+    let idents = locate(text, vec![Identifier::new("x".into(), "int".into(), 24, 25)]);
+    assert_eq!((idents[0].line, idents[0].column), (4, 1));
+}
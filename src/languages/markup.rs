@@ -0,0 +1,333 @@
+//! Provides the parser for HTML and XML
+
+use super::parsing::{Aliases, Call, Class, Classes, Diagnostic, Function, Functions, Identifier, Identifiers, Import, Imports};
+use super::{CommentSyntax, Language, Region};
+
+/// An Identifiers parser for HTML and XML, which exposes the parts of a
+/// document as identifiers: tag names (in both opening and closing tags)
+/// have the type `tag`, attribute names have the type `attr`, and text
+/// nodes have the type `text`, named by their trimmed contents.
+///
+/// Comments, CDATA sections, processing instructions, doctypes, and the
+/// bodies of `<script>` and `<style>` elements are skipped, so queries
+/// like `[[attr=class]]` never match inside them. Markup has no functions.
+///
+/// The bodies of `<script>` and `<style>` elements are regions in the
+/// language their `lang` attribute names, as in Vue's `<script lang="ts">`,
+/// or else JavaScript and CSS. Scripts whose `type` is not JavaScript, like
+/// `application/json` or a template, are left out.
+pub struct Markup {}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == ':' || c == '.'
+}
+
+impl Markup {
+    /// Reads the name starting at `i`, returning the index just past it
+    fn name_end(text: &str, i: usize) -> usize {
+        text[i..]
+            .find(|c: char| !is_name_char(c))
+            .map_or(text.len(), |x| i + x)
+    }
+
+    /// Skips to just past `close`, or to the end of the text
+    fn skip_past(text: &str, i: usize, close: &str) -> usize {
+        text[i..]
+            .find(close)
+            .map_or(text.len(), |x| i + x + close.len())
+    }
+
+    /// Reads the tag starting with the `<` at `i`, pushing its name and
+    /// attribute names, and returns the index just past it along with
+    /// the lowercased tag name.
+    fn tag(text: &str, i: usize, v: &mut Vec<Identifier>) -> (usize, String) {
+        let mut j = i + 1;
+        if text[j..].starts_with('/') {
+            j += 1;
+        }
+        let end = Self::name_end(text, j);
+        v.push(Identifier::new(
+            text[j..end].to_string(),
+            "tag".to_string(),
+            j,
+            end,
+        ));
+        let name = text[j..end].to_lowercase();
+        j = end;
+        while let Some(c) = text[j..].chars().next() {
+            match c {
+                '>' => return (j + 1, name),
+                '"' | '\'' => j = Self::skip_past(text, j + 1, &c.to_string()),
+                c if is_name_char(c) => {
+                    let end = Self::name_end(text, j);
+                    v.push(Identifier::new(
+                        text[j..end].to_string(),
+                        "attr".to_string(),
+                        j,
+                        end,
+                    ));
+                    j = end;
+                }
+                _ => j += c.len_utf8(),
+            }
+        }
+        (text.len(), name)
+    }
+
+    /// Finds the value of an attribute within the text of an opening tag,
+    /// like `ts` for `lang` in `<script lang="ts">`, where `tag` and `name`
+    /// are lowercase
+    fn attr_value<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+        let mut from = 0;
+        while let Some(i) = tag[from..].find(name).map(|x| from + x) {
+            from = i + name.len();
+            if !tag[..i].ends_with(char::is_whitespace) {
+                continue;
+            }
+            let value = match tag[from..].trim_start().strip_prefix('=') {
+                Some(x) => x.trim_start(),
+                None => continue,
+            };
+            return match value.chars().next()? {
+                q @ ('"' | '\'') => value[1..].split(q).next(),
+                _ => value.split(|c: char| c.is_whitespace() || c == '>' || c == '/').next(),
+            };
+        }
+        None
+    }
+
+    /// The language of the body of a `<script>` or `<style>` element, given
+    /// the text of its opening tag, lowercased
+    fn body_lang(name: &str, tag: &str) -> Option<String> {
+        if let Some(x) = Self::attr_value(tag, "lang") {
+            return Some(x.to_string());
+        }
+        if name == "style" {
+            return Some("css".to_string());
+        }
+        match Self::attr_value(tag, "type") {
+            Some(x) if !x.contains("javascript") && !x.contains("ecmascript") && x != "module" => None,
+            _ => Some("js".to_string()),
+        }
+    }
+
+    /// Reads a document, pushing its tag names, attribute names, and text
+    /// nodes to `v`, and the bodies of its `<script>` and `<style>`
+    /// elements to `regions`
+    fn scan(text: &str, v: &mut Vec<Identifier>, regions: &mut Vec<Region>) {
+        let mut text_start = 0;
+        let mut i = 0;
+        while let Some(x) = text[i..].find('<') {
+            i += x;
+            let rest = &text[i..];
+            let close = if rest.starts_with("<!--") {
+                Some("-->")
+            } else if rest.starts_with("<![CDATA[") {
+                Some("]]>")
+            } else if rest.starts_with("<?") {
+                Some("?>")
+            } else if rest.starts_with("<!") {
+                Some(">")
+            } else {
+                None
+            };
+            if let Some(close) = close {
+                Self::text_node(text, text_start, i, v);
+                i = Self::skip_past(text, i, close);
+                text_start = i;
+                continue;
+            }
+            let next = rest[1..].chars().next();
+            if !next.is_some_and(|c| c.is_alphabetic() || c == '/' || c == '_') {
+                // A stray `<` in text
+                i += 1;
+                continue;
+            }
+            Self::text_node(text, text_start, i, v);
+            let opening = !rest[1..].starts_with('/');
+            let (end, name) = Self::tag(text, i, v);
+            let tag = text[i..end].to_lowercase();
+            i = end;
+            if opening && !text[..end].ends_with("/>") && (name == "script" || name == "style") {
+                let lower = text[i..].to_lowercase();
+                let body = i;
+                i = lower
+                    .find(&format!("</{}", name))
+                    .map_or(text.len(), |x| i + x);
+                if let Some(lang) = Self::body_lang(&name, &tag).filter(|_| i > body) {
+                    regions.push(Region { lang, start: body, end: i });
+                }
+            }
+            text_start = i;
+        }
+        Self::text_node(text, text_start, text.len(), v);
+    }
+
+    /// Pushes the text node in `start..end`, without surrounding whitespace
+    fn text_node(text: &str, start: usize, end: usize, v: &mut Vec<Identifier>) {
+        let raw = &text[start..end];
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return;
+        }
+        let s = start + (raw.len() - raw.trim_start().len());
+        v.push(Identifier::new(
+            trimmed.to_string(),
+            "text".to_string(),
+            s,
+            s + trimmed.len(),
+        ));
+    }
+}
+
+impl Functions for Markup {
+    /// Markup has no functions, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the document
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_functions(&self, _text: &str) -> Vec<Function> {
+        Vec::new()
+    }
+}
+
+impl Identifiers for Markup {
+    /// Parses out tags, attributes, and text nodes from a document
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the document to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing every tag name, attribute name, and
+    /// text node outside of comments, CDATA, and raw text elements
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        let mut v = Vec::new();
+        Self::scan(text, &mut v, &mut Vec::new());
+        v
+    }
+}
+
+impl Classes for Markup {
+    /// Markup has no classes, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the document
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_classes(&self, _text: &str) -> Vec<Class> {
+        Vec::new()
+    }
+}
+
+impl Imports for Markup {
+    /// Markup has no imports, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the document
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_imports(&self, _text: &str) -> Vec<Import> {
+        Vec::new()
+    }
+}
+
+impl Aliases for Markup {
+    /// Markup has no type aliases, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the document
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_aliases(&self, _text: &str) -> Vec<Identifier> {
+        Vec::new()
+    }
+}
+
+impl Language for Markup {
+    fn name(&self) -> &'static str {
+        "markup"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["html", "htm", "xhtml", "xml", "svg"]
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax {
+            line: &[],
+            block: &[("<!--", "-->")],
+            quotes: &[],
+        }
+    }
+
+    fn regions(&self, text: &str) -> Vec<Region> {
+        let mut regions = Vec::new();
+        Self::scan(text, &mut Vec::new(), &mut regions);
+        regions
+    }
+
+    /// Documents have no calls, other than within their scripts, which are
+    /// regions of their own
+    fn read_calls(&self, _text: &str, _functions: &[Function]) -> Vec<Call> {
+        Vec::new()
+    }
+
+    /// Documents have no blocks to balance, and their text may hold any
+    /// number of braces
+    fn diagnose(&self, _text: &str) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+}
+
+#[test]
+fn test_markup_identifiers() {
+    let text = "<!DOCTYPE html>\n<div class=\"a<b\" id='x'>\n  Hello\n  <!-- <div class=\"gone\"> -->\n  <![CDATA[ <span class=\"gone\"> ]]>\n  <br/>\n  <script>if (a < b) { x = \"<div class>\"; }</script>\n</div>\n";
+    let idents = Markup {}.read_identifiers(text);
+    let of = |typ: &str| -> Vec<&str> {
+        idents
+            .iter()
+            .filter(|x| x.typ == typ)
+            .map(|x| x.name.as_str())
+            .collect()
+    };
+    assert_eq!(of("tag"), vec!["div", "br", "script", "script", "div"]);
+    assert_eq!(of("attr"), vec!["class", "id"]);
+    assert_eq!(of("text"), vec!["Hello"]);
+    for ident in &idents {
+        assert_eq!(&text[ident.start..ident.end], ident.name);
+    }
+}
+
+#[test]
+fn test_markup_regions() {
+    use super::cache::Parsed;
+    let text = "<p class=\"count\">count</p>\n<script>\nfunction f() { var count = 1; count++; }\n</script>\n<script type=\"text/template\">count</script>\n<style>p { color: red; }</style>\n<script lang=\"ts\">let count: number = 2;</script>";
+    let regions: Vec<(String, &str)> = Markup {}.regions(text).into_iter().map(|x| (x.lang, &text[x.start..x.end])).collect();
+    assert_eq!(regions.iter().map(|x| x.0.as_str()).collect::<Vec<_>>(), vec!["js", "css", "ts"]);
+    assert_eq!(regions[0].1, "\nfunction f() { var count = 1; count++; }\n");
+    let parsed = Parsed::new(text, &Markup {});
+    let counts: Vec<(&str, usize)> = parsed.identifiers.iter().filter(|x| x.name == "count").map(|x| (x.typ.as_str(), x.line)).collect();
+    assert_eq!(counts, vec![("text", 1), ("var", 3), ("var", 3), ("let", 7)]);
+    // The script's function body is a scope within the page
+    let body = parsed.identifiers.iter().find(|x| x.typ == "var").unwrap().scope_id;
+    assert_eq!(parsed.scopes.get(body).map(|x| &text[x.start..x.end]), Some("function f() { var count = 1; count++; }"));
+    assert_eq!(parsed.functions.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(), vec!["f"]);
+    let regex = crate::regexparser::parse("%s/[[name=count,type=var]]/total/g").unwrap();
+    let replaced = crate::nfa::replacer::replace_in(text, regex, |_, _| true, &Markup {}, false, false, false).unwrap();
+    assert!(replaced.contains("var total = 1; total++;") && replaced.starts_with("<p class=\"count\">count</p>"));
+}
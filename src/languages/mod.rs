@@ -7,17 +7,50 @@
 //! C and Java on the other hand have both extractable Functions
 //! and Identifiers. 
 
+use parsing::{Aliases, Classes, Functions, Identifiers, Imports};
+
 /// Provides traits for parsing different features of source code
 pub mod parsing {
     /// Represents a function in a code file
-    #[derive(Debug)]
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Function {
         /// We only care about named functions - thus, all functions have names
         pub name: String,
+        /// The return type of the function, which is empty when the function
+        /// returns nothing or its return type is not written down
+        pub typ: String,
+        /// The parameters of the function, in order
+        pub params: Vec<Parameter>,
+        /// The starting index within the source file of the function's
+        /// signature, including any modifiers
+        pub start: usize,
+        /// The index the function's body starts at, which is one past the
+        /// end of its signature
+        pub body: usize,
+        /// The index one past the end of the function's body
+        pub end: usize,
+        /// The line the function's signature starts on, counting from 1, or
+        /// 0 until it is filled in by `lines::locate`
+        pub line: usize,
+        /// The column, in characters counting from 1, the function's
+        /// signature starts at, or 0 until it is filled in by `lines::locate`
+        pub column: usize,
+    }
+
+    /// Represents a parameter of a function
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Parameter {
+        /// The name of the parameter
+        pub name: String,
+        /// The type of the parameter, which is empty when it is not written down
+        pub typ: String,
     }
 
     /// Represents an identifier in a piece of code, which has an associated type
-    #[derive(Debug)]
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Identifier {
         /// The name of the identifier, which is the set of characters used to refer to it
         pub name: String,
@@ -27,6 +60,86 @@ pub mod parsing {
         pub start: usize,
         /// The index one past the end of the identifier's location
         pub end: usize,
+        /// The line the identifier starts on, counting from 1, or 0 until it
+        /// is filled in by `lines::locate`
+        pub line: usize,
+        /// The column, in characters counting from 1, the identifier starts
+        /// at, or 0 until it is filled in by `lines::locate`
+        pub column: usize,
+        /// The ID of the innermost scope the identifier is within, or 0
+        /// until it is filled in by `ScopeTree::assign`
+        pub scope_id: usize,
+    }
+
+    /// Represents a class, struct, interface, or similar type declaration
+    #[derive(Debug)]
+    pub struct Class {
+        /// The name of the declared type
+        pub name: String,
+        /// The keyword that declares the type, like `class`, `struct`,
+        /// `interface`, or `enum`
+        pub kind: String,
+        /// The types this one extends or implements, in the order written
+        pub supertypes: Vec<String>,
+        /// The member fields declared directly within the type, with their types
+        pub fields: Vec<Identifier>,
+        /// The starting index within the source file of the declaration,
+        /// including any modifiers
+        pub start: usize,
+        /// The index the body of the declaration starts at
+        pub body: usize,
+        /// The index one past the end of the declaration's body
+        pub end: usize,
+    }
+
+    /// Represents an import, include, or other reference to another module
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Import {
+        /// The path of the imported module or name, as written, like
+        /// `java.util.List`, `stdio.h`, or `std::io`
+        pub path: String,
+        /// The name the import is bound to, if it is renamed
+        pub alias: Option<String>,
+        /// The starting index within the source file of the import statement
+        pub start: usize,
+        /// The index one past the end of the import statement
+        pub end: usize,
+    }
+
+    /// Represents a call of a function or method, like `load(path, true)`
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Call {
+        /// The name of the function called, which is the last name before
+        /// its arguments, so `load` for `self.cache.load(path)`
+        pub name: String,
+        /// The starting index within the source file of the name
+        pub start: usize,
+        /// The index of the `(` that opens the arguments
+        pub open: usize,
+        /// The index one past the `)` that closes the arguments
+        pub end: usize,
+        /// The start and end of each argument, without the whitespace
+        /// around it
+        pub args: Vec<(usize, usize)>,
+    }
+
+    /// Represents a place where a parser lost track of the structure of a
+    /// piece of code, like a `}` that closes no block, past which what it
+    /// finds may be wrong
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Diagnostic {
+        /// What went wrong, like "`{` is never closed"
+        pub message: String,
+        /// The starting index within the source file of what went wrong
+        pub start: usize,
+        /// The line `start` is on, counting from 1, or 0 until it is filled
+        /// in by `lines::locate`
+        pub line: usize,
+        /// The column, in characters counting from 1, of `start`, or 0 until
+        /// it is filled in by `lines::locate`
+        pub column: usize,
     }
 
     /// A trait for language processors that support named functions
@@ -53,6 +166,92 @@ pub mod parsing {
         fn read_identifiers(&self, text: &str) -> Vec<Identifier>;
     }
 
+    /// A trait for language processors that support classes and other
+    /// user-defined types with members
+    pub trait Classes {
+        /// Retrieves a vector of all the class-like declarations within a piece of source code
+        /// # Arguments
+        ///
+        /// * `text` - A string slice that contains the source code to be analyzed
+        ///
+        /// # Returns
+        ///
+        /// A `Vec<Class>` containing every class, struct, interface, and
+        /// similar declaration within `text`, including nested ones
+        fn read_classes(&self, text: &str) -> Vec<Class>;
+    }
+
+    /// A trait for language processors that support imports or includes
+    pub trait Imports {
+        /// Retrieves a vector of all the imports within a piece of source code
+        /// # Arguments
+        ///
+        /// * `text` - A string slice that contains the source code to be analyzed
+        ///
+        /// # Returns
+        ///
+        /// A `Vec<Import>` containing every import within `text`, with one
+        /// entry for each name an import statement brings in
+        fn read_imports(&self, text: &str) -> Vec<Import>;
+
+        /// Writes the statement that imports `path` in this language, as it
+        /// would appear on a line of its own, which `read_imports` reads back
+        /// with `path`, less any brackets or quotes around it, as its path
+        /// # Arguments
+        ///
+        /// * `path` - The path of the module or name to import, like
+        ///   `java.util.List`
+        ///
+        /// # Returns
+        ///
+        /// The statement, or None if the language has no way to import
+        /// anything
+        fn import_statement(&self, _path: &str) -> Option<String> {
+            None
+        }
+    }
+
+    /// A trait for language processors that support type aliases
+    pub trait Aliases {
+        /// Retrieves a vector of all the type aliases within a piece of source
+        /// code, like `typedef`s, `using X = Y;`, and names brought in by imports
+        /// # Arguments
+        ///
+        /// * `text` - A string slice that contains the source code to be analyzed
+        ///
+        /// # Returns
+        ///
+        /// A `Vec<Identifier>` with an entry for each alias within `text`, whose
+        /// name is the alias and whose type is the type it stands for
+        fn read_aliases(&self, text: &str) -> Vec<Identifier>;
+    }
+
+    /// Finds the names a set of imports bring into scope, as aliases of
+    /// what they import, so `import java.util.List;` makes `List` an alias
+    /// of `java.util.List` and `using Json = Newtonsoft.Json;` makes `Json`
+    /// an alias of `Newtonsoft.Json`
+    ///
+    /// # Arguments
+    ///
+    /// * `imports` - The imports, which should not include file includes
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<Identifier>` of the aliases, leaving out wildcard imports and
+    /// imports whose name is their whole path
+    pub fn import_aliases(imports: Vec<Import>) -> Vec<Identifier> {
+        imports
+            .into_iter()
+            .filter_map(|x| {
+                let name = match x.alias {
+                    Some(alias) => alias,
+                    None => x.path.rsplit(['.', ':']).next()?.to_string(),
+                };
+                Some(Identifier::new(name, x.path, x.start, x.end)).filter(|x| x.name != x.typ && !x.typ.ends_with('*'))
+            })
+            .collect()
+    }
+
     impl Identifier {
         /// Creates a new Identifier given a set of parameters
         /// # Arguments
@@ -68,6 +267,9 @@ pub mod parsing {
                 typ,
                 start,
                 end,
+                line: 0,
+                column: 0,
+                scope_id: 0,
             }
         }
     }
@@ -76,11 +278,283 @@ pub mod parsing {
         /// Creates a new Function given a set of parameters
         /// # Arguments
         ///
-        /// * `text` - A String that contains the name of the function
+        /// * `name` - A String that contains the name of the function
+        /// * `typ` - A String that contains the return type of the function
+        /// * `params` - The parameters of the function
+        /// * `start` - a number representing where the signature starts in the code
+        /// * `body` - a number representing where the body starts in the code
+        /// * `end` - a number representing where the body ends in the code
+        ///
+        pub fn new(name: String, typ: String, params: Vec<Parameter>, start: usize, body: usize, end: usize) -> Self {
+            Self {
+                name,
+                typ,
+                params,
+                start,
+                body,
+                end,
+                line: 0,
+                column: 0,
+            }
+        }
+    }
+
+    impl Class {
+        /// Creates a new Class given a set of parameters
+        /// # Arguments
+        ///
+        /// * `name` - A String that contains the name of the type
+        /// * `kind` - A String that contains the keyword declaring the type
+        /// * `supertypes` - The types the type extends or implements
+        /// * `fields` - The member fields of the type
+        /// * `start` - a number representing where the declaration starts in the code
+        /// * `body` - a number representing where the body starts in the code
+        /// * `end` - a number representing where the body ends in the code
         ///
-        pub fn new(name: String) -> Self {
-            Self { name }
+        pub fn new(
+            name: String,
+            kind: String,
+            supertypes: Vec<String>,
+            fields: Vec<Identifier>,
+            start: usize,
+            body: usize,
+            end: usize,
+        ) -> Self {
+            Self {
+                name,
+                kind,
+                supertypes,
+                fields,
+                start,
+                body,
+                end,
+            }
         }
     }
+
+    impl Import {
+        /// Creates a new Import given a set of parameters
+        /// # Arguments
+        ///
+        /// * `path` - A String that contains the path of the imported module
+        /// * `alias` - The name the import is bound to, if it is renamed
+        /// * `start` - a number representing where the import starts in the code
+        /// * `end` - a number representing where the import ends in the code
+        ///
+        pub fn new(path: String, alias: Option<String>, start: usize, end: usize) -> Self {
+            Self {
+                path,
+                alias,
+                start,
+                end,
+            }
+        }
+    }
+
+    impl Call {
+        /// Creates a new Call given a set of parameters
+        /// # Arguments
+        ///
+        /// * `name` - A String that contains the name of the function called
+        /// * `start` - a number representing where the name starts in the code
+        /// * `open` - a number representing where the arguments are opened
+        /// * `end` - a number representing where the arguments are closed, plus one
+        /// * `args` - the start and end of each argument
+        ///
+        pub fn new(name: String, start: usize, open: usize, end: usize, args: Vec<(usize, usize)>) -> Self {
+            Self { name, start, open, end, args }
+        }
+    }
+
+    impl Diagnostic {
+        /// Creates a new Diagnostic given what went wrong and where
+        /// # Arguments
+        ///
+        /// * `message` - A String that describes what went wrong
+        /// * `start` - a number representing where it went wrong in the code
+        ///
+        pub fn new(message: String, start: usize) -> Self {
+            Self { message, start, line: 0, column: 0 }
+        }
+    }
+
+    impl Parameter {
+        /// Creates a new Parameter given its name and type
+        /// # Arguments
+        ///
+        /// * `name` - A String that contains the name of the parameter
+        /// * `typ` - A String that contains the type of the parameter
+        ///
+        pub fn new(name: String, typ: String) -> Self {
+            Self { name, typ }
+        }
+    }
+}
+/// The comment and literal syntax of a language, so that callers can tell
+/// comments and strings apart from code without parsing the language themselves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CommentSyntax {
+    /// The tokens that start a comment running to the end of the line
+    pub line: &'static [&'static str],
+    /// The pairs of tokens that open and close a block comment
+    pub block: &'static [(&'static str, &'static str)],
+    /// The quotes that open and close string and character literals, within
+    /// which a `\` escapes the next character
+    pub quotes: &'static [char],
+}
+
+impl CommentSyntax {
+    /// A language with no comments or literals
+    pub const NONE: Self = Self {
+        line: &[],
+        block: &[],
+        quotes: &[],
+    };
+    /// The `//` and `/* */` comments and `"` and `'` literals shared by C
+    /// and its descendants
+    pub const C: Self = Self {
+        line: &["//"],
+        block: &[("/*", "*/")],
+        quotes: &['"', '\''],
+    };
+    /// The `#` comments and `"` and `'` literals of shell, Ruby, and other
+    /// scripting languages
+    pub const HASH: Self = Self {
+        line: &["#"],
+        block: &[],
+        quotes: &['"', '\''],
+    };
+}
+
+/// A region of a piece of code that is in another language, like the body
+/// of an HTML `<script>` element or a fenced block in Markdown
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    /// The language the region is in, as `Registry::by_name` takes it, like
+    /// `js` or `rust`
+    pub lang: String,
+    /// The byte index the region starts at
+    pub start: usize,
+    /// The byte index one past the end of the region
+    pub end: usize,
+}
+
+/// A language processor that supports named functions, typed identifiers,
+/// classes, imports, and type aliases, which is what the query engine needs to
+/// evaluate query sets, along with the metadata the `Registry` uses to pick it
+/// for a file. Languages are shared between the threads that parse files.
+pub trait Language: Functions + Identifiers + Classes + Imports + Aliases + Sync {
+    /// The name of the language, as given to `--lang`
+    fn name(&self) -> &'static str;
+
+    /// The file extensions, without the leading `.`, of files in this language
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// The interpreters that, when named in a `#!` line, mark a file as
+    /// being in this language
+    fn interpreters(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The comment syntax of the language
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax::NONE
+    }
+
+    /// Finds the comments and literals in a piece of code in this language,
+    /// along with any code disabled by conditional compilation, which the
+    /// matcher uses to skip strings and disabled code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Span covering every comment, literal, and disabled region
+    /// in `text`, in order of where they start
+    fn spans(&self, text: &str) -> Vec<lexer::Span> {
+        tokens::spans(&self.tokenize(text))
+    }
+
+    /// Finds the regions of a piece of code in this language that are in
+    /// other languages, which `Parsed::new` parses as those languages, so
+    /// query sets match within them as they would in a file of their own
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Region, in order of where they start, which do not overlap
+    fn regions(&self, _text: &str) -> Vec<Region> {
+        Vec::new()
+    }
+
+    /// Splits a piece of code in this language into tokens, which the
+    /// shared analyzers in `tokens` work on
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    ///
+    /// # Returns
+    ///
+    /// A Vec of every Token in `text`, including comments, in order
+    fn tokenize(&self, text: &str) -> Vec<tokens::Token> {
+        tokens::Tokenizer::new(self.comments()).tokenize(text)
+    }
+
+    /// Finds the calls in a piece of code in this language, which are the
+    /// names followed by a bracketed list of arguments, other than those of
+    /// the functions being declared and control keywords like `if`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    /// * `functions` - The functions declared in `text`
+    ///
+    /// # Returns
+    ///
+    /// A Vec of every Call in `text`, in order of where they start
+    fn read_calls(&self, text: &str, functions: &[parsing::Function]) -> Vec<parsing::Call> {
+        tokens::calls(text, &tokens::code(self.tokenize(text)), functions)
+    }
+
+    /// Finds where the structure of a piece of code in this language could
+    /// not be followed, which by default is wherever its `{ }` blocks do not
+    /// balance, as the scopes are built from them
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Diagnostic, in order of where they start, which is empty if
+    /// the code could be followed throughout
+    fn diagnose(&self, text: &str) -> Vec<parsing::Diagnostic> {
+        tokens::balance(text, &tokens::code(self.tokenize(text)))
+    }
 }
+
+pub mod cache;
 pub mod clike;
+pub mod csharp;
+pub mod go;
+pub mod keywords;
+pub mod kotlin;
+pub mod lexer;
+pub mod lines;
+pub mod markup;
+pub mod plain;
+pub mod registry;
+pub mod ruby;
+pub mod rust;
+pub mod scopes;
+pub mod shell;
+pub mod tokens;
+#[cfg(feature = "tree-sitter")]
+pub mod treesitter;
+
@@ -0,0 +1,179 @@
+//! Provides the fallback for files that are not in a known language
+
+use super::parsing::{Aliases, Call, Class, Classes, Diagnostic, Function, Functions, Identifier, Identifiers, Import, Imports};
+use super::{Language, Region};
+
+/// A language processor for plain text, used when no language is detected
+/// for a file (Markdown, JSON, lock files, ...). It finds no functions or
+/// identifiers, so query sets never match, but plain regex substitution
+/// works across the whole file, without skipping anything that looks like
+/// a string or comment.
+///
+/// Fenced code blocks, as in Markdown, whose info string names a language,
+/// like ` ```rust `, are regions in that language, so query sets do match
+/// within them.
+pub struct Plain {}
+
+/// The fence a line opens or closes a fenced code block with, which is a
+/// run of at least three backticks or tildes, indented by at most three
+/// spaces, along with the rest of the line
+fn fence(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let c = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(c).len();
+    Some((&trimmed[..len], &trimmed[len..])).filter(|_| len >= 3)
+}
+
+impl Functions for Plain {
+    /// Plain text has no functions, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the text
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_functions(&self, _text: &str) -> Vec<Function> {
+        Vec::new()
+    }
+}
+
+impl Identifiers for Plain {
+    /// Plain text has no identifiers, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the text
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_identifiers(&self, _text: &str) -> Vec<Identifier> {
+        Vec::new()
+    }
+}
+
+impl Classes for Plain {
+    /// Plain text has no classes, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the text
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_classes(&self, _text: &str) -> Vec<Class> {
+        Vec::new()
+    }
+}
+
+impl Imports for Plain {
+    /// Plain text has no imports, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the text
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_imports(&self, _text: &str) -> Vec<Import> {
+        Vec::new()
+    }
+}
+
+impl Aliases for Plain {
+    /// Plain text has no type aliases, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the text
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_aliases(&self, _text: &str) -> Vec<Identifier> {
+        Vec::new()
+    }
+}
+
+impl Language for Plain {
+    fn name(&self) -> &'static str {
+        "plain"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Plain text has no calls, though it may mention `f(x)`
+    fn read_calls(&self, _text: &str, _functions: &[Function]) -> Vec<Call> {
+        Vec::new()
+    }
+
+    /// Plain text has no blocks to balance, though it may hold braces
+    fn diagnose(&self, _text: &str) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+
+    fn regions(&self, text: &str) -> Vec<Region> {
+        let mut regions = Vec::new();
+        // The fence of the block being read, the language named after it,
+        // and where its code starts
+        let mut open: Option<(&str, &str, usize)> = None;
+        let mut at = 0;
+        for line in text.split_inclusive('\n') {
+            let start = at;
+            at += line.len();
+            let found = fence(line.trim_end());
+            match (open, found) {
+                (Some((marker, lang, body)), Some((x, rest))) if x.starts_with(marker) && rest.trim().is_empty() => {
+                    if !lang.is_empty() {
+                        regions.push(Region { lang: lang.to_string(), start: body, end: start });
+                    }
+                    open = None;
+                }
+                (None, Some((marker, info))) => {
+                    let lang = info.split(|c: char| c.is_whitespace() || c == ',' || c == '{').next().unwrap_or_default();
+                    open = Some((marker, lang, at));
+                }
+                _ => {}
+            }
+        }
+        regions
+    }
+}
+
+#[test]
+fn test_plain_replace() {
+    let text = "# Session\n\nThe `session` is \"session\".\n".to_string();
+    let regex = crate::regexparser::parse("%s/session/sess/g").unwrap();
+    let replaced = crate::nfa::replacer::replace_in(&text, regex, |_, _| true, &Plain {}, true, false, false).unwrap();
+    assert_eq!(replaced, "# Session\n\nThe `sess` is \"sess\".\n");
+    assert!(Plain {}.read_identifiers(&text).is_empty());
+}
+
+#[test]
+fn test_plain_regions() {
+    let text = "# Use
+
+```rust
+let x = 1;
+```
+
+~~~
+no language
+~~~
+  ````go {.numberLines}
+var y int
+```
+````
+";
+    let regions: Vec<(String, &str)> = Plain {}.regions(text).into_iter().map(|x| (x.lang, &text[x.start..x.end])).collect();
+    assert_eq!(regions, vec![("rust".to_string(), "let x = 1;\n"), ("go".to_string(), "var y int\n```\n")]);
+}
@@ -0,0 +1,319 @@
+//! Provides the registry that picks a language processor for each file
+
+use super::{clike, csharp, go, kotlin, markup, plain, ruby, rust, shell, Language};
+use std::path::Path;
+
+/// How many lines at each end of a file are looked through for a comment
+/// naming its language, which is as many as Vim looks through for modelines
+const HINT_LINES: usize = 5;
+
+/// A set of language processors, which picks one for each file based on
+/// its extension, falling back to its shebang or doctype. Languages beyond
+/// the builtin ones can be added with `register`, and take priority over
+/// any language registered before them. A comment naming a file's language,
+/// like a modeline, takes priority over all of these.
+pub struct Registry {
+    languages: Vec<Box<dyn Language>>,
+    fallback: Box<dyn Language>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Registry {
+    /// Creates a Registry with no languages, where every file is `Plain`
+    pub fn new() -> Self {
+        Self {
+            languages: Vec::new(),
+            fallback: Box::new(plain::Plain {}),
+        }
+    }
+
+    /// Creates a Registry holding every language spidior ships with, where
+    /// the tree-sitter parsers, if built, replace `Clike` for their files
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(clike::Clike {}));
+        registry.register(Box::new(rust::Rust {}));
+        registry.register(Box::new(go::Go {}));
+        registry.register(Box::new(ruby::Ruby {}));
+        registry.register(Box::new(csharp::CSharp {}));
+        registry.register(Box::new(kotlin::Kotlin {}));
+        registry.register(Box::new(shell::Shell {}));
+        registry.register(Box::new(markup::Markup {}));
+        #[cfg(feature = "tree-sitter")]
+        {
+            registry.register(Box::new(super::treesitter::TreeSitter::c()));
+            registry.register(Box::new(super::treesitter::TreeSitter::cpp()));
+            registry.register(Box::new(super::treesitter::TreeSitter::java()));
+            registry.register(Box::new(super::treesitter::TreeSitter::python()));
+        }
+        registry
+    }
+
+    /// Adds a language to the registry
+    ///
+    /// # Arguments
+    ///
+    /// * `lang` - The language processor to add
+    pub fn register(&mut self, lang: Box<dyn Language>) {
+        self.languages.push(lang);
+    }
+
+    /// Iterates over the registered languages, most recently registered first
+    fn iter(&self) -> impl Iterator<Item = &dyn Language> {
+        self.languages.iter().rev().map(|x| x.as_ref())
+    }
+
+    /// Picks a language processor by its name, as given to `--lang`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the language, like `rust`, or one of its
+    ///   extensions, like `rs`
+    ///
+    /// # Returns
+    ///
+    /// The language processor, or `None` if no language has that name
+    pub fn by_name(&self, name: &str) -> Option<&dyn Language> {
+        let name = name.to_lowercase();
+        if name == self.fallback.name() {
+            return Some(self.fallback.as_ref());
+        }
+        self.iter()
+            .find(|x| x.name() == name)
+            .or_else(|| self.by_extension(&name))
+    }
+
+    /// Picks a language processor for a file based on its extension
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - The extension of the file, without the leading `.`
+    ///
+    /// # Returns
+    ///
+    /// The language processor, or `None` if the extension is not known
+    pub fn by_extension(&self, extension: &str) -> Option<&dyn Language> {
+        self.iter().find(|x| x.extensions().contains(&extension))
+    }
+
+    /// Picks a language processor by looking at the start of a file, for
+    /// files whose extension does not give their language away
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - The contents of the file
+    ///
+    /// # Returns
+    ///
+    /// The language processor whose interpreter is named by a `#!` line,
+    /// or the `markup` language for files starting with `<!DOCTYPE`,
+    /// `<?xml`, or `<html`, or `None` otherwise
+    pub fn by_contents(&self, contents: &str) -> Option<&dyn Language> {
+        let first = contents.trim_start_matches('\u{feff}').lines().next().unwrap_or("");
+        if let Some(shebang) = first.strip_prefix("#!") {
+            let mut words = shebang.split_whitespace();
+            let mut interpreter = words.next()?.rsplit('/').next()?;
+            if interpreter == "env" {
+                interpreter = words.find(|w| !w.starts_with('-'))?;
+            }
+            let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+            return self.iter().find(|x| x.interpreters().contains(&interpreter));
+        }
+        let start = contents.trim_start().get(..9).unwrap_or("").to_lowercase();
+        if start.starts_with("<!doctype") || start.starts_with("<?xml") || start.starts_with("<html") {
+            return self.iter().find(|x| x.name() == "markup");
+        }
+        None
+    }
+
+    /// Picks a language processor named by a comment near the start or end
+    /// of a file, overriding its extension
+    ///
+    /// A comment may hold `spidior-lang: NAME`, a Vim modeline like
+    /// `vim: set ft=NAME:`, or an Emacs mode line like `-*- mode: NAME -*-`,
+    /// within the first or last `HINT_LINES` lines, and NAME is anything
+    /// `by_name` takes. A `spidior-lang` comment is preferred over the
+    /// others, and a name no language has is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - The contents of the file
+    ///
+    /// # Returns
+    ///
+    /// The language processor named, or `None` if no comment names one
+    pub fn by_hint(&self, contents: &str) -> Option<&dyn Language> {
+        let lines: Vec<&str> = contents.lines().take(HINT_LINES).chain(contents.lines().rev().take(HINT_LINES)).collect();
+        lines
+            .iter()
+            .filter_map(|x| spidior_hint(x))
+            .chain(lines.iter().filter_map(|x| modeline_hint(x)))
+            .find_map(|x| self.by_name(x))
+    }
+
+    /// Picks a language processor for a file based on a comment naming its
+    /// language, then its extension, falling back to its shebang or doctype
+    /// when the extension is missing or unknown
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file being processed
+    /// * `contents` - The contents of the file
+    ///
+    /// # Returns
+    ///
+    /// The language processor for the file, which is `Plain` unless
+    /// the file is known to belong to a language
+    pub fn detect(&self, path: &Path, contents: &str) -> &dyn Language {
+        self.by_hint(contents)
+            .or_else(|| path.extension().and_then(|x| x.to_str()).and_then(|x| self.by_extension(x)))
+            .or_else(|| self.by_contents(contents))
+            .unwrap_or_else(|| self.fallback.as_ref())
+    }
+}
+
+/// The language a `spidior-lang: NAME` comment on a line names
+fn spidior_hint(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("spidior-lang:")?;
+    // The name may run into the end of the comment, as in `<!-- spidior-lang: go-->`
+    Some(rest.split_whitespace().next()?.trim_end_matches(|c: char| "*/->".contains(c)))
+}
+
+/// The language a Vim modeline, like `vim: set ft=cpp:` or `vi: ft=cpp`, or
+/// an Emacs mode line, like `-*- mode: c -*-` or `-*- c -*-`, on a line
+/// names
+fn modeline_hint(line: &str) -> Option<&str> {
+    if let Some((_, rest)) = line.split_once("-*-") {
+        let (inner, _) = rest.split_once("-*-")?;
+        return inner.split(';').map(str::trim).find_map(|x| match x.split_once(':') {
+            Some((key, value)) if key.trim().eq_ignore_ascii_case("mode") => Some(value.trim()),
+            Some(_) => None,
+            None => Some(x),
+        });
+    }
+    // A modeline's marker starts the line or follows whitespace, so words
+    // like `complex:` are not taken for `ex:`
+    let start = ["vim:", "vi:", "ex:"]
+        .iter()
+        .filter_map(|marker| {
+            let (i, _) = line.match_indices(marker).find(|(i, _)| line[..*i].chars().last().is_none_or(char::is_whitespace))?;
+            Some(i + marker.len())
+        })
+        .min()?;
+    line[start..]
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .find_map(|x| x.strip_prefix("ft=").or_else(|| x.strip_prefix("filetype=")).or_else(|| x.strip_prefix("syntax=")))
+}
+
+#[test]
+fn test_detect() {
+    let registry = Registry::builtin();
+    let script = "#!/usr/bin/env bash\ngreet() { echo hi; }\n";
+    assert_eq!(registry.detect(Path::new("greet"), script).name(), "shell");
+    assert_eq!(registry.detect(Path::new("greet.sh"), "").name(), "shell");
+    assert_eq!(registry.detect(Path::new("index"), "<!DOCTYPE html>\n").name(), "markup");
+    assert_eq!(registry.detect(Path::new("notes.md"), "int x = 1;\n").name(), "plain");
+    assert_eq!(registry.by_name("rust").map(|x| x.name()), Some("rust"));
+    assert_eq!(registry.by_name("rs").map(|x| x.name()), Some("rust"));
+    assert!(registry.by_name("cobol").is_none());
+}
+
+#[test]
+fn test_detect_hint() {
+    let registry = Registry::builtin();
+    assert_eq!(registry.detect(Path::new("build"), "// spidior-lang: rust\nfn main() {}\n").name(), "rust");
+    assert_eq!(registry.detect(Path::new("page.html"), "<!-- spidior-lang: go-->\n").name(), "go");
+    assert_eq!(registry.detect(Path::new("a.c"), "/* spidior-lang: sh */\n").name(), "shell");
+    assert_eq!(registry.detect(Path::new("a.txt"), "x\n\n\n\n\n\n# vim: set ts=4 ft=ruby:\n").name(), "ruby");
+    assert_eq!(registry.detect(Path::new("a.txt"), "/* -*- mode: go; tab-width: 4 -*- */\n").name(), "go");
+    assert_eq!(registry.detect(Path::new("a.txt"), "# -*- sh -*-\n").name(), "shell");
+    // A hint overrides a shebang as well as an extension
+    assert_eq!(registry.detect(Path::new("build"), "#!/bin/sh\n# vim: ft=ruby\n").name(), "ruby");
+    // Neither too far from the ends of the file, nor naming no language
+    assert_eq!(registry.detect(Path::new("a.sh"), "\n\n\n\n\n// spidior-lang: go\n\n\n\n\n\n").name(), "shell");
+    assert_eq!(registry.detect(Path::new("a.sh"), "// spidior-lang: cobol\n").name(), "shell");
+    assert_eq!(registry.detect(Path::new("build"), "#!/bin/sh\n# vim: ft=cobol\n").name(), "shell");
+    assert_eq!(registry.detect(Path::new("a.sh"), "complex: ft=go\n").name(), "shell");
+}
+
+#[test]
+fn test_register() {
+    struct Cobol {}
+    impl super::parsing::Functions for Cobol {
+        fn read_functions(&self, _text: &str) -> Vec<super::parsing::Function> {
+            Vec::new()
+        }
+    }
+    impl super::parsing::Identifiers for Cobol {
+        fn read_identifiers(&self, _text: &str) -> Vec<super::parsing::Identifier> {
+            Vec::new()
+        }
+    }
+    impl super::parsing::Classes for Cobol {
+        fn read_classes(&self, _text: &str) -> Vec<super::parsing::Class> {
+            Vec::new()
+        }
+    }
+    impl super::parsing::Imports for Cobol {
+        fn read_imports(&self, _text: &str) -> Vec<super::parsing::Import> {
+            Vec::new()
+        }
+    }
+    impl super::parsing::Aliases for Cobol {
+        fn read_aliases(&self, _text: &str) -> Vec<super::parsing::Identifier> {
+            Vec::new()
+        }
+    }
+    impl Language for Cobol {
+        fn name(&self) -> &'static str {
+            "cobol"
+        }
+        fn extensions(&self) -> &'static [&'static str] {
+            &["cbl", "h"]
+        }
+    }
+    let mut registry = Registry::builtin();
+    registry.register(Box::new(Cobol {}));
+    assert_eq!(registry.detect(Path::new("pay.cbl"), "").name(), "cobol");
+    assert_eq!(registry.detect(Path::new("pay.h"), "").name(), "cobol");
+    assert_eq!(registry.detect(Path::new("pay.js"), "").name(), "clike");
+}
+
+#[test]
+fn test_cut_off_and_random_code() {
+    use crate::fuzz::Rng;
+    // Pieces of the syntax of every language, which random code is made of,
+    // so that it opens constructs it never closes and escapes characters of
+    // more than one byte
+    const PIECES: &[&str] = &[
+        "{", "}", "(", ")", "[", "]", "<", ">", "\"", "'", "`", "\\", "$", "${", "$((", "#", "/*", "*/", "//", ";", "=", ".", ",", ":", "::", "é", " ", "\n", "\t", "x", "import ", "package ", "func ",
+        "fn ", "class ", "module ", "def ", "end", "use ", "<<-'", "EOF", "-", "@", "@\"", "r#\"", "\"\"\"", "<!--", "int ", "val ", "type ", "struct {", "typedef ", "source ", "local ", "for ", "do", "#if ",
+    ];
+    let registry = Registry::builtin();
+    let mut texts: Vec<String> = Vec::new();
+    let dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let text = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        texts.extend(text.char_indices().step_by(41).map(|(i, _)| text[..i].to_string()));
+    }
+    let mut rng = Rng::new(400);
+    for _ in 0..500 {
+        texts.push((0..1 + rng.below(12)).map(|_| PIECES[rng.below(PIECES.len())]).collect());
+    }
+    for lang in registry.iter() {
+        for text in &texts {
+            let functions = lang.read_functions(text);
+            lang.read_identifiers(text);
+            lang.read_classes(text);
+            lang.read_imports(text);
+            lang.read_aliases(text);
+            lang.read_calls(text, &functions);
+            lang.diagnose(text);
+        }
+    }
+}
@@ -0,0 +1,734 @@
+//! Provides the parser for Ruby
+
+use super::parsing::{Aliases, Class, Classes, Function, Functions, Identifier, Identifiers, Import, Imports, Parameter};
+use super::tokens::{self, Token, TokenKind};
+use super::{keywords, CommentSyntax, Language};
+use std::collections::HashMap;
+
+/// A Functions and Identifiers parser for Ruby, which understands
+/// `def`/`end` scoping, classes and modules, and local, parameter,
+/// block parameter, and instance variables.
+///
+/// Ruby has no type annotations, so every identifier is given the type
+/// `unknown`, except for locals and instance variables assigned from
+/// `Foo.new`, which are given the type `Foo`. Predicates on names
+/// and positions work as they do for any other language.
+pub struct Ruby {}
+
+/// The type recorded for identifiers whose type we could not infer
+const UNKNOWN: &str = "unknown";
+
+/// What opened a frame that will be closed by `end`
+#[derive(Debug, PartialEq)]
+enum Opener {
+    /// `class` or `module`, which hides outer locals
+    Container,
+    /// `def`, which hides outer locals
+    Def,
+    /// `do`, or a brace block, which can see outer locals
+    Block,
+    /// `if`, `while`, `begin`, and friends, which do not introduce scopes
+    Control,
+}
+
+impl Ruby {
+    fn is_keyword(x: &str) -> bool {
+        keywords::is_keyword(
+            "ruby",
+            &[
+                "alias", "and", "begin", "break", "case", "class", "def", "defined", "do", "else",
+                "elsif", "end", "ensure", "false", "for", "if", "in", "module", "next", "nil", "not",
+                "or", "redo", "rescue", "retry", "return", "self", "super", "then", "true", "undef",
+                "unless", "until", "when", "while", "yield",
+            ],
+            x,
+        )
+    }
+
+    /// Splits Ruby source into tokens, dropping whitespace. Strings and
+    /// here-doc bodies become single `Str` tokens, and symbols and instance
+    /// variables become `Sigil` tokens. This cannot use a `Tokenizer`, as
+    /// here-doc bodies start on the line after they are opened.
+    fn read_tokens(text: &str) -> Vec<Token> {
+        let b = text.as_bytes();
+        let mut v = Vec::new();
+        let mut heredocs: Vec<String> = Vec::new();
+        let mut line_start = true;
+        let mut i = 0;
+        while i < b.len() {
+            let c = text[i..].chars().next().unwrap();
+            let start = i;
+            let kind = if c == '\n' {
+                i += 1;
+                line_start = true;
+                // Read the bodies of any here-docs started on this line
+                for id in heredocs.drain(..) {
+                    let body = i;
+                    while i < b.len() {
+                        let end = text[i..].find('\n').map_or(b.len(), |x| i + x + 1);
+                        let line = text[i..end].trim();
+                        i = end;
+                        if line == id {
+                            break;
+                        }
+                    }
+                    v.push(Token::new(TokenKind::Str, body, i, false));
+                }
+                continue;
+            } else if c.is_whitespace() {
+                i += c.len_utf8();
+                continue;
+            } else if c == '#' {
+                i = text[i..].find('\n').map_or(b.len(), |x| i + x);
+                TokenKind::Comment
+            } else if line_start && text[i..].starts_with("=begin") {
+                i = text[i..].find("\n=end").map_or(b.len(), |x| i + x + 5);
+                TokenKind::Comment
+            } else if text[i..].starts_with("<<~")
+                || text[i..].starts_with("<<-")
+                || (text[i..].starts_with("<<")
+                    && b.get(i + 2).is_some_and(|x| x.is_ascii_uppercase()))
+            {
+                i += if b[i + 2] == b'~' || b[i + 2] == b'-' {
+                    3
+                } else {
+                    2
+                };
+                let quote = b.get(i).copied().filter(|x| *x == b'\'' || *x == b'"');
+                if quote.is_some() {
+                    i += 1;
+                }
+                let id_start = i;
+                while i < b.len() && (b[i].is_ascii_alphanumeric() || b[i] == b'_') {
+                    i += 1;
+                }
+                heredocs.push(text[id_start..i].to_string());
+                // The closing quote, unless the file ends before it
+                if quote.is_some() && b.get(i).copied() == quote {
+                    i += 1;
+                }
+                TokenKind::Str
+            } else if c == '"' || c == '\'' || c == '`' {
+                i += 1;
+                while i < b.len() && b[i] != c as u8 {
+                    i += if b[i] == b'\\' { 2 } else { 1 };
+                }
+                i = (i + 1).min(b.len());
+                TokenKind::Str
+            } else if c == ':'
+                && b.get(i + 1)
+                    .is_some_and(|x| x.is_ascii_alphabetic() || *x == b'_')
+            {
+                i += 1;
+                while i < b.len() && (b[i].is_ascii_alphanumeric() || b[i] == b'_') {
+                    i += 1;
+                }
+                TokenKind::Sigil
+            } else if c == '@' {
+                i += 1;
+                while let Some(x) = text[i..].chars().next() {
+                    if !(x.is_alphanumeric() || x == '_' || x == '@') {
+                        break;
+                    }
+                    i += x.len_utf8();
+                }
+                TokenKind::Sigil
+            } else if c.is_alphabetic() || c == '_' || c == '$' {
+                i += c.len_utf8();
+                while let Some(x) = text[i..].chars().next() {
+                    if !(x.is_alphanumeric() || x == '_') {
+                        break;
+                    }
+                    i += x.len_utf8();
+                }
+                if i < b.len() && (b[i] == b'?' || b[i] == b'!') && b.get(i + 1) != Some(&b'=') {
+                    i += 1;
+                }
+                TokenKind::Ident
+            } else if c.is_ascii_digit() {
+                while i < b.len() && (b[i].is_ascii_alphanumeric() || b[i] == b'_' || b[i] == b'.')
+                {
+                    i += 1;
+                }
+                TokenKind::Number
+            } else if [
+                "==", "=~", "=>", "::", "||", "&&", "+=", "-=", "*=", "/=", "!=", "<=", ">=",
+            ]
+            .iter()
+            .any(|x| text[i..].starts_with(x))
+            {
+                i += 2;
+                TokenKind::Punct
+            } else {
+                i += c.len_utf8();
+                TokenKind::Punct
+            };
+            v.push(Token::new(kind, start, i, line_start));
+            line_start = false;
+        }
+        v
+    }
+
+    /// Reads the parameter names of a `def` or block, starting at token `i`
+    /// and stopping at the token `close` (or the end of the line when
+    /// `close` is None), returning the names and the index after the list.
+    fn read_params(
+        text: &str,
+        tokens: &[Token],
+        mut i: usize,
+        close: Option<&str>,
+    ) -> (Vec<Token>, usize) {
+        let mut v = Vec::new();
+        let mut depth = 0;
+        let mut expect_name = true;
+        while let Some(t) = tokens.get(i) {
+            let s = &text[t.start..t.end];
+            if close.is_none() && t.line_start || (depth == 0 && Some(s) == close) {
+                return (v, if close.is_some() { i + 1 } else { i });
+            }
+            match s {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => depth -= 1,
+                "," if depth == 0 => expect_name = true,
+                "*" | "&" | "**" => {}
+                ";" if close.is_none() => return (v, i),
+                _ if expect_name && depth == 0 && t.kind == TokenKind::Ident => {
+                    v.push(*t);
+                    expect_name = false;
+                }
+                _ => expect_name = false,
+            }
+            i += 1;
+        }
+        (v, i)
+    }
+
+    /// Guesses the type of the expression starting at token `i`,
+    /// which is only possible for `Foo.new` and `Foo::Bar.new`.
+    fn infer(text: &str, tokens: &[Token], mut i: usize) -> String {
+        let first = i;
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        while s(i).is_some_and(|x| x.starts_with(char::is_uppercase)) && s(i + 1) == Some("::") {
+            i += 2;
+        }
+        match (s(i), s(i + 1), s(i + 2)) {
+            (Some(x), Some("."), Some("new")) if x.starts_with(char::is_uppercase) => {
+                text[tokens[first].start..tokens[i].end].to_string()
+            }
+            _ => UNKNOWN.to_string(),
+        }
+    }
+
+    /// Finds the `end` closing a construct whose body starts at token `i`
+    ///
+    /// # Returns
+    ///
+    /// The index of the closing `end`, or the last token if it is unclosed
+    fn matching_end(text: &str, tokens: &[Token], i: usize) -> usize {
+        let s = |j: usize| &text[tokens[j].start..tokens[j].end];
+        let mut depth = 1;
+        for j in i..tokens.len() {
+            let prev = if j > 0 { Some(s(j - 1)) } else { None };
+            match s(j) {
+                "class" | "module" | "def" if prev != Some(".") => depth += 1,
+                "do" | "begin" | "case" | "for" => depth += 1,
+                "if" | "unless" | "while" | "until" if Self::opens_block(text, tokens, j) => depth += 1,
+                "end" if prev != Some(".") => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return j;
+                    }
+                }
+                _ => {}
+            }
+        }
+        tokens.len() - 1
+    }
+
+    /// Whether `if`, `unless`, `while`, or `until` at token `i` begins a
+    /// construct closed by `end`, rather than being a trailing modifier.
+    fn opens_block(text: &str, tokens: &[Token], i: usize) -> bool {
+        tokens[i].line_start
+            || i == 0
+            || matches!(
+                &text[tokens[i - 1].start..tokens[i - 1].end],
+                "=" | "(" | ";" | "||" | "&&" | "return"
+            )
+    }
+}
+
+impl Functions for Ruby {
+    /// Parses out method definitions from Ruby code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Function containing information on every method
+    /// defined within text, including singleton methods like `def self.x`
+    fn read_functions(&self, text: &str) -> Vec<Function> {
+        let tokens = tokens::code(Self::read_tokens(text));
+        let mut v = Vec::new();
+        for (i, t) in tokens.iter().enumerate() {
+            if &text[t.start..t.end] != "def" {
+                continue;
+            }
+            let s = |j: usize| tokens.get(j).map(|x| &text[x.start..x.end]);
+            let mut j = i + 1;
+            if s(j + 1) == Some(".") {
+                j += 2;
+            }
+            let n = match tokens.get(j) {
+                Some(n) => n,
+                None => continue,
+            };
+            let (params, next) = if s(j + 1) == Some("(") {
+                Self::read_params(text, &tokens, j + 2, Some(")"))
+            } else if s(j + 1) == Some("=") {
+                (Vec::new(), j + 1)
+            } else {
+                Self::read_params(text, &tokens, j + 1, None)
+            };
+            let params = params
+                .into_iter()
+                .map(|p| Parameter::new(text[p.start..p.end].to_string(), String::new()))
+                .collect();
+            let (body, end) = match tokens.get(next) {
+                // An endless method, which runs to the end of the line
+                Some(b) if s(next) == Some("=") => {
+                    let last = tokens[next + 1..]
+                        .iter()
+                        .position(|x| x.line_start)
+                        .map_or(tokens.len() - 1, |x| next + x);
+                    (b.start, tokens[last].end)
+                }
+                Some(b) => (b.start, tokens[Self::matching_end(text, &tokens, next)].end),
+                None => (text.len(), text.len()),
+            };
+            let name = text[n.start..n.end].to_string();
+            v.push(Function::new(name, String::new(), params, t.start, body, end));
+        }
+        v
+    }
+}
+
+impl Classes for Ruby {
+    /// Parses out class and module definitions from Ruby code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Class containing information on every class and module
+    /// defined within text. The supertypes are the superclass followed by
+    /// any modules the body includes, and the fields are the instance
+    /// variables assigned within the body, outside of nested classes.
+    fn read_classes(&self, text: &str) -> Vec<Class> {
+        let tokens = tokens::code(Self::read_tokens(text));
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        // The token ranges of each class, so nested ones can be skipped
+        let mut ranges = Vec::new();
+        for (i, t) in tokens.iter().enumerate() {
+            let word = &text[t.start..t.end];
+            let prev = if i > 0 { s(i - 1) } else { None };
+            if !(word == "class" || word == "module") || prev == Some(".") || s(i + 1).is_none_or(|x| x == "<<") {
+                continue;
+            }
+            let mut j = i + 1;
+            while s(j + 1) == Some("::") {
+                j += 2;
+            }
+            let name = j;
+            if name >= tokens.len() {
+                continue;
+            }
+            let mut supertypes = Vec::new();
+            j += 1;
+            if s(j) == Some("<") {
+                let first = j + 1;
+                j = first;
+                while tokens.get(j).is_some_and(|x| !x.line_start && s(j) != Some(";")) {
+                    j += 1;
+                }
+                if j > first {
+                    supertypes.push(text[tokens[first].start..tokens[j - 1].end].to_string());
+                }
+            }
+            let close = Self::matching_end(text, &tokens, j);
+            ranges.push((i, name, j, close, supertypes));
+        }
+        let mut v = Vec::new();
+        for (n, (i, name, body, close, mut supertypes)) in ranges.iter().cloned().enumerate() {
+            let nested: Vec<(usize, usize)> = ranges[n + 1..]
+                .iter()
+                .filter(|x| x.0 < close)
+                .map(|x| (x.0, x.3))
+                .collect();
+            let mut fields: Vec<Identifier> = Vec::new();
+            let mut j = body;
+            while j < close {
+                if let Some(&(_, end)) = nested.iter().find(|x| x.0 == j) {
+                    j = end + 1;
+                    continue;
+                }
+                let t = tokens[j];
+                let word = s(j).unwrap();
+                if t.line_start && ["include", "extend", "prepend"].contains(&word) {
+                    let mut k = j + 1;
+                    while tokens.get(k).is_some_and(|x| !x.line_start && x.kind != TokenKind::Punct) {
+                        supertypes.push(s(k).unwrap().to_string());
+                        if s(k + 1) != Some(",") {
+                            break;
+                        }
+                        k += 2;
+                    }
+                } else if t.kind == TokenKind::Sigil && text[t.start..].starts_with('@') && s(j + 1) == Some("=") {
+                    let typ = Self::infer(text, &tokens, j + 2);
+                    match fields.iter_mut().find(|x| x.name == word) {
+                        Some(f) if f.typ == UNKNOWN => f.typ = typ,
+                        Some(_) => {}
+                        None => fields.push(Identifier::new(word.to_string(), typ, t.start, t.end)),
+                    }
+                }
+                j += 1;
+            }
+            let body = tokens.get(body).map_or(text.len(), |x| x.start);
+            v.push(Class::new(
+                text[tokens[i + 1].start..tokens[name].end].to_string(),
+                s(i).unwrap().to_string(),
+                supertypes,
+                fields,
+                tokens[i].start,
+                body.min(tokens[close].start),
+                tokens[close].end,
+            ));
+        }
+        v
+    }
+}
+
+impl Imports for Ruby {
+    /// Writes a `require` of the file `path`, like `require "json"`
+    fn import_statement(&self, path: &str) -> Option<String> {
+        Some(format!("require \"{}\"", path))
+    }
+
+    /// Parses out `require`, `require_relative`, and `load` calls with a
+    /// literal path from Ruby code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Import for every file required within text. Ruby imports
+    /// cannot be renamed, so none has an alias.
+    fn read_imports(&self, text: &str) -> Vec<Import> {
+        let tokens = tokens::code(Self::read_tokens(text));
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        for (i, t) in tokens.iter().enumerate() {
+            if !t.line_start || !matches!(s(i), Some("require") | Some("require_relative") | Some("load")) {
+                continue;
+            }
+            let j = if s(i + 1) == Some("(") { i + 2 } else { i + 1 };
+            let path = match tokens.get(j) {
+                Some(x) if x.kind == TokenKind::Str && x.end - x.start >= 2 && "'\"".contains(&text[x.start..x.start + 1]) => x,
+                _ => continue,
+            };
+            let end = if j > i + 1 && s(j + 1) == Some(")") { tokens[j + 1].end } else { path.end };
+            v.push(Import::new(text[path.start + 1..path.end - 1].to_string(), None, t.start, end));
+        }
+        v
+    }
+}
+
+impl Identifiers for Ruby {
+    /// Parses out identifier uses from Ruby code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing information on every use of a
+    /// local variable, parameter, or instance variable within the code
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        let tokens = tokens::code(Self::read_tokens(text));
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        let mut frames: Vec<(Opener, HashMap<String, String>)> =
+            vec![(Opener::Container, HashMap::new())];
+        let mut ivars: Vec<HashMap<String, String>> = vec![HashMap::new()];
+        let mut i = 0;
+        while let Some(t) = tokens.get(i) {
+            let word = s(i).unwrap();
+            let prev = if i > 0 { s(i - 1) } else { None };
+            match word {
+                "class" | "module" if prev != Some(".") && s(i + 1) != Some("<<") => {
+                    frames.push((Opener::Container, HashMap::new()));
+                    ivars.push(HashMap::new());
+                }
+                "class" => {
+                    // `class << self` opens a singleton class body
+                    frames.push((Opener::Container, HashMap::new()));
+                }
+                "def" => {
+                    let mut j = i + 1;
+                    if s(j + 1) == Some(".") {
+                        j += 2;
+                    }
+                    j += 1;
+                    let (params, next) = if s(j) == Some("(") {
+                        Self::read_params(text, &tokens, j + 1, Some(")"))
+                    } else if s(j) == Some("=") {
+                        (Vec::new(), j)
+                    } else {
+                        Self::read_params(text, &tokens, j, None)
+                    };
+                    let mut frame = HashMap::new();
+                    for p in params {
+                        let name = text[p.start..p.end].to_string();
+                        v.push(Identifier::new(
+                            name.clone(),
+                            UNKNOWN.to_string(),
+                            p.start,
+                            p.end,
+                        ));
+                        frame.insert(name, UNKNOWN.to_string());
+                    }
+                    if s(next) == Some("=") {
+                        // An endless method, which has no `end`
+                        i = next + 1;
+                        continue;
+                    }
+                    frames.push((Opener::Def, frame));
+                    i = next;
+                    continue;
+                }
+                "do" | "{" => {
+                    let mut frame = HashMap::new();
+                    let mut j = i + 1;
+                    if s(j) == Some("|") {
+                        let (params, next) = Self::read_params(text, &tokens, j + 1, Some("|"));
+                        for p in params {
+                            let name = text[p.start..p.end].to_string();
+                            v.push(Identifier::new(
+                                name.clone(),
+                                UNKNOWN.to_string(),
+                                p.start,
+                                p.end,
+                            ));
+                            frame.insert(name, UNKNOWN.to_string());
+                        }
+                        j = next;
+                    }
+                    frames.push((Opener::Block, frame));
+                    i = j;
+                    continue;
+                }
+                "begin" | "case" | "for" => frames.push((Opener::Control, HashMap::new())),
+                "if" | "unless" | "while" | "until" if Self::opens_block(text, &tokens, i) => {
+                    frames.push((Opener::Control, HashMap::new()));
+                }
+                "end" | "}" => {
+                    if let Some((opener, _)) = frames.last() {
+                        if *opener == Opener::Container && frames.len() > 1 {
+                            ivars.pop();
+                        }
+                    }
+                    if frames.len() > 1 {
+                        frames.pop();
+                    }
+                }
+                _ if t.kind == TokenKind::Sigil && text[t.start..].starts_with('@') => {
+                    let typ = if s(i + 1) == Some("=") {
+                        let typ = Self::infer(text, &tokens, i + 2);
+                        let known = ivars.last().and_then(|x| x.get(word)).cloned();
+                        let typ = match known {
+                            Some(k) if typ == UNKNOWN => k,
+                            _ => typ,
+                        };
+                        ivars
+                            .last_mut()
+                            .unwrap()
+                            .insert(word.to_string(), typ.clone());
+                        typ
+                    } else {
+                        ivars
+                            .last()
+                            .and_then(|x| x.get(word))
+                            .cloned()
+                            .unwrap_or_else(|| UNKNOWN.to_string())
+                    };
+                    v.push(Identifier::new(word.to_string(), typ, t.start, t.end));
+                }
+                _ if t.kind == TokenKind::Ident
+                    && !Self::is_keyword(word)
+                    && prev != Some(".")
+                    && prev != Some("::") =>
+                {
+                    let mut found = None;
+                    for (opener, frame) in frames.iter().rev() {
+                        if let Some(typ) = frame.get(word) {
+                            found = Some(typ.clone());
+                            break;
+                        }
+                        if *opener == Opener::Container || *opener == Opener::Def {
+                            break;
+                        }
+                    }
+                    let assigned = s(i + 1) == Some("=") && !word.starts_with(char::is_uppercase);
+                    if assigned {
+                        let typ = Self::infer(text, &tokens, i + 2);
+                        let typ = match found {
+                            Some(k) if typ == UNKNOWN => k,
+                            _ => typ,
+                        };
+                        let frame = frames
+                            .iter_mut()
+                            .rev()
+                            .find(|(o, f)| f.contains_key(word) || *o != Opener::Control)
+                            .unwrap();
+                        frame.1.insert(word.to_string(), typ.clone());
+                        v.push(Identifier::new(word.to_string(), typ, t.start, t.end));
+                    } else if let Some(typ) = found {
+                        v.push(Identifier::new(word.to_string(), typ, t.start, t.end));
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        v
+    }
+}
+
+impl Aliases for Ruby {
+    /// Ruby has no type aliases, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the code
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_aliases(&self, _text: &str) -> Vec<Identifier> {
+        Vec::new()
+    }
+}
+
+impl Language for Ruby {
+    fn name(&self) -> &'static str {
+        "ruby"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["rb"]
+    }
+
+    fn interpreters(&self) -> &'static [&'static str] {
+        &["ruby"]
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax {
+            line: &["#"],
+            block: &[("=begin", "=end")],
+            quotes: &['"', '\''],
+        }
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        Self::read_tokens(text)
+    }
+}
+
+#[test]
+fn test_ruby_functions() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.rb");
+    let text = std::fs::read_to_string(d).unwrap();
+    let names: Vec<String> = Ruby {}
+        .read_functions(&text)
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, vec!["initialize", "on_spawn", "charged?", "build"]);
+
+    let text = "def go(me, *rest)\n  if me then 1 end\n  [1].each do |x| x end\nend\ndef twice(n) = n * 2\n";
+    let functions = Ruby {}.read_functions(text);
+    let found: Vec<(usize, usize, usize)> = functions.iter().map(|f| (f.start, f.body, f.end)).collect();
+    assert_eq!(found, vec![(0, 20, 64), (65, 78, 85)]);
+    assert_eq!(functions[0].params, vec![Parameter::new("me".into(), "".into()), Parameter::new("rest".into(), "".into())]);
+}
+
+#[test]
+fn test_ruby_identifiers() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.rb");
+    let text = std::fs::read_to_string(d).unwrap();
+    let idents = Ruby {}.read_identifiers(&text);
+    let of = |name: &str| -> Vec<String> {
+        idents
+            .iter()
+            .filter(|x| x.name == name)
+            .map(|x| x.typ.clone())
+            .collect()
+    };
+    assert_eq!(of("me"), vec!["unknown"; 4]);
+    assert_eq!(of("@session"), vec!["Game::Session"; 3]);
+    assert_eq!(of("@charge"), vec!["unknown"; 3]);
+    assert_eq!(of("charge"), vec!["unknown"; 4]);
+    assert_eq!(of("bolt"), vec!["Spells::Lightning"; 2]);
+    assert_eq!(of("spark"), vec!["unknown"; 2]);
+    assert_eq!(of("hidden"), vec!["unknown"]);
+    for ident in &idents {
+        assert_eq!(&text[ident.start..ident.end], ident.name);
+    }
+}
+
+#[test]
+fn test_ruby_imports() {
+    let text = "require 'json'\nrequire_relative(\"spells/lightning\")\n\nclass Caster\n  def load\n  end\nend\n";
+    let found: Vec<(String, &str)> = Ruby {}
+        .read_imports(text)
+        .into_iter()
+        .map(|x| (x.path, &text[x.start..x.end]))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("json".to_string(), "require 'json'"),
+            ("spells/lightning".to_string(), "require_relative(\"spells/lightning\")"),
+        ]
+    );
+}
+
+#[test]
+fn test_ruby_classes() {
+    let text = "module Spells\n  class Lightning < Game::Spell\n    include Castable, Named\n    def initialize(me)\n      @session = Game::Session.new\n      @charge = me\n    end\n    class Bolt; @size = 1; end\n  end\nend\n";
+    let classes = Ruby {}.read_classes(text);
+    let found: Vec<(&str, &str, Vec<String>)> =
+        classes.iter().map(|x| (x.name.as_str(), x.kind.as_str(), x.supertypes.clone())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Spells", "module", Vec::new()),
+            ("Lightning", "class", vec!["Game::Spell".to_string(), "Castable".to_string(), "Named".to_string()]),
+            ("Bolt", "class", Vec::new()),
+        ]
+    );
+    let fields: Vec<(&str, &str)> = classes[1].fields.iter().map(|x| (x.name.as_str(), x.typ.as_str())).collect();
+    assert_eq!(fields, vec![("@session", "Game::Session"), ("@charge", "unknown")]);
+    assert_eq!(&text[classes[2].start..classes[2].end], "class Bolt; @size = 1; end");
+    assert_eq!(classes[0].end, text.len() - 1);
+}
+
@@ -0,0 +1,734 @@
+//! Provides the parser for Rust
+
+use super::parsing::{import_aliases, Aliases, Class, Classes, Function, Functions, Identifier, Identifiers, Import, Imports, Parameter};
+use super::tokens::{self, Scopes, Token, TokenKind, Tokenizer};
+use super::{keywords, lexer, CommentSyntax, Language};
+use std::collections::HashMap;
+
+/// A Functions and Identifiers parser for Rust, which understands
+/// `fn` items, `let` bindings (with or without type annotations),
+/// parameters, and `self` within `impl` blocks.
+pub struct Rust {}
+
+/// Reads the Rust tokens the shared Tokenizer does not know: strings, which
+/// may span lines, raw and byte strings, char literals as opposed to
+/// lifetimes, and raw identifiers
+fn special(text: &str, i: usize) -> Option<(Option<TokenKind>, usize)> {
+    let b = text.as_bytes();
+    if let Some(end) = Rust::raw_string(text, i) {
+        return Some((Some(TokenKind::Str), end));
+    }
+    let quote = if b[i] == b'b' { i + 1 } else { i };
+    match b.get(quote) {
+        Some(b'"') => {
+            let mut j = quote + 1;
+            while j < b.len() && b[j] != b'"' {
+                j += if b[j] == b'\\' { 2 } else { 1 };
+            }
+            Some((Some(TokenKind::Str), (j + 1).min(b.len())))
+        }
+        Some(b'\'') => {
+            let mut chars = text[quote + 1..].chars();
+            match (chars.next(), chars.next()) {
+                (Some('\\'), _) => {
+                    let j = text[quote + 2..].find('\'').map_or(b.len(), |x| quote + 2 + x + 1);
+                    Some((Some(TokenKind::Char), j))
+                }
+                (Some(x), Some('\'')) => Some((Some(TokenKind::Char), quote + 1 + x.len_utf8() + 1)),
+                _ if quote == i => {
+                    let rest = &text[i + 1..];
+                    let end = i + 1 + rest.find(|x: char| !lexer::is_ident_char(x)).unwrap_or(rest.len());
+                    Some((Some(TokenKind::Sigil), end))
+                }
+                _ => None,
+            }
+        }
+        _ if text[i..].starts_with("r#") && text[i + 2..].starts_with(lexer::is_ident_start) => {
+            let rest = &text[i + 2..];
+            Some((Some(TokenKind::Ident), i + 2 + rest.find(|x: char| !lexer::is_ident_char(x)).unwrap_or(rest.len())))
+        }
+        _ => None,
+    }
+}
+
+/// Splits Rust source into tokens. String literals (including raw strings)
+/// and char literals become single tokens, so their contents are never
+/// parsed as code.
+const TOKENIZER: Tokenizer = Tokenizer {
+    nested: true,
+    operators: &["::", "->"],
+    special: Some(special),
+    ..Tokenizer::new(CommentSyntax::C)
+};
+
+/// The type recorded for bindings whose type is left to inference
+const INFERRED: &str = "_";
+
+impl Rust {
+    fn is_keyword(x: &str) -> bool {
+        keywords::is_keyword(
+            "rust",
+            &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+                "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+                "move", "mut", "pub", "ref", "return", "Self", "static", "struct", "super", "trait",
+                "true", "type", "unsafe", "use", "where", "while",
+            ],
+            x,
+        )
+    }
+
+    /// If a raw (byte) string literal starts at `i`, returns the index
+    /// one past its end.
+    fn raw_string(text: &str, i: usize) -> Option<usize> {
+        let rest = &text[i..];
+        let rest = rest.strip_prefix('b').unwrap_or(rest);
+        let rest = rest.strip_prefix('r')?;
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        if !rest[hashes..].starts_with('"') {
+            return None;
+        }
+        let close = format!("\"{}", "#".repeat(hashes));
+        let body = i + (text[i..].len() - rest.len()) + hashes + 1;
+        Some(text[body..].find(&close).map_or(text.len(), |x| body + x + close.len()))
+    }
+
+    /// Reads a type starting at token `i`, stopping at a `,`, `;`, `=`, `)`,
+    /// `|`, or `{` that is not nested inside brackets.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the type text (or None if there was no type) and the
+    /// index of the token following it.
+    fn read_type(text: &str, tokens: &[Token], mut i: usize) -> (Option<String>, usize) {
+        let first = i;
+        let mut depth = 0;
+        while let Some(t) = tokens.get(i) {
+            let s = &text[t.start..t.end];
+            match s {
+                "<" | "(" | "[" => depth += 1,
+                ">" | ")" | "]" if depth > 0 => depth -= 1,
+                "," | ";" | "=" | ")" | "|" | "{" if depth == 0 => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        if i == first {
+            (None, i)
+        } else {
+            let ty = &text[tokens[first].start..tokens[i - 1].end];
+            (Some(ty.split_whitespace().collect::<Vec<_>>().join(" ")), i)
+        }
+    }
+
+    /// Reads the parameters of a function whose parameter list opens at
+    /// token `i`, returning them as (name, type, token) triples along with
+    /// the index of the token after the closing parenthesis.
+    fn read_params(
+        text: &str,
+        tokens: &[Token],
+        mut i: usize,
+        self_type: Option<&String>,
+    ) -> (Vec<(String, String, Token)>, usize) {
+        let mut v = Vec::new();
+        i += 1;
+        while let Some(t) = tokens.get(i) {
+            let s = &text[t.start..t.end];
+            match s {
+                ")" => return (v, i + 1),
+                "," | "&" | "mut" => i += 1,
+                _ if t.kind == TokenKind::Sigil => i += 1,
+                "self" => {
+                    if let Some(ty) = self_type {
+                        v.push(("self".to_string(), ty.clone(), *t));
+                    }
+                    i += 1;
+                }
+                _ if t.kind == TokenKind::Ident
+                    && tokens.get(i + 1).map(|x| &text[x.start..x.end]) == Some(":") =>
+                {
+                    let (ty, next) = Self::read_type(text, tokens, i + 2);
+                    v.push((s.to_string(), ty.unwrap_or_default(), *t));
+                    i = next;
+                }
+                _ => {
+                    // A pattern we do not understand, e.g. a tuple pattern
+                    let (_, next) = Self::read_type(text, tokens, i + 1);
+                    i = next.max(i + 1);
+                }
+            }
+        }
+        (v, i)
+    }
+
+    /// Reads the type an `impl` block is for, given the index of the
+    /// `impl` keyword, i.e. `Foo` for both `impl Foo` and `impl Bar for Foo`.
+    fn read_impl_type(text: &str, tokens: &[Token], mut i: usize) -> Option<String> {
+        i += 1;
+        if tokens.get(i).map(|t| &text[t.start..t.end]) == Some("<") {
+            let mut depth = 0;
+            while let Some(t) = tokens.get(i) {
+                match &text[t.start..t.end] {
+                    "<" => depth += 1,
+                    ">" => depth -= 1,
+                    _ => {}
+                }
+                i += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+        let (ty, next) = Self::read_type(text, tokens, i);
+        let words: Vec<&str> = ty.as_deref()?.split(" for ").collect();
+        match tokens.get(next).map(|t| &text[t.start..t.end]) {
+            Some("{") => Some(words.last()?.split(" where ").next()?.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Functions for Rust {
+    /// Parses out `fn` items from Rust code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Function containing information on every function
+    /// declared within text
+    fn read_functions(&self, text: &str) -> Vec<Function> {
+        let tokens = TOKENIZER.code(text);
+        let mut v = Vec::new();
+        for (i, w) in tokens.windows(2).enumerate() {
+            if &text[w[0].start..w[0].end] != "fn" || w[1].kind != TokenKind::Ident {
+                continue;
+            }
+            // The signature starts at any qualifiers before the `fn`
+            let mut start = i;
+            while start > 0
+                && ["pub", "async", "const", "unsafe", "extern", "default"]
+                    .contains(&&text[tokens[start - 1].start..tokens[start - 1].end])
+            {
+                start -= 1;
+            }
+            // Skip any generic parameters to get to the parameter list
+            let mut j = i + 2;
+            let mut depth = 0;
+            while let Some(t) = tokens.get(j) {
+                match &text[t.start..t.end] {
+                    "<" => depth += 1,
+                    ">" => depth -= 1,
+                    "(" if depth == 0 => break,
+                    _ => {}
+                }
+                j += 1;
+            }
+            if j == tokens.len() {
+                continue;
+            }
+            let (params, mut next) = Self::read_params(text, &tokens, j, None);
+            let params = params.into_iter().map(|(x, y, _)| Parameter::new(x, y)).collect();
+            let mut typ = String::new();
+            if tokens.get(next).map(|t| &text[t.start..t.end]) == Some("->") {
+                let (ty, after) = Self::read_type(text, &tokens, next + 1);
+                typ = ty.unwrap_or_default().split(" where ").next().unwrap_or("").to_string();
+                next = after;
+            }
+            let (body, end) = match tokens.get(next).filter(|t| &text[t.start..t.end] == "{") {
+                Some(b) => (b.start, tokens[tokens::matching(text, &tokens, next)].end),
+                None => {
+                    let end = tokens[next.min(tokens.len()) - 1].end;
+                    (end, end)
+                }
+            };
+            let name = text[w[1].start..w[1].end].to_string();
+            v.push(Function::new(name, typ, params, tokens[start].start, body, end));
+        }
+        v
+    }
+}
+
+impl Classes for Rust {
+    /// Parses out struct, enum, union, and trait items from Rust code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Class containing information on every type and trait
+    /// declared within text. The supertypes of a trait are its supertraits,
+    /// and those of a type are the traits implemented for it within text.
+    fn read_classes(&self, text: &str) -> Vec<Class> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        // The traits implemented for each type, by the type's name
+        let mut impls: HashMap<String, Vec<String>> = HashMap::new();
+        for (i, t) in tokens.iter().enumerate() {
+            if &text[t.start..t.end] != "impl" {
+                continue;
+            }
+            let typ = match Self::read_impl_type(text, &tokens, i) {
+                Some(typ) => typ,
+                None => continue,
+            };
+            // Skip the impl's generic parameters, as in `impl<T> Foo for Bar<T>`
+            let mut j = i + 1;
+            let mut depth = 0;
+            while s(j) == Some("<") || depth > 0 {
+                match s(j) {
+                    Some("<") => depth += 1,
+                    Some(">") => depth -= 1,
+                    None => break,
+                    _ => {}
+                }
+                j += 1;
+            }
+            if let Some(header) = Self::read_type(text, &tokens, j).0.filter(|x| x.contains(" for ")) {
+                let name = typ.split('<').next().unwrap_or("").to_string();
+                let trait_name = header.split(" for ").next().unwrap_or("").to_string();
+                impls.entry(name).or_default().push(trait_name);
+            }
+        }
+        let mut v = Vec::new();
+        for (i, t) in tokens.iter().enumerate() {
+            let kind = &text[t.start..t.end];
+            if !["struct", "enum", "union", "trait"].contains(&kind) {
+                continue;
+            }
+            let name = match tokens.get(i + 1).filter(|x| x.kind == TokenKind::Ident) {
+                Some(x) => &text[x.start..x.end],
+                None => continue,
+            };
+            let mut start = i;
+            loop {
+                match start.checked_sub(1).and_then(s) {
+                    Some("pub") | Some("unsafe") | Some("auto") => start -= 1,
+                    Some(")") => match tokens[..start].iter().rposition(|x| &text[x.start..x.end] == "(") {
+                        Some(open) if open > 0 && s(open - 1) == Some("pub") => start = open - 1,
+                        _ => break,
+                    },
+                    _ => break,
+                }
+            }
+            let mut j = i + 2;
+            let mut depth = 0;
+            let mut bounds = None;
+            while let Some(x) = s(j) {
+                match x {
+                    "<" | "(" => depth += 1,
+                    ">" | ")" => depth -= 1,
+                    ":" if depth == 0 && kind == "trait" => bounds = Some(j + 1),
+                    "{" | ";" | "where" if depth == 0 => break,
+                    _ => {}
+                }
+                j += 1;
+            }
+            let supertypes = match bounds {
+                Some(b) if b < j => text[tokens[b].start..tokens[j - 1].end]
+                    .split('+')
+                    .map(|x| x.split_whitespace().collect::<Vec<_>>().join(" "))
+                    .collect(),
+                _ => impls.get(name).cloned().unwrap_or_default(),
+            };
+            while s(j).is_some_and(|x| x != "{" && x != ";") {
+                j += 1;
+            }
+            let mut fields = Vec::new();
+            let (body, end) = if s(j) == Some("{") {
+                let close = tokens::matching(text, &tokens, j);
+                let mut depth = 0;
+                let mut k = j + 1;
+                while k < close {
+                    match s(k).unwrap() {
+                        "(" | "[" | "{" | "<" => depth += 1,
+                        ")" | "]" | "}" | ">" => depth -= 1,
+                        _ if depth == 0
+                            && kind != "trait"
+                            && tokens[k].kind == TokenKind::Ident
+                            && s(k + 1) == Some(":") =>
+                        {
+                            let (ty, next) = Self::read_type(text, &tokens, k + 2);
+                            let n = tokens[k];
+                            fields.push(Identifier::new(text[n.start..n.end].to_string(), ty.unwrap_or_default(), n.start, n.end));
+                            k = next;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    k += 1;
+                }
+                (tokens[j].start, tokens[close].end)
+            } else {
+                let end = tokens[j.min(tokens.len() - 1)].end;
+                (end, end)
+            };
+            let kind = kind.to_string();
+            v.push(Class::new(name.to_string(), kind, supertypes, fields, tokens[start].start, body, end));
+        }
+        v
+    }
+}
+
+impl Rust {
+    /// Reads the use tree starting at `i`, like `a::b::{c, d::e as f}`,
+    /// flattening it into the paths it imports
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The path of the group the tree is within, if any
+    /// * `out` - Where the paths and their aliases are collected
+    ///
+    /// # Returns
+    ///
+    /// The index just past the tree
+    fn read_use_tree(text: &str, tokens: &[Token], mut i: usize, prefix: &str, out: &mut Vec<(String, Option<String>)>) -> usize {
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut path = prefix.to_string();
+        while let Some(x) = s(i) {
+            match x {
+                "::" => {
+                    i += 1;
+                    continue;
+                }
+                "{" => {
+                    i += 1;
+                    while s(i).is_some() && s(i) != Some("}") {
+                        let next = Self::read_use_tree(text, tokens, i, &path, out);
+                        // A token that is no part of a path ends the group
+                        if next == i {
+                            return i;
+                        }
+                        i = next;
+                        if s(i) == Some(",") {
+                            i += 1;
+                        }
+                    }
+                    return i + 1;
+                }
+                "self" if !path.is_empty() => {}
+                _ if tokens[i].kind == TokenKind::Ident || x == "*" => {
+                    if !path.is_empty() {
+                        path += "::";
+                    }
+                    path += x;
+                }
+                _ => break,
+            }
+            i += 1;
+            if s(i) == Some("as") {
+                out.push((path, s(i + 1).map(String::from)));
+                return i + 2;
+            }
+            if s(i) != Some("::") {
+                break;
+            }
+        }
+        out.push((path, None));
+        i
+    }
+}
+
+impl Imports for Rust {
+    /// Writes a `use` declaration of `path`, like `use std::io::Read;`
+    fn import_statement(&self, path: &str) -> Option<String> {
+        Some(format!("use {};", path))
+    }
+
+    /// Parses out `use` declarations from Rust code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Import for every path brought into scope within text, with
+    /// groups like `use a::{b, c as d};` flattened into one Import per path,
+    /// each spanning the whole declaration along with its visibility
+    fn read_imports(&self, text: &str) -> Vec<Import> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let line_start = i == 0 || matches!(s(i - 1), Some(";") | Some("{") | Some("}") | Some("]") | Some(")") | Some("pub"));
+            if s(i) != Some("use") || !line_start {
+                i += 1;
+                continue;
+            }
+            let start = match (i.checked_sub(1).and_then(s), i.checked_sub(4).and_then(s)) {
+                (Some("pub"), _) => tokens[i - 1].start,
+                (Some(")"), Some("pub")) => tokens[i - 4].start,
+                _ => tokens[i].start,
+            };
+            let mut paths = Vec::new();
+            i = Self::read_use_tree(text, &tokens, i + 1, "", &mut paths);
+            let end = match tokens.get(i) {
+                Some(t) if s(i) == Some(";") => t.end,
+                _ => tokens.get(i.max(1) - 1).map_or(0, |t| t.end),
+            };
+            v.extend(paths.into_iter().map(|(path, alias)| Import::new(path, alias, start, end)));
+        }
+        v
+    }
+}
+
+impl Aliases for Rust {
+    /// Parses out `type` aliases and the names brought in by `use`
+    /// declarations from Rust code. Generic aliases and associated types
+    /// within `impl` and `trait` blocks are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier for each alias within text, whose type is the
+    /// type or path it stands for
+    fn read_aliases(&self, text: &str) -> Vec<Identifier> {
+        let tokens = TOKENIZER.code(text);
+        let s = |j: usize| tokens.get(j).map(|t| &text[t.start..t.end]);
+        let mut v = Vec::new();
+        // Whether each open brace is the body of an `impl` or `trait`
+        let mut braces = Vec::new();
+        let mut item = None;
+        for i in 0..tokens.len() {
+            match s(i).unwrap() {
+                "impl" | "trait" | "fn" | "mod" | "struct" | "enum" | "union" => item = s(i),
+                ";" => item = None,
+                "{" => {
+                    braces.push(matches!(item, Some("impl") | Some("trait")));
+                    item = None;
+                }
+                "}" => {
+                    braces.pop();
+                }
+                "type" if s(i + 2) == Some("=") && braces.last() != Some(&true) => {
+                    if let (Some(typ), _) = Self::read_type(text, &tokens, i + 3) {
+                        let name = &tokens[i + 1];
+                        v.push(Identifier::new(text[name.start..name.end].to_string(), typ, name.start, name.end));
+                    }
+                }
+                _ => {}
+            }
+        }
+        v.extend(import_aliases(self.read_imports(text)));
+        v
+    }
+}
+
+impl Identifiers for Rust {
+    /// Parses out identifier uses from Rust code
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing information on every use of
+    /// an identifier declared within the code. Bindings without a type
+    /// annotation are given the type `_`.
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        let tokens = TOKENIZER.code(text);
+        let mut v = Vec::new();
+        let mut scopes = Scopes::new();
+        let mut pending = HashMap::new();
+        let mut impls: Vec<(usize, String)> = Vec::new();
+        let mut pending_impl = None;
+        let mut i = 0;
+        while let Some(t) = tokens.get(i) {
+            let s = &text[t.start..t.end];
+            let prev = if i > 0 {
+                Some(&text[tokens[i - 1].start..tokens[i - 1].end])
+            } else {
+                None
+            };
+            match s {
+                "{" => {
+                    scopes.push(std::mem::take(&mut pending));
+                    if let Some(ty) = pending_impl.take() {
+                        impls.push((scopes.depth(), ty));
+                    }
+                }
+                "}" => {
+                    if impls.last().map(|x| x.0) == Some(scopes.depth()) {
+                        impls.pop();
+                    }
+                    scopes.pop();
+                }
+                ";" => {
+                    pending.clear();
+                }
+                "impl" => {
+                    pending_impl = Self::read_impl_type(text, &tokens, i);
+                }
+                "use" => {
+                    while tokens.get(i).is_some_and(|x| &text[x.start..x.end] != ";") {
+                        i += 1;
+                    }
+                    continue;
+                }
+                "fn" => {
+                    let mut j = i + 1;
+                    while let Some(x) = tokens.get(j) {
+                        if &text[x.start..x.end] == "(" {
+                            break;
+                        }
+                        j += 1;
+                    }
+                    let self_type = impls.last().map(|x| &x.1);
+                    let (params, next) = Self::read_params(text, &tokens, j, self_type);
+                    for (name, typ, t) in params {
+                        v.push(Identifier::new(name.clone(), typ.clone(), t.start, t.end));
+                        pending.insert(name, typ);
+                    }
+                    i = next;
+                    continue;
+                }
+                "let" => {
+                    let mut j = i + 1;
+                    if tokens.get(j).map(|x| &text[x.start..x.end]) == Some("mut") {
+                        j += 1;
+                    }
+                    if let Some(n) = tokens.get(j).filter(|x| x.kind == TokenKind::Ident) {
+                        let name = text[n.start..n.end].to_string();
+                        let typ = if tokens.get(j + 1).map(|x| &text[x.start..x.end]) == Some(":") {
+                            let (ty, next) = Self::read_type(text, &tokens, j + 2);
+                            j = next;
+                            ty.unwrap_or_default()
+                        } else {
+                            j += 1;
+                            INFERRED.to_string()
+                        };
+                        v.push(Identifier::new(name.clone(), typ.clone(), n.start, n.end));
+                        scopes.declare(name, typ);
+                        i = j;
+                        continue;
+                    }
+                }
+                _ if t.kind == TokenKind::Ident
+                    && !Self::is_keyword(s)
+                    && prev != Some(".")
+                    && prev != Some("::") =>
+                {
+                    if let Some(typ) = scopes.lookup(s) {
+                        v.push(Identifier::new(s.to_string(), typ.to_string(), t.start, t.end));
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        v
+    }
+}
+
+impl Language for Rust {
+    fn name(&self) -> &'static str {
+        "rust"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["rs"]
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax::C
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        TOKENIZER.tokenize(text)
+    }
+}
+
+#[test]
+fn test_rust_functions() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.rs");
+    let text = std::fs::read_to_string(d).unwrap();
+    let names: Vec<String> = Rust {}
+        .read_functions(&text)
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, vec!["new", "spawn", "main"]);
+
+    let text = "pub fn split<T>(&self, n: usize, s: &str) -> Vec<T> where T: Copy {\n    vec![]\n}\n";
+    let f = &Rust {}.read_functions(text)[0];
+    assert_eq!((f.typ.as_str(), f.start, f.body, f.end), ("Vec<T>", 0, 66, 80));
+    assert_eq!(f.params, vec![Parameter::new("n".into(), "usize".into()), Parameter::new("s".into(), "&str".into())]);
+}
+
+#[test]
+fn test_rust_identifiers() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.rs");
+    let text = std::fs::read_to_string(d).unwrap();
+    let idents = Rust {}.read_identifiers(&text);
+    let of = |name: &str| -> Vec<String> {
+        idents
+            .iter()
+            .filter(|x| x.name == name)
+            .map(|x| x.typ.clone())
+            .collect()
+    };
+    assert_eq!(of("me"), vec!["&mut Session<'a>"; 4]);
+    assert_eq!(of("self"), vec!["Lightning"; 2]);
+    assert_eq!(of("charge"), vec!["u32", "u32"]);
+    assert_eq!(of("count"), vec!["_", "_"]);
+    assert!(of("brace").is_empty());
+    for ident in &idents {
+        assert_eq!(&text[ident.start..ident.end], ident.name);
+    }
+}
+
+#[test]
+fn test_rust_imports() {
+    let text = "use std::io;\npub(crate) use std::collections::{self, HashMap as Map, hash_map::*};\nfn main() {\n    use super::Spell;\n}\n";
+    let found: Vec<(String, Option<String>, usize, usize)> =
+        Rust {}.read_imports(text).into_iter().map(|x| (x.path, x.alias, x.start, x.end)).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("std::io".to_string(), None, 0, 12),
+            ("std::collections".to_string(), None, 13, 82),
+            ("std::collections::HashMap".to_string(), Some("Map".to_string()), 13, 82),
+            ("std::collections::hash_map::*".to_string(), None, 13, 82),
+            ("super::Spell".to_string(), None, 99, 116),
+        ]
+    );
+}
+
+#[test]
+fn test_rust_aliases() {
+    let text = "use std::collections::HashMap as Map;\ntype Id = u64;\ntype Res<T> = Result<T, Error>;\nimpl Iterator for Spells {\n    type Item = Spell;\n}\n";
+    let found: Vec<(String, String)> = Rust {}.read_aliases(text).into_iter().map(|x| (x.name, x.typ)).collect();
+    assert_eq!(found, vec![("Id".into(), "u64".into()), ("Map".into(), "std::collections::HashMap".into())]);
+}
+
+#[test]
+fn test_rust_classes() {
+    let text = "#[derive(Debug)]\npub(crate) struct Spell<T> where T: Copy {\n    pub me: Session,\n    charge: Vec<T>,\n}\nimpl<T> Display for Spell<T> {}\npub trait Castable: Named + Send {\n    fn cast(&self);\n}\nstruct Unit;\n";
+    let classes = Rust {}.read_classes(text);
+    let found: Vec<(&str, &str, Vec<String>, usize)> =
+        classes.iter().map(|x| (x.name.as_str(), x.kind.as_str(), x.supertypes.clone(), x.start)).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Spell", "struct", vec!["Display".to_string()], 17),
+            ("Castable", "trait", vec!["Named".to_string(), "Send".to_string()], 135),
+            ("Unit", "struct", Vec::new(), 192),
+        ]
+    );
+    let fields: Vec<(&str, &str)> = classes[0].fields.iter().map(|x| (x.name.as_str(), x.typ.as_str())).collect();
+    assert_eq!(fields, vec![("me", "Session"), ("charge", "Vec<T>")]);
+    assert!(classes[1].fields.is_empty());
+    assert_eq!((classes[2].body, classes[2].end), (text.len() - 1, text.len() - 1));
+}
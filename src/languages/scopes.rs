@@ -0,0 +1,169 @@
+//! Provides the tree of scopes within a piece of code, which records where
+//! each identifier lives, so that callers can tell whether a name is already
+//! taken where another is declared
+
+use super::parsing::{Function, Identifier};
+use super::tokens::{self, TokenKind};
+use super::Language;
+
+/// A scope within a piece of code, which is the whole code or a block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    /// The ID of the scope, which is its index within its ScopeTree. The
+    /// whole code is scope 0, and every other scope is numbered in the order
+    /// its block opens, so IDs only change when blocks before them do.
+    pub id: usize,
+    /// The ID of the scope this one is nested directly within, which is
+    /// None only for scope 0
+    pub parent: Option<usize>,
+    /// The byte index the scope starts at, which for a function's body is
+    /// the start of its signature, so that its parameters are within it
+    pub start: usize,
+    /// The byte index one past the end of the scope
+    pub end: usize,
+}
+
+/// The scopes within a piece of code, each of which knows its parent
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeTree {
+    scopes: Vec<Scope>,
+}
+
+impl ScopeTree {
+    /// Creates a ScopeTree from the scopes in it
+    ///
+    /// # Arguments
+    ///
+    /// * `scopes` - The scopes, in order of their IDs, the first of which
+    ///   must be the whole code
+    pub fn new(scopes: Vec<Scope>) -> Self {
+        Self { scopes }
+    }
+
+    /// Finds the scopes in a piece of code, which are its `{ }` blocks
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    /// * `lang` - The language `text` is in
+    /// * `functions` - The functions in `text`, whose bodies' scopes are
+    ///   extended back to include their signatures
+    pub fn build(text: &str, lang: &dyn Language, functions: &[Function]) -> Self {
+        let mut scopes = vec![Scope {
+            id: 0,
+            parent: None,
+            start: 0,
+            end: text.len(),
+        }];
+        let mut open = vec![0];
+        for t in tokens::code(lang.tokenize(text)) {
+            if t.kind != TokenKind::Punct {
+                continue;
+            }
+            match t.text(text) {
+                "{" => {
+                    let start = functions.iter().find(|x| x.body == t.start).map_or(t.start, |x| x.start);
+                    open.push(scopes.len());
+                    scopes.push(Scope {
+                        id: scopes.len(),
+                        parent: open.get(open.len() - 2).copied(),
+                        start,
+                        end: text.len(),
+                    });
+                }
+                "}" if open.len() > 1 => {
+                    let id = open.pop().unwrap();
+                    scopes[id].end = t.end;
+                }
+                _ => {}
+            }
+        }
+        Self::new(scopes)
+    }
+
+    /// The scope with an ID, if there is one
+    pub fn get(&self, id: usize) -> Option<&Scope> {
+        self.scopes.get(id)
+    }
+
+    /// Iterates over every scope, in order of their IDs
+    pub fn iter(&self) -> impl Iterator<Item = &Scope> {
+        self.scopes.iter()
+    }
+
+    /// Iterates over a scope and the scopes enclosing it, innermost first
+    pub fn ancestors(&self, id: usize) -> impl Iterator<Item = &Scope> {
+        std::iter::successors(self.get(id), move |x| x.parent.and_then(|p| self.get(p)))
+    }
+
+    /// Whether the scope `outer` is the scope `inner` or encloses it
+    pub fn encloses(&self, outer: usize, inner: usize) -> bool {
+        self.ancestors(inner).any(|x| x.id == outer)
+    }
+
+    /// Finds the innermost scope a byte offset is within
+    pub fn innermost(&self, offset: usize) -> usize {
+        // Scopes are numbered in the order they open, so the last one
+        // holding the offset is the innermost
+        self.scopes
+            .iter()
+            .rev()
+            .find(|x| x.start <= offset && offset < x.end)
+            .map_or(0, |x| x.id)
+    }
+
+    /// Sets the scope of each of a set of identifiers, which is the
+    /// innermost scope it is within
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The identifiers, like the result of `read_identifiers`
+    ///
+    /// # Returns
+    ///
+    /// The identifiers, with their `scope_id` set
+    pub fn assign(&self, mut items: Vec<Identifier>) -> Vec<Identifier> {
+        for item in items.iter_mut() {
+            item.scope_id = self.innermost(item.start);
+        }
+        items
+    }
+
+    /// Finds the identifiers that a name would collide with if it were
+    /// declared in a scope, which are those with the name in that scope or
+    /// any scope enclosing it
+    ///
+    /// # Arguments
+    ///
+    /// * `identifiers` - The identifiers in the code, with their scopes set
+    /// * `scope` - The ID of the scope the name would be declared in
+    /// * `name` - The name
+    pub fn collisions<'a>(&self, identifiers: &'a [Identifier], scope: usize, name: &str) -> Vec<&'a Identifier> {
+        identifiers
+            .iter()
+            .filter(|x| x.name == name && self.encloses(x.scope_id, scope))
+            .collect()
+    }
+}
+
+#[test]
+fn test_scope_tree() {
+    use super::clike::Clike;
+    use super::parsing::{Functions, Identifiers};
+    let text = "int g;\nvoid f(int a) {\n    int b = a;\n    { int a = g; }\n}\nvoid h() { int b; }\n";
+    let functions = Clike {}.read_functions(text);
+    let tree = ScopeTree::build(text, &Clike {}, &functions);
+    let scopes: Vec<(Option<usize>, &str)> = tree.iter().map(|x| (x.parent, &text[x.start..x.end])).collect();
+    assert_eq!(scopes.len(), 4);
+    assert_eq!(scopes[1], (Some(0), "void f(int a) {\n    int b = a;\n    { int a = g; }\n}"));
+    assert_eq!(scopes[2], (Some(1), "{ int a = g; }"));
+    assert_eq!(scopes[3], (Some(0), "void h() { int b; }"));
+    let idents = tree.assign(Clike {}.read_identifiers(text));
+    let found: Vec<(&str, usize)> = idents.iter().map(|x| (x.name.as_str(), x.scope_id)).collect();
+    assert_eq!(found, vec![("g", 0), ("a", 1), ("b", 1), ("a", 1), ("a", 2), ("g", 2), ("b", 3)]);
+    assert!(tree.encloses(0, 2) && tree.encloses(1, 2) && !tree.encloses(3, 2));
+    // `g` is visible in `f`'s inner block, but `b` in `h` is not
+    assert_eq!(tree.collisions(&idents, 2, "g").len(), 2);
+    assert_eq!(tree.collisions(&idents, 2, "b").len(), 1);
+    assert!(tree.collisions(&idents, 3, "a").is_empty());
+}
@@ -0,0 +1,607 @@
+//! Provides the parser for POSIX shell and bash scripts
+
+use super::parsing::{Aliases, Class, Classes, Function, Functions, Identifier, Identifiers, Import, Imports};
+use super::{CommentSyntax, Language};
+use std::collections::HashMap;
+
+/// A Functions and Identifiers parser for shell scripts, which understands
+/// variable assignments (including `local`, `export`, `declare`, `read`,
+/// and `for`), `function name`/`name()` definitions, quoting rules, and
+/// here-docs.
+///
+/// Variables have no real types, so they are given the type `string`,
+/// unless declared with `declare -i` (`integer`), `-a` (`array`), or
+/// `-A` (`associative`). Uses of variables that are never assigned in
+/// the script, like `$HOME`, are given the type `env`. Nothing inside
+/// single quotes or quoted here-docs is treated as a use.
+pub struct Shell {}
+
+/// The type recorded for variables without a more specific declaration
+const STRING: &str = "string";
+/// The type recorded for variables that come from the environment
+const ENV: &str = "env";
+
+/// Words after which a new command starts
+const COMMAND_STARTERS: &[&str] = &[
+    "then", "do", "else", "elif", "if", "while", "until", "!", "time",
+];
+
+struct Scanner<'a> {
+    text: &'a str,
+    functions: Vec<Function>,
+    identifiers: Vec<Identifier>,
+    frames: Vec<(usize, HashMap<String, String>)>,
+    /// Brace depth, used to tell when function bodies end
+    depth: usize,
+    /// The function whose body opens at the next `{` or `(`
+    pending_function: bool,
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            functions: Vec::new(),
+            identifiers: Vec::new(),
+            frames: vec![(0, HashMap::new())],
+            depth: 0,
+            pending_function: false,
+        }
+    }
+
+    /// The text from `i` on, which is empty if `i` is past the end or
+    /// within a character, so that a script cut off partway through a
+    /// construct is never sliced out of bounds
+    fn rest(&self, i: usize) -> &'a str {
+        self.text.get(i..).unwrap_or("")
+    }
+
+    /// The index just past the character that the `\` at `i` escapes
+    fn escaped(&self, i: usize) -> usize {
+        i + 1 + self.rest(i + 1).chars().next().map_or(0, char::len_utf8)
+    }
+
+    /// The length of the variable name starting at `i`, or 0 if none does
+    fn name_len(&self, i: usize) -> usize {
+        let rest = self.rest(i);
+        if !rest.starts_with(is_name_start) {
+            return 0;
+        }
+        rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len())
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        for (_, frame) in self.frames.iter().rev() {
+            if let Some(typ) = frame.get(name) {
+                return Some(typ.clone());
+            }
+        }
+        None
+    }
+
+    /// Records an assignment to the variable at `start..end`. Unless
+    /// `local` is set, the variable is assigned where it is already
+    /// declared, or globally.
+    fn declare(&mut self, start: usize, end: usize, typ: Option<&str>, local: bool) {
+        let name = self.text[start..end].to_string();
+        let frame = if local {
+            self.frames.len() - 1
+        } else {
+            self.frames
+                .iter()
+                .rposition(|(_, f)| f.contains_key(&name))
+                .unwrap_or(0)
+        };
+        let typ = match typ {
+            Some(t) => t.to_string(),
+            None => self.frames[frame]
+                .1
+                .get(&name)
+                .cloned()
+                .unwrap_or_else(|| STRING.to_string()),
+        };
+        self.identifiers
+            .push(Identifier::new(name.clone(), typ.clone(), start, end));
+        self.frames[frame].1.insert(name, typ);
+    }
+
+    fn use_var(&mut self, start: usize, end: usize) {
+        let name = &self.text[start..end];
+        let typ = self.lookup(name).unwrap_or_else(|| ENV.to_string());
+        self.identifiers
+            .push(Identifier::new(name.to_string(), typ, start, end));
+    }
+
+    /// Handles the expansion starting with the `$` at `i`, returning the
+    /// index just past the name (or past the `$` if it is not a variable).
+    fn expansion(&mut self, i: usize) -> usize {
+        let rest = self.rest(i + 1);
+        if rest.starts_with("((") {
+            return self.arithmetic(i + 3, "))");
+        }
+        if rest.starts_with('\'') {
+            // $'...' ANSI-C quoting
+            return self.single_quoted(i + 1);
+        }
+        let mut j = i + 1;
+        let braced = rest.starts_with('{');
+        if braced {
+            j += 1;
+            if self.rest(j).starts_with(['#', '!']) {
+                j += 1;
+            }
+        }
+        let len = self.name_len(j);
+        if len > 0 {
+            self.use_var(j, j + len);
+        }
+        if braced && self.rest(j + len).starts_with('}') {
+            j + len + 1
+        } else {
+            j + len
+        }
+    }
+
+    /// Handles an arithmetic expression, where bare names are variable uses,
+    /// until `close`, returning the index just past it.
+    fn arithmetic(&mut self, mut i: usize, close: &str) -> usize {
+        while let Some(c) = self.rest(i).chars().next().filter(|_| !self.rest(i).starts_with(close)) {
+            let len = self.name_len(i);
+            if len > 0 {
+                self.use_var(i, i + len);
+                i += len;
+            } else if c == '$' {
+                i = self.expansion(i);
+            } else {
+                i += c.len_utf8();
+            }
+        }
+        (i + close.len()).min(self.text.len())
+    }
+
+    fn single_quoted(&self, i: usize) -> usize {
+        self.rest(i + 1)
+            .find('\'')
+            .map_or(self.text.len(), |x| i + 1 + x + 1)
+    }
+
+    /// Handles a double-quoted string starting at `i`, in which expansions
+    /// are still variable uses, returning the index just past it.
+    fn double_quoted(&mut self, mut i: usize) -> usize {
+        i += 1;
+        while let Some(c) = self.rest(i).chars().next() {
+            match c {
+                '"' => return i + 1,
+                '\\' => i = self.escaped(i),
+                '$' => i = self.expansion(i),
+                _ => i += c.len_utf8(),
+            }
+        }
+        self.text.len()
+    }
+
+    /// Skips the body of a here-doc whose delimiter is `id`, starting at
+    /// the line `i`. Expansions in the body are variable uses unless
+    /// the delimiter was quoted.
+    fn heredoc(&mut self, mut i: usize, id: &str, expand: bool) -> usize {
+        while i < self.text.len() {
+            let end = self.rest(i)
+                .find('\n')
+                .map_or(self.text.len(), |x| i + x + 1);
+            if self.text[i..end].trim_start_matches('\t').trim_end() == id {
+                return end;
+            }
+            if expand {
+                let mut j = i;
+                while let Some(c) = self.rest(j).chars().next().filter(|_| j < end) {
+                    if c == '$' {
+                        j = self.expansion(j);
+                    } else {
+                        j += c.len_utf8();
+                    }
+                }
+            }
+            i = end;
+        }
+        i
+    }
+
+    fn scan(mut self) -> Self {
+        let text = self.text;
+        let mut heredocs: Vec<(String, bool)> = Vec::new();
+        let mut command_start = true;
+        // The declaring builtin (`local`, `read`, ...) whose arguments we are in
+        let mut declaring: Option<(&str, Option<&str>)> = None;
+        let rest = |i: usize| text.get(i..).unwrap_or("");
+        let mut i = 0;
+        while let Some(c) = rest(i).chars().next() {
+            let word_start =
+                i == 0 || text[..i].ends_with(|x: char| x.is_whitespace() || ";&|(){}".contains(x));
+            match c {
+                '\n' => {
+                    i += 1;
+                    for (id, expand) in std::mem::take(&mut heredocs) {
+                        i = self.heredoc(i, &id, expand);
+                    }
+                    command_start = true;
+                    declaring = None;
+                    continue;
+                }
+                ';' | '&' | '|' => {
+                    i += 1;
+                    command_start = true;
+                    declaring = None;
+                    continue;
+                }
+                _ if c.is_whitespace() => {
+                    i += c.len_utf8();
+                    continue;
+                }
+                '#' if word_start => {
+                    i = text[i..].find('\n').map_or(text.len(), |x| i + x);
+                    continue;
+                }
+                '\'' => {
+                    i = self.single_quoted(i);
+                }
+                '"' => {
+                    i = self.double_quoted(i);
+                }
+                '\\' => {
+                    i = self.escaped(i);
+                }
+                '$' => {
+                    i = self.expansion(i);
+                }
+                '<' if rest(i).starts_with("<<") && !rest(i).starts_with("<<<") => {
+                    i += 2;
+                    if rest(i).starts_with('-') {
+                        i += 1;
+                    }
+                    let after = rest(i).trim_start_matches([' ', '\t']);
+                    i = text.len() - after.len();
+                    let quote = after.chars().next().filter(|x| *x == '\'' || *x == '"');
+                    let id_start = i + quote.map_or(0, char::len_utf8);
+                    let len = rest(id_start)
+                        .find(|x: char| !(is_name_char(x) || x == '-'))
+                        .unwrap_or(text.len() - id_start);
+                    heredocs.push((text[id_start..id_start + len].to_string(), quote.is_none()));
+                    i = id_start + len;
+                    if quote.is_some_and(|x| rest(i).starts_with(x)) {
+                        i += 1;
+                    }
+                }
+                '(' if rest(i).starts_with("((") && command_start => {
+                    i = self.arithmetic(i + 2, "))");
+                }
+                '{' | '(' if word_start => {
+                    self.depth += 1;
+                    if self.pending_function {
+                        self.frames.push((self.depth, HashMap::new()));
+                        self.pending_function = false;
+                        self.functions.last_mut().unwrap().body = i;
+                    }
+                    i += 1;
+                    command_start = true;
+                    continue;
+                }
+                '}' | ')' if word_start || c == ')' => {
+                    if self.frames.len() > 1 && self.frames.last().unwrap().0 == self.depth {
+                        self.frames.pop();
+                        // The innermost function whose body is still open
+                        if let Some(f) = self.functions.iter_mut().rev().find(|f| f.body > 0 && f.end == 0) {
+                            f.end = i + 1;
+                        }
+                    }
+                    self.depth = self.depth.saturating_sub(1);
+                    i += 1;
+                    continue;
+                }
+                _ => {
+                    let end = rest(i)
+                        .find(|x: char| x.is_whitespace() || ";&|()<>'\"$`\\".contains(x))
+                        .map_or(text.len(), |x| i + x)
+                        .max(i + c.len_utf8());
+                    let word = &text[i..end];
+                    let len = self.name_len(i);
+                    if command_start && word == "function" {
+                        let after = rest(end).trim_start_matches([' ', '\t']);
+                        let start = text.len() - after.len();
+                        let name_end = after
+                            .find(|x: char| x.is_whitespace() || x == '(' || x == '{')
+                            .map_or(text.len(), |x| start + x);
+                        self.functions.push(Function::new(
+                            text[start..name_end].to_string(),
+                            String::new(),
+                            Vec::new(),
+                            i,
+                            0,
+                            0,
+                        ));
+                        self.pending_function = true;
+                        i = name_end;
+                        continue;
+                    } else if command_start
+                        && len > 0
+                        && rest(i + len).trim_start_matches(' ').starts_with("()")
+                    {
+                        self.functions.push(Function::new(
+                            text[i..i + len].to_string(),
+                            String::new(),
+                            Vec::new(),
+                            i,
+                            0,
+                            0,
+                        ));
+                        self.pending_function = true;
+                        let after = rest(i + len).trim_start_matches(' ');
+                        i = text.len() - after.len() + 2;
+                        continue;
+                    } else if (command_start || declaring.is_some())
+                        && len > 0
+                        && (rest(i + len).starts_with('=')
+                            || rest(i + len).starts_with("+=")
+                            || rest(i + len).starts_with('['))
+                    {
+                        let (local, typ) = match declaring {
+                            Some(("local", t)) | Some(("declare", t)) | Some(("typeset", t)) => {
+                                (self.frames.len() > 1, t)
+                            }
+                            Some((_, t)) => (false, t),
+                            None => (false, None),
+                        };
+                        self.declare(i, i + len, typ, local);
+                        i += len;
+                        // The value, which may itself contain expansions
+                        if rest(i).starts_with('[') {
+                            i = self.arithmetic(i + 1, "]");
+                        }
+                        if rest(i).starts_with("+=") {
+                            i += 2;
+                        } else if rest(i).starts_with('=') {
+                            i += 1;
+                        }
+                        continue;
+                    } else if command_start
+                        && ["local", "export", "declare", "typeset", "readonly", "read"]
+                            .contains(&word)
+                    {
+                        declaring = Some((word, None));
+                    } else if command_start && word == "for" {
+                        let after = rest(end).trim_start();
+                        let start = text.len() - after.len();
+                        let len = self.name_len(start);
+                        if len > 0 && !after.starts_with("((") {
+                            self.declare(start, start + len, None, false);
+                            i = start + len;
+                            command_start = false;
+                            continue;
+                        }
+                    } else if let Some((builtin, typ)) = declaring {
+                        if word.starts_with('-') {
+                            let t = if word.contains('A') {
+                                Some("associative")
+                            } else if word.contains('a') {
+                                Some("array")
+                            } else if word.contains('i') {
+                                Some("integer")
+                            } else {
+                                typ
+                            };
+                            declaring = Some((builtin, t));
+                        } else if len == word.len() {
+                            let local = builtin != "read"
+                                && builtin != "export"
+                                && builtin != "readonly"
+                                && self.frames.len() > 1;
+                            self.declare(i, end, typ, local);
+                        }
+                    }
+                    i = end;
+                    command_start = COMMAND_STARTERS.contains(&word);
+                    continue;
+                }
+            }
+            command_start = false;
+        }
+        self
+    }
+}
+
+impl Functions for Shell {
+    /// Parses out function definitions from a shell script
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the script to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Function containing information on every function
+    /// defined within text, in either `function name` or `name()` form
+    fn read_functions(&self, text: &str) -> Vec<Function> {
+        Scanner::new(text).scan().functions
+    }
+}
+
+impl Identifiers for Shell {
+    /// Parses out variable uses from a shell script
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the script to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing information on every assignment
+    /// and expansion of a variable outside of single quotes
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        Scanner::new(text).scan().identifiers
+    }
+}
+
+impl Classes for Shell {
+    /// Shell scripts have no classes, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the script
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_classes(&self, _text: &str) -> Vec<Class> {
+        Vec::new()
+    }
+}
+
+impl Imports for Shell {
+    /// Writes a `source` of the script `path`, like `source lib/common.sh`
+    fn import_statement(&self, path: &str) -> Option<String> {
+        Some(format!("source {}", path))
+    }
+
+    /// Parses out scripts sourced with `source` or `.` from a shell script,
+    /// when the command starts its line
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the script to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Import for every sourced file within text, with any quotes
+    /// around its path removed
+    fn read_imports(&self, text: &str) -> Vec<Import> {
+        let mut v = Vec::new();
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            let start = offset + line.len() - line.trim_start().len();
+            offset += line.len();
+            let command = &text[start..offset];
+            let rest = match command.split_once([' ', '\t']) {
+                Some((word, rest)) if word == "source" || word == "." => rest.trim_start(),
+                _ => continue,
+            };
+            let len = rest.find(|c: char| c.is_whitespace() || ";&|)#".contains(c)).unwrap_or(rest.len());
+            let path = rest[..len].trim_matches(|c| c == '"' || c == '\'');
+            if !path.is_empty() {
+                let end = offset - rest.len() + len;
+                v.push(Import::new(path.to_string(), None, start, end));
+            }
+        }
+        v
+    }
+}
+
+impl Aliases for Shell {
+    /// Shell scripts have no type aliases, so this is always empty
+    ///
+    /// # Arguments
+    ///
+    /// * `_text` - A string slice that contains the script
+    ///
+    /// # Returns
+    ///
+    /// An empty Vec
+    fn read_aliases(&self, _text: &str) -> Vec<Identifier> {
+        Vec::new()
+    }
+}
+
+impl Language for Shell {
+    fn name(&self) -> &'static str {
+        "shell"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["sh", "bash", "zsh", "ksh"]
+    }
+
+    fn interpreters(&self) -> &'static [&'static str] {
+        &["sh", "bash", "zsh", "ksh", "dash", "ash"]
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        CommentSyntax::HASH
+    }
+}
+
+#[test]
+fn test_shell_functions() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.sh");
+    let text = std::fs::read_to_string(d).unwrap();
+    let names: Vec<String> = Shell {}
+        .read_functions(&text)
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, vec!["spawn", "cleanup"]);
+
+    let text = "x=1\nfunction go {\n  inner() ( echo $x )\n}\n";
+    let found: Vec<(String, usize, usize, usize)> =
+        Shell {}.read_functions(text).into_iter().map(|f| (f.name, f.start, f.body, f.end)).collect();
+    assert_eq!(found, vec![("go".to_string(), 4, 16, 41), ("inner".to_string(), 20, 28, 39)]);
+}
+
+#[test]
+fn test_shell_imports() {
+    let text = "#!/bin/sh\n. ./env.sh\n  source \"$DIR/lib.sh\"; main\necho source x\n";
+    let found: Vec<(String, &str)> = Shell {}
+        .read_imports(text)
+        .into_iter()
+        .map(|x| (x.path, &text[x.start..x.end]))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("./env.sh".to_string(), ". ./env.sh"),
+            ("$DIR/lib.sh".to_string(), "source \"$DIR/lib.sh\""),
+        ]
+    );
+}
+
+#[test]
+fn test_shell_identifiers() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.sh");
+    let text = std::fs::read_to_string(d).unwrap();
+    let idents = Shell {}.read_identifiers(&text);
+    let of = |name: &str| -> Vec<String> {
+        idents
+            .iter()
+            .filter(|x| x.name == name)
+            .map(|x| x.typ.clone())
+            .collect()
+    };
+    assert_eq!(of("session"), vec!["string"; 5]);
+    assert_eq!(of("count"), vec!["integer"; 4]);
+    assert_eq!(of("item"), vec!["string"; 2]);
+    assert_eq!(of("line"), vec!["string"; 2]);
+    assert_eq!(of("HOME"), vec!["env"]);
+    for ident in &idents {
+        assert_eq!(&text[ident.start..ident.end], ident.name);
+    }
+}
+
+#[test]
+fn test_shell_cut_off() {
+    // Escaped characters of more than one byte, and scripts that end
+    // partway through an escape, expansion, or here-doc
+    for text in ["x=\"\\é\"\necho $x\n", "echo \\ü ok", "echo \\", "x=\"a\\", "echo ${", "echo ${#", "echo ${é}", "cat <<'é", "cat <<\"EOF", "a[1]é", "a[", "$((x"] {
+        Shell {}.read_functions(text);
+        Shell {}.read_identifiers(text);
+    }
+    let idents = Shell {}.read_identifiers("x=\"\\é\"\necho \\ü $x ${y}\n");
+    let found: Vec<(&str, &str)> = idents.iter().map(|x| (x.name.as_str(), x.typ.as_str())).collect();
+    assert_eq!(found, vec![("x", "string"), ("x", "string"), ("y", "env")]);
+}
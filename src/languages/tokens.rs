@@ -0,0 +1,520 @@
+//! Provides the token stream the language parsers are built on. Each
+//! language splits its code into tokens with a `Tokenizer` configured for
+//! its syntax, and the analyzers here derive the comment and string map,
+//! matching brackets, and scope frames from those tokens, so that no parser
+//! has to scan characters itself.
+
+use super::lexer::{self, Span, SpanKind};
+use super::parsing::{Call, Diagnostic, Function};
+use super::CommentSyntax;
+use std::collections::HashMap;
+
+/// The kinds of token a Tokenizer produces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    /// A name or keyword
+    Ident,
+    /// A name with a sigil, like a Rust lifetime `'a` or a Ruby `@ivar`
+    Sigil,
+    /// A string literal, including its quotes
+    Str,
+    /// A character literal, including its quotes
+    Char,
+    /// A numeric literal
+    Number,
+    /// An operator or bracket
+    Punct,
+    /// A line or block comment, including its delimiters
+    Comment,
+}
+
+/// A token, given in byte offsets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token {
+    /// What the token is
+    pub kind: TokenKind,
+    /// The byte index the token starts at
+    pub start: usize,
+    /// The byte index one past the end of the token
+    pub end: usize,
+    /// Whether the token is the first on its line
+    pub line_start: bool,
+}
+
+impl Token {
+    /// Creates a new Token given its kind and where it is
+    /// # Arguments
+    ///
+    /// * `kind` - What the token is
+    /// * `start` - The byte index the token starts at
+    /// * `end` - The byte index one past the end of the token
+    /// * `line_start` - Whether the token is the first on its line
+    ///
+    pub fn new(kind: TokenKind, start: usize, end: usize, line_start: bool) -> Self {
+        Self {
+            kind,
+            start,
+            end,
+            line_start,
+        }
+    }
+
+    /// The text of the token within the code it was read from
+    pub fn text<'a>(&self, code: &'a str) -> &'a str {
+        &code[self.start..self.end]
+    }
+}
+
+/// Reads a token particular to one language, like a Rust raw string, given
+/// the code and where the next token starts. Returns the kind of the token
+/// there, or `None` if what is there should be dropped, along with the index
+/// one past its end, or returns `None` to leave the token to the Tokenizer.
+pub type Special = fn(&str, usize) -> Option<(Option<TokenKind>, usize)>;
+
+/// Splits code into tokens, given the parts of a language's syntax that
+/// differ from its neighbours
+#[derive(Clone, Copy)]
+pub struct Tokenizer {
+    /// The comments and quotes of the language
+    pub comments: CommentSyntax,
+    /// Whether block comments nest, as in Rust and Kotlin
+    pub nested: bool,
+    /// The punctuation made of more than one character, like `::` or `->`,
+    /// which are otherwise split into one token per character
+    pub operators: &'static [&'static str],
+    /// The tokens the Tokenizer cannot read on its own, which are tried
+    /// before any other token
+    pub special: Option<Special>,
+}
+
+impl Tokenizer {
+    /// Creates a Tokenizer for a language with the given comments and quotes,
+    /// and no other syntax of its own
+    pub const fn new(comments: CommentSyntax) -> Self {
+        Self {
+            comments,
+            nested: false,
+            operators: &[],
+            special: None,
+        }
+    }
+
+    /// Splits a piece of code into tokens
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    ///
+    /// # Returns
+    ///
+    /// A Vec of every Token in `text`, including comments, in order. Line
+    /// comments stop before the newline that ends them, unterminated block
+    /// comments run to the end of `text`, and unterminated literals stop
+    /// before the end of their line.
+    pub fn tokenize(&self, text: &str) -> Vec<Token> {
+        let mut v = Vec::new();
+        let mut line_start = true;
+        let mut i = 0;
+        while let Some(c) = text[i..].chars().next() {
+            let start = i;
+            let rest = &text[i..];
+            if c.is_whitespace() {
+                line_start |= c == '\n';
+                i += c.len_utf8();
+                continue;
+            }
+            let kind = if let Some((kind, end)) = self.special.and_then(|f| f(text, i)) {
+                i = end.max(i + c.len_utf8());
+                match kind {
+                    Some(kind) => kind,
+                    None => continue,
+                }
+            } else if self.comments.quotes.contains(&c) {
+                i = Self::quoted(text, i, c);
+                if c == '\'' {
+                    TokenKind::Char
+                } else {
+                    TokenKind::Str
+                }
+            } else if self.comments.line.iter().any(|x| rest.starts_with(x)) {
+                i = rest.find('\n').map_or(text.len(), |x| i + x);
+                TokenKind::Comment
+            } else if let Some((open, close)) = self.comments.block.iter().find(|(open, _)| rest.starts_with(open)) {
+                i = self.block_comment(text, i, open, close);
+                TokenKind::Comment
+            } else if lexer::is_ident_start(c) {
+                i += rest.find(|x| !lexer::is_ident_char(x)).unwrap_or(rest.len());
+                TokenKind::Ident
+            } else if c.is_ascii_digit() {
+                let b = text.as_bytes();
+                i += 1;
+                while i < b.len()
+                    && (b[i].is_ascii_alphanumeric()
+                        || b[i] == b'_'
+                        || (b[i] == b'.' && b.get(i + 1).is_some_and(u8::is_ascii_digit)))
+                {
+                    i += 1;
+                }
+                TokenKind::Number
+            } else if let Some(op) = self.operators.iter().find(|x| rest.starts_with(**x)) {
+                i += op.len();
+                TokenKind::Punct
+            } else {
+                i += c.len_utf8();
+                TokenKind::Punct
+            };
+            v.push(Token::new(kind, start, i, line_start));
+            line_start = false;
+        }
+        v
+    }
+
+    /// Splits a piece of code into tokens, leaving out its comments, which
+    /// is what the parsers work on
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code
+    pub fn code(&self, text: &str) -> Vec<Token> {
+        code(self.tokenize(text))
+    }
+
+    /// The index one past the end of the literal quoted with `quote` that
+    /// starts at `i`, within which a `\` escapes the next character
+    fn quoted(text: &str, i: usize, quote: char) -> usize {
+        let mut chars = text[i..].char_indices().skip(1).peekable();
+        let mut end = i + quote.len_utf8();
+        while let Some(&(j, d)) = chars.peek() {
+            if d == '\n' {
+                break;
+            }
+            chars.next();
+            end = i + j + d.len_utf8();
+            if d == quote {
+                break;
+            } else if d == '\\' && matches!(chars.peek(), Some(&(_, e)) if e != '\n') {
+                let (k, e) = chars.next().unwrap();
+                end = i + k + e.len_utf8();
+            }
+        }
+        end
+    }
+
+    /// The index one past the end of the block comment opened with `open`
+    /// at `i`, which is the end of `text` if it is never closed
+    fn block_comment(&self, text: &str, i: usize, open: &str, close: &str) -> usize {
+        // Skip the opener, so that `/*/` is not a whole comment
+        let mut j = i + open.len();
+        let mut depth = 1;
+        while let Some(c) = text[j..].chars().next() {
+            if text[j..].starts_with(close) {
+                j += close.len();
+                depth -= 1;
+                if depth == 0 || !self.nested {
+                    return j;
+                }
+            } else if self.nested && text[j..].starts_with(open) {
+                j += open.len();
+                depth += 1;
+            } else {
+                j += c.len_utf8();
+            }
+        }
+        text.len()
+    }
+}
+
+/// Leaves the comments out of a stream of tokens
+///
+/// # Arguments
+///
+/// * `tokens` - The tokens, as given by `Tokenizer::tokenize`
+pub fn code(tokens: Vec<Token>) -> Vec<Token> {
+    tokens.into_iter().filter(|x| x.kind != TokenKind::Comment).collect()
+}
+
+/// Finds the comments and literals within a stream of tokens
+///
+/// # Arguments
+///
+/// * `tokens` - The tokens, as given by `Tokenizer::tokenize`
+///
+/// # Returns
+///
+/// A Vec of Span covering every comment, string, and character literal,
+/// in order
+pub fn spans(tokens: &[Token]) -> Vec<Span> {
+    tokens
+        .iter()
+        .filter_map(|x| {
+            let kind = match x.kind {
+                TokenKind::Comment => SpanKind::Comment,
+                TokenKind::Str => SpanKind::String,
+                TokenKind::Char => SpanKind::Char,
+                _ => return None,
+            };
+            Some(Span {
+                kind,
+                start: x.start,
+                end: x.end,
+            })
+        })
+        .collect()
+}
+
+/// Finds the bracket that closes the one at token `i`
+///
+/// # Arguments
+///
+/// * `text` - A string slice that contains the code the tokens are from
+/// * `tokens` - The tokens of the code, without comments
+/// * `i` - The index of a `(`, `[`, or `{` token
+///
+/// # Returns
+///
+/// The index of the token that closes it, or of the last token if it is
+/// never closed
+pub fn matching(text: &str, tokens: &[Token], i: usize) -> usize {
+    let open = tokens[i].text(text);
+    let close = match open {
+        "(" => ")",
+        "[" => "]",
+        _ => "}",
+    };
+    let mut depth = 0;
+    for (j, t) in tokens.iter().enumerate().skip(i) {
+        let s = t.text(text);
+        if s == open {
+            depth += 1;
+        } else if s == close {
+            depth -= 1;
+            if depth == 0 {
+                return j;
+            }
+        }
+    }
+    tokens.len() - 1
+}
+
+/// The keywords that are followed by a bracketed condition or value, like
+/// `if (x)`, so are never the names of calls
+const CONTROL: [&str; 12] = ["if", "elif", "while", "until", "unless", "for", "foreach", "switch", "catch", "return", "when", "using"];
+
+/// The keywords that declare a function, whose name is then not a call
+const DECLARERS: [&str; 6] = ["fn", "fun", "func", "function", "def", "sub"];
+
+/// Finds the calls within a stream of tokens, which are the names followed
+/// by a `(` that is closed
+///
+/// # Arguments
+///
+/// * `text` - A string slice that contains the code the tokens are from
+/// * `tokens` - The tokens of the code, without comments
+/// * `functions` - The functions declared in the code, whose names within
+///   their signatures are not calls
+///
+/// # Returns
+///
+/// A Vec of every Call in the code, in order of where they start
+pub fn calls(text: &str, tokens: &[Token], functions: &[Function]) -> Vec<Call> {
+    let mut out = Vec::new();
+    for (i, pair) in tokens.windows(2).enumerate() {
+        let (name, open) = (pair[0].text(text), pair[1].text(text));
+        if pair[0].kind != TokenKind::Ident || open != "(" || CONTROL.contains(&name) || DECLARERS.contains(&name) {
+            continue;
+        }
+        if i > 0 && DECLARERS.contains(&tokens[i - 1].text(text)) {
+            continue;
+        }
+        if functions.iter().any(|f| f.name == name && f.start <= pair[0].start && pair[0].start < f.body) {
+            continue;
+        }
+        let j = matching(text, tokens, i + 1);
+        let close = tokens[j];
+        if close.start > pair[1].start && close.text(text) == ")" {
+            out.push(Call::new(name.to_string(), pair[0].start, pair[1].start, close.end, arguments(text, &tokens[i + 2..j])));
+        }
+    }
+    out
+}
+
+/// Splits the tokens between the brackets of a call at the commas that are
+/// not within brackets of their own, leaving out empty arguments, like the
+/// one after a trailing comma
+///
+/// # Returns
+///
+/// The start and end of each argument
+fn arguments(text: &str, tokens: &[Token]) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut arg: Option<(usize, usize)> = None;
+    for t in tokens {
+        match t.text(text) {
+            "," if depth == 0 => {
+                out.extend(arg.take());
+                continue;
+            }
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth -= 1,
+            _ => {}
+        }
+        arg = Some((arg.map_or(t.start, |x| x.0), t.end));
+    }
+    out.extend(arg);
+    out
+}
+
+/// Finds where the `{ }` blocks within a stream of tokens do not balance,
+/// which are the `}`s that close no block and the `{`s never closed
+///
+/// # Arguments
+///
+/// * `text` - A string slice that contains the code the tokens are from
+/// * `tokens` - The tokens of the code, without comments
+///
+/// # Returns
+///
+/// A Vec of Diagnostic, in order of where they start, with no lines or
+/// columns filled in
+pub fn balance(text: &str, tokens: &[Token]) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let mut open = Vec::new();
+    for t in tokens.iter().filter(|x| x.kind == TokenKind::Punct) {
+        match t.text(text) {
+            "{" => open.push(t.start),
+            "}" if open.pop().is_none() => out.push(Diagnostic::new("`}` closes no block".to_string(), t.start)),
+            _ => {}
+        }
+    }
+    out.extend(open.into_iter().map(|x| Diagnostic::new("`{` is never closed".to_string(), x)));
+    out.sort_by_key(|x| x.start);
+    out
+}
+
+/// The nested scopes of a piece of code, each of which maps the names
+/// declared in it to their types
+#[derive(Debug)]
+pub struct Scopes {
+    frames: Vec<HashMap<String, String>>,
+}
+
+impl Scopes {
+    /// Creates a Scopes holding only the outermost scope
+    pub fn new() -> Self {
+        Self {
+            frames: vec![HashMap::new()],
+        }
+    }
+
+    /// Enters a scope
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The names already declared in the scope, like parameters
+    pub fn push(&mut self, frame: HashMap<String, String>) {
+        self.frames.push(frame);
+    }
+
+    /// Leaves the innermost scope, unless it is the outermost one
+    ///
+    /// # Returns
+    ///
+    /// The names declared in the scope that was left, if any was
+    pub fn pop(&mut self) -> Option<HashMap<String, String>> {
+        if self.frames.len() > 1 {
+            self.frames.pop()
+        } else {
+            None
+        }
+    }
+
+    /// The number of scopes entered, counting the outermost one
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Declares a name in the innermost scope
+    pub fn declare(&mut self, name: String, typ: String) {
+        self.frames.last_mut().unwrap().insert(name, typ);
+    }
+
+    /// Finds the type of a name in the innermost scope that declares it
+    pub fn lookup(&self, name: &str) -> Option<&String> {
+        self.frames.iter().rev().find_map(|x| x.get(name))
+    }
+}
+
+impl Default for Scopes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_tokenize() {
+    let text = "let x = a::b(\"s\\\"\", 'c'); /* a /* b */ x */ 1.5 // done\n$y";
+    let tokenizer = Tokenizer {
+        operators: &["::"],
+        nested: true,
+        ..Tokenizer::new(CommentSyntax::C)
+    };
+    let tokens = tokenizer.tokenize(text);
+    let found: Vec<(TokenKind, &str)> = tokens.iter().map(|x| (x.kind, x.text(text))).collect();
+    assert_eq!(
+        found,
+        vec![
+            (TokenKind::Ident, "let"),
+            (TokenKind::Ident, "x"),
+            (TokenKind::Punct, "="),
+            (TokenKind::Ident, "a"),
+            (TokenKind::Punct, "::"),
+            (TokenKind::Ident, "b"),
+            (TokenKind::Punct, "("),
+            (TokenKind::Str, "\"s\\\"\""),
+            (TokenKind::Punct, ","),
+            (TokenKind::Char, "'c'"),
+            (TokenKind::Punct, ")"),
+            (TokenKind::Punct, ";"),
+            (TokenKind::Comment, "/* a /* b */ x */"),
+            (TokenKind::Number, "1.5"),
+            (TokenKind::Comment, "// done"),
+            (TokenKind::Ident, "$y"),
+        ]
+    );
+    assert!(tokens[0].line_start && !tokens[1].line_start && tokens[15].line_start);
+    let code = code(tokens);
+    assert_eq!(matching(text, &code, 6), 10);
+    assert_eq!(spans(&code).len(), 2);
+}
+
+#[test]
+fn test_calls() {
+    let text = "fn load(a: u8) { if (a) { load (f(a, [b, c]),  (1) ,); } g(; }";
+    let tokens = Tokenizer::new(CommentSyntax::C).code(text);
+    let functions = vec![Function::new("load".into(), String::new(), Vec::new(), 0, 15, text.len())];
+    let found: Vec<(String, Vec<&str>)> = calls(text, &tokens, &functions).into_iter().map(|x| (x.name, x.args.iter().map(|(a, b)| &text[*a..*b]).collect())).collect();
+    // `g` is never closed, as its `(` is the last
+    assert_eq!(found, vec![("load".to_string(), vec!["f(a, [b, c])", "(1)"]), ("f".to_string(), vec!["a", "[b, c]"])]);
+}
+
+#[test]
+fn test_balance() {
+    let text = "} f() { if (x) { \"}\" }";
+    let found: Vec<(String, usize)> = balance(text, &Tokenizer::new(CommentSyntax::C).code(text)).into_iter().map(|x| (x.message, x.start)).collect();
+    assert_eq!(found, vec![("`}` closes no block".to_string(), 0), ("`{` is never closed".to_string(), 6)]);
+    assert!(balance("{ { } }", &Tokenizer::new(CommentSyntax::C).code("{ { } }")).is_empty());
+}
+
+#[test]
+fn test_scopes() {
+    let mut scopes = Scopes::new();
+    scopes.declare("me".into(), "Session".into());
+    scopes.push(HashMap::new());
+    scopes.declare("me".into(), "int".into());
+    assert_eq!(scopes.lookup("me").map(String::as_str), Some("int"));
+    assert_eq!(scopes.pop().map(|x| x.len()), Some(1));
+    assert_eq!(scopes.lookup("me").map(String::as_str), Some("Session"));
+    assert!(scopes.pop().is_none());
+    assert_eq!(scopes.depth(), 1);
+}
@@ -0,0 +1,848 @@
+//! Provides parsers backed by real tree-sitter grammars, for C, C++, Java,
+//! and Python. These are only built with the `tree-sitter` feature, and take
+//! priority over the hand-rolled parsers for the files they cover.
+
+use super::parsing::{import_aliases, Aliases, Class, Classes, Function, Functions, Identifier, Identifiers, Import, Imports, Parameter};
+use super::clike::Clike;
+use super::{lexer, CommentSyntax, Language};
+use std::collections::HashMap;
+use tree_sitter::{Node, Parser, Tree};
+
+/// A Functions and Identifiers parser that walks the syntax tree produced
+/// by a tree-sitter grammar. Which nodes declare functions, open scopes,
+/// and declare typed identifiers is described per grammar, so a single
+/// walker serves every language.
+///
+/// Declarations are nodes with a `type` field, and the names they declare
+/// are found through their `declarator`, `name`, or `left` fields. Every
+/// later use of a declared name within the same scope is reported with the
+/// declared type, as `Clike` does.
+pub struct TreeSitter {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    interpreters: &'static [&'static str],
+    comments: CommentSyntax,
+    grammar: fn() -> tree_sitter::Language,
+    /// Node kinds that define a named function
+    functions: &'static [&'static str],
+    /// Node kinds that define a class or other type with members
+    classes: &'static [&'static str],
+    /// Node kinds that import or include other modules
+    imports: &'static [&'static str],
+    /// Whether the language is run through the C preprocessor, and so
+    /// has macros and `#if 0` regions
+    preprocessor: bool,
+    /// Node kinds that open a new scope for declarations
+    scopes: &'static [&'static str],
+    /// Node kinds that declare typed identifiers
+    declarations: &'static [&'static str],
+}
+
+/// Node kinds that hold the name of an identifier
+const NAMES: &[&str] = &["identifier", "field_identifier"];
+
+impl TreeSitter {
+    /// Creates a parser for C
+    pub fn c() -> Self {
+        Self {
+            name: "c",
+            extensions: &["c", "h"],
+            interpreters: &[],
+            comments: CommentSyntax::C,
+            grammar: tree_sitter_c::language,
+            functions: &["function_definition"],
+            classes: &["struct_specifier", "union_specifier", "enum_specifier"],
+            imports: &["preproc_include"],
+            preprocessor: true,
+            scopes: &["function_definition", "compound_statement", "for_statement"],
+            declarations: &["declaration", "field_declaration", "parameter_declaration"],
+        }
+    }
+
+    /// Creates a parser for C++
+    pub fn cpp() -> Self {
+        Self {
+            name: "cpp",
+            extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+            interpreters: &[],
+            comments: CommentSyntax::C,
+            grammar: tree_sitter_cpp::language,
+            functions: &["function_definition"],
+            classes: &["class_specifier", "struct_specifier", "union_specifier", "enum_specifier"],
+            imports: &["preproc_include", "using_declaration"],
+            preprocessor: true,
+            scopes: &[
+                "function_definition",
+                "compound_statement",
+                "for_statement",
+                "for_range_loop",
+                "field_declaration_list",
+            ],
+            declarations: &[
+                "declaration",
+                "field_declaration",
+                "parameter_declaration",
+                "optional_parameter_declaration",
+                "for_range_loop",
+            ],
+        }
+    }
+
+    /// Creates a parser for Java
+    pub fn java() -> Self {
+        Self {
+            name: "java",
+            extensions: &["java"],
+            interpreters: &[],
+            comments: CommentSyntax::C,
+            grammar: tree_sitter_java::language,
+            functions: &["method_declaration", "constructor_declaration"],
+            classes: &[
+                "class_declaration",
+                "interface_declaration",
+                "enum_declaration",
+                "record_declaration",
+            ],
+            imports: &["import_declaration"],
+            preprocessor: false,
+            scopes: &[
+                "class_body",
+                "interface_body",
+                "method_declaration",
+                "constructor_declaration",
+                "block",
+                "for_statement",
+                "enhanced_for_statement",
+                "catch_clause",
+                "lambda_expression",
+            ],
+            declarations: &[
+                "local_variable_declaration",
+                "field_declaration",
+                "formal_parameter",
+                "catch_formal_parameter",
+                "enhanced_for_statement",
+                "resource",
+            ],
+        }
+    }
+
+    /// Creates a parser for Python, where only annotated parameters and
+    /// assignments have types
+    pub fn python() -> Self {
+        Self {
+            name: "python",
+            extensions: &["py", "pyi"],
+            interpreters: &["python"],
+            comments: CommentSyntax::HASH,
+            grammar: tree_sitter_python::language,
+            functions: &["function_definition"],
+            classes: &["class_definition"],
+            imports: &["import_statement", "import_from_statement"],
+            preprocessor: false,
+            scopes: &["function_definition", "class_definition", "lambda"],
+            declarations: &["typed_parameter", "typed_default_parameter", "assignment"],
+        }
+    }
+
+    /// Finds every definition and use of a preprocessor macro, which are
+    /// the names given to `#define`, `#ifdef`, `#ifndef`, and `defined`
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier of type `macro`, in order, which is empty for
+    /// languages without a preprocessor
+    fn read_macros(&self, text: &str, tree: &Tree) -> Vec<Identifier> {
+        let mut v = Vec::new();
+        if !self.preprocessor {
+            return v;
+        }
+        let slice = |x: Node| &text[x.start_byte()..x.end_byte()];
+        let mut names = Vec::new();
+        let mut nodes = Vec::new();
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.named_children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev());
+            match node.kind() {
+                "preproc_def" | "preproc_function_def" | "preproc_ifdef" => {
+                    names.extend(node.child_by_field_name("name").map(slice));
+                }
+                "preproc_defined" => {
+                    let mut cursor = node.walk();
+                    let name = node.named_children(&mut cursor).find(|x| x.kind() == "identifier");
+                    names.extend(name.map(slice));
+                }
+                "identifier" | "type_identifier" => nodes.push(node),
+                _ => {}
+            }
+        }
+        for node in nodes.into_iter().filter(|x| names.contains(&slice(*x))) {
+            v.push(Identifier::new(
+                slice(node).to_string(),
+                "macro".to_string(),
+                node.start_byte(),
+                node.end_byte(),
+            ));
+        }
+        v
+    }
+
+    /// Parses `text` with this parser's grammar
+    fn parse(&self, text: &str) -> Option<Tree> {
+        let mut parser = Parser::new();
+        parser.set_language((self.grammar)()).ok()?;
+        parser.parse(text, None)
+    }
+
+    /// Follows the `declarator` and `name` fields of `node` down to the
+    /// node holding the name it declares
+    fn declared_name(node: Node) -> Option<Node> {
+        if NAMES.contains(&node.kind()) {
+            return Some(node);
+        }
+        if let Some(x) = node
+            .child_by_field_name("declarator")
+            .or_else(|| node.child_by_field_name("name"))
+        {
+            return Self::declared_name(x);
+        }
+        let mut cursor = node.walk();
+        let name = node
+            .named_children(&mut cursor)
+            .find(|x| NAMES.contains(&x.kind()));
+        name
+    }
+
+    /// Whether `node` names a member of something else, like `x` in `p.x`
+    /// or `f` in `p.f()`, rather than being a use of a variable
+    fn is_member(node: Node) -> bool {
+        match node.parent() {
+            Some(parent) => ["field", "name"]
+                .iter()
+                .any(|x| parent.child_by_field_name(x) == Some(node)),
+            None => false,
+        }
+    }
+
+    /// Follows the `declarator` fields of the function `node` down to its
+    /// parameter list
+    fn parameters(node: Node) -> Option<Node> {
+        match node.child_by_field_name("parameters") {
+            Some(x) => Some(x),
+            None => Self::parameters(node.child_by_field_name("declarator")?),
+        }
+    }
+
+    /// Reads the parameters of the function `node`, where parameters with
+    /// no `type` field, like Python's untyped ones, have an empty type
+    fn read_parameters(text: &str, node: Node) -> Vec<Parameter> {
+        let mut v = Vec::new();
+        let params = match Self::parameters(node) {
+            Some(x) => x,
+            None => return v,
+        };
+        let slice = |x: Node| text[x.start_byte()..x.end_byte()].to_string();
+        let mut cursor = params.walk();
+        for param in params.named_children(&mut cursor) {
+            let typ = param.child_by_field_name("type").map(slice).unwrap_or_default();
+            for name in Self::declared_names(param) {
+                v.push(Parameter::new(slice(name), typ.clone()));
+            }
+        }
+        v
+    }
+
+    /// Reads the types named by the supertype clauses of the class `node`,
+    /// like Java's `extends` and `implements`, C++ base classes, and
+    /// Python's base classes
+    fn read_supertypes(text: &str, node: Node) -> Vec<String> {
+        const CLAUSES: &[&str] = &[
+            "superclass",
+            "super_interfaces",
+            "extends_interfaces",
+            "type_list",
+            "base_class_clause",
+            "argument_list",
+        ];
+        let mut v = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            if CLAUSES.contains(&child.kind()) {
+                let mut inner = child.walk();
+                for x in child.named_children(&mut inner) {
+                    if CLAUSES.contains(&x.kind()) {
+                        v.extend(Self::read_supertypes(text, child));
+                        break;
+                    } else if !["access_specifier", "keyword_argument", "comment"].contains(&x.kind()) {
+                        v.push(text[x.start_byte()..x.end_byte()].to_string());
+                    }
+                }
+            }
+        }
+        v
+    }
+
+    /// Reads the fields declared directly within the class body `body`
+    fn read_fields(&self, text: &str, body: Node) -> Vec<Identifier> {
+        let mut fields = Vec::new();
+        let mut cursor = body.walk();
+        for member in body.named_children(&mut cursor) {
+            // Python's class attributes are assignments in statements
+            let member = match member.kind() {
+                "expression_statement" => match member.named_child(0) {
+                    Some(x) => x,
+                    None => continue,
+                },
+                _ => member,
+            };
+            if !self.declarations.contains(&member.kind()) {
+                continue;
+            }
+            if let Some(typ) = member.child_by_field_name("type") {
+                let typ = &text[typ.start_byte()..typ.end_byte()];
+                for x in Self::declared_names(member) {
+                    let (start, end) = (x.start_byte(), x.end_byte());
+                    let name = text[x.start_byte()..x.end_byte()].to_string();
+                    fields.push(Identifier::new(name, typ.to_string(), start, end));
+                }
+            }
+        }
+        fields
+    }
+
+    /// Finds every name declared by the declaration `node`
+    fn declared_names(node: Node) -> Vec<Node> {
+        let mut names = Vec::new();
+        let mut cursor = node.walk();
+        for field in &["declarator", "name", "left"] {
+            for x in node.children_by_field_name(field, &mut cursor) {
+                names.extend(Self::declared_name(x));
+            }
+        }
+        if names.is_empty() {
+            names.extend(Self::declared_name(node));
+        }
+        names
+    }
+}
+
+/// Reads the paths an import node brings in, along with their aliases
+///
+/// # Arguments
+///
+/// * `text` - The source code the node was parsed from
+/// * `node` - A node of one of the kinds in a parser's `imports`
+///
+/// # Returns
+///
+/// A Vec of each imported path and the name it is bound to, if renamed.
+/// Python's `from a import b` imports `a.b`.
+fn read_import(text: &str, node: Node) -> Vec<(String, Option<String>)> {
+    let source = |x: Node| -> String { text[x.start_byte()..x.end_byte()].chars().filter(|c| !c.is_whitespace()).collect() };
+    let mut cursor = node.walk();
+    match node.kind() {
+        "preproc_include" => node
+            .child_by_field_name("path")
+            .filter(|x| x.kind() == "string_literal" || x.kind() == "system_lib_string")
+            .map(|x| text[x.start_byte() + 1..x.end_byte() - 1].to_string())
+            .map(|x| vec![(x, None)])
+            .unwrap_or_default(),
+        "import_statement" | "import_from_statement" => {
+            let module = node.child_by_field_name("module_name").map(source).map(|x| if x.ends_with('.') { x } else { x + "." });
+            let module = module.unwrap_or_default();
+            let mut names: Vec<(String, Option<String>)> = node
+                .children_by_field_name("name", &mut cursor)
+                .map(|x| match (x.child_by_field_name("name"), x.child_by_field_name("alias")) {
+                    (Some(name), alias) => (module.clone() + &source(name), alias.map(source)),
+                    _ => (module.clone() + &source(x), None),
+                })
+                .collect();
+            let mut cursor = node.walk();
+            if node.named_children(&mut cursor).any(|x| x.kind() == "wildcard_import") {
+                names.push((module + "*", None));
+            }
+            names
+        }
+        // `import java.util.*;` and C++'s `using std::vector;`
+        _ => {
+            let path: Vec<String> = node.named_children(&mut cursor).filter(|x| x.kind() != "comment").map(source).collect();
+            vec![(path.join("."), None)]
+        }
+    }
+}
+
+struct Walker<'a> {
+    lang: &'a TreeSitter,
+    text: &'a str,
+    frames: Vec<HashMap<String, String>>,
+    /// The byte offsets of names at the point they are declared
+    declared: HashMap<usize, String>,
+    identifiers: Vec<Identifier>,
+}
+
+impl<'a> Walker<'a> {
+    fn text(&self, node: Node) -> &'a str {
+        &self.text[node.start_byte()..node.end_byte()]
+    }
+
+    fn walk(&mut self, node: Node) {
+        let scoped = self.lang.scopes.contains(&node.kind());
+        if scoped {
+            // The fields of a class are in scope throughout its body, even
+            // in methods declared before them
+            let class_body = node
+                .parent()
+                .filter(|x| self.lang.classes.contains(&x.kind()))
+                .and_then(|x| x.child_by_field_name("body"))
+                == Some(node);
+            let mut frame = HashMap::new();
+            if class_body {
+                for field in self.lang.read_fields(self.text, node) {
+                    frame.insert(field.name, field.typ);
+                }
+            }
+            self.frames.push(frame);
+        }
+        if self.lang.declarations.contains(&node.kind()) {
+            if let Some(typ) = node.child_by_field_name("type") {
+                let typ = self.text(typ).to_string();
+                for name in TreeSitter::declared_names(node) {
+                    self.declared.insert(name.start_byte(), typ.clone());
+                }
+            }
+        }
+        if NAMES.contains(&node.kind()) {
+            let name = self.text(node).to_string();
+            let typ = match self.declared.remove(&node.start_byte()) {
+                Some(typ) => {
+                    self.frames.last_mut().unwrap().insert(name.clone(), typ.clone());
+                    Some(typ)
+                }
+                None if node.kind() == "identifier" && !TreeSitter::is_member(node) => {
+                    self.frames.iter().rev().find_map(|x| x.get(&name)).cloned()
+                }
+                None => None,
+            };
+            if let Some(typ) = typ {
+                let (start, end) = (node.start_byte(), node.end_byte());
+                self.identifiers.push(Identifier::new(name, typ, start, end));
+            }
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        for child in children {
+            self.walk(child);
+        }
+        if scoped {
+            self.frames.pop();
+        }
+    }
+}
+
+impl Functions for TreeSitter {
+    /// Parses out function definitions using the tree-sitter grammar
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Function containing every function defined within text,
+    /// in the order they appear
+    fn read_functions(&self, text: &str) -> Vec<Function> {
+        let mut v = Vec::new();
+        let tree = match self.parse(text) {
+            Some(tree) => tree,
+            None => return v,
+        };
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            if self.functions.contains(&node.kind()) {
+                if let Some(name) = Self::declared_name(node) {
+                    let typ = node
+                        .child_by_field_name("type")
+                        .or_else(|| node.child_by_field_name("return_type"))
+                        .map_or("", |x| &text[x.start_byte()..x.end_byte()]);
+                    let body = node.child_by_field_name("body").map_or(node.end_byte(), |x| x.start_byte());
+                    v.push(Function::new(
+                        text[name.start_byte()..name.end_byte()].to_string(),
+                        typ.to_string(),
+                        Self::read_parameters(text, node),
+                        node.start_byte(),
+                        body,
+                        node.end_byte(),
+                    ));
+                }
+            }
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.named_children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev());
+        }
+        v
+    }
+}
+
+impl Identifiers for TreeSitter {
+    /// Parses out identifier uses using the tree-sitter grammar
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier containing information on every declaration
+    /// and use of a typed identifier within the code. In C and C++, every
+    /// definition and use of a preprocessor macro has the type `macro`.
+    fn read_identifiers(&self, text: &str) -> Vec<Identifier> {
+        let tree = match self.parse(text) {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        let mut walker = Walker {
+            lang: self,
+            text,
+            frames: vec![HashMap::new()],
+            declared: HashMap::new(),
+            identifiers: Vec::new(),
+        };
+        walker.walk(tree.root_node());
+        let mut v = walker.identifiers;
+        let macros = self.read_macros(text, &tree);
+        if !macros.is_empty() {
+            v.extend(macros);
+            v.sort_by_key(|x| x.start);
+        }
+        v
+    }
+}
+
+impl Classes for TreeSitter {
+    /// Parses out class and other type definitions using the tree-sitter
+    /// grammar
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Class containing every type defined with a body within
+    /// text, along with the fields declared directly in that body
+    fn read_classes(&self, text: &str) -> Vec<Class> {
+        let mut v = Vec::new();
+        let tree = match self.parse(text) {
+            Some(tree) => tree,
+            None => return v,
+        };
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.named_children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev());
+            if !self.classes.contains(&node.kind()) {
+                continue;
+            }
+            let (name, body) = match (node.child_by_field_name("name"), node.child_by_field_name("body")) {
+                (Some(name), Some(body)) => (name, body),
+                _ => continue,
+            };
+            let fields = self.read_fields(text, body);
+            // The kind is the keyword in the node kind, like `class` in
+            // `class_declaration`
+            let kind = node.kind().split('_').next().unwrap_or("").to_string();
+            v.push(Class::new(
+                text[name.start_byte()..name.end_byte()].to_string(),
+                kind,
+                Self::read_supertypes(text, node),
+                fields,
+                node.start_byte(),
+                body.start_byte(),
+                node.end_byte(),
+            ));
+        }
+        v
+    }
+}
+
+impl Imports for TreeSitter {
+    /// Parses out imports and includes using the tree-sitter grammar
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Import with one entry for each name every import statement
+    /// within text brings in, each spanning the whole statement
+    fn read_imports(&self, text: &str) -> Vec<Import> {
+        let mut v = Vec::new();
+        let tree = match self.parse(text) {
+            Some(tree) => tree,
+            None => return v,
+        };
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            if !self.imports.contains(&node.kind()) {
+                let mut cursor = node.walk();
+                let children: Vec<Node> = node.named_children(&mut cursor).collect();
+                stack.extend(children.into_iter().rev());
+                continue;
+            }
+            for (path, alias) in read_import(text, node) {
+                v.push(Import::new(path, alias, node.start_byte(), node.end_byte()));
+            }
+        }
+        v
+    }
+}
+
+impl Aliases for TreeSitter {
+    /// Parses out `typedef`s, C++ `using X = Y;` aliases, and the names
+    /// brought in by imports using the tree-sitter grammar
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the code to be parsed
+    ///
+    /// # Returns
+    ///
+    /// A Vec of Identifier for each alias within text, whose type is the
+    /// type it stands for. As with declarations, pointers and arrays are not
+    /// part of the type, and typedefs of anonymous structs and function
+    /// pointers are left out.
+    fn read_aliases(&self, text: &str) -> Vec<Identifier> {
+        let mut v = Vec::new();
+        let tree = match self.parse(text) {
+            Some(tree) => tree,
+            None => return v,
+        };
+        let slice = |x: Node| text[x.start_byte()..x.end_byte()].split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut includes = Vec::new();
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.named_children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev());
+            let (typ, names) = match node.kind() {
+                "preproc_include" => {
+                    includes.push(node.start_byte());
+                    continue;
+                }
+                "alias_declaration" => match (node.child_by_field_name("type"), node.child_by_field_name("name")) {
+                    (Some(typ), Some(name)) => (slice(typ), vec![name]),
+                    _ => continue,
+                },
+                "type_definition" => {
+                    let typ = match node.child_by_field_name("type") {
+                        // `typedef struct Node { ... } Node;` aliases `struct Node`
+                        Some(typ) if typ.child_by_field_name("body").is_some() => match typ.child_by_field_name("name") {
+                            Some(name) => format!("{} {}", typ.kind().split('_').next().unwrap_or(""), slice(name)),
+                            None => continue,
+                        },
+                        Some(typ) => slice(typ),
+                        None => continue,
+                    };
+                    let mut cursor = node.walk();
+                    let names = node
+                        .children_by_field_name("declarator", &mut cursor)
+                        .filter_map(|mut x| {
+                            while x.kind() != "type_identifier" && x.kind() != "function_declarator" {
+                                x = x.child_by_field_name("declarator")?;
+                            }
+                            Some(x).filter(|x| x.kind() == "type_identifier")
+                        })
+                        .collect();
+                    (typ, names)
+                }
+                _ => continue,
+            };
+            for name in names {
+                v.push(Identifier::new(slice(name), typ.clone(), name.start_byte(), name.end_byte()));
+            }
+        }
+        // `#include`s name files rather than types
+        let imports = self.read_imports(text).into_iter().filter(|x| !includes.contains(&x.start));
+        v.extend(import_aliases(imports.collect()));
+        v
+    }
+}
+
+impl Language for TreeSitter {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        self.extensions
+    }
+
+    fn interpreters(&self) -> &'static [&'static str] {
+        self.interpreters
+    }
+
+    fn comments(&self) -> CommentSyntax {
+        self.comments
+    }
+
+    fn spans(&self, text: &str) -> Vec<lexer::Span> {
+        let mut v = lexer::lex(text, self.comments);
+        if self.preprocessor {
+            v.extend(Clike::disabled(text));
+            v.sort_by_key(|x| x.start);
+        }
+        v
+    }
+}
+
+#[test]
+fn test_tree_sitter_functions() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/functions.java");
+    let text = std::fs::read_to_string(d).unwrap();
+    let names: Vec<String> = TreeSitter::java().read_functions(&text).into_iter().map(|x| x.name).collect();
+    assert_eq!(
+        names,
+        vec!["LightningOvercharge", "getAction", "onSpawn", "getPassiveAction", "getCost", "getName", "getTip", "getActionNetwork"]
+    );
+    let c = "static int *twice(int x) { return x * 2; }\nint Foo::bar() const { return 0; }\n";
+    let names: Vec<String> = TreeSitter::cpp().read_functions(c).into_iter().map(|x| x.name).collect();
+    assert_eq!(names, vec!["twice", "bar"]);
+    let py = "def greet(name: str) -> None:\n    def inner():\n        pass\n";
+    let names: Vec<String> = TreeSitter::python().read_functions(py).into_iter().map(|x| x.name).collect();
+    assert_eq!(names, vec!["greet", "inner"]);
+    let functions = TreeSitter::python().read_functions(py);
+    assert_eq!(functions[0].typ, "None");
+    assert_eq!(functions[0].params, vec![Parameter::new("name".into(), "str".into())]);
+    assert_eq!((functions[1].start, functions[1].body, functions[1].end), (34, 55, 59));
+    let f = &TreeSitter::cpp().read_functions(c)[0];
+    assert_eq!((f.typ.as_str(), f.params.clone()), ("int", vec![Parameter::new("x".into(), "int".into())]));
+}
+
+#[test]
+fn test_tree_sitter_identifiers() {
+    let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("resources/test/identifiers.java");
+    let text = std::fs::read_to_string(d).unwrap();
+    let found: Vec<(String, String)> = TreeSitter::java()
+        .read_identifiers(&text)
+        .into_iter()
+        .map(|x| (x.name, x.typ))
+        .collect();
+    let expected = vec![
+        ("charge", "int"),
+        ("charge", "int"),
+        ("number", "double"),
+        ("me", "Session"),
+        ("number", "double"),
+        ("me", "Session"),
+    ];
+    let expected: Vec<(String, String)> = expected.into_iter().map(|(x, y)| (x.into(), y.into())).collect();
+    assert_eq!(found, expected);
+
+    let c = "int main(void) {\n    char *s, t[4];\n    // s is not a use\n    return s[0] + t[1];\n}\n";
+    let found: Vec<(String, usize)> = TreeSitter::c().read_identifiers(c).into_iter().map(|x| (x.typ, x.start)).collect();
+    assert_eq!(found, vec![("char".into(), 27), ("char".into(), 30), ("char".into(), 69), ("char".into(), 76)]);
+}
+
+#[test]
+fn test_tree_sitter_classes() {
+    let java = "class Spell extends Base implements Castable, Named {\n    private int charge, cost;\n    void cast() { int local; }\n}\n";
+    let classes = TreeSitter::java().read_classes(java);
+    assert_eq!((classes[0].name.as_str(), classes[0].kind.as_str()), ("Spell", "class"));
+    assert_eq!(classes[0].supertypes, vec!["Base", "Castable", "Named"]);
+    let fields: Vec<(&str, &str)> = classes[0].fields.iter().map(|x| (x.name.as_str(), x.typ.as_str())).collect();
+    assert_eq!(fields, vec![("charge", "int"), ("cost", "int")]);
+    let cpp = "struct Node : public Base, private Other {\n    Node *next;\n};\n";
+    let classes = TreeSitter::cpp().read_classes(cpp);
+    assert_eq!(classes[0].supertypes, vec!["Base", "Other"]);
+    assert_eq!((classes[0].fields[0].name.as_str(), classes[0].fields[0].typ.as_str()), ("next", "Node"));
+    let py = "class Spell(Base, metaclass=Meta):\n    charge: int = 0\n";
+    let classes = TreeSitter::python().read_classes(py);
+    assert_eq!(classes[0].supertypes, vec!["Base"]);
+    assert_eq!((classes[0].fields[0].name.as_str(), classes[0].fields[0].typ.as_str()), ("charge", "int"));
+}
+
+#[test]
+fn test_tree_sitter_imports() {
+    let found = |lang: TreeSitter, text: &str| -> Vec<(String, Option<String>)> {
+        lang.read_imports(text).into_iter().map(|x| (x.path, x.alias)).collect()
+    };
+    let java = "import java.util.*;\nimport static java.lang.Math.max;\nclass Spell {}\n";
+    assert_eq!(found(TreeSitter::java(), java), vec![("java.util.*".into(), None), ("java.lang.Math.max".into(), None)]);
+    let cpp = "#include <vector>\n#include \"spell.h\"\nusing std::string;\n";
+    assert_eq!(
+        found(TreeSitter::cpp(), cpp),
+        vec![("vector".into(), None), ("spell.h".into(), None), ("std::string".into(), None)]
+    );
+    let py = "import os, numpy as np\nfrom .spells import Lightning as Bolt, cast\nfrom game import *\n";
+    assert_eq!(
+        found(TreeSitter::python(), py),
+        vec![
+            ("os".into(), None),
+            ("numpy".into(), Some("np".into())),
+            (".spells.Lightning".into(), Some("Bolt".into())),
+            (".spells.cast".into(), None),
+            ("game.*".into(), None),
+        ]
+    );
+    let import = &TreeSitter::python().read_imports(py)[0];
+    assert_eq!((import.start, import.end), (0, 22));
+}
+
+#[test]
+fn test_tree_sitter_aliases() {
+    let found = |lang: TreeSitter, text: &str| -> Vec<(String, String)> {
+        lang.read_aliases(text).into_iter().map(|x| (x.name, x.typ)).collect()
+    };
+    let c = "#include <list.h>\ntypedef unsigned long u64;\ntypedef struct Node { int v; } Node, *NodePtr;\ntypedef struct { int x; } Point;\ntypedef int (*callback)(int);\n";
+    assert_eq!(
+        found(TreeSitter::c(), c),
+        vec![("u64".into(), "unsigned long".into()), ("Node".into(), "struct Node".into()), ("NodePtr".into(), "struct Node".into())]
+    );
+    let cpp = "using Names = std::vector<std::string>;\nusing std::string;\n";
+    assert_eq!(
+        found(TreeSitter::cpp(), cpp),
+        vec![("Names".into(), "std::vector<std::string>".into()), ("string".into(), "std::string".into())]
+    );
+    let py = "import numpy as np\nfrom game.spells import Lightning\n";
+    assert_eq!(
+        found(TreeSitter::python(), py),
+        vec![("np".into(), "numpy".into()), ("Lightning".into(), "game.spells.Lightning".into())]
+    );
+}
+
+#[test]
+fn test_tree_sitter_macros() {
+    let c = "#define LIMIT 4\n#ifdef DEBUG\nint x = LIMIT;\n#endif\n";
+    let found: Vec<(String, String, usize)> = TreeSitter::c()
+        .read_identifiers(c)
+        .into_iter()
+        .map(|x| (x.name, x.typ, x.start))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("LIMIT".into(), "macro".into(), 8),
+            ("DEBUG".into(), "macro".into(), 23),
+            ("x".into(), "int".into(), 33),
+            ("LIMIT".into(), "macro".into(), 37),
+        ]
+    );
+    assert!(TreeSitter::java().read_identifiers("class A { int LIMIT; }").iter().all(|x| x.typ != "macro"));
+}
+
+#[test]
+fn test_tree_sitter_members() {
+    let java = "class Spell {\n    void cast() { me.go(); }\n    private Session me;\n}\n";
+    let found: Vec<(String, String, usize)> = TreeSitter::java()
+        .read_identifiers(java)
+        .into_iter()
+        .map(|x| (x.name, x.typ, x.start))
+        .collect();
+    assert_eq!(found, vec![("me".into(), "Session".into(), 32), ("me".into(), "Session".into(), 63)]);
+}
@@ -0,0 +1,187 @@
+//! Semantic find and replace for code, usable from other tools as well as
+//! from the `spidior` binary, which is a thin command line over this crate.
+//!
+//! The simplest entry point is the [`Spidior`] facade, which runs a query
+//! over a piece of code:
+//!
+//! ```
+//! use spidior::Spidior;
+//!
+//! let code = "int count = 0;\ncount++;\n";
+//! let out = Spidior::replace_in(code, "%s/[[name=count]]/total/g", Some("clike")).unwrap();
+//! assert_eq!(out, "int total = 0;\ntotal++;\n");
+//! ```
+//!
+//! To run a query over files on disk, [`Spidior::search`] and
+//! [`Spidior::replace`] start a builder:
+//!
+//! ```no_run
+//! use spidior::{Lang, Spidior};
+//!
+//! let changed = Spidior::replace("%s/[[type=Session]]/sess/g").lang(Lang::Java).paths(["src"]).dry_run(true).run()?;
+//! for file in changed {
+//!     println!("{}: {} edits", file.path.display(), file.edits.len());
+//! }
+//! # Ok::<(), spidior::SpidiorError>(())
+//! ```
+//!
+//! Everything the binary uses is public beneath it: [`regexparser`] parses
+//! queries, [`nfa::matcher`] and [`nfa::replacer`] run them, [`languages`]
+//! parses the code they query, and [`editing`] applies the edits they make.
+
+use std::path::Path;
+
+#[macro_use]
+extern crate lalrpop_util;
+
+pub mod analysis;
+#[cfg(feature = "archives")]
+pub mod archive;
+#[cfg(feature = "fs")]
+pub mod builder;
+pub mod config;
+pub mod csv;
+#[cfg(feature = "fs")]
+pub mod daemon;
+pub mod derivative;
+pub mod editing;
+pub mod error;
+pub mod format;
+pub mod functions;
+pub mod fuzz;
+pub mod json;
+pub mod languages;
+#[cfg(feature = "fs")]
+pub mod lsp;
+#[cfg(feature = "fs")]
+pub mod migration;
+pub mod nfa;
+pub mod regex2nfa;
+pub mod regexparser;
+#[cfg(feature = "fs")]
+pub mod rename;
+pub mod repl;
+pub mod rules;
+pub mod source;
+pub mod structural;
+pub mod symbols;
+
+#[cfg(feature = "fs")]
+pub use builder::{Lang, ReplaceBuilder, SearchBuilder};
+pub use error::SpidiorError;
+pub use nfa::{matcher, replacer};
+
+use languages::{cache::Parsed, registry::Registry};
+use matcher::Match;
+
+/// The high-level entry point for running queries over code, which finds
+/// the language to parse the code as and parses it before running them
+pub struct Spidior;
+
+impl Spidior {
+    /// Parses a piece of code as a language, given its name, or as the
+    /// language its contents name if no name is given
+    fn parse(registry: &Registry, code: &str, lang: Option<&str>) -> Result<Parsed, SpidiorError> {
+        let lang = match lang {
+            Some(name) => registry.by_name(name).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}`", name)))?,
+            None => registry.detect(Path::new(""), code),
+        };
+        Ok(Parsed::new(code, lang))
+    }
+
+    /// Starts a search for the text the find part of a query matches
+    /// across files, which runs over the current directory unless given
+    /// paths
+    #[cfg(feature = "fs")]
+    pub fn search(query: &str) -> SearchBuilder {
+        SearchBuilder::new(query)
+    }
+
+    /// Starts a replacement across files, which runs over the current
+    /// directory unless given paths, and writes the files it changes unless
+    /// it is a dry run
+    #[cfg(feature = "fs")]
+    pub fn replace(query: &str) -> ReplaceBuilder {
+        ReplaceBuilder::new(query)
+    }
+
+    /// Finds the text the find part of a query matches within a piece of
+    /// code
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code to search
+    /// * `query` - The query, like `%s/[[type=Session]]/sess/g`, whose
+    ///   replacement is ignored
+    /// * `lang` - The name of the language to parse `code` as, like `java`,
+    ///   or None to detect it from a `#!` line or doctype in `code`, and
+    ///   otherwise treat it as plain text
+    ///
+    /// # Returns
+    ///
+    /// A Result<Vec<Match>, SpidiorError>, where on success, it returns
+    /// the matches in the order they appear. It will Err with a ParseError
+    /// if the query is not valid, a CompileError if it cannot be matched,
+    /// like one with `$`, or a LanguageError if the language is not valid.
+    pub fn find_in(code: &str, query: &str, lang: Option<&str>) -> Result<Vec<Match>, SpidiorError> {
+        let replace = regexparser::parse(query)?;
+        let parsed = Self::parse(&Registry::builtin(), code, lang)?;
+        Ok(matcher::find_parsed(code, replace.find, &parsed, false))
+    }
+
+    /// Performs every replacement a query makes within a piece of code
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code to change
+    /// * `query` - The query, like `%s/[[type=Session]]/sess/g`
+    /// * `lang` - The name of the language to parse `code` as, like `java`,
+    ///   or None to detect it from a `#!` line or doctype in `code`, and
+    ///   otherwise treat it as plain text
+    ///
+    /// # Returns
+    ///
+    /// A Result<String, SpidiorError>, where on success, it returns the
+    /// changed code. It will Err with a ParseError if the query is not
+    /// valid, a CompileError if it cannot be matched, like one with `$`, or
+    /// a LanguageError if the language is not valid.
+    pub fn replace_in(code: &str, query: &str, lang: Option<&str>) -> Result<String, SpidiorError> {
+        let replace = regexparser::parse(query)?;
+        let parsed = Self::parse(&Registry::builtin(), code, lang)?;
+        replacer::edits_parsed(code, replace, |_, _| true, &parsed, false, false, false)?.apply(code)
+    }
+}
+
+#[test]
+fn test_spidior() {
+    let code = "class A { Session s; void go() { s.run(); } }";
+    let found = Spidior::find_in(code, "%s/[[type=Session]]/sess/g", Some("java")).unwrap();
+    assert_eq!(found.iter().map(|x| &code[x.start()..x.start() + x.len()]).collect::<Vec<_>>(), vec!["s", "s"]);
+    assert_eq!(Spidior::replace_in(code, "%s/[[type=Session]]/sess/g", Some("clike")).unwrap(), "class A { Session sess; void go() { sess.run(); } }");
+    // Detected as shell from its `#!` line
+    let script = "#!/bin/sh\nNAME=x\necho $NAME\n";
+    assert_eq!(Spidior::replace_in(script, "%s/[[name=NAME]]/WHO/g", None).unwrap(), "#!/bin/sh\nWHO=x\necho $WHO\n");
+    assert!(matches!(Spidior::replace_in(code, "%s/a/b/g", Some("cobol")), Err(SpidiorError::LanguageError(_))));
+    assert!(matches!(Spidior::replace_in(code, "%s/a/b", None), Err(SpidiorError::ParseError(_))));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let code = "class A { Session s; void go() { s.run(); } }";
+    let query = regexparser::parse("%s/[[type=Session]]/sess/g").unwrap();
+    let json = serde_json::to_string(&query).unwrap();
+    let read: regexparser::ast::Replace = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    let found = Spidior::find_in(code, "%s/[[type=Session]]/sess/g", Some("java")).unwrap();
+    let read: Vec<Match> = serde_json::from_str(&serde_json::to_string(&found).unwrap()).unwrap();
+    assert_eq!(read.iter().map(|x| (x.start(), x.len())).collect::<Vec<_>>(), found.iter().map(|x| (x.start(), x.len())).collect::<Vec<_>>());
+    let parsed = Spidior::parse(&Registry::builtin(), code, Some("java")).unwrap();
+    let identifiers: Vec<languages::parsing::Identifier> = serde_json::from_str(&serde_json::to_string(&parsed.identifiers).unwrap()).unwrap();
+    assert_eq!(identifiers, parsed.identifiers);
+    let functions: Vec<languages::parsing::Function> = serde_json::from_str(&serde_json::to_string(&parsed.functions).unwrap()).unwrap();
+    assert_eq!(functions, parsed.functions);
+    let edits = replacer::edits_parsed(code, query, |_, _| true, &parsed, false, false, false).unwrap();
+    let read: Vec<editing::editset::Edit> = serde_json::from_str(&serde_json::to_string(&edits.iter().collect::<Vec<_>>()).unwrap()).unwrap();
+    assert_eq!(read.iter().collect::<Vec<_>>(), edits.iter().collect::<Vec<_>>());
+}
@@ -0,0 +1,527 @@
+//! Provides a Language Server Protocol server, run by `spidior lsp`, which
+//! talks JSON-RPC over stdin and stdout so that editors can rename symbols,
+//! find references, and apply configured queries without leaving the editor
+//!
+//! Renaming and finding references run a typed query, like
+//! `[[name=count,type=int]]`, for the identifier under the cursor across
+//! every file in the workspace. An identifier declared within a function is
+//! only looked for within that function. Code actions come from the
+//! `[lsp.actions]` section of the workspace's `.spidior.toml`, where each key
+//! is an action's title, which may be quoted, and its value is the query it
+//! applies to the current file.
+//!
+//! A message that panics is answered with an internal error, or logged with
+//! `window/logMessage` if it is a notification, and the server goes on to
+//! the next one.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::config::{self, Config};
+use crate::editing::editset::EditSet;
+use crate::error::{catch_panic, SpidiorError};
+use crate::json::{self, Value};
+use crate::languages::{cache::Parsed, lines::LineIndex, registry::Registry};
+use crate::matcher::{self, Match};
+use crate::regexparser;
+use crate::replacer;
+use crate::source::Visited;
+
+/// The JSON-RPC error code for a method the server does not offer
+const METHOD_NOT_FOUND: i64 = -32601;
+/// The JSON-RPC error code for a request the server could not carry out
+const REQUEST_FAILED: i64 = -32803;
+/// The JSON-RPC error code for a request that panicked, which the server
+/// answers rather than stopping
+const INTERNAL_ERROR: i64 = -32603;
+/// The `window/logMessage` type of an error
+const LOG_ERROR: i64 = 1;
+
+/// Builds a JSON object from its members
+fn object(members: Vec<(&str, Value)>) -> Value {
+    Value::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+/// Reads a message, which is a `Content-Length` header, a blank line, and
+/// that many bytes of JSON
+///
+/// # Returns
+///
+/// The message, or None once the input ends
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>, SpidiorError> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).map_err(|e| SpidiorError::io("Could not read a message", e))? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(x) = line.strip_prefix("Content-Length:") {
+            length = x.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| SpidiorError::parse("Expected a Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).map_err(|e| SpidiorError::io("Could not read a message", e))?;
+    let body = String::from_utf8(body).map_err(|_| SpidiorError::parse("Expected a message in UTF-8"))?;
+    json::parse(&body).map(Some).map_err(SpidiorError::parse)
+}
+
+/// Writes a message with its `Content-Length` header
+fn write_message(output: &mut impl Write, message: &Value) -> Result<(), SpidiorError> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| output.flush())
+        .map_err(|e| SpidiorError::io("Could not write a message", e))
+}
+
+/// Turns a path into a `file://` URI, escaping the bytes URIs cannot hold
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for b in path.to_string_lossy().bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' => uri.push(b as char),
+            b => uri += &format!("%{:02X}", b),
+        }
+    }
+    uri
+}
+
+/// Turns a `file://` URI into a path, or None if it is another kind of URI
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let mut iter = rest.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex: String = iter.by_ref().take(2).map(|x| x as char).collect();
+            bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+/// The LSP range of a span of a piece of text
+fn range(index: &LineIndex, start: usize, end: usize) -> Value {
+    let position = |offset| {
+        let (line, character) = index.utf16_position(offset);
+        object(vec![("line", Value::Number(line as i64)), ("character", Value::Number(character as i64))])
+    };
+    object(vec![("start", position(start)), ("end", position(end))])
+}
+
+/// Escapes the characters of a name that a query's regex treats specially
+fn escape(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || c == '_' { c.to_string() } else { format!("\\{}", c) }).collect()
+}
+
+/// The state of a language server, which is the workspace and the documents
+/// the editor has open
+pub struct Server {
+    /// The root of the workspace, whose files are searched
+    root: PathBuf,
+    /// The text of each open document, by URI, which is used instead of
+    /// what is on disk
+    documents: HashMap<String, String>,
+    /// The titles of the configured code actions, with their queries
+    actions: Vec<(String, String)>,
+    registry: Registry,
+    /// Whether the editor has asked the server to shut down
+    shutdown: bool,
+}
+
+impl Server {
+    /// Creates a Server for the workspace in the current directory, until
+    /// the editor names another
+    pub fn new() -> Self {
+        Self {
+            root: PathBuf::from("."),
+            documents: HashMap::new(),
+            actions: Vec::new(),
+            registry: Registry::builtin(),
+            shutdown: false,
+        }
+    }
+
+    /// Handles one message from the editor
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The request or notification
+    ///
+    /// # Returns
+    ///
+    /// The response to send, or None for a notification. A message that
+    /// panics is answered with an internal error, or, if it is a
+    /// notification, reported with `window/logMessage`.
+    pub fn handle(&mut self, message: &Value) -> Option<Value> {
+        self.answer(message, Self::call)
+    }
+
+    /// Handles one message from the editor, carrying it out with `call`
+    fn answer(&mut self, message: &Value, call: impl FnOnce(&mut Self, &str, &Value) -> Result<Value, (i64, String)>) -> Option<Value> {
+        let method = message.get("method").and_then(|x| x.as_str()).unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match catch_panic(|| call(self, method, &params)) {
+            Ok(x) => x,
+            Err(e) if message.get("id").is_none() => {
+                let params = object(vec![("type", Value::Number(LOG_ERROR)), ("message", Value::String(format!("Internal error handling `{}`: {}", method, e)))]);
+                return Some(object(vec![("jsonrpc", Value::String("2.0".into())), ("method", Value::String("window/logMessage".into())), ("params", params)]));
+            }
+            Err(e) => Err((INTERNAL_ERROR, format!("Internal error: {}", e))),
+        };
+        // Notifications have no ID, and get no response
+        let id = message.get("id")?.clone();
+        let outcome = match result {
+            Ok(x) => ("result", x),
+            Err((code, message)) => ("error", object(vec![("code", Value::Number(code)), ("message", Value::String(message))])),
+        };
+        Some(object(vec![("jsonrpc", Value::String("2.0".into())), ("id", id), outcome]))
+    }
+
+    /// Carries out a message for `method` with `params`
+    fn call(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => self.initialize(params),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/didOpen" | "textDocument/didChange" | "textDocument/didClose" => {
+                self.sync(method, params);
+                Ok(Value::Null)
+            }
+            "textDocument/references" => self.references(params),
+            "textDocument/rename" => self.rename(params),
+            "textDocument/codeAction" => self.code_actions(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method `{}`", method))),
+        }
+    }
+
+    /// Records the workspace's root, and reads its config for code actions
+    fn initialize(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        if let Some(root) = params.get("rootUri").and_then(|x| x.as_str()).and_then(uri_to_path) {
+            self.root = root;
+        } else if let Some(root) = params.get("rootPath").and_then(|x| x.as_str()) {
+            self.root = PathBuf::from(root);
+        }
+        let path = self.root.join(config::DEFAULT_PATH);
+        if path.is_file() {
+            let config = Config::load(&path).map_err(|e| (REQUEST_FAILED, e.to_string()))?;
+            for key in config.keys("lsp.actions") {
+                if let Some(query) = config.get("lsp.actions", key).and_then(|x| x.first()) {
+                    self.actions.push((key.trim_matches('"').to_string(), query.clone()));
+                }
+            }
+            self.actions.sort();
+        }
+        let capabilities = object(vec![
+            ("textDocumentSync", Value::Number(1)),
+            ("referencesProvider", Value::Bool(true)),
+            ("renameProvider", Value::Bool(true)),
+            ("codeActionProvider", Value::Bool(true)),
+        ]);
+        Ok(object(vec![
+            ("capabilities", capabilities),
+            ("serverInfo", object(vec![("name", Value::String("spidior".into()))])),
+        ]))
+    }
+
+    /// Keeps the text of open documents up to date, given the whole text
+    /// with each change
+    fn sync(&mut self, method: &str, params: &Value) {
+        let document = params.get("textDocument");
+        let uri = match document.and_then(|x| x.get("uri")).and_then(|x| x.as_str()) {
+            Some(x) => x.to_string(),
+            None => return,
+        };
+        let text = match method {
+            "textDocument/didOpen" => document.and_then(|x| x.get("text")),
+            "textDocument/didChange" => params.get("contentChanges").and_then(|x| x.as_array()).and_then(|x| x.last()).and_then(|x| x.get("text")),
+            _ => None,
+        };
+        match text.and_then(|x| x.as_str()) {
+            Some(text) => {
+                self.documents.insert(uri, text.to_string());
+            }
+            None if method == "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            None => {}
+        }
+    }
+
+    /// The text of a document, from the editor if it is open, or else from
+    /// disk
+    fn text(&self, uri: &str) -> Option<String> {
+        match self.documents.get(uri) {
+            Some(x) => Some(x.clone()),
+            None => fs::read_to_string(uri_to_path(uri)?).ok(),
+        }
+    }
+
+    /// Parses a document as the language detected for it
+    fn parse(&self, uri: &str, text: &str) -> Parsed {
+        let path = uri_to_path(uri).unwrap_or_default();
+        Parsed::new(text, self.registry.detect(&path, text))
+    }
+
+    /// The URIs of every file in the workspace, once each, leaving out
+    /// hidden directories like `.git`, along with any open document outside
+    /// it
+    fn workspace(&self) -> Vec<String> {
+        let mut visited = Visited::new();
+        let mut uris: Vec<String> = WalkDir::new(&self.root)
+            .follow_links(true)
+            .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+            .into_iter()
+            .filter_entry(|x| x.depth() == 0 || !x.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|x| x.ok())
+            .filter(|x| x.file_type().is_file() && visited.first(x.path()))
+            .map(|x| path_to_uri(&fs::canonicalize(x.path()).unwrap_or_else(|_| x.path().to_path_buf())))
+            .collect();
+        for uri in self.documents.keys() {
+            if !uris.contains(uri) {
+                uris.push(uri.clone());
+            }
+        }
+        uris
+    }
+
+    /// Finds the query for the identifier at a position, and the files it
+    /// should be run over
+    fn target(&self, params: &Value) -> Result<(String, Vec<String>), (i64, String)> {
+        let failed = |x: &str| (REQUEST_FAILED, x.to_string());
+        let uri = params.get("textDocument").and_then(|x| x.get("uri")).and_then(|x| x.as_str()).ok_or_else(|| failed("Expected a textDocument"))?;
+        let position = params.get("position").ok_or_else(|| failed("Expected a position"))?;
+        let line = position.get("line").and_then(|x| x.as_usize()).unwrap_or_default();
+        let character = position.get("character").and_then(|x| x.as_usize()).unwrap_or_default();
+        let text = self.text(uri).ok_or_else(|| failed("Could not read the document"))?;
+        let offset = LineIndex::new(&text).utf16_offset(line, character);
+        let parsed = self.parse(uri, &text);
+        let ident = parsed
+            .identifiers
+            .iter()
+            .find(|x| x.start <= offset && offset <= x.end)
+            .ok_or_else(|| failed("There is no identifier here"))?;
+        let mut query = format!("[[name={},type={}", escape(&ident.name), escape(&ident.typ));
+        // The declaration is taken to be the first identifier like this one
+        // that it can see, so that a field used within a function is still
+        // looked for everywhere
+        let declaration = parsed
+            .identifiers
+            .iter()
+            .filter(|x| x.name == ident.name && x.typ == ident.typ && parsed.scopes.encloses(x.scope_id, ident.scope_id))
+            .min_by_key(|x| x.start)
+            .unwrap_or(ident);
+        match parsed.functions.iter().find(|f| f.start <= declaration.start && declaration.start < f.end) {
+            Some(f) => {
+                query += &format!(",scope={}]]", parsed.scopes.innermost(f.start));
+                Ok((query, vec![uri.to_string()]))
+            }
+            None => Ok((query + "]]", self.workspace())),
+        }
+    }
+
+    /// Runs a pattern, like the find part of a query, over files
+    ///
+    /// # Returns
+    ///
+    /// Each file with a match, with its text and matches
+    fn find(&self, pattern: &str, uris: Vec<String>) -> Result<Vec<(String, String, Vec<Match>)>, SpidiorError> {
+        let find = regexparser::parse_pattern(pattern)?;
+        let mut found = Vec::new();
+        for uri in uris {
+            let text = match self.text(&uri) {
+                Some(x) => x,
+                None => continue,
+            };
+            let matches = matcher::find_parsed(&text, find.clone(), &self.parse(&uri, &text), false);
+            if !matches.is_empty() {
+                found.push((uri, text, matches));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Finds every reference to the identifier at a position
+    fn references(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (query, uris) = self.target(params)?;
+        let found = self.find(&query, uris).map_err(|e| (REQUEST_FAILED, e.to_string()))?;
+        let mut locations = Vec::new();
+        for (uri, text, matches) in found {
+            let index = LineIndex::new(&text);
+            for m in matches {
+                locations.push(object(vec![("uri", Value::String(uri.clone())), ("range", range(&index, m.start(), m.start() + m.len()))]));
+            }
+        }
+        Ok(Value::Array(locations))
+    }
+
+    /// Renames the identifier at a position, and every reference to it
+    fn rename(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params.get("newName").and_then(|x| x.as_str()).unwrap_or_default();
+        let mut chars = name.chars();
+        if !chars.next().is_some_and(unicode_xid::UnicodeXID::is_xid_start) || !chars.all(unicode_xid::UnicodeXID::is_xid_continue) {
+            return Err((REQUEST_FAILED, format!("`{}` is not an identifier", name)));
+        }
+        let (query, uris) = self.target(params)?;
+        let found = self.find(&query, uris).map_err(|e| (REQUEST_FAILED, e.to_string()))?;
+        let changes = found
+            .into_iter()
+            .map(|(uri, text, matches)| {
+                let index = LineIndex::new(&text);
+                let edits = matches
+                    .iter()
+                    .map(|m| object(vec![("range", range(&index, m.start(), m.start() + m.len())), ("newText", Value::String(name.to_string()))]))
+                    .collect();
+                (uri, Value::Array(edits))
+            })
+            .collect();
+        Ok(object(vec![("changes", Value::Object(changes))]))
+    }
+
+    /// Offers each configured query that would change the document as a
+    /// code action
+    fn code_actions(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = match params.get("textDocument").and_then(|x| x.get("uri")).and_then(|x| x.as_str()) {
+            Some(x) => x,
+            None => return Ok(Value::Array(Vec::new())),
+        };
+        let text = self.text(uri).unwrap_or_default();
+        let parsed = self.parse(uri, &text);
+        let index = LineIndex::new(&text);
+        let mut actions = Vec::new();
+        for (title, query) in &self.actions {
+            let edits: EditSet = regexparser::parse(query)
+                .and_then(|x| replacer::edits_parsed(&text, x, |_, _| true, &parsed, false, false, false))
+                .map_err(|e| (REQUEST_FAILED, format!("{}: {}", title, e)))?;
+            if edits.is_empty() {
+                continue;
+            }
+            let edits = edits
+                .iter()
+                .map(|x| object(vec![("range", range(&index, x.start, x.end)), ("newText", Value::String(x.replacement.clone()))]))
+                .collect();
+            actions.push(object(vec![
+                ("title", Value::String(title.clone())),
+                ("kind", Value::String("refactor.rewrite".into())),
+                ("edit", object(vec![("changes", Value::Object(vec![(uri.to_string(), Value::Array(edits))]))])),
+            ]));
+        }
+        Ok(Value::Array(actions))
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serves the Language Server Protocol until the editor sends `exit` or
+/// closes the input
+///
+/// # Arguments
+///
+/// * `input` - Where messages from the editor are read from, like stdin
+/// * `output` - Where responses are written to, like stdout
+///
+/// # Returns
+///
+/// A Result<(), SpidiorError>, which will Err if a message cannot be read
+/// or written, or if the editor exits without asking the server to shut
+/// down first
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> Result<(), SpidiorError> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(&mut input)? {
+        if message.get("method").and_then(|x| x.as_str()) == Some("exit") {
+            break;
+        }
+        if let Some(response) = server.handle(&message) {
+            write_message(&mut output, &response)?;
+        }
+    }
+    if server.shutdown {
+        Ok(())
+    } else {
+        Err(SpidiorError::parse("The editor exited without shutting the server down"))
+    }
+}
+
+#[test]
+fn test_lsp() {
+    let dir = std::env::temp_dir().join(format!("spidior-test-lsp-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::write(dir.join(".git").join("A.java"), "class A { Session s; }").unwrap();
+    fs::write(dir.join("A.java"), "class A { Session s; void go() { s.run(); } }").unwrap();
+    fs::write(dir.join("B.java"), "class B { Session s; int n; void go() { int k = n; } }").unwrap();
+    fs::write(dir.join(config::DEFAULT_PATH), "[lsp.actions]\n\"use sess\" = \"%s/[[type=Session]]/sess/g\"\n").unwrap();
+    let dir = fs::canonicalize(dir).unwrap();
+    let (a, b) = (path_to_uri(&dir.join("A.java")), path_to_uri(&dir.join("B.java")));
+    let request = |method: &str, params: String| format!("{{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"{}\", \"params\": {}}}", method, params);
+    let position = |uri: &str, character: usize| format!("{{\"textDocument\": {{\"uri\": \"{}\"}}, \"position\": {{\"line\": 0, \"character\": {}}}", uri, character);
+    let messages = [
+        request("initialize", format!("{{\"rootUri\": \"{}\"}}", path_to_uri(&dir))),
+        request("textDocument/references", position(&a, 18) + "}"),
+        request("textDocument/rename", position(&b, 44) + ", \"newName\": \"m\"}"),
+        request("textDocument/rename", position(&b, 48) + ", \"newName\": \"m\"}"),
+        request("textDocument/codeAction", format!("{{\"textDocument\": {{\"uri\": \"{}\"}}}}", a)),
+        request("textDocument/rename", position(&a, 18) + ", \"newName\": \"1x\"}"),
+        request("shutdown", "null".into()),
+        "{\"jsonrpc\": \"2.0\", \"method\": \"exit\"}".to_string(),
+    ];
+    let input: String = messages.iter().map(|x| format!("Content-Length: {}\r\n\r\n{}", x.len(), x)).collect();
+    let mut output = Vec::new();
+    serve(input.as_bytes(), &mut output).unwrap();
+    let mut output = output.as_slice();
+    let mut next = || read_message(&mut output).unwrap().unwrap();
+    assert!(next().get("result").and_then(|x| x.get("capabilities")).is_some());
+    // `s` in both files, but not in the hidden directory
+    let references = next();
+    let uris: Vec<&str> = references.get("result").and_then(|x| x.as_array()).unwrap().iter().map(|x| x.get("uri").and_then(|x| x.as_str()).unwrap()).collect();
+    assert_eq!(uris, vec![a.as_str(), a.as_str(), b.as_str()]);
+    // `k` is local to `go`, so only B.java changes
+    let rename = next();
+    let changes = rename.get("result").and_then(|x| x.get("changes")).unwrap();
+    assert_eq!(changes.get(&b).and_then(|x| x.as_array()).map(|x| x.len()), Some(1));
+    assert_eq!(changes.get(&a), None);
+    // `n` is a field, so its declaration changes along with its use in `go`
+    let rename = next();
+    let changes = rename.get("result").and_then(|x| x.get("changes")).unwrap();
+    assert_eq!(changes.get(&b).and_then(|x| x.as_array()).map(|x| x.len()), Some(2));
+    let actions = next();
+    let action = &actions.get("result").and_then(|x| x.as_array()).unwrap()[0];
+    assert_eq!(action.get("title").and_then(|x| x.as_str()), Some("use sess"));
+    assert_eq!(action.get("edit").and_then(|x| x.get("changes")).and_then(|x| x.get(&a)).and_then(|x| x.as_array()).map(|x| x.len()), Some(2));
+    assert!(next().get("error").is_some());
+    assert_eq!(next().get("result"), Some(&Value::Null));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_panic() {
+    let mut server = Server::new();
+    let request = json::parse("{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"textDocument/didOpen\", \"params\": {\"textDocument\": {\"uri\": \"file:///a.txt\", \"text\": \"a/b\"}}}").unwrap();
+    let response = server.answer(&request, |_, _, _| panic!("bad request")).unwrap();
+    assert_eq!(response.get("error").and_then(|x| x.get("code")), Some(&Value::Number(INTERNAL_ERROR)));
+    let notification = json::parse("{\"jsonrpc\": \"2.0\", \"method\": \"textDocument/didOpen\", \"params\": {\"textDocument\": {\"uri\": \"file:///a.txt\", \"text\": \"a/b\"}}}").unwrap();
+    let log = server.answer(&notification, |_, _, _| panic!("bad notification")).unwrap();
+    assert_eq!(log.get("method").and_then(|x| x.as_str()), Some("window/logMessage"));
+    assert_eq!(log.get("params").and_then(|x| x.get("message")).and_then(|x| x.as_str()), Some("Internal error handling `textDocument/didOpen`: bad notification"));
+    // The server handles the next message as if nothing had happened
+    assert!(server.handle(&notification).is_none());
+    // A pattern is never spliced into a query, so its `/` is matched
+    let found = server.find("a/b", vec!["file:///a.txt".to_string()]).unwrap();
+    assert_eq!(found[0].2.len(), 1);
+}
@@ -1,113 +1,1384 @@
-use clap::Clap;
-use languages::clike::Clike;
-use languages::parsing::*;
-use std::{error::Error, fs};
+use clap::{AppSettings, Clap};
+use std::{error::Error, fs, path::{Path, PathBuf}, time::Duration};
 use walkdir::WalkDir;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, IsTerminal, Write};
 
-#[macro_use]
-extern crate lalrpop_util;
-
-mod editing;
-mod languages;
-mod nfa;
-mod regex2nfa;
-mod regexparser;
-
-use crate::regex2nfa::build_nfa;
+use spidior::analysis::{Census, CENSUS_COLUMNS, DUPLICATE_COLUMNS, SHADOW_COLUMNS};
+use spidior::config::{self, Config};
+use spidior::daemon::{self, Daemon};
+use spidior::derivative::{self, Engine};
+use spidior::editing::{editset::{Conflict, EditSet}, html::HtmlReport, plan::Plan, report::{self, Report}, transaction::Transaction};
+use spidior::languages::{cache::{ParseCache, Parsed, Strictness}, keywords, lines::LineIndex, registry::Registry, Language};
+use spidior::format::{Format, Style};
+use spidior::json::Value;
+use spidior::migration::{Migration, Workspace};
+use spidior::nfa::matcher::{Budget, Match, PatternSet};
+use spidior::nfa::queryengine::{QueryEngine, Stat};
+use spidior::regex2nfa::build_nfa;
+use spidior::regexparser::ast::{Command, Location, Replace, ReplaceItem, Replacement};
+use spidior::repl::Repl;
+use spidior::rules::{RulePack, Severity};
+use spidior::source::{self, Source};
+use spidior::symbols::Listing;
+use spidior::{fuzz, lsp, nfa, rename, regexparser, structural, SpidiorError};
 
 #[derive(Clap)]
 #[clap(version = "0.1.1", author = "John Westhoff <johnjwesthoff@gmail.com>")]
+#[clap(setting = AppSettings::SubcommandsNegateReqs)]
 struct Opts {
     /// The path to the files we are reading
     #[clap(short, long, default_value = ".")]
     path: String,
-    /// The query string for find/replace for each file we find in the input, required if `dump` is not set
-    #[clap(short = 'q', long, required_unless_present("dump"))]
-    query: Option<String>,
+    /// The query string for find/replace for each file we find in the input, required if `dump` is not set; may be given more than once, in which case every query is matched against the original file
+    #[clap(short = 'q', long, required_unless_present_any(&["dump", "apply-plan", "pattern"]), number_of_values = 1)]
+    query: Vec<String>,
+    /// A pattern to find, like the find part of a query, such as `[[type=Session]]`; may be given more than once, in which case the patterns are matched together in one scan, and each match is printed after the pattern it matched, in brackets; needs `--format vimgrep`, `emacs`, `csv`, or `tsv`
+    #[clap(short = 'e', long, number_of_values = 1)]
+    pattern: Vec<String>,
+    /// What to do when edits from different queries overlap: `error`, keep the `first`, or `merge` them
+    #[clap(long, default_value = "error")]
+    on_conflict: Conflict,
     /// Whether we are are interactively replacing things or not
     #[clap(short = 'I', long)]
     interactive: bool,
     /// Whether we should edit files in place or print to stdout
     #[clap(short, long)]
     in_place: bool,
-    /// Whether we should just dump info without replacing
+    /// Whether lines a replacement introduces should be re-indented to match the line the match starts on
+    #[clap(long)]
+    reindent: bool,
+    /// Whether files that look generated, like minified bundles or files marked `@generated` or `DO NOT EDIT`, should be replaced in too, rather than skipped
+    #[clap(long)]
+    include_generated: bool,
+    /// Whether we should print a unified diff of the changes rather than the changed files
+    #[clap(long)]
+    diff: bool,
+    /// Whether a replacement that is a plain identifier should be refused wherever the new name is already declared in a scope visible from a match, reporting each such match as an error
+    #[clap(long)]
+    check_collisions: bool,
+    /// Whether we should print, for each query set, how many identifiers each of its predicates accepted, to show which one rules out a match
+    #[clap(long)]
+    stats: bool,
+    /// A file to append a record of every edit made in place to, with enough to undo it
+    #[clap(long)]
+    journal: Option<String>,
+    /// Whether an in-place run should leave out the report of what it changed that it otherwise writes to `.spidior/runs/`
+    #[clap(long)]
+    no_report: bool,
+    /// A file to write every edit to as JSON, to be reviewed and applied later with `--apply-plan`, instead of changing or printing any files
+    #[clap(long)]
+    plan: Option<String>,
+    /// A plan written by `--plan` to apply, as long as none of the files it edits have changed since
+    #[clap(long)]
+    apply_plan: Option<String>,
+    /// How matches are printed: `text` prints the replaced files, while `vimgrep` and `emacs` print each match of the queries, as `file:line:col:text` for Vim's quickfix list or `file:line:col: text` for Emacs, without replacing, `csv` and `tsv` print each match as a row for a spreadsheet, and `html` writes a report of the edits to `--html-dir`
+    #[clap(long, default_value = "text")]
+    format: Format,
+    /// The directory `--format html` writes its report to, as an `index.html` and a page for each file edited
+    #[clap(long, default_value = "spidior-report")]
+    html_dir: String,
+    /// Whether each match printed by `--format vimgrep` or `emacs` should show the text its query would replace it with, as `[old → new]`
+    #[clap(long)]
+    preview: bool,
+    /// The most characters of a line to print for each match of `--format vimgrep` or `emacs`, cutting longer lines short around the match; by default, lines are fit to the terminal, if printing to one, and 0 prints whole lines
+    #[clap(long)]
+    max_columns: Option<usize>,
+    /// How many seconds matching each file may take, after which it is reported and left alone
+    #[clap(long, parse(try_from_str = parse_seconds))]
+    timeout_per_file: Option<Duration>,
+    /// How many bytes the matches of each query in a file may hold, like `64M`, after which the file is reported and left alone; this limits the buffer of matches, which grows with the input, rather than all the memory spidior uses, as parsing and the rest of matching do not grow with the matches
+    #[clap(long, parse(try_from_str = parse_size))]
+    memory_limit: Option<usize>,
+    /// Whether a query the lint pass warns of, like `colou?r`, whose `?` only matches itself, should fail the run rather than be matched
+    #[clap(long)]
+    deny_warnings: bool,
+    /// How queries are matched: by the `nfa`, by `derivatives`, which find no groups for backreferences, or `differential`, which matches by both, keeps what the NFA matches, and reports wherever the two disagree
+    #[clap(long, default_value = "nfa")]
+    engine: Engine,
+    /// Whether we should just list the symbols of each file, as `spidior symbols` does, without replacing
     #[clap(short, long)]
     dump: bool,
     /// Whether we should print info about the regex nfa
     #[clap(short, long)]
     nfa: bool,
+    /// Whether we should print, on stderr, the language each file is parsed as, how many functions, identifiers, imports, and comments its parse found, how long it took, where it lost track of the file's structure, and each file as it is written
+    #[clap(short, long)]
+    verbose: bool,
+    /// What to do with files whose parse lost track of their structure, like a `}` that closes no block: `lenient` keeps what was found past it, while `strict` reports each such file and fails before matching anything
+    #[clap(long, default_value = "lenient")]
+    parser_strictness: Strictness,
     /// Whether we should search recursively
     #[clap(short, long)]
     recursive: bool,
+    /// Whether the files within `.zip`, `.jar`, and `.tar.gz` archives should be searched, reported as `archive!inner/path`; needs `--format vimgrep`, `emacs`, `csv`, or `tsv`, as archives are never changed
+    #[clap(long)]
+    archives: bool,
+    /// Whether matches inside string and char literals should be left alone
+    #[clap(short, long)]
+    skip_strings: bool,
+    /// Whether `type` criteria should also match through type aliases, like `typedef`s and imports
+    #[clap(short = 'a', long)]
+    resolve_aliases: bool,
+    /// Whether matches inside code disabled by conditional compilation, like `#if 0`, should be left alone
+    #[clap(long)]
+    skip_disabled: bool,
+    /// The language to parse files as, instead of detecting it from each file
+    #[clap(long)]
+    lang: Option<String>,
+    /// The config file to read, instead of `.spidior.toml` in the current directory
+    #[clap(long)]
+    config: Option<String>,
+    /// Whether every file should be parsed again, rather than reading the results of earlier runs from the parse cache
+    #[clap(long)]
+    no_cache: bool,
+    /// A rule pack to read rules from for `check` and `fix`; may be given more than once, in which case a rule replaces any earlier rule with its name
+    #[clap(long, number_of_values = 1)]
+    rules: Vec<String>,
+    #[clap(subcommand)]
+    subcmd: Option<SubCommand>,
+}
+
+#[derive(Clap)]
+enum SubCommand {
+    /// Match code by shape, using `:[name]` holes that match balanced regions
+    Structural(Structural),
+    /// Serve the Language Server Protocol over stdin and stdout, for editors
+    Lsp,
+    /// Answer find and replace requests in JSON-RPC, keeping parsed queries and files between them
+    Serve(Serve),
+    /// Report every place the rules given by `--rules` are broken
+    Check,
+    /// Apply the fix of every rule given by `--rules` that has one
+    Fix,
+    /// Check each rule in the given rule packs against its examples, failing if any rule gets one wrong
+    TestRules(TestRules),
+    /// Read the files once, then print the matches and replacement of each query typed, until `:quit`
+    Repl,
+    /// Rename the files and directories beneath `--path` whose names a substitution like `s/Session/Sess/` matches
+    RenameFiles(RenameFiles),
+    /// Run the steps of a migration script in order, writing nothing until every step has run
+    Run(RunScript),
+    /// List the functions, imports, aliases, and identifiers parsed from each file, with their types, spans, and scopes
+    Symbols(Symbols),
+    /// Show the report of an earlier in-place run, or list the runs with reports if no ID is given
+    Report(ShowReport),
+    /// Match random patterns against random inputs with both spidior and the `regex` crate, reporting every case on which they differ
+    FuzzCompare(FuzzCompare),
+    /// Remove, dedupe, or sort the imports of each file, as its language reads them
+    Imports(Imports),
+    /// Report what the identifiers and scopes of each file show, like declarations that shadow others
+    Analyze(Analyze),
+    /// Read records from stdin, one line at a time, and print each one a pattern matches, as it is or replaced
+    MatchOne(MatchOne),
+}
+
+#[derive(Clap)]
+struct MatchOne {
+    /// The pattern to match each record against, like the find part of a query, such as `[[type=Session]]`
+    pattern: String,
+    /// What to replace each match with, like the replacement part of a query, so that each record matched is printed replaced
+    #[clap(long)]
+    replace: Option<String>,
+    /// Whether records end with a NUL byte, as `find -print0` writes them, rather than a line break
+    #[clap(long)]
+    null: bool,
+}
+
+#[derive(Clap)]
+struct TestRules {
+    /// The rule packs to test, read as `--rules` reads them; if absent, those given by `--rules` are tested
+    rules: Vec<String>,
+}
+
+#[derive(Clap)]
+struct Analyze {
+    #[clap(subcommand)]
+    analysis: Analysis,
+}
+
+#[derive(Clap)]
+enum Analysis {
+    /// Report each declaration that shadows a declaration of the same name in a scope enclosing it, with where both are
+    Shadowing(Analyzed),
+    /// Count the identifiers, and the declarations among them, of each type, in each file and in total
+    Types(Analyzed),
+    /// List the function names declared in more than one file, with where each is declared
+    Duplicates(Analyzed),
+}
+
+#[derive(Clap)]
+struct Analyzed {
+    /// How the findings are listed: as a `table`, as `json` objects, each on a line of its own, or as `csv` or `tsv` rows under a header row
+    #[clap(long, default_value = "table")]
+    format: Listing,
+}
+
+#[derive(Clap)]
+struct Imports {
+    /// Remove every import whose path matches this pattern, in which `*` matches anything, e.g. `com.old.*`; may be given more than once
+    #[clap(long)]
+    remove: Vec<String>,
+    /// Remove each import statement that only imports what earlier ones in the file do
+    #[clap(long)]
+    dedupe: bool,
+    /// Sort each run of import statements on consecutive lines
+    #[clap(long)]
+    sort: bool,
+}
+
+#[derive(Clap)]
+struct Symbols {
+    /// The file, or directory of files, to list the symbols of; if absent, `--path` is used
+    path: Option<String>,
+    /// How symbols are listed: as a `table`, as a `json` object for each file, on a line of its own, or as `csv` or `tsv` rows under a header row
+    #[clap(long, default_value = "table")]
+    format: Listing,
+}
+
+#[derive(Clap)]
+struct ShowReport {
+    /// The ID of the run, as printed when it finished
+    id: Option<String>,
+    /// Whether the report should be printed as the JSON it is kept as, rather than rendered
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Clap)]
+struct FuzzCompare {
+    /// How many patterns to try
+    #[clap(long, default_value = "1000")]
+    cases: usize,
+    /// The seed to make the patterns and inputs from, to repeat an earlier run; if absent, one is picked from the time
+    #[clap(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Clap)]
+struct RunScript {
+    /// The migration script, with a `[steps.<name>]` section for each step
+    script: String,
+    /// Whether each step should only print a diff of its edits and its renames, rather than the files being changed
+    #[clap(long)]
+    dry_run: bool,
+    /// Whether renames should be made with `git mv`, so that git records them
+    #[clap(long)]
+    git: bool,
+}
+
+#[derive(Clap)]
+struct RenameFiles {
+    /// The substitution to apply to each name, e.g. `s/Session/Sess/`
+    substitution: String,
+    /// Whether the renames should only be printed, rather than made
+    #[clap(long)]
+    dry_run: bool,
+    /// Whether each rename should be made with `git mv`, so that git records it
+    #[clap(long)]
+    git: bool,
+}
+
+#[derive(Clap)]
+struct Serve {
+    /// A Unix socket to listen on, rather than reading requests from stdin
+    #[clap(long)]
+    socket: Option<String>,
+}
+
+#[derive(Clap)]
+struct Structural {
+    /// The template to match, e.g. `if (:[cond]) { :[body] }`
+    template: String,
+    /// The rewrite for each match, which may reference holes; if absent, matches are printed
+    rewrite: Option<String>,
 }
 
-fn ask(replace: &str, with: &str) -> bool {
-    println!("Replace:\n{}\nWith:\n{}\n?", replace, with);
+fn ask(m: &Match, with: &str) -> bool {
+    let (line, column) = m.position();
+    println!("Replace, at {}:{}:\n{}\nWith:\n{}\n?", line, column, m.text(), with);
     let mut answer = String::new();
     let stdin = io::stdin();
     stdin.lock().read_line(&mut answer).unwrap();
     return answer.to_lowercase().starts_with("y");
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
+    if let Err(e) = run() {
+        // Print the whole chain of causes, like `Could not write a.txt:
+        // Permission denied`
+        let mut message = format!("Error: {}", e);
+        let mut source = e.source();
+        while let Some(x) = source {
+            message += &format!(": {}", x);
+            source = x.source();
+        }
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), SpidiorError> {
     let opts: Opts = Opts::parse();
-    if opts.dump {
-        dump(opts)
+    configure(&opts)?;
+    if let Some(SubCommand::Structural(ref s)) = opts.subcmd {
+        structural(&opts, s)
+    } else if let Some(SubCommand::Lsp) = opts.subcmd {
+        lsp::serve(std::io::stdin().lock(), std::io::stdout().lock())
+    } else if let Some(SubCommand::Serve(ref s)) = opts.subcmd {
+        serve(&opts, s)
+    } else if let Some(SubCommand::Check) = opts.subcmd {
+        check(&opts)
+    } else if let Some(SubCommand::Fix) = opts.subcmd {
+        fix(&opts)
+    } else if let Some(SubCommand::TestRules(ref t)) = opts.subcmd {
+        test_rules(&opts, t)
+    } else if let Some(SubCommand::Repl) = opts.subcmd {
+        repl(&opts)
+    } else if let Some(SubCommand::RenameFiles(ref r)) = opts.subcmd {
+        rename_files(&opts, r)
+    } else if let Some(SubCommand::Run(ref r)) = opts.subcmd {
+        run_script(&opts, r)
+    } else if let Some(SubCommand::Symbols(ref s)) = opts.subcmd {
+        symbols(&opts, s.path.as_deref().unwrap_or(&opts.path), s.format)
+    } else if let Some(SubCommand::Report(ref r)) = opts.subcmd {
+        show_report(r)
+    } else if let Some(SubCommand::FuzzCompare(ref f)) = opts.subcmd {
+        fuzz_compare(f)
+    } else if let Some(SubCommand::Imports(ref i)) = opts.subcmd {
+        imports(&opts, i)
+    } else if let Some(SubCommand::Analyze(ref a)) = opts.subcmd {
+        analyze(&opts, a)
+    } else if let Some(SubCommand::MatchOne(ref m)) = opts.subcmd {
+        match_one(&opts, m)
+    } else if opts.dump {
+        symbols(&opts, &opts.path, Listing::Table)
+    } else if let Some(path) = &opts.apply_plan {
+        apply_plan(&opts, Path::new(path))
     } else {
         replace(opts)
     }
 }
 
-fn dump(opts: Opts) -> Result<(), Box<dyn Error>> {
-    let c = Clike {};
+/// Reads the config file given by `--config`, or `.spidior.toml` in the
+/// current directory if there is one, adds the words in each of its
+/// `[languages.<name>]` sections to that language's keyword and modifier
+/// tables, and defines the macros in its `[macros]` section
+fn configure(opts: &Opts) -> Result<(), SpidiorError> {
+    let config = match &opts.config {
+        Some(path) => Config::load(std::path::Path::new(path))?,
+        None if std::path::Path::new(config::DEFAULT_PATH).is_file() => Config::load(std::path::Path::new(config::DEFAULT_PATH))?,
+        None => return Ok(()),
+    };
+    for name in config.keys("macros") {
+        if name.is_empty() || !name.chars().all(|x| x.is_alphanumeric() || x == '_' || x == '-') {
+            return Err(SpidiorError::parse(format!("The macro name `{}` in config should be made of letters, digits, `_`, and `-`", name)));
+        }
+        match config.get("macros", name).unwrap_or_default() {
+            [pattern] => regexparser::macros::define(name, pattern),
+            _ => return Err(SpidiorError::parse(format!("The macro `{}` in config should be a single pattern", name))),
+        }
+    }
+    let registry = Registry::builtin();
+    for section in config.sections() {
+        let name = match section.strip_prefix("languages.") {
+            Some(name) => name,
+            None => continue,
+        };
+        let lang = registry.by_name(name).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}` in config", name)))?;
+        if let Some(key) = config.keys(section).find(|x| !["keywords", "modifiers"].contains(x)) {
+            return Err(SpidiorError::language(format!("Unknown setting `{}` for language `{}` in config", key, name)));
+        }
+        let words = |key| config.get(section, key).unwrap_or_default().to_vec();
+        keywords::extend(lang.name(), words("keywords"), words("modifiers"));
+    }
+    Ok(())
+}
+
+/// Picks the language for a file, which is the one given by `--lang` if
+/// present, or else the one detected from the file itself
+fn language<'a>(opts: &Opts, registry: &'a Registry, path: &std::path::Path, contents: &str) -> Result<&'a dyn Language, SpidiorError> {
+    match &opts.lang {
+        Some(name) => registry.by_name(name).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}`", name))),
+        None => Ok(registry.detect(path, contents)),
+    }
+}
+
+/// Prints, with `--verbose`, the file about to be written or printed, on
+/// stderr so that it stays apart from the files themselves
+fn progress(opts: &Opts, file: impl std::fmt::Display) {
+    if opts.verbose {
+        eprintln!("Parsing file {}", file);
+    }
+}
+
+/// Parses each file, in parallel and through the cache, printing what was
+/// found in it with `--verbose`, so that a file parsed as the wrong language
+/// stands out, and failing under `--parser-strictness strict` if any parse
+/// lost track of its file's structure
+fn parse_files<'a>(opts: &Opts, cache: &ParseCache, files: impl Iterator<Item = (&'a Path, &'a str, &'a dyn Language)>) -> Result<Vec<Parsed>, SpidiorError> {
+    let files: Vec<(&Path, &str, &dyn Language)> = files.collect();
+    let timed = cache.parse_all_timed(&files.iter().map(|(_, text, lang)| (*text, *lang)).collect::<Vec<_>>());
+    let strict = opts.parser_strictness == Strictness::Strict;
+    let mut unreliable = 0;
+    for ((path, _, lang), (parsed, took)) in files.iter().zip(&timed) {
+        if opts.verbose {
+            eprintln!("{}: {}, {}, parsed in {:.1?}", path.display(), lang.name(), parsed.summary(), took);
+        }
+        if opts.verbose || strict {
+            for x in &parsed.diagnostics {
+                eprintln!("{}:{}:{}: {}: {}", path.display(), x.line, x.column, if strict { "error" } else { "warning" }, x.message);
+            }
+        }
+        if !parsed.diagnostics.is_empty() {
+            unreliable += 1;
+        }
+    }
+    match unreliable {
+        n if n > 0 && strict => Err(SpidiorError::language(format!("{} file{} could not be parsed reliably, which --parser-strictness strict denies", n, if n == 1 { "" } else { "s" }))),
+        _ => Ok(timed.into_iter().map(|(parsed, _)| parsed).collect()),
+    }
+}
+
+/// Lists the symbols parsed from each file beneath `path`, as `spidior
+/// symbols` and `--dump` do
+fn symbols(opts: &Opts, path: &str, listing: Listing) -> Result<(), SpidiorError> {
+    let registry = Registry::builtin();
+    let cache = ParseCache::new(if opts.no_cache { None } else { ParseCache::default_dir() });
+    let mut files = Vec::new();
+    for entry in get_dir_iter(opts.recursive, path) {
+        let path = entry.path();
+        if path.is_file() {
+            if let Ok(contents) = fs::read_to_string(path) {
+                let lang = language(opts, &registry, path, &contents)?;
+                files.push((path.to_path_buf(), contents, lang));
+            }
+        }
+    }
+    let parsed = parse_files(opts, &cache, files.iter().map(|(path, contents, lang)| (path.as_path(), contents.as_str(), *lang)))?;
+    if let Some(delimited) = listing.delimited() {
+        println!("{}", delimited.row(&spidior::symbols::COLUMNS));
+    }
+    for ((path, contents, lang), parsed) in files.iter().zip(parsed) {
+        match listing {
+            Listing::Table => print!("{} ({})\n{}\n", path.display(), lang.name(), spidior::symbols::table(contents, &parsed)),
+            Listing::Json => println!("{}", spidior::symbols::json(&path.display().to_string(), lang.name(), contents, &parsed)),
+            Listing::Csv | Listing::Tsv => print!("{}", spidior::symbols::delimited(&path.display().to_string(), contents, &parsed, listing.delimited().unwrap())),
+        }
+    }
+    Ok(())
+}
+
+fn replace(opts: Opts) -> Result<(), SpidiorError> {
+    let mut replaces = Vec::new();
+    let mut warnings = 0;
+    for query in &opts.query {
+        // A query that cannot be split into its parts fails to parse below
+        for warning in regexparser::lint::lint(query).unwrap_or_default() {
+            eprintln!("warning: `{}`: {}", query, warning);
+            warnings += 1;
+        }
+    }
+    for pattern in &opts.pattern {
+        for warning in regexparser::lint::lint_pattern(pattern) {
+            eprintln!("warning: `{}`: {}", pattern, warning);
+            warnings += 1;
+        }
+    }
+    if opts.deny_warnings && warnings > 0 {
+        return Err(SpidiorError::parse(format!("{} warning{} in the queries, which --deny-warnings denies", warnings, if warnings == 1 { "" } else { "s" })));
+    }
+    for query in &opts.query {
+        let replace = regexparser::parse(query)?;
+        if opts.nfa {
+            let (nfa, _start, _end) = build_nfa(replace.clone().find);
+            println!("NFA is `{:?}`", nfa);
+        }
+        if opts.engine == Engine::Derivatives && replace.replace.replacements.iter().any(|x| matches!(x, ReplaceItem::BackRef(_) | ReplaceItem::Function(..) | ReplaceItem::Arithmetic(..) | ReplaceItem::Conditional(..) | ReplaceItem::Argument(_))) {
+            return Err(SpidiorError::parse(format!("The derivatives engine finds no groups, so cannot replace `{}`, which has backreferences", query)));
+        }
+        replaces.push(replace);
+    }
+    // The patterns given by `-e` are matched as one query, whose matches
+    // are each put down to the pattern that made them
+    let patterns = match opts.pattern.as_slice() {
+        [] => None,
+        x => Some(PatternSet::new(x.iter().map(|x| regexparser::parse_pattern(x)).collect::<Result<_, _>>()?)),
+    };
+    let patterns = patterns.map(|set| {
+        let query = opts.pattern.iter().map(|x| format!("-e {}", x)).collect::<Vec<_>>().join(" ");
+        let replace = Replace {
+            find: set.regex(),
+            replace: Box::new(Replacement { replacements: Vec::new() }),
+            global: true,
+            location: Box::new(Location::All),
+            count: false,
+            command: Command::Substitute,
+        };
+        (set, query, replace)
+    });
+    if patterns.is_some() && !opts.format.lists_matches() {
+        return Err(SpidiorError::parse("Patterns are only found, so -e needs --format vimgrep, emacs, csv, or tsv"));
+    }
+    if patterns.is_some() && opts.engine == Engine::Derivatives {
+        return Err(SpidiorError::parse("The derivatives engine finds no groups, so cannot tell which pattern given by -e made a match"));
+    }
+    let registry = Registry::builtin();
+    let cache = ParseCache::new(if opts.no_cache { None } else { ParseCache::default_dir() });
+    let mut disagreements = 0;
+    // How many files went over `--timeout-per-file` or `--memory-limit`
+    let mut over = 0;
+
+    if opts.archives && !cfg!(feature = "archives") {
+        return Err(SpidiorError::parse("--archives needs spidior built with the `archives` feature"));
+    }
+    if opts.preview && !opts.format.lists_matches() {
+        return Err(SpidiorError::parse("Only matches are previewed, so --preview needs --format vimgrep, emacs, csv, or tsv"));
+    }
+    if opts.archives && !opts.format.lists_matches() {
+        return Err(SpidiorError::parse("Archives are only searched, so --archives needs --format vimgrep, emacs, csv, or tsv"));
+    }
+
+    let mut files = Vec::new();
     for entry in get_dir_iter(opts.recursive, &opts.path)
     {
         let path = entry.path();
+        #[cfg(feature = "archives")]
+        if opts.archives && spidior::archive::is_archive(path) && path.is_file() {
+            for (path, bytes) in spidior::archive::read(path)? {
+                let source = Source::new(bytes);
+                if let Some(text) = source.text() {
+                    let lang = language(&opts, &registry, &path, text)?;
+                    files.push((path, source, lang));
+                }
+            }
+            continue;
+        }
         if path.is_file() {
-            if let Ok(contents) = fs::read_to_string(path) {
-                let f_name = entry.file_name().to_string_lossy();
-                println!("Parsing file {}", f_name);
-                println!("\tFunctions: {:?}", c.read_functions(&contents));
-                println!("\tIdentifiers: {:?}", c.read_identifiers(&contents));
+            if let Some(source) = read(path) {
+                let lang = language(&opts, &registry, path, source.text().unwrap_or_default())?;
+                files.push((path.to_path_buf(), source, lang));
+            }
+        }
+    }
+    // Parse every file up front, in parallel, so that only matching is left
+    let parsed = parse_files(&opts, &cache, files.iter().map(|(path, source, lang)| (path.as_path(), source.text().unwrap_or_default(), *lang)))?;
+    if opts.stats {
+        print_stats(&opts, &replaces, &parsed);
+    }
+    if opts.format.lists_matches() {
+        let style = Style::detect(
+            io::stdout().is_terminal(),
+            std::env::var("NO_COLOR").ok().as_deref(),
+            terminal_size::terminal_size().map(|(terminal_size::Width(x), _)| x as usize),
+            opts.max_columns,
+        );
+        if let Some(header) = opts.format.header() {
+            println!("{}", header);
+        }
+        'files: for ((path, source, _), parsed) in files.iter().zip(parsed) {
+            let contents = source.text().unwrap_or_default();
+            let budget = budget(&opts);
+            let mut matches = Vec::new();
+            for (query, replace) in opts.query.iter().zip(&replaces) {
+                match find(&opts, path, contents, query, replace, &parsed, &budget, &mut disagreements) {
+                    Ok(found) => matches.extend(found.into_iter().map(|m| (m, Some(replace), None))),
+                    Err(e) => {
+                        over_limit(path, e, &mut over)?;
+                        continue 'files;
+                    }
+                }
+            }
+            if let Some((set, query, replace)) = &patterns {
+                match find(&opts, path, contents, query, replace, &parsed, &budget, &mut disagreements) {
+                    Ok(found) => matches.extend(found.into_iter().map(|m| {
+                        let label = set.which(&m).map(|x| opts.pattern[x].as_str());
+                        (m, None, label)
+                    })),
+                    Err(e) => {
+                        over_limit(path, e, &mut over)?;
+                        continue 'files;
+                    }
+                }
+            }
+            matches.sort_by_key(|(m, _, _)| m.start());
+            matches.dedup_by_key(|(m, _, _)| m.start());
+            let path = path.display().to_string();
+            for (m, replace, label) in matches {
+                // Patterns given by `-e`, and commands on whole lines, have
+                // no replacement to preview
+                let with = replace.filter(|x| opts.preview && x.command == Command::Substitute).map(|x| nfa::replacer::replace_to_string(&x.replace, &m, contents)).transpose()?;
+                println!("{}", opts.format.styled(&path, contents, &m, with.as_deref(), label, &style).unwrap_or_default());
+            }
+        }
+        limited(over)?;
+        return disagreed(disagreements);
+    }
+    let mut out = Output::new();
+    // Queries with the `n` flag only count what they would replace, so a
+    // run of nothing but them prints no files
+    let counting = replaces.iter().any(|x| x.count);
+    let (mut total, mut counted) = (0, 0);
+    let mut collisions = 0;
+    // The edits of each file, which are only written once every file has
+    // been checked for collisions
+    let mut changed = Vec::new();
+    'files: for ((path, source, _), parsed) in files.iter().zip(parsed) {
+        let contents = source.text().unwrap_or_default();
+        // Generated files are skipped before they are matched, though
+        // `write_edits` would skip them too
+        if skip_generated(&opts, path, contents) {
+            continue;
+        }
+        let f_name = path.file_name().unwrap_or_default().to_string_lossy();
+        // Every query is matched against the original file, and their edits
+        // are combined, so a conflict is reported rather than one query
+        // corrupting the text another matched
+        let mut edits = EditSet::new();
+        let mut counts = Vec::new();
+        let budget = budget(&opts);
+        for (query, replace) in opts.query.iter().zip(&replaces) {
+            let matches = match find(&opts, path, contents, query, replace, &parsed, &budget, &mut disagreements) {
+                Ok(x) => x,
+                Err(e) => {
+                    over_limit(path, e, &mut over)?;
+                    continue 'files;
+                }
+            };
+            if replace.count {
+                counts.extend(matches);
+                continue;
+            }
+            if opts.check_collisions {
+                for (m, x) in nfa::replacer::collisions(replace, &matches, &parsed) {
+                    let (line, column) = m.position();
+                    eprintln!("{}:{}:{}: error: `{}` would be renamed to `{}`, which is declared at {}:{}", path.display(), line, column, m.text(), x.name, x.line, x.column);
+                    collisions += 1;
+                }
+            }
+            let found = nfa::replacer::edits_found(contents, replace, if opts.interactive { ask } else { |_, _| true }, matches)?.with_origin(query);
+            for report in edits.extend(found, opts.on_conflict, contents, &path.display().to_string())? {
+                eprintln!("{}", report);
+            }
+        }
+        let n = nfa::replacer::count(&counts);
+        if n > 0 {
+            println!("{}: {} replacement{}", path.display(), n, if n == 1 { "" } else { "s" });
+            total += n;
+            counted += 1;
+        }
+        if replaces.iter().all(|x| x.count) {
+            continue;
+        }
+        if opts.reindent {
+            edits = edits.reindented(contents);
+        }
+        changed.push((path, f_name, contents, edits));
+    }
+    if collisions > 0 {
+        return Err(SpidiorError::edit(format!("{} rename collision{} found, so no files were changed", collisions, if collisions == 1 { "" } else { "s" })));
+    }
+    for (path, f_name, contents, edits) in changed {
+        progress(&opts, f_name);
+        write_edits(&opts, &mut out, path, contents, &edits)?;
+    }
+    out.finish(&opts)?;
+    if counting {
+        println!("{} replacement{} in {} file{}", total, if total == 1 { "" } else { "s" }, counted, if counted == 1 { "" } else { "s" });
+    }
+    limited(over)?;
+    disagreed(disagreements)
+}
+
+/// Prints to stderr, for each query set of each query, how many identifiers
+/// across every file each of its predicates accepts, as `--stats` does
+fn print_stats(opts: &Opts, replaces: &[Replace], parsed: &[Parsed]) {
+    let engines: Vec<QueryEngine> = parsed.iter().map(|x| QueryEngine::build(x, opts.resolve_aliases)).collect();
+    for (query, replace) in opts.query.iter().zip(replaces) {
+        for criteria in nfa::matcher::query_sets(replace.find.clone()) {
+            let mut stats = Vec::new();
+            for engine in &engines {
+                for (i, stat) in engine.stats(&criteria).into_iter().enumerate() {
+                    match stats.get_mut(i) {
+                        Some(Stat { accepted, total, .. }) => {
+                            *accepted += stat.accepted;
+                            *total += stat.total;
+                        }
+                        None => stats.push(stat),
+                    }
+                }
+            }
+            for stat in stats {
+                eprintln!("`{}`: {}", query, stat);
+            }
+        }
+    }
+}
+
+/// Finds the matches of a query in a file with the engine given by
+/// `--engine`, leaving out those `--skip-strings` and `--skip-disabled`
+/// leave alone, and under `differential`, printing where the engines
+/// disagree and counting it in `disagreements`. Either engine is matched
+/// within `budget`.
+#[allow(clippy::too_many_arguments)]
+fn find(opts: &Opts, path: &Path, contents: &str, query: &str, replace: &Replace, parsed: &Parsed, budget: &Budget, disagreements: &mut usize) -> Result<Vec<Match>, SpidiorError> {
+    let mut found = match opts.engine {
+        Engine::Nfa => nfa::matcher::find_within(contents, replace.find.clone(), parsed, opts.resolve_aliases, budget)?,
+        Engine::Derivatives => derivative::find(contents, &replace.find, budget)?,
+        Engine::Differential => {
+            let found = nfa::matcher::find_within(contents, replace.find.clone(), parsed, opts.resolve_aliases, budget)?;
+            if let Some(report) = derivative::disagreement(contents, &found, &derivative::find(contents, &replace.find, budget)?) {
+                eprintln!("{}:{} for `{}`", path.display(), report, query);
+                *disagreements += 1;
+            }
+            found
+        }
+    };
+    if opts.skip_strings {
+        found = nfa::matcher::skip_literals(found, &parsed.spans);
+    }
+    if opts.skip_disabled {
+        found = nfa::matcher::skip_disabled(found, &parsed.spans);
+    }
+    Ok(found)
+}
+
+/// Starts the budget for matching a file, given by `--timeout-per-file` and
+/// `--memory-limit`
+fn budget(opts: &Opts) -> Budget {
+    Budget::new(opts.timeout_per_file, opts.memory_limit)
+}
+
+/// Reports a file that went over its budget, counting it in `over`, or
+/// passes on any other error
+fn over_limit(path: &Path, e: SpidiorError, over: &mut usize) -> Result<(), SpidiorError> {
+    match e {
+        SpidiorError::LimitError(_) => {
+            eprintln!("{}: error: {}, so it was left alone", path.display(), e);
+            *over += 1;
+            Ok(())
+        }
+        e => Err(e),
+    }
+}
+
+/// Fails if any files went over their budget
+fn limited(over: usize) -> Result<(), SpidiorError> {
+    match over {
+        0 => Ok(()),
+        n => Err(SpidiorError::limit(format!("{} file{} went over a resource limit", n, if n == 1 { "" } else { "s" }))),
+    }
+}
+
+/// Reads a number of seconds, like `2` or `0.5`, for `--timeout-per-file`
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|x| Duration::try_from_secs_f64(x).ok())
+        .ok_or_else(|| format!("`{}` is not a number of seconds", s))
+}
+
+/// Reads a size in bytes, like `1024`, or with a suffix of `K`, `M`, or `G`
+/// for kibibytes, mebibytes, or gibibytes, like `64M`, for `--memory-limit`
+fn parse_size(s: &str) -> Result<usize, String> {
+    let (digits, scale) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&s[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|x| x.checked_mul(scale))
+        .ok_or_else(|| format!("`{}` is not a size, like `4096` or `64M`", s))
+}
+
+/// Fails if `--engine differential` found the engines disagreeing
+fn disagreed(disagreements: usize) -> Result<(), SpidiorError> {
+    match disagreements {
+        0 => Ok(()),
+        n => Err(SpidiorError::compile(format!("The engines disagreed {} time{}", n, if n == 1 { "" } else { "s" }))),
+    }
+}
+
+/// Runs `fuzz-compare`, printing each case on which spidior and the `regex`
+/// crate differ, and the seed, so that the run can be repeated
+fn fuzz_compare(f: &FuzzCompare) -> Result<(), SpidiorError> {
+    let seed = f.seed.unwrap_or_else(|| std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(1, |x| x.as_nanos() as u64));
+    let divergences = fuzz::run(f.cases, seed);
+    for d in &divergences {
+        println!("{}", d);
+    }
+    println!("{} cases, {} divergences, with seed {}", f.cases, divergences.len(), seed);
+    match divergences.len() {
+        0 => Ok(()),
+        n => Err(SpidiorError::compile(format!("spidior and the regex crate differed on {} case{}", n, if n == 1 { "" } else { "s" }))),
+    }
+}
+
+fn serve(opts: &Opts, s: &Serve) -> Result<(), SpidiorError> {
+    let daemon = Daemon::new(ParseCache::new(if opts.no_cache { None } else { ParseCache::default_dir() }));
+    match &s.socket {
+        #[cfg(unix)]
+        Some(path) => daemon::serve_socket(daemon, Path::new(path)),
+        #[cfg(not(unix))]
+        Some(_) => Err(SpidiorError::parse("--socket is only supported on Unix")),
+        None => daemon::serve(&std::sync::Mutex::new(daemon), std::io::stdin().lock(), std::io::stdout().lock()),
+    }
+}
+
+/// Reads the rule packs given by `--rules`
+fn rule_pack(opts: &Opts) -> Result<RulePack, SpidiorError> {
+    if opts.rules.is_empty() {
+        return Err(SpidiorError::parse("expected a rule pack given by --rules"));
+    }
+    RulePack::load(&opts.rules)
+}
+
+/// A file read and parsed, with its language and parse results
+type ParsedFile<'a> = (std::path::PathBuf, Source, &'a dyn Language, Parsed);
+
+/// Reads and parses every file to run rules or import edits over, in the
+/// language `registry` picks for it
+fn parsed_files<'a>(opts: &Opts, registry: &'a Registry) -> Result<Vec<ParsedFile<'a>>, SpidiorError> {
+    let cache = ParseCache::new(if opts.no_cache { None } else { ParseCache::default_dir() });
+    let mut files = Vec::new();
+    for entry in get_dir_iter(opts.recursive, &opts.path) {
+        let path = entry.path();
+        if path.is_file() {
+            if let Some(source) = read(path) {
+                let lang = language(opts, registry, path, source.text().unwrap_or_default())?;
+                files.push((path.to_path_buf(), source, lang));
+            }
+        }
+    }
+    let parsed = parse_files(opts, &cache, files.iter().map(|(path, source, lang)| (path.as_path(), source.text().unwrap_or_default(), *lang)))?;
+    Ok(files.into_iter().zip(parsed).map(|((path, source, lang), parsed)| (path, source, lang, parsed)).collect())
+}
+
+/// Prints every place a rule is broken, like `src/a.c:3:5: error[no-goto]:
+/// Use structured control flow`, failing if any rule broken is an error
+fn check(opts: &Opts) -> Result<(), SpidiorError> {
+    let pack = rule_pack(opts)?;
+    let registry = Registry::builtin();
+    let files = parsed_files(opts, &registry)?;
+    let mut errors = 0;
+    let mut over = 0;
+    'files: for (path, source, _, parsed) in &files {
+        let contents = source.text().unwrap_or_default();
+        let index = LineIndex::new(contents);
+        let budget = budget(opts);
+        let mut found = Vec::new();
+        for rule in &pack.rules {
+            match rule.check_within(contents, parsed, &budget) {
+                Ok(matches) => found.extend(matches.into_iter().map(|m| (m.start(), rule))),
+                Err(e) => {
+                    over_limit(path, e, &mut over)?;
+                    continue 'files;
+                }
+            }
+        }
+        found.sort_by_key(|(start, _)| *start);
+        for (start, rule) in found {
+            let (line, column) = index.position(start);
+            println!("{}:{}:{}: {}[{}]: {}", path.display(), line, column, rule.severity, rule.name, rule.description);
+            if rule.severity == Severity::Error {
+                errors += 1;
+            }
+        }
+    }
+    match errors {
+        0 => limited(over),
+        n => Err(SpidiorError::edit(format!("{} rule error{} found", n, if n == 1 { "" } else { "s" }))),
+    }
+}
+
+/// Applies the fix of every rule that has one, combining the fixes to each
+/// file as the queries of a run are combined
+fn fix(opts: &Opts) -> Result<(), SpidiorError> {
+    let pack = rule_pack(opts)?;
+    let registry = Registry::builtin();
+    let files = parsed_files(opts, &registry)?;
+    let mut out = Output::new();
+    for (path, source, lang, parsed) in &files {
+        let contents = source.text().unwrap_or_default();
+        let (mut edits, reports) = pack.fix(contents, parsed, *lang, opts.on_conflict, &path.display().to_string())?;
+        for report in reports {
+            eprintln!("{}", report);
+        }
+        if opts.reindent {
+            edits = edits.reindented(contents);
+        }
+        progress(opts, path.display());
+        write_edits(opts, &mut out, path, contents, &edits)?;
+    }
+    out.finish(opts)
+}
+
+/// Checks each rule that has examples against them, printing `ok` or `FAIL`
+/// and the name of each, with what it got wrong
+fn test_rules(opts: &Opts, t: &TestRules) -> Result<(), SpidiorError> {
+    let pack = if t.rules.is_empty() { rule_pack(opts)? } else { RulePack::load(&t.rules)? };
+    let registry = Registry::builtin();
+    let (mut passed, mut failed) = (0, 0);
+    for rule in pack.rules.iter().filter(|x| x.examples() > 0) {
+        let name = rule.language.as_deref().unwrap_or("clike");
+        let lang = registry.by_name(name).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}` in rule `{}`", name, rule.name)))?;
+        let failures = rule.test(lang)?;
+        if failures.is_empty() {
+            println!("ok    {} ({} example{})", rule.name, rule.examples(), if rule.examples() == 1 { "" } else { "s" });
+            passed += 1;
+        } else {
+            println!("FAIL  {}", rule.name);
+            for failure in failures {
+                println!("    {}", failure);
+            }
+            failed += 1;
+        }
+    }
+    println!("{} rule{} passed, {} failed", passed, if passed == 1 { "" } else { "s" }, failed);
+    match failed {
+        0 => Ok(()),
+        n => Err(SpidiorError::edit(format!("{} rule{} failed {} examples", n, if n == 1 { "" } else { "s" }, if n == 1 { "its" } else { "their" }))),
+    }
+}
+
+/// Removes, dedupes, or sorts the imports of each file, writing the edits as
+/// a run writes those of its queries
+fn imports(opts: &Opts, i: &Imports) -> Result<(), SpidiorError> {
+    let registry = Registry::builtin();
+    let files = parsed_files(opts, &registry)?;
+    let mut out = Output::new();
+    for (path, source, _, parsed) in &files {
+        let contents = source.text().unwrap_or_default();
+        let edits = spidior::editing::imports::tidy(contents, &parsed.imports, &i.remove, i.dedupe, i.sort)?.with_origin("imports");
+        progress(opts, path.display());
+        write_edits(opts, &mut out, path, contents, &edits)?;
+    }
+    out.finish(opts)
+}
+
+/// Runs an analysis over each file, printing what it finds
+fn analyze(opts: &Opts, a: &Analyze) -> Result<(), SpidiorError> {
+    let registry = Registry::builtin();
+    let files = parsed_files(opts, &registry)?;
+    let string = |x: &str| Value::String(x.to_string());
+    match a.analysis {
+        Analysis::Shadowing(ref f) => {
+            if let Some(delimited) = f.format.delimited() {
+                println!("{}", delimited.row(&SHADOW_COLUMNS));
+            }
+            for (path, source, lang, parsed) in &files {
+                let path = path.display().to_string();
+                for x in spidior::analysis::shadowing(source.text().unwrap_or_default(), *lang, parsed) {
+                    let (inner, outer) = (&x.inner, &x.outer);
+                    match f.format {
+                        Listing::Table => println!("{}:{}:{}: `{}` shadows the declaration at {}:{}", path, inner.line, inner.column, inner.name, outer.line, outer.column),
+                        Listing::Json => println!(
+                            "{}",
+                            Value::Object(vec![
+                                ("path".to_string(), string(&path)),
+                                ("line".to_string(), Value::Number(inner.line as i64)),
+                                ("column".to_string(), Value::Number(inner.column as i64)),
+                                ("name".to_string(), string(&inner.name)),
+                                ("shadows".to_string(), Value::Object(vec![("line".to_string(), Value::Number(outer.line as i64)), ("column".to_string(), Value::Number(outer.column as i64))])),
+                            ])
+                        ),
+                        Listing::Csv | Listing::Tsv => {
+                            let fields = [path.clone(), inner.line.to_string(), inner.column.to_string(), inner.name.clone(), outer.line.to_string(), outer.column.to_string()];
+                            println!("{}", f.format.delimited().unwrap().row(&fields));
+                        }
+                    }
+                }
+            }
+        }
+        Analysis::Types(ref t) => {
+            if let Some(delimited) = t.format.delimited() {
+                println!("{}", delimited.row(&CENSUS_COLUMNS));
+            }
+            let mut total = Census::new();
+            for (path, source, lang, parsed) in &files {
+                let census = Census::of(source.text().unwrap_or_default(), *lang, parsed);
+                total.add(&census);
+                match t.format {
+                    Listing::Table if !census.counts.is_empty() => print!("{} ({})\n{}\n", path.display(), lang.name(), census.table()),
+                    Listing::Table => {}
+                    Listing::Json => println!("{}", Value::Object(vec![("path".to_string(), string(&path.display().to_string())), ("language".to_string(), string(lang.name())), ("types".to_string(), census.json())])),
+                    Listing::Csv | Listing::Tsv => print!("{}", census.delimited(&path.display().to_string(), t.format.delimited().unwrap())),
+                }
+            }
+            match t.format {
+                Listing::Table => print!("total ({} file{})\n{}", files.len(), if files.len() == 1 { "" } else { "s" }, total.table()),
+                Listing::Json => println!("{}", Value::Object(vec![("total".to_string(), Value::Bool(true)), ("files".to_string(), Value::Number(files.len() as i64)), ("types".to_string(), total.json())])),
+                Listing::Csv | Listing::Tsv => print!("{}", total.delimited("", t.format.delimited().unwrap())),
+            }
+        }
+        Analysis::Duplicates(ref f) => {
+            if let Some(delimited) = f.format.delimited() {
+                println!("{}", delimited.row(&DUPLICATE_COLUMNS));
+            }
+            let paths: Vec<String> = files.iter().map(|(path, ..)| path.display().to_string()).collect();
+            for x in spidior::analysis::duplicates(paths.iter().map(String::as_str).zip(files.iter().map(|(.., parsed)| parsed))) {
+                match f.format {
+                    Listing::Table => {
+                        println!("`{}` is declared in more than one file:", x.name);
+                        for (path, f) in x.functions {
+                            println!("    {}:{}:{}", path, f.line, f.column);
+                        }
+                    }
+                    Listing::Json => {
+                        let functions = x
+                            .functions
+                            .iter()
+                            .map(|(path, f)| Value::Object(vec![("path".to_string(), string(path)), ("line".to_string(), Value::Number(f.line as i64)), ("column".to_string(), Value::Number(f.column as i64))]))
+                            .collect();
+                        println!("{}", Value::Object(vec![("name".to_string(), string(&x.name)), ("functions".to_string(), Value::Array(functions))]));
+                    }
+                    Listing::Csv | Listing::Tsv => {
+                        for (path, function) in &x.functions {
+                            println!("{}", f.format.delimited().unwrap().row(&[x.name.clone(), path.clone(), function.line.to_string(), function.column.to_string()]));
+                        }
+                    }
+                }
             }
         }
     }
     Ok(())
 }
 
-fn replace(opts: Opts) -> Result<(), Box<dyn Error>> {
-    let replace = regexparser::parse(&opts.query.unwrap())?;
-    if opts.nfa {
-        let (nfa, _start, _end) = build_nfa(replace.clone().find);
-        println!("NFA is `{:?}`", nfa);
+/// Reads records from stdin, parsing each as the language given by `--lang`,
+/// or `clike`, and prints those the pattern matches, flushing each, so that
+/// spidior can sit in a pipeline as `grep` or `sed` would
+fn match_one(opts: &Opts, m: &MatchOne) -> Result<(), SpidiorError> {
+    let query = regexparser::parse_substitution(&m.pattern, m.replace.as_deref().unwrap_or_default())?;
+    let registry = Registry::builtin();
+    let name = opts.lang.as_deref().unwrap_or("clike");
+    let lang = registry.by_name(name).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}`", name)))?;
+    let delimiter = if m.null { b'\0' } else { b'\n' };
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut record = Vec::new();
+    loop {
+        record.clear();
+        let read = input.read_until(delimiter, &mut record).map_err(|e| SpidiorError::io("Could not read stdin", e))?;
+        if read == 0 {
+            return Ok(());
+        }
+        if record.last() == Some(&delimiter) {
+            record.pop();
+        }
+        let text = String::from_utf8_lossy(&record);
+        let parsed = Parsed::new(&text, lang);
+        if nfa::matcher::find_parsed(&text, query.find.clone(), &parsed, opts.resolve_aliases).is_empty() {
+            continue;
+        }
+        let text = match m.replace {
+            Some(_) => nfa::replacer::edits_parsed(&text, query.clone(), |_, _| true, &parsed, opts.skip_strings, opts.resolve_aliases, opts.skip_disabled)?.apply(&text)?,
+            None => text.into_owned(),
+        };
+        output
+            .write_all(text.as_bytes())
+            .and_then(|_| output.write_all(&[delimiter]))
+            .and_then(|_| output.flush())
+            .map_err(|e| SpidiorError::io("Could not write stdout", e))?;
+    }
+}
+
+/// Reads and parses the files, then runs queries typed on stdin over them,
+/// keeping a history of the queries in `~/.spidior_history`
+fn repl(opts: &Opts) -> Result<(), SpidiorError> {
+    let registry = Registry::builtin();
+    let cache = ParseCache::new(if opts.no_cache { None } else { ParseCache::default_dir() });
+    let mut files = Vec::new();
+    for entry in get_dir_iter(opts.recursive, &opts.path) {
+        let path = entry.path();
+        if path.is_file() {
+            if let Ok(contents) = fs::read_to_string(path) {
+                let lang = language(opts, &registry, path, &contents)?;
+                files.push((path.to_path_buf(), contents, lang));
+            }
+        }
     }
+    let parsed = parse_files(opts, &cache, files.iter().map(|(path, contents, lang)| (path.as_path(), contents.as_str(), *lang)))?;
+    let mut repl = Repl::new();
+    repl.skip_strings = opts.skip_strings;
+    repl.resolve_aliases = opts.resolve_aliases;
+    repl.skip_disabled = opts.skip_disabled;
+    for ((path, contents, lang), parsed) in files.into_iter().zip(parsed) {
+        repl.add(&path, contents, lang, parsed);
+    }
+    let count = repl.files().len();
+    eprintln!("Read {} file{}, type :help for commands", count, if count == 1 { "" } else { "s" });
+    let history = std::env::var_os("HOME").map(|x| Path::new(&x).join(".spidior_history"));
+    spidior::repl::run(&mut repl, io::stdin().lock(), io::stdout().lock(), history.as_deref())
+}
 
+/// Renames the files and directories beneath `--path` that a substitution
+/// matches, printing each rename
+fn rename_files(opts: &Opts, r: &RenameFiles) -> Result<(), SpidiorError> {
+    let renames = rename::plan(Path::new(&opts.path), opts.recursive, &r.substitution)?;
+    for x in &renames {
+        println!("{} -> {}", x.from.display(), x.to.display());
+    }
+    if r.dry_run {
+        return Ok(());
+    }
+    rename::apply(&renames, r.git)
+}
+
+/// Runs the steps of a migration script over `--path`, printing what each
+/// step did, then writes the result and appends every step to the journal
+/// given by `--journal`
+fn run_script(opts: &Opts, r: &RunScript) -> Result<(), SpidiorError> {
+    let migration = Migration::load(Path::new(&r.script))?;
+    let mut workspace = Workspace::load(Path::new(&opts.path), opts.recursive, opts.lang.as_deref(), opts.include_generated)?;
+    for step in &migration.steps {
+        let outcome = workspace.run(step)?;
+        for (path, why) in &outcome.skipped {
+            eprintln!("Skipping {}, as {}; pass --include-generated to replace in it", path.display(), why);
+        }
+        match step.description.as_str() {
+            "" => println!("Step {}", step.name),
+            description => println!("Step {}: {}", step.name, description),
+        }
+        if r.dry_run {
+            print!("{}", outcome.report);
+        } else {
+            println!("\t{} edits to {} files, {} renames", outcome.edits, outcome.files, outcome.renames);
+        }
+    }
+    if r.dry_run {
+        return Ok(());
+    }
+    let journal = workspace.journal().to_string();
+    workspace.commit(r.git)?;
+    if let Some(path) = &opts.journal {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(journal.as_bytes()))
+            .map_err(|e| SpidiorError::io(format!("Could not write {}", path), e))?;
+    }
+    Ok(())
+}
+
+fn structural(opts: &Opts, s: &Structural) -> Result<(), SpidiorError> {
+    let template = structural::Template::parse(&s.template)?;
+    let registry = Registry::builtin();
+    let origin = format!("{} -> {}", s.template, s.rewrite.as_deref().unwrap_or_default());
+    let mut out = Output::new();
+    if let Some(header) = opts.format.header().filter(|_| s.rewrite.is_none()) {
+        println!("{}", header);
+    }
     for entry in get_dir_iter(opts.recursive, &opts.path)
     {
         let path = entry.path();
         if path.is_file() {
             if let Ok(contents) = fs::read_to_string(path) {
                 let f_name = entry.file_name().to_string_lossy();
-                let res = nfa::replacer::replace(&contents, replace.clone(), if opts.interactive { ask } else { |x, y| true} )?;
-                println!("Parsing file {}", f_name);
-                if opts.in_place {
-                    fs::write(path, &res)?;
+                let lang = language(opts, &registry, path, &contents)?;
+                if let Some(rewrite) = &s.rewrite {
+                    let mut edits = structural::edits(&contents, &template, rewrite, lang, if opts.interactive { ask } else { |_, _| true })?.with_origin(&origin);
+                    if opts.reindent {
+                        edits = edits.reindented(&contents);
+                    }
+                    progress(opts, f_name);
+                    write_edits(opts, &mut out, path, &contents, &edits)?;
+                } else if opts.format.lists_matches() {
+                    for m in structural::as_matches(&contents, &template.find(&contents, lang)) {
+                        println!("{}", opts.format.line(&path.display().to_string(), &contents, &m).unwrap_or_default());
+                    }
                 } else {
-                    println!("{}", res);
+                    for m in template.find(&contents, lang) {
+                        println!("{}:{}: {}", f_name, m.start(), &contents[m.start()..m.start() + m.len()]);
+                    }
+                }
+            }
+        }
+    }
+    out.finish(opts)
+}
+
+/// Applies a plan written by `--plan`, as an in-place run would have
+fn apply_plan(opts: &Opts, path: &Path) -> Result<(), SpidiorError> {
+    let plan = Plan::load(path)?;
+    let mut out = Output::new();
+    for file in plan.files() {
+        let contents = fs::read_to_string(file).map_err(|e| SpidiorError::io(format!("Could not read {}", file), e))?;
+        let edits = plan.edits_for(file, &contents)?;
+        progress(opts, file);
+        out.stage(Path::new(file), &contents, &edits)?;
+        if opts.diff {
+            print!("{}", edits.diff(&contents, file)?);
+        }
+    }
+    out.finish(opts)
+}
+
+/// Prints the report of an earlier in-place run, kept beneath the root of
+/// the worktree the current directory is within, or lists the runs with
+/// reports if no ID is given
+fn show_report(r: &ShowReport) -> Result<(), SpidiorError> {
+    let root = report::worktree_root(Path::new(".")).unwrap_or_else(|| PathBuf::from("."));
+    let id = match &r.id {
+        Some(x) => x,
+        None => {
+            for id in Report::list(&root) {
+                match Report::load(&root, &id) {
+                    Ok(x) => {
+                        let (edits, files) = (x.files.iter().map(|x| x.edits).sum::<usize>(), x.files.len());
+                        println!("{}  {}  {} edit{} in {} file{}  {}", id, x.started, edits, if edits == 1 { "" } else { "s" }, files, if files == 1 { "" } else { "s" }, x.queries().join("  "));
+                    }
+                    Err(e) => eprintln!("warning: {}", e),
                 }
             }
+            return Ok(());
         }
+    };
+    let report = Report::load(&root, id)?;
+    if r.json {
+        print!("{}", report.to_json());
+    } else {
+        print!("{}", report.render());
     }
     Ok(())
 }
 
+/// What a run has to write once every file has been edited
+struct Output {
+    /// The files to write back in place
+    tx: Transaction,
+    /// The journal entries for the files written back
+    journal: String,
+    /// The edits to record, if the run is writing a plan
+    plan: Plan,
+    /// The pages of the report, if the run is writing one with `--format html`
+    html: HtmlReport,
+    /// The report of the files written back
+    report: Report,
+    /// The root of the worktree the first file written back is within, or
+    /// the current directory if it is not within one, where the report is
+    /// kept
+    root: Option<PathBuf>,
+}
+
+impl Output {
+    fn new() -> Self {
+        Self {
+            tx: Transaction::new(),
+            journal: String::new(),
+            plan: Plan::new(),
+            html: HtmlReport::new(),
+            report: Report::new(),
+            root: None,
+        }
+    }
+
+    /// Stages the edits to a file to be written back in place
+    fn stage(&mut self, path: &Path, contents: &str, edits: &EditSet) -> Result<(), SpidiorError> {
+        if !edits.is_empty() {
+            self.tx.stage(path, &edits.apply(contents)?)?;
+            self.journal += &edits.journal(contents, &path.display().to_string())?;
+            let root = self.root.get_or_insert_with(|| report::worktree_root(path.parent().unwrap_or(Path::new("."))).unwrap_or_else(|| PathBuf::from(".")));
+            let relative = fs::canonicalize(path).ok().and_then(|x| x.strip_prefix(&*root).ok().map(|x| x.display().to_string()));
+            self.report.add(&relative.unwrap_or_else(|| path.display().to_string()), contents, edits)?;
+        }
+        Ok(())
+    }
+
+    /// Writes every file staged back at once, so that if any cannot be
+    /// written none are, then records the edits in the journal, and the run
+    /// in its report, or writes the plan if the run is writing one. The
+    /// HTML report of `--format html` is written first.
+    fn finish(mut self, opts: &Opts) -> Result<(), SpidiorError> {
+        if let Some(path) = &opts.plan {
+            return fs::write(path, self.plan.to_json()).map_err(|e| SpidiorError::io(format!("Could not write {}", path), e));
+        }
+        if opts.format == Format::Html {
+            let dir = Path::new(&opts.html_dir);
+            self.html.write(dir)?;
+            eprintln!("Wrote the report to {}", dir.join("index.html").display());
+        }
+        let journal = self.journal;
+        self.tx.commit()?;
+        // The files are written by now, so a report that cannot be written
+        // is only warned of
+        if let (Some(root), false) = (&self.root, opts.no_report) {
+            match self.report.write(root) {
+                Ok(_) => eprintln!("Recorded this run as {}; show it again with `spidior report {}`", self.report.id, self.report.id),
+                Err(e) => eprintln!("warning: {}", e),
+            }
+        }
+        if let Some(path) = &opts.journal {
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(journal.as_bytes()))
+                .map_err(|e| SpidiorError::io(format!("Could not write {}", path), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies the edits made to a file, staging the result to be written back if
+/// `in_place` is set, and printing either a diff of the edits or the edited
+/// file, unless the run is writing a plan, which records them instead, or an
+/// HTML report, which gets a page for them instead of them being printed
+fn write_edits(opts: &Opts, out: &mut Output, path: &Path, contents: &str, edits: &EditSet) -> Result<(), SpidiorError> {
+    if skip_generated(opts, path, contents) {
+        return Ok(());
+    }
+    if opts.plan.is_some() {
+        out.plan.add(&path.display().to_string(), contents, edits);
+        return Ok(());
+    }
+    if opts.in_place {
+        out.stage(path, contents, edits)?;
+    }
+    if opts.format == Format::Html {
+        out.html.add(&path.display().to_string(), contents, edits);
+    } else if opts.diff {
+        print!("{}", edits.diff(contents, &path.display().to_string())?);
+    } else if !opts.in_place {
+        println!("{}", edits.apply(contents)?);
+    }
+    Ok(())
+}
+
+/// Whether a file that looks generated should be left alone, as it is
+/// unless `--include-generated` is given, reporting it on stderr if so
+fn skip_generated(opts: &Opts, path: &Path, contents: &str) -> bool {
+    match spidior::source::generated(contents).filter(|_| !opts.include_generated) {
+        Some(why) => {
+            eprintln!("Skipping {}, as {}; pass --include-generated to replace in it", path.display(), why);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Reads a file to search, which is None if it cannot be read or is not
+/// UTF-8, mapping it into memory if it is large and the `mmap` feature is on
+fn read(path: &Path) -> Option<Source> {
+    let source = Source::open(path).ok()?;
+    source.text()?;
+    Some(source)
+}
+
+/// Walks the files and directories under a path, following links, and
+/// leaving out spidior's own `.spidior` directory and keeping one path for
+/// each file reached through hard links or linked directories, as
+/// `source::once_each` picks
 fn get_dir_iter(recursive: bool, path: &str) -> impl Iterator<Item=walkdir::DirEntry> {
     let mut iter = WalkDir::new(path);
     if !recursive {
         iter = iter.max_depth(1);
     }
-    iter.follow_links(true)
+    let entries = iter.follow_links(true)
         .into_iter()
-        .filter_map(|e| e.ok())
+        .filter_entry(|e| e.depth() == 0 || e.file_name() != report::STATE_DIR)
+        .filter_map(|e| e.ok());
+    source::once_each(entries).into_iter()
 }
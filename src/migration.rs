@@ -0,0 +1,422 @@
+//! Provides the migration scripts run by `spidior run`, which make an
+//! ordered list of changes to the files beneath a directory, so that a
+//! migration of many steps can be reviewed and run again. A script is
+//! written in the same subset of TOML as the config file, with a
+//! `[steps.<name>]` section for each step, which run in the order they
+//! appear:
+//!
+//! ```toml
+//! [steps.rename-type]
+//! description = "Session is now Sess"
+//! replace = "%s/Session/Sess/g"
+//!
+//! [steps.move-files]
+//! rename-files = "s/Session/Sess/"
+//!
+//! [steps.imports]
+//! rewrite-import = ["org.old.Session", "org.new.Sess"]
+//! ```
+//!
+//! Each step has one of these, along with an optional `description`:
+//!
+//! * `replace` - a query, or an array of queries whose edits are combined,
+//!   run over the contents of every file
+//! * `rename-files` - a substitution applied to each file and directory
+//!   name, as by `spidior rename-files`
+//! * `rewrite-import` - an old and a new path, which replaces the old path
+//!   in every import of it
+//!
+//! Every step runs against the files as the steps before it left them, and
+//! nothing is written until every step has run.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::editing::{editset::{Conflict, EditSet}, transaction::Transaction};
+use crate::error::SpidiorError;
+use crate::languages::{cache::Parsed, registry::Registry};
+use crate::matcher;
+use crate::regexparser::{self, ast::Replace};
+use crate::rename;
+use crate::replacer;
+use crate::source::{generated, once_each, Generated};
+
+/// What a step of a migration does
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Runs queries over the contents of every file, each with the text it
+    /// was parsed from
+    Replace(Vec<(String, Replace)>),
+    /// Applies a substitution to every file and directory name
+    RenameFiles(Replace),
+    /// Replaces an import path, the first, with another, the second
+    RewriteImport(String, String),
+}
+
+/// A step of a migration
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub name: String,
+    pub description: String,
+    pub action: Action,
+}
+
+/// The steps of a migration script, in the order they run
+#[derive(Debug, Clone, Default)]
+pub struct Migration {
+    pub steps: Vec<Step>,
+}
+
+impl Migration {
+    /// Reads the steps from a migration script
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the script
+    ///
+    /// # Returns
+    ///
+    /// The Migration, or a message describing the first step that is not
+    /// valid
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let config = Config::parse(text)?;
+        let mut steps = Vec::new();
+        for section in config.sections() {
+            let name = match section.strip_prefix("steps.") {
+                Some(x) => x,
+                None => return Err(format!("unknown section `{}`, expected `steps.<name>`", section)),
+            };
+            let actions = ["replace", "rename-files", "rewrite-import"];
+            if let Some(key) = config.keys(section).find(|x| *x != "description" && !actions.contains(x)) {
+                return Err(format!("step `{}`: unknown setting `{}`", name, key));
+            }
+            let mut given = actions.iter().filter_map(|x| Some((*x, config.get(section, x)?)));
+            let action = match (given.next(), given.next()) {
+                (Some(("replace", queries)), None) => Action::Replace(
+                    queries
+                        .iter()
+                        .map(|x| regexparser::parse(x).map(|r| (x.clone(), r)))
+                        .collect::<Result<_, _>>()
+                        .map_err(|e| format!("step `{}`: {}", name, e))?,
+                ),
+                (Some(("rename-files", [query])), None) => Action::RenameFiles(rename::substitution(query).map_err(|e| format!("step `{}`: {}", name, e))?),
+                (Some(("rewrite-import", [old, new])), None) => Action::RewriteImport(old.clone(), new.clone()),
+                (Some((key, _)), None) => return Err(format!("step `{}`: `{}` has the wrong number of values", name, key)),
+                _ => return Err(format!("step `{}`: expected exactly one of `replace`, `rename-files`, or `rewrite-import`", name)),
+            };
+            steps.push(Step {
+                name: name.to_string(),
+                description: config.get(section, "description").and_then(|x| x.first()).cloned().unwrap_or_default(),
+                action,
+            });
+        }
+        Ok(Self { steps })
+    }
+
+    /// Reads the migration script at `path`
+    pub fn load(path: &Path) -> Result<Self, SpidiorError> {
+        let text = std::fs::read_to_string(path).map_err(|e| SpidiorError::io(format!("Could not read {}", path.display()), e))?;
+        Self::parse(&text).map_err(|x| SpidiorError::parse(format!("{}: {}", path.display(), x)))
+    }
+}
+
+/// A file a migration is changing
+struct File {
+    /// Where the file was when the migration started
+    original: PathBuf,
+    /// Where the file is after the steps run so far
+    path: PathBuf,
+    text: String,
+    /// Whether any step has changed the contents
+    changed: bool,
+}
+
+/// What a step did
+#[derive(Debug, Clone, Default)]
+pub struct Outcome {
+    /// A diff of each file whose contents changed, and a line for each
+    /// rename, like `a/Session.java -> a/Sess.java`
+    pub report: String,
+    pub edits: usize,
+    /// How many files the step changed the contents of
+    pub files: usize,
+    pub renames: usize,
+    /// The files the step left alone as they look generated, each with why
+    pub skipped: Vec<(PathBuf, Generated)>,
+}
+
+/// The files beneath a directory, as the steps of a migration change them
+/// in memory until they are committed
+pub struct Workspace {
+    root: PathBuf,
+    files: Vec<File>,
+    /// The name of the language to parse every file as, rather than
+    /// detecting it
+    lang: Option<String>,
+    /// Whether the contents of files that look generated are changed too
+    include_generated: bool,
+    journal: String,
+}
+
+impl Workspace {
+    /// Reads the files beneath a directory, leaving out hidden ones and any
+    /// that are not UTF-8, and reading a file reached through links once
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The directory, or a single file
+    /// * `recursive` - Whether files below the directory's own entries are
+    ///   read too
+    /// * `lang` - The name of the language to parse every file as, or None
+    ///   to detect it from each file
+    /// * `include_generated` - Whether steps change the contents of files
+    ///   that look generated, as `source::generated` tells, rather than
+    ///   listing them in their `Outcome` as skipped
+    pub fn load(root: &Path, recursive: bool, lang: Option<&str>, include_generated: bool) -> Result<Self, SpidiorError> {
+        if let Some(name) = lang {
+            Registry::builtin().by_name(name).ok_or_else(|| SpidiorError::language(format!("Unknown language `{}`", name)))?;
+        }
+        let mut walk = WalkDir::new(root).follow_links(true).sort_by(|a, b| a.file_name().cmp(b.file_name()));
+        if !recursive {
+            walk = walk.max_depth(1);
+        }
+        let entries = walk.into_iter().collect::<Result<Vec<_>, _>>().map_err(|e| SpidiorError::io(format!("Could not read {}", root.display()), e.into()))?;
+        let mut files = Vec::new();
+        for entry in once_each(entries) {
+            if !entry.file_type().is_file() || rename::hidden(root, entry.path()) {
+                continue;
+            }
+            if let Ok(text) = std::fs::read_to_string(entry.path()) {
+                files.push(File {
+                    original: entry.path().to_path_buf(),
+                    path: entry.path().to_path_buf(),
+                    text,
+                    changed: false,
+                });
+            }
+        }
+        Ok(Self {
+            root: root.to_path_buf(),
+            files,
+            lang: lang.map(String::from),
+            include_generated,
+            journal: String::new(),
+        })
+    }
+
+    /// The journal of every step run so far, which has a `# step <name>`
+    /// line before the lines for each step. A content edit has the line
+    /// [`EditSet::journal`] writes, and a rename has a line holding the old
+    /// and new paths, separated by a tab.
+    pub fn journal(&self) -> &str {
+        &self.journal
+    }
+
+    /// Runs a step over the files as the steps before it left them
+    pub fn run(&mut self, step: &Step) -> Result<Outcome, SpidiorError> {
+        self.journal += &format!("# step {}\n", step.name);
+        match &step.action {
+            Action::RenameFiles(replace) => self.rename(replace),
+            action => self.edit(action),
+        }
+    }
+
+    /// Runs a step that changes the contents of files
+    fn edit(&mut self, action: &Action) -> Result<Outcome, SpidiorError> {
+        let registry = Registry::builtin();
+        let mut outcome = Outcome::default();
+        let include_generated = self.include_generated;
+        for file in &mut self.files {
+            if let Some(why) = generated(&file.text).filter(|_| !include_generated) {
+                outcome.skipped.push((file.path.clone(), why));
+                continue;
+            }
+            let lang = match self.lang.as_deref().and_then(|x| registry.by_name(x)) {
+                Some(x) => x,
+                None => registry.detect(&file.path, &file.text),
+            };
+            let parsed = Parsed::new(&file.text, lang);
+            let path = file.path.display().to_string();
+            let edits = match action {
+                Action::Replace(queries) => {
+                    let mut edits = EditSet::new();
+                    for (query, replace) in queries {
+                        let found = replacer::edits_parsed(&file.text, replace.clone(), |_, _| true, &parsed, false, false, false)?.with_origin(query);
+                        edits.extend(found, Conflict::Error, &file.text, &path)?;
+                    }
+                    edits
+                }
+                Action::RewriteImport(old, new) => import_edits(&file.text, &parsed, old, new)?,
+                Action::RenameFiles(_) => EditSet::new(),
+            };
+            if edits.is_empty() {
+                continue;
+            }
+            outcome.report += &edits.diff(&file.text, &path)?;
+            self.journal += &edits.journal(&file.text, &path)?;
+            outcome.edits += edits.len();
+            outcome.files += 1;
+            file.text = edits.apply(&file.text)?;
+            file.changed = true;
+        }
+        Ok(outcome)
+    }
+
+    /// Runs a step that renames files, applying its substitution to each
+    /// part of their paths beneath the root
+    fn rename(&mut self, replace: &Replace) -> Result<Outcome, SpidiorError> {
+        let mut outcome = Outcome::default();
+        let mut moved = Vec::new();
+        for (i, file) in self.files.iter().enumerate() {
+            let relative = match file.path.strip_prefix(&self.root) {
+                Ok(x) if x.as_os_str().is_empty() => Path::new(""),
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            let mut path = self.root.clone();
+            for part in relative.iter() {
+                match part.to_str() {
+                    Some(name) => path.push(rename::renamed(replace, name).map_err(|e| SpidiorError::edit(format!("Cannot rename {}: {}", file.path.display(), e)))?),
+                    None => path.push(part),
+                }
+            }
+            if path != file.path {
+                moved.push((i, path));
+            }
+        }
+        let mut taken: HashSet<&Path> = self.files.iter().map(|x| x.path.as_path()).collect();
+        for (i, _) in &moved {
+            taken.remove(self.files[*i].path.as_path());
+        }
+        for (i, path) in &moved {
+            if !taken.insert(path) {
+                return Err(SpidiorError::edit(format!("Cannot rename {} to {}, which already exists", self.files[*i].path.display(), path.display())));
+            }
+        }
+        for (i, path) in moved {
+            let file = &mut self.files[i];
+            outcome.report += &format!("{} -> {}\n", file.path.display(), path.display());
+            self.journal += &format!("{}\t{}\n", file.path.display(), path.display());
+            outcome.renames += 1;
+            file.path = path;
+        }
+        Ok(outcome)
+    }
+
+    /// Writes the result of every step run: moves each renamed file, then
+    /// writes every changed file at once, then removes the directories the
+    /// moves left empty
+    ///
+    /// # Arguments
+    ///
+    /// * `git` - Whether files are moved with `git mv`, so that git records
+    ///   the renames
+    ///
+    /// # Returns
+    ///
+    /// A Result<(), SpidiorError>, which will Err with an IoError if a file
+    /// cannot be moved or written, or an EditError if a file would be moved
+    /// onto one the migration did not read
+    pub fn commit(self, git: bool) -> Result<(), SpidiorError> {
+        let originals: HashSet<&Path> = self.files.iter().map(|x| x.original.as_path()).collect();
+        for file in self.files.iter().filter(|x| x.path != x.original) {
+            if file.path.exists() && !originals.contains(file.path.as_path()) {
+                return Err(SpidiorError::edit(format!("Cannot move {} to {}, which already exists", file.original.display(), file.path.display())));
+            }
+        }
+        for file in self.files.iter().filter(|x| x.path != x.original) {
+            let failed = |e| SpidiorError::io(format!("Could not move {} to {}", file.original.display(), file.path.display()), e);
+            if let Some(parent) = file.path.parent() {
+                std::fs::create_dir_all(parent).map_err(failed)?;
+            }
+            rename::apply(&[rename::Rename { from: file.original.clone(), to: file.path.clone() }], git)?;
+        }
+        let mut tx = Transaction::new();
+        for file in self.files.iter().filter(|x| x.changed) {
+            tx.stage(&file.path, &file.text)?;
+        }
+        tx.commit()?;
+        // Remove each directory a move emptied, from the deepest up, which
+        // fails and is skipped for any that still hold something
+        for file in self.files.iter().filter(|x| x.path != x.original) {
+            for dir in file.original.ancestors().skip(1) {
+                if !dir.starts_with(&self.root) || dir == self.root || std::fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The edits that replace an import path with another in every import of it
+fn import_edits(text: &str, parsed: &Parsed, old: &str, new: &str) -> Result<EditSet, SpidiorError> {
+    let find = regexparser::parse_pattern(&format!("[[import={}]]", old))?;
+    let mut edits = EditSet::new();
+    for m in matcher::find_parsed(text, find, parsed, false) {
+        if let Some(i) = text[m.start()..m.start() + m.len()].find(old) {
+            edits.add(m.start() + i, m.start() + i + old.len(), new.to_string())?;
+        }
+    }
+    Ok(edits.with_origin(&format!("import {} -> {}", old, new)))
+}
+
+#[test]
+fn test_migration() {
+    let dir = std::env::temp_dir().join(format!("spidior-migration-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("session")).unwrap();
+    std::fs::write(dir.join("session").join("Session.java"), "class Session {}\n").unwrap();
+    std::fs::write(dir.join("Main.java"), "import org.old.Session;\n\nclass Main { Session s; }\n").unwrap();
+    std::fs::write(dir.join("Gen.java"), "// @generated\nclass Gen { Session s; }\n").unwrap();
+    let migration = Migration::parse(
+        "[steps.type]\ndescription = \"Session is now Sess\"\nreplace = \"%s/Session/Sess/g\"\n\n[steps.files]\nrename-files = \"s/[Ss]ession/sess/\"\n\n[steps.imports]\nrewrite-import = [\"org.old.Sess\", \"org.new.Sess\"]\n",
+    )
+    .unwrap();
+    assert_eq!(migration.steps.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(), vec!["type", "files", "imports"]);
+    let mut workspace = Workspace::load(&dir, true, Some("java"), false).unwrap();
+    let outcomes: Vec<Outcome> = migration.steps.iter().map(|x| workspace.run(x).unwrap()).collect();
+    assert_eq!((outcomes[0].edits, outcomes[0].files), (3, 2));
+    assert_eq!(outcomes[0].skipped.iter().map(|x| x.0.clone()).collect::<Vec<_>>(), vec![dir.join("Gen.java")]);
+    assert_eq!(outcomes[1].report, format!("{} -> {}\n", dir.join("session").join("Session.java").display(), dir.join("sess").join("sess.java").display()));
+    assert_eq!(outcomes[2].edits, 1);
+    assert_eq!(workspace.journal().lines().filter(|x| x.starts_with("# step ")).count(), 3);
+    workspace.commit(false).unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join("sess").join("sess.java")).unwrap(), "class Sess {}\n");
+    assert_eq!(std::fs::read_to_string(dir.join("Main.java")).unwrap(), "import org.new.Sess;\n\nclass Main { Sess s; }\n");
+    assert!(!dir.join("session").exists());
+    assert_eq!(std::fs::read_to_string(dir.join("Gen.java")).unwrap(), "// @generated\nclass Gen { Session s; }\n");
+    assert!(Migration::parse("[steps.x]\nreplace = \"%s/a/b/\"\nrename-files = \"s/a/b/\"\n").is_err());
+    assert!(Migration::parse("[steps.x]\nrewrite-import = \"a\"\n").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_migration_links() {
+    let dir = std::env::temp_dir().join(format!("spidior-migration-links-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("real")).unwrap();
+    std::fs::write(dir.join("real").join("a.c"), "int foo;\n").unwrap();
+    std::os::unix::fs::symlink("real", dir.join("also")).unwrap();
+    std::os::unix::fs::symlink(dir.join("real").join("a.c"), dir.join("b.c")).unwrap();
+    let mut workspace = Workspace::load(&dir, true, None, false).unwrap();
+    let step = Migration::parse("[steps.x]\nreplace = \"%s/foo/bar/g\"\n").unwrap().steps.remove(0);
+    let outcome = workspace.run(&step).unwrap();
+    assert_eq!((outcome.edits, outcome.files), (1, 1));
+    assert!(outcome.report.contains(&dir.join("real").join("a.c").display().to_string()));
+    workspace.commit(false).unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join("real").join("a.c")).unwrap(), "int bar;\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_import_path() {
+    use crate::languages::go::Go;
+    // A Go import path holds `/`, which is never taken for the end of a query
+    let text = "package m\n\nimport (\n\t\"fmt\"\n\t\"github.com/x/y\"\n)\n";
+    let edits = import_edits(text, &Parsed::new(text, &Go {}), "github.com/x/y", "github.com/x/z").unwrap();
+    assert_eq!(edits.apply(text).unwrap(), "package m\n\nimport (\n\t\"fmt\"\n\t\"github.com/x/z\"\n)\n");
+}
@@ -0,0 +1,249 @@
+//! Provides combinators for building an `Nfa` directly, out of fragments,
+//! rather than by writing a regex and parsing it
+//!
+//! Each fragment has one state it is entered by and one it is left by.
+//! The combinators take fragments by value and give back the fragment they
+//! make, so each fragment is part of the automaton once. They check that
+//! every fragment belongs to the NFA they are called on.
+//!
+//! ```
+//! use spidior::nfa::{combinators::Repeat, Nfa, NfaModel};
+//! use spidior::matcher;
+//!
+//! // (ab)+|[0-9]
+//! let mut nfa = Nfa::new(Vec::new());
+//! let ab = nfa.literal("ab");
+//! let (ab, _) = nfa.group(ab)?;
+//! let ab = nfa.repeat(ab, Repeat::OneOrMore)?;
+//! let digit = nfa.class("0123456789", false);
+//! let either = nfa.alternate(ab, digit)?;
+//! let model = NfaModel::from_fragment(nfa, either)?;
+//! let found = matcher::find_model("x abab 7", &model);
+//! assert_eq!(found.iter().map(|x| (x.start(), x.len())).collect::<Vec<_>>(), vec![(2, 4), (7, 1)]);
+//! # Ok::<(), spidior::SpidiorError>(())
+//! ```
+
+use super::{Nfa, NfaModel, NodePointer};
+use crate::error::SpidiorError;
+
+/// A piece of an NFA, with the state it is entered by and the state it is
+/// left by
+#[derive(Debug, PartialEq, Eq)]
+pub struct Fragment {
+    start: NodePointer,
+    end: NodePointer,
+}
+
+impl Fragment {
+    /// The state the fragment is entered by
+    pub fn start(&self) -> NodePointer {
+        self.start
+    }
+
+    /// The state the fragment is left by
+    pub fn end(&self) -> NodePointer {
+        self.end
+    }
+}
+
+/// How many times `Nfa::repeat` lets a fragment match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Any number of times, like `*`
+    ZeroOrMore,
+    /// At least once, like `+`
+    OneOrMore,
+    /// At most once
+    Optional,
+}
+
+impl Nfa {
+    /// Makes a fragment matching nothing but the empty string
+    pub fn empty(&mut self) -> Fragment {
+        let start = self.new_node();
+        let end = self.new_node();
+        self.add_transition_epsilon(&start, &end).unwrap();
+        Fragment { start, end }
+    }
+
+    /// Makes a fragment matching a character
+    pub fn char(&mut self, c: char) -> Fragment {
+        let start = self.new_node();
+        let end = self.new_node();
+        self.add_transition_alpha(&start, &end, c).unwrap();
+        Fragment { start, end }
+    }
+
+    /// Makes a fragment matching a string, a character at a time
+    pub fn literal(&mut self, s: &str) -> Fragment {
+        let mut chars = s.chars();
+        let first = match chars.next() {
+            Some(c) => self.char(c),
+            None => return self.empty(),
+        };
+        chars.fold(first, |acc, c| {
+            let next = self.char(c);
+            self.concat(acc, next).unwrap()
+        })
+    }
+
+    /// Makes a fragment matching a character of a set, like `[abc]`, or
+    /// with `negative` set, any character not in it, like `[^abc]`
+    ///
+    /// # Arguments
+    ///
+    /// * `chars` - The characters of the set, with any ranges spelled out
+    /// * `negative` - Whether the fragment matches the characters not in
+    ///   the set
+    pub fn class(&mut self, chars: &str, negative: bool) -> Fragment {
+        let start = self.new_node();
+        let end = self.new_node();
+        if negative {
+            self.add_transition_negativerange(&start, &end, chars.to_string()).unwrap();
+        } else {
+            self.add_transition_range(&start, &end, chars.to_string()).unwrap();
+        }
+        Fragment { start, end }
+    }
+
+    /// Makes a fragment matching any character but a newline, like `.`, or
+    /// with `cluster` set, any grapheme cluster but a line break, as `.`
+    /// does under the `c` flag
+    pub fn any(&mut self, cluster: bool) -> Fragment {
+        let start = self.new_node();
+        let end = self.new_node();
+        self.add_transition_any(&start, &end, cluster).unwrap();
+        Fragment { start, end }
+    }
+
+    /// Makes a fragment matching an identifier that meets criteria, like
+    /// `name=count,type=int`, as a `[[...]]` set does
+    pub fn query_set(&mut self, criteria: &str) -> Fragment {
+        let start = self.new_node();
+        let end = self.new_node();
+        self.add_transition_queryset(&start, &end, criteria.to_string()).unwrap();
+        Fragment { start, end }
+    }
+
+    /// Joins two fragments, so that `b` matches after `a`
+    ///
+    /// # Returns
+    ///
+    /// A Result<Fragment, SpidiorError>, which will Err with a CompileError
+    /// if either fragment belongs to another NFA
+    pub fn concat(&mut self, a: Fragment, b: Fragment) -> Result<Fragment, SpidiorError> {
+        self.add_transition_epsilon(&a.end, &b.start)?;
+        Ok(Fragment {
+            start: a.start,
+            end: b.end,
+        })
+    }
+
+    /// Makes a fragment matching either of two, preferring `a` where both
+    /// match, like `a|b`
+    ///
+    /// # Returns
+    ///
+    /// A Result<Fragment, SpidiorError>, which will Err with a CompileError
+    /// if either fragment belongs to another NFA
+    pub fn alternate(&mut self, a: Fragment, b: Fragment) -> Result<Fragment, SpidiorError> {
+        self.check(&a.start)?;
+        self.check(&b.start)?;
+        let start = self.new_node();
+        let end = self.new_node();
+        self.add_transition_epsilon(&start, &a.start)?;
+        self.add_transition_epsilon(&start, &b.start)?;
+        self.add_transition_epsilon(&a.end, &end)?;
+        self.add_transition_epsilon(&b.end, &end)?;
+        Ok(Fragment { start, end })
+    }
+
+    /// Repeats a fragment, preferring to match it again over leaving it
+    ///
+    /// # Returns
+    ///
+    /// A Result<Fragment, SpidiorError>, which will Err with a CompileError
+    /// if the fragment belongs to another NFA
+    pub fn repeat(&mut self, a: Fragment, times: Repeat) -> Result<Fragment, SpidiorError> {
+        if times != Repeat::Optional {
+            self.add_transition_epsilon(&a.end, &a.start)?;
+        }
+        if times != Repeat::OneOrMore {
+            self.add_transition_epsilon(&a.start, &a.end)?;
+        }
+        Ok(a)
+    }
+
+    /// Makes a group of a fragment, whose bounds are kept with each match,
+    /// like `(a)`
+    ///
+    /// # Returns
+    ///
+    /// A Result<(Fragment, usize), SpidiorError>, where on success, it
+    /// returns the group and its index, for `Match::get_group` and `\1`
+    /// style backreferences, as groups count from one in the order they are
+    /// made. It will Err with a CompileError if the fragment belongs to
+    /// another NFA.
+    pub fn group(&mut self, a: Fragment) -> Result<(Fragment, usize), SpidiorError> {
+        self.check(&a.start)?;
+        let start = self.new_node();
+        let end = self.new_node();
+        self.add_group(&start, &a.start, &a.end, &end)?;
+        Ok((Fragment { start, end }, self.groups()))
+    }
+}
+
+impl NfaModel {
+    /// Makes a model of an NFA whose matches are those of one of its
+    /// fragments
+    ///
+    /// # Returns
+    ///
+    /// A Result<NfaModel, SpidiorError>, which will Err with a CompileError
+    /// if the fragment belongs to another NFA
+    pub fn from_fragment(nfa: Nfa, fragment: Fragment) -> Result<Self, SpidiorError> {
+        nfa.check(&fragment.start)?;
+        nfa.check(&fragment.end)?;
+        Ok(Self::new(nfa, fragment.start, fragment.end))
+    }
+}
+
+#[test]
+fn test_combinators() -> Result<(), SpidiorError> {
+    use super::matcher;
+    let spans = |model: &NfaModel, input: &str| matcher::find_model(input, model).iter().map(|x| (x.start(), x.len())).collect::<Vec<_>>();
+
+    // a(b|c)*d?
+    let mut nfa = Nfa::new(Vec::new());
+    let a = nfa.char('a');
+    let b = nfa.char('b');
+    let c = nfa.class("c", false);
+    let bc = nfa.alternate(b, c)?;
+    let bc = nfa.repeat(bc, Repeat::ZeroOrMore)?;
+    let d = nfa.char('d');
+    let d = nfa.repeat(d, Repeat::Optional)?;
+    let abc = nfa.concat(a, bc)?;
+    let all = nfa.concat(abc, d)?;
+    let model = NfaModel::from_fragment(nfa, all)?;
+    assert_eq!(spans(&model, "abcbd a ad dd"), vec![(0, 5), (6, 1), (8, 2)]);
+
+    // (o*)o, whose group takes all but the last o
+    let mut nfa = Nfa::new(Vec::new());
+    let o = nfa.char('o');
+    let o = nfa.repeat(o, Repeat::ZeroOrMore)?;
+    let (group, index) = nfa.group(o)?;
+    let last = nfa.char('o');
+    let all = nfa.concat(group, last)?;
+    let model = NfaModel::from_fragment(nfa, all)?;
+    let found = matcher::find_model("ooo", &model);
+    assert_eq!((index, found[0].get_group(index, "ooo")), (1, "oo".to_string()));
+
+    let mut nfa = Nfa::new(Vec::new());
+    let mut other = Nfa::new(Vec::new());
+    let a = nfa.literal("ab");
+    let b = other.any(false);
+    assert!(nfa.concat(a, b).is_err());
+    let b = other.empty();
+    assert!(NfaModel::from_fragment(nfa, b).is_err());
+    Ok(())
+}
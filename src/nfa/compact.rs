@@ -0,0 +1,342 @@
+//! Provides a compact form of an NFA, made once it is built, which the
+//! matcher runs when the NFA cannot be made into a tagged DFA
+//!
+//! An `Nfa` keeps a `Vec` of transitions for each node, so it can grow a
+//! transition at a time, and its sets and query sets as strings, so
+//! stepping it chases a pointer for each node and searches or parses a
+//! string for each transition. Here every transition is in one array,
+//! ordered by the state it leaves, with an offset into it for each state.
+//! Sets are kept as their ranges, sorted and with those that overlap or
+//! touch merged, to be searched by halves, and the criteria of query sets
+//! are parsed once. The states a run is in are kept in a list, with a
+//! stamp for each state saying whether it is in the list, rather than in a
+//! `HashSet`.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::queryengine::{Criteria, QueryEngine};
+use super::{Group, Nfa, NodePointer, TransitionType};
+use crate::error::SpidiorError;
+
+/// What a transition of a compact NFA reads, which holds indices into the
+/// NFA's sets and criteria rather than their text
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Epsilon,
+    Alpha(char),
+    Set(usize),
+    NegativeSet(usize),
+    QuerySet(usize),
+    Any,
+    Open(usize),
+    Close(usize),
+}
+
+/// The characters of a set, as its ranges, sorted, with those that overlap
+/// or touch merged, so `cab-` is `[('-', '-'), ('a', 'c')]`
+fn ranges(chars: &str) -> Box<[(char, char)]> {
+    let mut chars: Vec<char> = chars.chars().collect();
+    chars.sort_unstable();
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    for c in chars {
+        match ranges.last_mut() {
+            Some((_, hi)) if *hi == c || char::from_u32(*hi as u32 + 1) == Some(c) => *hi = c,
+            _ => ranges.push((c, c)),
+        }
+    }
+    ranges.into()
+}
+
+/// Whether one of a set's sorted ranges holds `c`
+fn contains(ranges: &[(char, char)], c: char) -> bool {
+    let i = ranges.partition_point(|&(_, hi)| hi < c);
+    ranges.get(i).is_some_and(|&(lo, _)| lo <= c)
+}
+
+/// An NFA laid out for running rather than building
+#[derive(Debug)]
+pub struct Compact {
+    /// The index in `transitions` of each state's first transition, then
+    /// the number of transitions, so a state's transitions end where the
+    /// next state's start
+    offsets: Vec<usize>,
+    /// Each transition, with the state it leads to
+    transitions: Vec<(Step, usize)>,
+    /// The ranges of characters of each set, inclusive, sorted and merged
+    sets: Vec<Box<[(char, char)]>>,
+    /// The parsed criteria of each query set, or None for criteria that
+    /// could not be parsed, which match nothing
+    criteria: Vec<Option<Criteria>>,
+    start: usize,
+    end: usize,
+    /// Whether each step consumes a grapheme cluster, as in the `Nfa`
+    clusters: bool,
+}
+
+impl Compact {
+    /// Lays out an NFA for running
+    ///
+    /// # Arguments
+    ///
+    /// * `nfa` - The NFA
+    /// * `start` - The NFA's start state
+    /// * `end` - The NFA's end state, which a match must reach
+    ///
+    /// # Returns
+    ///
+    /// A Result<Compact, SpidiorError>, which will Err with a CompileError
+    /// if `start` or `end` belongs to another NFA
+    pub fn new(nfa: &Nfa, start: NodePointer, end: NodePointer) -> Result<Self, SpidiorError> {
+        nfa.check(&start)?;
+        nfa.check(&end)?;
+        let mut offsets = Vec::with_capacity(nfa.nodes.len() + 1);
+        let mut transitions = Vec::new();
+        let mut sets: Vec<Box<[(char, char)]>> = Vec::new();
+        let mut criteria = Vec::new();
+        for node in &nfa.nodes {
+            offsets.push(transitions.len());
+            for t in &node.transitions {
+                let mut set = |s: &str| {
+                    sets.push(ranges(s));
+                    sets.len() - 1
+                };
+                let step = match &t.kind {
+                    TransitionType::Epsilon => Step::Epsilon,
+                    TransitionType::Alpha(c) => Step::Alpha(*c),
+                    TransitionType::Range(s) => Step::Set(set(s)),
+                    TransitionType::NegativeRange(s) => Step::NegativeSet(set(s)),
+                    TransitionType::QuerySetRange(s) => {
+                        criteria.push(Criteria::parse(s).ok());
+                        Step::QuerySet(criteria.len() - 1)
+                    }
+                    TransitionType::Any => Step::Any,
+                    TransitionType::Open(i) => Step::Open(*i),
+                    TransitionType::Close(i) => Step::Close(*i),
+                };
+                transitions.push((step, t.dest.id));
+            }
+        }
+        offsets.push(transitions.len());
+        Ok(Self {
+            offsets,
+            transitions,
+            sets,
+            criteria,
+            start: start.id,
+            end: end.id,
+            clusters: nfa.clusters,
+        })
+    }
+
+    /// The text a step consumes at the start of `input`, as `Nfa::unit`
+    pub fn unit<'a>(&self, input: &'a str) -> &'a str {
+        let len = if self.clusters {
+            input.graphemes(true).next().map_or(0, str::len)
+        } else {
+            input.chars().next().map_or(0, char::len_utf8)
+        };
+        &input[..len]
+    }
+
+    /// The parsed criteria of each query set
+    pub fn criteria(&self) -> &[Option<Criteria>] {
+        &self.criteria
+    }
+
+    fn transitions(&self, state: usize) -> &[(Step, usize)] {
+        &self.transitions[self.offsets[state]..self.offsets[state + 1]]
+    }
+
+    /// Starts a run at the NFA's start state, before any input is read
+    pub fn run(&self) -> Run {
+        let mut run = Run {
+            states: Vec::new(),
+            next: Vec::new(),
+            pending: Vec::new(),
+            stamps: vec![0; self.offsets.len() - 1],
+            stamp: 1,
+            index: 0,
+        };
+        run.states.push((self.start, Vec::new()));
+        run.stamps[self.start] = run.stamp;
+        run.close_over(self);
+        run
+    }
+}
+
+/// The states a compact NFA is in partway through some input, each with
+/// the bounds of the groups passed on the way to it, relative to where the
+/// run started
+///
+/// Each state keeps its own groups, so a group that a branch or a repeat
+/// opens again does not move the bounds another state has already passed.
+/// A query set consumes a whole identifier in one transition, so the state
+/// it leads to waits in `pending` until the other states have stepped over
+/// the same characters, one at a time.
+#[derive(Debug, Clone)]
+pub struct Run {
+    states: Vec<(usize, Vec<Group>)>,
+    /// The states the next step leads to, kept to reuse their allocation
+    next: Vec<(usize, Vec<Group>)>,
+    /// The states reached by query sets, with the index each is reached at
+    pending: Vec<(usize, usize, Vec<Group>)>,
+    /// For each state, `stamp` if it is in `states`
+    stamps: Vec<u32>,
+    stamp: u32,
+    index: usize,
+}
+
+impl Run {
+    /// Whether the run is in no state, and is waiting on none, so can match
+    /// nothing more
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty() && self.pending.is_empty()
+    }
+
+    /// Whether the run is in the NFA's end state
+    pub fn is_end(&self, nfa: &Compact) -> bool {
+        self.stamps[nfa.end] == self.stamp
+    }
+
+    /// The groups of the way the run reached the NFA's end state, or none
+    /// if it is not in the end state
+    pub fn groups(&self, nfa: &Compact) -> &[Group] {
+        self.states.iter().find(|(x, _)| *x == nfa.end).map_or(&[], |(_, x)| x)
+    }
+
+    /// Moves every state over the character, or grapheme cluster, at the
+    /// start of `input`, which is the text from the current index on, as
+    /// `Context::step` does
+    ///
+    /// # Returns
+    ///
+    /// The index after the step
+    pub fn step(&mut self, nfa: &Compact, input: &str, q: &QueryEngine) -> usize {
+        let unit = nfa.unit(input);
+        let mut chars = unit.chars();
+        // A cluster of more than one character only matches `.` and
+        // negative sets
+        let single = chars.next().filter(|_| chars.next().is_none());
+        let in_set = |i: usize| single.is_some_and(|x| contains(&nfa.sets[i], x));
+        let next_index = self.index + unit.len();
+        let mut next = std::mem::take(&mut self.next);
+        next.clear();
+        self.stamp += 1;
+        let stamp = self.stamp;
+        for (state, groups) in &self.states {
+            for (step, dest) in nfa.transitions(*state) {
+                let taken = match *step {
+                    Step::Alpha(c) => single == Some(c),
+                    Step::Set(i) => in_set(i),
+                    Step::NegativeSet(i) => !in_set(i),
+                    Step::Any => unit != "\n" && unit != "\r\n",
+                    Step::QuerySet(i) => match nfa.criteria[i].as_ref().and_then(|x| q.query_criteria(self.index, x)) {
+                        Some(x) if x > next_index => {
+                            self.pending.push((x, *dest, groups.clone()));
+                            false
+                        }
+                        Some(_) => true,
+                        None => false,
+                    },
+                    Step::Epsilon | Step::Open(_) | Step::Close(_) => false,
+                };
+                if taken && self.stamps[*dest] != stamp {
+                    self.stamps[*dest] = stamp;
+                    next.push((*dest, groups.clone()));
+                }
+            }
+        }
+        let stamps = &mut self.stamps;
+        self.pending.retain(|(at, dest, groups)| {
+            if *at > next_index {
+                return true;
+            }
+            if stamps[*dest] != stamp {
+                stamps[*dest] = stamp;
+                next.push((*dest, groups.clone()));
+            }
+            false
+        });
+        self.next = std::mem::replace(&mut self.states, next);
+        self.index = next_index;
+        self.close_over(nfa);
+        self.index
+    }
+
+    /// Adds the states reached by transitions that consume no input,
+    /// setting the bounds of the groups opened and closed on the way
+    fn close_over(&mut self, nfa: &Compact) {
+        let mut i = 0;
+        while i < self.states.len() {
+            for (step, dest) in nfa.transitions(self.states[i].0) {
+                if !matches!(step, Step::Epsilon | Step::Open(_) | Step::Close(_)) || self.stamps[*dest] == self.stamp {
+                    continue;
+                }
+                let mut groups = self.states[i].1.clone();
+                match *step {
+                    Step::Open(g) => group(&mut groups, g).start = self.index,
+                    Step::Close(g) => {
+                        let group = group(&mut groups, g);
+                        group.len = self.index - group.start;
+                    }
+                    _ => {}
+                }
+                self.stamps[*dest] = self.stamp;
+                self.states.push((*dest, groups));
+            }
+            i += 1;
+        }
+    }
+}
+
+/// The `i`th of `groups`, adding empty groups up to it
+fn group(groups: &mut Vec<Group>, i: usize) -> &mut Group {
+    if i >= groups.len() {
+        groups.resize(i + 1, Group { start: 0, len: 0 });
+    }
+    &mut groups[i]
+}
+
+#[test]
+fn test_compact() -> Result<(), SpidiorError> {
+    use crate::regex2nfa::build_nfa;
+    use crate::regexparser;
+    let build = |query: &str| {
+        let (nfa, start, end) = build_nfa(regexparser::parse(query).unwrap().find);
+        Compact::new(&nfa, start, end)
+    };
+    let q = QueryEngine::new();
+    let nfa = build("%s/a([b-e]*)//g")?;
+    let mut run = nfa.run();
+    assert!(!run.is_end(&nfa));
+    let input = "abdcx";
+    let mut at = 0;
+    for _ in 0..4 {
+        at = run.step(&nfa, &input[at..], &q);
+        assert!(run.is_end(&nfa));
+    }
+    assert_eq!((run.groups(&nfa)[1].start, run.groups(&nfa)[1].len), (1, 3));
+    run.step(&nfa, &input[at..], &q);
+    assert!(run.is_empty());
+    let nfa = build("%s/[^a].//gc")?;
+    let mut run = nfa.run();
+    let at = run.step(&nfa, "e\u{301}\r\n", &q);
+    assert_eq!(at, 3);
+    run.step(&nfa, "\r\n", &q);
+    assert!(run.is_empty());
+    let nfa = build("%s/[[fun]]//g")?;
+    assert_eq!(nfa.criteria, vec![None]);
+    let mut run = nfa.run();
+    run.step(&nfa, "fun", &q);
+    assert!(run.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_ranges() {
+    let set = ranges("x0-a-c94b5");
+    assert_eq!(&*set, &[('-', '-'), ('0', '0'), ('4', '5'), ('9', '9'), ('a', 'c'), ('x', 'x')]);
+    assert!(contains(&set, 'b') && contains(&set, '4') && contains(&set, '-'));
+    assert!(!contains(&set, 'd') && !contains(&set, '1') && !contains(&set, ' ') && !contains(&set, 'z'));
+    assert!(ranges("").is_empty());
+}
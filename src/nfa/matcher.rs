@@ -1,16 +1,40 @@
 use std::collections::HashSet;
+use std::time::{Duration, Instant};
 
-use crate::nfa::{NfaModel, queryengine::QueryEngine};
+use crate::nfa::{Nfa, NfaModel, NodePointer, queryengine::{Criteria, QueryEngine}};
 use crate::nfa::Context;
 use crate::nfa::Group;
+use crate::nfa::compact::{Compact, Run};
+use crate::nfa::tdfa::Tdfa;
 use crate::regex2nfa::build_nfa;
-use crate::regexparser::ast::Regex;
-use crate::languages::clike::Clike;
-#[derive(Debug)]
+use crate::regexparser::ast::{self, Basic, Char, Concatenation, Elementary, Regex, Set, Simple, Union};
+use crate::languages::{cache::Parsed, clike::Clike, Language};
+use crate::languages::lexer::{Span, SpanKind};
+use crate::languages::lines::{locate, LineIndex, Locate};
+use crate::languages::parsing::{Call, Identifier};
+use crate::error::SpidiorError;
+
+/// A match of a query, which every way of finding matches returns, and
+/// every output format and replacement prints or reads
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Match {
     start: usize,
     len: usize,
+    /// The bounds of each group, relative to `start`, counting from one
     groups: Vec<Group>,
+    /// The text matched, which is empty until it is filled in by
+    /// `lines::locate`, as are the lines and columns
+    text: String,
+    /// The line and column, counting from 1, with the column in
+    /// characters, that the match starts at
+    position: (usize, usize),
+    /// The line and column the match ends at, just after its last character
+    end_position: (usize, usize),
+    /// The identifiers within the match that its query sets matched
+    identifiers: Vec<Identifier>,
+    /// The first call within the match that its `call` query sets matched
+    call: Option<Call>,
 }
 
 impl Match {
@@ -19,6 +43,11 @@ impl Match {
             start,
             len,
             groups: _groups,
+            text: String::new(),
+            position: (0, 0),
+            end_position: (0, 0),
+            identifiers: Vec::new(),
+            call: None,
         }
     }
 
@@ -30,94 +59,640 @@ impl Match {
         self.len
     }
 
-    pub fn get_group(&self, i: usize, s: &String) -> String {
+    /// The byte offset just after the match
+    pub fn end(&self) -> usize {
+        self.start + self.len
+    }
+
+    /// The bytes of the input the match spans
+    pub fn span(&self) -> std::ops::Range<usize> {
+        self.start..self.end()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The line and column the match starts at, counting from 1, with the
+    /// column in characters
+    pub fn position(&self) -> (usize, usize) {
+        self.position
+    }
+
+    /// The line and column just after the match's last character
+    pub fn end_position(&self) -> (usize, usize) {
+        self.end_position
+    }
+
+    /// The bytes of the input the `i`th group spans, counting from one, or
+    /// None if there is no such group
+    pub fn group(&self, i: usize) -> Option<std::ops::Range<usize>> {
+        let x = self.groups.get(i).filter(|_| i > 0)?;
+        Some(self.start + x.start..self.start + x.start + x.len)
+    }
+
+    /// The identifiers within the match that its query sets matched, like
+    /// the declaration of `count` for `[[name=count]]`
+    pub fn identifiers(&self) -> &[Identifier] {
+        &self.identifiers
+    }
+
+    /// The first call within the match that its `call` query sets matched,
+    /// like the call of `load` for `[[call=load]]`
+    pub fn call(&self) -> Option<&Call> {
+        self.call.as_ref()
+    }
+
+    /// The text of the `i`th argument, counting from one, of the match's
+    /// call, within `s`, the input the match was found in, which is empty
+    /// if the call has no such argument
+    pub fn get_argument(&self, i: usize, s: &str) -> String {
+        match self.call.as_ref().and_then(|x| x.args.get(i.wrapping_sub(1))) {
+            Some((start, end)) => s[*start..*end].to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// The text of the `i`th group, counting from one, within `s`, the
+    /// input the match was found in, as group boundaries are kept relative
+    /// to the start of the match
+    pub fn get_group(&self, i: usize, s: &str) -> String {
         if let Some(x) = self.groups.get(i) {
-            s[x.start..x.start + x.len].to_string()
+            s[self.start + x.start..self.start + x.start + x.len].to_string()
         } else {
             "".to_string()
         }
     }
 }
 
-pub fn find(input: &String, regex: Box<Regex>) -> Vec<Match> {
-    let mut v = Vec::new();
-    let (nfa, start, end) = build_nfa(regex);
-    let mut ctx0 = Context::new(HashSet::new());
-    ctx0.add_epsilons(vec![start].into_iter().collect(), &nfa);
+impl Locate for Match {
+    fn locate(&mut self, index: &LineIndex) {
+        self.text = index.text().get(self.span()).unwrap_or_default().to_string();
+        self.position = index.position(self.start);
+        self.end_position = index.position(self.end());
+    }
+}
+
+pub fn find(input: &str, regex: Box<Regex>) -> Vec<Match> {
+    find_in(input, regex, &Clike {}, false)
+}
+
+/// Finds all matches of `regex` within `input`, using `lang` to
+/// evaluate any query sets, resolving type aliases if `resolve_aliases` is set
+pub fn find_in(input: &str, regex: Box<Regex>, lang: &dyn Language, resolve_aliases: bool) -> Vec<Match> {
+    find_parsed(input, regex, &Parsed::new(input, lang), resolve_aliases)
+}
+
+/// Finds all matches of `regex` within `input`, using the parse results
+/// `parsed` of `input` to evaluate any query sets, resolving type aliases if
+/// `resolve_aliases` is set
+///
+/// Inputs of at least `CHUNKED_LEN` bytes are split into a chunk for each
+/// available core, as by `find_chunked`, when no match of `regex` can be
+/// longer than a known bound
+pub fn find_parsed(input: &str, regex: Box<Regex>, parsed: &Parsed, resolve_aliases: bool) -> Vec<Match> {
+    let threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+    if input.len() >= CHUNKED_LEN && threads > 1 && max_len(&regex).is_some() {
+        return find_chunked(input, regex, input.len().div_ceil(threads));
+    }
+    let searcher = Searcher::new(regex);
+    let mut qe = QueryEngine::build(parsed, resolve_aliases);
+    locate(input, searcher.scan(input, &mut qe, 0, input.len(), input.len()))
+}
+
+/// How long matching a file may take, and how much memory its matches may
+/// hold, for `--timeout-per-file` and `--memory-limit`. Only the buffer of
+/// matches is counted, not the memory of the whole process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Budget {
+    /// When matching must stop, if there is a time limit
+    deadline: Option<Instant>,
+    /// The most bytes the matches of a query may hold, if there is a limit
+    memory: Option<usize>,
+}
+
+impl Budget {
+    /// Starts a budget, whose time runs from now
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long matching may take, if there is a limit
+    /// * `memory` - The most bytes the matches of each query may hold, if
+    ///   there is a limit
+    pub fn new(timeout: Option<Duration>, memory: Option<usize>) -> Self {
+        Self {
+            deadline: timeout.map(|x| Instant::now() + x),
+            memory,
+        }
+    }
+
+    /// Whether the budget has any limit
+    pub fn is_limited(&self) -> bool {
+        self.deadline.is_some() || self.memory.is_some()
+    }
+
+    /// Checks that matching is within the budget, with `used` bytes held
+    /// by the matches found so far. Only the matches grow with the input,
+    /// as the NFA's run holds a state at most for each of its nodes.
+    pub(crate) fn check(&self, used: usize) -> Result<(), SpidiorError> {
+        if self.deadline.is_some_and(|x| Instant::now() >= x) {
+            return Err(SpidiorError::limit("Matching took longer than --timeout-per-file allows"));
+        }
+        match self.memory {
+            Some(memory) if used > memory => Err(SpidiorError::limit(format!("Matches held more than the {} bytes --memory-limit allows", memory))),
+            _ => Ok(()),
+        }
+    }
+
+    /// The bytes a match holds, before its text is filled in
+    pub(crate) fn held(m: &Match) -> usize {
+        size_of::<Match>() + m.groups.len() * size_of::<Group>() + m.identifiers.iter().map(|x| size_of::<Identifier>() + x.name.len() + x.typ.len()).sum::<usize>()
+    }
+}
+
+/// Finds all matches of `regex` within `input`, as `find_parsed` does, but
+/// stops once matching goes over `budget`
+///
+/// The time left is checked before each position a match could start at,
+/// and the memory each match holds as it is found. A limited budget is
+/// never split across threads, as `find_parsed` splits large inputs.
+///
+/// # Returns
+///
+/// A Result<Vec<Match>, SpidiorError>, which will Err with a LimitError if
+/// matching goes over the budget
+pub fn find_within(input: &str, regex: Box<Regex>, parsed: &Parsed, resolve_aliases: bool, budget: &Budget) -> Result<Vec<Match>, SpidiorError> {
+    if !budget.is_limited() {
+        return Ok(find_parsed(input, regex, parsed, resolve_aliases));
+    }
+    budget.check(0)?;
+    let searcher = Searcher::new(regex);
+    let mut qe = QueryEngine::build(parsed, resolve_aliases);
+    Ok(locate(input, searcher.scan_within(input, &mut qe, 0, input.len(), input.len(), budget)?))
+}
+
+/// The size of input above which `find_parsed` matches chunks of it in
+/// parallel
+pub const CHUNKED_LEN: usize = 1 << 20;
+
+/// Finds all matches of `regex` within `input`, as `find_parsed` does, but
+/// matches each `chunk` bytes of `input` on its own thread
+///
+/// Each chunk is matched as though a match could start at its first byte,
+/// looking past its end by the longest a match can be. The chunks are then
+/// stitched together in order: where a match from an earlier chunk runs
+/// into a chunk, the chunk is matched again from the end of that match,
+/// until it reaches a position the chunk's own matching tried too, after
+/// which the two agree. Regexes whose matches have no bound, because they
+/// use `*`, `+`, or query sets, are matched in one piece.
+///
+/// # Arguments
+///
+/// * `input` - The text to search
+/// * `regex` - The regex to find, which cannot use query sets
+/// * `chunk` - How many bytes each thread matches
+pub fn find_chunked(input: &str, regex: Box<Regex>, chunk: usize) -> Vec<Match> {
+    let bound = max_len(&regex);
+    let searcher = Searcher::new(regex);
+    let bound = match bound {
+        Some(x) => x,
+        None => return locate(input, searcher.scan(input, &mut QueryEngine::new(), 0, input.len(), input.len())),
+    };
+    let mut bounds = vec![0];
+    while let Some(&last) = bounds.last() {
+        if last >= input.len() {
+            break;
+        }
+        let mut next = (last + chunk.max(1)).min(input.len());
+        while !input.is_char_boundary(next) {
+            next += 1;
+        }
+        bounds.push(next);
+    }
+    let limit = |to: usize| (to + bound).min(input.len());
+    let searcher = &searcher;
+    let pieces: Vec<Vec<Match>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = bounds
+            .windows(2)
+            .map(|w| (w[0], w[1]))
+            .map(|(from, to)| scope.spawn(move || searcher.scan(input, &mut QueryEngine::new(), from, to, limit(to))))
+            .collect();
+        handles.into_iter().map(|x| x.join().unwrap()).collect()
+    });
+    let mut qe = QueryEngine::new();
+    let mut out = Vec::new();
     let mut is = 0;
-    let mut qe = QueryEngine::build(input, Box::new(Clike{}), Box::new(Clike{}));
-    while is < input.len() {
+    for (w, piece) in bounds.windows(2).zip(pieces) {
+        let to = w[1];
+        let mut piece = piece.into_iter().peekable();
+        // The end of the last of the chunk's matches skipped, before which
+        // are positions the chunk's matching never tried
+        let mut covered = 0;
+        while is < to {
+            while let Some(m) = piece.next_if(|m| m.start < is) {
+                covered = m.start + m.len;
+            }
+            if is >= covered {
+                for m in piece.by_ref() {
+                    is = m.start + m.len;
+                    out.push(m);
+                }
+                is = is.max(to);
+                break;
+            }
+            match searcher.longest(input, &mut qe, is, limit(is)) {
+                Some(x) => {
+                    is += x.len;
+                    out.push(x);
+                }
+                None => is += searcher.nfa.unit(&input[is..]).len(),
+            }
+        }
+    }
+    locate(input, out)
+}
+
+/// The most bytes a match of `regex` can span, or None if there is no bound
+fn max_len(regex: &Regex) -> Option<usize> {
+    match regex {
+        Regex::Union(x) => {
+            let Union::O(left, right) = x.as_ref();
+            Some(max_len(left)?.max(max_len_simple(right)?))
+        }
+        Regex::Simple(x) => max_len_simple(x),
+    }
+}
+
+fn max_len_simple(simple: &Simple) -> Option<usize> {
+    match simple {
+        Simple::Concatenation(x) => {
+            let Concatenation::O(left, right) = x.as_ref();
+            Some(max_len_simple(left)? + max_len_basic(right)?)
+        }
+        Simple::Basic(x) => max_len_basic(x),
+    }
+}
+
+fn max_len_basic(basic: &Basic) -> Option<usize> {
+    match basic {
+        Basic::Elementary(x) => match x.as_ref() {
+            Elementary::Group(g) => {
+                let ast::Group::O(r) = g.as_ref();
+                max_len(r)
+            }
+            Elementary::Char(c) => match c.as_ref() {
+                Char::Char(c) | Char::Meta(c) => Some(c.len_utf8()),
+            },
+            Elementary::Set(set) => match set.as_ref() {
+                Set::QuerySet(_) => None,
+                _ => Some(4),
+            },
+            Elementary::Any(_) | Elementary::Eos(_) => None,
+        },
+        Basic::Star(_) | Basic::Plus(_) => None,
+    }
+}
+
+/// An automaton built from a regex, ready to run over text
+struct Searcher {
+    nfa: Compact,
+    /// A run of the NFA before any input is read, copied at each position
+    run0: Run,
+    /// The NFA made deterministic, when it can be, which is run instead
+    tdfa: Option<Tdfa>,
+}
+
+impl Searcher {
+    fn new(regex: Box<Regex>) -> Self {
+        let (nfa, start, end) = build_nfa(regex);
+        Self::from_nfa(nfa, start, end)
+    }
+
+    fn from_nfa(nfa: Nfa, start: NodePointer, end: NodePointer) -> Self {
+        let tdfa = Tdfa::new(&nfa, start, end);
+        // The start and end were made by `nfa`, so are its own
+        let nfa = Compact::new(&nfa, start, end).unwrap();
+        let run0 = nfa.run();
+        Self { nfa, run0, tdfa }
+    }
+
+    /// Finds the matches that start between `from` and `to`, taking the
+    /// longest match at each position and carrying on after it, looking no
+    /// further than `limit`
+    fn scan(&self, input: &str, qe: &mut QueryEngine, from: usize, to: usize, limit: usize) -> Vec<Match> {
+        // Nothing is over a budget with no limits
+        self.scan_within(input, qe, from, to, limit, &Budget::default()).unwrap_or_default()
+    }
+
+    /// Finds the matches starting between `from` and `to`, as `scan` does,
+    /// checking `budget` before each position
+    fn scan_within(&self, input: &str, qe: &mut QueryEngine, from: usize, to: usize, limit: usize, budget: &Budget) -> Result<Vec<Match>, SpidiorError> {
+        let mut v = Vec::new();
+        let mut is = from;
+        let limited = budget.is_limited();
+        let mut used = 0;
+        while is < to {
+            if limited {
+                budget.check(used)?;
+            }
+            match self.longest(input, qe, is, limit) {
+                Some(x) => {
+                    is += x.len;
+                    if limited {
+                        used += Budget::held(&x);
+                    }
+                    v.push(x);
+                }
+                None => is += self.nfa.unit(&input[is..]).len(),
+            }
+        }
+        Ok(v)
+    }
+
+    /// Finds the longest match starting at `at` that ends by `limit`
+    fn longest(&self, input: &str, qe: &mut QueryEngine, at: usize, limit: usize) -> Option<Match> {
+        if let Some(tdfa) = &self.tdfa {
+            return tdfa.longest(input, at, limit);
+        }
         let mut new = None;
-        let mut ctx = ctx0.clone();
-        let mut i = is;
-        while i < input.len() {
-            let c = input.chars().nth(i).unwrap();
-            qe.set_offset(is);
-            i = is + ctx.step(&nfa, c, &qe);
-            if ctx.contains(&end) {
-                new = Some(Match::new(is, i - is, ctx.groups.clone()));
+        let mut run = self.run0.clone();
+        let mut i = at;
+        // Once no state is left, nothing further can match
+        while i < limit && !run.is_empty() {
+            qe.set_offset(at);
+            i = at + run.step(&self.nfa, &input[i..], qe);
+            if run.is_end(&self.nfa) {
+                new = Some(Match::new(at, i - at, run.groups(&self.nfa).to_vec()));
             }
         }
-        if let Some(x) = new {
-            is += x.len - 1;
-            v.push(x);
+        if let Some(m) = &mut new {
+            m.identifiers = qe.identifiers(m.start, m.end(), self.nfa.criteria());
+            m.call = qe.call(m.start, m.end(), self.nfa.criteria());
+        }
+        new
+    }
+}
+
+/// Drops every match that overlaps a string or char literal in `spans`,
+/// as given by `Language::spans`
+pub fn skip_literals(matches: Vec<Match>, spans: &[Span]) -> Vec<Match> {
+    skip_spans(matches, spans, &[SpanKind::String, SpanKind::Char])
+}
+
+/// Drops every match that overlaps code disabled by conditional
+/// compilation in `spans`, as given by `Language::spans`
+pub fn skip_disabled(matches: Vec<Match>, spans: &[Span]) -> Vec<Match> {
+    skip_spans(matches, spans, &[SpanKind::Disabled])
+}
+
+/// Drops every match that overlaps a span of one of `kinds`
+fn skip_spans(matches: Vec<Match>, spans: &[Span], kinds: &[SpanKind]) -> Vec<Match> {
+    let skipped: Vec<&Span> = spans
+        .iter()
+        .filter(|x| kinds.contains(&x.kind))
+        .collect();
+    matches
+        .into_iter()
+        .filter(|m| {
+            !skipped
+                .iter()
+                .any(|x| x.start < m.start + m.len.max(1) && m.start < x.end)
+        })
+        .collect()
+}
+
+/// The criteria of each query set in `regex`, in the order they appear,
+/// leaving out any that cannot be parsed, which match nothing
+pub fn query_sets(regex: Box<Regex>) -> Vec<Criteria> {
+    let (nfa, start, end) = build_nfa(regex);
+    Compact::new(&nfa, start, end).map(|x| x.criteria().iter().flatten().cloned().collect()).unwrap_or_default()
+}
+
+/// Several patterns matched in one scan, as the alternatives of one union,
+/// where each match can be traced back to the pattern that made it
+///
+/// Each pattern is put in a group of its own, so the pattern that made a
+/// match is the one whose group spans it. At any position, the longest
+/// match of any pattern is taken, and of patterns matching as much, the
+/// first.
+#[derive(Debug, Clone)]
+pub struct PatternSet {
+    regex: Box<Regex>,
+    /// The index of the group around each pattern, which comes after the
+    /// pattern's own groups, as a group is numbered once it is closed
+    outer: Vec<usize>,
+}
+
+impl PatternSet {
+    /// Combines patterns into one
+    ///
+    /// # Arguments
+    ///
+    /// * `patterns` - The find parts of the patterns, in order, of which
+    ///   there is at least one
+    pub fn new(patterns: Vec<Box<Regex>>) -> Self {
+        let mut outer = Vec::new();
+        let mut groups = 0;
+        let mut regex: Option<Box<Regex>> = None;
+        for pattern in patterns {
+            groups += count_groups(&pattern) + 1;
+            outer.push(groups);
+            let group = Box::new(Simple::Basic(Box::new(Basic::Elementary(Box::new(Elementary::Group(Box::new(ast::Group::O(pattern))))))));
+            regex = Some(Box::new(match regex {
+                Some(x) => Regex::Union(Box::new(Union::O(x, group))),
+                None => Regex::Simple(group),
+            }));
+        }
+        Self {
+            regex: regex.expect("a pattern set has at least one pattern"),
+            outer,
+        }
+    }
+
+    /// The union of the patterns, to be matched as any other find part
+    pub fn regex(&self) -> Box<Regex> {
+        self.regex.clone()
+    }
+
+    /// The number of patterns
+    pub fn len(&self) -> usize {
+        self.outer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outer.is_empty()
+    }
+
+    /// Finds which pattern made a match of `regex`, counting from zero, or
+    /// None if the match has no groups, as when found by the derivatives
+    /// engine
+    pub fn which(&self, m: &Match) -> Option<usize> {
+        self.outer.iter().position(|x| m.group(*x) == Some(m.span()))
+    }
+
+    /// Finds all matches of any of the patterns, as `find_within` does,
+    /// with the pattern that made each
+    pub fn find_within(&self, input: &str, parsed: &Parsed, resolve_aliases: bool, budget: &Budget) -> Result<Vec<(Match, Option<usize>)>, SpidiorError> {
+        Ok(find_within(input, self.regex(), parsed, resolve_aliases, budget)?.into_iter().map(|m| {
+            let which = self.which(&m);
+            (m, which)
+        }).collect())
+    }
+}
+
+/// The number of groups in a pattern
+fn count_groups(regex: &Regex) -> usize {
+    match regex {
+        Regex::Union(x) => {
+            let Union::O(left, right) = x.as_ref();
+            count_groups(left) + count_simple_groups(right)
+        }
+        Regex::Simple(x) => count_simple_groups(x),
+    }
+}
+
+fn count_simple_groups(simple: &Simple) -> usize {
+    match simple {
+        Simple::Concatenation(x) => {
+            let Concatenation::O(left, right) = x.as_ref();
+            count_simple_groups(left) + count_basic_groups(right)
         }
-        is += 1;
+        Simple::Basic(x) => count_basic_groups(x),
     }
-    v
 }
 
+fn count_basic_groups(basic: &Basic) -> usize {
+    let e = match basic {
+        Basic::Star(x) => {
+            let ast::Star::O(e) = x.as_ref();
+            e
+        }
+        Basic::Plus(x) => {
+            let ast::Plus::O(e) = x.as_ref();
+            e
+        }
+        Basic::Elementary(e) => e,
+    };
+    match e.as_ref() {
+        Elementary::Group(g) => {
+            let ast::Group::O(r) = g.as_ref();
+            count_groups(r) + 1
+        }
+        _ => 0,
+    }
+}
+
+/// Finds all matches of an NFA built by hand, as with the combinators of
+/// `nfa::combinators`, within `input`
+pub fn find_model(input: &str, model: &NfaModel) -> Vec<Match> {
+    let searcher = Searcher::from_nfa(model.nfa.clone(), model.start, model.end);
+    locate(input, searcher.scan(input, &mut QueryEngine::new(), 0, input.len(), input.len()))
+}
 
+/// Finds all matches of `regex` within `input` with its tagged DFA, or, if
+/// it has query sets, by running its NFA made deterministic
 pub fn find_dfa(input: &String, regex: Box<Regex>) -> Vec<Match> {
     let mut v = Vec::new();
     let (nfa, start, end) = build_nfa(regex);
+    if let Some(tdfa) = Tdfa::new(&nfa, start, end) {
+        let mut is = 0;
+        while is < input.len() {
+            match tdfa.longest(input, is, input.len()) {
+                Some(x) => {
+                    is += x.len;
+                    v.push(x);
+                }
+                None => is += nfa.unit(&input[is..]).len(),
+            }
+        }
+        return locate(input, v);
+    }
 
     let nfam = NfaModel::new(nfa, start, end);
     let nfam = nfam.to_dfa().unwrap();
     let (nfa, start, end) = (nfam.nfa, nfam.start, nfam.end);
-    println!("{:?}", nfa);
     let mut ctx0 = Context::new(HashSet::new());
     ctx0.add_epsilons(vec![start].into_iter().collect(), &nfa);
     let mut is = 0;
-    let mut qe = QueryEngine::build(input, Box::new(Clike{}), Box::new(Clike{}));
+    let mut qe = QueryEngine::build(&Parsed::new(input, &Clike {}), false);
     while is < input.len() {
         let mut new = None;
         let mut ctx = ctx0.clone();
         let mut i = is;
         while i < input.len() {
-            let c = input.chars().nth(i).unwrap();
             qe.set_offset(is);
-            i = is + ctx.step(&nfa, c, &qe);
+            i = is + ctx.step(&nfa, &input[i..], &qe);
             if ctx.is_end(&nfa) {
                 new = Some(Match::new(is, i - is, ctx.groups.clone()));
             }
         }
-        if let Some(x) = new {
-            is += x.len - 1;
-            v.push(x);
+        match new {
+            Some(x) => {
+                is += x.len;
+                v.push(x);
+            }
+            None => is += nfa.unit(&input[is..]).len(),
         }
-        is += 1;
     }
-    v
+    locate(input, v)
 }
 
 #[test]
 fn test_find() -> Result<(), Box<dyn std::error::Error>> {
     use crate::regexparser;
     let regex = regexparser::parse("%s/bob|joe|e*//g")?.find;
-    assert_eq!(find(&"bob dole".to_string(), regex).len(), 2); //matches bob and e
+    assert_eq!(find("bob dole", regex).len(), 2); //matches bob and e
     let regex = regexparser::parse("%s/bob|joe|e*//g")?.find;
-    assert_eq!(find(&"bo".to_string(), regex).len(), 0); //no match
+    assert_eq!(find("bo", regex).len(), 0); //no match
     let regex = regexparser::parse("%s/bob|joe|e*//g")?.find;
-    assert_eq!(find(&"joee".to_string(), regex).len(), 2); //"joe", "e"
+    assert_eq!(find("joee", regex).len(), 2); //"joe", "e"
     let regex = regexparser::parse("%s/(o*)o//g")?.find;
     let os = "ooooo";
-    let found = find(&os.to_string(), regex);
+    let found = find(os, regex);
     assert_eq!(found.len(), 1); //entire string
     assert_eq!(found.get(0).unwrap().get_group(1, &os.to_string()), "oooo");
     Ok(())
 }
 
+#[test]
+fn test_skip_literals() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "me(\"me\", 'm', me)".to_string();
+    let regex = regexparser::parse("%s/me//g")?.find;
+    let found = skip_literals(find(&input, regex), &Clike {}.spans(&input));
+    let starts: Vec<usize> = found.iter().map(|x| x.start()).collect();
+    assert_eq!(starts, vec![0, 14]);
+    Ok(())
+}
+
+#[test]
+fn test_skip_disabled() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "me();\n#if 0\nme();\n#else\nme();\n#endif\n".to_string();
+    let regex = regexparser::parse("%s/me//g")?.find;
+    let found = skip_disabled(find(&input, regex), &Clike {}.spans(&input));
+    let starts: Vec<usize> = found.iter().map(|x| x.start()).collect();
+    assert_eq!(starts, vec![0, 24]);
+    Ok(())
+}
+
+#[test]
+fn test_find_chunked() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "abab aab éab abba ababab bab".repeat(3);
+    for pattern in ["ab", "aba|ab", "(ab)(ab)", "a[a-b]b|é", "bab|aab|a"] {
+        let whole: Vec<(usize, usize)> = find(&input, regexparser::parse(&format!("%s/{}//g", pattern))?.find).iter().map(|x| (x.start(), x.len())).collect();
+        for chunk in 1..12 {
+            let chunked: Vec<(usize, usize)> = find_chunked(&input, regexparser::parse(&format!("%s/{}//g", pattern))?.find, chunk).iter().map(|x| (x.start(), x.len())).collect();
+            assert_eq!(chunked, whole, "`{}` in chunks of {}", pattern, chunk);
+        }
+    }
+    assert_eq!(max_len(&regexparser::parse("%s/a(bc|d)[x-z]//g")?.find), Some(7));
+    assert_eq!(max_len(&regexparser::parse("%s/ab*//g")?.find), None);
+    assert_eq!(max_len(&regexparser::parse("%s/[[name=a]]//g")?.find), None);
+    Ok(())
+}
+
 #[test]
 fn test_find_dfa() -> Result<(), Box<dyn std::error::Error>> {
     use crate::regexparser;
@@ -134,3 +709,90 @@ fn test_find_dfa() -> Result<(), Box<dyn std::error::Error>> {
     assert_eq!(found.get(0).unwrap().get_group(1, &os.to_string()), "oooo");
     Ok(())
 }
+
+#[test]
+fn test_find_flags() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let found = |text: &str, query: &str| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let text = text.to_string();
+        Ok(find(&text, regexparser::parse(query)?.find).iter().map(|m| text[m.start..m.start + m.len].to_string()).collect())
+    };
+    assert_eq!(found("Count COUNT count", "%s/count//g")?, vec!["count"]);
+    assert_eq!(found("Count COUNT count", "%s/count//gi")?, vec!["Count", "COUNT", "count"]);
+    assert_eq!(found("Ab aB x", "%s/[a-b]+//gi")?, vec!["Ab", "aB"]);
+    assert_eq!(found("ΣΑΣ σας", "%s/σας//gi")?, vec!["ΣΑΣ", "σας"]);
+    assert_eq!(found("École école ECOLE", "%s/école//gi")?, vec!["École", "école"]);
+    assert_eq!(found("Straße STRASSE STRAẞE", "%s/straße//gi")?, vec!["Straße", "STRAẞE"]);
+    assert_eq!(found("Ab\nc", "%s/.//g")?, vec!["A", "b", "c"]);
+    let flag = "🇫🇷 e\u{301}";
+    assert_eq!(found(flag, "%s/.//g")?.len(), 5);
+    assert_eq!(found(flag, "%s/.//gc")?, vec!["🇫🇷", " ", "e\u{301}"]);
+    assert_eq!(found("e\u{301}x ex", "%s/e.//gc")?, vec!["ex"]);
+    Ok(())
+}
+
+#[test]
+fn test_match() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "int count = 0;\nx = (count + é1);\n".to_string();
+    let found = find(&input, regexparser::parse("%s/\\((co+)unt//g")?.find);
+    assert_eq!(found.len(), 1);
+    let m = &found[0];
+    assert_eq!((m.span(), m.text()), (19..25, "(count"));
+    assert_eq!((m.position(), m.end_position()), ((2, 5), (2, 11)));
+    assert_eq!((m.group(1), m.group(0), m.group(2)), (Some(20..22), None, None));
+    assert!(m.identifiers().is_empty());
+    let found = find(&input, regexparser::parse("%s/\\([[name=count]]//g")?.find);
+    assert_eq!(found[0].text(), "(count");
+    let names: Vec<(&str, usize)> = found[0].identifiers().iter().map(|x| (x.name.as_str(), x.start)).collect();
+    assert_eq!(names, vec![("count", 20)]);
+    Ok(())
+}
+
+#[test]
+fn test_query_set_union() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "Session s; s.run(); this.session.run(); se.run(); s.run(); t.go();".to_string();
+    let groups = |query: &str| -> Vec<Vec<String>> {
+        find(&input, regexparser::parse(query).unwrap().find).iter().map(|m| (1..=2).map(|i| m.get_group(i, &input)).collect()).collect()
+    };
+    // Each branch keeps to its own characters, though the query set steps
+    // over the whole of `s` at once
+    assert_eq!(groups("%s/([[type=Session]]|this\\.session|se)\\.run//g"), vec![vec!["s", ""], vec!["this.session", ""], vec!["se", ""], vec!["s", ""]]);
+    assert_eq!(groups("%s/([[type=Session]]|[a-z]+)\\.(run|go)//g"), vec![vec!["s", "run"], vec!["session", "run"], vec!["se", "run"], vec!["s", "run"], vec!["t", "go"]]);
+    // The groups of a repeat are those of its last whole pass, not of one
+    // it started and could not finish
+    assert_eq!(groups("%s/(([[name=s]]|se)\\.run\\(\\);\\ )*t//g"), vec![vec!["s", "s.run(); "], vec!["s", "s.run(); "]]);
+    Ok(())
+}
+
+#[test]
+fn test_budget() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "a".repeat(1000);
+    let parsed = Parsed::new(&input, &Clike {});
+    let find = |query: &str, budget: &Budget| find_within(&input, regexparser::parse(query).unwrap().find, &parsed, false, budget);
+    assert_eq!(find("%s/a//g", &Budget::default())?.len(), 1000);
+    assert_eq!(find("%s/a//g", &Budget::new(Some(Duration::from_secs(60)), Some(1 << 20)))?.len(), 1000);
+    assert!(matches!(find("%s/a//g", &Budget::new(None, Some(100))), Err(SpidiorError::LimitError(_))));
+    assert!(matches!(find("%s/a*b//g", &Budget::new(Some(Duration::ZERO), None)), Err(SpidiorError::LimitError(_))));
+    Ok(())
+}
+
+#[test]
+fn test_pattern_set() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser::parse_pattern;
+    let input = "int count; // TODO: fix(count)\nFIXME";
+    let parsed = Parsed::new(input, &Clike {});
+    // The `count` in the comment is no identifier, so only the first matches
+    let set = PatternSet::new(vec![parse_pattern("TODO|FIXME")?, parse_pattern("(f)(i)x")?, parse_pattern("[[name=count]]")?]);
+    assert_eq!(set.len(), 3);
+    let found: Vec<(&str, Option<usize>)> = set.find_within(input, &parsed, false, &Budget::default())?.into_iter().map(|(m, x)| (&input[m.span()], x)).collect();
+    assert_eq!(found, vec![("count", Some(2)), ("TODO", Some(0)), ("fix", Some(1)), ("FIXME", Some(0))]);
+    // The longest match wins wherever patterns start at the same place, and
+    // the first of those that match as much
+    let set = PatternSet::new(vec![parse_pattern("ab")?, parse_pattern("a(b)*c")?, parse_pattern("a[b]c")?]);
+    let found: Vec<(usize, Option<usize>)> = find_within("ab abc", set.regex(), &parsed, false, &Budget::default())?.iter().map(|m| (m.start(), set.which(m))).collect();
+    assert_eq!(found, vec![(0, Some(0)), (3, Some(1))]);
+    Ok(())
+}
@@ -3,17 +3,24 @@
 
 use std::{
     collections::{HashMap, HashSet},
-    error::Error,
     hash::Hash,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::error::SpidiorError;
+
 use queryengine::QueryEngine;
 
 type Atom = char;
 
+pub mod combinators;
+pub mod compact;
 pub mod matcher;
 pub mod replacer;
 pub mod queryengine;
+pub mod tdfa;
 
 #[derive(Debug, Clone)]
 enum TransitionType {
@@ -22,6 +29,7 @@ enum TransitionType {
     Range(String),
     NegativeRange(String),
     QuerySetRange(String),
+    Any,
     Open(usize),
     Close(usize),
 }
@@ -46,14 +54,17 @@ impl Transition {
         Self { kind, dest }
     }
 }
+/// Identifies a node of an NFA, and the NFA it belongs to, so that it is
+/// not used to change another
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub struct NodePointer {
+    nfa: usize,
     id: usize,
 }
 
 impl NodePointer {
-    fn new(id: usize) -> Self {
-        Self { id }
+    fn new(nfa: usize, id: usize) -> Self {
+        Self { nfa, id }
     }
 }
 #[derive(Debug, Clone)]
@@ -87,8 +98,14 @@ impl Node {
 /// Represents a non-deterministic finite automaton
 #[derive(Debug, Clone)]
 pub struct Nfa {
+    /// Distinguishes this NFA's node pointers from those of every NFA
+    /// made since, though not from those of its clones
+    id: usize,
     nodes: Vec<Node>,
     index: usize,
+    /// Whether each step consumes a whole grapheme cluster rather than a
+    /// single character, which a `.` from the `c` flag turns on
+    clusters: bool,
 }
 
 impl Nfa {
@@ -99,7 +116,13 @@ impl Nfa {
     /// Internally, nodes are tracked by NodePointers,
     /// which simply index into the node vec from a given NFA.
     pub fn new(nodes: Vec<Node>) -> Self {
-        Self { nodes, index: 0 }
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            nodes,
+            index: 0,
+            clusters: false,
+        }
     }
 
     /// "Dereferences" a NodePointer in the context of an NFA.
@@ -114,7 +137,29 @@ impl Nfa {
     /// An Option<&Node>, which will be None if i is not found within
     /// the NFA, or Some(&x) where x is the node that was referenced.
     pub fn get(&self, i: &NodePointer) -> Option<&Node> {
-        self.nodes.get(i.id)
+        self.nodes.get(i.id).filter(|_| i.nfa == self.id)
+    }
+
+    /// Checks that a NodePointer was returned by this NFA
+    ///
+    /// # Returns
+    ///
+    /// A Result<(), SpidiorError>, which will Err with a CompileError if `i`
+    /// belongs to another NFA or is not a node of this one
+    pub fn check(&self, i: &NodePointer) -> Result<(), SpidiorError> {
+        if i.nfa != self.id {
+            return Err(SpidiorError::compile(format!("Node {} belongs to another NFA", i.id)));
+        }
+        if i.id >= self.nodes.len() {
+            return Err(SpidiorError::compile(format!("Node {} does not exist", i.id)));
+        }
+        Ok(())
+    }
+
+    /// The number of groups added so far, which is the index of the last
+    /// one, as groups count from one
+    pub fn groups(&self) -> usize {
+        self.index
     }
 
     /// Creates a new Node in the NFA, with no transitions to/from it.
@@ -142,7 +187,7 @@ impl Nfa {
     /// An NodePointer, which identifies the newly added Node.
     pub fn add_node(&mut self, node: Node) -> NodePointer {
         self.nodes.push(node);
-        NodePointer::new(self.nodes.len() - 1)
+        NodePointer::new(self.id, self.nodes.len() - 1)
     }
 
     pub fn add_transition_range(
@@ -150,7 +195,7 @@ impl Nfa {
         from: &NodePointer,
         to: &NodePointer,
         s: String,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), SpidiorError> {
         self.add_transition(from, Transition::new(TransitionType::Range(s), *to))
     }
 
@@ -159,7 +204,7 @@ impl Nfa {
         from: &NodePointer,
         to: &NodePointer,
         s: String,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), SpidiorError> {
         self.add_transition(from, Transition::new(TransitionType::QuerySetRange(s), *to))
     }
 
@@ -168,7 +213,7 @@ impl Nfa {
         from: &NodePointer,
         to: &NodePointer,
         s: String,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), SpidiorError> {
         self.add_transition(from, Transition::new(TransitionType::NegativeRange(s), *to))
     }
 
@@ -177,15 +222,40 @@ impl Nfa {
         from: &NodePointer,
         to: &NodePointer,
         on: Atom,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), SpidiorError> {
         self.add_transition(from, Transition::new(TransitionType::Alpha(on), *to))
     }
 
+    /// Adds a transition on any character but a newline, which with
+    /// `cluster` set is any grapheme cluster but a line break, and makes
+    /// every other transition consume whole clusters too
+    pub fn add_transition_any(
+        &mut self,
+        from: &NodePointer,
+        to: &NodePointer,
+        cluster: bool,
+    ) -> Result<(), SpidiorError> {
+        self.clusters |= cluster;
+        self.add_transition(from, Transition::new(TransitionType::Any, *to))
+    }
+
+    /// The text a step consumes at the start of `input`, which is its
+    /// first character, or its first grapheme cluster if any transition
+    /// matches clusters
+    pub fn unit<'a>(&self, input: &'a str) -> &'a str {
+        let len = if self.clusters {
+            input.graphemes(true).next().map_or(0, str::len)
+        } else {
+            input.chars().next().map_or(0, char::len_utf8)
+        };
+        &input[..len]
+    }
+
     pub fn add_transition_epsilon(
         &mut self,
         from: &NodePointer,
         to: &NodePointer,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), SpidiorError> {
         self.add_transition(from, Transition::new(TransitionType::Epsilon, *to))
     }
 
@@ -195,7 +265,10 @@ impl Nfa {
         start_to: &NodePointer,
         end_from: &NodePointer,
         end_to: &NodePointer,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), SpidiorError> {
+        for x in [start_from, start_to, end_from, end_to] {
+            self.check(x)?;
+        }
         self.index += 1;
         self.add_transition(
             start_from,
@@ -207,14 +280,18 @@ impl Nfa {
         )
     }
 
-    fn add_transition(&mut self, from: &NodePointer, to: Transition) -> Result<(), Box<dyn Error>> {
-        let node = self.nodes.get_mut(from.id).ok_or("Invalid source!")?;
-        node.transitions.push(to);
+    /// Adds a transition, after checking that both of its ends are nodes
+    /// of this NFA
+    fn add_transition(&mut self, from: &NodePointer, to: Transition) -> Result<(), SpidiorError> {
+        self.check(from)?;
+        self.check(&to.dest)?;
+        self.nodes[from.id].transitions.push(to);
         Ok(())
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Group {
     pub start: usize,
     pub len: usize,
@@ -266,25 +343,39 @@ impl Context {
         t.len = self.index - t.start;
     }
 
-    pub fn step(&mut self, nfa: &Nfa, input: Atom, q: &queryengine::QueryEngine) -> usize {
+    /// Moves every state over the character, or grapheme cluster, at the
+    /// start of `input`, which is the text from the current index on
+    ///
+    /// # Returns
+    ///
+    /// The index after the step
+    pub fn step(&mut self, nfa: &Nfa, input: &str, q: &queryengine::QueryEngine) -> usize {
         let mut nodes = HashSet::new();
+        let unit = nfa.unit(input);
+        let mut chars = unit.chars();
+        // A cluster of more than one character only matches `.` and
+        // negative sets
+        let single = chars.next().filter(|_| chars.next().is_none());
+        let mut next = self.index + unit.len();
         for nodeptr in &self.nodes {
             if let Some(node) = nfa.get(nodeptr) {
                 for t in &node.transitions {
                     match &t.kind {
-                        TransitionType::Alpha(c) if *c == input => {
+                        TransitionType::Alpha(c) if single == Some(*c) => {
+                            nodes.insert(t.dest);
+                        }
+                        TransitionType::Range(s) if single.is_some_and(|x| s.contains(x)) => {
                             nodes.insert(t.dest);
                         }
-                        TransitionType::Range(s) if s.contains(input) => {
+                        TransitionType::NegativeRange(s) if !single.is_some_and(|x| s.contains(x)) => {
                             nodes.insert(t.dest);
                         }
-                        TransitionType::NegativeRange(s) if !s.contains(input) => {
+                        TransitionType::Any if unit != "\n" && unit != "\r\n" => {
                             nodes.insert(t.dest);
                         }
                         TransitionType::QuerySetRange(s) => {
                             if let Some(x) = q.query(self.index, s) {
-                                //println!("{}", self.index);
-                                self.index = x - 1;
+                                next = x;
                                 nodes.insert(t.dest);
                             }
                         }
@@ -293,7 +384,7 @@ impl Context {
                 }
             }
         }
-        self.index += 1;
+        self.index = next;
         self.add_epsilons(nodes, nfa);
         self.index
     }
@@ -334,8 +425,9 @@ pub struct NfaModel {
 }
 
 impl NfaModel {
-    pub fn to_dfa(&self) -> Result<Self, Box<dyn Error>> {
+    pub fn to_dfa(&self) -> Result<Self, SpidiorError> {
         let mut dfa = Nfa::new(Vec::new());
+        dfa.clusters = self.nfa.clusters;
         let mut map = HashMap::new();
         let mut stack = Vec::new();
         let start = dfa.new_node();
@@ -346,12 +438,18 @@ impl NfaModel {
         let x: Vec<NodePointer> = ctx.nodes.into_iter().collect();
         map.insert(x.clone(), dfa_model.start);
         stack.push(x);
-        while !stack.is_empty() {
-            let x = stack.pop().ok_or("sad")?;
-            let my_p = *map.get(&x).ok_or("how?")?;
+        while let Some(x) = stack.pop() {
+            // Every set of states is given its DFA state before it is pushed
+            let my_p = *map
+                .get(&x)
+                .ok_or_else(|| SpidiorError::compile("A set of NFA states has no DFA state"))?;
 
             for old in &x {
-                for new in &self.nfa.get(&old).ok_or("ahh")?.transitions {
+                let node = self
+                    .nfa
+                    .get(old)
+                    .ok_or_else(|| SpidiorError::compile(format!("NFA node {} does not exist", old.id)))?;
+                for new in &node.transitions {
                     if let TransitionType::Epsilon = new.kind {
                     } else {
                         let mut ctx = Context::new(HashSet::new());
@@ -387,7 +485,7 @@ impl NfaModel {
 }
 
 #[test]
-fn test_nfa_insert() -> Result<(), Box<dyn Error>> {
+fn test_nfa_insert() -> Result<(), Box<dyn std::error::Error>> {
     let mut nfa = Nfa::new(Vec::new());
     nfa.add_node(Node::new());
     nfa.add_node(Node::new());
@@ -397,23 +495,23 @@ fn test_nfa_insert() -> Result<(), Box<dyn Error>> {
 }
 
 #[test]
-fn test_nfa_alpha_transition() -> Result<(), Box<dyn Error>> {
+fn test_nfa_alpha_transition() -> Result<(), Box<dyn std::error::Error>> {
     let mut nfa = Nfa::new(Vec::new());
     let a = nfa.add_node(Node::new());
     let b = nfa.add_node(Node::new());
     nfa.add_transition_alpha(&a, &b, 'a')?;
     let mut ctx = Context::new(vec![a].into_iter().collect());
-    ctx.step(&nfa, 'b', &QueryEngine::new());
+    ctx.step(&nfa, "b", &QueryEngine::new());
     assert_eq!(ctx.nodes.len(), 0);
     let mut ctx = Context::new(vec![a].into_iter().collect());
-    ctx.step(&nfa, 'a', &QueryEngine::new());
+    ctx.step(&nfa, "a", &QueryEngine::new());
     assert_eq!(ctx.nodes.len(), 1);
     assert!(ctx.nodes.contains(&b));
     Ok(())
 }
 
 #[test]
-fn test_nfa_epsilon_transition() -> Result<(), Box<dyn Error>> {
+fn test_nfa_epsilon_transition() -> Result<(), Box<dyn std::error::Error>> {
     let mut nfa = Nfa::new(Vec::new());
     let a = nfa.new_node();
     let b = nfa.new_node();
@@ -421,10 +519,10 @@ fn test_nfa_epsilon_transition() -> Result<(), Box<dyn Error>> {
     nfa.add_transition_alpha(&a, &b, 'a')?;
     nfa.add_transition_epsilon(&b, &c)?;
     let mut ctx = Context::new(vec![a].into_iter().collect());
-    ctx.step(&nfa, 'b', &QueryEngine::new());
+    ctx.step(&nfa, "b", &QueryEngine::new());
     assert_eq!(ctx.nodes.len(), 0);
     let mut ctx = Context::new(vec![a].into_iter().collect());
-    ctx.step(&nfa, 'a', &QueryEngine::new());
+    ctx.step(&nfa, "a", &QueryEngine::new());
     assert_eq!(ctx.nodes.len(), 2);
     assert!(ctx.nodes.contains(&b));
     assert!(ctx.nodes.contains(&c));
@@ -432,7 +530,7 @@ fn test_nfa_epsilon_transition() -> Result<(), Box<dyn Error>> {
 }
 
 #[test]
-fn test_nfa_to_dfa() -> Result<(), Box<dyn Error>> {
+fn test_nfa_to_dfa() -> Result<(), Box<dyn std::error::Error>> {
     let mut nfa = Nfa::new(Vec::new());
     let a = nfa.new_node();
     let b = nfa.new_node();
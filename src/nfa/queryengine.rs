@@ -1,7 +1,204 @@
-use crate::languages::parsing::{Function, Functions, Identifier, Identifiers};
+use crate::languages::cache::Parsed;
+use crate::languages::parsing::{Call, Function, Identifier, Import};
+use crate::languages::scopes::ScopeTree;
+use crate::error::SpidiorError;
+use crate::regexparser::ast::{Queries, Query};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The criteria of a query set, like `name=count,type=int`, parsed once
+/// so that matching does not parse them again at each position
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Criteria {
+    name: Option<String>,
+    /// The type, or for `tag`, `attr`, `text`, and `macro` criteria, the
+    /// key, whose value is then the name
+    kind: Option<String>,
+    import: Option<String>,
+    scope: Option<String>,
+    /// The position and length of a `pos=12:5` criterion, which stands
+    /// alone, or None within if they are not numbers
+    pos: Option<Option<(usize, usize)>>,
+    /// The name of the function called, for `callee`, which matches the name
+    /// of a call up to its `(`, and `args`, which matches its arguments, up
+    /// to its `)`. Each stands alone.
+    call: Option<(CallPart, String)>,
+    /// The number of arguments an `argc=2` criterion asks a call to have,
+    /// or None within if it is not a number
+    argc: Option<Option<usize>>,
+}
+
+/// The part of a call a `callee` or `args` criterion matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CallPart {
+    Callee,
+    Args,
+}
+
+impl Criteria {
+    /// Parses the criteria between the brackets of a query set
+    ///
+    /// # Returns
+    ///
+    /// A Result<Criteria, SpidiorError>, which will Err with a ParseError if
+    /// the criteria are not `key=value` pairs separated by commas
+    pub fn parse(query: &str) -> Result<Self, SpidiorError> {
+        let mut c = crate::regexparser::query::QueriesParser::new()
+            .parse(query)
+            .map_err(|_| SpidiorError::parse(format!("Failed to parse the query set `{}`", query)))?;
+        let mut criteria = Self::default();
+        loop {
+            let (x, rest) = match *c {
+                Queries::Query(x) => (x, None),
+                Queries::Queries(x, r) => (x, Some(r)),
+            };
+            match *x {
+                Query::Kv(k, v) if k == "type" => criteria.kind = Some(v),
+                Query::Kv(k, v) if k == "name" => criteria.name = Some(v),
+                Query::Kv(k, v) if k == "import" => criteria.import = Some(v),
+                Query::Kv(k, v) if k == "scope" => criteria.scope = Some(v),
+                Query::Kv(k, v) if k == "tag" || k == "attr" || k == "text" || k == "macro" => {
+                    criteria.kind = Some(k);
+                    criteria.name = Some(v);
+                }
+                Query::Kv(k, v) if k == "pos" => {
+                    let mut s = v.split(':');
+                    let pos = s.next().zip(s.next()).and_then(|(pos, len)| Some((pos.parse().ok()?, len.parse().ok()?)));
+                    criteria.pos = Some(pos);
+                    return Ok(criteria);
+                }
+                Query::Kv(k, v) if k == "call" || k == "callee" || k == "args" => {
+                    criteria.call = Some((if k != "args" { CallPart::Callee } else { CallPart::Args }, v));
+                }
+                Query::Kv(k, v) if k == "argc" => criteria.argc = Some(v.parse().ok()),
+                _ => {}
+            }
+            match rest {
+                Some(r) => c = r,
+                None => return Ok(criteria),
+            }
+        }
+    }
+}
+
+impl Criteria {
+    /// Whether the criteria are met by tags, attributes, text, or macros,
+    /// whose key is their kind
+    fn keyed(&self) -> bool {
+        self.name.is_some() && matches!(self.kind.as_deref(), Some("tag" | "attr" | "text" | "macro"))
+    }
+
+    /// Whether a call meets the name and number of arguments of `callee` or
+    /// `args` criteria
+    fn calls(&self, call: &Call) -> bool {
+        self.call.as_ref().is_some_and(|(_, name)| *name == call.name)
+            && match self.argc {
+                Some(n) => n == Some(call.args.len()),
+                None => true,
+            }
+    }
+
+    /// Whether the criteria are met by identifiers, rather than by imports,
+    /// calls, or positions
+    fn identifies(&self) -> bool {
+        self.import.is_none() && self.pos.is_none() && self.call.is_none()
+    }
+
+    /// Splits the criteria into one for each predicate they hold, like
+    /// `name=count` and `type=int` for `name=count,type=int`, where an
+    /// `import` keeps its name, and `tag` and its kin stay whole
+    pub fn predicates(&self) -> Vec<Criteria> {
+        if self.pos.is_some() || self.import.is_some() || self.call.is_some() || self.keyed() && self.scope.is_none() {
+            return vec![self.clone()];
+        }
+        let mut out = Vec::new();
+        if self.keyed() {
+            out.push(Self { scope: None, ..self.clone() });
+        } else {
+            if self.name.is_some() {
+                out.push(Self { name: self.name.clone(), ..Self::default() });
+            }
+            if self.kind.is_some() {
+                out.push(Self { kind: self.kind.clone(), ..Self::default() });
+            }
+        }
+        if self.scope.is_some() {
+            out.push(Self { scope: self.scope.clone(), ..Self::default() });
+        }
+        out
+    }
+}
+
+impl fmt::Display for Criteria {
+    /// Writes the criteria as they are written in a query set, like
+    /// `name=count,type=int`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(pos) = self.pos {
+            return match pos {
+                Some((pos, len)) => write!(f, "pos={}:{}", pos, len),
+                None => write!(f, "pos=?"),
+            };
+        }
+        if let Some((part, name)) = &self.call {
+            write!(f, "{}={}", if *part == CallPart::Callee { "callee" } else { "args" }, name)?;
+            return match self.argc {
+                Some(Some(n)) => write!(f, ",argc={}", n),
+                Some(None) => write!(f, ",argc=?"),
+                None => Ok(()),
+            };
+        }
+        let mut pairs = Vec::new();
+        match (&self.name, &self.kind) {
+            (Some(name), Some(kind)) if self.keyed() => pairs.push((kind.as_str(), name)),
+            (name, kind) => {
+                pairs.extend(name.iter().map(|x| ("name", x)));
+                pairs.extend(kind.iter().map(|x| ("type", x)));
+            }
+        }
+        pairs.extend(self.import.iter().map(|x| ("import", x)));
+        pairs.extend(self.scope.iter().map(|x| ("scope", x)));
+        let pairs: Vec<String> = pairs.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
+/// How many of the identifiers, or imports, in some code a predicate of a
+/// query set accepted, which `--stats` reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stat {
+    /// The predicate, like `type=Session`, or the whole query set when it
+    /// has more than one
+    pub predicate: String,
+    pub accepted: usize,
+    /// How many identifiers, or for `import` predicates imports, there were
+    pub total: usize,
+    /// What the predicate is met by, which is `identifiers`, `imports`, or
+    /// `calls`
+    pub of: &'static str,
+}
+
+impl fmt::Display for Stat {
+    /// Writes the stat like `type=Session matched 42 of 913 identifiers`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} matched {} of {} {}", self.predicate, self.accepted, self.total, self.of)
+    }
+}
+
 pub struct QueryEngine {
     idents: Vec<Identifier>,
+    /// The index in `idents` of each identifier starting at each position
+    by_start: HashMap<usize, Vec<usize>>,
     functs: Vec<Function>,
+    imports: Vec<Import>,
+    calls: Vec<Call>,
+    /// The index in `calls` of the call whose name, and whose arguments,
+    /// start at each position
+    callees: HashMap<usize, usize>,
+    args: HashMap<usize, usize>,
+    /// The type each alias stands for, used to resolve types when matching
+    /// `type` criteria, or None if types are matched as written
+    aliases: Option<HashMap<String, String>>,
+    scopes: ScopeTree,
     offset: usize,
 }
 
@@ -9,7 +206,14 @@ impl QueryEngine {
     pub fn new() -> Self {
         Self {
             idents: vec![],
+            by_start: HashMap::new(),
             functs: vec![],
+            imports: vec![],
+            calls: vec![],
+            callees: HashMap::new(),
+            args: HashMap::new(),
+            aliases: None,
+            scopes: ScopeTree::new(vec![]),
             offset: 0,
         }
     }
@@ -18,80 +222,248 @@ impl QueryEngine {
         self.offset = offset;
     }
 
-    pub fn build(s: &String, i: Box<dyn Identifiers>, f: Box<dyn Functions>) -> Self {
+    /// Builds a QueryEngine from the parse results of a piece of source code
+    /// # Arguments
+    ///
+    /// * `parsed` - The parse results of the code queries will be evaluated against
+    /// * `resolve_aliases` - Whether `type` criteria match the type an alias
+    ///   stands for, so that with `typedef int Id;` both `[[type=Id]]` and
+    ///   `[[type=int]]` match identifiers of either type
+    pub fn build(parsed: &Parsed, resolve_aliases: bool) -> Self {
+        let aliases = if resolve_aliases {
+            Some(parsed.aliases.iter().map(|x| (x.name.clone(), x.typ.clone())).filter(|(x, y)| x != y).collect())
+        } else {
+            None
+        };
+        let mut by_start: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, ident) in parsed.identifiers.iter().enumerate() {
+            by_start.entry(ident.start).or_default().push(i);
+        }
         Self {
-            idents: i.read_identifiers(s),
-            functs: f.read_functions(s),
+            idents: parsed.identifiers.clone(),
+            by_start,
+            functs: parsed.functions.clone(),
+            imports: parsed.imports.clone(),
+            calls: parsed.calls.clone(),
+            callees: parsed.calls.iter().enumerate().map(|(i, x)| (x.start, i)).collect(),
+            args: parsed.calls.iter().enumerate().map(|(i, x)| (x.open + 1, i)).collect(),
+            aliases,
+            scopes: parsed.scopes.clone(),
             offset: 0,
         }
     }
 
-    pub fn query(&self, position: usize, query: &String) -> Option<usize> {
-        let mut c = crate::regexparser::query::QueriesParser::new()
-            .parse(query)
-            .unwrap();
-        let mut name = None;
-        let mut kind = None;
-        loop {
-            match *c {
-                crate::regexparser::ast::Queries::Query(x) => {
-                    match *x {
-                        crate::regexparser::ast::Query::Kv(k, v) if k == "type" => {
-                            kind = Some(v);
-                        }
-                        crate::regexparser::ast::Query::Kv(k, v) if k == "name" => {
-                            name = Some(v);
-                        }
-                        crate::regexparser::ast::Query::Kv(k, v) if k == "pos" => {
-                            let mut s = v.split(":");
-                            let (pos_str, len_str) = (s.next()?, s.next()?);
-                            let (pos, len) = (pos_str.parse::<usize>().ok()?, len_str.parse::<usize>().ok()?);
-                            if position + self.offset == pos {
-                                return Some(len);
-                            } else {
-                                return None;
-                            }
-                        }
-                        _ => {}
-                    }
-                    break;
+    /// Replaces every alias within a type with the type it stands for, until
+    /// no aliases are left, so `Id*` becomes `unsigned long*` given
+    /// `typedef unsigned long Id;`
+    fn resolve(&self, typ: &str) -> String {
+        let aliases = match self.aliases {
+            Some(ref x) => x,
+            None => return typ.to_string(),
+        };
+        let mut typ = typ.to_string();
+        // Bounded, as aliases may be cyclic
+        for _ in 0..8 {
+            let mut out = String::with_capacity(typ.len());
+            let mut word = String::new();
+            for c in typ.chars().chain(std::iter::once('\0')) {
+                if c.is_alphanumeric() || "_.:$".contains(c) {
+                    word.push(c);
+                    continue;
                 }
-                crate::regexparser::ast::Queries::Queries(x, r) => {
-                    match *x {
-                        crate::regexparser::ast::Query::Kv(k, v) if k == "type" => {
-                            kind = Some(v);
-                        }
-                        crate::regexparser::ast::Query::Kv(k, v) if k == "name" => {
-                            name = Some(v);
-                        }
-                        crate::regexparser::ast::Query::Kv(k, v) if k == "pos" => {
-                            let mut s = v.split(":");
-                            let (pos_str, len_str) = (s.next()?, s.next()?);
-                            let (pos, len) = (pos_str.parse::<usize>().ok()?, len_str.parse::<usize>().ok()?);
-                            if position + self.offset == pos {
-                                return Some(len);
-                            } else {
-                                return None;
-                            }
-                        }
-                        _ => {}
+                out += aliases.get(&word).unwrap_or(&word);
+                word.clear();
+                if c != '\0' {
+                    out.push(c);
+                }
+            }
+            if out == typ {
+                break;
+            }
+            typ = out;
+        }
+        typ
+    }
+
+    /// Whether an identifier is within a scope, given either the ID of the
+    /// scope, which matches it and every scope nested within it, or the name
+    /// of a function, which matches the function's parameters and body
+    fn in_scope(&self, ident: &Identifier, scope: &str) -> bool {
+        match scope.parse::<usize>() {
+            Ok(id) => self.scopes.encloses(id, ident.scope_id),
+            Err(_) => self
+                .functs
+                .iter()
+                .any(|f| f.name == scope && f.start <= ident.start && ident.start < f.end),
+        }
+    }
+
+    /// Finds the identifier or import a query set matches at a position,
+    /// parsing its criteria first, which `query_criteria` does not need to
+    pub fn query(&self, position: usize, query: &String) -> Option<usize> {
+        self.query_criteria(position, &Criteria::parse(query).ok()?)
+    }
+
+    /// Finds the identifier or import that meets parsed criteria at a
+    /// position
+    ///
+    /// # Returns
+    ///
+    /// The position the identifier or import ends at, less the offset, or
+    /// None if none starts at the position and meets the criteria
+    pub fn query_criteria(&self, position: usize, criteria: &Criteria) -> Option<usize> {
+        let Criteria { name, import, pos, call, .. } = criteria;
+        if let Some(pos) = pos {
+            let (pos, len) = (*pos)?;
+            return Some(position + len).filter(|_| position + self.offset == pos);
+        }
+        if let Some((part, _)) = call {
+            // Empty arguments match nothing, as a query set always moves on
+            let (calls, end): (_, fn(&Call) -> usize) = match part {
+                CallPart::Callee => (&self.callees, |x| x.open),
+                CallPart::Args => (&self.args, |x| x.end - 1),
+            };
+            let x = &self.calls[*calls.get(&(position + self.offset))?];
+            return Some(end(x) - self.offset).filter(|_| criteria.calls(x) && end(x) > position + self.offset);
+        }
+        if let Some(path) = import {
+            // An import matches its whole statement, and `name` is its alias
+            for i in &self.imports {
+                if *path == i.path
+                    && match name {
+                        Some(ref y) => i.alias.as_ref() == Some(y),
+                        None => true,
                     }
-                    c = r;
+                    && position + self.offset == i.start
+                {
+                    return Some(i.end - self.offset);
                 }
             }
+            return None;
         }
-        for ident in &self.idents {
-            if match name {
-                Some(ref y) => *y == ident.name,
-                None => true,
-            } && match kind {
-                Some(ref y) => *y == ident.typ,
-                None => true,
-            } && position + self.offset == ident.start
-            {
+        for ident in self.by_start.get(&(position + self.offset)).into_iter().flatten().map(|x| &self.idents[*x]) {
+            if self.satisfies(ident, criteria) {
                 return Some(ident.end - self.offset);
             }
         }
         None
     }
+
+    /// Whether an identifier meets the name, type, and scope of criteria
+    fn satisfies(&self, ident: &Identifier, criteria: &Criteria) -> bool {
+        let Criteria { name, kind, scope, .. } = criteria;
+        (match name {
+            Some(ref y) => *y == ident.name,
+            None => true,
+        }) && (match kind {
+            Some(ref y) => *y == ident.typ || (self.aliases.is_some() && self.resolve(y) == self.resolve(&ident.typ)),
+            None => true,
+        }) && (match scope {
+            Some(ref y) => self.in_scope(ident, y),
+            None => true,
+        })
+    }
+
+    /// Counts the identifiers, or imports, each predicate of a query set's
+    /// criteria accepts on its own, then, if there is more than one, how
+    /// many the whole set accepts, to show which predicate rules out a match
+    ///
+    /// # Returns
+    ///
+    /// A Stat for each predicate, in the order `Criteria::predicates` gives
+    /// them, then one for the whole set. `pos` criteria, which match a place
+    /// rather than anything parsed, have none.
+    pub fn stats(&self, criteria: &Criteria) -> Vec<Stat> {
+        if criteria.pos.is_some() {
+            return Vec::new();
+        }
+        let mut predicates = criteria.predicates();
+        if predicates.len() > 1 {
+            predicates.push(criteria.clone());
+        }
+        predicates
+            .into_iter()
+            .map(|c| match (&c.import, &c.call) {
+                (Some(path), _) => Stat {
+                    accepted: self.imports.iter().filter(|i| i.path == *path && c.name.as_ref().is_none_or(|x| i.alias.as_ref() == Some(x))).count(),
+                    total: self.imports.len(),
+                    of: "imports",
+                    predicate: c.to_string(),
+                },
+                (None, Some(_)) => Stat {
+                    accepted: self.calls.iter().filter(|x| c.calls(x)).count(),
+                    total: self.calls.len(),
+                    of: "calls",
+                    predicate: c.to_string(),
+                },
+                (None, None) => Stat {
+                    accepted: self.idents.iter().filter(|x| self.satisfies(x, &c)).count(),
+                    total: self.idents.len(),
+                    of: "identifiers",
+                    predicate: c.to_string(),
+                },
+            })
+            .collect()
+    }
+
+    /// Finds the first call whose name is between two positions and that
+    /// meets any of the `callee` or `args` criteria of a query's query sets,
+    /// whose arguments `${a1}` and its kin in a replacement refer to
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The position the call's name starts at or after
+    /// * `end` - The position the call's name starts before
+    /// * `criteria` - The criteria, with None for any that did not parse
+    pub fn call(&self, start: usize, end: usize, criteria: &[Option<Criteria>]) -> Option<Call> {
+        let criteria: Vec<&Criteria> = criteria.iter().flatten().filter(|x| x.call.is_some()).collect();
+        if criteria.is_empty() {
+            return None;
+        }
+        (start..end).filter_map(|x| self.callees.get(&x)).map(|x| &self.calls[*x]).find(|x| criteria.iter().any(|c| c.calls(x))).cloned()
+    }
+
+    /// Finds the identifiers between two positions that meet any of the
+    /// criteria of a query's query sets, other than those that match
+    /// imports, calls, or positions rather than identifiers
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The position the identifiers start at or after
+    /// * `end` - The position the identifiers end at or before
+    /// * `criteria` - The criteria, with None for any that did not parse
+    pub fn identifiers(&self, start: usize, end: usize, criteria: &[Option<Criteria>]) -> Vec<Identifier> {
+        let criteria: Vec<&Criteria> = criteria.iter().flatten().filter(|x| x.identifies()).collect();
+        if criteria.is_empty() {
+            return Vec::new();
+        }
+        (start..end)
+            .filter_map(|x| self.by_start.get(&x))
+            .flatten()
+            .map(|x| &self.idents[*x])
+            .filter(|x| x.end <= end && criteria.iter().any(|c| self.satisfies(x, c)))
+            .cloned()
+            .collect()
+    }
+}
+
+#[test]
+fn test_stats() -> Result<(), SpidiorError> {
+    use crate::languages::clike::Clike;
+    let text = "int count;\nvoid f(int a) { double count = a; }\n";
+    let qe = QueryEngine::build(&Parsed::new(text, &Clike {}), false);
+    let stats = |query: &str| -> Result<Vec<String>, SpidiorError> { Ok(qe.stats(&Criteria::parse(query)?).iter().map(|x| x.to_string()).collect()) };
+    assert_eq!(
+        stats("type=int,name=count,scope=f")?,
+        vec![
+            "name=count matched 2 of 4 identifiers",
+            "type=int matched 3 of 4 identifiers",
+            "scope=f matched 3 of 4 identifiers",
+            "name=count,type=int,scope=f matched 0 of 4 identifiers",
+        ]
+    );
+    assert_eq!(stats("tag=div")?, vec!["tag=div matched 0 of 4 identifiers"]);
+    assert_eq!(stats("import=java.util.List")?, vec!["import=java.util.List matched 0 of 0 imports"]);
+    assert!(stats("pos=1:2")?.is_empty());
+    Ok(())
 }
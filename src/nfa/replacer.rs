@@ -1,31 +1,156 @@
-use std::error::Error;
+use crate::{editing::editset::EditSet, error::SpidiorError, regexparser::ast::{Command, Replace, ReplaceItem, Replacement}};
+use crate::nfa::matcher::Match;
+use crate::languages::{cache::Parsed, clike::Clike, lexer, parsing::Identifier, Language};
+use super::matcher::{find_parsed, skip_disabled, skip_literals};
 
-use textbuffer::TextBuffer;
+/// Decides whether to make a replacement, given the match and the text
+/// that would replace it
+pub type Acceptor = fn(&Match, &str) -> bool;
 
-use crate::{editing::textbuffer, regexparser::ast::{Replace, Replacement}};
-use crate::nfa::matcher::Match;
-use super::matcher::find;
+pub fn replace(input: &str, replacement: Replace, acceptor: Acceptor) -> Result<String, SpidiorError> {
+    replace_in(input, replacement, acceptor, &Clike {}, false, false, false)
+}
 
-pub type Acceptor = fn(&str, &str) -> bool;
+/// Performs `replacement` on `input`, using `lang` to evaluate any query sets,
+/// leaving string and char literals untouched if `skip_strings` is set,
+/// resolving type aliases in `type` criteria if `resolve_aliases` is set, and
+/// leaving code disabled by conditional compilation untouched if
+/// `skip_disabled` is set
+pub fn replace_in(input: &str, replacement: Replace, acceptor: Acceptor, lang: &dyn Language, skip_strings: bool, resolve_aliases: bool, skip_disabled: bool) -> Result<String, SpidiorError> {
+    edits_parsed(input, replacement, acceptor, &Parsed::new(input, lang), skip_strings, resolve_aliases, skip_disabled)?.apply(input)
+}
 
-pub fn replace(input: &String, replacement: Replace, acceptor: Acceptor) -> Result<String, Box<dyn Error>> {
-    let matches = find(&input, replacement.clone().find);
-    let mut tb = TextBuffer::new();
-    let mut offset:i32 = 0;
-    tb.add(input);
+/// Finds the edits that performing `replacement` on `input` makes, as
+/// `replace_in` does, given the parse results `parsed` of `input`, which may
+/// come from the parse cache
+pub fn edits_parsed(input: &str, replacement: Replace, acceptor: Acceptor, parsed: &Parsed, skip_strings: bool, resolve_aliases: bool, skip_disabled: bool) -> Result<EditSet, SpidiorError> {
+    let mut matches = find_parsed(input, replacement.clone().find, parsed, resolve_aliases);
+    if skip_strings {
+        matches = skip_literals(matches, &parsed.spans);
+    }
+    if skip_disabled {
+        matches = self::skip_disabled(matches, &parsed.spans);
+    }
+    edits_found(input, &replacement, acceptor, matches)
+}
+
+/// Finds the edits that performing `replacement` at `matches`, which have
+/// already been found in `input`, makes, keeping those `acceptor` accepts
+///
+/// A replacement with the `n` flag only counts its matches, so makes no
+/// edits, and its replacement text is never built. A `d` command deletes
+/// every line a match is on, from its start to just past its newline, and
+/// `i` and `a` commands insert their replacement, with the indentation of
+/// the line the match starts on, as lines before or after them, each once
+/// however many matches are on them, built from the first.
+pub fn edits_found(input: &str, replacement: &Replace, acceptor: Acceptor, matches: Vec<Match>) -> Result<EditSet, SpidiorError> {
+    let mut edits = EditSet::new();
+    if replacement.count {
+        return Ok(edits);
+    }
+    let mut found = Vec::new();
     for m in matches {
-        let r = replace_to_string(&replacement.replace, &m, input);
-        let start = (m.start() as i32 + offset) as usize;
-        let to_replace = tb.get(start, m.len())?;
-        if acceptor(&to_replace, &r) {
-            tb.replace(start, m.len(), &r)?;
+        let r = match replacement.command {
+            Command::Delete => String::new(),
+            _ => replace_to_string(&replacement.replace, &m, input)?,
+        };
+        if acceptor(&m, &r) {
+            found.push((m, r));
+        }
+    }
+    if replacement.command == Command::Substitute {
+        for (m, r) in found {
+            edits.add(m.start(), m.start() + m.len(), r)?;
         }
-        offset += r.len() as i32 - m.len() as i32;
+        return Ok(edits);
+    }
+    let mut lines: Vec<(usize, usize, String)> = Vec::new();
+    for (m, r) in found {
+        let start = input[..m.start()].rfind('\n').map_or(0, |x| x + 1);
+        let last = if m.len() > 0 { m.end() - 1 } else { m.start() };
+        let end = input[last..].find('\n').map_or(input.len(), |x| last + x + 1);
+        match lines.last_mut() {
+            Some(x) if start < x.1 => x.1 = x.1.max(end),
+            _ => lines.push((start, end, r)),
+        }
+    }
+    for (start, end, r) in lines {
+        let line = &input[start..];
+        let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        let text: String = r.split('\n').map(|x| format!("{}{}\n", indent, x)).collect();
+        match replacement.command {
+            Command::Before => edits.add(start, start, text)?,
+            // The last line may have no newline to put the new one after
+            Command::After if !input[..end].ends_with('\n') => edits.add(end, end, format!("\n{}", &text[..text.len() - 1]))?,
+            Command::After => edits.add(end, end, text)?,
+            _ => edits.add(start, end, r)?,
+        }
+    }
+    Ok(edits)
+}
+
+/// Counts the replacements that matches, of one or more queries, would
+/// make, for the `n` flag, where a match overlapping one already counted is
+/// not counted again, as only one of the two could be replaced
+pub fn count(matches: &[Match]) -> usize {
+    let mut spans: Vec<(usize, usize)> = matches.iter().map(|m| (m.start(), m.end())).collect();
+    spans.sort_unstable();
+    let mut end = 0;
+    let mut n = 0;
+    for (start, x) in spans {
+        if start >= end {
+            n += 1;
+            end = x;
+        }
+    }
+    n
+}
+
+/// Finds where performing `replacement` at `matches`, which have already
+/// been found in code whose parse results are `parsed`, would rename
+/// something to a name already declared in a scope visible from it, which
+/// would shadow that declaration or be captured by it
+///
+/// Only a replacement that is a plain identifier, like `total`, renames
+/// anything, so for any other this is always empty. Identifiers that are
+/// themselves replaced do not count.
+///
+/// # Returns
+///
+/// Each match that would collide, with an identifier it would collide
+/// with, in the order of `matches`
+pub fn collisions(replacement: &Replace, matches: &[Match], parsed: &Parsed) -> Vec<(Match, Identifier)> {
+    let name = match replacement.replace.replacements.as_slice() {
+        [ReplaceItem::String(x)] => x,
+        _ => return Vec::new(),
+    };
+    let mut chars = name.chars();
+    if !chars.next().is_some_and(lexer::is_ident_start) || !chars.all(lexer::is_ident_char) {
+        return Vec::new();
     }
-    Ok(tb.consume())
+    let replaced = |x: &Identifier| matches.iter().any(|m| m.start() <= x.start && x.end <= m.end());
+    matches
+        .iter()
+        .filter_map(|m| {
+            let scope = parsed.scopes.innermost(m.start());
+            let found = parsed.scopes.collisions(&parsed.identifiers, scope, name).into_iter().find(|x| !replaced(x))?;
+            Some((m.clone(), found.clone()))
+        })
+        .collect()
 }
 
-fn replace_to_string(replacement: &Replacement, m: &Match, s: &String) -> String {
+/// Builds the text that `replacement` would replace a match in `s` with,
+/// with any backreferences filled in from the match's groups, passed through
+/// any functions they name, or with any arithmetic they ask for done on
+/// them, each conditional part chosen by whether its group matched any
+/// text, and any arguments filled in from the match's call
+///
+/// # Returns
+///
+/// A Result<String, SpidiorError>, where on success, it returns the text. It
+/// will Err with an EditError if a group with arithmetic done on it is not a
+/// whole number, or the arithmetic overflows.
+pub fn replace_to_string(replacement: &Replacement, m: &Match, s: &str) -> Result<String, SpidiorError> {
     let mut ret = String::new();
     for ri in &replacement.replacements {
         match ri {
@@ -35,9 +160,30 @@ fn replace_to_string(replacement: &Replacement, m: &Match, s: &String) -> String
             crate::regexparser::ast::ReplaceItem::BackRef(x) => {
                 ret += &m.get_group(*x as usize, s);
             }
+            crate::regexparser::ast::ReplaceItem::Function(x, name) => {
+                // Functions are checked to exist when the query is parsed
+                let group = m.get_group(*x, s);
+                ret += &crate::functions::get(name).map_or(group.clone(), |f| f(&group));
+            }
+            crate::regexparser::ast::ReplaceItem::Arithmetic(x, op, number) => {
+                let group = m.get_group(*x, s);
+                let expr = format!("${{{}{}{}}}", x, op.symbol(), number);
+                let value = group.parse::<i64>().map_err(|_| SpidiorError::edit(format!("Group {} is `{}` at byte {}, which is not a whole number, so `{}` cannot be done", x, group, m.start(), expr)))?;
+                let result = op.apply(value, *number).ok_or_else(|| SpidiorError::edit(format!("`{}` overflows with group {} as {}, at byte {}", expr, x, value, m.start())))?;
+                ret += &result.to_string();
+            }
+            crate::regexparser::ast::ReplaceItem::Argument(x) => {
+                ret += &m.get_argument(*x, s);
+            }
+            crate::regexparser::ast::ReplaceItem::Conditional(x, matched, otherwise) => {
+                // A group that took no part in the match is as empty as one
+                // that matched nothing
+                let chosen = if m.get_group(*x, s).is_empty() { otherwise } else { matched };
+                ret += &replace_to_string(chosen, m, s)?;
+            }
         }
     }
-    ret
+    Ok(ret)
 }
 
 #[test]
@@ -51,7 +197,7 @@ fn test_replace_to_string() -> Result<(), Box<dyn std::error::Error>> {
             crate::regexparser::ast::ReplaceItem::String("!".into()),
         ],
     };
-    assert_eq!(replace_to_string(&x, &m, &"".to_string()), "hello world!");
+    assert_eq!(replace_to_string(&x, &m, "")?, "hello world!");
     Ok(())
 }
 
@@ -59,47 +205,105 @@ fn test_replace_to_string() -> Result<(), Box<dyn std::error::Error>> {
 fn test_replace() -> Result<(), Box<dyn std::error::Error>> {
     use crate::{regexparser};
     let regex = regexparser::parse("%s/bill/bob/g")?;
-    assert_eq!(replace(&"joejoe".into(), regex, |x, y| true)?, "joejoe");
+    assert_eq!(replace("joejoe", regex, |x, y| true)?, "joejoe");
 
     let regex = regexparser::parse("%s/bob|joe|e*/bob/g")?;
-    assert_eq!(replace(&"joejoe".into(), regex, |x, y| true)?, "bobbob");
+    assert_eq!(replace("joejoe", regex, |x, y| true)?, "bobbob");
 
     let regex = regexparser::parse("%s/bob|joe|e*/jack/g")?;
-    assert_eq!(replace(&"joee".into(), regex, |x, y| true)?, "jackjack");
+    assert_eq!(replace("joee", regex, |x, y| true)?, "jackjack");
 
     let regex = regexparser::parse("%s/bob|joe|e*/o/g")?;
-    assert_eq!(replace(&"joeejoe".into(), regex, |x, y| true)?, "ooo");
+    assert_eq!(replace("joeejoe", regex, |x, y| true)?, "ooo");
 
     let regex = regexparser::parse("%s/(joe)*/bob/g")?;
-    assert_eq!(replace(&"joejoejoejo".into(), regex, |x, y| true)?, "bobjo");
+    assert_eq!(replace("joejoejoejo", regex, |x, y| true)?, "bobjo");
 
     let regex = regexparser::parse("%s/(joe)*/bob/g")?;
-    assert_eq!(replace(&"eee".into(), regex, |x, y| true)?, "eee");
+    assert_eq!(replace("eee", regex, |x, y| true)?, "eee");
 
     let regex = regexparser::parse("%s/jo*e/bob/g")?;
-    assert_eq!(replace(&"jejoejooeej".into(), regex, |x, y| true)?, "bobbobbobej");
+    assert_eq!(replace("jejoejooeej", regex, |x, y| true)?, "bobbobbobej");
 
     let regex = regexparser::parse("%s/jo+e/bob/g")?;
-    assert_eq!(replace(&"jejoejooeej".into(), regex, |x, y| true)?, "jebobbobej");
+    assert_eq!(replace("jejoejooeej", regex, |x, y| true)?, "jebobbobej");
+
+    let regex = regexparser::parse("%s/(get_[a-z_]*)/${1:camelCase}()/g")?;
+    assert_eq!(replace("get_user_id;", regex, |_, _| true)?, "getUserId();");
+
+    let regex = regexparser::parse("%s/([a-z]+)=([0-9]*)/\\1=${2:-0}${2:+ (set ${2+1})}/g")?;
+    assert_eq!(replace("a=5 b=", regex, |_, _| true)?, "a=5 (set 6) b=0");
+
+    let regex = regexparser::parse("%s/port=([0-9]+)/port=${1+1000}/g")?;
+    assert_eq!(replace("port=80, port=443", regex, |_, _| true)?, "port=1080, port=1443");
+
+    let regex = regexparser::parse("%s/x=([a-z0-9]+)/x=${1*2}/g")?;
+    assert!(matches!(replace("x=4 x=ab", regex, |_, _| true), Err(SpidiorError::EditError(_))));
+
+    let regex = regexparser::parse("%s/([0-9]+)/${1*2}/g")?;
+    assert!(replace("9223372036854775807", regex, |_, _| true).is_err());
 
     let regex = regexparser::parse("%s/[a-z]*/bob/g")?;
-    assert_eq!(replace(&"-2607".into(), regex, |x, y| true)?, "-2607");
+    assert_eq!(replace("-2607", regex, |x, y| true)?, "-2607");
 
     let regex = regexparser::parse("%s/[a-z]*/bob/g")?;
-    assert_eq!(replace(&"-2e6f0z7a".into(), regex, |x, y| true)?, "-2bob6bob0bob7bob");
+    assert_eq!(replace("-2e6f0z7a", regex, |x, y| true)?, "-2bob6bob0bob7bob");
 
     let regex = regexparser::parse("%s/[^a-z]*/bob/g")?;
-    assert_eq!(replace(&"joe".into(), regex, |x, y| true)?, "joe");
+    assert_eq!(replace("joe", regex, |x, y| true)?, "joe");
 
     let regex = regexparser::parse("%s/[^a-z]*/bob/g")?;
-    assert_eq!(replace(&"2607".into(), regex, |x, y| true)?, "bob");
+    assert_eq!(replace("2607", regex, |x, y| true)?, "bob");
+    Ok(())
+}
+#[test]
+fn test_replace_skip_strings() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{regexparser};
+    let regex = regexparser::parse("%s/me/sess/g")?;
+    let input = "log(\"me\", me); // me".to_string();
+    assert_eq!(replace_in(&input, regex.clone(), |_, _| true, &Clike {}, true, false, false)?, "log(\"me\", sess); // sess");
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "log(\"sess\", sess); // sess");
+    Ok(())
+}
+
+#[test]
+fn test_replace_import() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{regexparser};
+    let regex = regexparser::parse("%s/[[import=java.util.Vector]]/import java.util.ArrayList;/g")?;
+    let input = "import java.util.Vector;\nimport java.util.Map;\nclass Vector {}\n".to_string();
+    assert_eq!(
+        replace_in(&input, regex, |_, _| true, &Clike {}, true, false, false)?,
+        "import java.util.ArrayList;\nimport java.util.Map;\nclass Vector {}\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_replace_resolve_aliases() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{regexparser};
+    let input = "typedef double Score;\nScore a;\ndouble b;\nint c;\n".to_string();
+    let regex = regexparser::parse("%s/[[type=double]]/x/g")?;
+    assert_eq!(replace_in(&input, regex.clone(), |_, _| true, &Clike {}, false, false, false)?, "typedef double Score;\nScore a;\ndouble x;\nint c;\n");
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, true, false)?, "typedef double Score;\nScore x;\ndouble x;\nint c;\n");
+    let regex = regexparser::parse("%s/[[type=Score]]/x/g")?;
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, true, false)?, "typedef double Score;\nScore x;\ndouble x;\nint c;\n");
     Ok(())
 }
+
+#[test]
+fn test_replace_macro() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{regexparser};
+    let regex = regexparser::parse("%s/[[macro=LIMIT]]/MAX_SPELLS/g")?;
+    let input = "#define LIMIT 4\nint LIMITS[LIMIT];\n".to_string();
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "#define MAX_SPELLS 4\nint LIMITS[MAX_SPELLS];\n");
+    Ok(())
+}
+
 #[test]
 fn test_replace_backref() -> Result<(), Box<dyn std::error::Error>> {
     use crate::{regexparser};
     let regex = regexparser::parse("%s/(1)/\\1\\1/g")?;
-    assert_eq!(replace(&"1".into(), regex, |x, y| true)?, "11");
+    assert_eq!(replace("1", regex, |x, y| true)?, "11");
     Ok(())
 }
 
@@ -108,10 +312,119 @@ fn test_replace_backref() -> Result<(), Box<dyn std::error::Error>> {
 fn test_pos() -> Result<(), Box<dyn std::error::Error>> {
     use crate::{regexparser};
     let regex = regexparser::parse("%s/[[pos=0:3]]/bob/g")?;
-    assert_eq!(replace(&"joejoe".into(), regex, |x, y| true)?, "bobjoe");
+    assert_eq!(replace("joejoe", regex, |x, y| true)?, "bobjoe");
     let regex = regexparser::parse("%s/[[pos=1:3]]/bob/g")?;
-    assert_eq!(replace(&"joejoe".into(), regex, |x, y| true)?, "jboboe");
+    assert_eq!(replace("joejoe", regex, |x, y| true)?, "jboboe");
     let regex = regexparser::parse("%s/[[pos=2:1]]joe/bob/g")?;
-    assert_eq!(replace(&"joejoe".into(), regex, |x, y| true)?, "jobob");
+    assert_eq!(replace("joejoe", regex, |x, y| true)?, "jobob");
+    Ok(())
+}
+#[test]
+fn test_replace_unicode() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{regexparser};
+    let regex = regexparser::parse("%s/[[name=naïve]]/simple/g")?;
+    let input = "/* ünïcödé */ int naïve = 1; naïve++;".to_string();
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "/* ünïcödé */ int simple = 1; simple++;");
+    Ok(())
+}
+
+#[test]
+fn test_replace_without_g() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{regexparser};
+    // Every match is replaced, whether or not `g` is given
+    let regex = regexparser::parse("%s/Old/New/")?;
+    assert!(!regex.global);
+    assert_eq!(replace("OldOld", regex, |_, _| true)?, "NewNew");
+    Ok(())
+}
+
+#[test]
+fn test_replace_scope() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{regexparser};
+    let input = "int a;\nvoid f(int a) { a++; { a--; } }\nvoid g() { a = 1; }\n".to_string();
+    let regex = regexparser::parse("%s/[[name=a,scope=f]]/b/g")?;
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "int a;\nvoid f(int b) { b++; { b--; } }\nvoid g() { a = 1; }\n");
+    let regex = regexparser::parse("%s/[[name=a,scope=2]]/b/g")?;
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "int a;\nvoid f(int a) { a++; { b--; } }\nvoid g() { a = 1; }\n");
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_replace_calls() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "int load(int a, int b) { return a; }\nvoid f() { load(1, g(2)); /* load(3) */ load (); }\n".to_string();
+    let regex = regexparser::parse("%s/[[call=load]]/load(${1:+${1}, }0)/g")?;
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "int load(int a, int b) { return a; }\nvoid f() { load(1, g(2), 0); /* load(3) */ load(0); }\n");
+    // The arguments are the first group and the whole call the second, both
+    // within the group around them
+    let regex = regexparser::parse("%s/-([[call=g]])/\\3.\\1/g")?;
+    assert_eq!(replace_in("int y = -g(3);", regex, |_, _| true, &Clike {}, false, false, false)?, "int y = g(3).3;");
+    let input = "void f() { connect(host, port); connect(pick(a, b), 80); connect(host); }".to_string();
+    let regex = regexparser::parse("%s/[[call=connect,argc=2]]/connect(${a2}, ${a1}, 30)/g")?;
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "void f() { connect(port, host, 30); connect(80, pick(a, b), 30); connect(host); }");
+    Ok(())
+}
+
+#[test]
+fn test_delete_lines() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{editing::editset::Conflict, regexparser};
+    let input = "Session s = open();\ns.log(1); s.log(2);\nint log = 0;\nlog++;".to_string();
+    let regex = regexparser::parse("%d/[[type=Session]].log/g")?;
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "Session s = open();\nint log = 0;\nlog++;");
+    // Deletions compose with substitutions on other lines, and a match on
+    // the last line takes it without a newline after it
+    let parsed = Parsed::new(&input, &Clike {});
+    let mut edits = edits_parsed(&input, regexparser::parse("%d/[[type=Session]].log/g")?, |_, _| true, &parsed, false, false, false)?;
+    edits.extend(edits_parsed(&input, regexparser::parse("%s/open/connect/g")?, |_, _| true, &parsed, false, false, false)?, Conflict::Error, &input, "")?;
+    edits.extend(edits_parsed(&input, regexparser::parse("%d/log\\+\\+/g")?, |_, _| true, &parsed, false, false, false)?, Conflict::Error, &input, "")?;
+    assert_eq!(edits.apply(&input)?, "Session s = connect();\nint log = 0;\n");
+    Ok(())
+}
+
+#[test]
+fn test_insert_lines() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "void f() {\n    s.close(); s.close();\n\tconnect(host);\n}".to_string();
+    let regex = regexparser::parse("%i/[[call=close]]/log(\"closing\");/g")?;
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "void f() {\n    log(\"closing\");\n    s.close(); s.close();\n\tconnect(host);\n}");
+    // Groups fill in the line, and a match on the last line puts a newline
+    // before it
+    let regex = regexparser::parse("%a/([[call=connect]])|}/\\/\\/ ${3:-end}/g")?;
+    assert_eq!(replace_in(&input, regex, |_, _| true, &Clike {}, false, false, false)?, "void f() {\n    s.close(); s.close();\n\tconnect(host);\n\t// connect(host)\n}\n// end");
+    Ok(())
+}
+
+#[test]
+fn test_count() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "int a; a++; ab;".to_string();
+    assert_eq!(replace(&input, regexparser::parse("%s/a/b/gn")?, |_, _| true)?, input);
+    let mut found = find_parsed(&input, regexparser::parse("%s/a/b/gn")?.find, &Parsed::new(&input, &Clike {}), false);
+    assert_eq!(count(&found), 3);
+    // `ab` overlaps the `a` already counted, and `+;` follows it
+    found.extend(find_parsed(&input, regexparser::parse("%s/ab|\\+;//gn")?.find, &Parsed::new(&input, &Clike {}), false));
+    assert_eq!(count(&found), 4);
+    Ok(())
+}
+
+#[test]
+fn test_collisions() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::regexparser;
+    let input = "int total;\nvoid f() { int count = 1; count++; }\nvoid g() { int count; { int sum; } }\n".to_string();
+    let parsed = Parsed::new(&input, &Clike {});
+    let check = |query: &str| -> Result<Vec<(usize, usize)>, SpidiorError> {
+        let replace = regexparser::parse(query)?;
+        let found = find_parsed(&input, replace.find.clone(), &parsed, false);
+        Ok(collisions(&replace, &found, &parsed).iter().map(|(m, x)| (m.start(), x.start)).collect())
+    };
+    // `total` is global, so visible from every `count`
+    assert_eq!(check("%s/[[name=count]]/total/g")?.len(), 3);
+    // `sum` is only declared within a block inside `g`,
+    assert!(check("%s/[[name=count]]/sum/g")?.is_empty());
+    // but a `sum` renamed to `count` would shadow the `count` of `g`
+    assert_eq!(check("%s/[[name=sum]]/count/g")?, vec![(input.find("sum").unwrap(), input.rfind("count").unwrap())]);
+    // Not a plain identifier, or renaming what it would collide with too
+    assert!(check("%s/[[name=count]]/total + 1/g")?.is_empty());
+    assert!(check("%s/[[name=count]]|[[name=total]]/total/g")?.is_empty());
+    Ok(())
+}
@@ -0,0 +1,389 @@
+//! Provides a tagged DFA, built from an NFA ahead of matching, so that a
+//! match and the boundaries of its groups are found in a single pass over
+//! the input, with a table lookup for each character rather than a step of
+//! every NFA state
+//!
+//! Each DFA state is the list of NFA states the NFA would be in after the
+//! same input, ordered by preference: a star prefers to go round again, and
+//! a union prefers its left side. Each NFA state in the list keeps its own
+//! group boundaries, in registers. A transition says, for each NFA state it
+//! leads to, which state of the list before it came from, and the tags, the
+//! opens and closes of groups, passed on the way, which are applied to a
+//! copy of that state's registers. A match takes the boundaries of the most
+//! preferred NFA state to reach the end, so `(o*)o` against `ooooo` gives
+//! `oooo` as its group.
+//!
+//! Characters are grouped into classes that every transition treats alike,
+//! so `[a-z]+` has two, one for the letters and one for every other
+//! character, and the table has a column for each class rather than each
+//! character. An NFA with query sets, whose transitions depend on where
+//! they are rather than what they read, or that steps over grapheme
+//! clusters, cannot be made into a tagged DFA, nor can one whose table
+//! would be larger than `MAX_TABLE`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::matcher::Match;
+use super::{Group, Nfa, NodePointer, TransitionType};
+
+/// The most entries, states times classes, a table may have
+pub const MAX_TABLE: usize = 1 << 18;
+
+/// A change to the group boundaries, made at the index it is passed at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tag {
+    Open(usize),
+    Close(usize),
+}
+
+/// The NFA states a DFA state or transition leads to, in order of
+/// preference, each with the NFA state of the list before it that it came
+/// from and the tags passed on the way
+type Threads = Vec<(usize, usize, Vec<Tag>)>;
+
+/// A transition between DFA states
+#[derive(Debug)]
+struct Edge {
+    next: usize,
+    /// For each NFA state of `next`, the index of the NFA state it came
+    /// from and the tags to apply to a copy of its registers
+    threads: Vec<(usize, Vec<Tag>)>,
+}
+
+/// The class of each character, which is the column of the table its
+/// transitions are in
+#[derive(Debug)]
+struct Classes {
+    /// The class of each ASCII character, which most input is
+    ascii: [usize; 128],
+    /// The class of each other character the NFA names, and which is not
+    /// in the same class as the characters it does not name
+    named: HashMap<char, usize>,
+    /// The class of every other character
+    other: usize,
+    /// How many classes there are
+    len: usize,
+}
+
+impl Classes {
+    /// Groups the characters by the consuming transitions of an NFA that
+    /// take them, so each class is the characters taken by the same
+    /// transitions
+    ///
+    /// # Returns
+    ///
+    /// The classes, and a character from each, or None if the NFA has
+    /// query sets or names every character
+    fn new(nfa: &Nfa) -> Option<(Self, Vec<char>)> {
+        let mut named = BTreeSet::new();
+        let mut consuming = Vec::new();
+        for node in &nfa.nodes {
+            for t in &node.transitions {
+                match &t.kind {
+                    TransitionType::Alpha(c) => {
+                        named.insert(*c);
+                    }
+                    TransitionType::Range(s) | TransitionType::NegativeRange(s) => named.extend(s.chars()),
+                    TransitionType::Any => {
+                        named.insert('\n');
+                    }
+                    TransitionType::QuerySetRange(_) => return None,
+                    TransitionType::Epsilon | TransitionType::Open(_) | TransitionType::Close(_) => continue,
+                }
+                consuming.push(&t.kind);
+            }
+        }
+        // Any character the NFA does not name stands in for all of them
+        let unnamed = (0..=char::MAX as u32).rev().filter_map(char::from_u32).find(|x| !named.contains(x))?;
+        let mut ids: HashMap<Vec<bool>, usize> = HashMap::new();
+        let mut symbols = Vec::new();
+        let mut class = |c: char| {
+            let taken = consuming.iter().map(|x| matches(x, c)).collect();
+            *ids.entry(taken).or_insert_with(|| {
+                symbols.push(c);
+                symbols.len() - 1
+            })
+        };
+        let other = class(unnamed);
+        let mut classes = Self {
+            ascii: [other; 128],
+            named: HashMap::new(),
+            other,
+            len: 0,
+        };
+        for c in named {
+            let id = class(c);
+            if c.is_ascii() {
+                classes.ascii[c as usize] = id;
+            } else if id != other {
+                classes.named.insert(c, id);
+            }
+        }
+        classes.len = symbols.len();
+        Some((classes, symbols))
+    }
+
+    fn of(&self, c: char) -> usize {
+        if c.is_ascii() {
+            self.ascii[c as usize]
+        } else {
+            self.named.get(&c).copied().unwrap_or(self.other)
+        }
+    }
+}
+
+/// A deterministic automaton whose transitions set group boundaries
+#[derive(Debug)]
+pub struct Tdfa {
+    classes: Classes,
+    /// The index into `edges` for each state and class, or None where no
+    /// NFA state is left
+    table: Vec<Option<usize>>,
+    edges: Vec<Edge>,
+    /// The tags applied to each NFA state of the first DFA state before
+    /// any input is read
+    initial: Vec<Vec<Tag>>,
+    /// For each state, the index of its most preferred NFA state that is
+    /// the NFA's end, if it has one
+    accepting: Vec<Option<usize>>,
+    /// The number of groups, plus one, as groups count from one
+    groups: usize,
+    /// The most NFA states any DFA state has
+    width: usize,
+}
+
+impl Tdfa {
+    /// Builds a tagged DFA from an NFA
+    ///
+    /// # Arguments
+    ///
+    /// * `nfa` - The NFA
+    /// * `start` - The NFA's start state
+    /// * `end` - The NFA's end state, which a match must reach
+    ///
+    /// # Returns
+    ///
+    /// The tagged DFA, or None if the NFA has query sets, steps over
+    /// grapheme clusters, or would need a table larger than `MAX_TABLE`
+    pub fn new(nfa: &Nfa, start: NodePointer, end: NodePointer) -> Option<Self> {
+        if nfa.clusters {
+            return None;
+        }
+        let (classes, symbols) = Classes::new(nfa)?;
+
+        let first = closure(nfa, end, vec![(0, start)]);
+        let initial = first.iter().map(|x| x.2.clone()).collect();
+        let mut states: Vec<Vec<usize>> = vec![first.iter().map(|x| x.0).collect()];
+        let mut ids = HashMap::from([(states[0].clone(), 0)]);
+        let mut table = Vec::new();
+        let mut edges = Vec::new();
+        let mut i = 0;
+        while i < states.len() {
+            if (states.len() * symbols.len()).max(table.len() + symbols.len()) > MAX_TABLE {
+                return None;
+            }
+            for c in &symbols {
+                let moved: Vec<(usize, NodePointer)> = states[i]
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(j, x)| nfa.nodes[*x].transitions.iter().map(move |t| (j, t)))
+                    .filter(|(_, t)| matches(&t.kind, *c))
+                    .map(|(j, t)| (j, t.dest))
+                    .collect();
+                if moved.is_empty() {
+                    table.push(None);
+                    continue;
+                }
+                let threads = closure(nfa, end, moved);
+                let list: Vec<usize> = threads.iter().map(|x| x.0).collect();
+                let next = *ids.entry(list.clone()).or_insert_with(|| {
+                    states.push(list);
+                    states.len() - 1
+                });
+                table.push(Some(edges.len()));
+                edges.push(Edge {
+                    next,
+                    threads: threads.into_iter().map(|x| (x.1, x.2)).collect(),
+                });
+            }
+            i += 1;
+        }
+        let accepting = states.iter().map(|x| x.iter().position(|x| *x == end.id)).collect();
+        let width = states.iter().map(Vec::len).max().unwrap_or(0);
+        Some(Self {
+            classes,
+            table,
+            edges,
+            initial,
+            accepting,
+            groups: nfa.index + 1,
+            width,
+        })
+    }
+
+    /// Finds the longest match starting at `at` that ends by `limit`, with
+    /// its groups
+    pub fn longest(&self, input: &str, at: usize, limit: usize) -> Option<Match> {
+        let c = input[at..].chars().next().filter(|_| at < limit)?;
+        let class = self.classes.of(c);
+        // Most positions match nothing, so are ruled out before any
+        // registers are made
+        self.table[class]?;
+        let g = self.groups;
+        let blank = Group { start: 0, len: 0 };
+        let mut registers = vec![blank; self.width * g];
+        let mut spare = registers.clone();
+        for (j, tags) in self.initial.iter().enumerate() {
+            apply(&mut registers[j * g..(j + 1) * g], tags, 0);
+        }
+        let mut state = 0;
+        let mut found = None;
+        for (i, c) in input[at..].char_indices() {
+            if at + i >= limit {
+                break;
+            }
+            let edge = match self.table[state * self.classes.len + self.classes.of(c)] {
+                Some(x) => &self.edges[x],
+                None => break,
+            };
+            let index = i + c.len_utf8();
+            for (j, (from, tags)) in edge.threads.iter().enumerate() {
+                spare[j * g..(j + 1) * g].clone_from_slice(&registers[from * g..(from + 1) * g]);
+                apply(&mut spare[j * g..(j + 1) * g], tags, index);
+            }
+            std::mem::swap(&mut registers, &mut spare);
+            state = edge.next;
+            if let Some(j) = self.accepting[state] {
+                found = Some(Match::new(at, index, registers[j * g..(j + 1) * g].to_vec()));
+            }
+        }
+        found
+    }
+}
+
+/// Whether a transition consumes a character
+fn matches(kind: &TransitionType, c: char) -> bool {
+    match kind {
+        TransitionType::Alpha(x) => *x == c,
+        TransitionType::Range(s) => s.contains(c),
+        TransitionType::NegativeRange(s) => !s.contains(c),
+        TransitionType::Any => c != '\n',
+        _ => false,
+    }
+}
+
+/// Follows the transitions that consume no input from each seed in turn,
+/// depth first and in the order the transitions were added
+///
+/// # Arguments
+///
+/// * `nfa` - The NFA
+/// * `end` - The NFA's end state
+/// * `seeds` - The states to start from, each with the index of the
+///   state it came from
+///
+/// # Returns
+///
+/// The states reached that consume input or are the end, in the order
+/// they were first reached, each with the index of the seed's state it
+/// came from and the tags passed on the way
+fn closure(nfa: &Nfa, end: NodePointer, seeds: Vec<(usize, NodePointer)>) -> Threads {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for (from, seed) in seeds {
+        let mut stack = vec![(seed, Vec::new())];
+        while let Some((x, tags)) = stack.pop() {
+            if !seen.insert(x.id) {
+                continue;
+            }
+            let node = &nfa.nodes[x.id];
+            let consumes = node.transitions.iter().any(|t| matches!(t.kind, TransitionType::Alpha(_) | TransitionType::Range(_) | TransitionType::NegativeRange(_) | TransitionType::Any));
+            if consumes || x == end {
+                out.push((x.id, from, tags.clone()));
+            }
+            for t in node.transitions.iter().rev() {
+                let tag = match t.kind {
+                    TransitionType::Epsilon => None,
+                    TransitionType::Open(i) => Some(Tag::Open(i)),
+                    TransitionType::Close(i) => Some(Tag::Close(i)),
+                    _ => continue,
+                };
+                let mut tags = tags.clone();
+                tags.extend(tag);
+                stack.push((t.dest, tags));
+            }
+        }
+    }
+    out
+}
+
+/// Applies tags to a state's registers at an index, relative to the start
+/// of the match
+fn apply(groups: &mut [Group], tags: &[Tag], index: usize) {
+    for tag in tags {
+        match *tag {
+            Tag::Open(i) => groups[i].start = index,
+            Tag::Close(i) => groups[i].len = index - groups[i].start,
+        }
+    }
+}
+
+#[test]
+fn test_tdfa() {
+    use crate::regex2nfa::build_nfa;
+    use crate::regexparser;
+    let build = |query: &str| {
+        let (nfa, start, end) = build_nfa(regexparser::parse(query).unwrap().find);
+        Tdfa::new(&nfa, start, end)
+    };
+    let tdfa = build("%s/bob|joe|e*//g").unwrap();
+    assert_eq!(tdfa.longest("bob dole", 0, 8).map(|x| x.len()), Some(3));
+    assert!(tdfa.longest("bo", 0, 2).is_none());
+    assert_eq!(tdfa.longest("eeex", 0, 4).map(|x| x.len()), Some(3));
+    assert_eq!(tdfa.longest("xeee", 1, 3).map(|x| x.len()), Some(2));
+    let tdfa = build("%s/a(b*)(c|é)//g").unwrap();
+    let m = tdfa.longest("xabbé", 1, 7).unwrap();
+    assert_eq!((m.start(), m.len()), (1, 5));
+    assert_eq!(m.get_group(1, "xabbé"), "bb");
+    assert_eq!(m.get_group(2, "xabbé"), "é");
+    let m = build("%s/(a|ab)(c|bcd)//g").unwrap().longest("abcd", 0, 4).unwrap();
+    assert_eq!((m.get_group(1, "abcd"), m.get_group(2, "abcd")), ("a".to_string(), "bcd".to_string()));
+    let m = build("%s/(ab)*//g").unwrap().longest("ababx", 0, 5).unwrap();
+    assert_eq!((m.len(), m.get_group(1, "abab")), (4, "ab".to_string()));
+    let tdfa = build("%s/[^a-c].//g").unwrap();
+    assert_eq!(tdfa.longest("zz", 0, 2).map(|x| x.len()), Some(2));
+    assert!(tdfa.longest("az", 0, 2).is_none());
+    assert!(tdfa.longest("z\n", 0, 2).is_none());
+    assert!(build("%s/[[name=x]]//g").is_none());
+    assert!(build("%s/.//gc").is_none());
+}
+
+#[test]
+fn test_classes() {
+    use crate::regex2nfa::build_nfa;
+    use crate::regexparser;
+    let build = |query: &str| {
+        let (nfa, start, end) = build_nfa(regexparser::parse(query).unwrap().find);
+        Tdfa::new(&nfa, start, end).unwrap()
+    };
+    // Every letter is taken by the same transition, so they are one class,
+    // and the table has a column for it and one for every other character
+    let tdfa = build("%s/[a-z]+//g");
+    assert_eq!(tdfa.classes.len, 2);
+    assert_eq!(tdfa.table.len() % tdfa.classes.len, 0);
+    assert_eq!(tdfa.classes.of('a'), tdfa.classes.of('q'));
+    assert_eq!((tdfa.classes.of('A'), tdfa.classes.of('é')), (tdfa.classes.other, tdfa.classes.other));
+    let tdfa = build("%s/(ab|[a-c])x*//g");
+    assert_eq!(tdfa.classes.len, 5);
+    assert_eq!((tdfa.classes.of('c'), tdfa.classes.of('x'), tdfa.classes.of('é')), (3, 4, tdfa.classes.other));
+    assert_eq!(build("%s/[^a-c].//g").classes.len, 3);
+    // Characters beyond ASCII that the query names get their own class,
+    // shared by those taken alike
+    let tdfa = build("%s/[éè]x|ü//g");
+    assert_eq!(tdfa.classes.of('é'), tdfa.classes.of('è'));
+    assert!(tdfa.classes.of('é') != tdfa.classes.of('ü') && tdfa.classes.of('ü') != tdfa.classes.other);
+    assert_eq!(tdfa.classes.of('ö'), tdfa.classes.other);
+    assert_eq!(tdfa.longest("èx", 0, 3).map(|x| x.len()), Some(3));
+    assert_eq!(tdfa.longest("ü", 0, 2).map(|x| x.len()), Some(2));
+    assert!(tdfa.longest("öx", 0, 3).is_none());
+}
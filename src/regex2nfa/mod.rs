@@ -3,45 +3,111 @@
 
 use std::collections::HashSet;
 
+use crate::error::SpidiorError;
+use crate::nfa::combinators::{Fragment, Repeat};
 use crate::{nfa::NodePointer, regexparser::parse_set};
 
 use super::nfa::Nfa;
 use super::regexparser::ast::*;
 
+/// Checks that a regex can be built into an NFA, as `build_nfa` expects of
+/// the regexes it is given, which every regex `regexparser` parses has been
+///
+/// # Returns
+///
+/// A Result<(), SpidiorError>, which will Err with a ParseError for a set
+/// whose items cannot be parsed, or a CompileError for a `$`, which no
+/// engine matches
+pub fn check(r: &Regex) -> Result<(), SpidiorError> {
+    match r {
+        Regex::Union(r) => {
+            let Union::O(x, y) = r.as_ref();
+            check(x)?;
+            check_simple(y)
+        }
+        Regex::Simple(r) => check_simple(r),
+    }
+}
+
+fn check_simple(r: &Simple) -> Result<(), SpidiorError> {
+    match r {
+        Simple::Concatenation(r) => {
+            let Concatenation::O(x, y) = r.as_ref();
+            check_simple(x)?;
+            check_basic(y)
+        }
+        Simple::Basic(r) => check_basic(r),
+    }
+}
+
+fn check_basic(r: &Basic) -> Result<(), SpidiorError> {
+    match r {
+        Basic::Star(x) => {
+            let Star::O(e) = x.as_ref();
+            check_elem(e)
+        }
+        Basic::Plus(x) => {
+            let Plus::O(e) = x.as_ref();
+            check_elem(e)
+        }
+        Basic::Elementary(e) => check_elem(e),
+    }
+}
+
+fn check_elem(e: &Elementary) -> Result<(), SpidiorError> {
+    match e {
+        Elementary::Group(g) => {
+            let Group::O(r) = g.as_ref();
+            check(r)
+        }
+        Elementary::Eos(_) => Err(SpidiorError::compile("`$` is not supported; match the end of a line with `\\n`")),
+        Elementary::Set(s) => match s.as_ref() {
+            Set::Positive(x) => {
+                let Positive::O(items) = x.as_ref();
+                parse_set(set_text(items.clone())).map(|_| ())
+            }
+            Set::Negative(x) => {
+                let Negative::O(items) = x.as_ref();
+                parse_set(set_text(items.clone())).map(|_| ())
+            }
+            Set::QuerySet(x) => {
+                let QuerySet::O(items) = x.as_ref();
+                parse_set(get_string(items.clone())).map(|_| ())
+            }
+        },
+        Elementary::Any(_) | Elementary::Char(_) => Ok(()),
+    }
+}
+
+/// Builds an NFA from a regex, which `check` must accept
 pub fn build_nfa(r: Box<Regex>) -> (Nfa, NodePointer, NodePointer) {
     let mut nfa = Nfa::new(Vec::new());
-    let (s, d) = do_regex(r, &mut nfa);
-    (nfa, s, d)
+    let f = do_regex(r, &mut nfa);
+    (nfa, f.start(), f.end())
 }
 
-fn do_regex(r: Box<Regex>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_regex(r: Box<Regex>, nfa: &mut Nfa) -> Fragment {
     match *r {
         Regex::Union(r) => do_union(r, nfa),
         Regex::Simple(r) => do_simple(r, nfa),
     }
 }
 
-fn do_union(r: Box<Union>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_union(r: Box<Union>, nfa: &mut Nfa) -> Fragment {
     let Union::O(x, y) = *r;
     let a = do_regex(x, nfa);
     let b = do_simple(y, nfa);
-    let s = nfa.new_node();
-    let d = nfa.new_node();
-    nfa.add_transition_epsilon(&s, &a.0).unwrap();
-    nfa.add_transition_epsilon(&s, &b.0).unwrap();
-    nfa.add_transition_epsilon(&a.1, &d).unwrap();
-    nfa.add_transition_epsilon(&b.1, &d).unwrap();
-    (s, d)
+    nfa.alternate(a, b).unwrap()
 }
 
-fn do_simple(r: Box<Simple>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_simple(r: Box<Simple>, nfa: &mut Nfa) -> Fragment {
     match *r {
         Simple::Concatenation(r) => do_concat(r, nfa),
         Simple::Basic(r) => do_basic(r, nfa),
     }
 }
 
-fn do_basic(r: Box<Basic>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_basic(r: Box<Basic>, nfa: &mut Nfa) -> Fragment {
     match *r {
         Basic::Star(r) => do_star(r, nfa),
         Basic::Plus(r) => do_plus(r, nfa),
@@ -49,86 +115,84 @@ fn do_basic(r: Box<Basic>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
     }
 }
 
-fn do_concat(r: Box<Concatenation>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_concat(r: Box<Concatenation>, nfa: &mut Nfa) -> Fragment {
     let Concatenation::O(x, y) = *r;
-    let (ls, ld) = do_simple(x, nfa);
-    let (rs, rd) = do_basic(y, nfa);
-    nfa.add_transition_epsilon(&ld, &rs).unwrap();
-    (ls, rd)
+    let left = do_simple(x, nfa);
+    let right = do_basic(y, nfa);
+    nfa.concat(left, right).unwrap()
 }
 
-fn do_elem(r: Box<Elementary>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_elem(r: Box<Elementary>, nfa: &mut Nfa) -> Fragment {
     match *r {
         Elementary::Group(r) => do_group(r, nfa),
-        Elementary::Any(_) => unimplemented!(),
-        Elementary::Eos(_) => unimplemented!(),
-        Elementary::Char(r) => do_char(r, nfa),
+        Elementary::Any(r) => nfa.any(matches!(*r, Any::Cluster)),
+        Elementary::Eos(_) => unreachable!("`check` refuses `$`"),
+        Elementary::Char(r) => nfa.char(get_char(r)),
         Elementary::Set(r) => do_set(r, nfa),
     }
 }
 
-fn do_star(r: Box<Star>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_star(r: Box<Star>, nfa: &mut Nfa) -> Fragment {
     let Star::O(r) = *r;
-    let (src, dst) = do_elem(r, nfa);
-    nfa.add_transition_epsilon(&dst, &src).unwrap();
-    nfa.add_transition_epsilon(&src, &dst).unwrap();
-
-    (src, dst)
+    let f = do_elem(r, nfa);
+    nfa.repeat(f, Repeat::ZeroOrMore).unwrap()
 }
 
-fn do_plus(r: Box<Plus>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_plus(r: Box<Plus>, nfa: &mut Nfa) -> Fragment {
     let Plus::O(r) = *r;
-    let (src, dst) = do_elem(r, nfa);
-    nfa.add_transition_epsilon(&dst, &src).unwrap();
-    (src, dst)
-}
-
-fn do_char(r: Box<Char>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
-    let src = nfa.new_node();
-    let dst = nfa.new_node();
-    let c = match *r {
-        Char::Char(c) => c,
-        Char::Meta(c) => c,
-    };
-    nfa.add_transition_alpha(&src, &dst, c).unwrap();
-    (src, dst)
+    let f = do_elem(r, nfa);
+    nfa.repeat(f, Repeat::OneOrMore).unwrap()
 }
 
-fn do_set(r: Box<Set>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_set(r: Box<Set>, nfa: &mut Nfa) -> Fragment {
     match *r {
-        Set::Positive(r) => do_positive(r, nfa),
-        Set::Negative(r) => do_negative(r, nfa),
+        Set::Positive(r) => {
+            let Positive::O(r) = *r;
+            nfa.class(&set_chars(r), false)
+        }
+        Set::Negative(r) => {
+            let Negative::O(r) = *r;
+            nfa.class(&set_chars(r), true)
+        }
         Set::QuerySet(r) => do_queryset(r, nfa),
     }
 }
 
-fn do_queryset(r: Box<QuerySet>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_queryset(r: Box<QuerySet>, nfa: &mut Nfa) -> Fragment {
     let QuerySet::O(r) = *r;
-    let r = parse_set(get_string(r));
-    let src = nfa.new_node();
-    let dst = nfa.new_node();
-    nfa.add_transition_queryset(&src, &dst, get_string(r))
-        .unwrap();
-    (src, dst)
+    let r = parse_set(get_string(r)).expect("`check` parses every set");
+    nfa.query_set(&get_string(r))
 }
 
-fn do_negative(r: Box<Negative>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
-    let Negative::O(r) = *r;
-    let r = parse_set(get_string(r));
-    let src = nfa.new_node();
-    let dst = nfa.new_node();
-    nfa.add_transition_negativerange(&src, &dst, get_string(r))
-        .unwrap();
-    (src, dst)
+/// The characters a positive or negative set holds, with its ranges
+/// spelled out
+pub fn set_chars(r: Box<Items>) -> String {
+    get_string(parse_set(set_text(r)).expect("`check` parses every set"))
 }
 
-fn do_positive(r: Box<Positive>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
-    let Positive::O(r) = *r;
-    let r = parse_set(get_string(r));
-    let src = nfa.new_node();
-    let dst = nfa.new_node();
-    nfa.add_transition_range(&src, &dst, get_string(r)).unwrap();
-    (src, dst)
+/// The text of a set as written, with its escaped characters escaped again,
+/// so that `[a\-z]` is not parsed again as the range `a-z`
+fn set_text(r: Box<Items>) -> String {
+    let write = |s: &mut String, c: Box<Char>| match *c {
+        Char::Char(c) => s.push(c),
+        Char::Meta(c) => {
+            s.push('\\');
+            s.push(c);
+        }
+    };
+    let mut s = String::new();
+    for item in get_items(r) {
+        match *item {
+            Item::Range(r) => {
+                let Range::O(a, b) = *r;
+                write(&mut s, a);
+                s.push('-');
+                write(&mut s, b);
+            }
+            Item::Char(c) => write(&mut s, c),
+        }
+    }
+    s
 }
 
 fn get_string(r: Box<Items>) -> String {
@@ -173,13 +237,10 @@ fn get_items(r: Box<Items>) -> Vec<Box<Item>> {
     }
 }
 
-fn do_group(r: Box<Group>, nfa: &mut Nfa) -> (NodePointer, NodePointer) {
+fn do_group(r: Box<Group>, nfa: &mut Nfa) -> Fragment {
     let Group::O(r) = *r;
-    let src = nfa.new_node();
-    let dst = nfa.new_node();
-    let x = do_regex(r, nfa);
-    nfa.add_group(&src, &x.0, &x.1, &dst).unwrap();
-    (src, dst)
+    let f = do_regex(r, nfa);
+    nfa.group(f).unwrap().0
 }
 
 #[test]
@@ -191,25 +252,25 @@ fn test_regex() -> Result<(), Box<dyn std::error::Error>> {
     let mut ctx = Context::new(HashSet::new());
     ctx.add_epsilons(vec![start].into_iter().collect(), &nfa);
     for c in "bob".chars() {
-        ctx.step(&nfa, c, &crate::nfa::queryengine::QueryEngine::new());
+        ctx.step(&nfa, &c.to_string(), &crate::nfa::queryengine::QueryEngine::new());
     }
     assert!(ctx.contains(&end));
     let mut ctx = Context::new(HashSet::new());
     ctx.add_epsilons(vec![start].into_iter().collect(), &nfa);
     for c in "bobd".chars() {
-        ctx.step(&nfa, c, &crate::nfa::queryengine::QueryEngine::new());
+        ctx.step(&nfa, &c.to_string(), &crate::nfa::queryengine::QueryEngine::new());
     }
     assert!(!ctx.contains(&end));
     let mut ctx = Context::new(HashSet::new());
     ctx.add_epsilons(vec![start].into_iter().collect(), &nfa);
     for c in "bo".chars() {
-        ctx.step(&nfa, c, &crate::nfa::queryengine::QueryEngine::new());
+        ctx.step(&nfa, &c.to_string(), &crate::nfa::queryengine::QueryEngine::new());
     }
     assert!(!ctx.contains(&end));
     let mut ctx = Context::new(HashSet::new());
     ctx.add_epsilons(vec![start].into_iter().collect(), &nfa);
     for c in "eeeeeeeeee".chars() {
-        ctx.step(&nfa, c, &crate::nfa::queryengine::QueryEngine::new());
+        ctx.step(&nfa, &c.to_string(), &crate::nfa::queryengine::QueryEngine::new());
     }
     assert!(ctx.contains(&end));
     Ok(())
@@ -20,28 +20,33 @@
 //!<query-items>        ::=         <query> | <query> <query-items>
 //!<range> 	::= 	<char> "-" <char>
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Union {
     O(Box<Regex>, Box<Simple>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Regex {
     Union(Box<Union>),
     Simple(Box<Simple>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Simple {
     Concatenation(Box<Concatenation>),
     Basic(Box<Basic>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Concatenation {
     O(Box<Simple>, Box<Basic>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Basic {
     Star(Box<Star>),
     Plus(Box<Plus>),
@@ -49,16 +54,19 @@ pub enum Basic {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Star {
     O(Box<Elementary>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Plus {
     O(Box<Elementary>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Elementary {
     Group(Box<Group>),
     Any(Box<Any>),
@@ -68,27 +76,34 @@ pub enum Elementary {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Group {
     O(Box<Regex>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Any {
-    O
+    O,
+    /// A `.` under the `c` flag, which matches a whole grapheme cluster
+    Cluster,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Eos {
     O
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Char {
     Char(char),
     Meta(char),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Set {
     Positive(Box<Positive>),
     Negative(Box<Negative>),
@@ -96,78 +111,173 @@ pub enum Set {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Positive {
     O(Box<Items>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Negative {
     O(Box<Items>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum QuerySet {
     O(Box<Items>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Items {
     Item(Box<Item>),
     Items(Box<Item>, Box<Items>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Item {
     Range(Box<Range>),
     Char(Box<Char>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Range {
     O(Box<Char>, Box<Char>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Queries {
     Query(Box<Query>),
     Queries(Box<Query>, Box<Queries>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Query {
     Kv(String, String),
     Fun
 }
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Location {
     Path(String),
     All
 }
 
+/// What a query does at each line or match it finds, given by the letter
+/// that ends its location
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Command {
+    /// `s`, replacing each match with the replacement
+    Substitute,
+    /// `d`, deleting every line a match is on
+    Delete,
+    /// `i`, inserting the replacement as a line before every line a match
+    /// is on
+    Before,
+    /// `a`, inserting the replacement as a line after every line a match
+    /// is on
+    After,
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ReplaceItem {
     String(String),
     BackRef(usize),
+    /// A group passed through a function, like `${1:upper}`, with the
+    /// group's index and the function's name
+    Function(usize, String),
+    /// A group read as a whole number, with arithmetic done on it, like
+    /// `${1+1000}`, with the group's index, the operator, and the number on
+    /// its right
+    Arithmetic(usize, Operator, i64),
+    /// A replacement chosen by whether a group matched any text, like
+    /// `${1:+prefix_${1}}` or `${1:-default}`, with the group's index, the
+    /// replacement used if it did, and the one used if it did not
+    Conditional(usize, Box<Replacement>, Box<Replacement>),
+    /// An argument of the call a `[[call=...]]` set matched, like `${a1}`,
+    /// with the argument's index, counting from one
+    Argument(usize),
+}
+
+/// An operator of arithmetic on a group in a replacement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Operator {
+    /// The operator written as a character, like `+`
+    pub fn symbol(self) -> char {
+        match self {
+            Operator::Add => '+',
+            Operator::Sub => '-',
+            Operator::Mul => '*',
+            Operator::Div => '/',
+        }
+    }
+
+    /// Applies the operator, or None if the result would overflow, or would
+    /// divide by zero
+    pub fn apply(self, left: i64, right: i64) -> Option<i64> {
+        match self {
+            Operator::Add => left.checked_add(right),
+            Operator::Sub => left.checked_sub(right),
+            Operator::Mul => left.checked_mul(right),
+            Operator::Div => left.checked_div(right),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Replace {
     pub find: Box<Regex>,
     pub replace: Box<Replacement>,
+    /// The `g` flag, which is kept as given, though every match is replaced
+    /// whether or not it is
     pub global: bool,
-    pub location: Box<Location>
+    pub location: Box<Location>,
+    /// The `n` flag, counting the matches that would be replaced rather
+    /// than replacing them
+    pub count: bool,
+    /// What the query does with its matches
+    pub command: Command,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReplaceUnparsed {
     pub find: String,
     pub replace: String,
     pub location: String,
     pub global: bool,
+    /// The `i` flag, matching letters whatever their case, by Unicode's
+    /// simple case folding
+    pub ignore_case: bool,
+    /// The `c` flag, matching `.` against a whole grapheme cluster
+    pub clusters: bool,
+    /// The `n` flag, counting the matches that would be replaced rather
+    /// than replacing them
+    pub count: bool,
+    /// What the query does with its matches, where `Command::Delete` has
+    /// no replacement
+    pub command: Command,
 }
 
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Replacement {
     pub replacements: Vec<ReplaceItem>
 }
@@ -0,0 +1,240 @@
+//! Rewrites a parsed regex for the `i` and `c` flags, so that the NFA built
+//! from it needs no notion of case, and spells out its `[[call=...]]` sets
+//!
+//! Under `i`, each letter becomes a set of its cases, and each set gains the
+//! other cases of the letters in it. Letters are folded by Unicode's simple
+//! case folding, so `é` and `É` match each other, and `σ`, `ς`, and `Σ` all
+//! do. Foldings that turn one character into several, like `ß` into `ss`,
+//! are left out, as the NFA steps a character at a time, so `straße` does
+//! not match `STRASSE`.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use super::ast::*;
+use super::reg;
+use crate::regex2nfa::set_chars;
+
+/// Makes the letters of a regex match whatever their case
+///
+/// # Arguments
+///
+/// * `r` - The regex
+pub fn fold_case(r: Regex) -> Box<Regex> {
+    map_regex(r, &|e| match e {
+        Elementary::Char(c) => {
+            let cases = cases(get_char(&c));
+            if cases.len() == 1 {
+                Elementary::Char(c)
+            } else {
+                let items = to_items(cases.into_iter().map(|x| Item::Char(Box::new(Char::Char(x)))).collect());
+                Elementary::Set(Box::new(Set::Positive(Box::new(Positive::O(items)))))
+            }
+        }
+        Elementary::Set(set) => Elementary::Set(Box::new(match *set {
+            Set::Positive(x) => {
+                let Positive::O(items) = *x;
+                Set::Positive(Box::new(Positive::O(fold_items(items))))
+            }
+            Set::Negative(x) => {
+                let Negative::O(items) = *x;
+                Set::Negative(Box::new(Negative::O(fold_items(items))))
+            }
+            x => x,
+        })),
+        e => e,
+    })
+}
+
+/// Makes each `.` of a regex match a whole grapheme cluster
+pub fn clusters(r: Regex) -> Box<Regex> {
+    map_regex(r, &|e| match e {
+        Elementary::Any(_) => Elementary::Any(Box::new(Any::Cluster)),
+        e => e,
+    })
+}
+
+/// Spells out each `[[call=NAME]]` as a group of the call's name, its `(`,
+/// a group of its arguments, and its `)`, which is
+/// `([[callee=NAME]]\(([[args=NAME]]*)\))`, so the arguments are counted
+/// as a group before the whole call. Other criteria, like `argc=2`, are
+/// kept in both query sets.
+pub fn calls(r: Regex) -> Box<Regex> {
+    map_regex(r, &|e| match e {
+        Elementary::Set(set) => match *set {
+            Set::QuerySet(q) => call(&q).unwrap_or_else(|| Elementary::Set(Box::new(Set::QuerySet(q)))),
+            x => Elementary::Set(Box::new(x)),
+        },
+        e => e,
+    })
+}
+
+/// The group a `[[call=NAME]]` set stands for, or None if it is another
+/// query set
+fn call(q: &QuerySet) -> Option<Elementary> {
+    let QuerySet::O(items) = q;
+    let text = set_chars(items.clone());
+    let pairs: Vec<&str> = text.split(',').collect();
+    if text.contains(']') || !pairs.iter().any(|x| x.starts_with("call=")) {
+        return None;
+    }
+    let part = |key: &str| pairs.iter().map(|x| x.strip_prefix("call=").map_or(x.to_string(), |name| format!("{}={}", key, name))).collect::<Vec<_>>().join(",");
+    let regex = reg::RegexParser::new().parse(&format!("[[{}]]\\(([[{}]]*)\\)", part("callee"), part("args"))).ok()?;
+    Some(Elementary::Group(Box::new(Group::O(regex))))
+}
+
+/// Rebuilds a regex with `f` applied to each elementary regex, including
+/// those within groups
+fn map_regex(r: Regex, f: &dyn Fn(Elementary) -> Elementary) -> Box<Regex> {
+    Box::new(match r {
+        Regex::Union(u) => {
+            let Union::O(left, right) = *u;
+            Regex::Union(Box::new(Union::O(map_regex(*left, f), map_simple(*right, f))))
+        }
+        Regex::Simple(s) => Regex::Simple(map_simple(*s, f)),
+    })
+}
+
+fn map_simple(s: Simple, f: &dyn Fn(Elementary) -> Elementary) -> Box<Simple> {
+    Box::new(match s {
+        Simple::Concatenation(c) => {
+            let Concatenation::O(left, right) = *c;
+            Simple::Concatenation(Box::new(Concatenation::O(map_simple(*left, f), map_basic(*right, f))))
+        }
+        Simple::Basic(b) => Simple::Basic(map_basic(*b, f)),
+    })
+}
+
+fn map_basic(b: Basic, f: &dyn Fn(Elementary) -> Elementary) -> Box<Basic> {
+    Box::new(match b {
+        Basic::Star(x) => {
+            let Star::O(e) = *x;
+            Basic::Star(Box::new(Star::O(map_elem(*e, f))))
+        }
+        Basic::Plus(x) => {
+            let Plus::O(e) = *x;
+            Basic::Plus(Box::new(Plus::O(map_elem(*e, f))))
+        }
+        Basic::Elementary(e) => Basic::Elementary(map_elem(*e, f)),
+    })
+}
+
+fn map_elem(e: Elementary, f: &dyn Fn(Elementary) -> Elementary) -> Box<Elementary> {
+    Box::new(match e {
+        Elementary::Group(g) => {
+            let Group::O(r) = *g;
+            Elementary::Group(Box::new(Group::O(map_regex(*r, f))))
+        }
+        e => f(e),
+    })
+}
+
+/// Adds the other cases of the characters of a set, before them
+fn fold_items(items: Box<Items>) -> Box<Items> {
+    let mut list = Vec::new();
+    let mut r = items;
+    loop {
+        match *r {
+            Items::Item(i) => {
+                list.push(*i);
+                break;
+            }
+            Items::Items(i, rest) => {
+                list.push(*i);
+                r = rest;
+            }
+        }
+    }
+    let mut present = Vec::new();
+    for item in &list {
+        match item {
+            Item::Char(c) => present.push(get_char(c)),
+            Item::Range(x) => {
+                let Range::O(a, b) = x.as_ref();
+                present.extend(get_char(a)..=get_char(b));
+            }
+        }
+    }
+    let mut added = Vec::new();
+    for c in &present {
+        for x in cases(*c) {
+            if !present.contains(&x) && !added.contains(&x) {
+                added.push(x);
+            }
+        }
+    }
+    // The set's characters are parsed again from their text when its NFA is
+    // built, so the cases go first, where they cannot join a range
+    let folded = added.into_iter().map(|x| Item::Char(Box::new(Char::Char(x)))).chain(list).collect();
+    to_items(folded)
+}
+
+fn to_items(items: Vec<Item>) -> Box<Items> {
+    let mut items = items.into_iter().rev();
+    let last = items.next().expect("A set has at least one item");
+    items.fold(Box::new(Items::Item(Box::new(last))), |rest, x| Box::new(Items::Items(Box::new(x), rest)))
+}
+
+fn get_char(c: &Char) -> char {
+    match c {
+        Char::Char(c) | Char::Meta(c) => *c,
+    }
+}
+
+/// Every case of a character, including itself, in order of code point
+fn cases(c: char) -> Vec<char> {
+    classes().get(&c).cloned().unwrap_or_else(|| vec![c])
+}
+
+/// Maps each character that has other cases to every character in its
+/// class, built the first time it is needed
+fn classes() -> &'static HashMap<char, Vec<char>> {
+    static CLASSES: OnceLock<HashMap<char, Vec<char>>> = OnceLock::new();
+    CLASSES.get_or_init(|| {
+        let mut by_key: HashMap<char, Vec<char>> = HashMap::new();
+        // Only alphabetic characters, which include symbols like Ⓐ and Ⅰ,
+        // have cases, and every one that does is in the first two planes
+        for c in (0..=0x1FFFF).filter_map(char::from_u32).filter(|x| x.is_alphabetic()) {
+            if let Some(key) = key(c) {
+                by_key.entry(key).or_default().push(c);
+            }
+        }
+        let mut classes = HashMap::new();
+        for class in by_key.into_values().filter(|x| x.len() > 1) {
+            for c in &class {
+                classes.insert(*c, class.clone());
+            }
+        }
+        classes
+    })
+}
+
+/// The character a character folds to, as its uppercase and then that
+/// uppercase's lowercase, where each is a single character
+fn key(c: char) -> Option<char> {
+    // The Turkish dotless i has I as its uppercase, but folds to nothing
+    if c == 'ı' {
+        return None;
+    }
+    let upper = single(c.to_uppercase()).unwrap_or(c);
+    let lower = single(upper.to_lowercase()).unwrap_or(upper);
+    Some(lower)
+}
+
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let first = chars.next();
+    first.filter(|_| chars.next().is_none())
+}
+
+#[test]
+fn test_fold() {
+    assert_eq!(cases('a'), vec!['A', 'a']);
+    assert_eq!(cases('é'), vec!['É', 'é']);
+    assert_eq!(cases('ς'), vec!['Σ', 'ς', 'σ']);
+    // The Kelvin sign and the long s fold to ASCII letters
+    assert_eq!(cases('k'), vec!['K', 'k', '\u{212A}']);
+    assert_eq!(cases('S'), vec!['S', 's', 'ſ']);
+    assert!(!cases('i').contains(&'ı'));
+    assert_eq!(cases('ß'), vec!['ß', 'ẞ']);
+    assert_eq!(cases('1'), vec!['1']);
+}
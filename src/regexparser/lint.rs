@@ -0,0 +1,253 @@
+//! Provides a lint pass over queries, which warns of patterns that parse
+//! but likely do not mean what they say
+//!
+//! Each warning is a sentence naming the part of the pattern it is about.
+//! Lints look at the pattern as written, before any flags rewrite it, and
+//! never change how a query is matched.
+
+use super::ast::{Basic, Char, Concatenation, Elementary, Group, Plus, QuerySet, Regex, Set, Simple, Star, Union};
+use super::{parsecommand, query, reg};
+use crate::error::SpidiorError;
+use crate::nfa::queryengine::Criteria;
+use crate::regex2nfa::set_chars;
+
+/// The keys a query set's criteria may have
+const PREDICATES: [&str; 13] = ["name", "type", "import", "scope", "pos", "tag", "attr", "text", "macro", "call", "callee", "args", "argc"];
+
+/// Characters that are operators in other regex dialects, but only match
+/// themselves here
+const LITERAL_META: [char; 4] = ['?', '{', '}', '^'];
+
+/// Lints a query, like `%s/[[name=count]]/total/g`
+///
+/// # Arguments
+///
+/// * `text` - The query
+///
+/// # Returns
+///
+/// A Result<Vec<String>, SpidiorError>, where on success, it returns a
+/// warning for each likely mistake, in the order they appear. A pattern
+/// with an empty alternative, like `a|`, which does not parse, is warned of
+/// rather than failing. It will Err with a ParseError if the query cannot
+/// be split into its parts.
+pub fn lint(text: &str) -> Result<Vec<String>, SpidiorError> {
+    Ok(lint_pattern(&parsecommand::parse(text)?.find))
+}
+
+/// Lints the find part of a query on its own, like `colou?r`, as `lint`
+/// does, after expanding its macros. A pattern whose macros cannot be
+/// expanded fails to parse, so is not linted.
+pub fn lint_pattern(find: &str) -> Vec<String> {
+    let Ok(find) = super::macros::expand(find) else {
+        return Vec::new();
+    };
+    let mut warnings = empty_branches(&find);
+    if let Ok(regex) = reg::RegexParser::new().parse(&find) {
+        lint_regex(&regex, &mut warnings);
+    }
+    warnings
+}
+
+/// Warns of an empty alternative in a pattern, which the grammar refuses,
+/// so is found in the text
+fn empty_branches(find: &str) -> Vec<String> {
+    // Whether an alternative has just started, at the start of the pattern
+    // or a group, or after a `|`, and whether it was after a `|`
+    let (mut opened, mut after_bar) = (true, false);
+    let mut empty = false;
+    let mut chars = find.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '|' => {
+                empty |= opened;
+                (opened, after_bar) = (true, true);
+                continue;
+            }
+            ')' => empty |= after_bar,
+            '\\' => {
+                chars.next();
+            }
+            // Sets hold no alternatives, though they may hold `|` and `(`
+            '[' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('\\') => {
+                            chars.next();
+                        }
+                        Some('[') => depth += 1,
+                        Some(']') => depth -= 1,
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+            }
+            _ => {}
+        }
+        (opened, after_bar) = (c == '(', false);
+    }
+    if empty || after_bar {
+        vec![format!("An alternative of `{}` is empty, which is not supported; to match nothing, make the other alternatives optional with `*` instead", find)]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Whether a pattern can match the empty string
+fn nullable(regex: &Regex) -> bool {
+    match regex {
+        Regex::Union(x) => {
+            let Union::O(left, right) = x.as_ref();
+            nullable(left) || nullable_simple(right)
+        }
+        Regex::Simple(x) => nullable_simple(x),
+    }
+}
+
+fn nullable_simple(simple: &Simple) -> bool {
+    flatten(simple).iter().all(|x| match x {
+        Basic::Star(_) => true,
+        Basic::Plus(x) => {
+            let Plus::O(e) = x.as_ref();
+            nullable_elementary(e)
+        }
+        Basic::Elementary(e) => nullable_elementary(e),
+    })
+}
+
+fn nullable_elementary(e: &Elementary) -> bool {
+    match e {
+        Elementary::Group(g) => {
+            let Group::O(r) = g.as_ref();
+            nullable(r)
+        }
+        Elementary::Eos(_) => true,
+        Elementary::Any(_) | Elementary::Char(_) | Elementary::Set(_) => false,
+    }
+}
+
+/// The parts of a concatenation, in order
+fn flatten(simple: &Simple) -> Vec<&Basic> {
+    match simple {
+        Simple::Concatenation(x) => {
+            let Concatenation::O(left, right) = x.as_ref();
+            let mut parts = flatten(left);
+            parts.push(right);
+            parts
+        }
+        Simple::Basic(x) => vec![x],
+    }
+}
+
+fn lint_regex(regex: &Regex, warnings: &mut Vec<String>) {
+    match regex {
+        Regex::Union(x) => {
+            let Union::O(left, right) = x.as_ref();
+            lint_regex(left, warnings);
+            lint_simple(right, warnings);
+        }
+        Regex::Simple(x) => lint_simple(x, warnings),
+    }
+}
+
+fn lint_simple(simple: &Simple, warnings: &mut Vec<String>) {
+    let parts = flatten(simple);
+    let word = |x: Option<&&Basic>| matches!(x, Some(Basic::Elementary(e)) if matches!(e.as_ref(), Elementary::Char(c) if matches!(c.as_ref(), Char::Char(c) if c.is_alphanumeric())));
+    for (i, part) in parts.iter().enumerate() {
+        let e = match part {
+            Basic::Star(x) => {
+                let Star::O(e) = x.as_ref();
+                lint_quantified(e, '*', warnings);
+                e
+            }
+            Basic::Plus(x) => {
+                let Plus::O(e) = x.as_ref();
+                lint_quantified(e, '+', warnings);
+                e
+            }
+            Basic::Elementary(e) => e,
+        };
+        match e.as_ref() {
+            Elementary::Any(_) if matches!(part, Basic::Elementary(_)) && i > 0 && word(parts.get(i - 1)) && word(parts.get(i + 1)) => {
+                warnings.push("`.` between letters, as in a file or package name, matches any character; escape it as `\\.` to match only a dot".to_string());
+            }
+            Elementary::Char(c) => {
+                if let Char::Char(c) = c.as_ref() {
+                    if LITERAL_META.contains(c) {
+                        warnings.push(format!("`{}` is not an operator here, so only matches itself; escape it as `\\{}` to say so", c, c));
+                    }
+                }
+            }
+            Elementary::Group(g) => {
+                let Group::O(r) = g.as_ref();
+                lint_regex(r, warnings);
+            }
+            Elementary::Set(s) => {
+                if let Set::QuerySet(q) = s.as_ref() {
+                    lint_query_set(q, warnings);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Warns of a `*` or `+` over a group that can match nothing, which loops
+/// without consuming anything
+fn lint_quantified(e: &Elementary, op: char, warnings: &mut Vec<String>) {
+    if let Elementary::Group(g) = e {
+        let Group::O(r) = g.as_ref();
+        if nullable(r) {
+            warnings.push(format!("A group that can match nothing is repeated with `{}`, which only makes matching slower; repeat what is inside it instead", op));
+        }
+    }
+}
+
+fn lint_query_set(q: &QuerySet, warnings: &mut Vec<String>) {
+    let QuerySet::O(items) = q;
+    let text = set_chars(items.clone());
+    if Criteria::parse(&text).is_err() {
+        warnings.push(format!("The query set `[[{}]]` is not a list of `key=value` criteria, so matches nothing", text));
+        return;
+    }
+    let Ok(mut queries) = query::QueriesParser::new().parse(&text) else {
+        return;
+    };
+    loop {
+        let (x, rest) = match *queries {
+            super::ast::Queries::Query(x) => (x, None),
+            super::ast::Queries::Queries(x, r) => (x, Some(r)),
+        };
+        if let super::ast::Query::Kv(k, _) = *x {
+            if !PREDICATES.contains(&k.as_str()) {
+                warnings.push(format!("`{}` in `[[{}]]` is not a query set predicate, so is ignored; expected one of {}", k, text, PREDICATES.join(", ")));
+            }
+        }
+        match rest {
+            Some(r) => queries = r,
+            None => return,
+        }
+    }
+}
+
+#[test]
+fn test_lint() -> Result<(), SpidiorError> {
+    assert!(lint("%s/[[name=count,type=int]]\\.size(a+|b)+/x/g")?.is_empty());
+    assert_eq!(lint("%s/colou?r/x/g")?, vec!["`?` is not an operator here, so only matches itself; escape it as `\\?` to say so"]);
+    assert_eq!(lint("%s/java.util/x/g")?.len(), 1);
+    assert!(lint("%s/a.*b/x/g")?.is_empty());
+    assert_eq!(lint("%s/(a*)*b/x/g")?, vec!["A group that can match nothing is repeated with `*`, which only makes matching slower; repeat what is inside it instead"]);
+    assert!(lint("%s/(ab)+/x/g")?.is_empty());
+    assert_eq!(lint("%s/[[nmae=count]]/x/g")?.len(), 1);
+    assert!(lint("%s/[[nmae=count]]/x/g")?[0].starts_with("`nmae` in `[[nmae=count]]` is not a query set predicate"));
+    assert_eq!(lint("%s/[[count]]/x/g")?, vec!["The query set `[[count]]` is not a list of `key=value` criteria, so matches nothing"]);
+    for query in ["%s/a|/x/g", "%s/|a/x/g", "%s/(a||b)/x/g", "%s/(|a)/x/g"] {
+        assert_eq!(lint(query)?.len(), 1, "{}", query);
+    }
+    assert!(lint("%s/a\\|/x/g")?.is_empty());
+    assert!(lint("%s/[|]a/x/g")?.is_empty());
+    assert!(lint("s/a").is_err());
+    assert_eq!(lint_pattern("java.util/List").len(), 1);
+    Ok(())
+}
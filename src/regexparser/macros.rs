@@ -0,0 +1,139 @@
+//! Provides query macros, which are named pattern fragments that the find
+//! part of a query refers to as `\m{name}`, defined in the `[macros]`
+//! section of the config file, like
+//!
+//! ```toml
+//! [macros]
+//! ident = "[A-Za-z_][A-Za-z0-9_]*"
+//! call = "\\m{ident}\\("
+//! ```
+//!
+//! A macro is expanded where it is referred to before the pattern is
+//! parsed, and may refer to other macros. A macro that is more than one
+//! character, set, or group is put in a group of its own, so that `\m{x}*`
+//! repeats the whole of `x`, and that group is counted by backreferences.
+//! Macros are not expanded within sets, like `[\m{x}]`.
+
+use std::sync::RwLock;
+
+use super::ast::{Basic, Regex, Simple};
+use super::reg;
+use crate::error::SpidiorError;
+
+/// The macros defined, with their names, which last for the life of the
+/// process
+static ADDED: RwLock<Vec<(String, String)>> = RwLock::new(Vec::new());
+
+/// Defines a macro, replacing any defined before with its name
+///
+/// # Arguments
+///
+/// * `name` - The name of the macro, which is made of letters, digits, `_`,
+///   and `-`
+/// * `pattern` - The pattern the macro stands for, which may refer to other
+///   macros
+pub fn define(name: &str, pattern: &str) {
+    if let Ok(mut added) = ADDED.write() {
+        added.retain(|(x, _)| x != name);
+        added.push((name.to_string(), pattern.to_string()));
+    }
+}
+
+/// Finds the pattern a macro stands for, before any macros it refers to are
+/// expanded
+pub fn get(name: &str) -> Option<String> {
+    ADDED.read().ok()?.iter().find(|(x, _)| x == name).map(|(_, x)| x.clone())
+}
+
+/// Expands every macro a pattern refers to
+///
+/// # Arguments
+///
+/// * `text` - The find part of a query, like `\m{ident}\.size`
+///
+/// # Returns
+///
+/// A Result<String, SpidiorError>, where on success, it returns the pattern
+/// with every macro expanded. It will Err with a ParseError if a macro is
+/// not defined, is not closed with a `}`, is not a valid pattern, or refers
+/// to itself.
+pub fn expand(text: &str) -> Result<String, SpidiorError> {
+    expand_within(text, &mut Vec::new())
+}
+
+/// Expands every macro a pattern refers to, given the macros being expanded
+/// already, which it may not refer to
+fn expand_within(text: &str, stack: &mut Vec<String>) -> Result<String, SpidiorError> {
+    let mut out = String::new();
+    let mut chars = text.char_indices().peekable();
+    // How many sets the pattern is within, as a query set is a set within a
+    // set
+    let mut depth = 0;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if depth == 0 && text[i + 1..].starts_with("m{") => {
+                let start = i + 3;
+                let end = text[start..].find('}').map(|x| start + x).ok_or_else(|| SpidiorError::parse(format!("`{}` is not closed with a `}}`", &text[i..])))?;
+                out += &expand_macro(&text[start..end], stack)?;
+                while chars.peek().is_some_and(|(x, _)| *x <= end) {
+                    chars.next();
+                }
+            }
+            '\\' => {
+                out.push(c);
+                if let Some((_, x)) = chars.next() {
+                    out.push(x);
+                }
+            }
+            '[' => {
+                depth += 1;
+                out.push(c);
+            }
+            ']' if depth > 0 => {
+                depth -= 1;
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Expands one macro, given its name, putting it in a group unless it is a
+/// single character, set, or group
+fn expand_macro(name: &str, stack: &mut Vec<String>) -> Result<String, SpidiorError> {
+    if stack.iter().any(|x| x == name) {
+        stack.push(name.to_string());
+        return Err(SpidiorError::parse(format!("The macro `{}` refers to itself, through {}", name, stack.join(" → "))));
+    }
+    let pattern = get(name).ok_or_else(|| SpidiorError::parse(format!("Unknown macro `{}`; define it in the `[macros]` section of the config file", name)))?;
+    stack.push(name.to_string());
+    let expanded = expand_within(&pattern, stack)?;
+    stack.pop();
+    let regex = reg::RegexParser::new().parse(&expanded).map_err(|_| SpidiorError::parse(format!("The macro `{}` is `{}`, which is not a valid pattern", name, pattern)))?;
+    match *regex {
+        Regex::Simple(x) if matches!(*x, Simple::Basic(ref b) if matches!(**b, Basic::Elementary(_))) => Ok(expanded),
+        _ => Ok(format!("({})", expanded)),
+    }
+}
+
+#[test]
+fn test_macros() -> Result<(), SpidiorError> {
+    define("test-ident", "[A-Za-z_][A-Za-z0-9_]*");
+    define("test-call", "\\m{test-ident}\\(");
+    define("test-digit", "[0-9]");
+    define("test-loop", "a\\m{test-loop-2}");
+    define("test-loop-2", "\\m{test-loop}");
+    define("test-bad", "(a");
+    assert_eq!(get("test-digit").as_deref(), Some("[0-9]"));
+    assert_eq!(expand("\\m{test-ident}\\.size")?, "([A-Za-z_][A-Za-z0-9_]*)\\.size");
+    assert_eq!(expand("\\m{test-call}")?, "(([A-Za-z_][A-Za-z0-9_]*)\\()");
+    assert_eq!(expand("\\m{test-digit}+ \\\\m{x} [\\m{x}]")?, "[0-9]+ \\\\m{x} [\\m{x}]");
+    assert!(expand("\\m{nope}").unwrap_err().to_string().starts_with("Unknown macro `nope`"));
+    assert_eq!(expand("\\m{test-loop}").unwrap_err().to_string(), "The macro `test-loop` refers to itself, through test-loop → test-loop-2 → test-loop");
+    assert!(expand("\\m{test-bad}").is_err());
+    assert!(expand("\\m{test-digit").is_err());
+    define("test-digit", "[0-7]");
+    assert_eq!(expand("\\m{test-digit}")?, "[0-7]");
+    Ok(())
+}
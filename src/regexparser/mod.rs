@@ -1,9 +1,13 @@
 use self::ast::{Items, Replace};
-use std::error::Error;
+use crate::error::SpidiorError;
 
 pub mod ast;
+mod fold;
+pub mod lint;
+pub mod macros;
 mod parsecommand;
 mod parsereplacement;
+mod sets;
 
 lalrpop_mod!(reg, "/regexparser/reg.rs");
 lalrpop_mod!(set, "/regexparser/set.rs");
@@ -26,28 +30,98 @@ lalrpop_mod!(location, "/regexparser/location.rs");
 ///
 /// # Returns
 ///
-/// A Result<Replace, Box<dyn Error>>, where on success, it returns a
+/// A Result<Replace, SpidiorError>, where on success, it returns a
 /// Replace containing the LOCATION, REGEX, REPLACEMENT, and
-/// whether it is global or not (ends with a g)
-pub fn parse(text: &str) -> Result<ast::Replace, Box<dyn Error>> {
+/// whether it is global or not (ends with a g), where the REGEX has had its
+/// macros expanded and its `[[call=...]]` sets spelled out, and been
+/// rewritten for the `i` and `c` flags
+pub fn parse(text: &str) -> Result<ast::Replace, SpidiorError> {
     let ru = parsecommand::parse(text)?;
     let location = location::LocationParser::new()
         .parse(&ru.location)
-        .map_err(|_| "Failed to parse location")?;
+        .map_err(|_| SpidiorError::parse("Failed to parse location"))?;
     let find = reg::RegexParser::new()
-        .parse(&ru.find)
-        .map_err(|_| "Failed to parse regex")?;
+        .parse(&expand(&ru.find)?)
+        .map_err(|_| SpidiorError::parse("Failed to parse regex"))?;
+    let mut find = fold::calls(*find);
+    if ru.ignore_case {
+        find = fold::fold_case(*find);
+    }
+    if ru.clusters {
+        find = fold::clusters(*find);
+    }
+    crate::regex2nfa::check(&find)?;
     let replace = parsereplacement::parse(&ru.replace)?;
     Ok(Replace {
         location,
         find,
         replace: Box::new(replace),
         global: ru.global,
+        count: ru.count,
+        command: ru.command,
     })
 }
 
-pub fn parse_set(s: String) -> Box<Items> {
-    set::ItemsParser::new().parse(&s).unwrap()
+/// Parses the find part of a query on its own, like `[[name=count]]`, as
+/// `-e` gives it, without any flags, expanding its macros and spelling
+/// out its `[[call=...]]` sets
+///
+/// # Returns
+///
+/// A Result<Box<Regex>, SpidiorError>, which will Err with a ParseError if
+/// `text` is not a valid pattern, or a CompileError if it cannot be matched
+pub fn parse_pattern(text: &str) -> Result<Box<ast::Regex>, SpidiorError> {
+    let find = reg::RegexParser::new()
+        .parse(&expand(text)?)
+        .map(|x| fold::calls(*x))
+        .map_err(|_| SpidiorError::parse(format!("Failed to parse pattern `{}`", text)))?;
+    crate::regex2nfa::check(&find)?;
+    Ok(find)
+}
+
+/// Parses a pattern, as `parse_pattern` does, and a replacement, like the
+/// replacement part of a query, into a query that replaces every match,
+/// without splicing them into `%s/.../.../g`, where a `/` in either would
+/// end its part early
+///
+/// # Arguments
+///
+/// * `find` - The pattern, like `[[type=Session]]`
+/// * `replace` - The replacement, like `sess`, in which `/` is itself
+///
+/// # Returns
+///
+/// A Result<Replace, SpidiorError>, which will Err with a ParseError if
+/// either part is not valid
+pub fn parse_substitution(find: &str, replace: &str) -> Result<ast::Replace, SpidiorError> {
+    Ok(Replace {
+        location: Box::new(ast::Location::All),
+        find: parse_pattern(find)?,
+        replace: Box::new(parsereplacement::parse(replace)?),
+        global: true,
+        count: false,
+        command: ast::Command::Substitute,
+    })
+}
+
+/// Expands the macros of the find part of a query, after checking its
+/// sets, and those the macros bring in
+fn expand(find: &str) -> Result<String, SpidiorError> {
+    sets::check(find)?;
+    let expanded = macros::expand(find)?;
+    sets::check(&expanded)?;
+    Ok(expanded)
+}
+
+/// Parses the text within a set's brackets into its items, spelling out
+/// its ranges
+///
+/// # Returns
+///
+/// A Result<Box<Items>, SpidiorError>, which will Err with a ParseError if
+/// `s` is not a valid set
+pub fn parse_set(s: String) -> Result<Box<Items>, SpidiorError> {
+    set::ItemsParser::new().parse(&s).map_err(|_| SpidiorError::parse(format!("Failed to parse set `[{}]`", s)))
 }
 
 #[test]
@@ -98,4 +172,29 @@ fn parsing_entire() {
     assert!(parse("%s/westoff/Westhoff").is_err());
     assert!(parse("%s/westoff/Westhoff/").is_ok());
     assert!(parse("mod.rs:s/jon/John/g").is_ok());
+    assert!(parse("%s/[a\\-z]//g").is_ok());
+    assert!(parse("%s/x[z-a]//g").unwrap_err().to_string().contains("Reversed range `z-a` at column 3"));
+    assert!(parse_pattern("[a-]").unwrap_err().to_string().contains("Dangling `-` at column 3"));
+}
+
+#[test]
+fn parsing_substitution() {
+    let x = parse_substitution("a/b", "c/d").unwrap();
+    assert!(x.global && matches!(*x.location, ast::Location::All));
+    assert_eq!(crate::replacer::replace("a/b a/b", x, |_, _| true).unwrap(), "c/d c/d");
+    assert!(parse_substitution("[[import=github.com/x/y]]", "").is_ok());
+    assert!(parse_substitution("[z-a]", "").is_err());
+}
+
+#[test]
+fn parsing_uncompilable() {
+    // These parse as regexes, but could not be built into an NFA
+    assert!(matches!(parse("%s/a$//g"), Err(SpidiorError::CompileError(_))));
+    assert!(matches!(parse_pattern("(b|$)"), Err(SpidiorError::CompileError(_))));
+    assert!(crate::Spidior::find_in("int a;", "%s/$//g", None).is_err());
+    // Every set the check of sets lets through is one the set parser takes
+    for set in ["[ -~]", "[ ]", "[a b]"] {
+        assert!(matches!(parse(&format!("%s/x{}//g", set)), Err(SpidiorError::ParseError(_))));
+    }
+    assert_eq!(crate::Spidior::replace_in("a b~", "%s/[\\ -~]/x/g", None).unwrap(), "xxxx");
 }
@@ -1,44 +1,58 @@
-use std::error::Error;
-
 use super::ast;
+use crate::error::SpidiorError;
 
 /// For parsing out statements of the form
-/// LOCATIONs/REGEX/REPLACEMENT/G
+/// LOCATIONs/REGEX/REPLACEMENT/FLAGS, or LOCATIONd/REGEX/FLAGS, which deletes
+/// every line a match is on, or LOCATIONi/REGEX/LINE/FLAGS and
+/// LOCATIONa/REGEX/LINE/FLAGS, which insert LINE before or after every line
+/// a match is on
+///
+/// FLAGS may hold each of `g`, `i`, `c`, and `n` at most once, in any
+/// order.
 /// # Arguments
 ///
 /// * `text` - A string slice that contains the command to be parsed
 ///
 /// # Returns
 ///
-/// A Result<ReplaceUnparsed, Box<dyn Error>>, where on success, it returns a
+/// A Result<ReplaceUnparsed, SpidiorError>, where on success, it returns a
 /// ReplaceUnparsed containing the LOCATION, REGEX, REPLACEMENT, and
-/// whether it is global or not (ends with a g)
-pub fn parse(text: &str) -> Result<ast::ReplaceUnparsed, Box<dyn Error>> {
+/// FLAGS
+pub fn parse(text: &str) -> Result<ast::ReplaceUnparsed, SpidiorError> {
     let (location, start) = parse_portion(text, 0)?;
-    if location.chars().last().ok_or("Location empty, expected at least an s")? != 's' {
-        return Err("s expected in location".into());
-    }
+    let command = match location.chars().last().ok_or_else(|| SpidiorError::parse("Location empty, expected at least an s, d, i, or a"))? {
+        's' => ast::Command::Substitute,
+        'd' => ast::Command::Delete,
+        'i' => ast::Command::Before,
+        'a' => ast::Command::After,
+        _ => return Err(SpidiorError::parse("s, d, i, or a expected in location")),
+    };
+    let delete = command == ast::Command::Delete;
     let (find, start) = parse_portion(text, start)?;
-    let (replace, start) = parse_portion(text, start)?;
-    let rest = &text[start..];
-    let global = if rest.len() == 0 {
-        Ok(false)
-    } else {
-        if rest.len() != 1 {
-            Err("Expected exactly one character after the replacement")
-        } else {
-            if rest.chars().next().ok_or("Really expected at least one last char")? == 'g' {
-                Ok(true)
-            } else {
-                Err("Expected a g")
-            }
+    let (replace, start) = if delete { (String::new(), start) } else { parse_portion(text, start)? };
+    let (mut global, mut ignore_case, mut clusters, mut count) = (false, false, false, false);
+    for c in text[start..].chars() {
+        let flag = match c {
+            'g' => &mut global,
+            'i' => &mut ignore_case,
+            'c' => &mut clusters,
+            'n' => &mut count,
+            _ => return Err(SpidiorError::parse(format!("Unknown flag `{}` after the {}, expected `g`, `i`, `c`, or `n`", c, if delete { "pattern" } else { "replacement" }))),
+        };
+        if *flag {
+            return Err(SpidiorError::parse(format!("The `{}` flag is given more than once", c)));
         }
-    }?;
+        *flag = true;
+    }
     Ok(ast::ReplaceUnparsed {
         location: location[..location.len() - 1].to_string(),
         find,
         replace,
         global,
+        ignore_case,
+        clusters,
+        count,
+        command,
     })
 }
 
@@ -46,27 +60,28 @@ pub fn parse(text: &str) -> Result<ast::ReplaceUnparsed, Box<dyn Error>> {
 /// # Arguments
 ///
 /// * `text` - A string slice that contains the command to be parsed
-/// * `start` - The index in the string to start from
+/// * `start` - The byte index in the string to start from
 ///
 /// # Returns
 ///
-/// A Result<(String, usize), Box<dyn Error>>, where on success, it returns a
+/// A Result<(String, usize), SpidiorError>, where on success, it returns a
 /// tuple containing the parsed string and the index of where to start
 /// for future parsing.
-fn parse_portion(text: &str, start: usize) -> Result<(String, usize), Box<dyn Error>> {
+fn parse_portion(text: &str, start: usize) -> Result<(String, usize), SpidiorError> {
     let mut escape = false;
-    for (i, c) in text.chars().enumerate().skip(start) {
+    for (i, c) in text[start..].char_indices().map(|(i, c)| (start + i, c)) {
         match c {
             '\\' => escape = !escape,
             '/' => {
                 if !escape {
                     return Ok((text[start..i].to_string(), i + 1));
                 }
+                escape = false;
             }
             _ => escape = false,
         }
     }
-    Err("Did not find an unescaped backslash!".into())
+    Err(SpidiorError::parse("Did not find an unescaped slash!"))
 }
 
 #[test]
@@ -88,4 +103,19 @@ fn parsing_command() {
     assert_eq!(x.find, "jon");
     assert_eq!(x.replace, "John");
     assert_eq!(x.global, true);
+    let x = parse("%s/straße/street/gic").unwrap();
+    assert!(x.global && x.ignore_case && x.clusters);
+    assert!(parse("%s/a/b/gn").unwrap().count && !x.count);
+    assert!(parse("%s/a/b/u").is_err());
+    assert!(parse("%s/a/b/gg").is_err());
+    assert!(parse("%s/a/b/x").is_err());
+    let x = parse("%d/[[type=Session]].*log/gi").unwrap();
+    assert!(x.command == ast::Command::Delete && x.ignore_case && x.replace.is_empty());
+    assert_eq!(x.find, "[[type=Session]].*log");
+    assert_eq!(parse("%s/a/b/").unwrap().command, ast::Command::Substitute);
+    assert!(parse("%d/a").is_err());
+    let x = parse("%a/log\\(/\\/\\/ logged/g").unwrap();
+    assert_eq!((x.command, x.replace.as_str()), (ast::Command::After, "\\/\\/ logged"));
+    assert_eq!(parse("%i/a/b/").unwrap().command, ast::Command::Before);
+    assert!(parse("%x/a/b/").is_err());
 }
@@ -1,6 +1,5 @@
-use std::error::Error;
-
-use super::ast::{ReplaceItem, Replacement};
+use super::ast::{Operator, ReplaceItem, Replacement};
+use crate::error::SpidiorError;
 
 /// For parsing out the replacement form of a command
 /// # Arguments
@@ -9,10 +8,10 @@ use super::ast::{ReplaceItem, Replacement};
 ///
 /// # Returns
 ///
-/// A Result<Replacement, Box<dyn Error>>, where on success, it returns a
+/// A Result<Replacement, SpidiorError>, where on success, it returns a
 /// Replacement containing the set of ReplaceItems
 /// that make up the new replacement string
-pub fn parse(text: &str) -> Result<Replacement, Box<dyn Error>> {
+pub fn parse(text: &str) -> Result<Replacement, SpidiorError> {
     let mut v = Vec::new();
     let mut i = 0;
     while i < text.len() {
@@ -27,20 +26,27 @@ pub fn parse(text: &str) -> Result<Replacement, Box<dyn Error>> {
 /// # Arguments
 ///
 /// * `text` - A string slice that contains the replacement to be parsed
-/// * `start` - The index in the string to start from
+/// * `start` - The byte index in the string to start from
 ///
 /// # Returns
 ///
-/// A Result<(ReplaceItem, usize), Box<dyn Error>>, where on success, it returns a
+/// A Result<(ReplaceItem, usize), SpidiorError>, where on success, it returns a
 /// tuple containing the parsed ReplaceItem and the index of where to start
 /// for future parsing.
-fn parse_item(text: &str, start: usize) -> Result<(ReplaceItem, usize), Box<dyn Error>> {
-    let mut chars = text.chars().enumerate().skip(start);
-    if chars.next().ok_or("Out of characters")?.1 == '\\' {
+fn parse_item(text: &str, start: usize) -> Result<(ReplaceItem, usize), SpidiorError> {
+    if text[start..].starts_with("${") {
+        return parse_function(text, start);
+    }
+    // An escaped slash, which would otherwise end the replacement
+    if text[start..].starts_with("\\/") {
+        return Ok((ReplaceItem::String("/".to_string()), start + 2));
+    }
+    let mut chars = text[start..].char_indices().map(|(i, c)| (start + i, c));
+    if chars.next().ok_or_else(|| SpidiorError::parse("Out of characters"))?.1 == '\\' {
         // We might be parsing a backreference
         let mut last = 0;
-        while let Some((i, c)) = chars.next() {
-            if !c.is_digit(10) {
+        for (i, c) in chars.by_ref() {
+            if !c.is_ascii_digit() {
                 break;
             } else {
                 last = i;
@@ -48,14 +54,14 @@ fn parse_item(text: &str, start: usize) -> Result<(ReplaceItem, usize), Box<dyn
         }
         if last != 0 {
             return Ok((
-                ReplaceItem::BackRef(text[start + 1..last+1].parse::<usize>()?),
+                ReplaceItem::BackRef(text[start + 1..last+1].parse::<usize>().map_err(|_| SpidiorError::parse("Backreference is too large"))?),
                 last + 1,
             ));
         }
     }
     //If we are here, we are parsing text and not a backreference
-    while let Some((i, c)) = chars.next() {
-        if c == '\\' {
+    for (i, c) in chars {
+        if c == '\\' || text[i..].starts_with("${") {
             return Ok((ReplaceItem::String(text[start..i].to_string()), i));
         }
     }
@@ -65,8 +71,91 @@ fn parse_item(text: &str, start: usize) -> Result<(ReplaceItem, usize), Box<dyn
     ))
 }
 
+/// Parses a group passed through a function, like `${1:upper}`, a group
+/// with arithmetic done on it, like `${1+1000}`, a replacement chosen by
+/// whether a group matched any text, like `${1:+prefix_${1}}` or
+/// `${1:-default}`, a bare group, like `${1}`, which is a backreference, or
+/// an argument of a call, like `${a1}`, starting at its `$`
+///
+/// Division is written `\/`, like `${1\/2}`, as an unescaped `/` ends the
+/// replacement.
+///
+/// # Returns
+///
+/// A Result<(ReplaceItem, usize), SpidiorError>, as `parse_item` does, which
+/// will Err with a ParseError if the group is not closed, is not a number,
+/// names a function that is neither builtin nor registered, names an
+/// argument that is not a number from one, or does
+/// arithmetic that is not an operator and a whole number, or divides by zero
+fn parse_function(text: &str, start: usize) -> Result<(ReplaceItem, usize), SpidiorError> {
+    let end = closing(text, start).ok_or_else(|| SpidiorError::parse(format!("`{}` is not closed with a `}}`", &text[start..])))?;
+    let inner = &text[start + 2..end];
+    if let Some(n) = inner.strip_prefix('a') {
+        let n = n.parse::<usize>().ok().filter(|x| *x > 0).ok_or_else(|| SpidiorError::parse(format!("`${{{}}}` should be `a` and the number of an argument, counting from one, like `${{a1}}`", inner)))?;
+        return Ok((ReplaceItem::Argument(n), end + 1));
+    }
+    let digits = inner.find(|c: char| !c.is_ascii_digit()).unwrap_or(inner.len());
+    let (group, rest) = inner.split_at(digits);
+    let group = group.parse::<usize>().map_err(|_| SpidiorError::parse(format!("`${{{}}}` should start with the number of a group, like `${{1:upper}}`", inner)))?;
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('\\').filter(|x| x.starts_with('/')).unwrap_or(rest);
+    let mut chars = rest.chars();
+    let operator = match chars.next() {
+        None => return Ok((ReplaceItem::BackRef(group), end + 1)),
+        Some(':') if chars.as_str().starts_with(['+', '-']) => {
+            let replacement = Box::new(parse(&chars.as_str()[1..])?);
+            let item = match chars.next() {
+                Some('+') => ReplaceItem::Conditional(group, replacement, Box::new(Replacement { replacements: Vec::new() })),
+                _ => ReplaceItem::Conditional(group, Box::new(Replacement { replacements: vec![ReplaceItem::BackRef(group)] }), replacement),
+            };
+            return Ok((item, end + 1));
+        }
+        Some(':') => {
+            let name = chars.as_str();
+            if crate::functions::get(name).is_none() {
+                let names: Vec<&str> = crate::functions::builtin().collect();
+                return Err(SpidiorError::parse(format!("Unknown replacement function `{}`, expected one of {} or a registered function", name, names.join(", "))));
+            }
+            return Ok((ReplaceItem::Function(group, name.to_string()), end + 1));
+        }
+        Some('+') => Operator::Add,
+        Some('-') => Operator::Sub,
+        Some('*') => Operator::Mul,
+        Some('/') => Operator::Div,
+        Some(_) => return Err(SpidiorError::parse(format!("`${{{}}}` should be a group, a group and a function, like `${{1:upper}}`, or arithmetic on a group, like `${{1+1000}}`", inner))),
+    };
+    let number = chars.as_str().trim();
+    let number = number.parse::<i64>().map_err(|_| SpidiorError::parse(format!("`{}` in `${{{}}}` is not a whole number", number, inner)))?;
+    if operator == Operator::Div && number == 0 {
+        return Err(SpidiorError::parse(format!("`${{{}}}` divides by zero", inner)));
+    }
+    Ok((ReplaceItem::Arithmetic(group, operator, number), end + 1))
+}
+
+/// Finds the `}` that closes the `${` at `start`, past any `${...}` nested
+/// within it
+fn closing(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < text.len() {
+        if text[i..].starts_with("${") {
+            depth += 1;
+            i += 2;
+            continue;
+        }
+        if text[i..].starts_with('}') {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+        i += text[i..].chars().next()?.len_utf8();
+    }
+    None
+}
+
 #[test]
-fn parsing_replacement() -> Result<(), Box<dyn Error>>{
+fn parsing_replacement() -> Result<(), Box<dyn std::error::Error>>{
     let parsed = parse("bob\\\\\\13dole")?;
     if let ReplaceItem::String(s) = parsed.replacements.get(0).ok_or("sad")? {
         assert_eq!(s, "bob");
@@ -89,5 +178,57 @@ fn parsing_replacement() -> Result<(), Box<dyn Error>>{
         panic!("Expected a string, but didn't get it");
     }
     assert_eq!(parsed.replacements.len(), 4);
+    let parsed = parse("a\\/\\/b")?;
+    assert!(matches!(parsed.replacements.as_slice(), [ReplaceItem::String(a), ReplaceItem::String(x), ReplaceItem::String(y), ReplaceItem::String(b)] if a == "a" && x == "/" && y == "/" && b == "b"));
+    Ok(())
+}
+
+#[test]
+fn parsing_functions() -> Result<(), SpidiorError> {
+    let parsed = parse("get${1:PascalCase}(${2})é${1:len}")?;
+    let items: Vec<String> = parsed.replacements.iter().map(|x| format!("{:?}", x)).collect();
+    assert_eq!(items, vec!["String(\"get\")", "Function(1, \"PascalCase\")", "String(\"(\")", "BackRef(2)", "String(\")é\")", "Function(1, \"len\")"]);
+    assert!(parse("${1:shout}").is_err());
+    assert!(parse("${x:upper}").is_err());
+    assert!(parse("${1:upper").is_err());
+    Ok(())
+}
+
+#[test]
+fn parsing_arguments() -> Result<(), SpidiorError> {
+    let parsed = parse("f(${a2}, ${a1}\\1)")?;
+    let items: Vec<String> = parsed.replacements.iter().map(|x| format!("{:?}", x)).collect();
+    assert_eq!(items, vec!["String(\"f(\")", "Argument(2)", "String(\", \")", "Argument(1)", "BackRef(1)", "String(\")\")"]);
+    assert!(parse("${a0}").is_err());
+    assert!(parse("${a}").is_err());
+    Ok(())
+}
+
+#[test]
+fn parsing_arithmetic() -> Result<(), SpidiorError> {
+    let parsed = parse("port=${1+1000} ${2 - 1}${1*-2}${1\\/4}")?;
+    let items: Vec<String> = parsed.replacements.iter().map(|x| format!("{:?}", x)).collect();
+    assert_eq!(items, vec!["String(\"port=\")", "Arithmetic(1, Add, 1000)", "String(\" \")", "Arithmetic(2, Sub, 1)", "Arithmetic(1, Mul, -2)", "Arithmetic(1, Div, 4)"]);
+    assert!(parse("${1+}").is_err());
+    assert!(parse("${1+x}").is_err());
+    assert!(parse("${1%2}").is_err());
+    assert!(parse("${1\\/0}").is_err());
+    Ok(())
+}
+
+#[test]
+fn parsing_conditionals() -> Result<(), SpidiorError> {
+    let parsed = parse("${1:+prefix_${1:upper}}é${2:-default}")?;
+    let items: Vec<String> = parsed.replacements.iter().map(|x| format!("{:?}", x)).collect();
+    assert_eq!(
+        items,
+        vec![
+            "Conditional(1, Replacement { replacements: [String(\"prefix_\"), Function(1, \"upper\")] }, Replacement { replacements: [] })",
+            "String(\"é\")",
+            "Conditional(2, Replacement { replacements: [BackRef(2)] }, Replacement { replacements: [String(\"default\")] })",
+        ]
+    );
+    assert!(parse("${1:+a${2}").is_err());
+    assert!(parse("${1:+${2:shout}}").is_err());
     Ok(())
 }
@@ -0,0 +1,143 @@
+//! Provides a check of the sets of a pattern, like `[a-z]`, made on its text
+//! before it is parsed, so that a set which cannot mean what it says is
+//! refused with where it goes wrong, rather than matching nothing or
+//! failing once its NFA is built
+//!
+//! A set may not be empty, a range may not run backwards, like `z-a`, and
+//! a `-` that does not join two characters, like the one in `[a-]` or the
+//! second in `[a-c-e]`, must be escaped, as `\-`. Whitespace, which the
+//! lexer skips everywhere in a pattern, must be escaped within a set too,
+//! as `\ `, rather than being dropped from it. Query sets, like
+//! `[[name=x]]`, are left to the query parser.
+
+use crate::error::SpidiorError;
+
+/// A character within a set, as written
+struct Written {
+    c: char,
+    /// The column of the character in the pattern, counting from 1
+    column: usize,
+    /// Whether the character was escaped, like `\-`
+    escaped: bool,
+}
+
+impl Written {
+    /// Whether this is a `-` that joins the characters around it
+    fn is_dash(&self) -> bool {
+        self.c == '-' && !self.escaped
+    }
+
+    /// Whether this is whitespace the lexer would skip
+    fn is_skipped(&self) -> bool {
+        self.c.is_whitespace() && !self.escaped
+    }
+}
+
+/// Checks the sets of a pattern
+///
+/// # Arguments
+///
+/// * `find` - The find part of a query, like `[a-z]+_[0-9]`
+///
+/// # Returns
+///
+/// A Result<(), SpidiorError>, which will Err with a ParseError naming the
+/// first set that is empty, has a range that runs backwards, has a
+/// dangling `-`, or holds unescaped whitespace, and its column. A set that is never closed is left for the
+/// parser to refuse.
+pub fn check(find: &str) -> Result<(), SpidiorError> {
+    let chars: Vec<char> = find.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '[' if chars.get(i + 1) == Some(&'[') => {
+                match (i + 2..chars.len()).find(|&j| chars[j] == ']' && chars.get(j + 1) == Some(&']')) {
+                    Some(j) => i = j + 2,
+                    None => return Ok(()),
+                }
+            }
+            '[' => {
+                let open = i;
+                i += 1;
+                if chars.get(i) == Some(&'^') {
+                    i += 1;
+                }
+                let mut items = Vec::new();
+                loop {
+                    match chars.get(i) {
+                        None => return Ok(()),
+                        Some(']') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            items.push(Written { c: chars[i + 1], column: i + 2, escaped: true });
+                            i += 2;
+                        }
+                        Some(&c) => {
+                            items.push(Written { c, column: i + 1, escaped: false });
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                let set: String = chars[open..i].iter().collect();
+                check_set(&set, open + 1, &items)?;
+            }
+            _ => i += 1,
+        }
+    }
+    Ok(())
+}
+
+/// Checks the characters of one set, written `set` at column `column`
+fn check_set(set: &str, column: usize, items: &[Written]) -> Result<(), SpidiorError> {
+    if items.is_empty() {
+        return Err(SpidiorError::parse(format!("Empty set `{}` at column {} matches nothing", set, column)));
+    }
+    if let Some(x) = items.iter().find(|x| x.is_skipped()) {
+        return Err(SpidiorError::parse(format!(
+            "Whitespace at column {} in set `{}` would be skipped, as it is everywhere in a pattern; write `\\ ` to match a space",
+            x.column, set
+        )));
+    }
+    let mut k = 0;
+    while k < items.len() {
+        let x = &items[k];
+        if x.is_dash() {
+            return Err(SpidiorError::parse(format!(
+                "Dangling `-` at column {} in set `{}`, which joins no range; write `\\-` to match a dash",
+                x.column, set
+            )));
+        }
+        match (items.get(k + 1), items.get(k + 2)) {
+            (Some(dash), Some(y)) if dash.is_dash() && !y.is_dash() => {
+                if x.c > y.c {
+                    return Err(SpidiorError::parse(format!(
+                        "Reversed range `{}-{}` at column {} in set `{}` matches nothing; write it `{}-{}`",
+                        x.c, y.c, x.column, set, y.c, x.c
+                    )));
+                }
+                k += 3;
+            }
+            _ => k += 1,
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_check() {
+    assert!(check("[a-z]+_[^0-9\\-]").is_ok());
+    assert!(check("[[name=a-]]\\[x-]").is_ok());
+    assert!(check("[a-c").is_ok());
+    let message = |find: &str| check(find).unwrap_err().to_string();
+    assert!(message("x[]").contains("Empty set `[]` at column 2"));
+    assert!(message("[^]").contains("Empty set `[^]` at column 1"));
+    assert!(message("ab[z-a]").contains("Reversed range `z-a` at column 4"));
+    assert!(message("[a-]").contains("Dangling `-` at column 3"));
+    assert!(message("[-a]").contains("Dangling `-` at column 2"));
+    assert!(message("[\\a-c-e]").contains("Dangling `-` at column 6 in set `[\\a-c-e]`"));
+    assert!(message("x[ -~]").contains("Whitespace at column 3 in set `[ -~]`"));
+    assert!(message("[ ]").contains("Whitespace at column 2 in set `[ ]`"));
+    assert!(message("[a\tb]").contains("Whitespace at column 3 in set `[a\tb]`"));
+    assert!(check("[a\\ b][\\ -~]").is_ok());
+}
@@ -0,0 +1,153 @@
+//! Provides the renaming of files and directories run by `spidior
+//! rename-files`, which applies a substitution like `s/Session/Sess/` to
+//! each name beneath a directory, so that a class can be renamed along with
+//! the file that holds it
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use walkdir::WalkDir;
+
+use crate::error::SpidiorError;
+use crate::languages::{cache::Parsed, plain::Plain};
+use crate::regexparser::{self, ast::Replace};
+use crate::replacer;
+
+/// A file or directory to rename
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Parses a substitution, which is a query like `%s/Session/Sess/`, though
+/// the `%` may be left out
+pub(crate) fn substitution(query: &str) -> Result<Replace, SpidiorError> {
+    match query.strip_prefix('%') {
+        Some(_) => regexparser::parse(query),
+        None => regexparser::parse(&format!("%{}", query)),
+    }
+}
+
+/// Whether a path beneath `root` is hidden, like `.git`, or within a hidden
+/// directory, which renames leave alone
+pub(crate) fn hidden(root: &Path, path: &Path) -> bool {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.components().any(|x| x.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+/// Applies a substitution to a name, matching it as plain text
+///
+/// # Returns
+///
+/// A Result<String, SpidiorError>, where on success, it returns the new
+/// name, which is `name` if the substitution does not match. It will Err
+/// with an EditError if the new name is empty or holds a path separator.
+pub(crate) fn renamed(replace: &Replace, name: &str) -> Result<String, SpidiorError> {
+    let parsed = Parsed::new(name, &Plain {});
+    let renamed = replacer::edits_parsed(name, replace.clone(), |_, _| true, &parsed, false, false, false)?.apply(name)?;
+    if renamed.is_empty() || renamed.contains('/') || renamed.contains(std::path::MAIN_SEPARATOR) {
+        return Err(SpidiorError::edit(format!("`{}` is not a valid name", renamed)));
+    }
+    Ok(renamed)
+}
+
+/// Works out the renames a substitution makes to the names beneath a
+/// directory
+///
+/// # Arguments
+///
+/// * `root` - The directory, which is not renamed itself, nor are hidden
+///   names beneath it
+/// * `recursive` - Whether names below the directory's own entries are
+///   renamed too
+/// * `query` - The substitution, like `s/Session/Sess/`, which is matched
+///   against each name as plain text
+///
+/// # Returns
+///
+/// A Result<Vec<Rename>, SpidiorError>, where on success, it returns the
+/// renames in the order they can be made, each entry of a directory before
+/// the directory itself. It will Err with a ParseError if the substitution
+/// is not valid, or an EditError if two names would be renamed to the same
+/// path, or a name would be renamed to one that exists.
+pub fn plan(root: &Path, recursive: bool, query: &str) -> Result<Vec<Rename>, SpidiorError> {
+    let replace = substitution(query)?;
+    let mut walk = WalkDir::new(root).min_depth(1).contents_first(true).sort_by(|a, b| a.file_name().cmp(b.file_name()));
+    if !recursive {
+        walk = walk.max_depth(1);
+    }
+    let mut renames = Vec::new();
+    let mut targets = HashSet::new();
+    for entry in walk {
+        let entry = entry.map_err(|e| SpidiorError::io(format!("Could not read {}", root.display()), e.into()))?;
+        if hidden(root, entry.path()) {
+            continue;
+        }
+        let name = match entry.file_name().to_str() {
+            Some(x) => x,
+            None => continue,
+        };
+        let renamed = renamed(&replace, name).map_err(|e| SpidiorError::edit(format!("Cannot rename {}: {}", entry.path().display(), e)))?;
+        if renamed == name {
+            continue;
+        }
+        let to = entry.path().with_file_name(&renamed);
+        if to.exists() || !targets.insert(to.clone()) {
+            return Err(SpidiorError::edit(format!("Cannot rename {} to {}, which already exists", entry.path().display(), to.display())));
+        }
+        renames.push(Rename { from: entry.path().to_path_buf(), to });
+    }
+    Ok(renames)
+}
+
+/// Makes renames worked out by [`plan`], in order
+///
+/// # Arguments
+///
+/// * `renames` - The renames
+/// * `git` - Whether each rename is made with `git mv`, so that git records
+///   it, rather than by renaming the file directly
+///
+/// # Returns
+///
+/// A Result<(), SpidiorError>, which will Err with an IoError at the first
+/// rename that fails, leaving the renames before it made
+pub fn apply(renames: &[Rename], git: bool) -> Result<(), SpidiorError> {
+    for rename in renames {
+        let failed = |e| SpidiorError::io(format!("Could not rename {} to {}", rename.from.display(), rename.to.display()), e);
+        if git {
+            let status = Command::new("git").arg("mv").arg("--").arg(&rename.from).arg(&rename.to).status().map_err(failed)?;
+            if !status.success() {
+                return Err(failed(std::io::Error::other(format!("git mv exited with {}", status))));
+            }
+        } else {
+            std::fs::rename(&rename.from, &rename.to).map_err(failed)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rename() {
+    let dir = std::env::temp_dir().join(format!("spidior-rename-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("SessionStore")).unwrap();
+    std::fs::write(dir.join("SessionStore").join("Session.java"), "").unwrap();
+    std::fs::write(dir.join("Main.java"), "").unwrap();
+    std::fs::create_dir_all(dir.join(".Session")).unwrap();
+    let renames = plan(&dir, true, "s/Session/Sess/").unwrap();
+    assert_eq!(
+        renames,
+        vec![
+            Rename { from: dir.join("SessionStore").join("Session.java"), to: dir.join("SessionStore").join("Sess.java") },
+            Rename { from: dir.join("SessionStore"), to: dir.join("SessStore") },
+        ]
+    );
+    assert_eq!(plan(&dir, false, "%s/Session/Sess/").unwrap().len(), 1);
+    assert!(plan(&dir, true, "s/SessionStore/Main.java/").is_err());
+    assert!(plan(&dir, true, "s/Session/Sess").is_err());
+    apply(&renames, false).unwrap();
+    assert!(dir.join("SessStore").join("Sess.java").is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,267 @@
+//! Provides the loop run by `spidior repl`, which reads files once and then
+//! runs each query typed at it over them, so that a query can be built up
+//! without parsing every file again for each try
+//!
+//! A line holding a whole query, like `%s/[[type=Session]]/sess/g`, prints
+//! its matches and a diff of the replacement. A line holding only the find
+//! part of a query, like `[[type=Session]]`, prints its matches. Lines
+//! starting with `:` are commands:
+//!
+//! * `:apply` - writes the replacement of the last whole query to the files
+//! * `:files` - lists the files read, with their languages
+//! * `:history` - lists the queries run, numbered so that `!N` runs the
+//!   `N`th again
+//! * `:help` - lists the commands
+//! * `:quit` - stops the loop, as does the end of the input
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::editing::{editset::EditSet, transaction::Transaction};
+use crate::error::SpidiorError;
+use crate::languages::{cache::Parsed, registry::Registry, Language};
+use crate::matcher;
+use crate::regexparser::{self, ast::Replace};
+use crate::replacer;
+
+const HELP: &str = "\
+%s/FIND/REPLACE/g  print the matches of a query and a diff of its replacement
+FIND               print the matches of the find part of a query
+!N                 run the Nth query in :history again
+:apply             write the replacement of the last whole query to the files
+:files             list the files read
+:history           list the queries run
+:help              print this message
+:quit              stop";
+
+/// A file read into the loop, with its parse results
+struct Loaded {
+    path: PathBuf,
+    text: String,
+    /// The name of the language the file is parsed as
+    lang: String,
+    parsed: Parsed,
+}
+
+/// The files and queries the loop keeps between lines
+#[derive(Default)]
+pub struct Repl {
+    files: Vec<Loaded>,
+    /// Every query run, oldest first
+    pub history: Vec<String>,
+    /// The last whole query run, which `:apply` writes
+    last: Option<Replace>,
+    /// Whether matches inside string and char literals should be left alone
+    pub skip_strings: bool,
+    /// Whether `type` criteria should also match through type aliases
+    pub resolve_aliases: bool,
+    /// Whether matches inside code disabled by conditional compilation
+    /// should be left alone
+    pub skip_disabled: bool,
+    /// Whether the loop has been asked to stop
+    pub quit: bool,
+}
+
+impl Repl {
+    /// Creates a Repl with no files
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file for queries to run over
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Where the file is, which `:apply` writes to
+    /// * `text` - The contents of the file
+    /// * `lang` - The language the file is parsed as
+    /// * `parsed` - The parse results of `text`
+    pub fn add(&mut self, path: &Path, text: String, lang: &dyn Language, parsed: Parsed) {
+        self.files.push(Loaded {
+            path: path.to_path_buf(),
+            text,
+            lang: lang.name().to_string(),
+            parsed,
+        });
+    }
+
+    /// The paths of the files added
+    pub fn files(&self) -> Vec<&Path> {
+        self.files.iter().map(|x| x.path.as_path()).collect()
+    }
+
+    /// Answers one line of input
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - A query or command
+    ///
+    /// # Returns
+    ///
+    /// What to print in answer, which describes the error if the line could
+    /// not be carried out
+    pub fn handle(&mut self, line: &str) -> String {
+        let line = line.trim();
+        let result = match line {
+            "" => Ok(String::new()),
+            ":quit" | ":q" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            ":help" => Ok(HELP.to_string()),
+            ":files" => Ok(self.files.iter().map(|x| format!("{} ({})", x.path.display(), x.lang)).collect::<Vec<_>>().join("\n")),
+            ":history" => Ok(self.history.iter().enumerate().map(|(i, x)| format!("{:>4}  {}", i + 1, x)).collect::<Vec<_>>().join("\n")),
+            ":apply" => self.apply(),
+            _ if line.starts_with(':') => Err(SpidiorError::parse(format!("Unknown command `{}`, try :help", line))),
+            _ if line.starts_with('!') => match line[1..].parse::<usize>().ok().and_then(|n| self.history.get(n.wrapping_sub(1))) {
+                Some(query) => {
+                    let query = query.clone();
+                    return self.handle(&query);
+                }
+                None => Err(SpidiorError::parse(format!("No query `{}` in the history", &line[1..]))),
+            },
+            _ => self.query(line),
+        };
+        result.unwrap_or_else(|e| format!("Error: {}", e))
+    }
+
+    /// Prints the matches of a query, and a diff of its replacement if it
+    /// is a whole query
+    fn query(&mut self, line: &str) -> Result<String, SpidiorError> {
+        let whole = line.starts_with("%s/");
+        let replace = if whole { regexparser::parse(line) } else { regexparser::parse(&format!("%s/{}//g", line)) };
+        self.history.push(line.to_string());
+        let replace = replace?;
+        let mut out = Vec::new();
+        let mut count = 0;
+        let mut diffs = String::new();
+        for file in &self.files {
+            let mut found = matcher::find_parsed(&file.text, replace.find.clone(), &file.parsed, self.resolve_aliases);
+            if self.skip_strings {
+                found = matcher::skip_literals(found, &file.parsed.spans);
+            }
+            if self.skip_disabled {
+                found = matcher::skip_disabled(found, &file.parsed.spans);
+            }
+            for m in &found {
+                let (line, column) = m.position();
+                out.push(format!("{}:{}:{}: {}", file.path.display(), line, column, m.text()));
+            }
+            count += found.len();
+            if whole {
+                diffs += &self.edits(file, &replace)?.diff(&file.text, &file.path.display().to_string())?;
+            }
+        }
+        out.push(format!("{} match{}", count, if count == 1 { "" } else { "es" }));
+        if whole {
+            self.last = Some(replace);
+            out.push(diffs.trim_end().to_string());
+        }
+        Ok(out.join("\n"))
+    }
+
+    /// The edits a query makes to a file
+    fn edits(&self, file: &Loaded, replace: &Replace) -> Result<EditSet, SpidiorError> {
+        replacer::edits_parsed(&file.text, replace.clone(), |_, _| true, &file.parsed, self.skip_strings, self.resolve_aliases, self.skip_disabled)
+    }
+
+    /// Writes the replacement of the last whole query to every file it
+    /// changes, together, then parses them again
+    fn apply(&mut self) -> Result<String, SpidiorError> {
+        let replace = self.last.clone().ok_or_else(|| SpidiorError::edit("No query to apply, run one like %s/FIND/REPLACE/g first"))?;
+        let mut tx = Transaction::new();
+        let mut changed = Vec::new();
+        let mut count = 0;
+        for (i, file) in self.files.iter().enumerate() {
+            let edits = self.edits(file, &replace)?;
+            if !edits.is_empty() {
+                let text = edits.apply(&file.text)?;
+                tx.stage(&file.path, &text)?;
+                changed.push((i, text));
+                count += edits.len();
+            }
+        }
+        tx.commit()?;
+        let registry = Registry::builtin();
+        let files = changed.len();
+        for (i, text) in changed {
+            let file = &mut self.files[i];
+            if let Some(lang) = registry.by_name(&file.lang) {
+                file.parsed = Parsed::new(&text, lang);
+            }
+            file.text = text;
+        }
+        self.last = None;
+        Ok(format!("Applied {} edit{} to {} file{}", count, if count == 1 { "" } else { "s" }, files, if files == 1 { "" } else { "s" }))
+    }
+}
+
+/// Runs the loop, prompting for and answering a line at a time until the
+/// input ends or `:quit` is typed
+///
+/// # Arguments
+///
+/// * `repl` - The loop, with its files added
+/// * `input` - Where lines are read from
+/// * `output` - Where prompts and answers are written to
+/// * `history` - A file the queries of earlier runs are read from, and the
+///   queries of this one are appended to
+pub fn run(repl: &mut Repl, input: impl BufRead, mut output: impl Write, history: Option<&Path>) -> Result<(), SpidiorError> {
+    let write_failed = |e| SpidiorError::io("Could not write the answer", e);
+    if let Some(path) = history {
+        if let Ok(text) = std::fs::read_to_string(path) {
+            repl.history.extend(text.lines().map(String::from));
+        }
+    }
+    let before = repl.history.len();
+    let mut lines = input.lines();
+    while !repl.quit {
+        write!(output, "> ").and_then(|_| output.flush()).map_err(write_failed)?;
+        let line = match lines.next() {
+            Some(x) => x.map_err(|e| SpidiorError::io("Could not read a line", e))?,
+            None => break,
+        };
+        let answer = repl.handle(&line);
+        if !answer.is_empty() {
+            writeln!(output, "{}", answer).map_err(write_failed)?;
+        }
+    }
+    if let Some(path) = history {
+        let added: String = repl.history[before..].iter().map(|x| format!("{}\n", x)).collect();
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(added.as_bytes()))
+            .map_err(|e| SpidiorError::io(format!("Could not write {}", path.display()), e))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_repl() {
+    use crate::languages::clike::Clike;
+    let dir = std::env::temp_dir().join(format!("spidior-repl-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.c");
+    let text = "void f() { Session s; s.run(); }";
+    std::fs::write(&path, text).unwrap();
+    let mut repl = Repl::new();
+    repl.add(&path, text.to_string(), &Clike {}, Parsed::new(text, &Clike {}));
+    assert!(repl.handle(":apply").starts_with("Error: "));
+    let found = repl.handle("[[type=Session]]");
+    assert!(found.contains("a.c:1:20: s\n"));
+    assert!(found.ends_with("2 matches"));
+    assert!(repl.handle("%s/[[type=Session]]/sess/g").contains("+void f() { Session sess; sess.run(); }"));
+    assert_eq!(repl.handle("!1"), found);
+    assert!(repl.handle("%s/[[type=Session]]/sess/g\n").contains("\n2 matches\n--- a/"));
+    assert_eq!(repl.handle(":apply"), "Applied 2 edits to 1 file");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "void f() { Session sess; sess.run(); }");
+    assert!(repl.handle("[[name=sess]]").ends_with("2 matches"));
+    assert_eq!(repl.history.len(), 5);
+    assert!(repl.handle("!9").starts_with("Error: "));
+    let mut out = Vec::new();
+    run(&mut repl, "[[name=nothing]]\n:quit\n[[name=sess]]\n".as_bytes(), &mut out, None).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "> 0 matches\n> ");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,333 @@
+//! Provides rule packs, which bundle queries into named, shareable rules
+//! that `spidior check` reports and `spidior fix` applies. A rule pack is
+//! written in the same subset of TOML as the config file, with a
+//! `[rules.<name>]` section for each rule:
+//!
+//! ```toml
+//! [rules.session-name]
+//! description = "Sessions are called `sess`"
+//! severity = "warning"
+//! match = "[[type=Session]]"
+//! fix = "sess"
+//! ```
+//!
+//! `match` is the find part of a query and `fix` its replacement, so the
+//! rule above runs `%s/[[type=Session]]/sess/g`. A rule without a `fix` is
+//! only reported. `severity` is `error`, `warning`, or `info`, and is
+//! `warning` if it is not given. `import` lists what the fix needs
+//! imported, like `import = ["new.pkg.NewClient"]`, which is added, in the
+//! way the language of the file writes it, to each file the fix edits that
+//! does not already import it.
+//!
+//! A rule can also carry examples, which `spidior test-rules` checks it
+//! against: `matches` lists snippets the rule should match, `ignores` those
+//! it should not, and `fixed` what the fix should make of each of `matches`,
+//! in order. The snippets are parsed as `language`, or as `clike` if it is
+//! not given:
+//!
+//! ```toml
+//! [rules.session-name]
+//! match = "[[type=Session]]"
+//! fix = "sess"
+//! matches = ["Session s = open();"]
+//! ignores = ["int s = 0;"]
+//! fixed = ["Session sess = open();"]
+//! ```
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::editing::{editset::{Conflict, EditSet}, imports};
+use crate::error::SpidiorError;
+use crate::languages::{cache::Parsed, Language};
+use crate::matcher::{self, Budget, Match};
+use crate::regexparser::{self, ast::Replace};
+use crate::replacer;
+
+/// How serious breaking a rule is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Severity::Error),
+            "warning" => Ok(Severity::Warning),
+            "info" => Ok(Severity::Info),
+            _ => Err(format!("expected `error`, `warning`, or `info`, found `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// A named query, with what breaking it means and how to fix it
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub description: String,
+    pub severity: Severity,
+    /// The query the rule runs, whose replacement is the fix, or empty if
+    /// the rule has none
+    query: Replace,
+    /// Whether the rule has a fix
+    pub fixable: bool,
+    /// The paths the fix needs imported into the files it edits
+    pub imports: Vec<String>,
+    /// The snippets the rule should match
+    pub matches: Vec<String>,
+    /// The snippets the rule should not match
+    pub ignores: Vec<String>,
+    /// What the fix should make of each of `matches`, in order, if given
+    pub fixed: Vec<String>,
+    /// The name of the language the examples are in, if not `clike`
+    pub language: Option<String>,
+}
+
+impl Rule {
+    /// Finds where a piece of code breaks the rule
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The code
+    /// * `parsed` - The parse results of `text`
+    pub fn check(&self, text: &str, parsed: &Parsed) -> Vec<Match> {
+        matcher::find_parsed(text, self.query.find.clone(), parsed, false)
+    }
+
+    /// Finds where a piece of code breaks the rule, as `check` does, but
+    /// stops once matching goes over `budget`
+    ///
+    /// # Returns
+    ///
+    /// A Result<Vec<Match>, SpidiorError>, which will Err with a LimitError
+    /// if matching goes over the budget
+    pub fn check_within(&self, text: &str, parsed: &Parsed, budget: &Budget) -> Result<Vec<Match>, SpidiorError> {
+        matcher::find_within(text, self.query.find.clone(), parsed, false, budget)
+    }
+
+    /// Works out the edits that fix a piece of code, which are empty if the
+    /// rule has no fix
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The code
+    /// * `parsed` - The parse results of `text`
+    pub fn fix(&self, text: &str, parsed: &Parsed) -> Result<EditSet, SpidiorError> {
+        if !self.fixable {
+            return Ok(EditSet::new());
+        }
+        Ok(replacer::edits_parsed(text, self.query.clone(), |_, _| true, parsed, false, false, false)?.with_origin(&self.name))
+    }
+
+    /// How many examples the rule has, counting a `fixed` example apart from
+    /// the snippet it fixes
+    pub fn examples(&self) -> usize {
+        self.matches.len() + self.ignores.len() + self.fixed.len()
+    }
+
+    /// Checks the rule against its examples
+    ///
+    /// # Arguments
+    ///
+    /// * `lang` - The language the examples are in
+    ///
+    /// # Returns
+    ///
+    /// A Result<Vec<String>, SpidiorError> with a message for each example
+    /// the rule gets wrong, which is empty if it passes them all
+    pub fn test(&self, lang: &dyn Language) -> Result<Vec<String>, SpidiorError> {
+        let mut failures = Vec::new();
+        for (i, text) in self.matches.iter().enumerate() {
+            let parsed = Parsed::new(text, lang);
+            if self.check(text, &parsed).is_empty() {
+                failures.push(format!("expected a match in `{}`", text));
+            }
+            if let Some(expected) = self.fixed.get(i) {
+                let fixed = self.fix(text, &parsed)?.apply(text)?;
+                if fixed != *expected {
+                    failures.push(format!("expected `{}` to be fixed to `{}`, found `{}`", text, expected, fixed));
+                }
+            }
+        }
+        for text in &self.ignores {
+            let found = self.check(text, &Parsed::new(text, lang));
+            if let Some(m) = found.first() {
+                failures.push(format!("expected no match in `{}`, found `{}`", text, &text[m.start()..m.start() + m.len()]));
+            }
+        }
+        Ok(failures)
+    }
+}
+
+/// The rules read from one or more rule packs
+#[derive(Debug, Clone, Default)]
+pub struct RulePack {
+    /// The rules, sorted by name
+    pub rules: Vec<Rule>,
+}
+
+impl RulePack {
+    /// Creates a RulePack with no rules
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Reads the rules from a rule pack
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the rule pack
+    ///
+    /// # Returns
+    ///
+    /// The RulePack, or a message describing the first rule that is not
+    /// valid
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let config = Config::parse(text)?;
+        let mut rules = Vec::new();
+        for section in config.sections() {
+            let name = match section.strip_prefix("rules.") {
+                Some(x) => x,
+                None => return Err(format!("unknown section `{}`, expected `rules.<name>`", section)),
+            };
+            if let Some(key) = config.keys(section).find(|x| !["description", "severity", "match", "fix", "import", "matches", "ignores", "fixed", "language"].contains(x)) {
+                return Err(format!("rule `{}`: unknown setting `{}`", name, key));
+            }
+            let get = |key| config.get(section, key).and_then(|x| x.first()).cloned();
+            let find = get("match").ok_or_else(|| format!("rule `{}`: expected a `match`", name))?;
+            let fix = get("fix");
+            let list = |key| config.get(section, key).map(|x| x.to_vec()).unwrap_or_default();
+            let (matches, fixed) = (list("matches"), list("fixed"));
+            if !fixed.is_empty() && (fix.is_none() || fixed.len() != matches.len()) {
+                return Err(format!("rule `{}`: expected a `fix`, and as many `fixed` as `matches`", name));
+            }
+            let query = regexparser::parse_substitution(&find, fix.as_deref().unwrap_or_default()).map_err(|e| format!("rule `{}`: {}", name, e))?;
+            rules.push(Rule {
+                name: name.to_string(),
+                description: get("description").unwrap_or_default(),
+                severity: get("severity").map_or(Ok(Severity::Warning), |x| x.parse()).map_err(|e| format!("rule `{}`: {}", name, e))?,
+                query,
+                fixable: fix.is_some(),
+                imports: list("import"),
+                matches,
+                ignores: list("ignores"),
+                fixed,
+                language: get("language"),
+            });
+        }
+        rules.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Self { rules })
+    }
+
+    /// Reads the rule packs at `paths` into one, in which a rule replaces
+    /// any earlier rule with its name
+    pub fn load(paths: &[impl AsRef<Path>]) -> Result<Self, SpidiorError> {
+        let mut pack = Self::new();
+        for path in paths {
+            let path = path.as_ref();
+            let text = std::fs::read_to_string(path).map_err(|e| SpidiorError::io(format!("Could not read {}", path.display()), e))?;
+            let read = Self::parse(&text).map_err(|x| SpidiorError::parse(format!("{}: {}", path.display(), x)))?;
+            pack.rules.retain(|x| !read.rules.iter().any(|y| y.name == x.name));
+            pack.rules.extend(read.rules);
+        }
+        pack.rules.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(pack)
+    }
+
+    /// Works out the edits that fix every fixable rule a piece of code
+    /// breaks, combining them as the queries of a run are combined, along
+    /// with the imports those fixes need
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The code
+    /// * `parsed` - The parse results of `text`
+    /// * `lang` - The language of `text`, which writes the imports
+    /// * `policy` - What to do when fixes from different rules overlap
+    /// * `path` - The path of the file, for reports of overlapping fixes
+    ///
+    /// # Returns
+    ///
+    /// The edits, and a report of each overlap that was resolved
+    pub fn fix(&self, text: &str, parsed: &Parsed, lang: &dyn Language, policy: Conflict, path: &str) -> Result<(EditSet, Vec<String>), SpidiorError> {
+        let mut edits = EditSet::new();
+        let mut reports = Vec::new();
+        let mut needed = Vec::new();
+        for rule in &self.rules {
+            let fixes = rule.fix(text, parsed)?;
+            if !fixes.is_empty() {
+                needed.extend(rule.imports.iter().cloned());
+            }
+            reports.extend(edits.extend(fixes, policy, text, path)?);
+        }
+        let added = imports::add(text, &parsed.imports, lang, &needed)?.with_origin("imports");
+        reports.extend(edits.extend(added, policy, text, path)?);
+        Ok((edits, reports))
+    }
+}
+
+#[test]
+fn test_rule_pack() {
+    use crate::languages::clike::Clike;
+    let pack = RulePack::parse(
+        "[rules.session-name]\ndescription = \"Sessions are called `sess`\"\nmatch = \"[[type=Session]]\"\nfix = \"sess\"\n\n[rules.no-goto]\nseverity = \"error\"\nmatch = \"goto\"\n",
+    )
+    .unwrap();
+    assert_eq!(pack.rules.iter().map(|x| (x.name.as_str(), x.severity, x.fixable)).collect::<Vec<_>>(), vec![("no-goto", Severity::Error, false), ("session-name", Severity::Warning, true)]);
+    let text = "void f() { Session s; goto end; s.run(); }";
+    let parsed = Parsed::new(text, &Clike {});
+    assert_eq!(pack.rules[0].check(text, &parsed).len(), 1);
+    assert_eq!(pack.rules[1].check(text, &parsed).len(), 2);
+    let (edits, reports) = pack.fix(text, &parsed, &Clike {}, Conflict::Error, "x.c").unwrap();
+    assert!(reports.is_empty());
+    assert_eq!(edits.apply(text).unwrap(), "void f() { Session sess; goto end; sess.run(); }");
+    // Imports are added to the files a fix edits, and no others
+    let pack = RulePack::parse("[rules.client]\nmatch = \"OldClient\"\nfix = \"NewClient\"\nimport = [\"new.pkg.NewClient\"]\n").unwrap();
+    let text = "import java.util.List;\n\nclass A { OldClient c; }\n";
+    let (edits, _) = pack.fix(text, &Parsed::new(text, &Clike {}), &Clike {}, Conflict::Error, "A.java").unwrap();
+    assert_eq!(edits.apply(text).unwrap(), "import java.util.List;\nimport new.pkg.NewClient;\n\nclass A { NewClient c; }\n");
+    let text = "class B {}\n";
+    assert!(pack.fix(text, &Parsed::new(text, &Clike {}), &Clike {}, Conflict::Error, "B.java").unwrap().0.is_empty());
+    assert!(RulePack::parse("[rules.x]\nfix = \"y\"\n").is_err());
+    assert!(RulePack::parse("[rules.x]\nmatch = \"a\"\nseverity = \"fatal\"\n").is_err());
+    assert!(RulePack::parse("[languages.c]\nkeywords = []\n").is_err());
+    // A `/` in `match` or `fix` is part of it, and never starts the flags
+    let pack = RulePack::parse("[rules.path]\nmatch = \"a/b\"\nfix = \"x/i\"\n").unwrap();
+    let text = "a/b A/B";
+    let (edits, _) = pack.fix(text, &Parsed::new(text, &Clike {}), &Clike {}, Conflict::Error, "x.c").unwrap();
+    assert_eq!(edits.apply(text).unwrap(), "x/i A/B");
+}
+
+#[test]
+fn test_examples() -> Result<(), SpidiorError> {
+    use crate::languages::clike::Clike;
+    let pack = RulePack::parse(
+        "[rules.a]\nmatch = \"[[type=Session]]\"\nfix = \"sess\"\nmatches = [\"Session s;\", \"f(Session s, int t) {}\"]\nignores = [\"int s;\"]\nfixed = [\"Session sess;\", \"f(Session sess, int t) {}\"]\n",
+    )
+    .unwrap();
+    assert_eq!(pack.rules[0].examples(), 5);
+    assert!(pack.rules[0].test(&Clike {})?.is_empty());
+    let pack = RulePack::parse("[rules.b]\nmatch = \"goto\"\nmatches = [\"int x;\"]\nignores = [\"goto end;\"]\n").unwrap();
+    assert_eq!(pack.rules[0].test(&Clike {})?, vec!["expected a match in `int x;`", "expected no match in `goto end;`, found `goto`"]);
+    let pack = RulePack::parse("[rules.c]\nmatch = \"a\"\nfix = \"b\"\nmatches = [\"a\"]\nfixed = [\"c\"]\n").unwrap();
+    assert_eq!(pack.rules[0].test(&Clike {})?, vec!["expected `a` to be fixed to `c`, found `b`"]);
+    assert!(RulePack::parse("[rules.d]\nmatch = \"a\"\nmatches = [\"a\"]\nfixed = [\"b\"]\n").is_err());
+    Ok(())
+}
@@ -0,0 +1,352 @@
+//! Provides the reading of files to search, which with the `mmap` feature
+//! maps large files into memory rather than copying them into a `String`
+//!
+//! A file is only checked to be UTF-8 once its text is first asked for, and
+//! then in regions, on a thread for each available core, so a large file
+//! costs neither a copy nor a single-threaded pass before it is matched.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::error::SpidiorError;
+
+/// The size of file at and above which the `mmap` feature maps a file
+/// rather than reading it, below which reading it is faster
+pub const MMAP_LEN: u64 = 1 << 20;
+
+/// Where the bytes of a file are held
+enum Data {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+/// The contents of a file, which may not be UTF-8
+pub struct Source {
+    data: Data,
+    /// Whether the contents are UTF-8, once they have been checked
+    valid: OnceLock<bool>,
+}
+
+impl Source {
+    /// Holds contents that have already been read
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            data: Data::Owned(bytes),
+            valid: OnceLock::new(),
+        }
+    }
+
+    /// Opens a file, mapping it into memory if the `mmap` feature is on and
+    /// it is at least `MMAP_LEN` bytes, and otherwise, or if it cannot be
+    /// mapped, reading it
+    ///
+    /// A mapped file that another process changes while it is being
+    /// searched may give inconsistent matches. spidior's own writes replace
+    /// files rather than changing them, so they never do this.
+    pub fn open(path: &Path) -> Result<Self, SpidiorError> {
+        let failed = |e| SpidiorError::io(format!("Could not read {}", path.display()), e);
+        #[cfg(feature = "mmap")]
+        {
+            let file = std::fs::File::open(path).map_err(failed)?;
+            if file.metadata().map_err(failed)?.len() >= MMAP_LEN {
+                // Safety: the map is only read, and only as bytes until they
+                // have been checked to be UTF-8
+                if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+                    return Ok(Self {
+                        data: Data::Mapped(map),
+                        valid: OnceLock::new(),
+                    });
+                }
+            }
+        }
+        std::fs::read(path).map(Self::new).map_err(failed)
+    }
+
+    /// Whether the file was mapped into memory rather than read
+    pub fn is_mapped(&self) -> bool {
+        match self.data {
+            Data::Owned(_) => false,
+            #[cfg(feature = "mmap")]
+            Data::Mapped(_) => true,
+        }
+    }
+
+    /// The contents, as bytes
+    pub fn bytes(&self) -> &[u8] {
+        match &self.data {
+            Data::Owned(x) => x,
+            #[cfg(feature = "mmap")]
+            Data::Mapped(x) => x,
+        }
+    }
+
+    /// The contents, as text, or None if they are not UTF-8, which is only
+    /// checked the first time this is called
+    pub fn text(&self) -> Option<&str> {
+        let bytes = self.bytes();
+        if !*self.valid.get_or_init(|| validate(bytes)) {
+            return None;
+        }
+        // Safety: `validate` has checked that the bytes are UTF-8
+        Some(unsafe { std::str::from_utf8_unchecked(bytes) })
+    }
+
+    /// A region of the contents, as text, checking only that region
+    ///
+    /// # Returns
+    ///
+    /// The text between `start` and `end`, or None if the region is out of
+    /// bounds, starts or ends within a character, or is not UTF-8
+    pub fn region(&self, start: usize, end: usize) -> Option<&str> {
+        std::str::from_utf8(self.bytes().get(start..end)?).ok()
+    }
+}
+
+/// How many lines at the start of a file are searched for a marker saying
+/// it is generated
+pub const GENERATED_LINES: usize = 10;
+
+/// Markers that say a file is generated, as tools write them in a comment
+/// near its top
+const GENERATED_MARKERS: [&str; 2] = ["@generated", "DO NOT EDIT"];
+
+/// The length, in bytes, of a line at and above which a file holding little
+/// else is taken for minified
+pub const MINIFIED_LINE_LEN: usize = 1000;
+
+/// Why a file is taken to be generated, so that replacements leave it alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generated {
+    /// One of its first lines has a marker, like `@generated`
+    Marker(&'static str),
+    /// Most of it is on one very long line, like a minified bundle
+    Minified,
+}
+
+impl std::fmt::Display for Generated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Generated::Marker(x) => write!(f, "it is marked `{}`", x),
+            Generated::Minified => write!(f, "it looks minified"),
+        }
+    }
+}
+
+/// Tells whether a file looks generated, as a bundle or the output of a code
+/// generator, which are rebuilt from their sources, so should not be edited
+///
+/// A file is generated if one of its first `GENERATED_LINES` lines holds
+/// `@generated` or `DO NOT EDIT`, or minified if a line of at least
+/// `MINIFIED_LINE_LEN` bytes makes up more than half of it.
+///
+/// # Returns
+///
+/// Why the file looks generated, or None if it does not
+pub fn generated(text: &str) -> Option<Generated> {
+    let marker = text.lines().take(GENERATED_LINES).find_map(|line| GENERATED_MARKERS.iter().find(|x| line.contains(*x)));
+    if let Some(x) = marker {
+        return Some(Generated::Marker(x));
+    }
+    text.lines()
+        .any(|x| x.len() >= MINIFIED_LINE_LEN && x.len() * 2 > text.len())
+        .then_some(Generated::Minified)
+}
+
+/// What a file is, however it is reached: its device and inode on Unix,
+/// and its canonical path elsewhere
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    #[cfg(not(unix))]
+    Path(std::path::PathBuf),
+}
+
+impl FileId {
+    /// Finds what a file is, following any links to it, or None if it
+    /// cannot be found
+    fn of(path: &Path) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = std::fs::metadata(path).ok()?;
+            Some(FileId::Inode(metadata.dev(), metadata.ino()))
+        }
+        #[cfg(not(unix))]
+        {
+            std::fs::canonicalize(path).ok().map(FileId::Path)
+        }
+    }
+}
+
+/// The files a walk has visited, so that a file reached more than once,
+/// through hard links or through directories linked into the tree more than
+/// once, is only searched and edited the first time
+#[derive(Debug, Default)]
+pub struct Visited {
+    seen: HashSet<FileId>,
+}
+
+impl Visited {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tells whether a file is being visited for the first time, marking it
+    /// visited. A file that cannot be found is always new, so that reading
+    /// it reports why.
+    pub fn first(&mut self, path: &Path) -> bool {
+        FileId::of(path).is_none_or(|x| self.seen.insert(x))
+    }
+}
+
+/// Keeps one entry for each file a walk following links reaches, so it is
+/// searched and edited once: the first that reaches it without going
+/// through a link, if any does, and otherwise the first to reach it at all.
+/// A file is thus reported at where it is, rather than through a link to it,
+/// whenever the walk finds it there.
+///
+/// # Arguments
+///
+/// * `entries` - The entries of one or more walks, each in the order a walk
+///   gives them, with every directory before what is within it
+///
+/// # Returns
+///
+/// The entries, in their order, without those reaching a file kept through
+/// another entry
+#[cfg(feature = "fs")]
+pub fn once_each(entries: impl IntoIterator<Item = walkdir::DirEntry>) -> Vec<walkdir::DirEntry> {
+    use std::collections::HashMap;
+    let entries: Vec<walkdir::DirEntry> = entries.into_iter().collect();
+    // Whether the entry at each depth above the current one is, or is
+    // within, a link
+    let mut linked: Vec<bool> = Vec::new();
+    let mut ids = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        linked.truncate(entry.depth());
+        let through = entry.path_is_symlink() || linked.last().copied().unwrap_or(false);
+        linked.push(through);
+        ids.push(Some(entry).filter(|x| x.file_type().is_file()).and_then(|x| FileId::of(x.path())).map(|x| (x, through)));
+    }
+    let mut kept: HashMap<&FileId, (usize, bool)> = HashMap::new();
+    for (i, (id, through)) in ids.iter().enumerate().filter_map(|(i, x)| Some((i, x.as_ref()?))) {
+        let first = kept.entry(id).or_insert((i, *through));
+        if first.1 && !through {
+            *first = (i, false);
+        }
+    }
+    let keep: HashSet<usize> = kept.values().map(|x| x.0).collect();
+    entries.into_iter().zip(&ids).enumerate().filter(|(i, (_, id))| id.is_none() || keep.contains(i)).map(|(_, (x, _))| x).collect()
+}
+
+/// Checks that bytes are UTF-8, checking a region for each available core
+/// on its own thread, each of which starts at the start of a character
+fn validate(bytes: &[u8]) -> bool {
+    let threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+    if threads == 1 || bytes.len() < MMAP_LEN as usize {
+        return std::str::from_utf8(bytes).is_ok();
+    }
+    let size = bytes.len().div_ceil(threads);
+    let mut bounds = vec![0];
+    while let Some(&last) = bounds.last() {
+        if last >= bytes.len() {
+            break;
+        }
+        let mut next = (last + size).min(bytes.len());
+        // Move past continuation bytes, which have `10` as their top bits,
+        // of which a character has at most three
+        for _ in 0..3 {
+            if next < bytes.len() && bytes[next] & 0xC0 == 0x80 {
+                next += 1;
+            }
+        }
+        bounds.push(next);
+    }
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = bounds.windows(2).map(|w| (w[0], w[1])).map(|(from, to)| scope.spawn(move || std::str::from_utf8(&bytes[from..to]).is_ok())).collect();
+        handles.into_iter().all(|x| x.join().unwrap())
+    })
+}
+
+#[test]
+fn test_source() {
+    let dir = std::env::temp_dir().join(format!("spidior-source-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let text = "int é = 0;\n".repeat(MMAP_LEN as usize / 8);
+    std::fs::write(dir.join("big.c"), &text).unwrap();
+    std::fs::write(dir.join("small.c"), "int x;").unwrap();
+    let big = Source::open(&dir.join("big.c")).unwrap();
+    assert_eq!(big.is_mapped(), cfg!(feature = "mmap"));
+    assert_eq!(big.text(), Some(text.as_str()));
+    assert_eq!(big.region(4, 6), Some("é"));
+    assert_eq!(big.region(4, 5), None);
+    assert!(!Source::open(&dir.join("small.c")).unwrap().is_mapped());
+    assert!(Source::open(&dir.join("missing.c")).is_err());
+    let mut bytes = text.into_bytes();
+    let last = bytes.len() - 2;
+    bytes[last] = 0xFF;
+    assert!(Source::new(bytes).text().is_none());
+    assert!(validate("é".repeat(MMAP_LEN as usize).as_bytes()));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_generated() {
+    assert_eq!(generated("// Code generated by protoc-gen-go. DO NOT EDIT.\npackage pb\n"), Some(Generated::Marker("DO NOT EDIT")));
+    assert_eq!(generated("/**\n * @generated\n */\nvar a;\n"), Some(Generated::Marker("@generated")));
+    assert_eq!(generated(&format!("{}\n// @generated", "\n".repeat(GENERATED_LINES))), None);
+    let bundle = format!("/*! v1 */\n{}\n", "var a=1;".repeat(200));
+    assert_eq!(generated(&bundle), Some(Generated::Minified));
+    let long = format!("{}\n{}", "x".repeat(MINIFIED_LINE_LEN), "int a;\n".repeat(200));
+    assert_eq!(generated(&long), None);
+    assert_eq!(Generated::Minified.to_string(), "it looks minified");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_visited() {
+    let dir = std::env::temp_dir().join(format!("spidior-visited-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.c"), "int x;").unwrap();
+    std::fs::write(dir.join("b.c"), "int x;").unwrap();
+    std::fs::hard_link(dir.join("a.c"), dir.join("sub").join("link.c")).unwrap();
+    std::os::unix::fs::symlink(dir.join("sub"), dir.join("alias")).unwrap();
+    let mut visited = Visited::new();
+    assert!(visited.first(&dir.join("a.c")));
+    assert!(visited.first(&dir.join("b.c")));
+    assert!(!visited.first(&dir.join("sub").join("link.c")));
+    assert!(!visited.first(&dir.join("alias").join("link.c")));
+    assert!(!visited.first(&dir.join("sub").join("..").join("a.c")));
+    assert!(visited.first(&dir.join("missing.c")));
+    assert!(visited.first(&dir.join("missing.c")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(all(unix, feature = "fs"))]
+#[test]
+fn test_once_each() {
+    let dir = std::env::temp_dir().join(format!("spidior-once-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("z")).unwrap();
+    std::fs::write(dir.join("z").join("x.c"), "int x;").unwrap();
+    std::fs::write(dir.join("out.c"), "int y;").unwrap();
+    // `a` is walked before `z`, but only links to it
+    std::os::unix::fs::symlink(dir.join("z"), dir.join("a")).unwrap();
+    std::os::unix::fs::symlink(dir.join("out.c"), dir.join("b.c")).unwrap();
+    let walk = walkdir::WalkDir::new(&dir).follow_links(true).sort_by(|a, b| a.file_name().cmp(b.file_name()));
+    let files: Vec<_> = once_each(walk.into_iter().filter_map(|x| x.ok()))
+        .into_iter()
+        .filter(|x| x.file_type().is_file())
+        .map(|x| x.path().strip_prefix(&dir).unwrap().to_path_buf())
+        .collect();
+    assert_eq!(files, vec![Path::new("out.c").to_path_buf(), Path::new("z/x.c").to_path_buf()]);
+    // A file only reached through a link is kept at that link
+    let walk = walkdir::WalkDir::new(dir.join("a")).follow_links(true);
+    let files: Vec<_> = once_each(walk.into_iter().filter_map(|x| x.ok())).into_iter().filter(|x| x.file_type().is_file()).map(|x| x.into_path()).collect();
+    assert_eq!(files, vec![dir.join("a").join("x.c")]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,387 @@
+//! This module is for comby-style structural matching, where a template
+//! such as `if (:[cond]) { :[body] }` is matched against code by shape.
+//!
+//! Holes (`:[name]`) match balanced regions of code: a hole never ends
+//! inside a string literal or comment, as the language of the code finds
+//! them, and never swallows an unmatched closing bracket. Whitespace in a
+//! template matches any run of whitespace (including none) in the input.
+
+use std::collections::HashMap;
+
+use crate::editing::editset::EditSet;
+use crate::error::SpidiorError;
+use crate::languages::lexer::{Span, SpanKind};
+use crate::languages::lines::locate;
+use crate::languages::Language;
+use crate::matcher::Match;
+
+/// A piece of a parsed structural template
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Text that must appear verbatim
+    Literal(String),
+    /// A run of whitespace, which matches any amount of whitespace
+    Space,
+    /// A named hole, matching a balanced region of code
+    Hole(String),
+}
+
+/// A parsed structural template
+#[derive(Debug, Clone)]
+pub struct Template {
+    tokens: Vec<Token>,
+}
+
+/// A single match of a structural template against some text
+#[derive(Debug, Clone)]
+pub struct StructuralMatch {
+    start: usize,
+    len: usize,
+    holes: HashMap<String, (usize, usize)>,
+}
+
+impl StructuralMatch {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the text bound to the hole `name` within `s`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the hole
+    /// * `s` - The text that was matched against
+    ///
+    /// # Returns
+    ///
+    /// The bound text, or an empty string if there is no such hole
+    pub fn get_hole(&self, name: &str, s: &str) -> String {
+        if let Some((start, len)) = self.holes.get(name) {
+            s[*start..*start + *len].to_string()
+        } else {
+            "".to_string()
+        }
+    }
+}
+
+impl Template {
+    /// Parses a structural template, splitting it into literals,
+    /// whitespace, and `:[name]` holes.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that contains the template
+    ///
+    /// # Returns
+    ///
+    /// A Result<Template, SpidiorError>, which will Err if a hole is
+    /// left unterminated or two holes are adjacent (which would be ambiguous).
+    pub fn parse(text: &str) -> Result<Self, SpidiorError> {
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with(":[") {
+                let end = rest.find(']').ok_or_else(|| SpidiorError::parse("Unterminated hole in template"))?;
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(literal.clone()));
+                    literal.clear();
+                }
+                if let Some(Token::Hole(_)) = tokens.last() {
+                    return Err(SpidiorError::parse("Adjacent holes in template are ambiguous"));
+                }
+                tokens.push(Token::Hole(rest[2..end].to_string()));
+                rest = &rest[end + 1..];
+            } else if c.is_whitespace() {
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(literal.clone()));
+                    literal.clear();
+                }
+                if tokens.last() != Some(&Token::Space) {
+                    tokens.push(Token::Space);
+                }
+                rest = rest.trim_start();
+            } else {
+                literal.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+        Ok(Self { tokens })
+    }
+
+    /// Finds all non-overlapping matches of the template within `input`
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The text to search
+    /// * `lang` - The language of `input`, whose comments and literals holes
+    ///   take whole
+    ///
+    /// # Returns
+    ///
+    /// A Vec of StructuralMatch, in order of where they start
+    pub fn find(&self, input: &str, lang: &dyn Language) -> Vec<StructuralMatch> {
+        let code = Code::new(input, lang);
+        let mut v = Vec::new();
+        let mut bound = Vec::new();
+        let mut is = 0;
+        while is < input.len() {
+            bound.clear();
+            match self.match_at(0, &code, is, &mut bound) {
+                Some(end) if end > is => {
+                    v.push(StructuralMatch {
+                        start: is,
+                        len: end - is,
+                        holes: bound.iter().map(|(name, start, len)| (name.to_string(), (*start, *len))).collect(),
+                    });
+                    is = end;
+                }
+                _ => {
+                    is = code.skip(is);
+                }
+            }
+        }
+        v
+    }
+
+    /// Matches the tokens from `ti` on at `pos`, pushing each hole bound
+    /// onto `bound`, which is left as it was if they do not match, so that
+    /// backtracking undoes a binding by popping it
+    fn match_at<'t>(
+        &'t self,
+        ti: usize,
+        code: &Code,
+        pos: usize,
+        bound: &mut Vec<(&'t str, usize, usize)>,
+    ) -> Option<usize> {
+        let s = code.s;
+        let token = match self.tokens.get(ti) {
+            Some(t) => t,
+            None => return Some(pos),
+        };
+        match token {
+            Token::Literal(l) => {
+                if s[pos..].starts_with(l.as_str()) {
+                    self.match_at(ti + 1, code, pos + l.len(), bound)
+                } else {
+                    None
+                }
+            }
+            Token::Space => {
+                let trimmed = s[pos..].trim_start();
+                self.match_at(ti + 1, code, s.len() - trimmed.len(), bound)
+            }
+            Token::Hole(name) => {
+                let last = ti + 1 == self.tokens.len();
+                let mut end = pos;
+                loop {
+                    if !last {
+                        bound.push((name, pos, end - pos));
+                        if let Some(x) = self.match_at(ti + 1, code, end, bound) {
+                            return Some(x);
+                        }
+                        bound.pop();
+                    }
+                    match code.unit(end) {
+                        Some(next) => end = next,
+                        None => break,
+                    }
+                }
+                if last {
+                    let trimmed = s[pos..end].trim_end();
+                    bound.push((name, pos, trimmed.len()));
+                    Some(pos + trimmed.len())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// The text a template is matched against, with the comments and literals
+/// its language finds in it, each of which a hole takes as a whole
+struct Code<'a> {
+    s: &'a str,
+    /// The comments, strings, and character literals of `s`, in order of
+    /// where they start
+    spans: Vec<Span>,
+}
+
+impl<'a> Code<'a> {
+    fn new(s: &'a str, lang: &dyn Language) -> Self {
+        let spans = lang
+            .spans(s)
+            .into_iter()
+            .filter(|x| matches!(x.kind, SpanKind::Comment | SpanKind::String | SpanKind::Char))
+            .collect();
+        Self { s, spans }
+    }
+
+    /// Returns the index just past the comment or literal starting at `pos`,
+    /// if one does
+    fn literal(&self, pos: usize) -> Option<usize> {
+        let i = self.spans.partition_point(|x| x.start < pos);
+        self.spans.get(i).filter(|x| x.start == pos && x.end > pos).map(|x| x.end)
+    }
+
+    /// Returns the index just past the balanced unit of code starting at
+    /// `pos`, or None if `pos` is the end of the input or an unmatched
+    /// closing bracket.
+    fn unit(&self, pos: usize) -> Option<usize> {
+        let s = self.s;
+        let c = s[pos..].chars().next()?;
+        if let Some(end) = self.literal(pos) {
+            return Some(end);
+        }
+        match c {
+            ')' | ']' | '}' => None,
+            '(' | '[' | '{' => {
+                let close = match c {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                };
+                let mut i = pos + 1;
+                while i < s.len() {
+                    if s[i..].starts_with(close) {
+                        return Some(i + 1);
+                    }
+                    i = self.unit(i)?;
+                }
+                None
+            }
+            _ => Some(pos + c.len_utf8()),
+        }
+    }
+
+    /// Advances past the character at `pos`, or past the whole comment or
+    /// literal starting there, so that matches never begin inside one.
+    fn skip(&self, pos: usize) -> usize {
+        match self.literal(pos) {
+            Some(end) => end,
+            None => pos + self.s[pos..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+}
+
+/// Fills the holes of a rewrite template with the text bound by a match
+///
+/// # Arguments
+///
+/// * `rewrite` - The rewrite template, which may reference holes as `:[name]`
+/// * `m` - The match whose holes we are substituting
+/// * `s` - The text that was matched against
+///
+/// # Returns
+///
+/// The rewritten text
+pub fn rewrite_to_string(rewrite: &str, m: &StructuralMatch, s: &str) -> String {
+    let mut ret = String::new();
+    let mut rest = rewrite;
+    while let Some(i) = rest.find(":[") {
+        ret += &rest[..i];
+        if let Some(j) = rest[i..].find(']') {
+            ret += &m.get_hole(&rest[i + 2..i + j], s);
+            rest = &rest[i + j + 1..];
+        } else {
+            ret += &rest[i..];
+            rest = "";
+        }
+    }
+    ret += rest;
+    ret
+}
+
+/// Finds the edits that rewriting every match of `template` within `input`
+/// using `rewrite` makes
+///
+/// # Arguments
+///
+/// * `input` - The text to rewrite
+/// * `template` - The structural template to match
+/// * `rewrite` - The rewrite template, which may reference holes as `:[name]`
+/// * `lang` - The language of `input`
+/// * `acceptor` - Decides whether each individual rewrite is performed
+///
+/// # Returns
+///
+/// A Result<EditSet, SpidiorError>, where on success, it returns an edit
+/// for each accepted rewrite, which can be applied to `input` to rewrite it.
+pub fn edits(
+    input: &str,
+    template: &Template,
+    rewrite: &str,
+    lang: &dyn Language,
+    acceptor: crate::nfa::replacer::Acceptor,
+) -> Result<EditSet, SpidiorError> {
+    let mut edits = EditSet::new();
+    let found = template.find(input, lang);
+    for (m, whole) in found.iter().zip(as_matches(input, &found)) {
+        let r = rewrite_to_string(rewrite, m, input);
+        if acceptor(&whole, &r) {
+            edits.add(m.start(), m.start() + m.len(), r)?;
+        }
+    }
+    Ok(edits)
+}
+
+/// Makes a `Match`, as queries find, of each structural match, so that they
+/// can be printed and accepted the same way, without their holes
+pub fn as_matches(input: &str, found: &[StructuralMatch]) -> Vec<Match> {
+    locate(input, found.iter().map(|m| Match::new(m.start, m.len, Vec::new())).collect())
+}
+
+#[test]
+fn test_structural_find() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::languages::clike::Clike;
+    let t = Template::parse("if (:[cond]) { :[body] }")?;
+    let s = "x(); if (a && (b || c)) { foo(\"}\"); { bar(); } } y();";
+    let found = t.find(s, &Clike {});
+    assert_eq!(found.len(), 1);
+    let m = found.first().unwrap();
+    assert_eq!(m.get_hole("cond", s), "a && (b || c)");
+    assert_eq!(m.get_hole("body", s), "foo(\"}\"); { bar(); }");
+    assert_eq!(&s[m.start()..m.start() + m.len()], "if (a && (b || c)) { foo(\"}\"); { bar(); } }");
+    assert!(Template::parse("f(:[a]:[b])").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_structural_replace() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::languages::clike::Clike;
+    let t = Template::parse("assertEquals(:[a], :[b])")?;
+    let s = "assertEquals(f(1, 2), x); // assertEquals(c, d)\nassertEquals(\"a,b\", y);";
+    assert_eq!(
+        edits(s, &t, "assertEquals(:[b], :[a])", &Clike {}, |_, _| true)?.apply(s)?,
+        "assertEquals(x, f(1, 2)); // assertEquals(c, d)\nassertEquals(y, \"a,b\");"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_structural_languages() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::languages::{rust::Rust, shell::Shell};
+    // A `#` comment is whole in shell, where `//` starts nothing
+    let t = Template::parse("echo :[a];")?;
+    let s = "echo 'a;b' # c;d\necho x//y;";
+    let found = t.find(s, &Shell {});
+    assert_eq!(found.iter().map(|m| m.get_hole("a", s)).collect::<Vec<_>>(), vec!["'a;b' # c;d\necho x//y"]);
+    // A lifetime is not the start of a character literal
+    let t = Template::parse("f(:[a], :[b])")?;
+    let s = "f(&'a x, y); g('(');";
+    let found = t.find(s, &Rust {});
+    assert_eq!((found[0].get_hole("a", s), found[0].get_hole("b", s)), ("&'a x".to_string(), "y".to_string()));
+    Ok(())
+}
@@ -0,0 +1,264 @@
+//! Provides listings of what the language layer finds in a file - its
+//! functions, imports, type aliases, and identifiers, with their types,
+//! spans, and scopes - for `spidior symbols` and `--dump`
+//!
+//! A listing is either a table, with a row for each symbol in the order it
+//! appears, or a JSON object, with a member for each kind of symbol and one
+//! for the file's scopes, so that other tools can read it, or the rows of
+//! the table as CSV or TSV, with the file's path on each, for spreadsheets.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::csv::Delimited;
+use crate::json::Value;
+use crate::languages::cache::Parsed;
+use crate::languages::lines::LineIndex;
+
+/// How `spidior symbols` lists what it finds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Listing {
+    /// A table, with a row for each symbol
+    Table,
+    /// A JSON object for each file, on a line of its own
+    Json,
+    /// Comma-separated values, with a header row and then a row for each
+    /// symbol
+    Csv,
+    /// Tab-separated values, laid out as `Csv` is
+    Tsv,
+}
+
+impl Listing {
+    /// The kind of delimited text the listing is, if it is one
+    pub fn delimited(&self) -> Option<Delimited> {
+        match self {
+            Listing::Csv => Some(Delimited::Csv),
+            Listing::Tsv => Some(Delimited::Tsv),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Listing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Listing::Table),
+            "json" => Ok(Listing::Json),
+            "csv" => Ok(Listing::Csv),
+            "tsv" => Ok(Listing::Tsv),
+            _ => Err(format!("Unknown format `{}`, expected `table`, `json`, `csv`, or `tsv`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Listing::Table => write!(f, "table"),
+            Listing::Json => write!(f, "json"),
+            Listing::Csv => write!(f, "csv"),
+            Listing::Tsv => write!(f, "tsv"),
+        }
+    }
+}
+
+/// A row of a table listing
+struct Row {
+    kind: &'static str,
+    name: String,
+    typ: String,
+    start: usize,
+    end: usize,
+    scope: usize,
+}
+
+/// The rows of a table listing, ordered by where each symbol starts. A
+/// function's scope is the one it opens, which starts at its signature, so
+/// that it can be given to a `scope=` criterion, while every other symbol's
+/// is the innermost scope it is within.
+fn rows(parsed: &Parsed) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for f in &parsed.functions {
+        let params: Vec<String> = f.params.iter().map(|x| if x.typ.is_empty() { x.name.clone() } else { format!("{} {}", x.typ, x.name) }).collect();
+        rows.push(Row {
+            kind: "function",
+            name: format!("{}({})", f.name, params.join(", ")),
+            typ: f.typ.clone(),
+            start: f.start,
+            end: f.end,
+            scope: parsed.scopes.innermost(f.start),
+        });
+    }
+    for i in &parsed.imports {
+        rows.push(Row {
+            kind: "import",
+            name: i.alias.clone().unwrap_or_default(),
+            typ: i.path.clone(),
+            start: i.start,
+            end: i.end,
+            scope: parsed.scopes.innermost(i.start),
+        });
+    }
+    for (kind, idents) in [("alias", &parsed.aliases), ("identifier", &parsed.identifiers)] {
+        rows.extend(idents.iter().map(|x| Row {
+            kind,
+            name: x.name.clone(),
+            typ: x.typ.clone(),
+            start: x.start,
+            end: x.end,
+            scope: x.scope_id,
+        }));
+    }
+    rows.sort_by_key(|x| x.start);
+    rows
+}
+
+/// Lists the symbols of a file as a table, with a column each for their
+/// kind, name, type, the line and column they start at, their byte span,
+/// and their scope
+///
+/// # Arguments
+///
+/// * `text` - The file's contents
+/// * `parsed` - The parse results of `text`
+///
+/// # Returns
+///
+/// The table, with a header row, and a line for each symbol
+pub fn table(text: &str, parsed: &Parsed) -> String {
+    let index = LineIndex::new(text);
+    let mut lines = vec![["KIND".to_string(), "NAME".into(), "TYPE".into(), "LINE:COL".into(), "SPAN".into(), "SCOPE".into()]];
+    for row in rows(parsed) {
+        let (line, column) = index.position(row.start);
+        lines.push([row.kind.to_string(), row.name, row.typ, format!("{}:{}", line, column), format!("{}..{}", row.start, row.end), row.scope.to_string()]);
+    }
+    let mut widths = [0; 6];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for line in lines {
+        let cells: Vec<String> = line.iter().zip(widths).map(|(cell, width)| format!("{:width$}", cell, width = width)).collect();
+        out += cells.join("  ").trim_end();
+        out += "\n";
+    }
+    out
+}
+
+/// The header row of a delimited listing
+pub const COLUMNS: [&str; 9] = ["path", "kind", "name", "type", "line", "column", "start", "end", "scope"];
+
+/// Lists the symbols of a file as the rows of a delimited listing, under
+/// `COLUMNS`, with the same rows as `table`
+///
+/// # Arguments
+///
+/// * `path` - The file's path, as it should be reported
+/// * `text` - The file's contents
+/// * `parsed` - The parse results of `text`
+/// * `delimited` - Whether the rows are CSV or TSV
+///
+/// # Returns
+///
+/// The rows, each ending with a line break
+pub fn delimited(path: &str, text: &str, parsed: &Parsed, delimited: Delimited) -> String {
+    let index = LineIndex::new(text);
+    let mut out = String::new();
+    for row in rows(parsed) {
+        let (line, column) = index.position(row.start);
+        out += &delimited.row(&[path.to_string(), row.kind.to_string(), row.name, row.typ, line.to_string(), column.to_string(), row.start.to_string(), row.end.to_string(), row.scope.to_string()]);
+        out += "\n";
+    }
+    out
+}
+
+/// Lists the symbols of a file as a JSON object, with `path`, `language`,
+/// `functions`, `imports`, `aliases`, `identifiers`, and `scopes` members
+///
+/// # Arguments
+///
+/// * `path` - The file's path, as it should be reported
+/// * `lang` - The name of the language the file was parsed as
+/// * `text` - The file's contents
+/// * `parsed` - The parse results of `text`
+pub fn json(path: &str, lang: &str, text: &str, parsed: &Parsed) -> Value {
+    let index = LineIndex::new(text);
+    let string = |x: &str| Value::String(x.to_string());
+    let number = |x: usize| Value::Number(x as i64);
+    let span = |start: usize, end: usize| {
+        let (line, column) = index.position(start);
+        vec![("start".to_string(), number(start)), ("end".to_string(), number(end)), ("line".to_string(), number(line)), ("column".to_string(), number(column))]
+    };
+    let object = |members: Vec<(&str, Value)>, start: usize, end: usize, scope: usize| {
+        let mut members: Vec<(String, Value)> = members.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        members.extend(span(start, end));
+        members.push(("scope".to_string(), number(scope)));
+        Value::Object(members)
+    };
+    let functions = parsed
+        .functions
+        .iter()
+        .map(|f| {
+            let params = f.params.iter().map(|x| Value::Object(vec![("name".to_string(), string(&x.name)), ("type".to_string(), string(&x.typ))])).collect();
+            object(vec![("name", string(&f.name)), ("type", string(&f.typ)), ("params", Value::Array(params)), ("body", number(f.body))], f.start, f.end, parsed.scopes.innermost(f.start))
+        })
+        .collect();
+    let imports = parsed
+        .imports
+        .iter()
+        .map(|i| object(vec![("path", string(&i.path)), ("alias", i.alias.as_deref().map_or(Value::Null, string))], i.start, i.end, parsed.scopes.innermost(i.start)))
+        .collect();
+    let idents = |idents: &[crate::languages::parsing::Identifier]| {
+        Value::Array(idents.iter().map(|x| object(vec![("name", string(&x.name)), ("type", string(&x.typ))], x.start, x.end, x.scope_id)).collect())
+    };
+    let scopes = parsed
+        .scopes
+        .iter()
+        .map(|x| Value::Object(vec![("id".to_string(), number(x.id)), ("parent".to_string(), x.parent.map_or(Value::Null, number)), ("start".to_string(), number(x.start)), ("end".to_string(), number(x.end))]))
+        .collect();
+    Value::Object(vec![
+        ("path".to_string(), string(path)),
+        ("language".to_string(), string(lang)),
+        ("functions".to_string(), Value::Array(functions)),
+        ("imports".to_string(), Value::Array(imports)),
+        ("aliases".to_string(), idents(&parsed.aliases)),
+        ("identifiers".to_string(), idents(&parsed.identifiers)),
+        ("scopes".to_string(), Value::Array(scopes)),
+    ])
+}
+
+#[test]
+fn test_symbols() {
+    use crate::languages::clike::Clike;
+    let text = "int count;\nint add(int a) {\n    return a + count;\n}\n";
+    let parsed = Parsed::new(text, &Clike {});
+    assert_eq!(
+        table(text, &parsed),
+        "KIND        NAME        TYPE  LINE:COL  SPAN    SCOPE
+identifier  count       int   1:5       4..9    0
+function    add(int a)  int   2:1       11..51  1
+identifier  add         int   2:5       15..18  1
+identifier  a           int   2:13      23..24  1
+identifier  a           int   3:12      39..40  1
+identifier  count       int   3:16      43..48  1
+"
+    );
+    let json = json("a.c", "clike", text, &parsed);
+    let functions = json.get("functions").and_then(Value::as_array).unwrap();
+    assert_eq!(functions[0].get("name").and_then(Value::as_str), Some("add"));
+    assert_eq!(functions[0].get("params").and_then(Value::as_array).map(|x| x.len()), Some(1));
+    assert_eq!(functions[0].get("line").and_then(Value::as_usize), Some(2));
+    let identifiers = json.get("identifiers").and_then(Value::as_array).unwrap();
+    assert_eq!(identifiers.len(), 5);
+    assert_eq!(identifiers[4].get("scope").and_then(Value::as_usize), Some(1));
+    assert_eq!(json.get("scopes").and_then(Value::as_array).map(|x| x.len()), Some(2));
+    assert_eq!(crate::json::parse(&json.to_string()).unwrap(), json);
+    let csv = delimited("a.c", text, &parsed, Delimited::Csv);
+    assert_eq!(csv.lines().nth(1), Some("a.c,function,add(int a),int,2,1,11,51,1"));
+    assert_eq!(csv.lines().count(), 6);
+}
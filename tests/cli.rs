@@ -0,0 +1,215 @@
+//! Runs the `spidior` binary over files written to a temporary directory, to
+//! check what it prints and changes as a whole
+
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Creates an empty directory for one test, named after it
+///
+/// # Arguments
+///
+/// * `name` - The name of the test, which keeps tests run in parallel apart
+///
+/// # Returns
+///
+/// The path of the directory, which is removed first if an earlier run left it
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("spidior-test-cli-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs `spidior` in `dir` with `args`, without reading or writing the parse
+/// cache, and with stdin closed
+fn spidior(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_spidior")).current_dir(dir).arg("--no-cache").args(args).stdin(Stdio::null()).output().unwrap()
+}
+
+/// Runs `spidior` in `dir` with `args`, as `spidior` does, writing `input`
+/// to its stdin
+fn spidior_with_input(dir: &Path, args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_spidior"))
+        .current_dir(dir)
+        .arg("--no-cache")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// What a run printed to stdout and stderr
+fn printed(output: &Output) -> (String, String) {
+    (String::from_utf8_lossy(&output.stdout).into_owned(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+#[test]
+fn test_parser_strictness() {
+    let dir = scratch("strictness");
+    std::fs::write(dir.join("a.c"), "int f() {\n    int a = 1;\n}\n}\n").unwrap();
+    let output = spidior(&dir, &["-p", "a.c", "-q", "%s/a/b/g", "--parser-strictness", "strict"]);
+    let (stdout, stderr) = printed(&output);
+    assert!(!output.status.success());
+    assert!(stderr.contains("a.c:4:1: error: `}` closes no block"), "{}", stderr);
+    assert!(stderr.contains("1 file could not be parsed reliably"), "{}", stderr);
+    assert!(!stdout.contains("int b"), "{}", stdout);
+    let output = spidior(&dir, &["-p", "a.c", "-q", "%s/a/b/g"]);
+    assert!(output.status.success());
+    assert!(printed(&output).0.contains("int b = 1;"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_reports_left_alone() {
+    let dir = scratch("reports");
+    std::fs::write(dir.join("a.c"), "int foo = 1;\n").unwrap();
+    let runs = dir.join(".spidior").join("runs");
+    let reports = || -> Vec<String> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&runs).unwrap().map(|x| x.unwrap().path()).collect();
+        paths.sort();
+        paths.iter().map(|x| std::fs::read_to_string(x).unwrap()).collect()
+    };
+    assert!(spidior(&dir, &["-r", "-i", "-q", "%s/foo/bar/g"]).status.success());
+    let first = reports();
+    assert_eq!(first.len(), 1);
+    assert!(first[0].contains("%s/foo/bar/g"));
+    // The report holds `foo`, but the second run never looks in it
+    assert!(spidior(&dir, &["-r", "-i", "-q", "%s/foo/bar/g"]).status.success());
+    assert_eq!(reports()[0], first[0]);
+    assert_eq!(std::fs::read_to_string(dir.join("a.c")).unwrap(), "int bar = 1;\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_match_one() {
+    let dir = scratch("match-one");
+    let output = spidior_with_input(&dir, &["match-one", "a"], "a\nb\nA\n");
+    assert!(output.status.success());
+    assert_eq!(printed(&output).0, "a\n");
+    // A `/` in the replacement is part of it, and not the start of flags
+    let output = spidior_with_input(&dir, &["match-one", "a", "--replace", "c/d"], "a\nb\n");
+    assert_eq!(printed(&output).0, "c/d\n");
+    let output = spidior_with_input(&dir, &["match-one", "a", "--replace", "x/i"], "a\nA\n");
+    assert_eq!(printed(&output).0, "x/i\n");
+    let output = spidior_with_input(&dir, &["match-one", "a/b", "--replace", "c", "--null"], "a/b\0b\0");
+    assert_eq!(printed(&output).0, "c\0");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_collisions_print_nothing() {
+    let dir = scratch("collisions");
+    std::fs::write(dir.join("a.c"), "void f() { int count; int total; count++; }\n").unwrap();
+    std::fs::write(dir.join("b.c"), "void g() { int count; }\n").unwrap();
+    let output = spidior(&dir, &["-r", "--check-collisions", "-q", "%s/[[name=count]]/total/g"]);
+    let (stdout, stderr) = printed(&output);
+    assert!(!output.status.success());
+    assert!(stderr.contains("2 rename collisions found, so no files were changed"), "{}", stderr);
+    // Neither file is printed, not even the one without a collision
+    assert!(!stdout.contains("int"), "{}", stdout);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_preview() {
+    let dir = scratch("preview");
+    let text = "int count;\nvoid f(int a) { double count = a; }\n";
+    std::fs::write(dir.join("s.c"), text).unwrap();
+    let output = spidior(&dir, &["-p", "s.c", "--format", "vimgrep", "--preview", "-q", "%s/[[name=count]]/total/g"]);
+    assert!(output.status.success());
+    assert_eq!(printed(&output).0, "s.c:1:5:int [count → total];\ns.c:2:24:void f(int a) { double [count → total] = a; }\n");
+    assert_eq!(std::fs::read_to_string(dir.join("s.c")).unwrap(), text);
+    // Backreferences are filled in from each match
+    let output = spidior(&dir, &["-p", "s.c", "--format", "emacs", "--preview", "-q", "%s/(d)ouble/\\1x/g"]);
+    assert_eq!(printed(&output).0, "s.c:2:17: void f(int a) { [double → dx] count = a; }\n");
+    let output = spidior(&dir, &["-p", "s.c", "--preview", "-q", "%s/count/total/g"]);
+    assert!(!output.status.success());
+    assert!(printed(&output).1.contains("so --preview needs --format vimgrep"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_max_columns() {
+    let dir = scratch("max-columns");
+    std::fs::write(dir.join("min.js"), format!("{}count{}\n", "a".repeat(40), "b".repeat(40))).unwrap();
+    // Output to a pipe is whole and uncolored, even without NO_COLOR
+    let output = Command::new(env!("CARGO_BIN_EXE_spidior"))
+        .current_dir(&dir)
+        .env_remove("NO_COLOR")
+        .args(["--no-cache", "-p", "min.js", "--format", "vimgrep", "-q", "%s/count//g"])
+        .output()
+        .unwrap();
+    assert_eq!(printed(&output).0, format!("min.js:1:41:{}count{}\n", "a".repeat(40), "b".repeat(40)));
+    let output = spidior(&dir, &["-p", "min.js", "--format", "vimgrep", "--max-columns", "35", "-q", "%s/count//g"]);
+    assert_eq!(printed(&output).0, "min.js:1:41:…aaaaaaaacountbbbbbbbb…\n");
+    let output = spidior(&dir, &["-p", "min.js", "--format", "vimgrep", "--max-columns", "0", "-q", "%s/count//g"]);
+    assert_eq!(printed(&output).0.len(), "min.js:1:41:".len() + 86);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_verbose() {
+    let dir = scratch("verbose");
+    std::fs::write(dir.join("a.c"), "int foo = 1;\n").unwrap();
+    std::fs::write(dir.join("notes.md"), "plain words\n").unwrap();
+    let output = spidior(&dir, &["-p", "a.c", "-q", "%s/foo/bar/g"]);
+    let (stdout, stderr) = printed(&output);
+    // Without --verbose, only the changed file is printed
+    assert_eq!(stdout, "int bar = 1;\n\n");
+    assert_eq!(stderr, "");
+    let output = spidior(&dir, &["-r", "-v", "-q", "%s/foo/bar/g"]);
+    let (stdout, stderr) = printed(&output);
+    assert!(output.status.success());
+    assert!(!stdout.contains("Parsing file"), "{}", stdout);
+    assert!(stdout.contains("int bar = 1;"), "{}", stdout);
+    // The tree-sitter feature, if built, parses C rather than `clike`
+    let lang = spidior::languages::registry::Registry::builtin().detect(Path::new("a.c"), "int foo = 1;\n").name();
+    assert!(stderr.contains(&format!("a.c: {}, 0 functions, 1 identifier, 0 imports, 0 comments, parsed in ", lang)), "{}", stderr);
+    assert!(stderr.contains("notes.md: plain, 0 functions, 0 identifiers"), "{}", stderr);
+    assert!(stderr.contains("Parsing file a.c\n"), "{}", stderr);
+    // The imports subcommand names each file on stderr as well
+    let output = spidior(&dir, &["-p", "a.c", "-v", "imports"]);
+    assert!(printed(&output).1.contains("Parsing file a.c\n"));
+    assert!(!printed(&output).0.contains("Parsing file"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_linked_paths() {
+    let dir = scratch("linked");
+    std::fs::create_dir(dir.join("d")).unwrap();
+    std::fs::write(dir.join("d").join("a.c"), "int foo;\n").unwrap();
+    // Links that a walk may reach before the files they lead to
+    std::os::unix::fs::symlink(dir.join("d"), dir.join("e")).unwrap();
+    std::os::unix::fs::symlink(dir.join("d"), dir.join("0")).unwrap();
+    let output = spidior(&dir, &["-r", "--format", "vimgrep", "-q", "%s/foo//g"]);
+    assert_eq!(printed(&output).0, "./d/a.c:1:5:int foo;\n");
+    assert!(spidior(&dir, &["-r", "-i", "-q", "%s/foo/bar/g"]).status.success());
+    assert_eq!(std::fs::read_to_string(dir.join("d").join("a.c")).unwrap(), "int bar;\n");
+    // Walking only the link still finds the file, at the link
+    let output = spidior(&dir, &["-p", "e", "-r", "--format", "vimgrep", "-q", "%s/bar//g"]);
+    assert_eq!(printed(&output).0, "e/a.c:1:5:int bar;\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_generated_left_alone() {
+    let dir = scratch("generated");
+    let generated = "// @generated by protoc\nint foo(int a);\n";
+    std::fs::write(dir.join("gen.c"), generated).unwrap();
+    std::fs::write(dir.join("a.c"), "int foo(int a);\n").unwrap();
+    let output = spidior(&dir, &["-r", "-i", "structural", "foo(:[x])", "bar(:[x])"]);
+    assert!(output.status.success());
+    assert!(printed(&output).1.contains("Skipping ./gen.c, as it is marked `@generated`"), "{}", printed(&output).1);
+    assert_eq!(std::fs::read_to_string(dir.join("gen.c")).unwrap(), generated);
+    assert_eq!(std::fs::read_to_string(dir.join("a.c")).unwrap(), "int bar(int a);\n");
+    assert!(spidior(&dir, &["-r", "-i", "--include-generated", "structural", "foo(:[x])", "baz(:[x])"]).status.success());
+    assert_eq!(std::fs::read_to_string(dir.join("gen.c")).unwrap(), "// @generated by protoc\nint baz(int a);\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,81 @@
+//! JavaScript bindings for spidior, built for `wasm32-unknown-unknown` with
+//! wasm-bindgen, so that queries can be tried against pasted code in a
+//! browser. `www/index.html` is a playground built on them.
+//!
+//! Offsets given to JavaScript count UTF-16 code units, as JavaScript
+//! strings do, rather than the bytes spidior counts internally.
+
+use spidior::{SpidiorError, Spidior};
+use wasm_bindgen::prelude::*;
+
+/// Turns an error into a JavaScript `Error` holding its message
+fn to_js(e: SpidiorError) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+/// The number of UTF-16 code units before each of some byte offsets into
+/// `text`, counted in one pass over it, from the lowest offset to the
+/// highest, rather than from the start of `text` for each
+///
+/// # Returns
+///
+/// The offsets in UTF-16 code units, in the order `bytes` gives them
+fn utf16_offsets(text: &str, bytes: &[usize]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..bytes.len()).collect();
+    order.sort_by_key(|&i| bytes[i]);
+    let mut out = vec![0; bytes.len()];
+    let (mut at, mut units) = (0, 0);
+    for i in order {
+        units += text[at..bytes[i]].encode_utf16().count();
+        at = bytes[i];
+        out[i] = units;
+    }
+    out
+}
+
+/// Finds the text the find part of a query matches within a piece of code
+///
+/// # Arguments
+///
+/// * `code` - The code to search
+/// * `query` - The query, like `%s/[[type=Session]]/sess/g`
+/// * `lang` - The name of the language to parse `code` as, like `java`, or
+///   undefined to detect it from a `#!` line or doctype in `code`
+///
+/// # Returns
+///
+/// The start and end of each match, one after the other, in the order the
+/// matches appear, or throws an `Error` if the query or language is not
+/// valid
+#[wasm_bindgen]
+pub fn find(code: &str, query: &str, lang: Option<String>) -> Result<Vec<u32>, JsError> {
+    let found = Spidior::find_in(code, query, lang.as_deref()).map_err(to_js)?;
+    let bytes: Vec<usize> = found.iter().flat_map(|x| [x.start(), x.start() + x.len()]).collect();
+    Ok(utf16_offsets(code, &bytes).into_iter().map(|x| x as u32).collect())
+}
+
+/// Performs every replacement a query makes within a piece of code
+///
+/// # Arguments
+///
+/// * `code` - The code to change
+/// * `query` - The query, like `%s/[[type=Session]]/sess/g`
+/// * `lang` - The name of the language to parse `code` as, like `java`, or
+///   undefined to detect it from a `#!` line or doctype in `code`
+///
+/// # Returns
+///
+/// The changed code, or throws an `Error` if the query or language is not
+/// valid
+#[wasm_bindgen]
+pub fn replace(code: &str, query: &str, lang: Option<String>) -> Result<String, JsError> {
+    Spidior::replace_in(code, query, lang.as_deref()).map_err(to_js)
+}
+
+#[test]
+fn test_wasm() {
+    let code = "String é = \"\"; int count = 0; count++;";
+    assert_eq!(find(code, "%s/[[name=count]]/total/g", Some("java".into())).ok(), Some(vec![19, 24, 30, 35]));
+    assert_eq!(replace(code, "%s/[[name=count]]/total/g", Some("java".into())).ok().as_deref(), Some("String é = \"\"; int total = 0; total++;"));
+    assert_eq!(utf16_offsets("a😀é b", &[8, 1, 0, 5, 8]), vec![5, 1, 0, 3, 5]);
+}